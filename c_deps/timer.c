@@ -201,42 +201,66 @@ static int acquire_timer(void) {
   return tid;
 }
 
+/// Returns the slot a handle refers to, or -1 if the handle is out of range
+/// or its slot has since been freed or handed to another timer.
+static int timer_slot(int tid) {
+  int slot = tid & TIMER_SLOT_MASK;
+
+  if (tid < 0 || slot >= timer_data_num) return -1;
+  if (!timer_data[slot].type || timer_data[slot].id != tid) return -1;
+
+  return slot;
+}
+
 /// Starts a new timer that is deleted once it expires (single-use).
-/// Returns the timer's id.
+/// Returns the timer's handle.
 int timer_insert(unsigned int tick, unsigned int interval,
                  int (*func)(int, int), int data1, int data2) {
-  int tid;
+  int tid, serial;
 
   tid = acquire_timer();
+  // bump the slot's serial so handles from its previous timer go stale
+  serial = (timer_data[tid].id >> TIMER_SLOT_BITS) % TIMER_SERIAL_MAX + 1;
   timer_data[tid].tick = gettick() + tick;
   timer_data[tid].func = func;
-  timer_data[tid].id = tid;
+  timer_data[tid].id = (serial << TIMER_SLOT_BITS) | tid;
   timer_data[tid].data1 = data1;
   timer_data[tid].data2 = data2;
   timer_data[tid].type = TIMER_INTERVAL;
   timer_data[tid].interval = interval;
   push_timer_heap(tid);
   // printf("Added: %u\n",(int)func);
-  return tid;
+  return timer_data[tid].id;
 }
 
-/// Retrieves internal timer data
-const struct TimerData* get_timer(int tid) {
-  return (tid >= 0 && tid < timer_data_num) ? &timer_data[tid] : NULL;
+/// Retrieves internal timer data by slot (not by handle); the slot's current
+/// handle is in its 'id' field.
+const struct TimerData* get_timer(int slot) {
+  return (slot >= 0 && slot < timer_data_num) ? &timer_data[slot] : NULL;
 }
 
 /// Marks a timer specified by 'id' for immediate deletion once it expires.
 /// Param 'func' is used for debug/verification purposes.
 /// Returns 0 on success, < 0 on failure.
 int timer_remove(int tid) {
-  if (tid < 0 || tid >= timer_data_num) {
+  int slot;
+
+  if (tid < 0 || (tid & TIMER_SLOT_MASK) >= timer_data_num) {
     printf("timer_remove error: no such timer %d\n", tid);
     // ShowError("delete_timer error : no such timer %d\n", tid);
     return -1;
   }
 
-  timer_data[tid].func = NULL;
-  timer_data[tid].type = TIMER_ONCE_AUTODEL;
+  // a handle whose timer already expired is not an error, but its slot may
+  // now belong to someone else's timer, so leave it alone
+  slot = timer_slot(tid);
+  if (slot < 0) return -1;
+
+  timer_data[slot].func = NULL;
+  // keep TIMER_REMOVE_HEAP so a timer removed from inside its own callback
+  // is still released by timer_do instead of leaking its slot
+  timer_data[slot].type =
+      TIMER_ONCE_AUTODEL | (timer_data[slot].type & TIMER_REMOVE_HEAP);
 
   return 0;
 }
@@ -277,7 +301,7 @@ int timer_do(unsigned int tick) {
     }
     // printf("Diff: %d\n",diff);
     if (toDel) {
-      timer_remove(tid);
+      timer_remove(timer_data[tid].id);
     }
 
     // in the case the function didn't change anything...
@@ -10,6 +10,12 @@
 #define TIMER_INTERVAL 0x02
 #define TIMER_REMOVE_HEAP 0x10
 
+// timer handles are (serial << TIMER_SLOT_BITS) | slot; the serial changes
+// every time a slot is reused so a stale handle cannot reach the new timer
+#define TIMER_SLOT_BITS 20
+#define TIMER_SLOT_MASK ((1 << TIMER_SLOT_BITS) - 1)
+#define TIMER_SERIAL_MAX ((1 << (31 - TIMER_SLOT_BITS)) - 1)
+
 struct TimerData {
   unsigned int tick;
   int (*func)(int, int);
@@ -17,14 +23,17 @@ struct TimerData {
   unsigned int interval;
   int heap_pos;
 
-  // general-purpose storage
+  // handle of the timer currently in this slot (see TIMER_SLOT_BITS)
   int id;
+
+  // general-purpose storage
   int data1;
   int data2;
 };
 
 int timer_insert(unsigned int, unsigned int, int (*)(int, int), int, int);
 int timer_remove(int);
+const struct TimerData* get_timer(int slot);
 int timer_do(unsigned int tick);
 int getDay(void);
 int getHour(void);
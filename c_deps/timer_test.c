@@ -0,0 +1,94 @@
+// Standalone checks for the timer heap: build with cmake and run via ctest.
+
+#undef NDEBUG
+#include <assert.h>
+#include <stdio.h>
+
+#include "timer.h"
+
+static int fired;
+static int self_tid;
+
+static int count_cb(int data1, int data2) {
+  fired++;
+  return 0;
+}
+
+static int remove_self_cb(int data1, int data2) {
+  fired++;
+  assert(timer_remove(self_tid) == 0);
+  return 0;
+}
+
+static int remove_self_and_reinsert_cb(int data1, int data2) {
+  fired++;
+  assert(timer_remove(self_tid) == 0);
+  // the running slot is still held, so this must land in a different one
+  assert(((timer_insert(60000, 60000, count_cb, 0, 0) ^ self_tid) &
+          TIMER_SLOT_MASK) != 0);
+  return 0;
+}
+
+static int live_timers(void) {
+  int n = 0, slot;
+  const struct TimerData* t;
+
+  for (slot = 0; (t = get_timer(slot)) != NULL; slot++) {
+    if (t->type) n++;
+  }
+  return n;
+}
+
+// removing an interval timer from inside its own callback frees its slot
+static void test_remove_inside_callback(unsigned int now) {
+  fired = 0;
+  self_tid = timer_insert(0, 60000, remove_self_cb, 0, 0);
+  timer_do(now);
+  assert(fired == 1);
+  assert(live_timers() == 0);
+
+  timer_do(now + 120000);
+  assert(fired == 1);
+}
+
+// a handle kept after its timer was freed must not cancel the slot's new timer
+static void test_stale_handle_after_reuse(unsigned int now) {
+  int old_tid, new_tid;
+
+  fired = 0;
+  old_tid = timer_insert(0, 60000, count_cb, 0, 0);
+  assert(timer_remove(old_tid) == 0);
+  timer_do(now);
+  assert(fired == 0);
+
+  new_tid = timer_insert(0, 60000, count_cb, 0, 0);
+  assert((new_tid & TIMER_SLOT_MASK) == (old_tid & TIMER_SLOT_MASK));
+  assert(new_tid != old_tid);
+
+  assert(timer_remove(old_tid) == -1);
+  timer_do(gettick());
+  assert(fired == 1);
+
+  assert(timer_remove(new_tid) == 0);
+  timer_do(now + 120000);
+  assert(live_timers() == 0);
+}
+
+// a callback that replaces itself keeps only the replacement alive
+static void test_remove_inside_callback_then_insert(unsigned int now) {
+  fired = 0;
+  self_tid = timer_insert(0, 60000, remove_self_and_reinsert_cb, 0, 0);
+  timer_do(now);
+  assert(fired == 1);
+  assert(live_timers() == 1);
+}
+
+int main(void) {
+  test_remove_inside_callback(gettick());
+  test_stale_handle_after_reuse(gettick());
+  test_remove_inside_callback_then_insert(gettick());
+
+  printf("timer_test: ok\n");
+  timer_clear();
+  return 0;
+}
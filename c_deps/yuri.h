@@ -322,6 +322,10 @@ typedef struct MobDbData {
   unsigned short startm;
   unsigned short startx;
   unsigned short starty;
+  /**
+   * Flee below this percent of maxvita (0 = leave escape to Lua).
+   */
+  unsigned char flee_pct;
 } MobDbData;
 
 typedef struct RecipeData {
@@ -1030,6 +1034,23 @@ int rust_session_skip(int fd, uintptr_t len);
  */
 uintptr_t rust_session_available(int fd);
 
+/**
+ * Committed-but-unflushed bytes in the write buffer; 0 for an unknown fd.
+ * Check against `rust_session_write_high_water()` before a large write and
+ * back off while above it.
+ */
+uintptr_t rust_session_write_pressure(int fd);
+
+/**
+ * The session's `SessionKind` as its u8 value, or -1 if there is no such session.
+ */
+int rust_session_get_kind(int fd);
+
+/**
+ * The recommended high-water mark for `rust_session_write_pressure`.
+ */
+uintptr_t rust_session_write_high_water(void);
+
 /**
  * Commit write buffer (like WFIFOSET).
  * Returns 0 on success, -1 on error.
@@ -1105,6 +1126,21 @@ void rust_session_set_default_timeout(int (*callback)(int));
  */
 void rust_session_set_default_shutdown(int (*callback)(int));
 
+/**
+ * Set default closed callback, called after shutdown with the fd and its
+ * `DisconnectReason` code.
+ *
+ * # Safety
+ * Callback must be a valid C function pointer.
+ */
+void rust_session_set_default_closed(void (*callback)(int, int));
+
+/**
+ * Override the idle timeout for sessions accepted on listener `fd` (as
+ * returned by `rust_make_listen_port`); `secs` 0 disables it there.
+ */
+void rust_session_set_listen_idle_timeout(int fd, int secs);
+
 /**
  * Get session_data pointer (opaque void* for C).
  */
@@ -1125,6 +1161,17 @@ int rust_session_get_eof(int fd);
  */
 void rust_session_set_eof(int fd, int eof);
 
+/**
+ * Mark the session as logging out on the client's request (see
+ * `Session::quitting`).
+ */
+void rust_session_set_quitting(int fd);
+
+/**
+ * 1 if the client asked to log out on this session.
+ */
+int rust_session_get_quitting(int fd);
+
 /**
  * Get client IP address as u32 (network byte order, matches sin_addr.s_addr).
  */
@@ -1135,6 +1182,16 @@ uint32_t rust_session_get_client_ip(int fd);
  */
 uint8_t rust_session_get_increment(int fd);
 
+/**
+ * Run the session's cipher over the frame at `buff` (`outgoing` 1 = encrypt,
+ * 0 = decrypt). Returns 0 when the session has no cipher of its own, in
+ * which case the caller falls back to `tk_crypt_static`.
+ *
+ * # Safety
+ * `buff` must point at a whole frame whose length is in bytes 1..3.
+ */
+int rust_session_crypt(int fd, unsigned char *buff, int outgoing);
+
 /**
  * Increment packet counter and return new value.
  */
@@ -1247,4 +1304,11 @@ extern int timer_clear(void);
  */
 extern int timer_insert(uint32_t tick, uint32_t interval, int (*func)(int, int), int id, int data);
 
+/**
+ * Mark a timer for deletion; it is freed the next time it reaches the
+ * head of the heap (or after its callback returns, if it is running).
+ * Returns -1 for a stale handle whose slot has been freed or reused.
+ */
+extern int timer_remove(int tid);
+
 #endif  /* YURI_RS_H */
@@ -14,6 +14,7 @@
 #include "mmo.h"
 #include "net_crypt.h"
 #include "pc.h"
+#include "scripting.h"
 #include "session.h"
 #include "strlib.h"
 #include "timer.h"
@@ -100,6 +101,7 @@ int intif_mmo_tosd(int fd, struct mmo_charstatus* p) {
   // 05-28-18
 
   if (sd->status.gm_level) sd->optFlags = optFlag_walkthrough;  //.
+  sd->status.last_pos.m = rust_instance_save_map(sd->status.last_pos.m);
   if (!map_isloaded(sd->status.last_pos.m)) {
     sd->status.last_pos.m=0; sd->status.last_pos.x=8;
     sd->status.last_pos.y=7;
@@ -157,11 +159,52 @@ int intif_mmo_tosd(int fd, struct mmo_charstatus* p) {
   pc_calcstat(sd);
   pc_checklevel(sd);
   clif_mystaytus(sd);
+  rust_pc_send_motd(sd);
+  {
+    void *args[1] = {&sd->bl};
+    rust_sl_emit_blargs("playerLogin", 1, args);
+  }
   map_foreachinarea(clif_updatestate, sd->bl.m, sd->bl.x, sd->bl.y, AREA, BL_PC,
                     sd);
   clif_retrieveprofile(sd);
   return 0;
 }
+// Re-attach a player parked by rust_resume_park to a new connection and
+// resend the world state. The character never left the map, so unlike
+// intif_mmo_tosd there is no load, setpos or timer start here.
+int intif_resume_tosd(int fd, USER* sd) {
+  if (!sd) return 0;
+
+  sd->fd = fd;
+  rust_session_set_data(fd, sd);
+
+  clif_sendack(sd);
+  clif_sendtime(sd);
+  clif_sendid(sd);
+  clif_sendmapinfo(sd);
+  clif_sendstatus(sd, SFLAG_FULLSTATS | SFLAG_HPMP | SFLAG_XPMONEY);
+  clif_mystaytus(sd);
+  clif_spawn(sd);
+  clif_refresh(sd);
+  clif_sendxy(sd);
+  clif_getchararea(sd);
+
+  clif_mob_look_start(sd);
+  map_foreachinarea(clif_object_look_sub, sd->bl.m, sd->bl.x, sd->bl.y,
+                    SAMEAREA, BL_ALL, LOOK_GET, sd);
+  clif_mob_look_close(sd);
+
+  pc_loaditem(sd);
+  pc_loadequip(sd);
+
+  printf("[map] [intif_resume_tosd] resumed name=%.*s fd=%d\n",
+         (int)sizeof(sd->status.name), sd->status.name, fd);
+  fflush(stdout);
+
+  map_foreachinarea(clif_updatestate, sd->bl.m, sd->bl.x, sd->bl.y, AREA, BL_PC,
+                    sd);
+  return 0;
+}
 int authdb_init() {
   // auth_db=strdb_alloc(DB_OPT_BASE,32);
   return 0;
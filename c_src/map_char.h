@@ -15,6 +15,11 @@ void rust_intif_load(int fd, uint32_t char_id, const char* name);
 void rust_intif_quit(uint32_t char_id);
 void rust_intif_save(const uint8_t* data, uint32_t len);
 void rust_intif_savequit(const uint8_t* data, uint32_t len);
+uint16_t rust_instance_save_map(uint16_t m);
+void rust_charstatus_header(uint8_t* out);
+void rust_intif_disconnect(uint32_t char_id, int eof);
+uint32_t rust_resume_park(void* sd, uint32_t char_id, uint32_t client_ip, int eof);
+void* rust_resume_take(uint32_t char_id, uint32_t client_ip);
 
 // ---------------------------------------------------------------------------
 // auth_db helpers — still backed by SQL (Authorize table) in map_char.c
@@ -27,6 +32,7 @@ int auth_add(char*, unsigned int, unsigned int);
 int intif_init();
 int intif_timer(int, int);
 int intif_mmo_tosd(int, struct mmo_charstatus*);
+int intif_resume_tosd(int, USER*);
 int intif_parse(int);
 
 // ---------------------------------------------------------------------------
@@ -34,6 +40,21 @@ int intif_parse(int);
 // to Rust which sends over the char_server TCP connection.
 // ---------------------------------------------------------------------------
 
+// Bytes of the versioned header Rust puts before every mmo_charstatus blob.
+#define CHARSTATUS_HEADER_LEN 8
+
+// Compress header + sd->status into buf + 6; returns 0 and sets *clen on success.
+static inline int intif_pack_status(USER* sd, uint8_t* buf, uLongf* clen) {
+  size_t ulen = CHARSTATUS_HEADER_LEN + sizeof(struct mmo_charstatus);
+  uint8_t* ubuf = (uint8_t*)malloc(ulen);
+  if (!ubuf) return -1;
+  rust_charstatus_header(ubuf);
+  memcpy(ubuf + CHARSTATUS_HEADER_LEN, &sd->status, sizeof(struct mmo_charstatus));
+  int rc = compress2(buf + 6, clen, ubuf, ulen, 1) == Z_OK ? 0 : -1;
+  free(ubuf);
+  return rc;
+}
+
 static inline int intif_quit(USER* sd) {
   if (!sd) return -1;
   rust_intif_quit((uint32_t)sd->status.id);
@@ -52,15 +73,15 @@ static inline int intif_save(USER* sd) {
   sd->status.last_pos.m = sd->bl.m;
   sd->status.last_pos.x = sd->bl.x;
   sd->status.last_pos.y = sd->bl.y;
+  sd->status.last_pos.m = rust_instance_save_map(sd->status.last_pos.m);
   sd->status.disguise       = sd->disguise;
   sd->status.disguisecolor  = sd->disguise_color;
 
-  size_t ulen = sizeof(struct mmo_charstatus);
-  uLongf clen = compressBound(ulen);
+  uLongf clen = compressBound(CHARSTATUS_HEADER_LEN + sizeof(struct mmo_charstatus));
   uint8_t* buf = (uint8_t*)malloc(clen + 6);
   if (!buf) return -1;
 
-  if (compress2(buf + 6, &clen, (const uint8_t*)&sd->status, ulen, 1) != Z_OK) {
+  if (intif_pack_status(sd, buf, &clen) != 0) {
     free(buf);
     return -1;
   }
@@ -89,15 +110,15 @@ static inline int intif_savequit(USER* sd) {
     sd->status.last_pos.x = sd->bl.x;
     sd->status.last_pos.y = sd->bl.y;
   }
+  sd->status.last_pos.m = rust_instance_save_map(sd->status.last_pos.m);
   sd->status.disguise      = sd->disguise;
   sd->status.disguisecolor = sd->disguise_color;
 
-  size_t ulen = sizeof(struct mmo_charstatus);
-  uLongf clen = compressBound(ulen);
+  uLongf clen = compressBound(CHARSTATUS_HEADER_LEN + sizeof(struct mmo_charstatus));
   uint8_t* buf = (uint8_t*)malloc(clen + 6);
   if (!buf) return -1;
 
-  if (compress2(buf + 6, &clen, (const uint8_t*)&sd->status, ulen, 1) != Z_OK) {
+  if (intif_pack_status(sd, buf, &clen) != 0) {
     free(buf);
     return -1;
   }
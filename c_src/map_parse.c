@@ -297,6 +297,14 @@ int clif_accept2(int fd, char *name, int name_len) {
     SqlStmt_Free(stmt);
   }
 
+  // A player parked after a network drop resumes in place, skipping the load.
+  USER *parked = rust_resume_take(id, rust_session_get_client_ip(fd));
+  if (parked) {
+    auth_delete(n);
+    intif_resume_tosd(fd, parked);
+    return 0;
+  }
+
   // session[fd]->name removed — name field is write-only (all reads are commented out)
   intif_load(fd, id, n);
   auth_delete(n);
@@ -1035,6 +1043,7 @@ int clif_pc_damage(USER *sd, USER *src) {
   nullpo_ret(0, src);
 
   if (src->status.state == 1) return 0;
+  if (!rust_can_target(&sd->bl, &src->bl)) return 0;
 
   sl_doscript_blargs("hitCritChance", NULL, 2, &sd->bl, &src->bl);
 
@@ -2011,6 +2020,7 @@ int clif_mob_damage(USER *sd, MOB *mob) {
   nullpo_ret(0, mob);
 
   if (mob->state == MOB_DEAD) return 0;
+  if (!rust_can_target(&sd->bl, &mob->bl)) return 0;
 
   sl_doscript_blargs("hitCritChance", NULL, 2, &sd->bl, &mob->bl);
 
@@ -2402,6 +2412,7 @@ int clif_send_mob_healthscript(MOB *mob, int damage, int critical) {
 
     sl_doscript_blargs(mob->data->yname, "after_death", 2, &mob->bl, bl);
     sl_doscript_blargs("after_death", NULL, 2, &mob->bl, &sd->bl);
+    rust_sl_emit_mob_kill(mob, bl);
   }
 
   return 0;
@@ -4797,6 +4808,13 @@ int clif_parsewalk(USER *sd) {
     return 0;
   }
 
+  if (!rust_pc_walk_allowed(sd)) {
+    clif_blockmovement(sd, 0);
+    clif_sendxy(sd);
+    clif_blockmovement(sd, 1);
+    return 0;
+  }
+
   sd->canmove = 0;
 
   switch (direction) {
@@ -11248,6 +11266,10 @@ int clif_stoptimers(USER *sd) {
   return 0;
 }
 int clif_handle_disconnect(USER *sd) {
+  return clif_handle_disconnect_eof(sd, rust_session_get_eof(sd->fd));
+}
+// The logout itself; eof is the session's close code, reported to char_server.
+int clif_handle_disconnect_eof(USER *sd, int eof) {
   USER *tsd = NULL;
   if (sd->exchange.target) {
     tsd = map_id2sd(sd->exchange.target);
@@ -11266,6 +11288,7 @@ int clif_handle_disconnect(USER *sd) {
   clif_stoptimers(sd);
 
   sl_doscript_blargs("logout", NULL, 1, &sd->bl);
+  rust_intif_disconnect(sd->status.id, eof);
   intif_savequit(sd);
   clif_quit(sd);
   map_deliddb(&sd->bl);
@@ -11279,6 +11302,15 @@ int clif_handle_disconnect(USER *sd) {
   printf("[map] [handle_disconnect] name=%s\n", sd->status.name);
   return 0;
 }
+// Resume window passed for a parked player: run the normal logout with the
+// eof of the drop that parked it (sd->fd is 0 by now).
+int clif_resume_expire(void *p, int eof) {
+  USER *sd = (USER *)p;
+  if (!sd) return 0;
+  printf("[map] [resume_expire] name=%s eof=%d\n", sd->status.name, eof);
+  clif_handle_disconnect_eof(sd, eof);
+  return 0;
+}
 int clif_handle_missingobject(USER *sd) {
   struct block_list *bl = NULL;
   bl = map_id2bl(SWAP32(RFIFOL(sd->fd, 5)));
@@ -11596,7 +11628,16 @@ int clif_parse(int fd) {
 
   // for(pnum=0;pnum<3 && rust_session_exists(fd) && session[fd]->rdata_size;pnum++) {
   if (rust_session_get_eof(fd)) {
-    if (sd) {
+    // A client that sent 0x0B is logging out, whatever its close looks like.
+    if (sd && !rust_session_get_quitting(fd) &&
+        rust_resume_park(sd, sd->status.id, rust_session_get_client_ip(fd),
+                         rust_session_get_eof(fd))) {
+      // Parked: stays in the world, detached from the dead fd, until it is
+      // resumed by clif_accept2 or expired via clif_resume_expire.
+      printf("[map] [session_eof] name=%s parked for resume\n", sd->status.name);
+      sd->fd = 0;
+      rust_session_set_data(fd, NULL);
+    } else if (sd) {
       printf("[map] [session_eof] name=%s\n", sd->status.name);
       clif_handle_disconnect(sd);
       clif_closeit(sd);
@@ -11721,6 +11762,7 @@ printf("\n");*/
       break;
     case 0x0B:
       clif_cancelafk(sd);
+      rust_session_set_quitting(fd);
       clif_closeit(sd);
       break;
     case 0x0C:  // < missing object/char/monster
@@ -12064,6 +12106,8 @@ printf("\n");*/
       break;
 
     default:
+      if (rust_sl_packet_hook(sd, (const unsigned char *)RFIFOP(fd, 0), len))
+        break;
       printf("[Map] Unknown Packet ID: %02X\nPacket content:\n",
              RFIFOB(sd->fd, 3));
       clif_debug(RFIFOP(sd->fd, 0), SWAP16(RFIFOW(sd->fd, 1)));
@@ -12143,7 +12187,7 @@ int send_metafile(USER *sd, char *file) {
   // printf("%s\n",file);
   WFIFOW(sd->fd, 1) = SWAP16(len + 3);
   set_packet_indexes((unsigned char *)WFIFOP(sd->fd, 0));
-  tk_crypt_static((unsigned char *)WFIFOP(sd->fd, 0));
+  tk_crypt_session(sd->fd, (unsigned char *)WFIFOP(sd->fd, 0), 1);
   WFIFOSET(sd->fd, len + 6 + 3);
 
   free(cbuf);
@@ -12185,7 +12229,7 @@ int send_metalist(USER *sd) {
 
   WFIFOW(sd->fd, 1) = SWAP16(len + 4);
   set_packet_indexes((unsigned char *)WFIFOP(sd->fd, 0));
-  tk_crypt_static((unsigned char *)WFIFOP(sd->fd, 0));
+  tk_crypt_session(sd->fd, (unsigned char *)WFIFOP(sd->fd, 0), 1);
   WFIFOSET(sd->fd, len + 7 + 3);
 
   return 0;
@@ -174,6 +174,8 @@ int clif_speak(struct block_list *, va_list);
 int clif_parsebuy(USER *);
 int clif_playsound(struct block_list *, int);
 int clif_handle_disconnect(USER *);
+int clif_handle_disconnect_eof(USER *, int);
+int clif_resume_expire(void *, int);
 int clif_handitem(USER *);
 int clif_exchange_money(USER *, USER *);
 int clif_exchange_additem(USER *, USER *, int, int);
@@ -81,7 +81,6 @@ int auth_n = 0;
 struct userlist_data userlist;
 // map and map_n are defined in src/ffi/map_db.rs (libyuri.a) as Rust statics.
 // extern declarations are in map_server.h.
-struct game_data gamereg;
 int oldHour;
 int oldMinute;
 int cronjobtimer;
@@ -813,11 +812,14 @@ int map_loadregistry(int id) {
   return rust_map_loadregistry(id);
 }
 int map_lastdeath_mob(MOB* p) {
+  char table[65];
+  if (rust_spawn_table_for(p->id, table, sizeof(table)) != 0)
+    snprintf(table, sizeof(table), "Spawns%i", serverid);
   if (SQL_ERROR ==
       Sql_Query(sql_handle,
-                "UPDATE `Spawns%i` SET `SpnLastDeath`='%u' WHERE `SpnX`='%u' "
+                "UPDATE `%s` SET `SpnLastDeath`='%u' WHERE `SpnX`='%u' "
                 "AND `SpnY`='%u' AND `SpnMapId`='%u' AND `SpnId`='%u'",
-                serverid, p->last_death, p->startx, p->starty, p->bl.m,
+                table, p->last_death, p->startx, p->starty, p->bl.m,
                 p->id)) {
     Sql_ShowDebug(sql_handle);
   }
@@ -1839,12 +1841,15 @@ int nmail_sendmail(USER* sd, const char* to_user, const char* topic,
 
 int map_addmob(USER* sd, unsigned int id, int start, int end,
                unsigned int replace) {
+  char table[65];
+  if (rust_spawn_primary_table(table, sizeof(table)) != 0)
+    snprintf(table, sizeof(table), "Spawns%d", serverid);
   if (SQL_ERROR ==
       Sql_Query(sql_handle,
-                "INSERT INTO `Spawns%d` (`SpnMapId`, `SpnX`, `SpnY`, "
+                "INSERT INTO `%s` (`SpnMapId`, `SpnX`, `SpnY`, "
                 "`SpnMobId`, `SpnLastDeath`, `SpnStartTime`, `SpnEndTime`, "
                 "`SpnMobIdReplace`) VALUES(%d, %d, %d, %d, %d, %d, %d, %d)",
-                serverid, sd->bl.m, sd->bl.x, sd->bl.y, id, 0, start, end,
+                table, sd->bl.m, sd->bl.x, sd->bl.y, id, 0, start, end,
                 replace))
     Sql_ShowDebug(sql_handle);
   return 0;
@@ -2146,181 +2151,22 @@ int map_readglobalreg(int m, const char* reg) {
   return 0;
 }
 // Game registries
-// loads game registries
+// Backed by the Rust server-state store (servers/map/server_state.rs), which
+// is shared with GAMEREG in Lua and persisted on the autosave tick.
 int map_loadgameregistry() {
-  SqlStmt* stmt;
-  struct global_reg reg;
-  int i;
-
-  gamereg.registry_num = 0;
-  CALLOC(gamereg.registry, struct global_reg, MAX_GAMEREG);
-  memset(&reg, 0, sizeof(reg));
-
-  stmt = SqlStmt_Malloc(sql_handle);
-  if (stmt == NULL) {
-    SqlStmt_ShowDebug(stmt);
-    return 0;
-  }
-
-  if (SQL_ERROR == SqlStmt_Prepare(stmt,
-                                   "SELECT `GrgIdentifier`, `GrgValue` FROM "
-                                   "`GameRegistry%d` LIMIT %d",
-                                   serverid, MAX_GAMEREG) ||
-      SQL_ERROR == SqlStmt_Execute(stmt) ||
-      SQL_ERROR == SqlStmt_BindColumn(stmt, 0, SQLDT_STRING, &reg.str,
-                                      sizeof(reg.str), NULL, NULL) ||
-      SQL_ERROR ==
-          SqlStmt_BindColumn(stmt, 1, SQLDT_INT, &reg.val, 0, NULL, NULL)) {
-    SqlStmt_ShowDebug(stmt);
-    SqlStmt_Free(stmt);
-    return 0;
-  }
-
-  gamereg.registry_num = SqlStmt_NumRows(stmt);
-
-  for (i = 0; i < gamereg.registry_num && SQL_SUCCESS == SqlStmt_NextRow(stmt);
-       i++) {
-    memcpy(&gamereg.registry[i], &reg, sizeof(reg));
-  }
-
-  printf("[map] [load_game_registry] count=%d\n", gamereg.registry_num);
-  SqlStmt_Free(stmt);
-  return 0;
-}
-// saves gameregistries
-int map_savegameregistry(int i) {
-  struct global_reg* p = &gamereg.registry[i];
-  unsigned int save_id = 0;
-  SqlStmt* stmt;
-  unsigned int gameregid;
-
-  stmt = SqlStmt_Malloc(sql_handle);
-  if (stmt == NULL) {
-    SqlStmt_ShowDebug(stmt);
-    return 0;
-  }
-
-  if (SQL_ERROR == SqlStmt_Prepare(stmt,
-                                   "SELECT `GrgId` FROM `GameRegistry%d` WHERE "
-                                   "`GrgIdentifier` = '%s'",
-                                   serverid, p->str) ||
-      SQL_ERROR == SqlStmt_Execute(stmt) ||
-      SQL_ERROR ==
-          SqlStmt_BindColumn(stmt, 0, SQLDT_UINT, &gameregid, 0, NULL, NULL)) {
-    SqlStmt_ShowDebug(stmt);
-    SqlStmt_Free(stmt);
-    return 0;
-  }
-
-  if (SQL_SUCCESS == SqlStmt_NextRow(stmt)) {
-    save_id = gameregid;
-  }
-
-  SqlStmt_Free(stmt);
-
-  if (save_id) {
-    if (p->val == 0) {
-      if (SQL_ERROR ==
-          Sql_Query(sql_handle,
-                    "DELETE FROM `GameRegistry%d` WHERE `GrgIdentifier` = '%s'",
-                    serverid, p->str)) {
-        Sql_ShowDebug(sql_handle);
-        return 0;
-      }
-    } else {
-      if (SQL_ERROR ==
-          Sql_Query(sql_handle,
-                    "UPDATE `GameRegistry%d` SET `GrgIdentifier` = '%s', "
-                    "`GrgValue` = '%d' WHERE `GrgId` = '%d'",
-                    serverid, p->str, p->val, save_id)) {
-        Sql_ShowDebug(sql_handle);
-        return 0;
-      }
-    }
-  } else {
-    if (p->val > 0) {
-      if (SQL_ERROR ==
-          Sql_Query(sql_handle,
-                    "INSERT INTO `GameRegistry%d` (`GrgIdentifier`, "
-                    "`GrgValue`) VALUES ('%s', '%d')",
-                    serverid, p->str, p->val)) {
-        Sql_ShowDebug(sql_handle);
-        return 0;
-      }
-    }
-  }
-
+  int n = rust_server_state_load();
+  printf("[map] [load_game_registry] count=%d\n", n);
   return 0;
 }
 // sets game registry
 int map_setglobalgamereg(const char* reg, int val) {
-  int i, exist;
-
-  exist = -1;
   nullpo_ret(0, reg);
-
-  // if registry exists, get number
-  for (i = 0; i < gamereg.registry_num; i++) {
-    if (!strcasecmp(gamereg.registry[i].str, reg)) {
-      exist = i;
-      break;
-    }
-  }
-  // if registry exists, set value
-  if (exist != -1) {
-    if (val == 0) {
-      gamereg.registry[exist].val = val;
-      map_savegameregistry(exist);
-      strcpy(gamereg.registry[exist].str, "");  // empty registry
-      return 0;
-    } else {
-      gamereg.registry[exist].val = val;
-      map_savegameregistry(exist);
-      return 0;
-    }
-  } else {
-    for (i = 0; i < gamereg.registry_num; i++) {
-      if (!strcasecmp(gamereg.registry[i].str, "")) {
-        strcpy(gamereg.registry[i].str, reg);
-        gamereg.registry[i].val = val;
-        map_savegameregistry(i);
-        return 0;
-      }
-    }
-
-    if (gamereg.registry_num < MAX_GLOBALREG) {
-      gamereg.registry_num = gamereg.registry_num + 1;
-      i = gamereg.registry_num - 1;
-      strcpy(gamereg.registry[i].str, reg);
-      gamereg.registry[i].val = val;
-      map_savegameregistry(i);
-      return 0;
-    }
-  }
-
-  return 0;
+  return rust_server_state_set(reg, val);
 }
 // reads game registry
 int map_readglobalgamereg(const char* reg) {
-  int i, exist;
-
-  exist = -1;
   nullpo_ret(0, reg);
-
-  for (i = 0; i < gamereg.registry_num; i++) {
-    if (!strcasecmp(gamereg.registry[i].str, reg)) {
-      exist = i;
-      break;
-    }
-  }
-
-  if (exist != -1) {
-    return gamereg.registry[exist].val;
-  } else {
-    return 0;
-  }
-
-  return 0;
+  return rust_server_state_get(reg);
 }
 
 int map_loadclanbank(int id) {
@@ -171,6 +171,8 @@ struct mobdb_data {
   short protection, miss;
   unsigned short sex, face, face_color, hair, hair_color, armor_color,
       skin_color, startm, startx, starty;
+  unsigned char flee_pct;
+  unsigned char faction;
 };
 
 struct mobspawn_data {
@@ -492,9 +494,11 @@ int map_readglobalreg(int, const char *);
 int map_registrysave(int, int);
 int map_registrydelete(int, int);
 int map_loadgameregistry();
-int map_savegameregistry(int);
 int map_setglobalgamereg(const char *, int);
 int map_readglobalgamereg(const char *);
+int rust_server_state_load(void);
+int rust_server_state_get(const char *);
+int rust_server_state_set(const char *, int);
 int map_loadclanbank(int);
 
 int map_weather(int, int);
@@ -34,6 +34,9 @@ static inline void mobdb_term(void)                                 { rust_mobdb
 
 // ─── mob game logic — implemented in Rust (src/game/mob.rs) ──────────────────
 int rust_mobspawn_read(void);
+/* Spawn table a spawn was loaded from / new spawns go to; -1 before mobspawn_read. */
+int rust_spawn_table_for(unsigned int spn_id, char *buf, int len);
+int rust_spawn_primary_table(char *buf, int len);
 int rust_mob_timer_spawns(int, int);
 int rust_mob_respawn_getstats(MOB*);
 int rust_mob_warp(MOB*, int, int, int);
@@ -69,6 +72,7 @@ int rust_mob_find_target(struct block_list*, ...);
 int rust_mob_attack(MOB*, int);
 int rust_mob_calc_critical(MOB*, USER*);
 int rust_mob_move(struct block_list*, ...);
+int rust_can_target(struct block_list* attacker, struct block_list* target);
 
 static inline int mob_addtocurrent(struct block_list* bl, ...) {
   fprintf(stderr, "[mob] mob_addtocurrent called from C wrapper (bl=%p); "
@@ -10,7 +10,8 @@
 /* encrypt / decrypt remain in C: they operate on FIFO buffers and USER->EncHash,
    both of which are tied to the C session and USER structs.
    All crypto primitives (set_packet_indexes, generate_key2, tk_crypt_dynamic,
-   tk_crypt_static) now live in Rust (src/network/crypt.rs). */
+   tk_crypt_static) now live in Rust (src/network/crypt.rs). The static
+   branch goes through the session's cipher when its listener has one. */
 
 int encrypt(int fd) {
   USER *sd = rust_session_get_data(fd);
@@ -35,7 +36,7 @@ int encrypt(int fd) {
     generate_key2(buf, sd->EncHash, key, 0);
     tk_crypt_dynamic(buf, key);
   } else {
-    tk_crypt_static(buf);
+    tk_crypt_session(fd, buf, 1);
   }
   int pkt_len = (int)SWAP16(*(unsigned short *)(buf + 1)) + 3;
   return pkt_len;
@@ -51,7 +52,7 @@ int decrypt(int fd) {
     generate_key2((unsigned char *)RFIFOP(fd, 0), sd->EncHash, key, 1);
     tk_crypt_dynamic((unsigned char *)RFIFOP(fd, 0), key);
   } else {
-    tk_crypt_static((unsigned char *)RFIFOP(fd, 0));
+    tk_crypt_session(fd, (unsigned char *)RFIFOP(fd, 0), 0);
   }
   return 0;
 }
@@ -17,6 +17,7 @@ int    rust_crypt_set_packet_indexes(unsigned char *packet);
 char  *rust_crypt_generate_key2(unsigned char *packet, const char *table, char *keyout, int fromclient);
 void   rust_crypt_dynamic(unsigned char *buff, const char *key);
 void   rust_crypt_static(unsigned char *buff, const char *xor_key);
+int    rust_session_crypt(int fd, unsigned char *buff, int outgoing);
 
 /* xor_key is defined in config.c */
 extern char xor_key[10];
@@ -44,6 +45,11 @@ static inline void tk_crypt_dynamic(unsigned char *buff, const char *key) {
 static inline void tk_crypt_static(unsigned char *buff) {
     rust_crypt_static(buff, xor_key);
 }
+/* Static branch for a client session: the cipher of the listener that
+   accepted fd (map_cipher), else the plain xor_key scheme. */
+static inline void tk_crypt_session(int fd, unsigned char *buff, int outgoing) {
+    if (!rust_session_crypt(fd, buff, outgoing)) tk_crypt_static(buff);
+}
 
 /* ── Active C functions (still in net_crypt.c) ───────────────────────────── */
 int encrypt(int fd);
@@ -152,6 +152,14 @@ int rust_pc_getitemsaround(USER *sd);
 int rust_pc_handle_item(int a, int b);
 int rust_pc_handle_item_sub(struct block_list *bl, va_list ap);
 int rust_pc_runfloor_sub(USER *sd);
+/* Shop transactions: 0 on success, negative ShopError code otherwise. */
+int rust_pc_buy_item(USER *sd, unsigned int item_id, int amount);
+int rust_pc_sell_item(USER *sd, int slot, int amount);
+int rust_pc_bank_deposit_item(USER *sd, int inv_slot, int bank_slot, int amount);
+int rust_pc_bank_withdraw_item(USER *sd, int bank_slot, int amount);
+int rust_pc_bank_gold(USER *sd, int amount);
+int rust_pc_walk_allowed(USER *sd);
+int rust_pc_craft(USER *sd, unsigned int recipe_id);
 
 static inline int pc_additem(USER *sd, struct item *fl)        { return rust_pc_additem(sd, fl); }
 static inline int pc_additemnolog(USER *sd, struct item *fl)   { return rust_pc_additemnolog(sd, fl); }
@@ -164,6 +172,12 @@ static inline int pc_getitemsaround(USER *sd)                  { return rust_pc_
 static inline int pc_handle_item(int a, int b)                 { return rust_pc_handle_item(a, b); }
 static inline int pc_handle_item_sub(struct block_list *bl, va_list ap) { return rust_pc_handle_item_sub(bl, ap); }
 static inline int pc_runfloor_sub(USER *sd)                    { return rust_pc_runfloor_sub(sd); }
+static inline int pc_buy_item(USER *sd, unsigned int id, int n) { return rust_pc_buy_item(sd, id, n); }
+static inline int pc_sell_item(USER *sd, int slot, int n)       { return rust_pc_sell_item(sd, slot, n); }
+static inline int pc_bank_deposit_item(USER *sd, int inv, int bank, int n) { return rust_pc_bank_deposit_item(sd, inv, bank, n); }
+static inline int pc_bank_withdraw_item(USER *sd, int bank, int n)         { return rust_pc_bank_withdraw_item(sd, bank, n); }
+static inline int pc_bank_gold(USER *sd, int n)                            { return rust_pc_bank_gold(sd, n); }
+static inline int pc_craft(USER *sd, unsigned int recipe)                  { return rust_pc_craft(sd, recipe); }
 
 /* ── load item/equip display ───────────────────────────────────────────────── */
 int rust_pc_loaditem(USER *sd);
@@ -211,6 +225,9 @@ static inline int pc_loadmagic(USER *sd)     { return rust_pc_loadmagic(sd); }
 static inline int pc_magic_startup(USER *sd) { return rust_pc_magic_startup(sd); }
 static inline int pc_reload_aether(USER *sd) { return rust_pc_reload_aether(sd); }
 
+/* ── login ───────────────────────────────────────────────────────────────── */
+int rust_pc_send_motd(USER *sd);
+
 /* ── death / resurrection / combat state ──────────────────────────────────── */
 int rust_pc_die(USER *sd);
 int rust_pc_diescript(USER *sd);
@@ -18,6 +18,9 @@ extern int   rust_sl_doscript_blargs_vec(const char *root, const char *method,
 extern int   rust_sl_doscript_strings_vec(const char *root, const char *method,
                                            int nargs, const char **args);
 extern int   rust_sl_doscript_stackargs(const char *root, const char *method, int nargs);
+extern int   rust_sl_emit_blargs(const char *event, int nargs, void **args);
+extern int   rust_sl_emit_mob_kill(void *mob, void *killer);
+extern int   rust_sl_packet_hook(void *sd, const unsigned char *frame, int len);
 extern int   rust_sl_updatepeople(struct block_list *bl, void *ap);
 extern void  rust_sl_resumemenu(unsigned int id, void *sd);
 extern void  rust_sl_resumemenuseq(unsigned int id, int choice, void *sd);
@@ -79,8 +79,20 @@ static inline void set_defaultparse(int (*cb)(int))    { rust_session_set_defaul
 static inline void set_defaultaccept(int (*cb)(int))   { rust_session_set_default_accept(cb); }
 static inline void set_defaulttimeout(int (*cb)(int))  { rust_session_set_default_timeout(cb); }
 static inline void set_defaultshutdown(int (*cb)(int)) { rust_session_set_default_shutdown(cb); }
+static inline void set_defaultclosed(void (*cb)(int, int)) { rust_session_set_default_closed(cb); }
+
+// Reasons passed to the closed callback (Rust DisconnectReason / eof codes).
+enum {
+  CLOSE_UNKNOWN = 0,
+  CLOSE_SERVER_KICK = 1,
+  CLOSE_WRITE_ERROR = 2,
+  CLOSE_READ_ERROR = 3,
+  CLOSE_PEER_CLOSED = 4,
+  CLOSE_SLOW_CONSUMER = 5,
+};
 
 static inline int make_listen_port(int port) { return rust_make_listen_port(port); }
+static inline void set_listen_idle_timeout(int fd, int secs) { rust_session_set_listen_idle_timeout(fd, secs); }
 static inline int make_connection(long ip, int port) { return rust_make_connection((uint32_t)ip, port); }
 
 static inline int session_eof(int fd) {
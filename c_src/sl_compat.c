@@ -311,12 +311,15 @@ void sl_g_sendmeta(void) {
 
 /* --- addMob (SQL) --- */
 int sl_g_addmob(int m, int x, int y, int mobid) {
+    char table[65];
     if (!map_isloaded(m)) return 0;
+    if (rust_spawn_primary_table(table, sizeof(table)) != 0)
+        snprintf(table, sizeof(table), "Spawns%d", serverid);
     if (SQL_ERROR == Sql_Query(sql_handle,
-        "INSERT INTO `Spawns%d` (`SpnMapId`,`SpnX`,`SpnY`,`SpnMobId`,"
+        "INSERT INTO `%s` (`SpnMapId`,`SpnX`,`SpnY`,`SpnMobId`,"
         "`SpnLastDeath`,`SpnStartTime`,`SpnEndTime`,`SpnMobIdReplace`) "
         "VALUES(%d,%d,%d,%d,0,25,25,0)",
-        serverid, m, x, y, mobid)) {
+        table, m, x, y, mobid)) {
         Sql_ShowDebug(sql_handle); return 0;
     }
     return 1;
@@ -1351,6 +1354,43 @@ void sl_pc_deductdurainv(void *sd, int slot, int v) {
     USER *user = (USER*)sd;
     if (slot >= 0 && slot < MAX_INVENTORY) user->status.inventory[slot].dura -= v;
 }
+// Durability getters return -1 for an empty or out-of-range slot; *max gets
+// the item's full durability from the item db.
+int  sl_pc_getdura(void *sd, int slot, int *max) {
+    USER *user = (USER*)sd;
+    if (slot < 0 || slot >= MAX_INVENTORY || !user->status.inventory[slot].id) return -1;
+    *max = itemdb_dura(user->status.inventory[slot].id);
+    return user->status.inventory[slot].dura;
+}
+int  sl_pc_getequipdura(void *sd, int eq, int *max) {
+    USER *user = (USER*)sd;
+    if (eq < 0 || eq >= MAX_EQUIP || !user->status.equip[eq].id) return -1;
+    *max = itemdb_dura(user->status.equip[eq].id);
+    return user->status.equip[eq].dura;
+}
+// Setters clamp to 0..max, resend the slot, and return the stored value (-1
+// if the slot is empty).
+static int sl_clampdura(struct item *it, int value) {
+    int max = itemdb_dura(it->id);
+    if (value > max) value = max;
+    if (value < 0) value = 0;
+    it->dura = value;
+    return value;
+}
+int  sl_pc_setdura(void *sd, int slot, int value) {
+    USER *user = (USER*)sd;
+    if (slot < 0 || slot >= MAX_INVENTORY || !user->status.inventory[slot].id) return -1;
+    value = sl_clampdura(&user->status.inventory[slot], value);
+    clif_sendadditem(user, slot);
+    return value;
+}
+int  sl_pc_setequipdura(void *sd, int eq, int value) {
+    USER *user = (USER*)sd;
+    if (eq < 0 || eq >= MAX_EQUIP || !user->status.equip[eq].id) return -1;
+    value = sl_clampdura(&user->status.equip[eq], value);
+    clif_sendequip(user, eq);
+    return value;
+}
 int  sl_pc_hasequipped(void *sd, unsigned int item_id) {
     USER *user = (USER*)sd;
     for (int i = 0; i < MAX_EQUIP; i++)
@@ -0,0 +1,9 @@
+-- Track where and when each character last logged in.
+--
+-- ChaLastLoginIp is the IPv4 address in network byte order packed into a u32
+-- (a.b.c.d -> a<<24 | b<<16 | c<<8 | d); 0 means "never recorded".
+-- ChaLastLoginTime is a Unix timestamp in seconds; 0 means "never recorded".
+
+ALTER TABLE `Character`
+  ADD COLUMN `ChaLastLoginIp`   INT(10) UNSIGNED NOT NULL DEFAULT '0',
+  ADD COLUMN `ChaLastLoginTime` INT(10) UNSIGNED NOT NULL DEFAULT '0';
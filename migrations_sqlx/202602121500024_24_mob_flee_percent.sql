@@ -0,0 +1,7 @@
+-- Native low-health flee threshold for mob AI.
+--
+-- A mob switches to MOB_ESCAPE once its vita falls below MobFleePercent
+-- percent of its max. 0 leaves escape behaviour to the Lua AI scripts.
+
+ALTER TABLE `Mobs`
+  ADD COLUMN `MobFleePercent` int(10) unsigned NOT NULL DEFAULT '0';
@@ -0,0 +1,12 @@
+-- World-shared state that survives restarts (GAMEREG, world-event timers,
+-- global counters). One versioned JSON document per map server, written on
+-- the autosave tick and at shutdown. Replaces the per-key GameRegistry<n>
+-- tables, which are imported on the first boot and then left untouched.
+
+CREATE TABLE IF NOT EXISTS `ServerState` (
+  `SstServerId` int(10) NOT NULL,
+  `SstVersion` int(10) unsigned NOT NULL DEFAULT '1',
+  `SstData` mediumtext NOT NULL,
+  `SstUpdated` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  PRIMARY KEY (`SstServerId`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE utf8mb4_unicode_ci;
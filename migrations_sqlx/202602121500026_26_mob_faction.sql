@@ -0,0 +1,8 @@
+-- Faction for native combat targeting.
+--
+-- A mob may target a player when the faction relation between MobFaction and
+-- the player's country allows it (see faction_relations in server.yaml).
+-- 255 is the wild faction, hostile to everyone not configured otherwise.
+
+ALTER TABLE `Mobs`
+  ADD COLUMN `MobFaction` int(10) unsigned NOT NULL DEFAULT '255';
@@ -0,0 +1,11 @@
+-- World-scoped script key-value store (Lua `kv`). One versioned JSON
+-- document per map server holding each key's value and absolute expiry,
+-- written on the autosave tick and at shutdown.
+
+CREATE TABLE IF NOT EXISTS `ScriptKv` (
+  `SkvServerId` int(10) NOT NULL,
+  `SkvVersion` int(10) unsigned NOT NULL DEFAULT '1',
+  `SkvData` mediumtext NOT NULL,
+  `SkvUpdated` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  PRIMARY KEY (`SkvServerId`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE utf8mb4_unicode_ci;
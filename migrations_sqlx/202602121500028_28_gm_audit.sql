@@ -0,0 +1,17 @@
+-- Audit trail for GM level changes made through the scripting API
+-- (`setGmLevel`). One row per change; ChaGMLevel itself is updated in the
+-- same transaction. GmaActorId 0 = a script acting without a player.
+
+CREATE TABLE IF NOT EXISTS `GmAudit` (
+  `GmaId` int(10) unsigned NOT NULL AUTO_INCREMENT,
+  `GmaActorId` int(10) unsigned NOT NULL DEFAULT '0',
+  `GmaActorName` varchar(16) NOT NULL DEFAULT '',
+  `GmaTargetId` int(10) unsigned NOT NULL,
+  `GmaTargetName` varchar(16) NOT NULL DEFAULT '',
+  `GmaOldLevel` int(10) unsigned NOT NULL,
+  `GmaNewLevel` int(10) unsigned NOT NULL,
+  `GmaReason` varchar(255) NOT NULL,
+  `GmaTime` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`GmaId`),
+  KEY `GmaTargetId` (`GmaTargetId`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE utf8mb4_unicode_ci;
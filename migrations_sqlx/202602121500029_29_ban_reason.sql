@@ -0,0 +1,8 @@
+-- Why an IP was banned and until when, shown to the client on connect.
+--
+-- BndExpires is a Unix timestamp in seconds; NULL means the ban is
+-- permanent. A ban whose expiry has passed is ignored by the login server.
+
+ALTER TABLE `BannedIP`
+  ADD COLUMN `BndReason`  varchar(255) NOT NULL DEFAULT '',
+  ADD COLUMN `BndExpires` BIGINT NULL DEFAULT NULL;
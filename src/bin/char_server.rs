@@ -1,6 +1,5 @@
 use std::sync::Arc;
 use anyhow::{Context, Result};
-use sqlx::mysql::MySqlPoolOptions;
 use yuri::config::ServerConfig;
 use yuri::servers::char::CharState;
 use yuri::servers::char::db;
@@ -42,24 +41,30 @@ async fn main() -> Result<()> {
             .with_context(|| format!("Cannot parse config: {}", conf_file))?
     };
 
-    let pool = {
-        let db_url = format!(
-            "mysql://{}:{}@{}:{}/{}",
-            config.sql_id, config.sql_pw, config.sql_ip, config.sql_port, config.sql_db
-        );
-        MySqlPoolOptions::new()
-            .max_connections(5)
-            .connect(&db_url)
-            .await
-            .with_context(|| format!(
-                "Cannot connect to MySQL (host={}:{} db={} user={})",
-                config.sql_ip, config.sql_port, config.sql_db, config.sql_id
-            ))?
-    };
+    let pool = yuri::database::open_pool(&config)
+        .await
+        .with_context(|| format!(
+            "Cannot connect to MySQL (host={}:{} db={} user={})",
+            config.sql_ip, config.sql_port, config.sql_db, config.sql_id
+        ))?;
+
+    if config.db_health_check_secs > 0 {
+        let every = std::time::Duration::from_secs(config.db_health_check_secs);
+        tokio::spawn(yuri::database::run_health_checks(pool.clone(), every));
+    }
 
     db::reset_all_online(&pool).await;
     tracing::info!("[char] [started] Char Server Started.");
 
+    if config.char_metrics_port != 0 {
+        let addr = format!("{}:{}", config.metrics_ip, config.char_metrics_port);
+        tokio::spawn(async move {
+            if let Err(e) = yuri::metrics::serve(addr).await {
+                tracing::error!("[char] [metrics] endpoint failed: {}", e);
+            }
+        });
+    }
+
     let bind_addr = format!("{}:{}", config.char_ip, config.char_port);
     let state = Arc::new(CharState::new(pool, config));
 
@@ -71,5 +76,25 @@ async fn main() -> Result<()> {
         });
     }
 
-    CharState::run(state, &bind_addr).await
+    let live = Arc::clone(&state.config);
+    tokio::spawn(async move {
+        if let Err(e) = yuri::servers::reload_on_hangup(live, conf_file.into(), "char").await {
+            tracing::error!("[char] [config] SIGHUP reload unavailable: {}", e);
+        }
+    });
+
+    // Stop after the map servers: their final saves arrive over the links.
+    tokio::select! {
+        r = CharState::run(Arc::clone(&state), &bind_addr) => r,
+        r = yuri::servers::terminated() => {
+            r?;
+            let timeout = std::time::Duration::from_secs(state.config.get().shutdown_timeout_secs);
+            tracing::info!("[char] [shutdown] waiting up to {:?} for map servers to disconnect", timeout);
+            match state.drain_map_links(timeout).await {
+                0 => tracing::info!("[char] [shutdown] all map servers drained"),
+                n => tracing::warn!("[char] [shutdown] {} map server(s) still connected, stopping anyway", n),
+            }
+            Ok(())
+        }
+    }
 }
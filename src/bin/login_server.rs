@@ -1,6 +1,5 @@
 use std::sync::Arc;
 use anyhow::{Context, Result};
-use sqlx::mysql::MySqlPoolOptions;
 use yuri::config::ServerConfig;
 use yuri::servers::login::{LoginState, parse_lang_file};
 
@@ -13,13 +12,14 @@ async fn main() -> Result<()> {
 
     let mut conf_file = "conf/server.yaml".to_string();
     let mut lang_file = "conf/lang.yaml".to_string();
+    let mut drain = false;
 
     let args: Vec<String> = std::env::args().collect();
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
             "--help" | "--h" | "--?" | "/?" => {
-                println!("Usage: login_server [--conf FILE] [--lang FILE]");
+                println!("Usage: login_server [--conf FILE] [--lang FILE] [--drain]");
                 return Ok(());
             }
             "--conf" => {
@@ -40,6 +40,7 @@ async fn main() -> Result<()> {
                     return Ok(());
                 }
             }
+            "--drain" => drain = true,
             _ => {}
         }
         i += 1;
@@ -55,21 +56,47 @@ async fn main() -> Result<()> {
     let lang_content = std::fs::read_to_string(&lang_file).unwrap_or_default();
     let messages = parse_lang_file(&lang_content)?;
 
-    let db_url = format!(
-        "mysql://{}:{}@{}:{}/{}",
-        config.sql_id, config.sql_pw, config.sql_ip, config.sql_port, config.sql_db
-    );
-    let pool = MySqlPoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
+    let pool = yuri::database::open_pool(&config)
         .await
         .with_context(|| format!("Cannot connect to DB: {}", config.sql_ip))?;
 
+    if config.db_health_check_secs > 0 {
+        let every = std::time::Duration::from_secs(config.db_health_check_secs);
+        tokio::spawn(yuri::database::run_health_checks(pool.clone(), every));
+    }
+
     tracing::info!("[login] [started] Login Server Started");
 
+    if config.login_metrics_port != 0 {
+        let addr = format!("{}:{}", config.metrics_ip, config.login_metrics_port);
+        tokio::spawn(async move {
+            if let Err(e) = yuri::metrics::serve(addr).await {
+                tracing::error!("[login] [metrics] endpoint failed: {}", e);
+            }
+        });
+    }
+
     let bind = format!("{}:{}", config.login_ip, config.login_port);
     let state = Arc::new(LoginState::new(pool, config, messages));
 
+    if drain {
+        state.drain.set(true);
+        tracing::warn!("[login] [drain] started draining: new logins are refused");
+    }
+    let s = Arc::clone(&state);
+    tokio::spawn(async move {
+        if let Err(e) = LoginState::run_drain_control(s, std::time::Duration::from_secs(10)).await {
+            tracing::error!("[login] [drain] signal handlers unavailable: {}", e);
+        }
+    });
+
+    let live = Arc::clone(&state.config);
+    tokio::spawn(async move {
+        if let Err(e) = yuri::servers::reload_on_hangup(live, conf_file.into(), "login").await {
+            tracing::error!("[login] [config] SIGHUP reload unavailable: {}", e);
+        }
+    });
+
     LoginState::run(state, &bind).await?;
     Ok(())
 }
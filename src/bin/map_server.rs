@@ -1,5 +1,4 @@
 use anyhow::{Context, Result};
-use sqlx::mysql::MySqlPoolOptions;
 use std::ffi::CString;
 use std::sync::Arc;
 use yuri::config::ServerConfig;
@@ -28,6 +27,7 @@ extern "C" {
     fn rust_mob_timer_spawns(id: i32, n: i32) -> i32;
     fn map_cronjob(id: i32, n: i32) -> i32;
     fn npc_runtimers(id: i32, n: i32) -> i32;
+    fn clif_resume_expire(sd: *mut std::ffi::c_void, eof: i32) -> i32;
 
     // Legacy C SQL functions from libdeps.a
     fn Sql_Malloc() -> *mut std::ffi::c_void;
@@ -138,20 +138,12 @@ async fn main() -> Result<()> {
     tracing::info!("[map] Map Server Started.");
 
     // Rust async DB pool
-    let pool = {
-        let db_url = format!(
-            "mysql://{}:{}@{}:{}/{}",
-            config.sql_id, config.sql_pw, config.sql_ip, config.sql_port, config.sql_db
-        );
-        MySqlPoolOptions::new()
-            .max_connections(5)
-            .connect(&db_url)
-            .await
-            .with_context(|| format!(
-                "Cannot connect to MySQL (host={}:{} db={} user={})",
-                config.sql_ip, config.sql_port, config.sql_db, config.sql_id
-            ))?
-    };
+    let pool = yuri::database::open_pool(&config)
+        .await
+        .with_context(|| format!(
+            "Cannot connect to MySQL (host={}:{} db={} user={})",
+            config.sql_ip, config.sql_port, config.sql_db, config.sql_id
+        ))?;
 
     // Register the pool with the Rust DB module layer (map_db, mob_db, etc.).
     // We use set_pool() here instead of rust_db_connect() to avoid
@@ -183,6 +175,12 @@ async fn main() -> Result<()> {
         .await
         .ok();
 
+    // Script kv store must be in place before the startup script runs.
+    match yuri::servers::map::kv::load(&pool, config.server_id).await {
+        Ok(n) => tracing::info!("[map] [kv] loaded {} keys", n),
+        Err(e) => tracing::error!("[map] [kv] load failed, starting empty and not saving: {e:#}"),
+    }
+
     // Run all blocking init (rust_map_init, rust_*db_init, C game init) on a
     // dedicated thread. spawn_blocking is required because these functions call
     // blocking_run() internally, which panics if called from within the tokio runtime.
@@ -191,6 +189,7 @@ async fn main() -> Result<()> {
         let data_dir = config.data_dir.clone();
         let serverid = config.server_id;
         let map_port = config.map_port;
+        let cipher = yuri::network::crypt::listener_cipher(&config, &config.map_cipher);
 
         tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
             let maps_dir_c = CString::new(maps_dir.as_str()).unwrap();
@@ -221,7 +220,12 @@ async fn main() -> Result<()> {
                 map_loadgameregistry();
                 rust_session_set_default_parse(clif_parse);
                 rust_session_set_default_timeout(clif_timeout);
-                rust_make_listen_port(map_port as i32);
+                let listen_fd = rust_make_listen_port(map_port as i32);
+                let manager = yuri::session::get_session_manager();
+                tracing::info!("[map] [crypt] client cipher: {}", cipher.name());
+                // -1 is the map_ws_port listener (see run_async_server)
+                manager.set_listener_cipher(listen_fd, cipher.clone());
+                manager.set_listener_cipher(-1, cipher);
                 authdb_init();
 
                 // Timers from the old do_init — restored here after do_init was removed.
@@ -230,6 +234,7 @@ async fn main() -> Result<()> {
                 yuri::ffi::timer::timer_insert(50,   50,   Some(rust_mob_timer_spawns), 0, 0);
                 yuri::ffi::timer::timer_insert(100,  100,  Some(npc_runtimers),    0, 0);
                 yuri::ffi::timer::timer_insert(1000, 1000, Some(map_cronjob),      0, 0);
+                yuri::ffi::timer::timer_insert(1000, 1000, Some(yuri::ffi::map_char::rust_resume_tick), 0, 0);
 
                 rust_set_termfunc(Some(map_do_term));
             }
@@ -244,6 +249,32 @@ async fn main() -> Result<()> {
     yuri::ffi::map_char::set_map_state(Arc::clone(&state));
     // Register intif_mmo_tosd so packet.rs can call it without linking map_game into libyuri.
     yuri::ffi::map_char::set_mmo_tosd_fn(intif_mmo_tosd);
+    // Report 0x3812 save-now acks back to the GM that issued /savenow.
+    yuri::ffi::map_char::set_savenow_ack_fn(yuri::game::gm_command::rust_gm_savenow_ack);
+    // Log out parked players whose reconnect-resume window has passed.
+    yuri::ffi::map_char::set_resume_expire_fn(clif_resume_expire);
+
+    let config = state.config.get();
+    if config.map_metrics_port != 0 {
+        let addr = format!("{}:{}", config.metrics_ip, config.map_metrics_port);
+        tokio::spawn(async move {
+            if let Err(e) = yuri::metrics::serve(addr).await {
+                tracing::error!("[map] [metrics] endpoint failed: {}", e);
+            }
+        });
+    }
+
+    // SIGTERM/SIGINT end the session loop the same way @shutdown does, so the
+    // shutdown below (player saves, char_server ack) still runs.
+    tokio::spawn(async {
+        match yuri::servers::terminated().await {
+            Ok(()) => {
+                tracing::info!("[map] [shutdown] stop signal received");
+                yuri::ffi::core::rust_request_shutdown();
+            }
+            Err(e) => tracing::error!("[map] [shutdown] cannot watch stop signals: {}", e),
+        }
+    });
 
     // Spawn char server reconnect loop (replaces check_connect_char timer)
     {
@@ -265,18 +296,66 @@ async fn main() -> Result<()> {
         });
     }
 
-    tracing::info!("[map] [ready] Listening on {}:{}", state.config.map_ip, state.config.map_port);
+    // Drop expired script kv keys every minute (reads already ignore them)
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        loop {
+            ticker.tick().await;
+            yuri::servers::map::kv::sweep();
+        }
+    });
+
+    // Autosave world-shared state (GAMEREG, world counters, script kv) every save_time seconds
+    {
+        let s = Arc::clone(&state);
+        tokio::spawn(async move {
+            let secs = s.config.get().save_time.max(1) as u64;
+            let mut ticker = tokio::time::interval(tokio::time::Duration::from_secs(secs));
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = yuri::servers::map::server_state::flush(&s.db, s.config.get().server_id).await {
+                    tracing::error!("[map] [server_state] autosave failed: {e:#}");
+                }
+                if let Err(e) = yuri::servers::map::kv::flush(&s.db, s.config.get().server_id).await {
+                    tracing::error!("[map] [kv] autosave failed: {e:#}");
+                }
+            }
+        });
+    }
+
+    tracing::info!("[map] [ready] Listening on {}:{}", config.map_ip, config.map_port);
 
     // Run the C session event loop. LocalSet is required for spawn_local (accept_loop,
     // session_io_task). This drives client accept + I/O until shutdown is signalled.
     let local = tokio::task::LocalSet::new();
-    local.run_until(yuri::session::run_async_server(state.config.map_port)).await
+    local.run_until(yuri::session::run_async_server(config.map_port)).await
         .map_err(|e| anyhow::anyhow!("session loop error: {}", e))?;
 
     tracing::info!("[map] Shutting down...");
+    if let Err(e) = yuri::servers::map::server_state::flush(&state.db, config.server_id).await {
+        tracing::error!("[map] [server_state] final save failed: {e:#}");
+    }
+    if let Err(e) = yuri::servers::map::kv::flush(&state.db, config.server_id).await {
+        tracing::error!("[map] [kv] final save failed: {e:#}");
+    }
+    // Parked players are off the session table, so log them out first or
+    // map_savechars would miss them.
+    let parked = yuri::ffi::map_char::expire_all_parked();
+    if parked > 0 {
+        tracing::info!("[map] [shutdown] logged out {} parked players", parked);
+    }
     // Deregister the term callback before calling map_do_term() explicitly so
     // a signal arriving after the session loop cannot fire it a second time.
     unsafe { rust_set_termfunc(None); }
     unsafe { map_do_term(); }
+    // map_do_term queued a save for every player; exit only once char_server
+    // confirms they are written (or the timeout passes).
+    let timeout = std::time::Duration::from_secs(state.config.get().shutdown_timeout_secs);
+    if yuri::servers::map::char::shutdown_flush(&state, timeout).await {
+        tracing::info!("[map] [shutdown] char server confirmed final saves");
+    } else {
+        tracing::error!("[map] [shutdown] char server did not confirm final saves within {:?}", timeout);
+    }
     Ok(())
 }
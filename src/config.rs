@@ -1,15 +1,17 @@
 //! Server configuration module
 //!
-//! Parses and manages server configuration from YAML files.
+//! Parses and manages server configuration from YAML (or JSON/TOML) files.
 //! This replaces the legacy C config.c implementation with a type-safe Rust version.
 //!
-//! Uses serde_yaml for automatic parsing - just define the struct and serde handles
-//! all the parsing, validation, and type conversion!
+//! Uses serde for automatic parsing - just define the struct and serde handles
+//! all the parsing, validation, and type conversion! The file extension picks
+//! the format; anything unrecognised is treated as YAML.
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
+use std::sync::{Arc, RwLock};
 
 /// Maximum number of meta files that can be loaded
 pub const META_MAX: usize = 20;
@@ -17,6 +19,91 @@ pub const META_MAX: usize = 20;
 /// Maximum number of towns supported
 pub const TOWN_MAX: usize = 255;
 
+/// Every problem `ServerConfig::validate` found, one per line
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid config:\n  - {}", .problems.join("\n  - "))]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+/// On-disk config encoding, selected by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Pick the format for `path` (`.json`, `.toml`, otherwise YAML)
+    pub fn from_path(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("json") => ConfigFormat::Json,
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    /// Human-readable name used in error messages
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Toml => "TOML",
+        }
+    }
+}
+
+/// Load configuration from `path`, choosing the parser by file extension
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ServerConfig> {
+    ServerConfig::from_file(path)
+}
+
+/// Re-read `path` for a live reload; the result still needs
+/// `keep_restart_only` against the running config before it is swapped in
+pub fn reload_from_path(path: &Path) -> Result<ServerConfig> {
+    ServerConfig::from_file(path)
+}
+
+/// The running server's config, replaced whole on reload
+///
+/// Readers take a snapshot with `get` and keep it for the operation at hand,
+/// so one request never sees half an old and half a new config.
+#[derive(Debug)]
+pub struct LiveConfig {
+    current: RwLock<Arc<ServerConfig>>,
+}
+
+impl LiveConfig {
+    pub fn new(config: ServerConfig) -> Self {
+        Self { current: RwLock::new(Arc::new(config)) }
+    }
+
+    /// The config in effect now
+    pub fn get(&self) -> Arc<ServerConfig> {
+        Arc::clone(&self.current.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Swap in `config` as is (callers have already applied `keep_restart_only`)
+    pub fn set(&self, config: Arc<ServerConfig>) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    /// Re-read `path` and swap it in, keeping restart-only settings. Returns
+    /// the restart-only keys whose new values were ignored. On error the
+    /// running config is left as it was.
+    pub fn reload(&self, path: &Path) -> Result<Vec<&'static str>> {
+        let mut next = reload_from_path(path)?;
+        let ignored = next.keep_restart_only(&self.get());
+        self.set(Arc::new(next));
+        Ok(ignored)
+    }
+}
+
 /// A point in 3D space (map, x, y)
 ///
 /// This matches the C struct exactly due to #[repr(C)]
@@ -53,6 +140,30 @@ pub struct ServerConfig {
     pub sql_pw: String,
     pub sql_db: String,
 
+    /// Database connect attempts at startup (at least 1), the first retry
+    /// `sql_connect_retry_ms` after a failure and doubling up to 30s
+    #[serde(default = "default_sql_connect_attempts")]
+    pub sql_connect_attempts: u32,
+    #[serde(default = "default_sql_connect_retry_ms")]
+    pub sql_connect_retry_ms: u64,
+
+    /// Database pool: most open connections, how long a query may wait for
+    /// one (ms), and how long an idle one is kept (ms, 0 = forever)
+    #[serde(default = "default_db_max_connections")]
+    pub db_max_connections: u32,
+    #[serde(default = "default_db_acquire_timeout_ms")]
+    pub db_acquire_timeout_ms: u64,
+    #[serde(default = "default_db_idle_timeout_ms")]
+    pub db_idle_timeout_ms: u64,
+
+    /// Seconds between `SELECT 1` pool health checks (0 = off)
+    #[serde(default = "default_db_health_check_secs")]
+    pub db_health_check_secs: u64,
+
+    /// Log p50/p99 of character loads every 100 loads (char server; debugging)
+    #[serde(default)]
+    pub char_load_timing: bool,
+
     // ============================================
     // Login Server Configuration
     // ============================================
@@ -83,6 +194,15 @@ pub struct ServerConfig {
     #[serde(default = "default_char_port")]
     pub char_port: u16,
 
+    /// Compression for mmo_charstatus transfers sent by this server; receivers
+    /// accept every codec regardless
+    #[serde(default)]
+    pub charstatus_codec: crate::servers::char::codec::Codec,
+
+    /// Shortest password accepted for a new character
+    #[serde(default = "default_min_password_len")]
+    pub min_password_len: usize,
+
     // ============================================
     // Map Server Configuration
     // ============================================
@@ -95,6 +215,14 @@ pub struct ServerConfig {
     #[serde(default)]
     pub server_id: i32,
 
+    /// Shard ids whose `Spawns<id>` tables this server loads (empty: its own)
+    #[serde(default)]
+    pub spawn_shards: Vec<u32>,
+
+    /// One unified spawn table to load instead of any shard tables
+    #[serde(default)]
+    pub spawn_table: Option<String>,
+
     // ============================================
     // Encryption & Security
     // ============================================
@@ -102,6 +230,42 @@ pub struct ServerConfig {
     #[serde(default)]
     pub xor_key: String,
 
+    /// Append an HMAC to login↔char↔map frames (all servers must agree)
+    #[serde(default)]
+    pub interserver_mac: bool,
+
+    /// Shared HMAC key for inter-server frames (required when interserver_mac is on)
+    #[serde(default)]
+    pub interserver_secret: String,
+
+    /// Append a CRC32 to inter-server frames when no MAC is in use, to catch
+    /// corrupted transfers (all servers must agree)
+    #[serde(default)]
+    pub interserver_crc: bool,
+
+    /// Addresses allowed to open inter-server links (char→login, map→char):
+    /// hosts or CIDR ranges like "10.0.0.0/8". Empty allows any address.
+    #[serde(default)]
+    pub interserver_allow: Vec<String>,
+
+    /// Client packet cipher on the login port (and its WebSocket listener):
+    /// "xor" (legacy clients) or "rolling"
+    #[serde(default = "default_cipher")]
+    pub cipher: String,
+
+    /// Second login port for clients on another cipher (0 = disabled)
+    #[serde(default)]
+    pub login_alt_port: u16,
+
+    /// Cipher spoken on `login_alt_port`
+    #[serde(default = "default_login_alt_cipher")]
+    pub login_alt_cipher: String,
+
+    /// Client packet cipher on the map port (and `map_ws_port`) for opcodes
+    /// not keyed by the character's EncHash
+    #[serde(default = "default_cipher")]
+    pub map_cipher: String,
+
     // ============================================
     // Game Settings
     // ============================================
@@ -132,6 +296,226 @@ pub struct ServerConfig {
     #[serde(default = "default_droprate")]
     pub droprate: i32,
 
+    /// Event experience multiplier, applied on top of `xprate` (e.g. 2.0 for a 2x weekend)
+    #[serde(default = "default_event_rate")]
+    pub exp_rate: f32,
+
+    /// Event drop multiplier, applied to mobs' native drops and exposed to drop scripts
+    #[serde(default = "default_event_rate")]
+    pub drop_rate: f32,
+
+    /// Half the client viewport width in tiles (mob look-broadcast strips)
+    #[serde(default = "default_viewport_half_width")]
+    pub viewport_half_width: i32,
+
+    /// Half the client viewport height in tiles (mob look-broadcast strips)
+    #[serde(default = "default_viewport_half_height")]
+    pub viewport_half_height: i32,
+
+    /// Connection attempts from one IP before it is refused until the next reset
+    #[serde(default = "default_throttle_threshold")]
+    pub throttle_threshold: u32,
+
+    /// Seconds between throttle table resets
+    #[serde(default = "default_throttle_reset_secs")]
+    pub throttle_reset_secs: u32,
+
+    /// Wrong passwords from one IP before the login server refuses it
+    #[serde(default = "default_login_lockout_threshold")]
+    pub login_lockout_threshold: u32,
+
+    /// Seconds after an IP's last wrong password until its count resets
+    #[serde(default = "default_login_lockout_window_secs")]
+    pub login_lockout_window_secs: u64,
+
+    /// Maximum concurrent sessions (client + inter-server) on the session layer
+    #[serde(default = "default_max_sessions")]
+    pub max_sessions: usize,
+
+    /// Most concurrent client sessions from one IP (0 = unlimited); loopback
+    /// and the configured server IPs are exempt
+    #[serde(default = "default_max_connections_per_ip")]
+    pub max_connections_per_ip: usize,
+
+    /// New connections from one IP within a second that lock it out for ten
+    /// minutes; loopback and the configured server IPs are exempt
+    #[serde(default = "default_ddos_connections_per_sec")]
+    pub ddos_connections_per_sec: u32,
+
+    /// Log a warning when one pass of the timer loop takes longer than this
+    /// many milliseconds (0 = never)
+    #[serde(default = "default_tick_budget_ms")]
+    pub tick_budget_ms: u64,
+
+    /// Stop the mob sweep after this many milliseconds and resume it on the
+    /// next mob timer call (0 = sweep every mob each call)
+    #[serde(default)]
+    pub mob_tick_budget_ms: u64,
+
+    /// Fixed seed for game-layer rolls (mob targeting, hit chance, drops);
+    /// unset = seeded from OS entropy. For reproducible test servers only.
+    #[serde(default)]
+    pub rng_seed: Option<u64>,
+
+    /// WebSocket listener ports for browser clients (0 = disabled).
+    /// Requires building with `--features websocket`.
+    #[serde(default)]
+    pub login_ws_port: u16,
+    #[serde(default)]
+    pub map_ws_port: u16,
+
+    /// Prometheus `/metrics` endpoints, one port per server (0 = disabled),
+    /// all bound on `metrics_ip`
+    #[serde(default = "default_metrics_ip")]
+    pub metrics_ip: String,
+    #[serde(default)]
+    pub login_metrics_port: u16,
+    #[serde(default)]
+    pub char_metrics_port: u16,
+    #[serde(default)]
+    pub map_metrics_port: u16,
+
+    /// Initial read/write buffer capacity (bytes) for game client sessions
+    #[serde(default = "default_client_read_buffer")]
+    pub client_read_buffer: usize,
+    #[serde(default = "default_client_write_buffer")]
+    pub client_write_buffer: usize,
+
+    /// Initial read/write buffer capacity (bytes) for inter-server links
+    #[serde(default = "default_interserver_read_buffer")]
+    pub interserver_read_buffer: usize,
+    #[serde(default = "default_interserver_write_buffer")]
+    pub interserver_write_buffer: usize,
+
+    /// Most bytes a game client session may have queued for sending; a write
+    /// past it fails and the client is dropped. Links always get 4 MiB.
+    #[serde(default = "default_client_write_cap")]
+    pub client_write_cap: usize,
+
+    /// Queue up to this many unsent flushes per game client instead of
+    /// waiting on its socket; one more closes it as a slow consumer (0 = off)
+    #[serde(default)]
+    pub client_send_queue_depth: usize,
+
+    /// Longest text (bytes) a script's `speak`/`msg` sends; longer text is
+    /// cut at a character boundary (at most `chat::MAX_CHAT_LEN`)
+    #[serde(default = "default_chat_max_len")]
+    pub chat_max_len: usize,
+
+    /// Compression offered to game clients that negotiate it (unset = off),
+    /// and the smallest outbound packet (bytes) worth compressing
+    #[serde(default)]
+    pub client_compression: Option<crate::servers::char::codec::Codec>,
+    #[serde(default = "default_client_compression_threshold")]
+    pub client_compression_threshold: usize,
+
+    /// Outbound inter-server connect retry: first delay, cap on the doubling
+    /// delay, and failures before giving up (0 = retry forever)
+    #[serde(default = "default_reconnect_initial_ms")]
+    pub reconnect_initial_ms: u64,
+    #[serde(default = "default_reconnect_max_ms")]
+    pub reconnect_max_ms: u64,
+    #[serde(default)]
+    pub reconnect_max_attempts: u32,
+
+    /// Outbound inter-server connect attempts still pending after this many
+    /// milliseconds fail and are retried as above
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+
+    /// TCP keepalive on accepted sockets: seconds of silence before the first
+    /// probe (0 = keepalive off), seconds between probes, unanswered probes
+    /// before the kernel resets the connection
+    #[serde(default = "default_tcp_keepalive_idle_secs")]
+    pub tcp_keepalive_idle_secs: u64,
+    #[serde(default = "default_tcp_keepalive_interval_secs")]
+    pub tcp_keepalive_interval_secs: u64,
+    #[serde(default = "default_tcp_keepalive_probes")]
+    pub tcp_keepalive_probes: u32,
+
+    /// Set TCP_NODELAY on the map server's client sockets
+    #[serde(default)]
+    pub nodelay: bool,
+
+    /// Seconds without a read before a client session's timeout callback runs
+    /// and it is closed; also applies to login-port clients (0 = never)
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+
+    /// Seconds a player whose connection dropped without an in-game logout
+    /// stays in the world awaiting reconnect from the same subnet (0 = disabled)
+    #[serde(default = "default_resume_grace_secs")]
+    pub resume_grace_secs: u16,
+
+    /// Seconds a stopping map server waits for char_server to confirm its
+    /// final saves, and a stopping char server waits for map links to close
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+
+    /// Seconds a stopping server keeps running with new connections refused,
+    /// waiting for connected clients to leave (0 = stop at once)
+    #[serde(default)]
+    pub drain_timeout_secs: u64,
+
+    /// Refuse logins from a /24 other than the character's last recorded one
+    /// (a subnet change is always logged; this turns the warning into a block)
+    #[serde(default)]
+    pub login_subnet_lock: bool,
+
+    /// A login for a character already online: reject it, or also kick the
+    /// existing session
+    #[serde(default)]
+    pub double_login: crate::servers::char::DoubleLoginPolicy,
+
+    /// Percent of current exp lost when a player dies (0-100)
+    #[serde(default)]
+    pub death_exp_loss_pct: u8,
+
+    /// Percent of max durability each equipped item loses on death (0-100)
+    #[serde(default)]
+    pub death_dura_loss_pct: u8,
+
+    /// Where `pc:deathRespawn()` brings a dead player back: bind, town or here
+    #[serde(default)]
+    pub death_respawn: crate::servers::map::death::DeathRespawn,
+
+    /// Town respawn point; unset = start_point
+    #[serde(default)]
+    pub death_town: Option<Point>,
+
+    /// Faction relations for combat targeting; unlisted pairs use the
+    /// defaults in `servers::map::faction`
+    #[serde(default)]
+    pub faction_relations: Vec<crate::servers::map::faction::FactionRelation>,
+
+    /// Players below this level are untargetable near start_point (0 = off)
+    #[serde(default)]
+    pub newbie_protect_until_level: u8,
+
+    /// Tiles around start_point where newbie protection applies (0 = anywhere)
+    #[serde(default)]
+    pub newbie_safe_radius: u16,
+
+    /// Refuse walk steps faster than the character's speed allows
+    #[serde(default)]
+    pub move_check: bool,
+
+    /// Percent shaved off each step's expected duration for latency jitter (0-90)
+    #[serde(default = "default_move_tolerance_pct")]
+    pub move_tolerance_pct: u8,
+
+    /// Early walk steps let through back to back before pacing applies
+    #[serde(default = "default_move_burst")]
+    pub move_burst: u8,
+
+    /// Refused steps (within a minute of each other) before disconnect (0 = never)
+    #[serde(default)]
+    pub move_kick_after: u32,
+
+    /// Path to a message-of-the-day text file shown on world-enter (empty = none)
+    #[serde(default)]
+    pub motd: String,
+
     // ============================================
     // Meta Files & Towns
     // ============================================
@@ -152,6 +536,10 @@ pub struct ServerConfig {
     #[serde(default = "default_lua_dir")]
     pub lua_dir: String,
 
+    /// Warn at boot about literal ITEM(n)/RECIPE(n) ids missing from the dbs
+    #[serde(default)]
+    pub lua_validate_refs: bool,
+
     #[serde(default = "default_maps_dir")]
     pub maps_dir: String,
 
@@ -164,6 +552,34 @@ pub struct ServerConfig {
 // These are called by serde when a field is missing
 // ============================================
 
+fn default_sql_connect_attempts() -> u32 {
+    1
+}
+
+fn default_sql_connect_retry_ms() -> u64 {
+    1000
+}
+
+fn default_db_max_connections() -> u32 {
+    crate::database::PoolSettings::DEFAULT.max_connections
+}
+
+fn default_db_acquire_timeout_ms() -> u64 {
+    crate::database::PoolSettings::DEFAULT.acquire_timeout.as_millis() as u64
+}
+
+fn default_db_idle_timeout_ms() -> u64 {
+    crate::database::PoolSettings::DEFAULT.idle_timeout.map_or(0, |d| d.as_millis() as u64)
+}
+
+fn default_db_health_check_secs() -> u64 {
+    30
+}
+
+fn default_min_password_len() -> usize {
+    4
+}
+
 fn default_sql_port() -> u16 {
     3306
 }
@@ -188,6 +604,18 @@ fn default_require_reg() -> i32 {
     1
 }
 
+fn default_idle_timeout_secs() -> u64 {
+    60
+}
+
+fn default_resume_grace_secs() -> u16 {
+    30
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
 fn default_save_time() -> i32 {
     60
 }
@@ -200,6 +628,122 @@ fn default_droprate() -> i32 {
     1
 }
 
+fn default_event_rate() -> f32 {
+    1.0
+}
+
+fn default_viewport_half_width() -> i32 {
+    9
+}
+
+fn default_viewport_half_height() -> i32 {
+    8
+}
+
+fn default_throttle_threshold() -> u32 {
+    1
+}
+
+fn default_throttle_reset_secs() -> u32 {
+    600
+}
+
+fn default_login_lockout_threshold() -> u32 {
+    10
+}
+
+fn default_login_lockout_window_secs() -> u64 {
+    15 * 60
+}
+
+fn default_move_tolerance_pct() -> u8 {
+    20
+}
+
+fn default_move_burst() -> u8 {
+    3
+}
+
+fn default_tick_budget_ms() -> u64 {
+    10
+}
+
+fn default_max_connections_per_ip() -> usize {
+    crate::session::DEFAULT_MAX_PER_IP
+}
+
+fn default_ddos_connections_per_sec() -> u32 {
+    crate::network::ddos::DDOS_COUNT
+}
+
+fn default_max_sessions() -> usize {
+    crate::session::MAX_SESSIONS
+}
+
+fn default_cipher() -> String {
+    "xor".to_string()
+}
+
+fn default_login_alt_cipher() -> String {
+    "rolling".to_string()
+}
+
+fn default_metrics_ip() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_chat_max_len() -> usize {
+    crate::servers::map::chat::MAX_CHAT_LEN
+}
+
+fn default_client_compression_threshold() -> usize {
+    512
+}
+
+fn default_tcp_keepalive_idle_secs() -> u64 {
+    crate::session::Keepalive::DEFAULT.idle.as_secs()
+}
+
+fn default_tcp_keepalive_interval_secs() -> u64 {
+    crate::session::Keepalive::DEFAULT.interval.as_secs()
+}
+
+fn default_tcp_keepalive_probes() -> u32 {
+    crate::session::Keepalive::DEFAULT.count
+}
+
+fn default_reconnect_initial_ms() -> u64 {
+    crate::session::ReconnectPolicy::DEFAULT.initial.as_millis() as u64
+}
+
+fn default_reconnect_max_ms() -> u64 {
+    crate::session::ReconnectPolicy::DEFAULT.max.as_millis() as u64
+}
+
+fn default_connect_timeout_ms() -> u64 {
+    crate::session::ReconnectPolicy::DEFAULT.connect_timeout.as_millis() as u64
+}
+
+fn default_client_read_buffer() -> usize {
+    crate::session::RFIFO_SIZE
+}
+
+fn default_client_write_cap() -> usize {
+    crate::session::CLIENT_WDATA_CAP
+}
+
+fn default_client_write_buffer() -> usize {
+    crate::session::WFIFO_SIZE
+}
+
+fn default_interserver_read_buffer() -> usize {
+    crate::session::INTERSERVER_RFIFO_SIZE
+}
+
+fn default_interserver_write_buffer() -> usize {
+    crate::session::INTERSERVER_WFIFO_SIZE
+}
+
 fn default_data_dir() -> String {
     "./data/".to_string()
 }
@@ -217,7 +761,10 @@ fn default_meta_dir() -> String {
 }
 
 impl ServerConfig {
-    /// Load configuration from a YAML file
+    /// Load configuration from a file
+    ///
+    /// The format is chosen from the extension (`.yaml`/`.yml`, `.json`,
+    /// `.toml`); unknown extensions fall back to YAML.
     ///
     /// # Example
     /// ```no_run
@@ -229,14 +776,17 @@ impl ServerConfig {
     /// ```
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
+        let format = ConfigFormat::from_path(path);
 
         // Read file contents
         let contents = fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {}", path.display()))?;
 
-        // Parse YAML - serde does ALL the work!
-        let config: ServerConfig = serde_yaml::from_str(&contents)
-            .with_context(|| format!("Failed to parse YAML in {}", path.display()))?;
+        // Parse - serde does ALL the work!
+        let config = Self::parse(&contents, format)
+            .with_context(|| format!("Failed to parse {} in {}", format.name(), path.display()))?
+            .resolve_env()
+            .with_context(|| format!("Failed to resolve {}", path.display()))?;
 
         // Validate the config
         config.validate()?;
@@ -248,28 +798,126 @@ impl ServerConfig {
     ///
     /// Useful for testing
     pub fn from_str(contents: &str) -> Result<Self> {
-        let config: ServerConfig = serde_yaml::from_str(contents)
-            .context("Failed to parse YAML")?;
+        Self::from_str_format(contents, ConfigFormat::Yaml)
+    }
+
+    /// Parse configuration from a string in the given format
+    pub fn from_str_format(contents: &str, format: ConfigFormat) -> Result<Self> {
+        let config = Self::parse(contents, format)
+            .with_context(|| format!("Failed to parse {}", format.name()))?
+            .resolve_env()?;
 
         config.validate()?;
 
         Ok(config)
     }
 
+    /// Server-wide death penalty (maps may override it from scripts)
+    pub fn death_policy(&self) -> crate::servers::map::death::DeathPolicy {
+        crate::servers::map::death::DeathPolicy {
+            exp_loss_pct: self.death_exp_loss_pct,
+            dura_loss_pct: self.death_dura_loss_pct,
+            respawn: self.death_respawn,
+        }
+    }
+
+    /// Welcome-area protection for low-level characters
+    pub fn newbie_policy(&self) -> crate::servers::map::newbie::NewbiePolicy {
+        crate::servers::map::newbie::NewbiePolicy {
+            until_level: self.newbie_protect_until_level,
+            safe_radius: self.newbie_safe_radius,
+            start: self.start_point,
+        }
+    }
+
+    /// Walk rate check settings
+    pub fn move_policy(&self) -> crate::servers::map::movement::MovePolicy {
+        crate::servers::map::movement::MovePolicy {
+            enabled: self.move_check,
+            tolerance_pct: self.move_tolerance_pct,
+            burst: self.move_burst,
+            kick_after: self.move_kick_after,
+        }
+    }
+
+    /// The spawn tables to load, in precedence order
+    pub fn spawn_sources(&self) -> Vec<crate::servers::map::spawn_shards::SpawnSource> {
+        crate::servers::map::spawn_shards::sources(self.server_id, &self.spawn_shards, self.spawn_table.as_deref())
+    }
+
+    /// `lua_dir`, if it names an existing directory scripts can load from
+    pub fn script_dir(&self) -> Result<&Path> {
+        anyhow::ensure!(!self.lua_dir.trim().is_empty(), "lua_dir is empty");
+        let dir = Path::new(&self.lua_dir);
+        anyhow::ensure!(dir.is_dir(), "lua_dir {} is not a directory", dir.display());
+        Ok(dir)
+    }
+
+    fn parse(contents: &str, format: ConfigFormat) -> Result<Self> {
+        Ok(match format {
+            ConfigFormat::Yaml => serde_yaml::from_str(contents)?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+            ConfigFormat::Toml => toml::from_str(contents)?,
+        })
+    }
+
+    /// Replace every string value written as `${NAME}` with the environment
+    /// variable `NAME`, so secrets like `sql_pw` can be injected by the
+    /// deployment instead of living in the file. Other strings are kept as
+    /// written; an unset variable is an error naming the field.
+    fn resolve_env(self) -> Result<Self> {
+        let mut tree = serde_json::to_value(&self)?;
+        let mut unset = Vec::new();
+        substitute_env(&mut tree, "", &mut unset);
+        anyhow::ensure!(unset.is_empty(), "environment variables not set: {}", unset.join(", "));
+        Ok(serde_json::from_value(tree)?)
+    }
+
     /// Validate configuration values
     ///
-    /// Checks that required fields are set and values are reasonable
-    fn validate(&self) -> Result<()> {
+    /// Checks that required fields are set and values are reasonable. Every
+    /// problem is collected, so one run lists all that needs fixing.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        macro_rules! check {
+            ($cond:expr, $($msg:tt)+) => {
+                if !$cond {
+                    problems.push(format!($($msg)+));
+                }
+            };
+        }
+
         // Check required fields aren't empty
-        anyhow::ensure!(!self.sql_ip.is_empty(), "sql_ip cannot be empty");
-        anyhow::ensure!(!self.sql_id.is_empty(), "sql_id cannot be empty");
-        anyhow::ensure!(!self.sql_db.is_empty(), "sql_db cannot be empty");
-        anyhow::ensure!(!self.map_ip.is_empty(), "map_ip cannot be empty");
-        anyhow::ensure!(!self.char_ip.is_empty(), "char_ip cannot be empty");
-        anyhow::ensure!(!self.login_ip.is_empty(), "login_ip cannot be empty");
+        check!(!self.sql_ip.is_empty(), "sql_ip cannot be empty");
+        check!(!self.sql_id.is_empty(), "sql_id cannot be empty");
+        check!(!self.sql_db.is_empty(), "sql_db cannot be empty");
+        check!(!self.map_ip.is_empty(), "map_ip cannot be empty");
+        check!(!self.char_ip.is_empty(), "char_ip cannot be empty");
+        check!(!self.login_ip.is_empty(), "login_ip cannot be empty");
+
+        // Bind/advertised addresses must be IPv4 literals (sql_ip may be a hostname)
+        for (name, ip) in [
+            ("login_ip", &self.login_ip),
+            ("char_ip", &self.char_ip),
+            ("map_ip", &self.map_ip),
+            ("metrics_ip", &self.metrics_ip),
+        ] {
+            check!(
+                ip.is_empty() || ip.parse::<std::net::Ipv4Addr>().is_ok(),
+                "{} must be an IPv4 address like 127.0.0.1 (got {:?})", name, ip
+            );
+        }
+        for (name, port) in [
+            ("sql_port", self.sql_port),
+            ("login_port", self.login_port),
+            ("char_port", self.char_port),
+            ("map_port", self.map_port),
+        ] {
+            check!(port != 0, "{} must be nonzero", name);
+        }
 
         // Check meta files count
-        anyhow::ensure!(
+        check!(
             self.meta.len() <= META_MAX,
             "Too many meta files: {} (max {})",
             self.meta.len(),
@@ -277,39 +925,232 @@ impl ServerConfig {
         );
 
         // Check towns count
-        anyhow::ensure!(
+        check!(
             self.town.len() <= TOWN_MAX,
             "Too many towns: {} (max {})",
             self.town.len(),
             TOWN_MAX
         );
 
+        check!(
+            self.viewport_half_width > 0 && self.viewport_half_height > 0,
+            "viewport_half_width/viewport_half_height must be positive"
+        );
+
+        for (name, pct) in [("death_exp_loss_pct", self.death_exp_loss_pct), ("death_dura_loss_pct", self.death_dura_loss_pct)] {
+            check!(pct <= 100, "{} must be between 0 and 100 (got {})", name, pct);
+        }
+
+        check!(
+            self.move_tolerance_pct <= 90,
+            "move_tolerance_pct must be between 0 and 90 (got {})", self.move_tolerance_pct
+        );
+
+        for (name, rate) in [("exp_rate", self.exp_rate), ("drop_rate", self.drop_rate)] {
+            check!(
+                crate::servers::map::rates::in_bounds(rate),
+                "{} must be between 0 and {} (got {})", name, crate::servers::map::rates::MAX_RATE, rate
+            );
+        }
+
+        if let Some(t) = &self.spawn_table {
+            check!(
+                crate::servers::map::spawn_shards::valid_table_name(t),
+                "spawn_table must be 1-64 letters, digits or underscores (got {:?})", t
+            );
+            check!(self.spawn_shards.is_empty(), "set spawn_table or spawn_shards, not both");
+        }
+
+        if let Some(&id) = self.spawn_shards.iter().find(|&&id| id > crate::servers::map::spawn_shards::MAX_SHARD_ID) {
+            problems.push(format!("spawn_shards ids must be between 0 and {} (got {})", crate::servers::map::spawn_shards::MAX_SHARD_ID, id));
+        }
+
+        check!(
+            (1..=crate::servers::char::db::MAX_PASSWORD_LEN).contains(&self.min_password_len),
+            "min_password_len must be between 1 and {} (got {})",
+            crate::servers::char::db::MAX_PASSWORD_LEN, self.min_password_len
+        );
+        check!(self.ddos_connections_per_sec > 0, "ddos_connections_per_sec must be at least 1");
+        check!(self.throttle_threshold > 0, "throttle_threshold must be at least 1");
+        check!(self.throttle_reset_secs > 0, "throttle_reset_secs must be positive");
+        check!(self.login_lockout_threshold > 0, "login_lockout_threshold must be at least 1");
+        check!(self.login_lockout_window_secs > 0, "login_lockout_window_secs must be positive");
+        check!(
+            self.max_sessions > 0 && self.max_sessions <= i32::MAX as usize,
+            "max_sessions must be between 1 and {}", i32::MAX
+        );
+        check!(
+            cfg!(feature = "websocket") || (self.login_ws_port == 0 && self.map_ws_port == 0),
+            "login_ws_port/map_ws_port need a build with --features websocket"
+        );
+        for (name, size, max) in [
+            ("client_read_buffer", self.client_read_buffer, crate::session::MAX_RDATA_SIZE),
+            ("interserver_read_buffer", self.interserver_read_buffer, crate::session::MAX_RDATA_SIZE),
+            ("client_write_buffer", self.client_write_buffer, crate::session::MAX_WDATA_SIZE),
+            ("interserver_write_buffer", self.interserver_write_buffer, crate::session::MAX_WDATA_SIZE),
+            ("client_write_cap", self.client_write_cap, crate::session::MAX_WDATA_SIZE),
+        ] {
+            check!(
+                size > 0 && size <= max,
+                "{} must be between 1 and {} bytes (got {})", name, max, size
+            );
+        }
+
+        check!(
+            (1..=crate::servers::map::chat::MAX_CHAT_LEN).contains(&self.chat_max_len),
+            "chat_max_len must be between 1 and {} (got {})",
+            crate::servers::map::chat::MAX_CHAT_LEN, self.chat_max_len
+        );
+        check!(
+            self.client_compression_threshold >= 64,
+            "client_compression_threshold must be at least 64 bytes (got {})", self.client_compression_threshold
+        );
+        for (name, codec) in [("charstatus_codec", Some(self.charstatus_codec)), ("client_compression", self.client_compression)] {
+            check!(
+                codec.is_none_or(|c| c.is_available()),
+                "{} is zstd, but this build lacks the `zstd` feature", name
+            );
+        }
+        check!(self.reconnect_initial_ms > 0, "reconnect_initial_ms must be positive");
+        check!(self.connect_timeout_ms > 0, "connect_timeout_ms must be positive");
+        check!(self.db_max_connections > 0, "db_max_connections must be at least 1");
+        check!(self.db_acquire_timeout_ms > 0, "db_acquire_timeout_ms must be positive");
+        check!(
+            self.tcp_keepalive_idle_secs == 0 || (self.tcp_keepalive_interval_secs > 0 && self.tcp_keepalive_probes > 0),
+            "tcp_keepalive_interval_secs and tcp_keepalive_probes must be positive when keepalive is on"
+        );
+        check!(
+            self.reconnect_max_ms >= self.reconnect_initial_ms,
+            "reconnect_max_ms ({}) must be at least reconnect_initial_ms ({})",
+            self.reconnect_max_ms, self.reconnect_initial_ms
+        );
+
         // Check XOR key length (max 9 chars + null terminator in C)
-        if !self.xor_key.is_empty() {
-            anyhow::ensure!(
-                self.xor_key.len() <= 9,
-                "xor_key too long: {} chars (max 9)",
-                self.xor_key.len()
+        check!(!self.xor_key.is_empty(), "xor_key cannot be empty");
+        check!(
+            self.xor_key.len() <= 9,
+            "xor_key too long: {} chars (max 9)",
+            self.xor_key.len()
+        );
+
+        // New characters are placed here; 0,0,0 is what an unset point looks like
+        let sp = self.start_point;
+        check!(
+            i32::from(sp.m) < crate::game::pc::MAX_MAP_PER_SERVER,
+            "start_point.m must be below {} (got {})", crate::game::pc::MAX_MAP_PER_SERVER, sp.m
+        );
+        check!(sp != Point::new(0, 0, 0), "start_point is 0,0,0; set the map and tile new characters start on");
+
+        check!(
+            !self.interserver_mac || !self.interserver_secret.is_empty(),
+            "interserver_mac is enabled but interserver_secret is empty"
+        );
+        if let Err(e) = crate::network::acl::Acl::parse(&self.interserver_allow) {
+            problems.push(format!("interserver_allow: {}", e));
+        }
+        for (name, cipher) in [("cipher", &self.cipher), ("login_alt_cipher", &self.login_alt_cipher),
+                               ("map_cipher", &self.map_cipher)] {
+            check!(
+                crate::network::crypt::CIPHER_NAMES.contains(&cipher.as_str()),
+                "{} must be one of {:?} (got {:?})", name, crate::network::crypt::CIPHER_NAMES, cipher
             );
         }
+        check!(
+            self.login_alt_port == 0 || self.login_alt_port != self.login_port,
+            "login_alt_port must differ from login_port"
+        );
 
-        Ok(())
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { problems })
+        }
+    }
+
+    /// Copy the settings that only take effect at startup (database, bind
+    /// addresses and ports, server identity, inter-server credentials, the
+    /// client cipher) from `running` into `self`, so a reload cannot change
+    /// them under live sessions. Returns the keys whose values differed.
+    pub fn keep_restart_only(&mut self, running: &ServerConfig) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+        macro_rules! keep {
+            ($($field:ident),* $(,)?) => {$(
+                if self.$field != running.$field {
+                    ignored.push(stringify!($field));
+                    self.$field = Clone::clone(&running.$field);
+                }
+            )*};
+        }
+        keep!(
+            sql_ip, sql_port, sql_id, sql_pw, sql_db,
+            db_max_connections, db_acquire_timeout_ms, db_idle_timeout_ms, db_health_check_secs,
+            login_id, login_pw, login_ip, login_port,
+            char_id, char_pw, char_ip, char_port,
+            map_ip, map_port, server_id, spawn_shards, spawn_table,
+            xor_key, interserver_mac, interserver_secret, interserver_crc, interserver_allow,
+            cipher, login_alt_port, login_alt_cipher, map_cipher, login_ws_port, map_ws_port,
+            metrics_ip, login_metrics_port, char_metrics_port, map_metrics_port,
+            throttle_reset_secs, max_sessions,
+            client_read_buffer, client_write_buffer, interserver_read_buffer, interserver_write_buffer,
+            maps_dir, data_dir, lua_dir, meta_dir,
+        );
+        ignored
     }
 
-    /// Save configuration to a YAML file
+    /// Save configuration to a file, in the format implied by its extension
     ///
     /// Useful for generating config templates or saving modified configs
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let yaml = serde_yaml::to_string(&self)
-            .context("Failed to serialize config to YAML")?;
-
-        fs::write(path.as_ref(), yaml)
+        let format = ConfigFormat::from_path(path.as_ref());
+        let text = match format {
+            ConfigFormat::Yaml => serde_yaml::to_string(&self)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(&self)?,
+            ConfigFormat::Toml => toml::to_string(&self)?,
+        };
+
+        fs::write(path.as_ref(), text)
             .with_context(|| format!("Failed to write config to {}", path.as_ref().display()))?;
 
         Ok(())
     }
 }
 
+/// The `NAME` in a string that is exactly `${NAME}`
+fn env_reference(s: &str) -> Option<&str> {
+    let name = s.strip_prefix("${")?.strip_suffix('}')?;
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    ((first.is_ascii_alphabetic() || first == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .then_some(name)
+}
+
+/// Substitute `${NAME}` strings throughout `value`; `path` is its dotted key,
+/// recorded in `unset` with the variable for each one missing.
+fn substitute_env(value: &mut serde_json::Value, path: &str, unset: &mut Vec<String>) {
+    use serde_json::Value;
+    match value {
+        Value::String(s) => {
+            let Some(name) = env_reference(s) else { return };
+            match std::env::var(name) {
+                Ok(v) => *s = v,
+                Err(_) => unset.push(format!("{} (from {})", name, path)),
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                substitute_env(item, &format!("{}[{}]", path, i), unset);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                let sub = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                substitute_env(field, &sub, unset);
+            }
+        }
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -331,6 +1172,7 @@ char_pw: "charpw"
 char_ip: "127.0.0.1"
 
 map_ip: "127.0.0.1"
+xor_key: "TestKey"
 
 start_point:
   m: 0
@@ -372,8 +1214,26 @@ start_point:
         assert_eq!(config.deep, 0);
         assert_eq!(config.require_reg, 1);
         assert_eq!(config.save_time, 60);
+        assert_eq!(config.idle_timeout_secs, 60);
+        assert_eq!(config.connect_timeout_ms, 10_000);
+        assert_eq!((config.sql_connect_attempts, config.sql_connect_retry_ms), (1, 1000));
+        assert_eq!(config.db_max_connections, 10);
+        assert_eq!((config.db_acquire_timeout_ms, config.db_idle_timeout_ms), (30_000, 600_000));
+        assert_eq!(config.db_health_check_secs, 30);
+        assert_eq!(config.tcp_keepalive_idle_secs, 120);
+        assert_eq!(config.max_connections_per_ip, 8);
+        assert_eq!(config.ddos_connections_per_sec, 10);
+        assert_eq!(config.resume_grace_secs, 30);
         assert_eq!(config.xprate, 10);
         assert_eq!(config.droprate, 1);
+        assert_eq!(config.exp_rate, 1.0);
+        assert_eq!(config.drop_rate, 1.0);
+        assert_eq!(config.viewport_half_width, 9);
+        assert_eq!(config.viewport_half_height, 8);
+        assert_eq!(config.throttle_threshold, 1);
+        assert_eq!(config.throttle_reset_secs, 600);
+        assert_eq!((config.tick_budget_ms, config.mob_tick_budget_ms), (10, 0));
+        assert!(config.motd.is_empty());
     }
 
     #[test]
@@ -396,6 +1256,7 @@ char_ip: "127.0.0.1"
 char_port: 3005
 
 map_ip: "127.0.0.1"
+xor_key: "TestKey"
 map_port: 3001
 
 start_point:
@@ -425,6 +1286,7 @@ char_id: "charid"
 char_pw: "charpw"
 char_ip: "127.0.0.1"
 map_ip: "127.0.0.1"
+xor_key: "TestKey"
 start_point:
   m: 0
   x: 1
@@ -457,6 +1319,7 @@ char_id: "charid"
 char_pw: "charpw"
 char_ip: "127.0.0.1"
 map_ip: "127.0.0.1"
+xor_key: "TestKey"
 start_point:
   m: 0
   x: 1
@@ -516,6 +1379,7 @@ char_id: "charid"
 char_pw: "charpw"
 char_ip: "127.0.0.1"
 map_ip: "127.0.0.1"
+xor_key: "TestKey"
 start_point:
   m: 0
   x: 1
@@ -540,6 +1404,7 @@ char_id: "charid"
 char_pw: "charpw"
 char_ip: "127.0.0.1"
 map_ip: "127.0.0.1"
+xor_key: "TestKey"
 start_point:
   m: 0
   x: 1
@@ -553,6 +1418,43 @@ start_point:
         assert!(err_msg.contains("sql_ip"));
     }
 
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let mut config = ServerConfig::from_str(minimal_config()).unwrap();
+        config.map_ip = "localhost".into();
+        config.char_port = 0;
+        config.xor_key.clear();
+        config.start_point = Point::new(0, 0, 0);
+
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.problems.len(), 4, "{err}");
+        let msg = err.to_string();
+        for key in ["map_ip", "char_port", "xor_key", "start_point"] {
+            assert!(msg.contains(key), "{key} missing from {msg}");
+        }
+
+        config.start_point = Point::new(u16::MAX, 1, 1);
+        assert!(config.validate().unwrap_err().to_string().contains("start_point.m"));
+    }
+
+    #[test]
+    fn test_env_var_substitution() {
+        std::env::set_var("YURI_TEST_DB_PASS", "s3cret");
+        let config_str = minimal_config().replace("sql_pw: \"pass\"", "sql_pw: \"${YURI_TEST_DB_PASS}\"");
+        let config = ServerConfig::from_str(&config_str).unwrap();
+        assert_eq!(config.sql_pw, "s3cret");
+        assert_eq!(config.login_pw, "loginpw", "literal values are kept");
+
+        let unset = minimal_config().replace("char_pw: \"charpw\"", "char_pw: \"${YURI_TEST_UNSET_VAR}\"");
+        let err = format!("{:#}", ServerConfig::from_str(&unset).unwrap_err());
+        assert!(err.contains("YURI_TEST_UNSET_VAR (from char_pw)"), "{err}");
+
+        assert_eq!(env_reference("${A_1}"), Some("A_1"));
+        assert_eq!(env_reference("x${A}"), None);
+        assert_eq!(env_reference("${}"), None);
+        assert_eq!(env_reference("${1A}"), None);
+    }
+
     #[test]
     fn test_too_many_meta_files() {
         let mut config_str = String::from(minimal_config());
@@ -570,6 +1472,106 @@ start_point:
         assert!(err_msg.contains("Too many meta files"));
     }
 
+    #[test]
+    fn test_chat_max_len_bounds() {
+        let base = minimal_config();
+        assert_eq!(ServerConfig::from_str(base).unwrap().chat_max_len, 127);
+        for bad in ["0", "128"] {
+            let err = ServerConfig::from_str(&format!("{base}chat_max_len: {bad}\n")).unwrap_err();
+            assert!(format!("{err}").contains("chat_max_len"));
+        }
+    }
+
+    #[test]
+    fn test_newbie_policy() {
+        let base = minimal_config();
+        let policy = ServerConfig::from_str(base).unwrap().newbie_policy();
+        assert_eq!(policy.until_level, 0);
+
+        let config = ServerConfig::from_str(&format!(
+            "{base}newbie_protect_until_level: 10\nnewbie_safe_radius: 8\n"
+        )).unwrap();
+        let policy = config.newbie_policy();
+        assert_eq!((policy.until_level, policy.safe_radius, policy.start), (10, 8, config.start_point));
+    }
+
+    #[test]
+    fn test_move_policy() {
+        let base = minimal_config();
+        let policy = ServerConfig::from_str(base).unwrap().move_policy();
+        assert_eq!((policy.enabled, policy.tolerance_pct, policy.burst, policy.kick_after), (false, 20, 3, 0));
+
+        let config = ServerConfig::from_str(&format!("{base}move_check: true\nmove_kick_after: 30\n")).unwrap();
+        assert!(config.move_policy().enabled);
+        assert_eq!(config.move_policy().kick_after, 30);
+        assert!(ServerConfig::from_str(&format!("{base}move_tolerance_pct: 95\n")).is_err());
+    }
+
+    #[test]
+    fn test_interserver_mac_requires_secret() {
+        let base = minimal_config();
+        assert!(!ServerConfig::from_str(base).unwrap().interserver_mac);
+
+        let on = format!("{}interserver_mac: true\n", base);
+        let err = ServerConfig::from_str(&on).unwrap_err();
+        assert!(format!("{}", err).contains("interserver_secret"));
+
+        let keyed = format!("{}interserver_mac: true\ninterserver_secret: s3cret\n", base);
+        assert_eq!(ServerConfig::from_str(&keyed).unwrap().interserver_secret, "s3cret");
+    }
+
+    #[test]
+    fn test_interserver_allow_rules_are_checked() {
+        let base = minimal_config();
+        assert!(ServerConfig::from_str(base).unwrap().interserver_allow.is_empty());
+        let ok = format!("{base}interserver_allow: [\"10.0.0.0/8\", \"192.168.1.5\"]\n");
+        assert_eq!(ServerConfig::from_str(&ok).unwrap().interserver_allow.len(), 2);
+        let bad = format!("{base}interserver_allow: [\"10.0.0.0/40\"]\n");
+        let err = ServerConfig::from_str(&bad).unwrap_err();
+        assert!(format!("{err}").contains("interserver_allow"), "{err}");
+    }
+
+    #[test]
+    fn test_cipher_names_are_checked() {
+        let base = minimal_config();
+        let c = ServerConfig::from_str(base).unwrap();
+        assert_eq!((c.cipher.as_str(), c.login_alt_port, c.login_alt_cipher.as_str()), ("xor", 0, "rolling"));
+        let alt = format!("{base}login_alt_port: 2010\nlogin_alt_cipher: xor\ncipher: rolling\n");
+        assert_eq!(ServerConfig::from_str(&alt).unwrap().cipher, "rolling");
+        let err = ServerConfig::from_str(&format!("{base}cipher: aes\n")).unwrap_err();
+        assert!(format!("{err}").contains("cipher must be one of"), "{err}");
+        assert_eq!(c.map_cipher, "xor");
+        let err = ServerConfig::from_str(&format!("{base}map_cipher: aes\n")).unwrap_err();
+        assert!(format!("{err}").contains("map_cipher must be one of"), "{err}");
+    }
+
+    #[test]
+    fn test_zstd_codec_needs_the_feature() {
+        use crate::servers::char::codec::Codec;
+        let base = minimal_config();
+        assert_eq!(ServerConfig::from_str(base).unwrap().charstatus_codec, Codec::Zlib);
+        let zstd = ServerConfig::from_str(&format!("{base}charstatus_codec: zstd\n"));
+        if cfg!(feature = "zstd") {
+            assert_eq!(zstd.unwrap().charstatus_codec, Codec::Zstd);
+        } else {
+            let err = zstd.unwrap_err();
+            assert!(format!("{err}").contains("charstatus_codec is zstd"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_session_buffer_bounds() {
+        let base = minimal_config();
+        let c = ServerConfig::from_str(base).unwrap();
+        assert_eq!(c.client_read_buffer, crate::session::RFIFO_SIZE);
+        assert_eq!(c.interserver_write_buffer, crate::session::INTERSERVER_WFIFO_SIZE);
+
+        let zero = format!("{}client_write_buffer: 0\n", base);
+        assert!(format!("{}", ServerConfig::from_str(&zero).unwrap_err()).contains("client_write_buffer"));
+        let huge = format!("{}interserver_read_buffer: 1048576\n", base);
+        assert!(format!("{}", ServerConfig::from_str(&huge).unwrap_err()).contains("interserver_read_buffer"));
+    }
+
     #[test]
     fn test_xor_key_too_long() {
         let config_str = r#"
@@ -682,4 +1684,166 @@ town:
         // Cleanup
         std::fs::remove_file(temp_file).ok();
     }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(ConfigFormat::from_path(Path::new("a.yaml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("a.yml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("a.JSON")), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path(Path::new("a.toml")), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path(Path::new("server.conf")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("server")), ConfigFormat::Yaml);
+    }
+
+    #[test]
+    fn test_json_config() {
+        let config_str = r#"{
+            "sql_ip": "127.0.0.1", "sql_id": "user", "sql_pw": "pass", "sql_db": "testdb",
+            "login_id": "loginid", "login_pw": "loginpw", "login_ip": "127.0.0.1",
+            "char_id": "charid", "char_pw": "charpw", "char_ip": "127.0.0.1",
+            "map_ip": "127.0.0.1", "xor_key": "TestKey",
+            "start_point": { "m": 0, "x": 1, "y": 1 }
+        }"#;
+
+        let config = ServerConfig::from_str_format(config_str, ConfigFormat::Json).unwrap();
+        assert_eq!(config.sql_db, "testdb");
+        assert_eq!(config.sql_port, 3306);
+        assert_eq!(config.start_point, Point::new(0, 1, 1));
+    }
+
+    #[test]
+    fn test_reload_keeps_restart_only_fields() {
+        let path = std::env::temp_dir().join(format!("yuri_reload_{}.yaml", std::process::id()));
+        let live = LiveConfig::new(ServerConfig::from_str(minimal_config()).unwrap());
+        let edited = minimal_config()
+            .replace("login_ip: \"127.0.0.1\"", "login_ip: \"10.0.0.1\"")
+            .replace("xor_key: \"TestKey\"", "xor_key: \"newkey\"\nthrottle_threshold: 5");
+        std::fs::write(&path, edited).unwrap();
+
+        let ignored = live.reload(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(ignored, vec!["login_ip", "xor_key"]);
+        let now = live.get();
+        assert_eq!(now.throttle_threshold, 5);
+        assert_eq!(now.login_ip, "127.0.0.1");
+        assert_eq!(now.xor_key, "TestKey");
+    }
+
+    #[test]
+    fn test_reload_error_keeps_running_config() {
+        let live = LiveConfig::new(ServerConfig::from_str(minimal_config()).unwrap());
+        assert!(live.reload(Path::new("/nonexistent/yuri.yaml")).is_err());
+        assert_eq!(live.get().sql_db, "testdb");
+    }
+
+    #[test]
+    fn test_spawn_sources() {
+        use crate::servers::map::spawn_shards::SpawnSource;
+        let mut config = ServerConfig::from_str(minimal_config()).unwrap();
+        config.server_id = 4;
+        assert_eq!(config.spawn_sources(), vec![SpawnSource::Shard(4)]);
+        config.spawn_shards = vec![4, 100];
+        assert!(config.validate().is_err(), "shard ids stop at 99");
+        config.spawn_shards = vec![4, 9];
+        assert!(config.validate().is_ok());
+        config.spawn_table = Some("SpawnsWorld".into());
+        assert!(config.validate().is_err(), "table and shards are exclusive");
+        config.spawn_shards.clear();
+        assert_eq!(config.spawn_sources(), vec![SpawnSource::Table("SpawnsWorld".into())]);
+        config.spawn_table = Some("Spawns` x".into());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_script_dir_must_exist() {
+        let mut config = ServerConfig::from_str(minimal_config()).unwrap();
+        config.lua_dir = std::env::temp_dir().to_string_lossy().into_owned();
+        assert!(config.script_dir().is_ok());
+        config.lua_dir = "  ".into();
+        assert!(config.script_dir().unwrap_err().to_string().contains("empty"));
+        config.lua_dir = "/nonexistent/yuri/lua".into();
+        assert!(config.script_dir().unwrap_err().to_string().contains("not a directory"));
+    }
+
+    #[test]
+    fn test_death_penalty_config() {
+        use crate::servers::map::death::DeathRespawn;
+        let base = r#""sql_ip": "127.0.0.1", "sql_id": "user", "sql_pw": "pass", "sql_db": "testdb",
+            "login_id": "loginid", "login_pw": "loginpw", "login_ip": "127.0.0.1",
+            "char_id": "charid", "char_pw": "charpw", "char_ip": "127.0.0.1",
+            "map_ip": "127.0.0.1", "xor_key": "TestKey", "start_point": { "m": 0, "x": 1, "y": 1 }"#;
+
+        let config = ServerConfig::from_str_format(&format!("{{{base}}}"), ConfigFormat::Json).unwrap();
+        assert_eq!(config.death_respawn, DeathRespawn::Here);
+        assert_eq!(config.death_policy().exp_loss_pct, 0);
+        assert_eq!(config.death_town, None);
+
+        let config = ServerConfig::from_str_format(&format!(
+            r#"{{{base}, "death_exp_loss_pct": 5, "death_respawn": "bind", "death_town": {{ "m": 3, "x": 4, "y": 5 }}}}"#
+        ), ConfigFormat::Json).unwrap();
+        assert_eq!(config.death_policy().exp_loss_pct, 5);
+        assert_eq!(config.death_respawn, DeathRespawn::Bind);
+        assert_eq!(config.death_town, Some(Point::new(3, 4, 5)));
+
+        assert!(ServerConfig::from_str_format(&format!(
+            r#"{{{base}, "death_dura_loss_pct": 101}}"#
+        ), ConfigFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_toml_config() {
+        let config_str = r#"
+sql_ip = "127.0.0.1"
+sql_id = "user"
+sql_pw = "pass"
+sql_db = "testdb"
+login_id = "loginid"
+login_pw = "loginpw"
+login_ip = "127.0.0.1"
+char_id = "charid"
+char_pw = "charpw"
+char_ip = "127.0.0.1"
+map_ip = "127.0.0.1"
+xor_key = "TestKey"
+meta = ["RidableAnimals"]
+
+[start_point]
+m = 0
+x = 1
+y = 1
+"#;
+
+        let config = ServerConfig::from_str_format(config_str, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.sql_db, "testdb");
+        assert_eq!(config.meta, vec!["RidableAnimals".to_string()]);
+        assert_eq!(config.start_point, Point::new(0, 1, 1));
+    }
+
+    #[test]
+    fn test_format_specific_parse_error() {
+        let temp_file = std::env::temp_dir().join("test_bad_config.json");
+        std::fs::write(&temp_file, "{ not json").unwrap();
+
+        let err_msg = format!("{}", load(&temp_file).unwrap_err());
+        assert!(err_msg.contains("Failed to parse JSON"));
+
+        std::fs::remove_file(temp_file).ok();
+    }
+
+    #[test]
+    fn test_save_and_load_all_formats() {
+        let config = ServerConfig::from_str(minimal_config()).unwrap();
+
+        for ext in ["yaml", "json", "toml"] {
+            let temp_file = std::env::temp_dir().join(format!("test_save_config_fmt.{}", ext));
+            config.save(&temp_file).unwrap();
+            let loaded = load(&temp_file).unwrap();
+
+            assert_eq!(config.sql_ip, loaded.sql_ip);
+            assert_eq!(config.start_point, loaded.start_point);
+            assert_eq!(config.meta, loaded.meta);
+
+            std::fs::remove_file(temp_file).ok();
+        }
+    }
 }
@@ -9,6 +9,7 @@ use std::sync::{Mutex, OnceLock};
 use sqlx::Row;
 
 use super::{blocking_run, get_pool};
+use super::name_index::NameIndex;
 
 const ITM_ETC: c_uchar = 18;
 
@@ -85,6 +86,30 @@ fn db() -> &'static Mutex<HashMap<u32, Box<ItemData>>> {
     ITEM_DB.get().expect("[item_db] not initialized")
 }
 
+/// name/yname → id, rebuilt after every load.
+static ITEM_NAMES: Mutex<Option<NameIndex>> = Mutex::new(None);
+
+/// Rebuild `ITEM_NAMES` from the cache. Placeholder `??` names of entries
+/// `search` made up are left out.
+fn rebuild_name_index(map: &HashMap<u32, Box<ItemData>>) {
+    let names = |item: &ItemData| {
+        [fixed_str(&item.name), fixed_str(&item.yname)].into_iter().filter(|n| n != "??").collect::<Vec<_>>()
+    };
+    let owned: Vec<(u32, Vec<String>)> = map.iter().map(|(&id, item)| (id, names(item))).collect();
+    let (index, dupes) = NameIndex::build(owned.iter().map(|(id, n)| (*id, n.iter().map(String::as_str))));
+    for d in &dupes {
+        tracing::warn!("[item_db] name {:?} used by items {} and {}; lookups get {}", d.name, d.kept, d.dropped, d.kept);
+    }
+    tracing::info!("[item_db] name index built names={}", index.len());
+    *ITEM_NAMES.lock().unwrap() = Some(index);
+}
+
+/// A NUL-terminated fixed array as a String.
+pub(crate) fn fixed_str(a: &[c_char]) -> String {
+    let bytes: Vec<u8> = a.iter().take_while(|&&c| c != 0).map(|&c| c as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
 pub(crate) fn str_to_fixed<const N: usize>(dst: &mut [c_char; N], src: &str) {
     let bytes = src.as_bytes();
     let len = bytes.len().min(N - 1);
@@ -234,6 +259,7 @@ async fn load_items() -> Result<usize, sqlx::Error> {
         item.unequip       = row.try_get::<u32, _>(48).map(|v| v as u8).unwrap_or(0);
         item.icon += 49152;
     }
+    rebuild_name_index(&map);
     Ok(count)
 }
 
@@ -257,6 +283,7 @@ pub fn term() {
     if let Some(m) = ITEM_DB.get() {
         m.lock().unwrap().clear();
     }
+    *ITEM_NAMES.lock().unwrap() = None;
 }
 
 /// Returns pointer to item, creating a default entry if missing.
@@ -289,7 +316,7 @@ pub fn searchexist(id: u32) -> *mut ItemData {
     }
 }
 
-/// Linear scan by name or yname (case-insensitive).
+/// Look up by name or yname (case-insensitive) through the name index.
 ///
 /// # Safety
 ///
@@ -301,18 +328,9 @@ pub fn searchname(s: *const c_char) -> *mut ItemData {
     if s.is_null() {
         return null_mut();
     }
-    let target = unsafe { CStr::from_ptr(s) }.to_string_lossy().to_lowercase();
-    let map = db().lock().unwrap();
-    for item in map.values() {
-        let name = unsafe { CStr::from_ptr(item.name.as_ptr()) }
-            .to_string_lossy()
-            .to_lowercase();
-        let yname = unsafe { CStr::from_ptr(item.yname.as_ptr()) }
-            .to_string_lossy()
-            .to_lowercase();
-        if name == target || yname == target {
-            return item.as_ref() as *const ItemData as *mut ItemData;
-        }
-    }
-    null_mut()
+    let target = unsafe { CStr::from_ptr(s) }.to_string_lossy();
+    let Some(id) = ITEM_NAMES.lock().unwrap().as_ref().and_then(|idx| idx.get(&target)) else {
+        return null_mut();
+    };
+    searchexist(id)
 }
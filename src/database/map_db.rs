@@ -314,6 +314,153 @@ fn load_all_registries(
     Ok(map)
 }
 
+/// One row of the Maps table. Types match the DB schema (all INT UNSIGNED
+/// except MapReqLvl, which is INT).
+#[derive(sqlx::FromRow)]
+struct MapRow {
+    map_id: u32,
+    map_name: String,
+    map_bgm: u32,
+    map_bgm_type: u32,
+    map_pv_p: u32,
+    map_spells: u32,
+    map_light: u32,
+    map_weather: u32,
+    map_sweep_time: u32,
+    map_chat: u32,
+    map_ghosts: u32,
+    map_region: u32,
+    map_indoor: u32,
+    map_warpout: u32,
+    map_bind: u32,
+    map_file: String,
+    map_req_lvl: i32,
+    map_req_path: u32,
+    map_req_mark: u32,
+    map_can_summon: u32,
+    map_req_vita: u32,
+    map_req_mana: u32,
+    map_lvl_max: u32,
+    map_vita_max: u32,
+    map_mana_max: u32,
+    map_reject_msg: String,
+    map_can_use: u32,
+    map_can_eat: u32,
+    map_can_smoke: u32,
+    map_can_mount: u32,
+    map_can_group: u32,
+    map_can_equip: u32,
+}
+
+const MAP_SELECT: &str = "SELECT MapId AS map_id, MapName AS map_name, MapBGM AS map_bgm,
+     MapBGMType AS map_bgm_type, MapPvP AS map_pv_p, MapSpells AS map_spells,
+     MapLight AS map_light, MapWeather AS map_weather, MapSweepTime AS map_sweep_time,
+     MapChat AS map_chat, MapGhosts AS map_ghosts, MapRegion AS map_region,
+     MapIndoor AS map_indoor, MapWarpout AS map_warpout, MapBind AS map_bind,
+     MapFile AS map_file, MapReqLvl AS map_req_lvl, MapReqPath AS map_req_path,
+     MapReqMark AS map_req_mark, MapCanSummon AS map_can_summon,
+     MapReqVita AS map_req_vita, MapReqMana AS map_req_mana, MapLvlMax AS map_lvl_max,
+     MapVitaMax AS map_vita_max, MapManaMax AS map_mana_max,
+     MapRejectMsg AS map_reject_msg, MapCanUse AS map_can_use, MapCanEat AS map_can_eat,
+     MapCanSmoke AS map_can_smoke, MapCanMount AS map_can_mount,
+     MapCanGroup AS map_can_group, MapCanEquip AS map_can_equip
+     FROM Maps";
+
+fn query_maps(server_id: i32) -> Result<Vec<MapRow>> {
+    Ok(blocking_run(
+        sqlx::query_as(&format!("{MAP_SELECT} WHERE MapServer = ? ORDER BY MapId"))
+            .bind(server_id)
+            .fetch_all(get_pool()),
+    )?)
+}
+
+/// Whether `id` may hold a static map; logs why not.
+fn static_slot_ok(id: usize) -> bool {
+    if id >= MAP_SLOTS {
+        tracing::warn!("[map] map_id={id} >= MAP_SLOTS={MAP_SLOTS}, skipping");
+        return false;
+    }
+    if crate::servers::map::instance::is_instance(id as u16) {
+        tracing::warn!("[map] map_id={id} is in the instance id range, skipping");
+        return false;
+    }
+    true
+}
+
+/// Copy a Maps row and its parsed tiles into `slot` with a fresh, empty
+/// registry. The slot's previous tile arrays and registry must already be
+/// freed; the block grid and warps are not touched.
+fn fill_slot(slot: &mut MapData, row: &MapRow, mut tiles: ParsedTiles) {
+    copy_str_to_fixed(&mut slot.title, &row.map_name);
+    copy_str_to_fixed(&mut slot.mapfile, &row.map_file);
+    copy_str_to_fixed(&mut slot.maprejectmsg, &row.map_reject_msg);
+    slot.id = row.map_id as c_int;
+    slot.bgm = row.map_bgm as c_ushort;
+    slot.bgmtype = row.map_bgm_type as c_ushort;
+    slot.pvp = row.map_pv_p as c_uchar;
+    slot.spell = row.map_spells as c_uchar;
+    slot.light = row.map_light as c_uchar;
+    slot.weather = row.map_weather as c_uchar;
+    slot.sweeptime = row.map_sweep_time;
+    slot.cantalk = row.map_chat as c_uchar;
+    slot.show_ghosts = row.map_ghosts as c_uchar;
+    slot.region = row.map_region as c_uchar;
+    slot.indoor = row.map_indoor as c_uchar;
+    slot.warpout = row.map_warpout as c_uchar;
+    slot.bind = row.map_bind as c_uchar;
+    slot.reqlvl = row.map_req_lvl as c_uint;
+    slot.reqpath = row.map_req_path as c_uchar;
+    slot.reqmark = row.map_req_mark as c_uchar;
+    slot.summon = row.map_can_summon as c_uchar;
+    slot.reqvita = row.map_req_vita;
+    slot.reqmana = row.map_req_mana;
+    slot.lvlmax = row.map_lvl_max;
+    slot.vitamax = row.map_vita_max;
+    slot.manamax = row.map_mana_max;
+    slot.can_use = row.map_can_use as c_uchar;
+    slot.can_eat = row.map_can_eat as c_uchar;
+    slot.can_smoke = row.map_can_smoke as c_uchar;
+    slot.can_mount = row.map_can_mount as c_uchar;
+    slot.can_group = row.map_can_group as c_uchar;
+    slot.can_equip = row.map_can_equip as c_uchar;
+
+    slot.xs = tiles.xs;
+    slot.ys = tiles.ys;
+    slot.bxs = tiles.bxs;
+    slot.bys = tiles.bys;
+    // Transfer ownership of tile arrays to the slot; null out tiles so
+    // ParsedTiles::drop does not double-free the transferred pointers.
+    slot.tile = std::mem::replace(&mut tiles.tile, std::ptr::null_mut());
+    slot.pass = std::mem::replace(&mut tiles.pass, std::ptr::null_mut());
+    slot.obj = std::mem::replace(&mut tiles.obj, std::ptr::null_mut());
+    slot.map = std::mem::replace(&mut tiles.map, std::ptr::null_mut());
+    slot.registry = alloc_zeroed_registry(MAX_MAPREG);
+}
+
+/// Free a slot's tile arrays and registry and null them. Dimensions, flags,
+/// the block grid and warps are left as they are.
+///
+/// # Safety
+/// The arrays must have been allocated by the loader or `clone_slot`.
+unsafe fn free_tiles(slot: &mut MapData) {
+    unsafe fn free_slice<T>(ptr: &mut *mut T, len: usize) {
+        if !ptr.is_null() {
+            drop(Vec::from_raw_parts(*ptr, len, len));
+            *ptr = std::ptr::null_mut();
+        }
+    }
+    let cells = slot.xs as usize * slot.ys as usize;
+    free_slice(&mut slot.tile, cells);
+    free_slice(&mut slot.pass, cells);
+    free_slice(&mut slot.obj, cells);
+    free_slice(&mut slot.map, cells);
+    if !slot.registry.is_null() {
+        let reg_layout = std::alloc::Layout::array::<GlobalReg>(MAX_MAPREG).unwrap();
+        std::alloc::dealloc(slot.registry as *mut u8, reg_layout);
+        slot.registry = std::ptr::null_mut();
+    }
+}
+
 /// Query the Maps table and populate map slots. Called once at startup.
 /// Returns the number of maps loaded, or an error.
 pub fn load_maps(
@@ -321,63 +468,7 @@ pub fn load_maps(
     server_id: i32,
     slots: &mut [MapData; MAP_SLOTS],
 ) -> Result<usize> {
-    // Types match DB schema (all INT UNSIGNED except MapReqLvl which is INT).
-    // All int(10) unsigned → u32; MapReqLvl int(10) → i32.
-    #[derive(sqlx::FromRow)]
-    struct MapRow {
-        map_id: u32,
-        map_name: String,
-        map_bgm: u32,
-        map_bgm_type: u32,
-        map_pv_p: u32,
-        map_spells: u32,
-        map_light: u32,
-        map_weather: u32,
-        map_sweep_time: u32,
-        map_chat: u32,
-        map_ghosts: u32,
-        map_region: u32,
-        map_indoor: u32,
-        map_warpout: u32,
-        map_bind: u32,
-        map_file: String,
-        map_req_lvl: i32,
-        map_req_path: u32,
-        map_req_mark: u32,
-        map_can_summon: u32,
-        map_req_vita: u32,
-        map_req_mana: u32,
-        map_lvl_max: u32,
-        map_vita_max: u32,
-        map_mana_max: u32,
-        map_reject_msg: String,
-        map_can_use: u32,
-        map_can_eat: u32,
-        map_can_smoke: u32,
-        map_can_mount: u32,
-        map_can_group: u32,
-        map_can_equip: u32,
-    }
-
-    let rows: Vec<MapRow> = blocking_run(
-        sqlx::query_as(
-            "SELECT MapId AS map_id, MapName AS map_name, MapBGM AS map_bgm,
-             MapBGMType AS map_bgm_type, MapPvP AS map_pv_p, MapSpells AS map_spells,
-             MapLight AS map_light, MapWeather AS map_weather, MapSweepTime AS map_sweep_time,
-             MapChat AS map_chat, MapGhosts AS map_ghosts, MapRegion AS map_region,
-             MapIndoor AS map_indoor, MapWarpout AS map_warpout, MapBind AS map_bind,
-             MapFile AS map_file, MapReqLvl AS map_req_lvl, MapReqPath AS map_req_path,
-             MapReqMark AS map_req_mark, MapCanSummon AS map_can_summon,
-             MapReqVita AS map_req_vita, MapReqMana AS map_req_mana, MapLvlMax AS map_lvl_max,
-             MapVitaMax AS map_vita_max, MapManaMax AS map_mana_max,
-             MapRejectMsg AS map_reject_msg, MapCanUse AS map_can_use, MapCanEat AS map_can_eat,
-             MapCanSmoke AS map_can_smoke, MapCanMount AS map_can_mount,
-             MapCanGroup AS map_can_group, MapCanEquip AS map_can_equip
-             FROM Maps WHERE MapServer = ? ORDER BY MapId",
-        )
-        .bind(server_id)
-        .fetch_all(get_pool()),
-    )?;
+    let rows = query_maps(server_id)?;
 
     // Phase 1: parse all .map files in parallel across rayon's thread pool.
     let parsed: Vec<(u32, Result<ParsedTiles>)> = rows
@@ -396,58 +487,12 @@ pub fn load_maps(
     let mut loaded = 0usize;
     for (row, (_, tiles_result)) in rows.iter().zip(parsed.into_iter()) {
         let id = row.map_id as usize;
-        if id >= MAP_SLOTS {
-            tracing::warn!("[map] map_id={id} >= MAP_SLOTS={MAP_SLOTS}, skipping");
+        if !static_slot_ok(id) {
             continue;
         }
-        let mut tiles = tiles_result.with_context(|| format!("loading map id={}", row.map_id))?;
+        let tiles = tiles_result.with_context(|| format!("loading map id={}", row.map_id))?;
         let slot = &mut slots[id];
-
-        copy_str_to_fixed(&mut slot.title, &row.map_name);
-        copy_str_to_fixed(&mut slot.mapfile, &row.map_file);
-        copy_str_to_fixed(&mut slot.maprejectmsg, &row.map_reject_msg);
-        slot.id = row.map_id as c_int;
-        slot.bgm = row.map_bgm as c_ushort;
-        slot.bgmtype = row.map_bgm_type as c_ushort;
-        slot.pvp = row.map_pv_p as c_uchar;
-        slot.spell = row.map_spells as c_uchar;
-        slot.light = row.map_light as c_uchar;
-        slot.weather = row.map_weather as c_uchar;
-        slot.sweeptime = row.map_sweep_time;
-        slot.cantalk = row.map_chat as c_uchar;
-        slot.show_ghosts = row.map_ghosts as c_uchar;
-        slot.region = row.map_region as c_uchar;
-        slot.indoor = row.map_indoor as c_uchar;
-        slot.warpout = row.map_warpout as c_uchar;
-        slot.bind = row.map_bind as c_uchar;
-        slot.reqlvl = row.map_req_lvl as c_uint;
-        slot.reqpath = row.map_req_path as c_uchar;
-        slot.reqmark = row.map_req_mark as c_uchar;
-        slot.summon = row.map_can_summon as c_uchar;
-        slot.reqvita = row.map_req_vita;
-        slot.reqmana = row.map_req_mana;
-        slot.lvlmax = row.map_lvl_max;
-        slot.vitamax = row.map_vita_max;
-        slot.manamax = row.map_mana_max;
-        slot.can_use = row.map_can_use as c_uchar;
-        slot.can_eat = row.map_can_eat as c_uchar;
-        slot.can_smoke = row.map_can_smoke as c_uchar;
-        slot.can_mount = row.map_can_mount as c_uchar;
-        slot.can_group = row.map_can_group as c_uchar;
-        slot.can_equip = row.map_can_equip as c_uchar;
-
-        slot.xs = tiles.xs;
-        slot.ys = tiles.ys;
-        slot.bxs = tiles.bxs;
-        slot.bys = tiles.bys;
-        // Transfer ownership of tile arrays to the slot; null out tiles so
-        // ParsedTiles::drop does not double-free the transferred pointers.
-        slot.tile = std::mem::replace(&mut tiles.tile, std::ptr::null_mut());
-        slot.pass = std::mem::replace(&mut tiles.pass, std::ptr::null_mut());
-        slot.obj = std::mem::replace(&mut tiles.obj, std::ptr::null_mut());
-        slot.map = std::mem::replace(&mut tiles.map, std::ptr::null_mut());
-        slot.registry = alloc_zeroed_registry(MAX_MAPREG);
-
+        fill_slot(slot, row, tiles);
         if let Some(regs) = registries.remove(&row.map_id) {
             apply_registry(slot, &regs);
         }
@@ -464,137 +509,421 @@ pub fn reload_maps(
     server_id: i32,
     slots: &mut [MapData; MAP_SLOTS],
 ) -> Result<usize> {
-    #[derive(sqlx::FromRow)]
-    struct MapRow {
-        map_id: u32,
-        map_name: String,
-        map_bgm: u32,
-        map_bgm_type: u32,
-        map_pv_p: u32,
-        map_spells: u32,
-        map_light: u32,
-        map_weather: u32,
-        map_sweep_time: u32,
-        map_chat: u32,
-        map_ghosts: u32,
-        map_region: u32,
-        map_indoor: u32,
-        map_warpout: u32,
-        map_bind: u32,
-        map_file: String,
-        map_req_lvl: i32,
-        map_req_path: u32,
-        map_req_mark: u32,
-        map_can_summon: u32,
-        map_req_vita: u32,
-        map_req_mana: u32,
-        map_lvl_max: u32,
-        map_vita_max: u32,
-        map_mana_max: u32,
-        map_reject_msg: String,
-        map_can_use: u32,
-        map_can_eat: u32,
-        map_can_smoke: u32,
-        map_can_mount: u32,
-        map_can_group: u32,
-        map_can_equip: u32,
-    }
-
-    let rows: Vec<MapRow> = blocking_run(
-        sqlx::query_as(
-            "SELECT MapId AS map_id, MapName AS map_name, MapBGM AS map_bgm,
-             MapBGMType AS map_bgm_type, MapPvP AS map_pv_p, MapSpells AS map_spells,
-             MapLight AS map_light, MapWeather AS map_weather, MapSweepTime AS map_sweep_time,
-             MapChat AS map_chat, MapGhosts AS map_ghosts, MapRegion AS map_region,
-             MapIndoor AS map_indoor, MapWarpout AS map_warpout, MapBind AS map_bind,
-             MapFile AS map_file, MapReqLvl AS map_req_lvl, MapReqPath AS map_req_path,
-             MapReqMark AS map_req_mark, MapCanSummon AS map_can_summon,
-             MapReqVita AS map_req_vita, MapReqMana AS map_req_mana, MapLvlMax AS map_lvl_max,
-             MapVitaMax AS map_vita_max, MapManaMax AS map_mana_max,
-             MapRejectMsg AS map_reject_msg, MapCanUse AS map_can_use, MapCanEat AS map_can_eat,
-             MapCanSmoke AS map_can_smoke, MapCanMount AS map_can_mount,
-             MapCanGroup AS map_can_group, MapCanEquip AS map_can_equip
-             FROM Maps WHERE MapServer = ? ORDER BY MapId",
-        )
-        .bind(server_id)
-        .fetch_all(get_pool()),
-    )?;
+    let rows = query_maps(server_id)?;
 
     for row in &rows {
         let id = row.map_id as usize;
-        if id >= MAP_SLOTS {
-            tracing::warn!("[map] map_id={id} >= MAP_SLOTS={MAP_SLOTS}, skipping");
+        if !static_slot_ok(id) {
             continue;
         }
         let slot = &mut slots[id];
 
         // Parse the map file first — on failure, leave the slot untouched.
         let path = format!("{}{}", maps_dir, row.map_file);
-        let mut tiles =
+        let tiles =
             parse_map_file(&path).with_context(|| format!("reloading map id={}", row.map_id))?;
 
         // Parse succeeded — now free the old tile arrays and registry.
-        if !slot.registry.is_null() {
-            let old_cells = slot.xs as usize * slot.ys as usize;
+        unsafe { free_tiles(slot) };
+        fill_slot(slot, row, tiles);
+        load_registry(slot, row.map_id)?;
+    }
+
+    Ok(rows.len())
+}
+
+/// Load one map (Maps row for this server, `.map` file, registry) into
+/// `slot`, replacing whatever tile data it held. Returns the previous
+/// `(xs, ys)`, zero if the slot was empty. On error the slot is untouched.
+/// The block grid and warps are not touched: callers rebuild them when the
+/// dimensions change.
+///
+/// # Safety
+/// The slot's arrays must be null or come from the loader.
+pub unsafe fn load_map(maps_dir: &str, server_id: i32, map_id: u16, slot: &mut MapData) -> Result<(u16, u16)> {
+    anyhow::ensure!(static_slot_ok(map_id as usize), "map id {map_id} can't hold a static map");
+    let row: Option<MapRow> = blocking_run(
+        sqlx::query_as(&format!("{MAP_SELECT} WHERE MapServer = ? AND MapId = ?"))
+            .bind(server_id)
+            .bind(map_id as u32)
+            .fetch_optional(get_pool()),
+    )?;
+    let row = row.with_context(|| format!("map id {map_id} is not in Maps for server {server_id}"))?;
+    let path = format!("{}{}", maps_dir, row.map_file);
+    let tiles = parse_map_file(&path).with_context(|| format!("loading map id={map_id}"))?;
+    anyhow::ensure!(tiles.xs > 0 && tiles.ys > 0, "map file {path} has no cells");
+
+    let old = (slot.xs, slot.ys);
+    free_tiles(slot);
+    fill_slot(slot, &row, tiles);
+    load_registry(slot, row.map_id)?;
+    Ok(old)
+}
+
+// ============================================
+// Instances
+// ============================================
+
+/// Heap copy of `len` elements at `ptr` (null stays null); freed like the
+/// loader's arrays, via `Vec::from_raw_parts(ptr, len, len)`.
+unsafe fn dup_slice<T: Copy>(ptr: *const T, len: usize) -> *mut T {
+    if ptr.is_null() {
+        return std::ptr::null_mut();
+    }
+    let mut v = std::slice::from_raw_parts(ptr, len).to_vec();
+    let p = v.as_mut_ptr();
+    std::mem::forget(v);
+    p
+}
+
+/// Fill the empty slot `dst` with a copy of `src` under map id `id`: same
+/// flags and metadata, private tile/walkability arrays and registry. The
+/// block grid and warps are left null for the caller to allocate.
+///
+/// # Safety
+/// `src` must be a loaded slot; `dst` must be empty (zeroed) or its arrays leak.
+pub unsafe fn clone_slot(src: &MapData, dst: &mut MapData, id: u16) {
+    let cells = src.xs as usize * src.ys as usize;
+    std::ptr::copy_nonoverlapping(src, dst, 1);
+    dst.id = id as c_int;
+    dst.user = 0;
+    dst.block = std::ptr::null_mut();
+    dst.block_mob = std::ptr::null_mut();
+    dst.warp = std::ptr::null_mut();
+    dst.tile = dup_slice(src.tile, cells);
+    dst.pass = dup_slice(src.pass, cells);
+    dst.obj = dup_slice(src.obj, cells);
+    dst.map = dup_slice(src.map, cells);
+    dst.registry = alloc_zeroed_registry(MAX_MAPREG);
+    if !src.registry.is_null() {
+        std::ptr::copy_nonoverlapping(src.registry, dst.registry, MAX_MAPREG);
+    }
+}
+
+/// Free a slot's tile arrays and registry and zero it, so it reads as not
+/// loaded again. The block grid must already have been released.
+///
+/// # Safety
+/// The slot's arrays must have been allocated by `clone_slot` or the loader.
+pub unsafe fn free_slot(slot: &mut MapData) {
+    free_tiles(slot);
+    std::ptr::write_bytes(slot as *mut MapData, 0, 1);
+}
+
+/// Copy every warp in `src`'s grid into `dst`'s (same dimensions). Warps
+/// that lead elsewhere on `src` itself (`tm == src_id`) are retargeted to
+/// `dst_id` so they stay inside the copy; the rest keep their destination,
+/// which is how players leave an instance on foot. Returns the count.
+///
+/// # Safety
+/// Both slots' `warp` arrays must hold `bxs * bys` valid chain heads, with
+/// `dst`'s chains empty.
+pub unsafe fn clone_warps(src: &MapData, dst: &mut MapData, src_id: u16, dst_id: u16) -> usize {
+    let cells = src.bxs as usize * src.bys as usize;
+    if src.warp.is_null() || dst.warp.is_null() {
+        return 0;
+    }
+    let mut n = 0;
+    for i in 0..cells {
+        let mut w = *src.warp.add(i);
+        while !w.is_null() {
+            let tm = if (*w).tm == src_id as c_int { dst_id as c_int } else { (*w).tm };
+            let head = *dst.warp.add(i);
+            let copy = Box::into_raw(Box::new(WarpList {
+                x: (*w).x, y: (*w).y, tm, tx: (*w).tx, ty: (*w).ty,
+                next: head, prev: std::ptr::null_mut(),
+            }));
+            if !head.is_null() {
+                (*head).prev = copy;
+            }
+            *dst.warp.add(i) = copy;
+            n += 1;
+            w = (*w).next;
+        }
+    }
+    n
+}
+
+/// Unlink and free every warp in `slot`'s grid that leads to map `target`.
+/// Returns the count.
+///
+/// # Safety
+/// The slot's `warp` array must be null or hold `bxs * bys` chain heads of
+/// `Box`ed nodes.
+pub unsafe fn drop_warps_to(slot: &mut MapData, target: u16) -> usize {
+    if slot.warp.is_null() {
+        return 0;
+    }
+    let mut n = 0;
+    for i in 0..slot.bxs as usize * slot.bys as usize {
+        let mut w = *slot.warp.add(i);
+        while !w.is_null() {
+            let next = (*w).next;
+            if (*w).tm == target as c_int {
+                if (*w).prev.is_null() {
+                    *slot.warp.add(i) = next;
+                } else {
+                    (*(*w).prev).next = next;
+                }
+                if !next.is_null() {
+                    (*next).prev = (*w).prev;
+                }
+                drop(Box::from_raw(w));
+                n += 1;
+            }
+            w = next;
+        }
+    }
+    n
+}
+
+// ============================================
+// Census
+// ============================================
+
+/// Object counts for one loaded map.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MapCensus {
+    pub map_id: u16,
+    pub players: u32,
+    pub mobs: u32,
+    pub npcs: u32,
+}
+
+/// Per-map counts for every loaded map that has anything on it.
+#[derive(Debug, Clone, Default)]
+pub struct CensusReport {
+    pub maps: Vec<MapCensus>,
+    /// The node budget ran out; maps after the last entry were not counted.
+    pub truncated: bool,
+}
+
+/// Block-list nodes a single census may visit before it stops.
+pub const CENSUS_NODE_BUDGET: usize = 500_000;
+
+const BL_NPC: c_uchar = 0x04;
+
+/// Count one map's mobs and npcs by walking its block grid. Players come
+/// from the `user` counter that map_addblock/map_delblock maintain.
+/// Decrements `budget` per node visited; returns None once it hits zero.
+///
+/// # Safety
+/// `slot`'s block arrays must be null or hold `bxs * bys` valid chain heads,
+/// and no other thread may mutate the chains during the walk.
+pub unsafe fn census_map(map_id: u16, slot: &MapData, budget: &mut usize) -> Option<MapCensus> {
+    let mut c = MapCensus { map_id, players: slot.user.max(0) as u32, ..Default::default() };
+    let cells = slot.bxs as usize * slot.bys as usize;
+    for pos in 0..cells {
+        if !slot.block.is_null() {
+            let mut bl = *slot.block.add(pos);
+            while !bl.is_null() {
+                *budget = budget.checked_sub(1)?;
+                if (*bl).bl_type == BL_NPC {
+                    c.npcs += 1;
+                }
+                bl = (*bl).next;
+            }
+        }
+        if !slot.block_mob.is_null() {
+            let mut bl = *slot.block_mob.add(pos);
+            while !bl.is_null() {
+                *budget = budget.checked_sub(1)?;
+                c.mobs += 1;
+                bl = (*bl).next;
+            }
+        }
+    }
+    Some(c)
+}
+
+/// Census of all loaded maps in `slots`, visiting at most `budget` nodes.
+/// Empty maps are left out of the report.
+///
+/// # Safety
+/// Same as [`census_map`], for every loaded slot.
+pub unsafe fn census(slots: &[MapData], mut budget: usize) -> CensusReport {
+    let mut report = CensusReport::default();
+    for (id, slot) in slots.iter().enumerate() {
+        if slot.registry.is_null() {
+            continue;
+        }
+        match census_map(id as u16, slot, &mut budget) {
+            Some(c) if c.players + c.mobs + c.npcs > 0 => report.maps.push(c),
+            Some(_) => {}
+            None => {
+                report.truncated = true;
+                break;
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod census_tests {
+    use super::*;
+
+    /// Map slots for one census, built on the heap and freed again on drop
+    /// so no test sees (or leaks) another's blocks.
+    #[derive(Default)]
+    struct World {
+        slots: Vec<MapData>,
+        nodes: Vec<*mut BlockList>,
+    }
+
+    impl World {
+        fn node(&mut self, bl_type: c_uchar, next: *mut BlockList) -> *mut BlockList {
+            let mut bl: BlockList = unsafe { std::mem::zeroed() };
+            bl.bl_type = bl_type;
+            bl.next = next;
+            let bl = Box::into_raw(Box::new(bl));
+            self.nodes.push(bl);
+            bl
+        }
+
+        /// A loaded 1x1-block map with `npcs` npcs and `mobs` mobs in its only cell.
+        fn add(&mut self, npcs: usize, mobs: usize, user: c_int) {
+            let mut slot: MapData = unsafe { std::mem::zeroed() };
+            slot.bxs = 1;
+            slot.bys = 1;
+            slot.user = user;
+            slot.registry = alloc_zeroed_registry(MAX_MAPREG);
+            let mut head = std::ptr::null_mut();
+            for _ in 0..npcs { head = self.node(BL_NPC, head); }
+            slot.block = Box::into_raw(Box::new(head));
+            let mut mhead = std::ptr::null_mut();
+            for _ in 0..mobs { mhead = self.node(0x02, mhead); }
+            slot.block_mob = Box::into_raw(Box::new(mhead));
+            self.slots.push(slot);
+        }
+
+        fn add_unloaded(&mut self) {
+            self.slots.push(unsafe { std::mem::zeroed() });
+        }
+    }
+
+    impl Drop for World {
+        fn drop(&mut self) {
             unsafe {
-                drop(Vec::from_raw_parts(slot.tile, old_cells, old_cells));
-                drop(Vec::from_raw_parts(slot.pass, old_cells, old_cells));
-                drop(Vec::from_raw_parts(slot.obj, old_cells, old_cells));
-                drop(Vec::from_raw_parts(slot.map, old_cells, old_cells));
-                let reg_layout = std::alloc::Layout::array::<GlobalReg>(MAX_MAPREG).unwrap();
-                std::alloc::dealloc(slot.registry as *mut u8, reg_layout);
+                for &bl in &self.nodes {
+                    drop(Box::from_raw(bl));
+                }
+                for slot in &mut self.slots {
+                    if !slot.block.is_null() { drop(Box::from_raw(slot.block)); }
+                    if !slot.block_mob.is_null() { drop(Box::from_raw(slot.block_mob)); }
+                    free_slot(slot);
+                }
             }
         }
+    }
 
-        copy_str_to_fixed(&mut slot.title, &row.map_name);
-        copy_str_to_fixed(&mut slot.mapfile, &row.map_file);
-        copy_str_to_fixed(&mut slot.maprejectmsg, &row.map_reject_msg);
-        slot.id = row.map_id as c_int;
-        slot.bgm = row.map_bgm as c_ushort;
-        slot.bgmtype = row.map_bgm_type as c_ushort;
-        slot.pvp = row.map_pv_p as c_uchar;
-        slot.spell = row.map_spells as c_uchar;
-        slot.light = row.map_light as c_uchar;
-        slot.weather = row.map_weather as c_uchar;
-        slot.sweeptime = row.map_sweep_time;
-        slot.cantalk = row.map_chat as c_uchar;
-        slot.show_ghosts = row.map_ghosts as c_uchar;
-        slot.region = row.map_region as c_uchar;
-        slot.indoor = row.map_indoor as c_uchar;
-        slot.warpout = row.map_warpout as c_uchar;
-        slot.bind = row.map_bind as c_uchar;
-        slot.reqlvl = row.map_req_lvl as c_uint;
-        slot.reqpath = row.map_req_path as c_uchar;
-        slot.reqmark = row.map_req_mark as c_uchar;
-        slot.summon = row.map_can_summon as c_uchar;
-        slot.reqvita = row.map_req_vita;
-        slot.reqmana = row.map_req_mana;
-        slot.lvlmax = row.map_lvl_max;
-        slot.vitamax = row.map_vita_max;
-        slot.manamax = row.map_mana_max;
-        slot.can_use = row.map_can_use as c_uchar;
-        slot.can_eat = row.map_can_eat as c_uchar;
-        slot.can_smoke = row.map_can_smoke as c_uchar;
-        slot.can_mount = row.map_can_mount as c_uchar;
-        slot.can_group = row.map_can_group as c_uchar;
-        slot.can_equip = row.map_can_equip as c_uchar;
-
-        slot.xs = tiles.xs;
-        slot.ys = tiles.ys;
-        slot.bxs = tiles.bxs;
-        slot.bys = tiles.bys;
-        // Transfer ownership of tile arrays to the slot; null out tiles so
-        // ParsedTiles::drop does not double-free the transferred pointers.
-        slot.tile = std::mem::replace(&mut tiles.tile, std::ptr::null_mut());
-        slot.pass = std::mem::replace(&mut tiles.pass, std::ptr::null_mut());
-        slot.obj = std::mem::replace(&mut tiles.obj, std::ptr::null_mut());
-        slot.map = std::mem::replace(&mut tiles.map, std::ptr::null_mut());
-        slot.registry = alloc_zeroed_registry(MAX_MAPREG);
+    #[test]
+    fn test_census_counts_and_skips_empty() {
+        let mut w = World::default();
+        w.add(2, 3, 1);
+        w.add(0, 0, 0);
+        w.add_unloaded();
+        let r = unsafe { census(&w.slots, CENSUS_NODE_BUDGET) };
+        assert!(!r.truncated);
+        assert_eq!(r.maps, vec![MapCensus { map_id: 0, players: 1, mobs: 3, npcs: 2 }]);
+    }
 
-        load_registry(slot, row.map_id)?;
+    #[test]
+    fn test_census_stops_at_budget() {
+        let mut w = World::default();
+        w.add(4, 4, 0);
+        let r = unsafe { census(&w.slots, 5) };
+        assert!(r.truncated);
+        assert!(r.maps.is_empty());
     }
+}
 
-    Ok(rows.len())
+#[cfg(test)]
+mod instance_tests {
+    use super::*;
+
+    #[test]
+    fn test_clone_slot_is_deep_and_free_slot_unloads() {
+        let mut base: MapData = unsafe { std::mem::zeroed() };
+        base.xs = 2;
+        base.ys = 2;
+        base.pvp = 1;
+        base.user = 5;
+        base.pass = alloc_zeroed_slice::<c_ushort>(4);
+        base.map = alloc_zeroed_slice::<c_uchar>(4);
+        base.registry = alloc_zeroed_registry(MAX_MAPREG);
+        unsafe { *base.pass.add(3) = 7 };
+
+        let mut inst: MapData = unsafe { std::mem::zeroed() };
+        unsafe { clone_slot(&base, &mut inst, 60000) };
+        assert_eq!((inst.id, inst.pvp, inst.user), (60000, 1, 0));
+        assert!(inst.tile.is_null() && inst.block.is_null());
+        assert_ne!(inst.pass, base.pass);
+        unsafe {
+            assert_eq!(*inst.pass.add(3), 7);
+            *inst.pass.add(3) = 0;
+            assert_eq!(*base.pass.add(3), 7);
+            free_slot(&mut inst);
+            free_slot(&mut base);
+        }
+        assert!(inst.registry.is_null() && inst.xs == 0);
+    }
+
+    #[test]
+    fn test_clone_warps_retargets_internal_warps() {
+        let out = Box::into_raw(Box::new(WarpList {
+            x: 1, y: 1, tm: 3, tx: 9, ty: 9, next: std::ptr::null_mut(), prev: std::ptr::null_mut(),
+        }));
+        let inner = Box::into_raw(Box::new(WarpList {
+            x: 2, y: 2, tm: 7, tx: 0, ty: 0, next: out, prev: std::ptr::null_mut(),
+        }));
+        let mut src: MapData = unsafe { std::mem::zeroed() };
+        src.bxs = 1;
+        src.bys = 1;
+        src.warp = Box::into_raw(Box::new(inner));
+        let mut dst: MapData = unsafe { std::mem::zeroed() };
+        dst.warp = Box::into_raw(Box::new(std::ptr::null_mut::<WarpList>()));
+
+        assert_eq!(unsafe { clone_warps(&src, &mut dst, 7, 60001) }, 2);
+        let mut tms = Vec::new();
+        let mut w = unsafe { *dst.warp };
+        while !w.is_null() {
+            tms.push(unsafe { (*w).tm });
+            w = unsafe { (*w).next };
+        }
+        tms.sort();
+        assert_eq!(tms, vec![3, 60001]);
+    }
+
+    #[test]
+    fn test_drop_warps_to_unlinks_only_matching() {
+        let tail = Box::into_raw(Box::new(WarpList {
+            x: 3, y: 3, tm: 5, tx: 0, ty: 0, next: std::ptr::null_mut(), prev: std::ptr::null_mut(),
+        }));
+        let mid = Box::into_raw(Box::new(WarpList {
+            x: 2, y: 2, tm: 8, tx: 0, ty: 0, next: tail, prev: std::ptr::null_mut(),
+        }));
+        let head = Box::into_raw(Box::new(WarpList {
+            x: 1, y: 1, tm: 5, tx: 0, ty: 0, next: mid, prev: std::ptr::null_mut(),
+        }));
+        unsafe {
+            (*mid).prev = head;
+            (*tail).prev = mid;
+        }
+        let mut slot: MapData = unsafe { std::mem::zeroed() };
+        slot.bxs = 1;
+        slot.bys = 1;
+        slot.warp = Box::into_raw(Box::new(head));
+
+        assert_eq!(unsafe { drop_warps_to(&mut slot, 5) }, 2);
+        let w = unsafe { *slot.warp };
+        assert_eq!(w, mid);
+        unsafe {
+            assert!((*w).prev.is_null() && (*w).next.is_null());
+            drop(Box::from_raw(w));
+            drop(Box::from_raw(slot.warp));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -85,6 +85,10 @@ pub struct MobDbData {
     pub startm: c_ushort,
     pub startx: c_ushort,
     pub starty: c_ushort,
+    /// Flee below this percent of maxvita (0 = leave escape to Lua).
+    pub flee_pct: c_uchar,
+    /// Faction for targeting rules (`servers::map::faction`; 255 = wild).
+    pub faction: c_uchar,
 }
 
 unsafe impl Send for MobDbData {}
@@ -100,6 +104,7 @@ fn make_default(id: u32) -> Box<MobDbData> {
     // SAFETY: all-zero is a valid initial state for this repr(C) struct.
     let mut m: Box<MobDbData> = unsafe { Box::new(std::mem::zeroed()) };
     m.id = id;
+    m.faction = crate::servers::map::faction::WILD;
     str_to_fixed(&mut m.name, "??");
     m
 }
@@ -150,6 +155,8 @@ fn make_default(id: u32) -> Box<MobDbData> {
 //   32 MobMark        u8   → mark
 //   33 MobIsNpc       u8   → isnpc
 //   34 MobIsBoss      u8   → isboss
+//   35 MobFleePercent u8   → flee_pct
+//   36 MobFaction     u8   → faction
 //
 // MobEquipment columns: MeqLook→item.id, MeqColor→item.custom, MeqSlot→pos (index into equip[])
 async fn load_mobs() -> Result<usize, sqlx::Error> {
@@ -163,7 +170,7 @@ async fn load_mobs() -> Result<usize, sqlx::Error> {
          `MobReturnDistance`, `MobSex`, `MobFace`, `MobFaceColor`, \
          `MobHair`, `MobHairColor`, `MobSkinColor`, `MobState`, \
          `MobIsChar`, `MobWill`, `MobMinimumDamage`, `MobMaximumDamage`, \
-         `MobMark`, `MobIsNpc`, `MobIsBoss` FROM `Mobs`",
+         `MobMark`, `MobIsNpc`, `MobIsBoss`, `MobFleePercent`, `MobFaction` FROM `Mobs`",
     )
     .fetch_all(pool)
     .await?;
@@ -211,6 +218,8 @@ async fn load_mobs() -> Result<usize, sqlx::Error> {
         m.mark       = row.try_get::<u32, _>(32).unwrap_or(0) as c_uchar;
         m.isnpc      = row.try_get::<u32, _>(33).unwrap_or(0) as c_uchar;
         m.isboss     = row.try_get::<u32, _>(34).unwrap_or(0) as c_uchar;
+        m.flee_pct   = row.try_get::<u32, _>(35).unwrap_or(0).min(100) as c_uchar;
+        m.faction    = row.try_get::<u32, _>(36).unwrap_or(crate::servers::map::faction::WILD as u32).min(255) as c_uchar;
 
         if m.mobtype == 1 {
             let eq_rows = sqlx::query(
@@ -312,7 +321,7 @@ mod layout_tests {
     #[test]
     fn mob_db_data_size() {
         // Calculated from C struct layout
-        assert_eq!(std::mem::size_of::<MobDbData>(), 13408,
+        assert_eq!(std::mem::size_of::<MobDbData>(), 13412,
             "MobDbData size mismatch — check field ordering vs map_server.h");
     }
 }
@@ -1,7 +1,11 @@
 use std::future::Future;
 use std::sync::OnceLock;
+use std::time::Duration;
 
+use sqlx::mysql::MySqlPoolOptions;
 use sqlx::MySqlPool;
+
+use crate::config::ServerConfig;
 use tokio::runtime::Runtime;
 
 pub mod board_db;
@@ -11,6 +15,7 @@ pub mod item_db;
 pub mod magic_db;
 pub mod map_db;
 pub mod mob_db;
+pub mod name_index;
 pub mod recipe_db;
 
 static DB_POOL: OnceLock<MySqlPool> = OnceLock::new();
@@ -31,30 +36,199 @@ pub(crate) fn get_pool() -> &'static MySqlPool {
     DB_POOL.get().expect("[db] pool not initialized — rust_db_connect() must be called first")
 }
 
+/// The shared pool, if `connect` or `set_pool` has run.
+pub(crate) fn try_pool() -> Option<&'static MySqlPool> {
+    DB_POOL.get()
+}
+
 pub(crate) fn blocking_run<F: Future>(f: F) -> F::Output {
     get_runtime().block_on(f)
 }
 
+/// Pool sizing and timeouts (`db_max_connections`, `db_acquire_timeout_ms`,
+/// `db_idle_timeout_ms`). The defaults are sqlx's own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolSettings {
+    pub max_connections: u32,
+    /// How long a query waits for a free connection before failing
+    pub acquire_timeout: Duration,
+    /// Idle connections are closed after this; None keeps them
+    pub idle_timeout: Option<Duration>,
+}
+
+impl PoolSettings {
+    pub const DEFAULT: Self = Self {
+        max_connections: 10,
+        acquire_timeout: Duration::from_secs(30),
+        idle_timeout: Some(Duration::from_secs(600)),
+    };
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            max_connections: config.db_max_connections,
+            acquire_timeout: Duration::from_millis(config.db_acquire_timeout_ms),
+            idle_timeout: (config.db_idle_timeout_ms > 0).then(|| Duration::from_millis(config.db_idle_timeout_ms)),
+        }
+    }
+
+    pub fn options(&self) -> MySqlPoolOptions {
+        MySqlPoolOptions::new()
+            .max_connections(self.max_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+    }
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl std::fmt::Display for PoolSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "max_connections={} acquire_timeout={}ms idle_timeout={}ms",
+            self.max_connections,
+            self.acquire_timeout.as_millis(),
+            self.idle_timeout.map_or(0, |d| d.as_millis())
+        )
+    }
+}
+
+/// Longest wait between database connect attempts.
+pub const CONNECT_RETRY_CAP: Duration = Duration::from_secs(30);
+
+/// Retry schedule for `attempts` tries starting `base_delay` apart, doubling
+/// up to `CONNECT_RETRY_CAP`.
+fn retry_policy(attempts: u32, base_delay: Duration) -> crate::session::ReconnectPolicy {
+    crate::session::ReconnectPolicy {
+        initial: base_delay,
+        max: CONNECT_RETRY_CAP.max(base_delay),
+        max_attempts: attempts.max(1),
+        ..crate::session::ReconnectPolicy::DEFAULT
+    }
+}
+
+/// Open a pool with `options`, trying up to `attempts` times (at least once)
+/// with exponential backoff from `base_delay`. Each retry is logged; the
+/// last error is returned once attempts run out.
+pub async fn connect_pool_with_retry(
+    options: MySqlPoolOptions,
+    url: &str,
+    attempts: u32,
+    base_delay: Duration,
+) -> Result<MySqlPool, sqlx::Error> {
+    let policy = retry_policy(attempts, base_delay);
+    let mut attempt = 0u32;
+    loop {
+        attempt += 1;
+        let err = match options.clone().connect(url).await {
+            Ok(pool) => return Ok(pool),
+            Err(e) => e,
+        };
+        match policy.delay(attempt) {
+            Some(wait) => {
+                tracing::warn!(
+                    "[db] connect failed (attempt {}/{}): {}; retrying in {}ms",
+                    attempt, policy.max_attempts, err, wait.as_millis()
+                );
+                tokio::time::sleep(wait).await;
+            }
+            None => return Err(err),
+        }
+    }
+}
+
+/// Open the pool for `config`'s database with its pool settings and connect
+/// retries, for the async server binaries.
+pub async fn open_pool(config: &ServerConfig) -> Result<MySqlPool, sqlx::Error> {
+    let url = format!(
+        "mysql://{}:{}@{}:{}/{}",
+        config.sql_id, config.sql_pw, config.sql_ip, config.sql_port, config.sql_db
+    );
+    let settings = PoolSettings::from_config(config);
+    let pool = connect_pool_with_retry(
+        settings.options(),
+        &url,
+        config.sql_connect_attempts,
+        Duration::from_millis(config.sql_connect_retry_ms),
+    )
+    .await?;
+    tracing::info!("[db] Connected to MariaDB ({})", settings);
+    Ok(pool)
+}
+
 /// Connect to the database. Called from ffi::database::rust_db_connect.
 ///
 /// Returns an error if the pool is already initialized or if the connection fails.
 pub fn connect(url: &str) -> Result<(), sqlx::Error> {
+    connect_with_retry(url, 1, Duration::ZERO)
+}
+
+/// [`connect`], retrying up to `attempts` times with backoff from
+/// `base_delay` (see [`connect_pool_with_retry`]). Waits on `DB_RUNTIME`.
+pub fn connect_with_retry(url: &str, attempts: u32, base_delay: Duration) -> Result<(), sqlx::Error> {
     if DB_POOL.get().is_some() {
         return Err(sqlx::Error::Configuration(
             "database pool already initialized".into(),
         ));
     }
-    let pool = blocking_run(MySqlPool::connect(url))?;
+    #[cfg(not(test))]
+    let settings = crate::ffi::config::try_config().map(PoolSettings::from_config).unwrap_or_default();
+    #[cfg(test)]
+    let settings = PoolSettings::default();
+    let pool = blocking_run(connect_pool_with_retry(settings.options(), url, attempts, base_delay))?;
     // set() only fails if another thread raced us; drop the new pool and return an error.
     if DB_POOL.set(pool).is_err() {
         return Err(sqlx::Error::Configuration(
             "database pool already initialized".into(),
         ));
     }
-    tracing::info!("[db] Connected to MariaDB");
+    tracing::info!("[db] Connected to MariaDB ({})", settings);
     Ok(())
 }
 
+/// Run `SELECT 1` on `pool`. A connection that went stale (say, after a
+/// MariaDB restart) fails the query and is closed by sqlx rather than
+/// returned to the pool; the check is retried once per idle connection so a
+/// single pass flushes them all. Returns false if the database is still
+/// unreachable.
+pub async fn ensure_healthy_pool(pool: &MySqlPool) -> bool {
+    let tries = pool.num_idle() + 1;
+    for attempt in 1..=tries {
+        match sqlx::query("SELECT 1").execute(pool).await {
+            Ok(_) if attempt == 1 => return true,
+            Ok(_) => {
+                tracing::info!("[db] [health] recovered after {} stale connection(s)", attempt - 1);
+                return true;
+            }
+            Err(e) => tracing::warn!("[db] [health] ping failed ({}/{}): {}", attempt, tries, e),
+        }
+    }
+    false
+}
+
+/// [`ensure_healthy_pool`] on the shared pool; true if none is registered.
+pub async fn ensure_healthy() -> bool {
+    match DB_POOL.get() {
+        Some(pool) => ensure_healthy_pool(pool).await,
+        None => true,
+    }
+}
+
+/// Ping `pool` every `every` (see [`ensure_healthy_pool`]) so a database
+/// bounce heals within one interval instead of surfacing as failed saves.
+pub async fn run_health_checks(pool: MySqlPool, every: Duration) {
+    let mut ticker = tokio::time::interval(every);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        ensure_healthy_pool(&pool).await;
+    }
+}
+
 /// Register an already-connected pool (for use from async Rust binaries that
 /// create their own pool before calling C FFI init functions).
 /// Avoids the `block_on`-inside-runtime panic that `connect()` would cause.
@@ -67,3 +241,41 @@ pub fn set_pool(pool: MySqlPool) -> Result<(), sqlx::Error> {
     tracing::info!("[db] Pool registered from async context");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connect_retries_with_backoff_then_fails() {
+        let url = crate::servers::testing::UNREACHABLE_DB_URL;
+        let started = std::time::Instant::now();
+        let opts = MySqlPoolOptions::new().acquire_timeout(Duration::from_millis(100));
+        assert!(connect_pool_with_retry(opts, url, 3, Duration::from_millis(20)).await.is_err());
+        // Waits of 20ms then 40ms between the three attempts.
+        assert!(started.elapsed() >= Duration::from_millis(60));
+
+        let p = retry_policy(0, Duration::from_secs(20));
+        assert_eq!((p.max_attempts, p.delay(1)), (1, None));
+        assert_eq!(retry_policy(9, Duration::from_secs(20)).delay(3), Some(CONNECT_RETRY_CAP));
+    }
+
+    #[tokio::test]
+    async fn test_health_check_reports_unreachable_database() {
+        let pool = crate::servers::testing::unreachable_pool();
+        assert!(!ensure_healthy_pool(&pool).await);
+        // Nothing registered in tests: the shared check is a no-op.
+        assert!(ensure_healthy().await);
+    }
+
+    #[test]
+    fn test_pool_settings_from_config() {
+        let config = crate::servers::testing::test_config();
+        assert_eq!(PoolSettings::from_config(&config), PoolSettings::DEFAULT);
+        let tuned = ServerConfig { db_max_connections: 32, db_idle_timeout_ms: 0, ..config };
+        let s = PoolSettings::from_config(&tuned);
+        assert_eq!((s.max_connections, s.idle_timeout), (32, None));
+        assert_eq!(s.to_string(), "max_connections=32 acquire_timeout=30000ms idle_timeout=0ms");
+        assert_eq!(s.options().get_max_connections(), 32);
+    }
+}
@@ -0,0 +1,84 @@
+//! Case-insensitive name → id index for the item and recipe caches.
+//!
+//! `ITEM("name")`/`RECIPE("name")` resolve through `searchname`, which used to
+//! scan every entry. The caches build one of these after each load. When two
+//! entries share a name the lowest id keeps it, which is stable across loads
+//! (a hash-map scan wasn't), and each collision is reported for logging.
+
+use std::collections::HashMap;
+
+/// `name` was claimed by `kept` and also by `dropped`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    pub name: String,
+    pub kept: u32,
+    pub dropped: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct NameIndex {
+    ids: HashMap<String, u32>,
+}
+
+impl NameIndex {
+    /// Index every non-empty name of every entry. An entry listing the same
+    /// name twice (e.g. identical name and yname) is not a duplicate.
+    pub fn build<'a, N>(entries: impl IntoIterator<Item = (u32, N)>) -> (Self, Vec<Duplicate>)
+    where
+        N: IntoIterator<Item = &'a str>,
+    {
+        let mut ids: HashMap<String, u32> = HashMap::new();
+        let mut claims: Vec<(String, u32)> = Vec::new();
+        for (id, names) in entries {
+            for name in names {
+                if !name.is_empty() {
+                    claims.push((name.to_lowercase(), id));
+                }
+            }
+        }
+        claims.sort_by_key(|c| c.1);
+        let mut dupes = Vec::new();
+        for (name, id) in claims {
+            match ids.get(&name) {
+                Some(&kept) if kept != id => dupes.push(Duplicate { name, kept, dropped: id }),
+                Some(_) => {}
+                None => {
+                    ids.insert(name, id);
+                }
+            }
+        }
+        (Self { ids }, dupes)
+    }
+
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.ids.get(&name.to_lowercase()).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_is_case_insensitive_and_lowest_id_wins() {
+        let (idx, dupes) = NameIndex::build([
+            (9, vec!["Iron Sword", "iron_sword"]),
+            (3, vec!["IRON SWORD", ""]),
+            (5, vec!["Apple", "apple"]),
+        ]);
+        assert_eq!(idx.get("iron sword"), Some(3));
+        assert_eq!(idx.get("Iron_Sword"), Some(9));
+        assert_eq!(idx.get("APPLE"), Some(5));
+        assert_eq!(idx.get(""), None);
+        assert_eq!(dupes, vec![Duplicate { name: "iron sword".into(), kept: 3, dropped: 9 }]);
+        assert_eq!(idx.len(), 3);
+    }
+}
@@ -7,7 +7,8 @@ use std::sync::{Mutex, OnceLock};
 use sqlx::Row;
 
 use super::{blocking_run, get_pool};
-use super::item_db::str_to_fixed;
+use super::item_db::{fixed_str, str_to_fixed};
+use super::name_index::NameIndex;
 
 #[repr(C)]
 pub struct RecipeData {
@@ -37,6 +38,25 @@ fn db() -> &'static Mutex<HashMap<u32, Box<RecipeData>>> {
     RECIPE_DB.get().expect("[recipe_db] not initialized")
 }
 
+/// identifier/description (and crit variants) → id, rebuilt after every load.
+static RECIPE_NAMES: Mutex<Option<NameIndex>> = Mutex::new(None);
+
+fn rebuild_name_index(map: &HashMap<u32, Box<RecipeData>>) {
+    let owned: Vec<(u32, Vec<String>)> = map.iter().map(|(&id, r)| {
+        let names = [&r.identifier, &r.description, &r.crit_identifier, &r.crit_description]
+            .into_iter()
+            .map(|a| fixed_str(a))
+            .filter(|n| n != "??")
+            .collect();
+        (id, names)
+    }).collect();
+    let (index, dupes) = NameIndex::build(owned.iter().map(|(id, n)| (*id, n.iter().map(String::as_str))));
+    for d in &dupes {
+        tracing::warn!("[recipe_db] name {:?} used by recipes {} and {}; lookups get {}", d.name, d.kept, d.dropped, d.kept);
+    }
+    *RECIPE_NAMES.lock().unwrap() = Some(index);
+}
+
 fn make_default(id: u32) -> Box<RecipeData> {
     let mut r = Box::new(RecipeData {
         id: id as c_int,
@@ -100,6 +120,7 @@ async fn load_recipes() -> Result<usize, sqlx::Error> {
         r.superior_materials[0] = row.try_get::<u32, _>(22).unwrap_or(0) as c_int;
         r.superior_materials[1] = row.try_get::<u32, _>(23).unwrap_or(0) as c_int;
     }
+    rebuild_name_index(&map);
     Ok(count)
 }
 
@@ -117,6 +138,7 @@ pub fn term() {
     if let Some(m) = RECIPE_DB.get() {
         m.lock().unwrap().clear();
     }
+    *RECIPE_NAMES.lock().unwrap() = None;
 }
 
 /// Returns a raw pointer to the `RecipeData` for `id`, inserting a default entry if absent.
@@ -141,20 +163,12 @@ pub fn searchexist(id: u32) -> *mut RecipeData {
     }
 }
 
+/// Look up by identifier or description, plain or crit (case-insensitive).
 pub fn searchname(s: *const c_char) -> *mut RecipeData {
     if s.is_null() { return null_mut(); }
-    let target = unsafe { CStr::from_ptr(s) }.to_string_lossy().to_lowercase();
-    let map = db().lock().unwrap();
-    for r in map.values() {
-        // SAFETY: str_to_fixed always writes a NUL at index `len` (≤ N-1), so these
-        // fixed-size arrays are guaranteed to be NUL-terminated within their bounds.
-        let ident = unsafe { CStr::from_ptr(r.identifier.as_ptr()) }.to_string_lossy().to_lowercase();
-        let desc = unsafe { CStr::from_ptr(r.description.as_ptr()) }.to_string_lossy().to_lowercase();
-        let crit_ident = unsafe { CStr::from_ptr(r.crit_identifier.as_ptr()) }.to_string_lossy().to_lowercase();
-        let crit_desc = unsafe { CStr::from_ptr(r.crit_description.as_ptr()) }.to_string_lossy().to_lowercase();
-        if ident == target || desc == target || crit_ident == target || crit_desc == target {
-            return r.as_ref() as *const RecipeData as *mut RecipeData;
-        }
+    let target = unsafe { CStr::from_ptr(s) }.to_string_lossy();
+    match RECIPE_NAMES.lock().unwrap().as_ref().and_then(|idx| idx.get(&target)) {
+        Some(id) => searchexist(id),
+        None => null_mut(),
     }
-    null_mut()
 }
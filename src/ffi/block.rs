@@ -8,7 +8,7 @@
 use std::os::raw::{c_int, c_ushort};
 use std::ptr;
 
-use crate::database::map_db::{BlockList, WarpList, MAP_SLOTS, BLOCK_SIZE};
+use crate::database::map_db::{BlockList, MapData, WarpList, MAP_SLOTS, BLOCK_SIZE};
 use crate::ffi::map_db::map;
 
 const BL_MOB: u8 = 0x02;
@@ -47,13 +47,81 @@ pub unsafe extern "C" fn map_initblock() {
         if slot.bxs == 0 || slot.bys == 0 {
             continue; // sparse slot — not a loaded map
         }
-        let cells = slot.bxs as usize * slot.bys as usize;
-        slot.block     = alloc_ptr_array::<BlockList>(cells);
-        slot.block_mob = alloc_ptr_array::<BlockList>(cells);
-        slot.warp      = alloc_ptr_array::<WarpList>(cells);
+        alloc_grid(slot);
     }
 }
 
+/// Allocate empty block/block_mob/warp arrays for one slot.
+pub fn alloc_grid(slot: &mut MapData) {
+    let cells = slot.bxs as usize * slot.bys as usize;
+    slot.block     = alloc_ptr_array::<BlockList>(cells);
+    slot.block_mob = alloc_ptr_array::<BlockList>(cells);
+    slot.warp      = alloc_ptr_array::<WarpList>(cells);
+}
+
+/// True when no block-list node is linked into any cell of `slot`.
+///
+/// # Safety
+/// The slot's block arrays must be null or hold `bxs * bys` chain heads.
+pub unsafe fn grid_is_empty(slot: &MapData) -> bool {
+    let cells = slot.bxs as usize * slot.bys as usize;
+    (0..cells).all(|i| {
+        (slot.block.is_null() || (*slot.block.add(i)).is_null())
+            && (slot.block_mob.is_null() || (*slot.block_mob.add(i)).is_null())
+    })
+}
+
+/// Every node linked into `slot`'s block and mob grids.
+///
+/// # Safety
+/// The slot's block arrays must be null or hold `bxs * bys` chain heads.
+pub unsafe fn grid_entries(slot: &MapData) -> Vec<*mut BlockList> {
+    let cells = slot.bxs as usize * slot.bys as usize;
+    let mut out = Vec::new();
+    for heads in [slot.block, slot.block_mob] {
+        if heads.is_null() {
+            continue;
+        }
+        for i in 0..cells {
+            let mut bl = *heads.add(i);
+            while !bl.is_null() {
+                out.push(bl);
+                bl = (*bl).next;
+            }
+        }
+    }
+    out
+}
+
+/// Free one slot's grid arrays and its warp chains; the inverse of
+/// `alloc_grid`. The block chains must already be empty.
+///
+/// # Safety
+/// Arrays must come from `alloc_grid`/`map_initblock` and warps from `Box`.
+pub unsafe fn free_grid(slot: &mut MapData) {
+    let cells = slot.bxs as usize * slot.bys as usize;
+    if !slot.warp.is_null() {
+        for i in 0..cells {
+            let mut w = *slot.warp.add(i);
+            while !w.is_null() {
+                let next = (*w).next;
+                drop(Box::from_raw(w));
+                w = next;
+            }
+        }
+        drop(Vec::from_raw_parts(slot.warp, cells, cells));
+    }
+    if !slot.block.is_null() {
+        drop(Vec::from_raw_parts(slot.block, cells, cells));
+    }
+    if !slot.block_mob.is_null() {
+        drop(Vec::from_raw_parts(slot.block_mob, cells, cells));
+    }
+    slot.block = ptr::null_mut();
+    slot.block_mob = ptr::null_mut();
+    slot.warp = ptr::null_mut();
+}
+
 /// Free block grid arrays for all map slots.
 /// Replaces `map_termblock()` in `map_server.c`. Currently a no-op (matches C).
 ///
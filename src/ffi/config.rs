@@ -8,13 +8,18 @@ use std::ffi::{CStr, CString};
 use std::net::Ipv4Addr;
 use std::os::raw::{c_char, c_int};
 use std::ptr;
-use std::sync::OnceLock;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
 
 use crate::config::{Point, ServerConfig};
 
-/// Global config instance
-/// OnceLock = thread-safe, can only be set once, lazy initialization
-static CONFIG: OnceLock<ServerConfig> = OnceLock::new();
+/// Global config instance, set by rust_config_read and replaced by `reload`.
+/// Each version is leaked so callers can keep handing out `&'static`
+/// references; reloads are operator-driven and rare.
+static CONFIG: RwLock<Option<&'static ServerConfig>> = RwLock::new(None);
+
+/// File rust_config_read loaded, re-read by `reload`
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
 
 /// Load configuration from file (C-compatible entry point)
 ///
@@ -47,10 +52,15 @@ pub unsafe extern "C" fn rust_config_read(cfg_file: *const c_char) -> c_int {
             println!("[rust_config_read] Successfully loaded config from: {}", file_path);
 
             // Store in global
-            if CONFIG.set(config).is_err() {
-                eprintln!("[rust_config_read] Error: Config already loaded");
-                return -1;
+            {
+                let mut slot = CONFIG.write().unwrap_or_else(|e| e.into_inner());
+                if slot.is_some() {
+                    eprintln!("[rust_config_read] Error: Config already loaded");
+                    return -1;
+                }
+                *slot = Some(Box::leak(Box::new(config)));
             }
+            let _ = CONFIG_PATH.set(PathBuf::from(file_path));
 
             // Automatically populate C global variables
             unsafe {
@@ -69,12 +79,34 @@ pub unsafe extern "C" fn rust_config_read(cfg_file: *const c_char) -> c_int {
 /// Get a reference to the loaded config
 /// Returns None if config hasn't been loaded yet
 fn get_config() -> Option<&'static ServerConfig> {
-    CONFIG.get()
+    *CONFIG.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Re-read the file rust_config_read loaded and make it current, keeping
+/// restart-only settings (see `ServerConfig::keep_restart_only`) and
+/// refreshing the C globals. Returns the new config and the ignored keys;
+/// on error the running config stays in place.
+pub fn reload() -> anyhow::Result<(&'static ServerConfig, Vec<&'static str>)> {
+    let (Some(path), Some(running)) = (CONFIG_PATH.get(), get_config()) else {
+        anyhow::bail!("config was never loaded");
+    };
+    let mut next = crate::config::reload_from_path(Path::new(path))?;
+    let ignored = next.keep_restart_only(running);
+    let next: &'static ServerConfig = Box::leak(Box::new(next));
+    *CONFIG.write().unwrap_or_else(|e| e.into_inner()) = Some(next);
+    unsafe { rust_config_populate_c_globals(); }
+    Ok((next, ignored))
+}
+
+/// Non-panicking accessor for code that also runs in processes that never
+/// call rust_config_read (e.g. the session layer under rust_server_run).
+pub fn try_config() -> Option<&'static ServerConfig> {
+    get_config()
 }
 
 /// Public accessor for the loaded config — used by game modules (e.g. scripting).
 pub fn config() -> &'static ServerConfig {
-    CONFIG.get().expect("config not loaded — rust_config_read must be called first")
+    get_config().expect("config not loaded — rust_config_read must be called first")
 }
 
 //
@@ -435,6 +467,14 @@ pub unsafe extern "C" fn rust_config_populate_c_globals() {
             save_time = (config.save_time * 1000) as c_int;  // Convert to milliseconds
             xp_rate = config.xprate as c_int;
             d_rate = config.droprate as c_int;
+            crate::servers::map::rates::set(config.exp_rate, config.drop_rate);
+            crate::servers::map::faction::install(&config.faction_relations);
+            crate::servers::map::newbie::install(config.newbie_policy());
+            crate::servers::map::movement::install(config.move_policy());
+            crate::servers::map::tick_budget::install(std::time::Duration::from_millis(config.mob_tick_budget_ms));
+            if let Some(seed) = config.rng_seed {
+                crate::rng::seed(seed);
+            }
 
             // Meta files
             metamax = config.meta.len().min(20) as c_int;
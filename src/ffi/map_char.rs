@@ -2,10 +2,10 @@
 // needs to send packets to char_server. The Rust map_server binary sets MAP_STATE
 // after startup via set_map_state(). Before that, calls are silently dropped.
 
-use std::ffi::c_char;
+use std::ffi::{c_char, c_void};
 use std::sync::{Arc, OnceLock};
 use tokio::runtime::Handle;
-use crate::servers::map::{MapState, packet};
+use crate::servers::map::{MapState, packet, presence, resume};
 
 static MAP_STATE: OnceLock<Arc<MapState>> = OnceLock::new();
 
@@ -28,21 +28,66 @@ pub fn call_intif_mmo_tosd(fd: i32, raw: &mut Vec<u8>) -> i32 {
     }
 }
 
+// Function pointer set by map_server.rs at startup: reports a 0x3812 save-now
+// ack back to whoever requested it (e.g. the GM who ran /savenow).
+static SAVENOW_ACK_FN: OnceLock<unsafe extern "C" fn(i32, u32, u8)> = OnceLock::new();
+
+/// Called by map_server.rs main() to register the save-now ack reporter.
+pub fn set_savenow_ack_fn(f: unsafe extern "C" fn(i32, u32, u8)) {
+    let _ = SAVENOW_ACK_FN.set(f);
+}
+
+/// Forward a save-now ack to the registered reporter.
+/// No-ops if none was registered (non-map_server binaries).
+pub fn call_savenow_ack(requester_fd: i32, char_id: u32, result: u8) {
+    if let Some(f) = SAVENOW_ACK_FN.get() {
+        unsafe { f(requester_fd, char_id, result) }
+    }
+}
+
+// Function pointer set by map_server.rs at startup: runs the normal
+// disconnect path for a parked player whose resume window has passed,
+// given the eof code of the original drop.
+static RESUME_EXPIRE_FN: OnceLock<unsafe extern "C" fn(*mut c_void, i32) -> i32> = OnceLock::new();
+
+/// Called by map_server.rs main() to register the parked-player logout.
+pub fn set_resume_expire_fn(f: unsafe extern "C" fn(*mut c_void, i32) -> i32) {
+    let _ = RESUME_EXPIRE_FN.set(f);
+}
+
 /// Called by map_server.rs main() after MapState is constructed.
 pub fn set_map_state(state: Arc<MapState>) {
     let _ = MAP_STATE.set(state);
 }
 
+/// Swap a reloaded config into MapState, if it is registered yet.
+pub fn set_config(config: Arc<crate::config::ServerConfig>) {
+    if let Some(state) = MAP_STATE.get() {
+        state.config.set(config);
+    }
+}
+
 /// Send raw bytes to char_server via the Rust channel.
 fn send(data: Vec<u8>) {
+    use crate::servers::map::char::SENDS_IN_FLIGHT;
+    use std::sync::atomic::Ordering;
     if let Some(state) = MAP_STATE.get() {
         let s = Arc::clone(state);
         if let Ok(handle) = Handle::try_current() {
-            handle.spawn(async move { packet::send_to_char(&s, data).await; });
+            SENDS_IN_FLIGHT.fetch_add(1, Ordering::AcqRel);
+            handle.spawn(async move {
+                packet::send_to_char(&s, data).await;
+                SENDS_IN_FLIGHT.fetch_sub(1, Ordering::AcqRel);
+            });
         }
     }
 }
 
+/// 0x3001 — Re-send this server's map list after maps were added or removed.
+pub fn send_mapset() {
+    send(packet::mapset_packet(&packet::loaded_map_ids()));
+}
+
 /// 0x3003 — Request char data (map→char, 24 bytes).
 /// C: intif_load(fd, id, name) — replaces WFIFOW/WFIFOSET dance.
 ///
@@ -71,12 +116,56 @@ pub unsafe extern "C" fn rust_intif_load(fd: i32, char_id: u32, name: *const c_c
 ///   [2..6] = char_id (u32 LE)
 #[no_mangle]
 pub unsafe extern "C" fn rust_intif_quit(char_id: u32) {
+    if let Some(state) = MAP_STATE.get() {
+        state.presence.lock().unwrap().forget(char_id);
+    }
+    crate::servers::map::movement::forget(char_id);
     let mut pkt = vec![0u8; 6];
     pkt[0] = 0x05; pkt[1] = 0x30; // 0x3005 LE
     pkt[2..6].copy_from_slice(&char_id.to_le_bytes());
     send(pkt);
 }
 
+/// 0x3014 — `char_id` was placed on map `m`; sent only when the map differs
+/// from the last one reported (see `servers::map::presence`).
+pub fn notify_map(char_id: u32, m: u16) {
+    let Some(state) = MAP_STATE.get() else { return };
+    let seq = state.presence.lock().unwrap().note(char_id, m);
+    if let Some(seq) = seq {
+        send(presence::build_map_change(char_id, m, seq));
+    }
+}
+
+/// 0x3012 — Disconnect reason (map→char, 7 bytes).
+/// C: clif_handle_disconnect(sd) sends this just before intif_savequit so the
+/// char server can log why the player went offline.
+///
+/// Layout:
+///   [0..2] = 0x3012 cmd (LE)
+///   [2..6] = char_id (u32 LE)
+///   [6]    = DisconnectReason (session eof code)
+#[no_mangle]
+pub unsafe extern "C" fn rust_intif_disconnect(char_id: u32, eof: i32) {
+    let reason = crate::session::DisconnectReason::from_eof(eof);
+    let mut pkt = vec![0u8; 7];
+    pkt[0] = 0x12; pkt[1] = 0x30; // 0x3012 LE
+    pkt[2..6].copy_from_slice(&char_id.to_le_bytes());
+    pkt[6] = reason as u8;
+    send(pkt);
+}
+
+/// Write the 8-byte charstatus wire header into `out`, which C places
+/// before `sd->status` when compressing it for 0x3004/0x3007.
+///
+/// # Safety
+/// `out` must be null or valid for `CHARSTATUS_HEADER_LEN` (8) byte writes.
+#[no_mangle]
+pub unsafe extern "C" fn rust_charstatus_header(out: *mut u8) {
+    if out.is_null() { return; }
+    let h = crate::servers::char::charstatus::char_status_header();
+    std::ptr::copy_nonoverlapping(h.as_ptr(), out, h.len());
+}
+
 /// 0x3004 — Save char (map→char, variable — zlib-compressed mmo_charstatus).
 /// C: intif_save(sd) — C already does zlib compress2; passes raw packet bytes here.
 ///
@@ -107,6 +196,92 @@ pub unsafe extern "C" fn rust_intif_savequit(data: *const u8, len: u32) {
     send(pkt);
 }
 
+/// Map id to put in `last_pos` for a character on map `m`. Instance ids are
+/// replaced by their base map so a save or logout inside an instance never
+/// strands the character on a map no server owns.
+#[no_mangle]
+pub extern "C" fn rust_instance_save_map(m: u16) -> u16 {
+    crate::servers::map::instance::save_map(m)
+}
+
+/// 0x3011 — Save-now request (map→char, variable — compressed mmo_charstatus).
+/// Asks char_server to persist one character immediately and ack with 0x3812.
+///
+/// `status` points to a raw mmo_charstatus of `len` bytes; `requester_fd` is
+/// echoed back in the ack. See `packet::build_save_now` for the layout.
+#[no_mangle]
+pub unsafe extern "C" fn rust_intif_savenow(requester_fd: i32, status: *const u8, len: u32) {
+    if status.is_null() || len < 4 { return; }
+    let raw = std::slice::from_raw_parts(status, len as usize);
+    send(packet::build_save_now(crate::ffi::config::config().charstatus_codec, requester_fd as u16, raw));
+}
+
+/// Park a player whose socket just died instead of logging them out.
+///
+/// Returns the resume token, or 0 when the caller should run the normal
+/// disconnect (resume disabled, or `eof` is not a transient drop). On a
+/// non-zero return C must detach `sd` from its fd and keep it alive until
+/// `rust_resume_take` hands it back or the expire callback runs.
+/// `client_ip` is as returned by `rust_session_get_client_ip`.
+#[no_mangle]
+pub extern "C" fn rust_resume_park(sd: *mut c_void, char_id: u32, client_ip: u32, eof: i32) -> u32 {
+    let Some(state) = MAP_STATE.get() else { return 0 };
+    let grace = state.config.get().resume_grace_secs;
+    if sd.is_null() || grace == 0
+        || !resume::is_transient(crate::session::DisconnectReason::from_eof(eof))
+    {
+        return 0;
+    }
+    let ip = u32::from_be(client_ip);
+    let token = state.resume.lock().unwrap()
+        .park(char_id, ip, sd as usize, eof, std::time::Duration::from_secs(grace as u64));
+    tracing::info!("[map] [resume] parked char_id={} token={:08X} grace={}s", char_id, token, grace);
+    send(resume::build_resume_hold(char_id, token, ip, grace));
+    token
+}
+
+/// Reclaim a parked player for a new connection from `client_ip`.
+/// Returns the parked `USER*`, or null to fall back to a normal load.
+#[no_mangle]
+pub extern "C" fn rust_resume_take(char_id: u32, client_ip: u32) -> *mut c_void {
+    let Some(state) = MAP_STATE.get() else { return std::ptr::null_mut() };
+    match state.resume.lock().unwrap().take(char_id, u32::from_be(client_ip)) {
+        Some(p) => {
+            tracing::info!("[map] [resume] resumed char_id={} token={:08X}", char_id, p.token);
+            p.sd as *mut c_void
+        }
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Timer callback (1s): log out parked players whose window has passed.
+#[no_mangle]
+pub extern "C" fn rust_resume_tick(_id: i32, _data: i32) -> i32 {
+    let Some(state) = MAP_STATE.get() else { return 0 };
+    let expired = state.resume.lock().unwrap().drain_expired(std::time::Instant::now());
+    for (char_id, p) in expired {
+        tracing::info!("[map] [resume] expired char_id={} token={:08X}", char_id, p.token);
+        if let Some(f) = RESUME_EXPIRE_FN.get() {
+            unsafe { f(p.sd as *mut c_void, p.eof); }
+        }
+    }
+    0
+}
+
+/// Log out every parked player now (server shutdown), so they are saved
+/// with everyone else. Returns how many there were.
+pub fn expire_all_parked() -> usize {
+    let Some(state) = MAP_STATE.get() else { return 0 };
+    let parked = state.resume.lock().unwrap().drain_all();
+    for (char_id, p) in &parked {
+        tracing::info!("[map] [resume] shutdown expired char_id={} token={:08X}", char_id, p.token);
+        if let Some(f) = RESUME_EXPIRE_FN.get() {
+            unsafe { f(p.sd as *mut c_void, p.eof); }
+        }
+    }
+    parked.len()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,6 +75,48 @@ pub unsafe extern "C" fn rust_map_reload(maps_dir: *const c_char, server_id: c_i
     })
 }
 
+/// How long a world census is reused before it is recomputed.
+const CENSUS_TTL: std::time::Duration = std::time::Duration::from_secs(2);
+
+static CENSUS_CACHE: std::sync::Mutex<Option<(std::time::Instant, std::sync::Arc<db::CensusReport>)>> =
+    std::sync::Mutex::new(None);
+
+/// Per-map player/mob/npc counts for the whole world, cached for CENSUS_TTL.
+/// Must be called on the game thread (walks the C-owned block grid).
+pub fn world_census() -> std::sync::Arc<db::CensusReport> {
+    let mut cache = CENSUS_CACHE.lock().unwrap();
+    if let Some((at, report)) = cache.as_ref() {
+        if at.elapsed() < CENSUS_TTL {
+            return report.clone();
+        }
+    }
+    let report = unsafe {
+        if map.is_null() {
+            db::CensusReport::default()
+        } else {
+            db::census(std::slice::from_raw_parts(map, MAP_SLOTS), db::CENSUS_NODE_BUDGET)
+        }
+    };
+    if report.truncated {
+        tracing::warn!("[map] census stopped at node budget after {} maps", report.maps.len());
+    }
+    let report = std::sync::Arc::new(report);
+    *cache = Some((std::time::Instant::now(), report.clone()));
+    report
+}
+
+/// Counts for a single map, computed fresh (bounded by the map's own size).
+pub fn map_census(id: u16) -> Option<db::MapCensus> {
+    unsafe {
+        let mp = get_map_ptr(id);
+        if mp.is_null() || (*mp).registry.is_null() {
+            return None;
+        }
+        let mut budget = db::CENSUS_NODE_BUDGET;
+        db::census_map(id, &*mp, &mut budget)
+    }
+}
+
 /// Returns a raw pointer to the MapData slot for `id`, or null if out of range.
 pub unsafe fn get_map_ptr(id: u16) -> *mut MapData {
     if map.is_null() || id as usize >= MAP_SLOTS {
@@ -121,3 +163,46 @@ pub unsafe extern "C" fn rust_map_loadregistry(map_id: c_int) -> c_int {
         }
     })
 }
+
+/// Create a private copy of loaded map `base` in the instance id range:
+/// walkability, tiles, flags, registry and warps are copied, the block grid
+/// starts empty. Returns the instance id, or None if `base` is not a loaded
+/// static map or the range is full.
+///
+/// # Safety
+/// Game thread only; the `map` array must be initialized.
+pub unsafe fn create_instance_slot(base: u16) -> Option<u16> {
+    use crate::servers::map::instance::{is_instance, INSTANCES};
+    if is_instance(base) || !map_is_loaded(base) || (*get_map_ptr(base)).registry.is_null() {
+        return None;
+    }
+    let id = INSTANCES.lock().unwrap().alloc(base)?;
+    let src = &*get_map_ptr(base);
+    let dst = &mut *get_map_ptr(id);
+    db::clone_slot(src, dst, id);
+    crate::ffi::block::alloc_grid(dst);
+    let warps = db::clone_warps(src, dst, base, id);
+    tracing::info!("[map] [instance] created id={id} base={base} warps={warps}");
+    Some(id)
+}
+
+/// Free instance `id`'s slot. Refuses (returns false) while anything is
+/// still linked into its block grid; callers evict and despawn first.
+///
+/// # Safety
+/// Game thread only; nothing may hold pointers into the instance's arrays.
+pub unsafe fn destroy_instance_slot(id: u16) -> bool {
+    use crate::servers::map::instance::INSTANCES;
+    let mut table = INSTANCES.lock().unwrap();
+    let Some(base) = table.base_of(id) else { return false };
+    let slot = &mut *get_map_ptr(id);
+    if !crate::ffi::block::grid_is_empty(slot) {
+        tracing::warn!("[map] [instance] destroy id={id} refused: map not empty");
+        return false;
+    }
+    crate::ffi::block::free_grid(slot);
+    db::free_slot(slot);
+    table.release(id);
+    tracing::info!("[map] [instance] destroyed id={id} base={base}");
+    true
+}
@@ -8,6 +8,37 @@ pub unsafe extern "C" fn rust_mobspawn_read() -> c_int {
     g::mobspawn_read()
 }
 
+/// Copy `table` into `buf` NUL-terminated; 0 on success, -1 if absent or too long.
+unsafe fn copy_table_name(table: Option<String>, buf: *mut c_char, len: c_int) -> c_int {
+    match table {
+        Some(t) if !buf.is_null() && t.len() < len.max(0) as usize => {
+            std::ptr::copy_nonoverlapping(t.as_ptr() as *const c_char, buf, t.len());
+            *buf.add(t.len()) = 0;
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Table spawn `spn_id` was loaded from, for `SpnLastDeath` updates.
+/// Returns -1 (leave `buf` alone) before the first `mobspawn_read`.
+///
+/// # Safety
+/// `buf` must be null or valid for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rust_spawn_table_for(spn_id: c_uint, buf: *mut c_char, len: c_int) -> c_int {
+    copy_table_name(crate::servers::map::spawn_shards::table_for(spn_id), buf, len)
+}
+
+/// Table new spawns are inserted into (the first configured source).
+///
+/// # Safety
+/// `buf` must be null or valid for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rust_spawn_primary_table(buf: *mut c_char, len: c_int) -> c_int {
+    copy_table_name(crate::servers::map::spawn_shards::primary_table(), buf, len)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn rust_mob_timer_spawns(id: c_int, n: c_int) -> c_int {
     g::mob_timer_spawns(id, n)
@@ -27,6 +27,7 @@ pub mod magic_db;
 pub mod map_db;
 pub mod mob_db;
 pub mod recipe_db;
+pub mod server_state;
 pub mod session;
 pub mod timer;
 // NPC FFI bridge — only compile when map-game feature is enabled.
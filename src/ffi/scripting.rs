@@ -34,6 +34,39 @@ pub unsafe extern "C" fn rust_sl_doscript_blargs_vec(
     ffi_catch!(0, sl::sl_doscript_blargs_vec(root, method, nargs, args))
 }
 
+/// Fire an event-bus event whose arguments are all block lists.
+#[no_mangle]
+pub unsafe extern "C" fn rust_sl_emit_blargs(
+    event: *const c_char,
+    nargs: c_int,
+    args:  *const *mut c_void,
+) -> c_int {
+    ffi_catch!(0, {
+        let Ok(name) = std::ffi::CStr::from_ptr(event).to_str() else { return 0 };
+        let bls = if nargs <= 0 || args.is_null() { &[][..] } else { std::slice::from_raw_parts(args, nargs as usize) };
+        sl::events::emit_bl(name, bls, &[]) as c_int
+    })
+}
+
+/// Offer an unrecognised client frame (`len` bytes, decrypted) to the packet
+/// handler for its opcode. 1 if one consumed it.
+#[no_mangle]
+pub unsafe extern "C" fn rust_sl_packet_hook(sd: *mut c_void, frame: *const u8, len: c_int) -> c_int {
+    ffi_catch!(0, {
+        if sd.is_null() || frame.is_null() || len <= 0 { return 0; }
+        sl::packets::dispatch(sd, std::slice::from_raw_parts(frame, len as usize)) as c_int
+    })
+}
+
+/// Fire `mobKill` for `mob` with kill credits from its threat table.
+#[no_mangle]
+pub unsafe extern "C" fn rust_sl_emit_mob_kill(mob: *mut c_void, killer: *mut c_void) -> c_int {
+    ffi_catch!(0, {
+        if mob.is_null() { return 0; }
+        sl::events::emit_mob_kill(mob as *mut crate::game::mob::MobSpawnData, killer) as c_int
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn rust_sl_doscript_strings_vec(
     root:   *const c_char,
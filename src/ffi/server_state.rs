@@ -0,0 +1,41 @@
+//! FFI bridge for the server-state store (C game registry wrappers).
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+use crate::database::{blocking_run, get_pool};
+use crate::servers::map::server_state;
+
+/// Load this server's state at boot. Returns the number of keys, or 0 after
+/// logging on failure (the store then starts empty and is never saved).
+#[no_mangle]
+pub extern "C" fn rust_server_state_load() -> c_int {
+    ffi_catch!(0, {
+        let server_id = crate::ffi::config::config().server_id;
+        match blocking_run(server_state::load(get_pool(), server_id)) {
+            Ok(n) => n as c_int,
+            Err(e) => {
+                tracing::error!("[map] [server_state] load failed, changes will not be saved: {e:#}");
+                0
+            }
+        }
+    })
+}
+
+/// # Safety
+/// `key` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rust_server_state_get(key: *const c_char) -> c_int {
+    if key.is_null() { return 0; }
+    let key = CStr::from_ptr(key).to_string_lossy();
+    ffi_catch!(0, server_state::get(&key).clamp(c_int::MIN as i64, c_int::MAX as i64) as c_int)
+}
+
+/// # Safety
+/// `key` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rust_server_state_set(key: *const c_char, val: c_int) -> c_int {
+    if key.is_null() { return 0; }
+    let key = CStr::from_ptr(key).to_string_lossy();
+    ffi_catch!(0, { server_state::set(&key, val as i64); 0 })
+}
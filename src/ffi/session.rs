@@ -18,7 +18,7 @@
 use std::sync::Arc;
 use std::os::raw::c_int;
 use tokio::sync::Mutex;
-use crate::session::{init_runtime, run_async_server, Session};
+use crate::session::{init_runtime, run_async_server, Session, SessionKind};
 
 /// Called by C's session.c to register the fd_max update function.
 /// Rust calls this callback whenever a new session is created so that
@@ -161,7 +161,10 @@ pub extern "C" fn rust_make_connection(ip: u32, port: c_int) -> c_int {
         }
     };
 
-    let mut session = Session::new(fd);
+    // The only server C dials is char_server.
+    let kind = SessionKind::CharLink;
+    let mut session = Session::with_kind(fd, kind, manager.buffer_sizes(kind.role()));
+    session.set_write_cap(manager.write_cap(kind.role()));
     session.client_addr = Some(addr);
     // Store in network byte order — same value C passed in, ready to return via get_client_ip
     session.client_addr_raw = ip;
@@ -283,6 +286,26 @@ pub extern "C" fn rust_session_available(fd: c_int) -> usize {
     with_session(fd, 0, |session| session.available())
 }
 
+/// Committed-but-unflushed bytes in the write buffer; 0 for an unknown fd.
+/// Check against `rust_session_write_high_water()` before a large write and
+/// back off while above it.
+#[no_mangle]
+pub extern "C" fn rust_session_write_pressure(fd: c_int) -> usize {
+    with_session(fd, 0, |session| session.write_pressure())
+}
+
+/// The session's `SessionKind` as its u8 value, or -1 if there is no such session.
+#[no_mangle]
+pub extern "C" fn rust_session_get_kind(fd: c_int) -> c_int {
+    with_session(fd, -1, |session| session.kind as c_int)
+}
+
+/// The recommended high-water mark for `rust_session_write_pressure`.
+#[no_mangle]
+pub extern "C" fn rust_session_write_high_water() -> usize {
+    crate::session::WRITE_HIGH_WATER
+}
+
 /// Commit write buffer (like WFIFOSET).
 /// Returns 0 on success, -1 on error.
 #[no_mangle]
@@ -394,6 +417,14 @@ pub unsafe extern "C" fn rust_session_set_default_timeout(
     manager.default_callbacks.lock().unwrap().timeout = Some(callback);
 }
 
+/// Override the idle timeout for sessions accepted on listener `fd` (as
+/// returned by `rust_make_listen_port`); `secs` 0 disables it there.
+#[no_mangle]
+pub extern "C" fn rust_session_set_listen_idle_timeout(fd: c_int, secs: c_int) {
+    let manager = crate::session::get_session_manager();
+    manager.set_listener_idle_timeout(fd, Some(std::time::Duration::from_secs(secs.max(0) as u64)));
+}
+
 /// Set default shutdown callback.
 ///
 /// # Safety
@@ -407,6 +438,20 @@ pub unsafe extern "C" fn rust_session_set_default_shutdown(
     manager.default_callbacks.lock().unwrap().shutdown = Some(callback);
 }
 
+/// Set default closed callback, called after shutdown with the fd and its
+/// `DisconnectReason` code.
+///
+/// # Safety
+/// Callback must be a valid C function pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rust_session_set_default_closed(
+    callback: unsafe extern "C" fn(c_int, c_int),
+) {
+    tracing::info!("[FFI] Setting default closed callback");
+    let manager = crate::session::get_session_manager();
+    manager.default_callbacks.lock().unwrap().closed = Some(callback);
+}
+
 /// Get session_data pointer (opaque void* for C).
 #[no_mangle]
 pub extern "C" fn rust_session_get_data(fd: c_int) -> *mut std::ffi::c_void {
@@ -423,6 +468,18 @@ pub extern "C" fn rust_session_set_data(fd: c_int, data: *mut std::ffi::c_void)
     });
 }
 
+/// Queue the same `len` bytes on every live session. Returns how many
+/// sessions could not take them (see `SessionManager::broadcast`).
+///
+/// # Safety
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rust_session_broadcast_all(data: *const u8, len: c_int) -> c_int {
+    if data.is_null() || len <= 0 { return 0; }
+    let payload = std::slice::from_raw_parts(data, len as usize);
+    crate::session::get_session_manager().broadcast_all(payload).len() as c_int
+}
+
 /// Get session eof flag.
 #[no_mangle]
 pub extern "C" fn rust_session_get_eof(fd: c_int) -> c_int {
@@ -437,6 +494,21 @@ pub extern "C" fn rust_session_set_eof(fd: c_int, eof: c_int) {
     });
 }
 
+/// Mark the session as logging out on the client's request (see
+/// `Session::quitting`).
+#[no_mangle]
+pub extern "C" fn rust_session_set_quitting(fd: c_int) {
+    with_session(fd, (), |session| {
+        session.quitting = true;
+    });
+}
+
+/// 1 if the client asked to log out on this session.
+#[no_mangle]
+pub extern "C" fn rust_session_get_quitting(fd: c_int) -> c_int {
+    with_session(fd, 0, |session| session.quitting as c_int)
+}
+
 /// Get client IP address as u32 (network byte order, matches sin_addr.s_addr).
 #[no_mangle]
 pub extern "C" fn rust_session_get_client_ip(fd: c_int) -> u32 {
@@ -458,6 +530,33 @@ pub extern "C" fn rust_session_increment(fd: c_int) -> u8 {
     })
 }
 
+/// Run the session's cipher over the frame at `buff` (`outgoing` 1 = encrypt,
+/// 0 = decrypt). Returns 0 when the session has no cipher of its own, in
+/// which case the caller falls back to `tk_crypt_static`.
+///
+/// # Safety
+/// `buff` must point at a whole frame whose length is in bytes 1..3.
+#[no_mangle]
+pub unsafe extern "C" fn rust_session_crypt(fd: c_int, buff: *mut u8, outgoing: c_int) -> c_int {
+    if buff.is_null() {
+        return 0;
+    }
+    let Some(cipher) = with_session(fd, None, |session| session.cipher.clone()) else {
+        return 0;
+    };
+    let total = ((*buff.add(1) as usize) << 8) | (*buff.add(2) as usize);
+    if total < 5 || total > crate::session::RFIFO_SIZE {
+        return 1;
+    }
+    let buf = std::slice::from_raw_parts_mut(buff, total);
+    if outgoing != 0 {
+        cipher.encrypt_packet(buf);
+    } else {
+        cipher.decrypt_packet(buf);
+    }
+    1
+}
+
 /// Check if session exists (returns 1 if exists, 0 if not).
 #[no_mangle]
 pub extern "C" fn rust_session_exists(fd: c_int) -> c_int {
@@ -493,6 +592,20 @@ pub unsafe extern "C" fn rust_session_set_shutdown(
     });
 }
 
+/// Override the closed callback for a specific session.
+///
+/// # Safety
+/// Callback must be a valid C function pointer.
+#[no_mangle]
+pub unsafe extern "C" fn rust_session_set_closed(
+    fd: c_int,
+    callback: unsafe extern "C" fn(c_int, c_int),
+) {
+    with_session(fd, (), |session| {
+        session.callbacks.closed = Some(callback);
+    });
+}
+
 /// Call the parse callback for a session.
 #[no_mangle]
 pub extern "C" fn rust_session_call_parse(fd: c_int) {
@@ -555,6 +668,23 @@ pub extern "C" fn rust_connect_check_clear(_id: c_int, _data: c_int) -> c_int {
     crate::network::ddos::connect_check_clear()
 }
 
+/// Timer callback: shrink the session, throttle and DDoS tables if a spike
+/// left them mostly empty.
+#[no_mangle]
+pub extern "C" fn rust_compact_tables(_id: c_int, _data: c_int) -> c_int {
+    let tables = [
+        ("sessions", crate::session::get_session_manager().compact()),
+        ("throttle", crate::network::throttle::compact()),
+        ("ddos", crate::network::ddos::compact()),
+    ];
+    for (name, shrunk) in tables {
+        if let Some((old, new)) = shrunk {
+            tracing::info!("[session] [compact] {name} capacity {old} -> {new}");
+        }
+    }
+    0
+}
+
 /// Record a throttled connection attempt from an IP.
 ///
 /// `ip` is in network byte order (sin_addr.s_addr), as returned by
@@ -564,9 +694,17 @@ pub extern "C" fn rust_add_throttle(ip: u32) {
     crate::network::throttle::add_throttle(ip);
 }
 
+/// Clear one IP's throttle entry. Returns 1 if an entry was removed, else 0.
+///
+/// `ip` is in network byte order (sin_addr.s_addr).
+#[no_mangle]
+pub extern "C" fn rust_unthrottle(ip: u32) -> c_int {
+    crate::network::throttle::unthrottle(ip) as c_int
+}
+
 /// Timer callback: reset all throttle counts.
 ///
-/// Registered with timer_insert at server startup (interval 10 min).
+/// Registered with timer_insert at server startup (interval `throttle_reset_secs`).
 /// Signature matches C's `int (*func)(int, int)`.
 #[no_mangle]
 pub extern "C" fn rust_remove_throttle(_id: c_int, _data: c_int) -> c_int {
@@ -34,3 +34,105 @@ extern "C" {
         data: c_int,
     ) -> c_int;
 }
+
+extern "C" {
+    /// Mark a timer for deletion; it is freed the next time it reaches the
+    /// head of the heap (or after its callback returns, if it is running).
+    /// Returns -1 for a stale handle whose slot has been freed or reused.
+    pub fn timer_remove(tid: c_int) -> c_int;
+
+    /// Raw timer slot (not handle), or null past the end of the timer table.
+    fn get_timer(slot: c_int) -> *const TimerData;
+}
+
+/// Mirror of `struct TimerData` in c_deps/timer.h.
+#[repr(C)]
+struct TimerData {
+    tick: u32,
+    func: Option<unsafe extern "C" fn(c_int, c_int) -> c_int>,
+    kind: c_int,
+    interval: u32,
+    heap_pos: c_int,
+    /// Handle of the timer currently occupying the slot.
+    id: c_int,
+    data1: c_int,
+    data2: c_int,
+}
+
+const TIMER_INTERVAL: c_int = 0x02;
+/// Low bits of a handle that index the slot (`TIMER_SLOT_MASK` in timer.h).
+const TIMER_SLOT_MASK: c_int = (1 << 20) - 1;
+
+/// Snapshot of one live timer, as returned by [`list`].
+#[derive(Debug, Clone)]
+pub struct TimerInfo {
+    pub id: i32,
+    pub interval: u32,
+    /// Milliseconds until the next fire; negative if it is overdue.
+    pub next_fire: i32,
+    /// Callback symbol name, or its address when the symbol is not exported.
+    pub source: String,
+    pub data1: i32,
+    pub data2: i32,
+}
+
+/// A slot is live while it has a callback and has not been handed to
+/// `timer_remove` (which resets the type to `TIMER_ONCE_AUTODEL`).
+unsafe fn live_slot(slot: c_int) -> Option<&'static TimerData> {
+    let t = get_timer(slot).as_ref()?;
+    (t.func.is_some() && t.kind & TIMER_INTERVAL != 0).then_some(t)
+}
+
+/// The live timer behind handle `tid`, if the handle is still current.
+unsafe fn live_timer(tid: c_int) -> Option<&'static TimerData> {
+    if tid < 0 {
+        return None;
+    }
+    live_slot(tid & TIMER_SLOT_MASK).filter(|t| t.id == tid)
+}
+
+fn callback_name(func: unsafe extern "C" fn(c_int, c_int) -> c_int) -> String {
+    let addr = func as *const libc::c_void;
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    if unsafe { libc::dladdr(addr, &mut info) } != 0 && !info.dli_sname.is_null() {
+        return unsafe { std::ffi::CStr::from_ptr(info.dli_sname) }.to_string_lossy().into_owned();
+    }
+    format!("{:p}", addr)
+}
+
+/// All live timers, soonest first. Must be called from the map server thread
+/// that runs `timer_do`; the C timer table is not synchronised.
+pub fn list() -> Vec<TimerInfo> {
+    let now = unsafe { gettick() };
+    let mut out = Vec::new();
+    let mut slot = 0;
+    while !unsafe { get_timer(slot) }.is_null() {
+        if let Some(t) = unsafe { live_slot(slot) } {
+            out.push(TimerInfo {
+                id: t.id,
+                interval: t.interval,
+                next_fire: t.tick.wrapping_sub(now) as i32,
+                source: t.func.map(callback_name).unwrap_or_default(),
+                data1: t.data1,
+                data2: t.data2,
+            });
+        }
+        slot += 1;
+    }
+    out.sort_by_key(|t| t.next_fire);
+    out
+}
+
+/// Cancel a live timer. Returns false if `id` is not a live timer.
+///
+/// Safe to call from inside a timer callback, including the timer's own:
+/// the slot is only marked here and `timer_do` frees it once the callback
+/// returns. Owners that cache the handle (e.g. `sd->timer`) still hold the
+/// old id; once the slot is reused their `timer_remove` is refused by the
+/// handle's serial rather than cancelling the new timer.
+pub fn cancel(id: i32) -> bool {
+    if unsafe { live_timer(id) }.is_none() {
+        return false;
+    }
+    unsafe { timer_remove(id) == 0 }
+}
@@ -0,0 +1,88 @@
+//! Typed view of a C `block_list`.
+//!
+//! Every game object starts with a `BlockList` whose `bl_type` says which C
+//! struct it heads. `Entity::from_bl` is the one place that reads the tag and
+//! casts; callers match on the variant instead of hand-rolling
+//! `bl as *mut MobSpawnData` after their own `bl_type` check.
+
+use std::ffi::c_int;
+
+use crate::database::map_db::BlockList;
+use crate::game::mob::{MobSpawnData, BL_ITEM, BL_MOB, BL_NPC, BL_PC};
+use crate::game::npc::NpcData;
+use crate::game::pc::MapSessionData;
+use crate::game::scripting::types::floor::FloorItemData;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum EntityError {
+    #[error("null block_list")]
+    Null,
+    #[error("unknown bl_type {0:#04x}")]
+    UnknownType(u8),
+}
+
+/// A block list resolved to the object it heads. Variants hold raw pointers
+/// because the C engine owns and mutates these objects; the tag check is what
+/// this type guarantees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entity {
+    Pc(*mut MapSessionData),
+    Mob(*mut MobSpawnData),
+    Npc(*mut NpcData),
+    Item(*mut FloorItemData),
+}
+
+impl Entity {
+    /// # Safety
+    /// `bl` must be null or point to a live block list embedded at the start
+    /// of the C object its `bl_type` names.
+    pub unsafe fn from_bl(bl: *mut BlockList) -> Result<Self, EntityError> {
+        if bl.is_null() {
+            return Err(EntityError::Null);
+        }
+        Ok(match (*bl).bl_type as c_int {
+            BL_PC => Self::Pc(bl.cast()),
+            BL_MOB => Self::Mob(bl.cast()),
+            BL_NPC => Self::Npc(bl.cast()),
+            BL_ITEM => Self::Item(bl.cast()),
+            _ => return Err(EntityError::UnknownType((*bl).bl_type)),
+        })
+    }
+
+    /// The shared header.
+    pub fn bl(self) -> *mut BlockList {
+        match self {
+            Self::Pc(p) => p.cast(),
+            Self::Mob(p) => p.cast(),
+            Self::Npc(p) => p.cast(),
+            Self::Item(p) => p.cast(),
+        }
+    }
+
+    /// # Safety
+    /// As for `from_bl`.
+    pub unsafe fn pc(bl: *mut BlockList) -> Option<*mut MapSessionData> {
+        match Self::from_bl(bl) {
+            Ok(Self::Pc(p)) => Some(p),
+            _ => None,
+        }
+    }
+
+    /// # Safety
+    /// As for `from_bl`.
+    pub unsafe fn mob(bl: *mut BlockList) -> Option<*mut MobSpawnData> {
+        match Self::from_bl(bl) {
+            Ok(Self::Mob(p)) => Some(p),
+            _ => None,
+        }
+    }
+
+    /// # Safety
+    /// As for `from_bl`.
+    pub unsafe fn npc(bl: *mut BlockList) -> Option<*mut NpcData> {
+        match Self::from_bl(bl) {
+            Ok(Self::Npc(p)) => Some(p),
+            _ => None,
+        }
+    }
+}
@@ -6,7 +6,7 @@ use std::ffi::{c_char, c_int, c_long, c_uint, c_ulong};
 use std::os::raw::c_void;
 
 use crate::database::map_db::BlockList;
-use crate::game::mob::{MobSpawnData, BL_MOB, BL_PC, MOB_DEAD};
+use crate::game::mob::{onetime_id_range, spawn_id_range, MobSpawnData, BL_MOB, BL_PC, MOB_DEAD};
 use crate::game::pc::{MapSessionData, PC_DIE, SFLAG_FULLSTATS, SFLAG_HPMP};
 
 // Module globals (mirrors C file-scope vars)
@@ -40,10 +40,6 @@ extern "C" {
     static mut d_rate:  c_int;
     static char_fd:     c_int;
     static map_n:           c_int;
-    static MOB_SPAWN_START:   c_uint;
-    static MOB_SPAWN_MAX:     c_uint;
-    static MOB_ONETIME_START: c_uint;
-    static MOB_ONETIME_MAX:   c_uint;
 }
 
 // UserList struct -- only user_count needed
@@ -222,6 +218,7 @@ static COMMANDS: &[CommandEntry] = &[
     CommandEntry { func: command_legend,          name: "legend",          level: 99 },
     CommandEntry { func: command_luareload,       name: "reloadlua",       level: 99 },
     CommandEntry { func: command_luareload,       name: "rl",              level: 99 },
+    CommandEntry { func: command_validaterefs,    name: "validaterefs",    level: 99 },
     CommandEntry { func: command_magicreload,     name: "reloadmagic",     level: 99 },
     CommandEntry { func: command_lua,             name: "lua",             level: 0  },
     CommandEntry { func: command_speed,           name: "speed",           level: 10 },
@@ -285,6 +282,17 @@ static COMMANDS: &[CommandEntry] = &[
     CommandEntry { func: command_reloadlevels,    name: "reloadlevels",    level: 99 },
     CommandEntry { func: command_reloadwarps,     name: "reloadwarps",     level: 99 },
     CommandEntry { func: command_transfer,        name: "transfer",        level: 99 },
+    CommandEntry { func: command_savenow,         name: "savenow",         level: 50 },
+    CommandEntry { func: command_throttles,       name: "throttles",       level: 99 },
+    CommandEntry { func: command_unthrottle,      name: "unthrottle",      level: 99 },
+    CommandEntry { func: command_census,          name: "census",          level: 50 },
+    CommandEntry { func: command_sessions,        name: "sessions",        level: 99 },
+    CommandEntry { func: command_eventrate,       name: "eventrate",       level: 99 },
+    CommandEntry { func: command_timers,          name: "timers",          level: 99 },
+    CommandEntry { func: command_timercancel,     name: "timercancel",     level: 99 },
+    CommandEntry { func: command_mapload,         name: "mapload",         level: 99 },
+    CommandEntry { func: command_mapreload,       name: "mapreload",       level: 99 },
+    CommandEntry { func: command_mapunload,       name: "mapunload",       level: 99 },
 ];
 
 // ─── Stub implementations (replaced batch-by-batch below) ────────────────────
@@ -486,7 +494,7 @@ unsafe fn command_spell(sd: *mut MapSessionData, line: *mut c_char, _s: *mut Lua
 }
 unsafe fn command_val(sd: *mut MapSessionData, _line: *mut c_char, _s: *mut LuaState) -> c_int {
     if sd.is_null() { return 0; }
-    let count = (MOB_SPAWN_MAX - MOB_SPAWN_START) + (MOB_ONETIME_MAX - MOB_ONETIME_START);
+    let count = spawn_id_range().len() + onetime_id_range().len();
     let mut buf = [0i8; 255];
     let msg = format!("Mob spawn count: {}\0", count);
     for (i, b) in msg.bytes().take(254).enumerate() { buf[i] = b as i8; }
@@ -588,6 +596,14 @@ unsafe fn command_luareload(sd: *mut MapSessionData, _line: *mut c_char, s: *mut
     clif_sendminitext(sd, b"LUA Scripts reloaded!\0".as_ptr() as *const c_char);
     errors
 }
+/// `/validaterefs` — scan scripts for literal item/recipe ids that don't exist.
+unsafe fn command_validaterefs(sd: *mut MapSessionData, _line: *mut c_char, _s: *mut LuaState) -> c_int {
+    let bad = crate::game::scripting::sl_validate_item_refs();
+    if sd.is_null() { return 0; }
+    let msg = format!("Unresolved ITEM/RECIPE ids: {bad} (see server log)\0");
+    clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    0
+}
 unsafe fn command_magicreload(sd: *mut MapSessionData, _line: *mut c_char, _s: *mut LuaState) -> c_int {
     magicdb_read();
     if sd.is_null() { return 0; }
@@ -1211,6 +1227,202 @@ unsafe fn command_transfer(sd: *mut MapSessionData, _line: *mut c_char, _s: *mut
     0
 }
 
+/// `/savenow <name>` — ask the char server to persist one player right away.
+/// The outcome arrives asynchronously via `rust_gm_savenow_ack`.
+unsafe fn command_savenow(sd: *mut MapSessionData, line: *mut c_char, _s: *mut LuaState) -> c_int {
+    if sd.is_null() { return 0; }
+    let tsd = map_name2sd(line);
+    if tsd.is_null() {
+        clif_sendminitext(sd, b"User not found.\0".as_ptr() as *const c_char);
+        return 0;
+    }
+    crate::ffi::map_char::rust_intif_savenow(
+        (*sd).fd,
+        &raw const (*tsd).status as *const u8,
+        std::mem::size_of_val(&(*tsd).status) as u32,
+    );
+    clif_sendminitext(sd, b"Save requested.\0".as_ptr() as *const c_char);
+    0
+}
+
+/// `/throttles` — list throttled IPs on this server and their attempt counts.
+unsafe fn command_throttles(sd: *mut MapSessionData, _line: *mut c_char, _s: *mut LuaState) -> c_int {
+    if sd.is_null() { return 0; }
+    let entries = crate::network::throttle::list();
+    let header = format!("Throttle entries: {}\0", entries.len());
+    clif_sendminitext(sd, header.as_ptr() as *const c_char);
+    for (ip, count) in entries.iter().take(10) {
+        let msg = format!("{} x{}\0", std::net::Ipv4Addr::from(*ip), count);
+        clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    }
+    0
+}
+
+/// `/unthrottle <ip>` — clear one IP's throttle entry without waiting for the reset.
+unsafe fn command_unthrottle(sd: *mut MapSessionData, line: *mut c_char, _s: *mut LuaState) -> c_int {
+    if sd.is_null() { return 0; }
+    let arg = std::ffi::CStr::from_ptr(line).to_str().unwrap_or("").trim();
+    let Ok(ip) = arg.parse::<std::net::Ipv4Addr>() else {
+        clif_sendminitext(sd, b"Usage: /unthrottle <ipv4>\0".as_ptr() as *const c_char);
+        return 0;
+    };
+    if crate::network::throttle::unthrottle(u32::from(ip).to_be()) {
+        clif_sendminitext(sd, b"Throttle cleared.\0".as_ptr() as *const c_char);
+    } else {
+        clif_sendminitext(sd, b"IP is not throttled.\0".as_ptr() as *const c_char);
+    }
+    0
+}
+
+/// `/census [map]` — player/mob/npc counts for one map, or the busiest maps.
+unsafe fn command_census(sd: *mut MapSessionData, line: *mut c_char, _s: *mut LuaState) -> c_int {
+    if sd.is_null() { return 0; }
+    let arg = std::ffi::CStr::from_ptr(line).to_str().unwrap_or("").trim();
+    if let Ok(m) = arg.parse::<u16>() {
+        let msg = match crate::ffi::map_db::map_census(m) {
+            Some(c) => format!("Map {}: {} players, {} mobs, {} npcs\0", m, c.players, c.mobs, c.npcs),
+            None => format!("Map {} is not loaded.\0", m),
+        };
+        clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+        return 0;
+    }
+    let report = crate::ffi::map_db::world_census();
+    let mut maps = report.maps.clone();
+    maps.sort_by_key(|c| std::cmp::Reverse((c.players, c.mobs + c.npcs)));
+    let players: u32 = maps.iter().map(|c| c.players).sum();
+    let header = format!(
+        "Census: {} maps in use, {} players{}\0",
+        maps.len(), players, if report.truncated { " (partial)" } else { "" }
+    );
+    clif_sendminitext(sd, header.as_ptr() as *const c_char);
+    for c in maps.iter().take(10) {
+        let msg = format!("Map {}: {}p {}m {}n\0", c.map_id, c.players, c.mobs, c.npcs);
+        clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    }
+    0
+}
+
+/// `/sessions` — live sessions by kind and closed sessions by reason.
+unsafe fn command_sessions(sd: *mut MapSessionData, _line: *mut c_char, _s: *mut LuaState) -> c_int {
+    if sd.is_null() { return 0; }
+    let manager = crate::session::get_session_manager();
+    let (count, limit) = manager.utilization();
+    let kinds: Vec<String> = manager.kind_counts().into_iter()
+        .filter(|&(_, n)| n > 0)
+        .map(|(k, n)| format!("{} {}", n, k.name()))
+        .collect();
+    let msg = format!("Sessions: {}/{} ({})\0", count, limit, kinds.join(", "));
+    clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    let closed: Vec<String> = manager.disconnect_counts().into_iter()
+        .filter(|&(_, n)| n > 0)
+        .map(|(r, n)| format!("{} {}", n, r.name()))
+        .collect();
+    let msg = format!("Closed: {}\0", if closed.is_empty() { "none".to_string() } else { closed.join(", ") });
+    clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    let st = manager.stats();
+    let msg = format!(
+        "Buffered: {} B out (max {} B), {} B in; oldest idle {}s\0",
+        st.write_buffered, st.max_write_buffered, st.read_buffered, st.oldest_idle.as_secs()
+    );
+    clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    0
+}
+
+/// `/mapload <id>`, `/mapreload <id>`, `/mapunload <id>` — hot-add, re-read
+/// or remove one map (see `game::hotload`); unload sends players to start_point.
+unsafe fn command_mapload(sd: *mut MapSessionData, line: *mut c_char, _s: *mut LuaState) -> c_int {
+    hotload_command(sd, line, "loaded", |id| crate::game::hotload::map_load(id))
+}
+unsafe fn command_mapreload(sd: *mut MapSessionData, line: *mut c_char, _s: *mut LuaState) -> c_int {
+    hotload_command(sd, line, "reloaded", |id| crate::game::hotload::map_reload(id))
+}
+unsafe fn command_mapunload(sd: *mut MapSessionData, line: *mut c_char, _s: *mut LuaState) -> c_int {
+    let fallback = crate::ffi::config::config().start_point;
+    hotload_command(sd, line, "unloaded", |id| crate::game::hotload::map_unload(id, fallback))
+}
+unsafe fn hotload_command(sd: *mut MapSessionData, line: *mut c_char, done: &str, f: impl FnOnce(c_int) -> c_int) -> c_int {
+    let Some(id) = parse_int(line) else { return -1 };
+    let ok = f(id) == 0;
+    if sd.is_null() { return 0; }
+    let msg = if ok {
+        format!("Map {id} {done}.\0")
+    } else {
+        format!("Map {id} not {done} (see server log).\0")
+    };
+    clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    0
+}
+
+/// `/eventrate [exp] [drop]` — show or set the event exp/drop multipliers.
+unsafe fn command_eventrate(sd: *mut MapSessionData, line: *mut c_char, _s: *mut LuaState) -> c_int {
+    use crate::servers::map::rates;
+    if sd.is_null() { return 0; }
+    let arg = std::ffi::CStr::from_ptr(line).to_str().unwrap_or("");
+    let vals: Vec<f32> = arg.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+    if let [exp, drop] = vals[..] {
+        if !rates::set(exp, drop) {
+            let msg = format!("Rates must be between 0 and {}.\0", rates::MAX_RATE);
+            clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+            return 0;
+        }
+        tracing::info!("[map] [gm] {} set event rates exp={} drop={}",
+            std::ffi::CStr::from_ptr((*sd).status.name.as_ptr()).to_string_lossy(), exp, drop);
+    }
+    let msg = format!("Event rates: {}x exp, {}x drop\0", rates::exp_rate(), rates::drop_rate());
+    clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    0
+}
+
+/// `/timers` — count of live timers and the ten due soonest.
+unsafe fn command_timers(sd: *mut MapSessionData, _line: *mut c_char, _s: *mut LuaState) -> c_int {
+    if sd.is_null() { return 0; }
+    let timers = crate::ffi::timer::list();
+    let header = format!("Live timers: {}\0", timers.len());
+    clif_sendminitext(sd, header.as_ptr() as *const c_char);
+    for t in timers.iter().take(10) {
+        let msg = format!("#{} {} in {}ms every {}ms\0", t.id, t.source, t.next_fire, t.interval);
+        clif_sendminitext(sd, msg.as_ptr() as *const c_char);
+    }
+    0
+}
+
+/// `/timercancel <id>` — cancel one live timer by id (see `/timers`).
+unsafe fn command_timercancel(sd: *mut MapSessionData, line: *mut c_char, _s: *mut LuaState) -> c_int {
+    if sd.is_null() { return 0; }
+    let arg = std::ffi::CStr::from_ptr(line).to_str().unwrap_or("").trim();
+    let Ok(id) = arg.parse::<i32>() else {
+        clif_sendminitext(sd, b"Usage: /timercancel <id>\0".as_ptr() as *const c_char);
+        return 0;
+    };
+    if crate::ffi::timer::cancel(id) {
+        tracing::info!("[map] [gm] {} cancelled timer {}",
+            std::ffi::CStr::from_ptr((*sd).status.name.as_ptr()).to_string_lossy(), id);
+        clif_sendminitext(sd, b"Timer cancelled.\0".as_ptr() as *const c_char);
+    } else {
+        clif_sendminitext(sd, b"No such live timer.\0".as_ptr() as *const c_char);
+    }
+    0
+}
+
+/// Save-now ack reporter registered via `ffi::map_char::set_savenow_ack_fn`.
+/// Tells the requesting GM (if still connected) whether the save committed.
+pub unsafe extern "C" fn rust_gm_savenow_ack(fd: c_int, char_id: c_uint, result: u8) {
+    use crate::servers::char::packet::SaveNowResult;
+    if rust_session_exists(fd) == 0 { return; }
+    let sd = rust_session_get_data(fd);
+    if sd.is_null() { return; }
+    let what = SaveNowResult::from_u8(result).map(|r| r.describe()).unwrap_or("unknown result");
+    // char_id is 0 when char_server could not decode what we sent.
+    let msg = if char_id == 0 {
+        format!("Save failed: {}.", what)
+    } else {
+        format!("Save of char {}: {}.", char_id, what)
+    };
+    if let Ok(c) = std::ffi::CString::new(msg) {
+        clif_sendminitext(sd, c.as_ptr());
+    }
+}
+
 // ─── rust_command_reload: exported entry point for full mini-reset ────────────
 
 #[no_mangle]
@@ -0,0 +1,186 @@
+//! Adding, reloading and removing single maps while the server runs.
+//!
+//! `map_load` brings a map listed in `Maps` for this server into its empty
+//! slot with a fresh block grid and its warps (including warps on other maps
+//! that lead to it, which were skipped at boot). `map_reload` re-reads one
+//! loaded map in place: everything standing on it is unlinked, the grid is
+//! rebuilt for the new dimensions, and occupants are linked back in with
+//! their coordinates clamped to the new bounds. `map_unload` evicts players
+//! to a fallback point, drops warps elsewhere that lead to the map, and
+//! frees the slot. Each change re-sends this server's map list to
+//! char_server so logins and transfers route to it.
+
+use std::ffi::{c_int, c_void};
+
+use crate::config::Point;
+use crate::database::map_db::{self as db, BlockList, MapData, MAP_SLOTS};
+use crate::ffi::block::{alloc_grid, free_grid, grid_entries, grid_is_empty};
+use crate::ffi::map_db::{get_map_ptr, map_is_loaded, map_n};
+use crate::game::instance::{clear_blocker, clear_occupants};
+use crate::game::mob::{map_addblock, map_delblock, map_id2mob, spawn_id_range, BL_PC};
+use crate::game::npc::warp_load_async;
+use crate::servers::map::instance::{is_instance, INSTANCES};
+
+extern "C" {
+    fn sl_pc_refresh(sd: *mut c_void);
+    fn map_registrysave(m: c_int, i: c_int) -> c_int;
+}
+
+fn static_id(id: c_int) -> Option<u16> {
+    let m = u16::try_from(id).ok()?;
+    (!is_instance(m)).then_some(m)
+}
+
+unsafe fn load_warps(m: u16) {
+    crate::database::blocking_run(async { warp_load_async(Some(m)).await });
+}
+
+/// Write every named registry entry of `m` back to `MapRegistry`, so the
+/// values the slot holds survive it being re-read or freed even if an
+/// earlier write-through failed.
+unsafe fn flush_registry(m: u16, slot: &MapData) {
+    if slot.registry.is_null() {
+        return;
+    }
+    for i in 0..slot.registry_num {
+        if (*slot.registry.add(i as usize)).str[0] != 0 {
+            map_registrysave(m as c_int, i);
+        }
+    }
+}
+
+/// Load map `id` into its empty slot. Returns 0 on success, -1 if it is
+/// already loaded or could not be read.
+pub unsafe fn map_load(id: c_int) -> c_int {
+    let Some(m) = static_id(id) else { return -1 };
+    if get_map_ptr(m).is_null() || map_is_loaded(m) {
+        tracing::warn!("[map] [hotload] load id={id} refused: already loaded or no slot");
+        return -1;
+    }
+    let cfg = crate::ffi::config::config();
+    let slot = &mut *get_map_ptr(m);
+    if let Err(e) = db::load_map(&cfg.maps_dir, cfg.server_id as i32, m, slot) {
+        tracing::error!("[map] [hotload] load id={id} failed: {e:#}");
+        return -1;
+    }
+    alloc_grid(slot);
+    load_warps(m);
+    map_n += 1;
+    crate::ffi::map_char::send_mapset();
+    tracing::info!("[map] [hotload] loaded id={id} size={}x{}", slot.xs, slot.ys);
+    0
+}
+
+/// Re-read loaded map `id` (flags, tiles, registry, warps). Occupants stay
+/// on the map; any outside the new bounds are moved to the nearest edge
+/// cell, and players are sent the refreshed map. Returns 0 on success, -1
+/// if the map is not loaded or could not be read (the old data stays).
+pub unsafe fn map_reload(id: c_int) -> c_int {
+    let Some(m) = static_id(id) else { return -1 };
+    if !map_is_loaded(m) {
+        tracing::warn!("[map] [hotload] reload id={id} refused: not loaded");
+        return -1;
+    }
+    let cfg = crate::ffi::config::config();
+    let slot = &mut *get_map_ptr(m);
+    flush_registry(m, slot);
+
+    // Unlink everything while the grid still has the old dimensions.
+    let entries = grid_entries(slot);
+    for &bl in &entries {
+        map_delblock(bl);
+    }
+    free_grid(slot);
+
+    let result = db::load_map(&cfg.maps_dir, cfg.server_id as i32, m, slot);
+
+    alloc_grid(slot);
+    load_warps(m);
+    let (xs, ys) = (slot.xs, slot.ys);
+    let mut moved = 0;
+    for &bl in &entries {
+        let b: &mut BlockList = &mut *bl;
+        if b.x >= xs || b.y >= ys {
+            b.x = b.x.min(xs.saturating_sub(1));
+            b.y = b.y.min(ys.saturating_sub(1));
+            moved += 1;
+        }
+        map_addblock(bl);
+    }
+    for &bl in &entries {
+        if (*bl).bl_type as c_int == BL_PC {
+            sl_pc_refresh(bl as *mut c_void);
+        }
+    }
+
+    match result {
+        Ok((old_xs, old_ys)) => {
+            tracing::info!(
+                "[map] [hotload] reloaded id={id} size={old_xs}x{old_ys}->{xs}x{ys} occupants={} clamped={moved}",
+                entries.len()
+            );
+            0
+        }
+        Err(e) => {
+            tracing::error!("[map] [hotload] reload id={id} failed, keeping old data: {e:#}");
+            -1
+        }
+    }
+}
+
+/// Remove loaded map `id`: players are warped to `fallback`, one-time mobs
+/// and floor items are cleared, warps on other maps that lead here are
+/// dropped, and the slot is freed. Refused (-1, nothing touched) while NPCs
+/// stand on the map, permanent spawns are homed there or instances of it
+/// are live; NPCs and spawns have to be removed from the DB tables and
+/// reloaded first.
+pub unsafe fn map_unload(id: c_int, fallback: Point) -> c_int {
+    let Some(m) = static_id(id) else { return -1 };
+    if !map_is_loaded(m) {
+        tracing::warn!("[map] [hotload] unload id={id} refused: not loaded");
+        return -1;
+    }
+    if fallback.m == m || !map_is_loaded(fallback.m) {
+        tracing::warn!("[map] [hotload] unload id={id} refused: fallback map {} unusable", fallback.m);
+        return -1;
+    }
+    let copies = INSTANCES.lock().unwrap().list().iter().filter(|&&(_, base)| base == m).count();
+    if copies > 0 {
+        tracing::warn!("[map] [hotload] unload id={id} refused: {copies} instances of it are live");
+        return -1;
+    }
+    if let Some(why) = clear_blocker(m) {
+        tracing::warn!("[map] [hotload] unload id={id} refused: {why}");
+        return -1;
+    }
+    let homed = spawn_id_range().filter(|&sid| {
+        let mob = map_id2mob(sid);
+        !mob.is_null() && (*mob).onetime == 0 && (*mob).startm == m
+    }).count();
+    if homed > 0 {
+        tracing::warn!("[map] [hotload] unload id={id} refused: {homed} spawns start there");
+        return -1;
+    }
+
+    clear_occupants(m, |_| (fallback.m, fallback.x, fallback.y));
+    let slot = &mut *get_map_ptr(m);
+    if !grid_is_empty(slot) {
+        // Only a mapLeave/mapEnter script can put something back; the map
+        // stays loaded and the occupants already moved stay moved.
+        tracing::error!("[map] [hotload] unload id={id} aborted: a script repopulated the map during eviction");
+        return -1;
+    }
+    let mut dropped = 0;
+    for other in 0..MAP_SLOTS as u16 {
+        if other != m && map_is_loaded(other) {
+            dropped += db::drop_warps_to(&mut *get_map_ptr(other), m);
+        }
+    }
+    flush_registry(m, slot);
+    free_grid(slot);
+    db::free_slot(slot);
+    map_n -= 1;
+    crate::ffi::map_char::send_mapset();
+    tracing::info!("[map] [hotload] unloaded id={id} warps_dropped={dropped}");
+    0
+}
@@ -0,0 +1,154 @@
+//! Instanced maps: spawns and occupants.
+//!
+//! `ffi::map_db::create_instance_slot` does the map-level copy (tiles,
+//! flags, warps, empty block grid). This layer copies the base map's
+//! permanent spawns in as one-time mobs, and on teardown sends players back
+//! to the base map at the same cell and clears mobs and floor items so the
+//! slot can be freed.
+
+use std::ffi::{c_int, c_uint, c_ushort};
+
+use crate::database::map_db::BlockList;
+use crate::ffi::block::grid_entries;
+use crate::ffi::map_db::{create_instance_slot, destroy_instance_slot, get_map_ptr, map_is_loaded};
+use crate::game::mob::{
+    free_onetime, map_delblock, map_deliddb, map_id2mob, mob_warp, mobspawn_onetime,
+    spawn_id_range, BL_ITEM, BL_MOB, BL_NPC, BL_PC,
+};
+use crate::game::pc::MapSessionData;
+use crate::servers::map::instance;
+
+extern "C" {
+    fn pc_warp(sd: *mut MapSessionData, m: c_int, x: c_int, y: c_int) -> c_int;
+    #[link_name = "map_id2sd"]
+    fn map_id2sd_pc(id: c_uint) -> *mut MapSessionData;
+    fn map_delitem(id: c_uint);
+}
+
+/// Create an instance of `base` with a copy of its spawns.
+/// Returns the instance map id, or -1.
+pub unsafe fn map_create_instance(base: c_int) -> c_int {
+    if !(0..=u16::MAX as c_int).contains(&base) {
+        return -1;
+    }
+    let Some(id) = create_instance_slot(base as u16) else { return -1 };
+    let mut spawned = 0;
+    for sid in spawn_id_range() {
+        let mob = map_id2mob(sid);
+        if mob.is_null() || (*mob).startm != base as c_ushort || (*mob).onetime != 0 {
+            continue;
+        }
+        let ids = mobspawn_onetime(
+            (*mob).mobid, id as c_int, (*mob).startx as c_int, (*mob).starty as c_int,
+            1, (*mob).start as c_int, (*mob).end as c_int, (*mob).replace, 0,
+        );
+        if !ids.is_null() {
+            libc::free(ids as *mut libc::c_void);
+            spawned += 1;
+        }
+    }
+    tracing::info!("[map] [instance] id={id} base={base} spawns={spawned}");
+    id as c_int
+}
+
+/// Ids of everything of `bl_type` linked into `m`'s block grid.
+pub(crate) unsafe fn ids_on_map(m: u16, bl_type: c_int) -> Vec<c_uint> {
+    let slot = &*get_map_ptr(m);
+    let heads = if bl_type == BL_MOB { slot.block_mob } else { slot.block };
+    let mut out = Vec::new();
+    if heads.is_null() {
+        return out;
+    }
+    for i in 0..slot.bxs as usize * slot.bys as usize {
+        let mut bl: *mut BlockList = *heads.add(i);
+        while !bl.is_null() {
+            if (*bl).bl_type as c_int == bl_type {
+                out.push((*bl).id);
+            }
+            bl = (*bl).next;
+        }
+    }
+    out
+}
+
+/// Why `clear_occupants(m, ..)` would leave something behind on `m`, if
+/// anything: an NPC, a permanent spawn whose home is `m` itself, a player
+/// or mob whose id no longer resolves, or a block type it does not handle.
+/// Callers check this first so a refused teardown touches nothing.
+pub(crate) unsafe fn clear_blocker(m: u16) -> Option<String> {
+    for bl in grid_entries(&*get_map_ptr(m)) {
+        let id = (*bl).id;
+        match (*bl).bl_type as c_int {
+            BL_NPC => return Some("npcs still present".into()),
+            BL_PC if map_id2sd_pc(id).is_null() => {
+                return Some(format!("player {id} has no session"));
+            }
+            BL_MOB => {
+                let mob = map_id2mob(id);
+                if mob.is_null() {
+                    return Some(format!("mob {id} is not registered"));
+                }
+                if (*mob).onetime == 0 && (*mob).startm == m {
+                    return Some(format!("mob {id} spawns there"));
+                }
+            }
+            BL_PC | BL_ITEM => {}
+            t => return Some(format!("unknown block type {t:#x} (id {id})")),
+        }
+    }
+    None
+}
+
+/// Empty map `m` of everything but NPCs: players are warped to `dest(sd)`,
+/// one-time mobs and floor items are removed, and permanent spawns are sent
+/// back to their spawn point.
+pub(crate) unsafe fn clear_occupants(m: u16, dest: impl Fn(*mut MapSessionData) -> (u16, u16, u16)) {
+    for pid in ids_on_map(m, BL_PC) {
+        let sd = map_id2sd_pc(pid);
+        if !sd.is_null() {
+            let (dm, dx, dy) = dest(sd);
+            pc_warp(sd, dm as c_int, dx as c_int, dy as c_int);
+        }
+    }
+    for mid in ids_on_map(m, BL_MOB) {
+        let mob = map_id2mob(mid);
+        if mob.is_null() {
+            continue;
+        }
+        if (*mob).onetime != 0 {
+            map_delblock(&mut (*mob).bl);
+            map_deliddb(&mut (*mob).bl);
+            free_onetime(mob);
+        } else {
+            // A permanent spawn a script moved in goes home.
+            mob_warp(mob, (*mob).startm as c_int, (*mob).startx as c_int, (*mob).starty as c_int);
+        }
+    }
+    for iid in ids_on_map(m, BL_ITEM) {
+        map_delitem(iid);
+    }
+}
+
+/// Tear down instance `id`: players are warped to the base map at their
+/// current cell, one-time mobs and floor items are removed, permanent
+/// spawns a script moved in are sent back to their spawn point, and the slot
+/// is freed. NPCs placed into the instance by scripts must be removed
+/// first; while any remain (or anything else `clear_blocker` reports),
+/// nothing is touched and -1 is returned. Returns 0 on success.
+pub unsafe fn map_destroy_instance(id: c_int) -> c_int {
+    if !(0..=u16::MAX as c_int).contains(&id) {
+        return -1;
+    }
+    let m = id as u16;
+    let Some(base) = instance::base_of(m) else { return -1 };
+    if !map_is_loaded(base) {
+        tracing::warn!("[map] [instance] destroy id={id} refused: base map {base} not loaded");
+        return -1;
+    }
+    if let Some(why) = clear_blocker(m) {
+        tracing::warn!("[map] [instance] destroy id={id} refused: {why}");
+        return -1;
+    }
+    clear_occupants(m, |sd| (base, (*sd).bl.x, (*sd).bl.y));
+    if destroy_instance_slot(m) { 0 } else { -1 }
+}
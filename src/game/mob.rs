@@ -9,9 +9,13 @@ use crate::database::mob_db::MobDbData;
 #[cfg(not(test))]
 use crate::ffi::map_db::{get_map_ptr as ffi_get_map_ptr, map_is_loaded as ffi_map_is_loaded};
 use crate::game::pc::MapSessionData;
+use crate::game::entity::Entity;
 use crate::game::types::GfxViewer;
 use crate::servers::char::charstatus::{Item, SkillInfo};
+use crate::servers::map::onetime_ids::OnetimeIds;
 use std::ffi::{c_char, c_double, c_float, c_int, c_schar, c_short, c_uchar, c_uint, c_ushort};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use std::sync::Arc;
 
 // ─── Constants ──────────────────────────────────────────────────────────────
 pub const MOB_START_NUM: u32 = 1073741823;
@@ -166,26 +170,46 @@ pub struct MobSpawnData {
 unsafe impl Send for MobSpawnData {}
 unsafe impl Sync for MobSpawnData {}
 
-// ─── Mutable globals (unsafe statics, C-compatible) ──────────────────────────
+// ─── Shared mob id counters (atomic, C-compatible) ───────────────────────────
+// AtomicU32/AtomicU8 have the same in-memory representation as u32/u8, so C
+// code that declares `extern unsigned int MOB_SPAWN_START;` (mob.h) still reads
+// these symbols directly. Rust code goes through load/store/fetch_* instead of
+// `static mut`, which removes the data-race hazard once callers leave the
+// single game thread. Relaxed ordering is enough: each counter is independent
+// and no other memory is published through them.
 // Use #[export_name] for uppercase globals to avoid sqlx #[derive(FromRow)]
 // let-binding conflicts (see MEMORY.md: "npc_id #[export_name]").
 #[export_name = "mob_id"]
-pub static mut MOB_ID: c_uint = MOB_START_NUM;
+pub static MOB_ID: AtomicU32 = AtomicU32::new(MOB_START_NUM);
 #[export_name = "max_normal_id"]
-pub static mut MAX_NORMAL_ID: c_uint = MOB_START_NUM;
+pub static MAX_NORMAL_ID: AtomicU32 = AtomicU32::new(MOB_START_NUM);
 #[export_name = "cmob_id"]
-pub static mut CMOB_ID: c_uint = 0;
+pub static CMOB_ID: AtomicU32 = AtomicU32::new(0);
 #[export_name = "MOB_SPAWN_MAX"]
-pub static mut MOB_SPAWN_MAX: c_uint = MOB_START_NUM;
+pub static MOB_SPAWN_MAX: AtomicU32 = AtomicU32::new(MOB_START_NUM);
 #[export_name = "MOB_SPAWN_START"]
-pub static mut MOB_SPAWN_START: c_uint = MOB_START_NUM;
+pub static MOB_SPAWN_START: AtomicU32 = AtomicU32::new(MOB_START_NUM);
 #[export_name = "MOB_ONETIME_MAX"]
-pub static mut MOB_ONETIME_MAX: c_uint = MOBOT_START_NUM;
+pub static MOB_ONETIME_MAX: AtomicU32 = AtomicU32::new(MOBOT_START_NUM);
 #[export_name = "MOB_ONETIME_START"]
-pub static mut MOB_ONETIME_START: c_uint = MOBOT_START_NUM;
+pub static MOB_ONETIME_START: AtomicU32 = AtomicU32::new(MOBOT_START_NUM);
 #[export_name = "MIN_TIMER"]
-pub static mut MIN_TIMER: c_uint = 1000;
-pub static mut TIMERCHECK: c_uchar = 0; // internal only
+pub static MIN_TIMER: AtomicU32 = AtomicU32::new(1000);
+pub static TIMERCHECK: AtomicU8 = AtomicU8::new(0); // internal only
+/// First mob id the next `mob_timer_spawns` call handles; 0 = start a new pass.
+static MOB_SWEEP_RESUME: AtomicU32 = AtomicU32::new(0);
+
+/// Snapshot of the permanent spawn id range `[start, max)`.
+#[inline]
+pub fn spawn_id_range() -> std::ops::Range<c_uint> {
+    MOB_SPAWN_START.load(Ordering::Relaxed)..MOB_SPAWN_MAX.load(Ordering::Relaxed)
+}
+
+/// Snapshot of the one-time (scripted) mob id range `[start, max)`.
+#[inline]
+pub fn onetime_id_range() -> std::ops::Range<c_uint> {
+    MOB_ONETIME_START.load(Ordering::Relaxed)..MOB_ONETIME_MAX.load(Ordering::Relaxed)
+}
 
 // ─── Extern C declarations ────────────────────────────────────────────────────
 
@@ -274,9 +298,6 @@ extern "C" {
     #[link_name = "rust_mobdb_search"]
     pub fn mobdb_search(id: c_uint) -> *mut MobDbData;
 
-    // rnd is a C macro (#define rnd(x) ((int)(randomMT() & 0xFFFFFF) % (x))).
-    // Call randomMT() directly and apply the same mask/modulus in Rust.
-    pub fn randomMT() -> c_uint;
     pub fn gettick() -> c_uint;
     static cur_time: c_int;
     static serverid: c_int;
@@ -295,31 +316,27 @@ unsafe fn sl_doscript_simple(
 // ─── Mob ID management ────────────────────────────────────────────────────────
 
 pub unsafe fn mob_get_new_id() -> c_uint {
-    let id = MOB_ID;
-    MOB_ID += 1;
-    id
+    take_id(&MOB_ID)
+}
+
+/// Hand out the counter's current value and move it on by one.
+fn take_id(counter: &AtomicU32) -> c_uint {
+    counter.fetch_add(1, Ordering::Relaxed)
 }
 
+/// One-time mob ids; `MOB_ONETIME_MAX` mirrors its high-water mark.
+static ONETIME_IDS: std::sync::Mutex<OnetimeIds> =
+    std::sync::Mutex::new(OnetimeIds::new(MOBOT_START_NUM, NPC_START_NUM));
+
 #[cfg(not(test))]
 pub unsafe fn mob_get_free_id() -> c_uint {
-    let mut x = MOB_ONETIME_START;
-    loop {
-        if x >= NPC_START_NUM {
-            eprintln!("[mob] mob_get_free_id: onetime range exhausted");
-            return 0;
-        }
-        if x == MOB_ONETIME_MAX {
-            if MOB_ONETIME_MAX >= NPC_START_NUM {
-                eprintln!("[mob] mob_get_free_id: onetime range full");
-                return 0;
-            }
-            MOB_ONETIME_MAX += 1;
-        }
-        if map_id2bl(x).is_null() {
-            return x;
-        }
-        x += 1;
-    }
+    let mut ids = ONETIME_IDS.lock().unwrap();
+    let id = ids.alloc();
+    MOB_ONETIME_MAX.store(ids.top(), Ordering::Relaxed);
+    id.unwrap_or_else(|| {
+        tracing::warn!("[mob] mob_get_free_id: onetime range exhausted");
+        0
+    })
 }
 
 #[cfg(not(test))]
@@ -327,26 +344,41 @@ pub unsafe fn onetime_avail(id: c_uint) -> *mut BlockList {
     map_id2bl(id)
 }
 
+/// Liveness flags for one-time mobs, keyed by block id and shared by every
+/// Lua `MobObject` that refers to the mob. Freeing the mob sets the flag and
+/// drops the entry, so handles a script kept read as deleted instead of
+/// dereferencing freed memory (ids are reused by `mob_get_free_id`).
+static ONETIME_TOKENS: std::sync::Mutex<Option<std::collections::HashMap<c_uint, Arc<AtomicBool>>>> =
+    std::sync::Mutex::new(None);
+
+/// The shared liveness flag for one-time mob `id` (created on first use).
+pub fn onetime_token(id: c_uint) -> Arc<AtomicBool> {
+    let mut tokens = ONETIME_TOKENS.lock().unwrap();
+    Arc::clone(tokens.get_or_insert_with(Default::default).entry(id).or_default())
+}
+
+/// Mark every handle to one-time mob `id` as deleted.
+pub fn retire_onetime_token(id: c_uint) {
+    let mut tokens = ONETIME_TOKENS.lock().unwrap();
+    if let Some(flag) = tokens.as_mut().and_then(|t| t.remove(&id)) {
+        flag.store(true, Ordering::Release);
+    }
+}
+
 #[cfg(not(test))]
 pub unsafe fn free_onetime(mob: *mut MobSpawnData) -> c_int {
     if mob.is_null() {
         return 0;
     }
+    retire_onetime_token((*mob).bl.id);
     (*mob).data = std::ptr::null_mut();
+    let id = (*mob).bl.id;
     libc::free(mob as *mut libc::c_void);
-    // compact onetime range downward
-    let mut x = MOB_ONETIME_START;
-    while x <= MOB_ONETIME_MAX {
-        let bl = map_id2bl(x);
-        if bl.is_null() {
-            return 0;
-        }
-        if x == MOB_ONETIME_MAX {
-            map_deliddb(bl);
-            MOB_ONETIME_MAX -= 1;
-        }
-        x += 1;
+    let mut ids = ONETIME_IDS.lock().unwrap();
+    if !ids.release(id) {
+        tracing::warn!("[mob] free_onetime: id {} was not allocated", id);
     }
+    MOB_ONETIME_MAX.store(ids.top(), Ordering::Relaxed);
     0
 }
 
@@ -433,29 +465,54 @@ use crate::database::{blocking_run, get_pool};
 
 #[cfg(not(test))]
 pub unsafe fn mobspawn_read() -> c_int {
-    let serverid_val = serverid;
-    let result = blocking_run(async move {
-        let pool = get_pool();
-        let query = format!(
-            "SELECT `SpnMapId`, `SpnX`, `SpnY`, `SpnMobId`, \
-             `SpnLastDeath`, `SpnId`, `SpnStartTime`, `SpnEndTime`, \
-             `SpnMobIdReplace` FROM `Spawns{}` ORDER BY `SpnId`",
-            serverid_val
-        );
-        sqlx::query(&query).fetch_all(pool).await
-    });
+    use crate::servers::map::spawn_shards;
+    use sqlx::Row;
+    let started = std::time::Instant::now();
+    let sources = match crate::ffi::config::try_config() {
+        Some(c) => c.spawn_sources(),
+        None => match spawn_shards::shard_for_server(serverid) {
+            Some(src) => vec![src],
+            None => {
+                tracing::error!("[mob] [spawn] serverid {} outside 0..={}; spawns not loaded",
+                    serverid, spawn_shards::MAX_SHARD_ID);
+                return -1;
+            }
+        },
+    };
 
-    let rows = match result {
-        Ok(r) => r,
-        Err(e) => {
-            eprintln!("[mob] spawn read error: {}", e);
-            return 0;
+    let mut per_source = Vec::with_capacity(sources.len());
+    for src in &sources {
+        let Some(table) = src.checked_table() else {
+            tracing::error!("[mob] [spawn] refusing spawn source {:?}: shard id above {} or bad table name",
+                src, spawn_shards::MAX_SHARD_ID);
+            return -1;
+        };
+        let result = blocking_run(async move {
+            let query = format!(
+                "SELECT `SpnMapId`, `SpnX`, `SpnY`, `SpnMobId`, \
+                 `SpnLastDeath`, `SpnId`, `SpnStartTime`, `SpnEndTime`, \
+                 `SpnMobIdReplace` FROM `{}` ORDER BY `SpnId`",
+                table
+            );
+            sqlx::query(&query).fetch_all(get_pool()).await
+        });
+        match result {
+            Ok(r) => per_source.push(r),
+            Err(e) => {
+                tracing::error!("[mob] [spawn] read failed ({}): {}", src.table(), e);
+                return -1;
+            }
         }
-    };
+    }
+    let (rows, conflicts) = spawn_shards::merge(per_source, |row| row.try_get::<u32, _>(5).unwrap_or(0));
+    for c in &conflicts {
+        tracing::warn!("[mob] [spawn] SpnId {} in {} skipped: already loaded from {}",
+            c.id, sources[c.dropped].table(), sources[c.kept].table());
+    }
+    spawn_shards::record(&sources, rows.iter().map(|(src, row)| (row.try_get::<u32, _>(5).unwrap_or(0), *src)));
 
     let mut mstr = 0i32;
-    for row in &rows {
-        use sqlx::Row;
+    for (_, row) in &rows {
         // All Spawns columns are int(10) unsigned → read as u32, cast to dest type
         let startm: c_ushort = row.try_get::<u32, _>(0).unwrap_or(0) as c_ushort;
         let startx: c_ushort = row.try_get::<u32, _>(1).unwrap_or(0) as c_ushort;
@@ -501,7 +558,7 @@ pub unsafe fn mobspawn_read() -> c_int {
 
         if (*db).bl.id < MOB_START_NUM {
             let new_id = mob_get_new_id();
-            MAX_NORMAL_ID = new_id;
+            MAX_NORMAL_ID.store(new_id, Ordering::Relaxed);
             (*db).bl.m = startm;
             (*db).bl.x = startx;
             (*db).bl.y = starty;
@@ -530,9 +587,10 @@ pub unsafe fn mobspawn_read() -> c_int {
         mstr += 1;
     }
 
-    MOB_SPAWN_MAX = MOB_ID;
+    MOB_SPAWN_MAX.store(MOB_ID.load(Ordering::Relaxed), Ordering::Relaxed);
     libc::srand(gettick());
-    println!("[mob] [spawn] read done count={}", mstr);
+    println!("[mob] [spawn] read done rows={} count={} elapsed_ms={}",
+        rows.len(), mstr, started.elapsed().as_millis());
     0
 }
 
@@ -1009,6 +1067,10 @@ pub unsafe fn mob_handle_sub(mob: *mut MobSpawnData) {
 
     (*mob).time_ = (*mob).time_.wrapping_add(50);
 
+    if matches!((*mob).state, MOB_ALIVE | MOB_HIT) && mob_should_flee(mob) {
+        (*mob).state = MOB_ESCAPE;
+    }
+
     match (*mob).state {
         MOB_DEAD => {
             if (*mob).onetime != 0 {
@@ -1110,13 +1172,48 @@ pub unsafe fn mob_handle_sub(mob: *mut MobSpawnData) {
                 }
                 let bl = mob_resolve_target(mob);
                 (*mob).time_ = 0;
-                dispatch_ai(mob, bl, c"escape".as_ptr());
+                if data.flee_pct == 0 {
+                    dispatch_ai(mob, bl, c"escape".as_ptr());
+                } else if bl.is_null() || !mob_should_flee(mob) {
+                    (*mob).state = MOB_ALIVE;
+                } else {
+                    mob_flee_step(mob, bl);
+                }
             }
         }
         _ => {}
     }
 }
 
+/// Native flee threshold from mob_db; false when it is 0 (Lua decides).
+#[cfg(not(test))]
+unsafe fn mob_should_flee(mob: *mut MobSpawnData) -> bool {
+    use crate::servers::map::flee::should_flee;
+    let pct = (*mob).data.as_ref().map_or(0, |d| d.flee_pct);
+    pct > 0
+        && ((*mob).target != 0 || (*mob).attacker != 0)
+        && should_flee((*mob).current_vita, (*mob).maxvita, pct)
+}
+
+/// One step away from `bl`, trying the side-steps if the direct route is blocked.
+#[cfg(not(test))]
+unsafe fn mob_flee_step(mob: *mut MobSpawnData, bl: *mut BlockList) {
+    use crate::servers::map::flee::flee_sides;
+    let prev = (*mob).side;
+    for side in flee_sides((*mob).bl.x as c_int, (*mob).bl.y as c_int, (*bl).x as c_int, (*bl).y as c_int) {
+        (*mob).side = side;
+        (*mob).canmove = 0;
+        if move_mob(mob) != 0 {
+            if side != prev {
+                clif_sendmob_side(mob);
+            }
+            return;
+        }
+    }
+    // Cornered: keep facing as before.
+    (*mob).side = prev;
+}
+
 /// Resolves mob->target to a block_list*. Clears target if dead/invalid.
 #[cfg(not(test))]
 unsafe fn mob_resolve_target(mob: *mut MobSpawnData) -> *mut BlockList {
@@ -1211,22 +1308,24 @@ pub unsafe extern "C" fn mob_trap_look_ffi(bl: *mut BlockList, mut ap: ...) -> c
 /// Called every 50ms by the timer system.
 #[cfg(not(test))]
 pub unsafe fn mob_timer_spawns(_id: c_int, _n: c_int) -> c_int {
-    TIMERCHECK = TIMERCHECK.wrapping_add(1);
-
-    if MOB_SPAWN_START != MOB_SPAWN_MAX {
-        let mut x = MOB_SPAWN_START;
-        while x < MOB_SPAWN_MAX {
-            let mob = map_id2mob(x);
-            if !mob.is_null() {
-                tick_mob(mob);
+    use crate::servers::map::tick_budget::{mob_budget, Deadline};
+
+    // A pass cut short by the budget resumes at the first mob it missed; the
+    // phase counter only moves when a new pass starts.
+    let from = MOB_SWEEP_RESUME.swap(0, Ordering::Relaxed);
+    if from == 0 {
+        TIMERCHECK.fetch_add(1, Ordering::Relaxed); // wraps like the old u8
+    }
+    let deadline = Deadline::after(mob_budget());
+
+    // Bounds are re-read every step: a tick can spawn or free one-time mobs.
+    for (start, max) in [(&MOB_SPAWN_START, &MOB_SPAWN_MAX), (&MOB_ONETIME_START, &MOB_ONETIME_MAX)] {
+        let mut x = start.load(Ordering::Relaxed).max(from);
+        while x < max.load(Ordering::Relaxed) {
+            if deadline.passed() {
+                MOB_SWEEP_RESUME.store(x, Ordering::Relaxed);
+                return 0;
             }
-            x += 1;
-        }
-    }
-
-    if MOB_ONETIME_START != MOB_ONETIME_MAX {
-        let mut x = MOB_ONETIME_START;
-        while x < MOB_ONETIME_MAX {
             let mob = map_id2mob(x);
             if !mob.is_null() {
                 tick_mob(mob);
@@ -1235,15 +1334,15 @@ pub unsafe fn mob_timer_spawns(_id: c_int, _n: c_int) -> c_int {
         }
     }
 
-    if TIMERCHECK >= 30 {
-        TIMERCHECK = 0;
+    if TIMERCHECK.load(Ordering::Relaxed) >= 30 {
+        TIMERCHECK.store(0, Ordering::Relaxed);
     }
     0
 }
 
 #[cfg(not(test))]
 unsafe fn tick_mob(mob: *mut MobSpawnData) {
-    let tc = TIMERCHECK;
+    let tc = TIMERCHECK.load(Ordering::Relaxed);
     if tc % 5 == 0 {
         mob_secondduratimer(mob);
     }
@@ -1261,7 +1360,7 @@ unsafe fn tick_mob(mob: *mut MobSpawnData) {
 
 // ─── Movement functions ───────────────────────────────────────────────────────
 
-/// Shared warp-tile check used by all three move_mob variants.
+/// Warp-tile check applied to every mob step regardless of `MoveMode`.
 #[cfg(not(test))]
 unsafe fn warp_at(slot: *mut crate::database::map_db::MapData, dx: c_int, dy: c_int) -> bool {
     let bxs = (*slot).bxs as usize;
@@ -1284,6 +1383,32 @@ unsafe fn warp_at(slot: *mut crate::database::map_db::MapData, dx: c_int, dy: c_
     false
 }
 
+/// Client viewport half-extents in tiles.
+///
+/// The look-broadcast strips sent on each mob step are derived from these, so
+/// a client with a larger window sees mobs appear at its true screen edge
+/// instead of popping in. Defaults match the stock client (19×17 tiles).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub half_width: c_int,
+    pub half_height: c_int,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self { half_width: 9, half_height: 8 }
+    }
+}
+
+#[cfg(not(test))]
+fn viewport() -> Viewport {
+    let cfg = crate::ffi::config::config();
+    Viewport {
+        half_width: cfg.viewport_half_width,
+        half_height: cfg.viewport_half_height,
+    }
+}
+
 /// Compute viewport delta strip for a one-step move in `direction`.
 /// Returns `(x0, y0, x1, y1, dx, dy, nothingnew)`.
 #[cfg(not(test))]
@@ -1291,39 +1416,62 @@ unsafe fn viewport_delta(
     mob: *const MobSpawnData,
     slot: *mut crate::database::map_db::MapData,
 ) -> (c_int, c_int, c_int, c_int, c_int, c_int, bool) {
-    let backx = (*mob).bl.x as c_int;
-    let backy = (*mob).bl.y as c_int;
-    let xs = (*slot).xs as c_int;
-    let ys = (*slot).ys as c_int;
+    viewport_strip(
+        (*mob).side,
+        (*mob).bl.x as c_int,
+        (*mob).bl.y as c_int,
+        (*slot).xs as c_int,
+        (*slot).ys as c_int,
+        viewport(),
+    )
+}
+
+/// Pure strip geometry behind [`viewport_delta`].
+///
+/// `(x0, y0)` is the strip origin and `(x1, y1)` its extent; the strip is the
+/// row/column of tiles that scrolls into view when stepping from
+/// `(backx, backy)` towards `side` on an `xs`×`ys` map.
+fn viewport_strip(
+    side: c_int,
+    backx: c_int,
+    backy: c_int,
+    xs: c_int,
+    ys: c_int,
+    vp: Viewport,
+) -> (c_int, c_int, c_int, c_int, c_int, c_int, bool) {
+    let hw = vp.half_width;
+    let hh = vp.half_height;
+    let width = hw * 2 + 1;
+    let height = hh * 2 + 1;
     let (mut x0, mut y0) = (backx, backy);
     let (mut x1, mut y1) = (0, 0);
     let mut dx = backx;
     let mut dy = backy;
     let mut nothingnew = false;
 
-    match (*mob).side {
+    match side {
         0 => {
             // UP
             if backy > 0 {
                 dy = backy - 1;
-                x0 -= 9;
+                x0 -= hw;
                 if x0 < 0 {
                     x0 = 0;
                 }
-                y0 -= 9;
+                y0 -= hh + 1;
                 y1 = 1;
-                x1 = 19;
-                if y0 < 7 {
+                x1 = width;
+                if y0 < hh - 1 {
                     nothingnew = true;
                 }
-                if y0 == 7 {
-                    y1 += 7;
+                if y0 == hh - 1 {
+                    y1 += hh - 1;
                     y0 = 0;
                 }
-                if x0 + 19 + 9 >= xs {
-                    x1 += 9 - ((x0 + 19 + 9) - xs);
+                if x0 + width + hw >= xs {
+                    x1 += hw - ((x0 + width + hw) - xs);
                 }
-                if x0 <= 8 {
+                if x0 < hw {
                     x1 += x0;
                     x0 = 0;
                 }
@@ -1332,24 +1480,24 @@ unsafe fn viewport_delta(
         1 => {
             // Right
             if backx < xs {
-                x0 += 10;
-                y0 -= 8;
+                x0 += hw + 1;
+                y0 -= hh;
                 if y0 < 0 {
                     y0 = 0;
                 }
                 dx = backx + 1;
-                y1 = 17;
+                y1 = height;
                 x1 = 1;
-                if x0 > xs - 9 {
+                if x0 > xs - hw {
                     nothingnew = true;
                 }
-                if x0 == xs - 9 {
-                    x1 += 9;
+                if x0 == xs - hw {
+                    x1 += hw;
                 }
-                if y0 + 17 + 8 >= ys {
-                    y1 += 8 - ((y0 + 17 + 8) - ys);
+                if y0 + height + hh >= ys {
+                    y1 += hh - ((y0 + height + hh) - ys);
                 }
-                if y0 <= 7 {
+                if y0 < hh {
                     y1 += y0;
                     y0 = 0;
                 }
@@ -1358,24 +1506,24 @@ unsafe fn viewport_delta(
         2 => {
             // Down
             if backy < ys {
-                x0 -= 9;
+                x0 -= hw;
                 if x0 < 0 {
                     x0 = 0;
                 }
-                y0 += 9;
+                y0 += hh + 1;
                 dy = backy + 1;
                 y1 = 1;
-                x1 = 19;
-                if y0 + 8 > ys {
+                x1 = width;
+                if y0 + hh > ys {
                     nothingnew = true;
                 }
-                if y0 + 8 == ys {
-                    y1 += 8;
+                if y0 + hh == ys {
+                    y1 += hh;
                 }
-                if x0 + 19 + 9 >= xs {
-                    x1 += 9 - ((x0 + 19 + 9) - xs);
+                if x0 + width + hw >= xs {
+                    x1 += hw - ((x0 + width + hw) - xs);
                 }
-                if x0 <= 8 {
+                if x0 < hw {
                     x1 += x0;
                     x0 = 0;
                 }
@@ -1384,25 +1532,25 @@ unsafe fn viewport_delta(
         3 => {
             // Left
             if backx > 0 {
-                x0 -= 10;
-                y0 -= 8;
+                x0 -= hw + 1;
+                y0 -= hh;
                 if y0 < 0 {
                     y0 = 0;
                 }
-                y1 = 17;
+                y1 = height;
                 x1 = 1;
                 dx = backx - 1;
-                if x0 < 8 {
+                if x0 < hw - 1 {
                     nothingnew = true;
                 }
-                if x0 == 8 {
+                if x0 == hw - 1 {
                     x0 = 0;
-                    x1 += 8;
+                    x1 += hw - 1;
                 }
-                if y0 + 17 + 8 >= ys {
-                    y1 += 8 - ((y0 + 17 + 8) - ys);
+                if y0 + height + hh >= ys {
+                    y1 += hh - ((y0 + height + hh) - ys);
                 }
-                if y0 <= 7 {
+                if y0 < hh {
                     y1 += y0;
                     y0 = 0;
                 }
@@ -1413,7 +1561,7 @@ unsafe fn viewport_delta(
     (x0, y0, x1, y1, dx, dy, nothingnew)
 }
 
-/// Shared post-move broadcast used by move_mob variants.
+/// Post-move broadcast shared by every `MoveMode`.
 #[cfg(not(test))]
 unsafe fn broadcast_move(
     mob: *mut MobSpawnData,
@@ -1553,8 +1701,45 @@ unsafe fn check_pc_collision(moving_mob: *mut MobSpawnData, m: c_int, x: c_int,
     }
 }
 
+/// Which collision checks a single mob step applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveMode {
+    /// Occupants, directional object walls and map passability all block.
+    Normal,
+    /// Only directional object walls block; occupants and passability are ignored.
+    IgnoreObjects,
+    /// Like `Normal`, but a mob with a target walks through everything.
+    Ghost,
+}
+
+impl MoveMode {
+    /// Whether mobs/PCs/NPCs standing on the destination cell are probed
+    /// (setting `canmove = 1` when one blocks).
+    fn probes_occupants(self) -> bool {
+        !matches!(self, MoveMode::IgnoreObjects)
+    }
+
+    /// Decide whether the step is refused.
+    ///
+    /// `object_blocked` runs the directional object-wall checks and
+    /// `cell_blocked` the passability/occupant check; both are evaluated
+    /// lazily and in that order, matching the original per-variant code.
+    fn step_refused(
+        self,
+        has_target: bool,
+        object_blocked: impl FnOnce() -> bool,
+        cell_blocked: impl FnOnce() -> bool,
+    ) -> bool {
+        match self {
+            MoveMode::Normal => object_blocked() || cell_blocked(),
+            MoveMode::IgnoreObjects => object_blocked(),
+            MoveMode::Ghost => !has_target && (object_blocked() || cell_blocked()),
+        }
+    }
+}
+
 #[cfg(not(test))]
-pub unsafe fn move_mob(mob: *mut MobSpawnData) -> c_int {
+unsafe fn move_mob_mode(mob: *mut MobSpawnData, mode: MoveMode) -> c_int {
     let m = (*mob).bl.m as c_int;
     let backx = (*mob).bl.x as c_int;
     let backy = (*mob).bl.y as c_int;
@@ -1579,19 +1764,19 @@ pub unsafe fn move_mob(mob: *mut MobSpawnData) -> c_int {
         return 0;
     }
 
-    check_mob_collision(mob, m, dx, dy);
-    check_pc_collision(mob, m, dx, dy);
-    map_foreachincell(rust_mob_move, m, dx, dy, BL_NPC, mob as *mut _);
-
-    if clif_object_canmove(m, dx, dy, (*mob).side) != 0 {
-        (*mob).canmove = 0;
-        return 0;
+    if mode.probes_occupants() {
+        check_mob_collision(mob, m, dx, dy);
+        check_pc_collision(mob, m, dx, dy);
+        map_foreachincell(rust_mob_move, m, dx, dy, BL_NPC, mob as *mut _);
     }
-    if clif_object_canmove_from(m, backx, backy, (*mob).side) != 0 {
-        (*mob).canmove = 0;
-        return 0;
-    }
-    if map_canmove(m, dx, dy) == 1 || (*mob).canmove == 1 {
+
+    let side = (*mob).side;
+    let refused = mode.step_refused(
+        (*mob).target != 0,
+        || clif_object_canmove(m, dx, dy, side) != 0 || clif_object_canmove_from(m, backx, backy, side) != 0,
+        || map_canmove(m, dx, dy) == 1 || (*mob).canmove == 1,
+    );
+    if refused {
         (*mob).canmove = 0;
         return 0;
     }
@@ -1623,129 +1808,19 @@ pub unsafe fn move_mob(mob: *mut MobSpawnData) -> c_int {
 }
 
 #[cfg(not(test))]
-pub unsafe fn move_mob_ignore_object(mob: *mut MobSpawnData) -> c_int {
-    let m = (*mob).bl.m as c_int;
-    let backx = (*mob).bl.x as c_int;
-    let backy = (*mob).bl.y as c_int;
-    let slot = ffi_get_map_ptr((*mob).bl.m);
-    if slot.is_null() {
-        return 0;
-    }
-
-    let (x0, y0, x1, y1, mut dx, mut dy, nothingnew) = viewport_delta(mob, slot);
-    let xs = (*slot).xs as c_int;
-    let ys = (*slot).ys as c_int;
-    if dx >= xs {
-        dx = xs - 1;
-    }
-    if dy >= ys {
-        dy = ys - 1;
-    }
-    if warp_at(slot, dx, dy) {
-        return 0;
-    }
-
-    // No collision callbacks — ignore objects
-    if clif_object_canmove(m, dx, dy, (*mob).side) != 0 {
-        (*mob).canmove = 0;
-        return 0;
-    }
-    if clif_object_canmove_from(m, backx, backy, (*mob).side) != 0 {
-        (*mob).canmove = 0;
-        return 0;
-    }
-
-    let dx = if dx >= xs {
-        backx
-    } else if dx < 0 {
-        backx
-    } else {
-        dx
-    };
-    let dy = if dy >= ys {
-        backy
-    } else if dy < 0 {
-        backy
-    } else {
-        dy
-    };
+pub unsafe fn move_mob(mob: *mut MobSpawnData) -> c_int {
+    move_mob_mode(mob, MoveMode::Normal)
+}
 
-    if dx != backx || dy != backy {
-        (*mob).bx = backx as c_ushort;
-        (*mob).by_ = backy as c_ushort;
-        map_moveblock(&mut (*mob).bl, dx, dy);
-        broadcast_move(mob, x0, y0, x1, y1, nothingnew);
-        return 1;
-    }
-    0
+#[cfg(not(test))]
+pub unsafe fn move_mob_ignore_object(mob: *mut MobSpawnData) -> c_int {
+    move_mob_mode(mob, MoveMode::IgnoreObjects)
 }
 
+/// Collision checks only apply when the mob has no target.
 #[cfg(not(test))]
 pub unsafe fn moveghost_mob(mob: *mut MobSpawnData) -> c_int {
-    let m = (*mob).bl.m as c_int;
-    let backx = (*mob).bl.x as c_int;
-    let backy = (*mob).bl.y as c_int;
-    let slot = ffi_get_map_ptr((*mob).bl.m);
-    if slot.is_null() {
-        return 0;
-    }
-
-    let (x0, y0, x1, y1, mut dx, mut dy, nothingnew) = viewport_delta(mob, slot);
-    let xs = (*slot).xs as c_int;
-    let ys = (*slot).ys as c_int;
-    if dx >= xs {
-        dx = xs - 1;
-    }
-    if dy >= ys {
-        dy = ys - 1;
-    }
-    if warp_at(slot, dx, dy) {
-        return 0;
-    }
-
-    check_mob_collision(mob, m, dx, dy);
-    check_pc_collision(mob, m, dx, dy);
-    map_foreachincell(rust_mob_move, m, dx, dy, BL_NPC, mob as *mut _);
-
-    // Collision checks only apply when mob has no target
-    if (*mob).target == 0 {
-        if clif_object_canmove(m, dx, dy, (*mob).side) != 0 {
-            (*mob).canmove = 0;
-            return 0;
-        }
-        if clif_object_canmove_from(m, backx, backy, (*mob).side) != 0 {
-            (*mob).canmove = 0;
-            return 0;
-        }
-        if map_canmove(m, dx, dy) == 1 || (*mob).canmove == 1 {
-            (*mob).canmove = 0;
-            return 0;
-        }
-    }
-
-    let dx = if dx >= xs {
-        backx
-    } else if dx < 0 {
-        backx
-    } else {
-        dx
-    };
-    let dy = if dy >= ys {
-        backy
-    } else if dy < 0 {
-        backy
-    } else {
-        dy
-    };
-
-    if dx != backx || dy != backy {
-        (*mob).bx = backx as c_ushort;
-        (*mob).by_ = backy as c_ushort;
-        map_moveblock(&mut (*mob).bl, dx, dy);
-        broadcast_move(mob, x0, y0, x1, y1, nothingnew);
-        return 1;
-    }
-    0
+    move_mob_mode(mob, MoveMode::Ghost)
 }
 
 #[cfg(not(test))]
@@ -2006,6 +2081,9 @@ pub unsafe extern "C" fn rust_mob_dropitem(
 #[cfg(not(test))]
 pub unsafe fn mobdb_drops(mob: *mut MobSpawnData, sd: *mut std::ffi::c_void) -> c_int {
     // sd->bl is the first field — cast gives the block_list* for sl_doscript_blargs
+    // Only what the hook drops from the mob itself counts as its drop table
+    // and is scaled by the event rate (see `servers::map::rates`).
+    crate::servers::map::rates::set_native_drops((*mob).bl.id);
     sl_doscript_blargs(
         c"mobDrops".as_ptr(),
         std::ptr::null(),
@@ -2013,6 +2091,7 @@ pub unsafe fn mobdb_drops(mob: *mut MobSpawnData, sd: *mut std::ffi::c_void) ->
         sd as *mut BlockList,
         &raw mut (*mob).bl,
     );
+    crate::servers::map::rates::set_native_drops(0);
     let sd_typed = sd as *mut MapSessionData;
     for i in 0..MAX_INVENTORY {
         let slot = &(*mob).inventory[i];
@@ -2041,6 +2120,59 @@ pub unsafe fn mobdb_drops(mob: *mut MobSpawnData, sd: *mut std::ffi::c_void) ->
 
 // ─── USER-dependent mob functions (ported from c_src/mob.c) ──────────────────
 
+/// Faction of a player (`country`) or mob (`MobFaction`); `None` for other objects.
+///
+/// # Safety
+/// `bl` must be null or a live block list.
+pub unsafe fn faction_of(bl: *const BlockList) -> Option<u8> {
+    match Entity::from_bl(bl as *mut BlockList).ok()? {
+        Entity::Pc(sd) => Some((*sd).status.country as u8),
+        Entity::Mob(mob) => {
+            let data = (*mob).data;
+            Some(if data.is_null() { crate::servers::map::faction::WILD } else { (*data).faction })
+        }
+        _ => None,
+    }
+}
+
+/// Whether `bl` is a player under newbie protection (`servers::map::newbie`).
+///
+/// # Safety
+/// `bl` must be null or a live block list.
+pub unsafe fn newbie_protected(bl: *const BlockList) -> bool {
+    match Entity::from_bl(bl as *mut BlockList) {
+        Ok(Entity::Pc(sd)) => crate::servers::map::newbie::protects(
+            (*sd).status.level,
+            crate::config::Point::new((*sd).bl.m, (*sd).bl.x, (*sd).bl.y),
+        ),
+        _ => false,
+    }
+}
+
+/// The one targeting rule for mobs and players: may `attacker` attack `target`
+/// on the target's map? Objects without a faction can't target or be targeted.
+/// Newbie-protected players are never targets.
+///
+/// # Safety
+/// Both must be null or live block lists.
+pub unsafe fn can_target(attacker: *const BlockList, target: *const BlockList) -> bool {
+    let (Some(a), Some(b)) = (faction_of(attacker), faction_of(target)) else { return false };
+    if newbie_protected(target) {
+        return false;
+    }
+    let m = (*target).m;
+    let pvp = if ffi_map_is_loaded(m) { (*ffi_get_map_ptr(m)).pvp } else { 0 };
+    crate::servers::map::faction::can_target(a, b, pvp)
+}
+
+/// `can_target` for C: `clif_pc_damage` and `clif_mob_damage` check it
+/// before a player's swing lands, so player attacks follow the same factions,
+/// PvP flag and newbie protection as mob targeting. Returns 1 if allowed.
+#[no_mangle]
+pub unsafe extern "C" fn rust_can_target(attacker: *const BlockList, target: *const BlockList) -> c_int {
+    can_target(attacker, target) as c_int
+}
+
 /// va_list callback: selects a PC as this mob's target.
 /// Args (via va_list): `MOB* mob`.
 /// Reads `sd->status.dura_aether` to check sneak/cloak/hide, then conditionally
@@ -2058,6 +2190,9 @@ pub unsafe extern "C" fn rust_mob_find_target(bl: *mut BlockList, mut ap: ...) -
         return 0;
     }
     let sd = bl as *mut MapSessionData;
+    if !can_target(&(*mob).bl, bl) {
+        return 0;
+    }
     let seeinvis = if (*mob).data.is_null() {
         0i8
     } else {
@@ -2105,7 +2240,7 @@ pub unsafe extern "C" fn rust_mob_find_target(bl: *mut BlockList, mut ap: ...) -
         return 0;
     }
     if (*mob).target != 0 {
-        let num = (randomMT() & 0xFFFFFF) % 1000;
+        let num = crate::rng::below(1000);
         if num <= 499 && (*sd).status.gm_level < 50 {
             (*mob).target = (*sd).status.id;
         }
@@ -2130,16 +2265,8 @@ pub unsafe extern "C" fn rust_mob_attack(mob: *mut MobSpawnData, id: c_int) -> c
     if bl.is_null() {
         return 0;
     }
-    let sd: *mut MapSessionData = if (*bl).bl_type == BL_PC as u8 {
-        bl as *mut MapSessionData
-    } else {
-        std::ptr::null_mut()
-    };
-    let tmob: *mut MobSpawnData = if (*bl).bl_type == BL_MOB as u8 {
-        bl as *mut MobSpawnData
-    } else {
-        std::ptr::null_mut()
-    };
+    let sd: *mut MapSessionData = Entity::pc(bl).unwrap_or(std::ptr::null_mut());
+    let tmob: *mut MobSpawnData = Entity::mob(bl).unwrap_or(std::ptr::null_mut());
     if !sd.is_null() {
         if ((*sd).uFlags & U_FLAG_IMMORTAL != 0) || ((*sd).optFlags & OPT_FLAG_STEALTH != 0) {
             (*mob).target = 0;
@@ -2242,7 +2369,7 @@ pub unsafe extern "C" fn rust_mob_calc_critical(
     let equat = ((*db).hit + (*db).level + ((*db).might / 5) + 20)
         - ((*sd).status.level as c_int + ((*sd).grace / 2));
     let mut equat = equat - ((*sd).grace / 4) + (*sd).status.level as c_int;
-    let chance = ((randomMT() & 0xFFFFFF) % 100) as c_int;
+    let chance = crate::rng::below(100) as c_int;
     if equat < 5 {
         equat = 5;
     }
@@ -2395,4 +2522,69 @@ mod tests {
         println!("GlobalReg    = {} bytes", size_of::<GlobalReg>());
         println!("GfxViewer    = {} bytes", size_of::<GfxViewer>());
     }
+
+    #[test]
+    fn viewport_strip_default_matches_stock_client() {
+        let vp = Viewport::default();
+
+        // Open field: 100×100 map, mob at (50, 50).
+        assert_eq!(viewport_strip(0, 50, 50, 100, 100, vp), (41, 41, 19, 1, 50, 49, false));
+        assert_eq!(viewport_strip(1, 50, 50, 100, 100, vp), (60, 42, 1, 17, 51, 50, false));
+        assert_eq!(viewport_strip(2, 50, 50, 100, 100, vp), (41, 59, 19, 1, 50, 51, false));
+        assert_eq!(viewport_strip(3, 50, 50, 100, 100, vp), (40, 42, 1, 17, 49, 50, false));
+
+        // Near the map edges, where the strip is clipped or suppressed.
+        assert_eq!(viewport_strip(0, 5, 16, 100, 100, vp), (0, 0, 19, 8, 5, 15, false));
+        assert_eq!(viewport_strip(1, 81, 3, 100, 50, vp), (91, 0, 10, 17, 82, 3, false));
+        assert_eq!(viewport_strip(2, 95, 42, 100, 50, vp), (86, 51, 14, 1, 95, 43, true));
+        assert_eq!(viewport_strip(3, 18, 45, 100, 50, vp), (0, 37, 9, 13, 17, 45, false));
+    }
+
+    /// One row of a synthetic map: open floor, a cell occupied by another
+    /// entity, a cell behind a directional object wall, and an impassable tile.
+    #[derive(Clone, Copy)]
+    struct Cell {
+        occupied: bool,
+        object: bool,
+        wall: bool,
+    }
+
+    const ROW: [Cell; 4] = [
+        Cell { occupied: false, object: false, wall: false },
+        Cell { occupied: true, object: false, wall: false },
+        Cell { occupied: false, object: true, wall: false },
+        Cell { occupied: false, object: false, wall: true },
+    ];
+
+    fn can_step(mode: MoveMode, has_target: bool, cell: Cell) -> bool {
+        let canmove = mode.probes_occupants() && cell.occupied;
+        !mode.step_refused(has_target, || cell.object, || cell.wall || canmove)
+    }
+
+    #[test]
+    fn move_mode_collision_semantics() {
+        let walk = |mode, has_target| ROW.map(|c| can_step(mode, has_target, c));
+
+        for has_target in [false, true] {
+            assert_eq!(walk(MoveMode::Normal, has_target), [true, false, false, false]);
+            assert_eq!(walk(MoveMode::IgnoreObjects, has_target), [true, true, false, true]);
+        }
+        assert_eq!(walk(MoveMode::Ghost, false), [true, false, false, false]);
+        assert_eq!(walk(MoveMode::Ghost, true), [true, true, true, true]);
+    }
+
+    #[test]
+    fn take_id_is_unique_across_threads() {
+        let counter = AtomicU32::new(MOB_START_NUM);
+        let mut ids: Vec<c_uint> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| s.spawn(|| (0..256).map(|_| take_id(&counter)).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+        });
+        ids.sort_unstable();
+        // Every id from the start value up, each handed out exactly once.
+        assert_eq!(ids, (MOB_START_NUM..MOB_START_NUM + 1024).collect::<Vec<_>>());
+        assert_eq!(counter.load(Ordering::Relaxed), MOB_START_NUM + 1024);
+    }
 }
@@ -1,8 +1,16 @@
 pub mod mob;
 pub mod npc;
 #[cfg(feature = "map-game")]
+pub mod entity;
+#[cfg(feature = "map-game")]
 pub mod gm_command;
 #[cfg(feature = "map-game")]
+pub mod hotload;
+#[cfg(feature = "map-game")]
+pub mod instance;
+#[cfg(feature = "map-game")]
+pub mod motd;
+#[cfg(feature = "map-game")]
 pub mod pc;
 pub mod scripting;
 pub mod types;
@@ -0,0 +1,88 @@
+//! Message of the day, popped up once when a character enters the world.
+//!
+//! The file named by `motd` in server.yaml is read fresh on every login, so
+//! operators can edit it (or point the config at another file on reload)
+//! without restarting the map server.
+
+#[cfg(not(test))]
+use std::ffi::{c_char, c_int, CStr, CString};
+
+#[cfg(not(test))]
+use crate::game::pc::MapSessionData;
+
+#[cfg(not(test))]
+extern "C" {
+    fn clif_popup(sd: *mut MapSessionData, buf: *const c_char) -> c_int;
+}
+
+/// Read the MOTD template at `path`. Empty path, missing file, or a file with
+/// nothing but whitespace all mean "no MOTD".
+pub fn load(path: &str) -> Option<String> {
+    if path.is_empty() {
+        return None;
+    }
+    let text = std::fs::read_to_string(path).ok()?;
+    let text = text.trim_end();
+    if text.trim().is_empty() {
+        return None;
+    }
+    // Normalise CRLF files so the client sees one line break per line.
+    Some(text.replace("\r\n", "\n"))
+}
+
+/// Substitute `{name}` and `{level}` in the template.
+pub fn render(template: &str, name: &str, level: u32) -> String {
+    template
+        .replace("{name}", name)
+        .replace("{level}", &level.to_string())
+}
+
+/// Send the MOTD to a freshly spawned player. Called from intif_mmo_tosd once
+/// the character is fully set up; no-op when no MOTD is configured.
+#[cfg(not(test))]
+#[no_mangle]
+pub unsafe extern "C" fn rust_pc_send_motd(sd: *mut MapSessionData) -> c_int {
+    if sd.is_null() {
+        return 0;
+    }
+    let Some(template) = load(&crate::ffi::config::config().motd) else {
+        return 0;
+    };
+    let name = CStr::from_ptr((*sd).status.name.as_ptr()).to_string_lossy();
+    let text = render(&template, &name, (*sd).status.level as u32);
+    // Interior NULs would truncate the popup anyway; drop them.
+    let Ok(msg) = CString::new(text.replace('\0', "")) else {
+        return 0;
+    };
+    clif_popup(sd, msg.as_ptr());
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_substitutes_placeholders() {
+        let out = render("Welcome {name}!\nYou are level {level}, {name}.", "Yuria", 42);
+        assert_eq!(out, "Welcome Yuria!\nYou are level 42, Yuria.");
+    }
+
+    #[test]
+    fn load_treats_missing_or_blank_as_none() {
+        assert_eq!(load(""), None);
+        assert_eq!(load("/nonexistent/motd.txt"), None);
+
+        let dir = std::env::temp_dir();
+        let blank = dir.join(format!("yuri_motd_blank_{}.txt", std::process::id()));
+        std::fs::write(&blank, "  \n\n").unwrap();
+        assert_eq!(load(blank.to_str().unwrap()), None);
+
+        let real = dir.join(format!("yuri_motd_real_{}.txt", std::process::id()));
+        std::fs::write(&real, "line one\r\nline two\r\n").unwrap();
+        assert_eq!(load(real.to_str().unwrap()).as_deref(), Some("line one\nline two"));
+
+        let _ = std::fs::remove_file(blank);
+        let _ = std::fs::remove_file(real);
+    }
+}
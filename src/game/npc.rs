@@ -576,6 +576,14 @@ pub fn npc_warp_add(_file: *const c_char) -> c_int { 0 }
 /// Mirrors `warp_init` in `npc.c`.
 #[cfg(not(test))]
 pub async unsafe fn warp_init_async() -> c_int {
+    warp_load_async(None).await
+}
+
+/// Load warps into the map grid: every row of `Warps`, or with `only` just
+/// the ones leaving or entering that map (hot-loaded maps). A warp already
+/// linked into its cell is not added again.
+#[cfg(not(test))]
+pub async unsafe fn warp_load_async(only: Option<u16>) -> c_int {
     let p = get_pool();
 
     #[derive(sqlx::FromRow)]
@@ -589,12 +597,20 @@ pub async unsafe fn warp_init_async() -> c_int {
         dst_y:   i32,  // int(10) signed
     }
 
-    let rows: Vec<WarpRow> = match sqlx::query_as(
+    let mut sql = String::from(
         "SELECT `WarpId` AS warp_id, `SourceMapId` AS src_map, \
          `SourceX` AS src_x, `SourceY` AS src_y, \
          `DestinationMapId` AS dst_map, `DestinationX` AS dst_x, \
          `DestinationY` AS dst_y FROM `Warps`"
-    ).fetch_all(p).await {
+    );
+    if only.is_some() {
+        sql.push_str(" WHERE `SourceMapId` = ? OR `DestinationMapId` = ?");
+    }
+    let mut query = sqlx::query_as(&sql);
+    if let Some(m) = only {
+        query = query.bind(m).bind(m);
+    }
+    let rows: Vec<WarpRow> = match query.fetch_all(p).await {
         Ok(r) => r,
         Err(e) => { tracing::error!("[warp] query error: {e}"); return -1; }
     };
@@ -626,6 +642,22 @@ pub async unsafe fn warp_init_async() -> c_int {
                 row.dst_map, row.dst_x, row.dst_y);
         }
 
+        let idx = (row.src_x as usize / BLOCK_SIZE)
+            + (row.src_y as usize / BLOCK_SIZE) * md.bxs as usize;
+        // SAFETY: idx is in bounds when src coords are valid (checked above).
+        // If coords are out of bounds, idx can exceed bxs*bys — this is an inherited
+        // C behavior (npc.c does not guard this either).
+
+        let existing = md.warp.add(idx).read();
+        let key = (row.src_x, row.src_y, row.dst_map, row.dst_x, row.dst_y);
+        let mut w = existing;
+        while !w.is_null() && ((*w).x, (*w).y, (*w).tm, (*w).tx, (*w).ty) != key {
+            w = (*w).next;
+        }
+        if !w.is_null() {
+            continue;
+        }
+
         let war = Box::new(WarpList {
             x:    row.src_x as i32,
             y:    row.src_y as i32,
@@ -636,14 +668,6 @@ pub async unsafe fn warp_init_async() -> c_int {
             prev: std::ptr::null_mut(),
         });
         let war_ptr = Box::into_raw(war);
-
-        let idx = (row.src_x as usize / BLOCK_SIZE)
-            + (row.src_y as usize / BLOCK_SIZE) * md.bxs as usize;
-        // SAFETY: idx is in bounds when src coords are valid (checked above).
-        // If coords are out of bounds, idx can exceed bxs*bys — this is an inherited
-        // C behavior (npc.c does not guard this either).
-
-        let existing = md.warp.add(idx).read();
         (*war_ptr).next = existing;
         if !existing.is_null() {
             (*existing).prev = war_ptr;
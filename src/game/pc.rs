@@ -598,6 +598,7 @@ extern "C" {
 
     /// `int clif_sendminitext(USER* sd, const char* text)`
     pub fn clif_sendminitext(sd: *mut MapSessionData, text: *const c_char) -> c_int;
+    pub fn clif_Hacker(name: *mut c_char, reason: *const c_char) -> c_int;
 
     /// `int clif_sendadditem(USER* sd, int slot)`
     pub fn clif_sendadditem(sd: *mut MapSessionData, slot: c_int) -> c_int;
@@ -725,6 +726,12 @@ extern "C" {
     #[link_name = "rust_itemdb_stackamount"]
     pub fn itemdb_stackamount(id: c_uint) -> c_int;
 
+    #[link_name = "rust_itemdb_price"]
+    pub fn itemdb_price(id: c_uint) -> c_int;
+
+    #[link_name = "rust_itemdb_sell"]
+    pub fn itemdb_sell(id: c_uint) -> c_int;
+
     #[link_name = "rust_itemdb_type"]
     pub fn itemdb_type(id: c_uint) -> c_int;
 
@@ -837,8 +844,8 @@ extern "C" {
     #[link_name = "rust_session_set_eof"]
     pub fn rust_session_set_eof(fd: c_int, val: c_int);
 
-    // ── rnd / tick / time ─────────────────────────────────────────────────────
-    // rnd and gettick are already declared in mob.rs extern block.
+    // ── tick / time ─────────────────────────────────────────────────────
+    // gettick is already declared in mob.rs extern block; rolls use crate::rng.
     // cur_time is already declared in mob.rs extern block.
 
     // ── map_msg global array ──────────────────────────────────────────────────
@@ -924,11 +931,6 @@ extern "C" {
     /// Called by the C `Sql_ShowDebug(self)` macro; we invoke it directly in Rust.
     pub fn Sql_ShowDebug_(self_: *mut Sql, file: *const c_char, line: c_ulong);
 
-    // ── rnd — rnd(x) is a C macro over randomMT(); use randomMT() directly ──────
-    /// `unsigned int randomMT(void)` — Mersenne Twister generator.
-    /// Equivalent to: `(int)(randomMT() & 0xFFFFFF) % n`
-    pub fn randomMT() -> c_uint;
-
     // ── network encryption (net_crypt.c) ──────────────────────────────────────
     /// `int encrypt(int fd)` — encrypts the WFIFO buffer and returns the encrypted length.
     #[link_name = "encrypt"]
@@ -1749,6 +1751,7 @@ pub unsafe extern "C" fn rust_pc_requestmp(sd: *mut MapSessionData) -> c_int {
 pub unsafe extern "C" fn rust_pc_checklevel(sd: *mut MapSessionData) -> c_int {
     let path_raw = (*sd).status.class as c_int;
     let path = if path_raw > 5 { classdb_path(path_raw) } else { path_raw };
+    let old_level = (*sd).status.level;
 
     for x in (*sd).status.level as c_int..99 {
         let lvlxp = classdb_level(path, x);
@@ -1762,6 +1765,14 @@ pub unsafe extern "C" fn rust_pc_checklevel(sd: *mut MapSessionData) -> c_int {
         }
     }
 
+    if (*sd).status.level > old_level {
+        crate::game::scripting::events::emit_bl(
+            crate::game::scripting::events::LEVEL_UP,
+            &[&mut (*sd).bl as *mut BlockList as *mut c_void],
+            &[old_level as c_int, (*sd).status.level as c_int],
+        );
+    }
+
     0
 }
 
@@ -1844,7 +1855,11 @@ pub unsafe extern "C" fn rust_pc_givexp(
         return 0;
     }
 
-    let totalxp: i64 = (exp as i64).wrapping_mul(xprate as i64);
+    // Event multiplier is applied before the cap below, so checklevel sees the boosted total.
+    let totalxp: i64 = crate::servers::map::rates::scale_exp(
+        (exp as i64).wrapping_mul(xprate as i64),
+        crate::servers::map::rates::exp_rate(),
+    );
     let difxp: c_uint = 4294967295u32.wrapping_sub((*sd).status.exp);
 
     let (tempxp, defaultxp): (c_uint, c_uint) = if (difxp as i64) > totalxp {
@@ -2093,7 +2108,7 @@ pub unsafe extern "C" fn rust_pc_calcdamage(sd: *mut MapSessionData) -> c_float
     if (*sd).minSdam > 0 && (*sd).maxSdam > 0 {
         let mut ran = (*sd).maxSdam - (*sd).minSdam;
         if ran <= 0 { ran = 1; }
-        ran = ((randomMT() & 0xFFFFFF) % (ran as c_uint)) as c_int + (*sd).minSdam;
+        ran = crate::rng::below(ran as c_uint) as c_int + (*sd).minSdam;
         damage += (ran as c_float) / 2.0f32;
     }
 
@@ -3224,6 +3239,364 @@ pub unsafe extern "C" fn rust_pc_delitem(
     0
 }
 
+// ─── pc_buy_item / pc_sell_item ──────────────────────────────────────────────
+
+/// Slot summaries for `shop::room_for`: shop stock is unowned, unengraved and
+/// has no custom look, so only such slots can take more of it.
+unsafe fn shop_slot_views(sd: *const MapSessionData) -> Vec<crate::servers::map::shop::SlotView> {
+    let maxinv = ((*sd).status.maxinv as usize).min((*sd).status.inventory.len());
+    (*sd).status.inventory[..maxinv].iter()
+        .map(|inv| crate::servers::map::shop::SlotView {
+            id: inv.id,
+            amount: inv.amount,
+            plain: inv.owner == 0
+                && inv.real_name[0] == 0
+                && inv.custom_look == 0 && inv.custom_look_color == 0
+                && inv.custom_icon == 0 && inv.custom_icon_color == 0,
+        })
+        .collect()
+}
+
+/// `shop_slot_views` with protected slots also marked not plain, so crafting
+/// only consumes ordinary copies of a reagent.
+unsafe fn craft_slot_views(sd: *const MapSessionData) -> Vec<crate::servers::map::shop::SlotView> {
+    let mut views = shop_slot_views(sd);
+    for (v, inv) in views.iter_mut().zip((*sd).status.inventory.iter()) {
+        v.plain &= inv.protected == 0;
+    }
+    views
+}
+
+/// `int pc_buy_item(USER* sd, unsigned int item_id, int amount)` — buy
+/// `amount` of `item_id` at its item_db price as one step.
+///
+/// Price, money and inventory room are all checked before anything changes,
+/// so the player either pays and receives every unit or nothing happens.
+/// Returns 0 on success or a negative `ShopError::code`.
+#[cfg(not(test))]
+#[no_mangle]
+pub unsafe extern "C" fn rust_pc_buy_item(
+    sd:      *mut MapSessionData,
+    item_id: c_uint,
+    amount:  c_int,
+) -> c_int {
+    use crate::servers::map::shop::{buy_cost, room_for, ShopError};
+    if sd.is_null() { return ShopError::BadSlot.code(); }
+
+    let cost = match buy_cost(itemdb_price(item_id), amount, (*sd).status.money) {
+        Ok(c) => c,
+        Err(e) => return e.code(),
+    };
+
+    let owned = (*sd).status.inventory.iter().filter(|i| i.id == item_id).map(|i| i.amount).sum::<c_int>()
+        + (*sd).status.equip.iter().filter(|e| e.id == item_id).count() as c_int;
+    let room = room_for(
+        &shop_slot_views(sd), item_id,
+        itemdb_stackamount(item_id), itemdb_maxamount(item_id), owned,
+    );
+    if room < amount {
+        clif_sendminitext(sd, map_msg[MAP_ERRITMFULL].message.as_ptr());
+        return ShopError::NoSpace.code();
+    }
+
+    (*sd).status.money -= cost;
+    let mut it: Item = std::mem::zeroed();
+    it.id     = item_id;
+    it.amount = amount;
+    it.dura   = itemdb_dura(item_id);
+    rust_pc_additem(sd, &mut it);
+    clif_sendstatus(sd, SFLAG_XPMONEY);
+    0
+}
+
+/// `int pc_sell_item(USER* sd, int slot, int amount)` — sell `amount` units
+/// from inventory `slot` at the item_db sell price as one step.
+/// Returns 0 on success or a negative `ShopError::code`.
+#[cfg(not(test))]
+#[no_mangle]
+pub unsafe extern "C" fn rust_pc_sell_item(
+    sd:     *mut MapSessionData,
+    slot:   c_int,
+    amount: c_int,
+) -> c_int {
+    use crate::servers::map::shop::{sell_credit, ShopError};
+    if sd.is_null() || slot < 0 || slot >= (*sd).status.maxinv as c_int {
+        return ShopError::BadSlot.code();
+    }
+    let inv = &(*sd).status.inventory[slot as usize];
+    if inv.id == 0 { return ShopError::BadSlot.code(); }
+    if inv.protected != 0 { return ShopError::NotSellable.code(); }
+
+    let credit = match sell_credit(itemdb_sell(inv.id), amount, inv.amount, (*sd).status.money) {
+        Ok(c) => c,
+        Err(e) => return e.code(),
+    };
+
+    rust_pc_delitem(sd, slot, amount, 0);
+    (*sd).status.money += credit;
+    clif_sendstatus(sd, SFLAG_XPMONEY);
+    0
+}
+
+// ─── pc_bank_* ────────────────────────────────────────────────────────────────
+
+fn name_bytes(name: &[c_char]) -> &[u8] {
+    let bytes = unsafe { std::slice::from_raw_parts(name.as_ptr() as *const u8, name.len()) };
+    bytes.iter().position(|&b| b == 0).map_or(bytes, |n| &bytes[..n])
+}
+
+fn inv_key(i: &Item) -> crate::servers::map::bank::ItemKey<'_> {
+    crate::servers::map::bank::ItemKey {
+        id: i.id,
+        owner: i.owner,
+        name: name_bytes(&i.real_name),
+        look: (i.custom_look, i.custom_look_color),
+        icon: (i.custom_icon, i.custom_icon_color),
+    }
+}
+
+fn bank_key(b: &crate::servers::char::charstatus::BankData) -> crate::servers::map::bank::ItemKey<'_> {
+    crate::servers::map::bank::ItemKey {
+        id: b.item_id,
+        owner: b.owner,
+        name: name_bytes(&b.real_name),
+        look: (b.custom_look, b.custom_look_color),
+        icon: (b.custom_icon, b.custom_icon_color),
+    }
+}
+
+/// Bank slots usable by this player (`maxslots`, capped at the array).
+unsafe fn bank_limit(sd: *const MapSessionData) -> usize {
+    ((*sd).status.maxslots as usize).min((*sd).status.banks.len())
+}
+
+/// `int pc_bank_deposit_item(USER* sd, int inv_slot, int bank_slot, int amount)`
+/// — move `amount` units from inventory `inv_slot` into the bank as one step.
+///
+/// `bank_slot` < 0 picks the slot already holding the same item, else the
+/// first free one within `maxslots`. Returns 0 or a negative `BankError::code`.
+#[cfg(not(test))]
+#[no_mangle]
+pub unsafe extern "C" fn rust_pc_bank_deposit_item(
+    sd:        *mut MapSessionData,
+    inv_slot:  c_int,
+    bank_slot: c_int,
+    amount:    c_int,
+) -> c_int {
+    use crate::servers::map::bank::{deposit_slot, BankError, BankView};
+    if sd.is_null() || inv_slot < 0 || inv_slot >= (*sd).status.maxinv as c_int {
+        return BankError::BadSlot.code();
+    }
+    let inv = (*sd).status.inventory[inv_slot as usize];
+    if inv.id == 0 { return BankError::BadSlot.code(); }
+    if amount <= 0 { return BankError::BadAmount.code(); }
+    if amount > inv.amount { return BankError::BadSlot.code(); }
+
+    let views: Vec<BankView> = (*sd).status.banks[..bank_limit(sd)].iter()
+        .map(|b| BankView { key: bank_key(b), amount: b.amount })
+        .collect();
+    let want = (bank_slot >= 0).then_some(bank_slot as usize);
+    let slot = match deposit_slot(&views, views.len(), &inv_key(&inv), amount as u32, want) {
+        Ok(s) => s,
+        Err(e) => return e.code(),
+    };
+    drop(views);
+
+    let bank = &mut (*sd).status.banks[slot];
+    if bank.item_id == 0 {
+        *bank = std::mem::zeroed();
+        bank.item_id           = inv.id;
+        bank.owner             = inv.owner;
+        bank.time              = inv.time;
+        bank.custom_look       = inv.custom_look;
+        bank.custom_look_color = inv.custom_look_color;
+        bank.custom_icon       = inv.custom_icon;
+        bank.custom_icon_color = inv.custom_icon_color;
+        bank.protected         = inv.protected;
+        bank.real_name         = inv.real_name;
+        bank.note              = inv.note;
+    }
+    bank.amount += amount as u32;
+    rust_pc_delitem(sd, inv_slot, amount, 0);
+    0
+}
+
+/// `int pc_bank_withdraw_item(USER* sd, int bank_slot, int amount)` — move
+/// `amount` units from bank `bank_slot` into the inventory as one step.
+/// Returns 0 or a negative `BankError::code`.
+#[cfg(not(test))]
+#[no_mangle]
+pub unsafe extern "C" fn rust_pc_bank_withdraw_item(
+    sd:        *mut MapSessionData,
+    bank_slot: c_int,
+    amount:    c_int,
+) -> c_int {
+    use crate::servers::map::bank::BankError;
+    use crate::servers::map::shop::{room_for, SlotView};
+    if sd.is_null() || bank_slot < 0 || bank_slot as usize >= bank_limit(sd) {
+        return BankError::BadSlot.code();
+    }
+    let bank = (*sd).status.banks[bank_slot as usize];
+    if bank.item_id == 0 { return BankError::BadSlot.code(); }
+    if amount <= 0 { return BankError::BadAmount.code(); }
+    if amount as u32 > bank.amount { return BankError::BadSlot.code(); }
+
+    // Only slots holding this exact item (or empty ones) can take it.
+    let key = bank_key(&bank);
+    let maxinv = ((*sd).status.maxinv as usize).min((*sd).status.inventory.len());
+    let views: Vec<SlotView> = (*sd).status.inventory[..maxinv].iter()
+        .map(|i| SlotView { id: i.id, amount: i.amount, plain: inv_key(i).same(&key) })
+        .collect();
+    let owned = (*sd).status.inventory.iter().filter(|i| i.id == bank.item_id).map(|i| i.amount).sum::<c_int>()
+        + (*sd).status.equip.iter().filter(|e| e.id == bank.item_id).count() as c_int;
+    let room = room_for(
+        &views, bank.item_id,
+        itemdb_stackamount(bank.item_id), itemdb_maxamount(bank.item_id), owned,
+    );
+    if room < amount {
+        clif_sendminitext(sd, map_msg[MAP_ERRITMFULL].message.as_ptr());
+        return BankError::NoSpace.code();
+    }
+
+    let slot = &mut (*sd).status.banks[bank_slot as usize];
+    if slot.amount == amount as u32 {
+        *slot = std::mem::zeroed();
+    } else {
+        slot.amount -= amount as u32;
+    }
+    let mut it: Item = std::mem::zeroed();
+    it.id                = bank.item_id;
+    it.amount            = amount;
+    it.dura              = itemdb_dura(bank.item_id);
+    it.owner             = bank.owner;
+    it.time              = bank.time;
+    it.custom_look       = bank.custom_look;
+    it.custom_look_color = bank.custom_look_color;
+    it.custom_icon       = bank.custom_icon;
+    it.custom_icon_color = bank.custom_icon_color;
+    it.protected         = bank.protected;
+    it.real_name         = bank.real_name;
+    it.note              = bank.note;
+    rust_pc_additem(sd, &mut it);
+    0
+}
+
+/// `int pc_bank_gold(USER* sd, int amount)` — move `amount` gold into the
+/// bank (negative: out of it), refusing overdrafts and overflow of either
+/// balance. Returns 0 or a negative `BankError::code`.
+#[cfg(not(test))]
+#[no_mangle]
+pub unsafe extern "C" fn rust_pc_bank_gold(sd: *mut MapSessionData, amount: c_int) -> c_int {
+    use crate::servers::map::bank::{gold_transfer, BankError};
+    if sd.is_null() { return BankError::BadSlot.code(); }
+    match gold_transfer(amount as i64, (*sd).status.money, (*sd).status.bankmoney) {
+        Ok((money, bank)) => {
+            (*sd).status.money = money;
+            (*sd).status.bankmoney = bank;
+            clif_sendstatus(sd, SFLAG_XPMONEY);
+            0
+        }
+        Err(e) => e.code(),
+    }
+}
+
+// ─── pc_craft ─────────────────────────────────────────────────────────────────
+
+/// Item id named by a recipe identifier, or 0.
+unsafe fn recipe_product(name: &[c_char; 64]) -> c_uint {
+    if name[0] == 0 { return 0; }
+    let item = crate::database::item_db::searchname(name.as_ptr());
+    if item.is_null() { 0 } else { (*item).id }
+}
+
+/// `int pc_craft(USER* sd, unsigned int recipe_id)` — make `recipe_id` as
+/// one step: take every reagent, roll, and grant the product.
+///
+/// Reagents and room for the product (either product, when the recipe has a
+/// critical one) are checked before the roll, so a refusal changes nothing
+/// and can't be used to re-roll. Returns a `CraftOutcome::code` (> 0) or a
+/// negative `CraftError::code`.
+#[cfg(not(test))]
+#[no_mangle]
+pub unsafe extern "C" fn rust_pc_craft(sd: *mut MapSessionData, recipe_id: c_uint) -> c_int {
+    use crate::servers::map::craft::{after_plan, reagents, roll, take_plan, CraftError, CraftOutcome};
+    use crate::servers::map::shop::room_for;
+    if sd.is_null() { return CraftError::UnknownRecipe.code(); }
+    let recipe = crate::database::recipe_db::searchexist(recipe_id);
+    if recipe.is_null() { return CraftError::UnknownRecipe.code(); }
+    let recipe = &*recipe;
+
+    let product = recipe_product(&recipe.identifier);
+    if product == 0 { return CraftError::NoProduct.code(); }
+    let crit_product = match recipe_product(&recipe.crit_identifier) {
+        0 => product,
+        id => id,
+    };
+
+    let views = craft_slot_views(sd);
+    let plan = match take_plan(&views, &reagents(&recipe.materials)) {
+        Ok(p) => p,
+        Err(e) => return e.code(),
+    };
+    let after = after_plan(&views, &plan);
+    for id in [product, crit_product] {
+        let owned = after.iter().filter(|s| s.id == id).map(|s| s.amount).sum::<c_int>()
+            + (*sd).status.equip.iter().filter(|e| e.id == id).count() as c_int;
+        if room_for(&after, id, itemdb_stackamount(id), itemdb_maxamount(id), owned) < 1 {
+            clif_sendminitext(sd, map_msg[MAP_ERRITMFULL].message.as_ptr());
+            return CraftError::NoSpace.code();
+        }
+    }
+
+    for &(slot, amount) in &plan {
+        rust_pc_delitem(sd, slot as c_int, amount, 0);
+    }
+    let outcome = roll(
+        recipe.success_rate, recipe.crit_rate,
+        crate::rng::below(100), crate::rng::below(100),
+    );
+    let made = match outcome {
+        CraftOutcome::Success => product,
+        CraftOutcome::Critical => crit_product,
+        CraftOutcome::Failed => return outcome.code(),
+    };
+    let mut it: Item = std::mem::zeroed();
+    it.id     = made;
+    it.amount = 1;
+    it.dura   = itemdb_dura(made);
+    rust_pc_additem(sd, &mut it);
+    outcome.code()
+}
+
+// ─── pc_walk_allowed ──────────────────────────────────────────────────────────
+
+/// `int pc_walk_allowed(USER* sd)` — whether a walk step arriving now fits
+/// the character's speed (see `servers::map::movement`). GMs always pass.
+/// Reports repeat offenders to GMs and disconnects at `move_kick_after`.
+#[cfg(not(test))]
+#[no_mangle]
+pub unsafe extern "C" fn rust_pc_walk_allowed(sd: *mut MapSessionData) -> c_int {
+    use crate::servers::map::movement::{check, Verdict};
+    if sd.is_null() { return 0; }
+    if (*sd).status.gm_level != 0 { return 1; }
+    let name = || std::ffi::CStr::from_ptr((*sd).status.name.as_ptr()).to_string_lossy();
+    match check((*sd).status.id, (*sd).speed) {
+        Verdict::Allow => return 1,
+        Verdict::Reject { strikes } => {
+            tracing::debug!("[map] [movement] name={} step refused strikes={strikes}", name());
+        }
+        Verdict::Report { strikes } => {
+            tracing::warn!("[map] [movement] name={} walking too fast strikes={strikes}", name());
+            clif_Hacker((*sd).status.name.as_mut_ptr(), c"Speed hack".as_ptr());
+        }
+        Verdict::Kick { strikes } => {
+            tracing::warn!("[map] [movement] name={} disconnected strikes={strikes}", name());
+            rust_session_set_eof((*sd).fd, 1);
+        }
+    }
+    0
+}
+
 // ─── pc_dropitemmap ───────────────────────────────────────────────────────────
 
 /// `int pc_dropitemmap(USER* sd, int id, int type)` — drop one (or all) units
@@ -4243,10 +4616,16 @@ pub unsafe extern "C" fn rust_pc_getitemscript(
 
     if (*fl).data.id == 0 {
         // It's gold — credit the amount and remove from map.
-        (*sd).status.money += (*fl).data.amount as u32;
+        let gold = (*fl).data.amount;
+        (*sd).status.money += gold as u32;
         clif_sendstatus(sd, SFLAG_XPMONEY);
         clif_lookgone_pc(&mut (*fl).bl as *mut BlockList);
         map_delitem((*fl).bl.id);
+        crate::game::scripting::events::emit_bl(
+            crate::game::scripting::events::ITEM_PICKUP,
+            &[&mut (*sd).bl as *mut BlockList as *mut c_void],
+            &[0, gold],
+        );
 
         let mut _escape = [0i8; 255];
         Sql_EscapeString(sql_handle, _escape.as_mut_ptr(), (*fl).data.real_name.as_ptr());
@@ -4293,6 +4672,11 @@ pub unsafe extern "C" fn rust_pc_getitemscript(
 
     if add {
         rust_pc_additem(sd, &mut it as *mut _);
+        crate::game::scripting::events::emit_bl(
+            crate::game::scripting::events::ITEM_PICKUP,
+            &[&mut (*sd).bl as *mut BlockList as *mut c_void],
+            &[it.id as c_int, it.amount],
+        );
     }
 
     if (*sd).pickuptype > 0 && (*fl).data.amount > 0 {
@@ -4310,7 +4694,7 @@ pub unsafe extern "C" fn rust_pc_getitemscript(
 /// position without sending any client packets.
 ///
 /// Guards against attempting to set position on a mob object (bl.id >= MOB_START_NUM).
-/// Sets bl.m, bl.x, bl.y, and bl.type.
+/// Sets bl.m, bl.x, bl.y, and bl.type, and reports a map change to char_server.
 #[cfg(not(test))]
 #[no_mangle]
 pub unsafe extern "C" fn rust_pc_setpos(
@@ -4325,6 +4709,7 @@ pub unsafe extern "C" fn rust_pc_setpos(
     (*sd).bl.x  = x as u16;
     (*sd).bl.y  = y as u16;
     (*sd).bl.bl_type = BL_PC as c_uchar;
+    crate::ffi::map_char::notify_map((*sd).status.id, m as u16);
     0
 }
 
@@ -4605,8 +4990,7 @@ pub unsafe extern "C" fn rust_pc_diescript(sd: *mut MapSessionData) -> c_int {
     use crate::servers::char::charstatus::MAX_MAGIC_TIMERS;
     use crate::game::mob::{
         MAX_THREATCOUNT,
-        MOB_SPAWN_START, MOB_SPAWN_MAX,
-        MOB_ONETIME_START, MOB_ONETIME_MAX,
+        spawn_id_range, onetime_id_range,
         map_id2mob,
     };
 
@@ -4679,39 +5063,33 @@ pub unsafe extern "C" fn rust_pc_diescript(sd: *mut MapSessionData) -> c_int {
     }
 
     // Remove dead player from all spawn-mob threat tables.
-    if MOB_SPAWN_START != MOB_SPAWN_MAX {
-        let mut x = MOB_SPAWN_START;
-        while x < MOB_SPAWN_MAX {
-            let tmob = map_id2mob(x);
-            if !tmob.is_null() {
-                for i in 0..MAX_THREATCOUNT {
-                    if (*tmob).threat[i].user == (*sd).bl.id {
-                        (*tmob).threat[i].user   = 0;
-                        (*tmob).threat[i].amount = 0;
-                    }
+    for x in spawn_id_range() {
+        let tmob = map_id2mob(x);
+        if !tmob.is_null() {
+            for i in 0..MAX_THREATCOUNT {
+                if (*tmob).threat[i].user == (*sd).bl.id {
+                    (*tmob).threat[i].user   = 0;
+                    (*tmob).threat[i].amount = 0;
                 }
             }
-            x += 1;
         }
     }
 
     // Remove dead player from all one-time mob threat tables.
-    if MOB_ONETIME_START != MOB_ONETIME_MAX {
-        let mut x = MOB_ONETIME_START;
-        while x < MOB_ONETIME_MAX {
-            let tmob = map_id2mob(x);
-            if !tmob.is_null() {
-                for i in 0..MAX_THREATCOUNT {
-                    if (*tmob).threat[i].user == (*sd).bl.id {
-                        (*tmob).threat[i].user   = 0;
-                        (*tmob).threat[i].amount = 0;
-                    }
+    for x in onetime_id_range() {
+        let tmob = map_id2mob(x);
+        if !tmob.is_null() {
+            for i in 0..MAX_THREATCOUNT {
+                if (*tmob).threat[i].user == (*sd).bl.id {
+                    (*tmob).threat[i].user   = 0;
+                    (*tmob).threat[i].amount = 0;
                 }
             }
-            x += 1;
         }
     }
 
+    pc_apply_death_penalty(sd);
+
     // Reset combat modifiers.
     (*sd).enchanted  = 1.0_f32;
     (*sd).flank      = 0;
@@ -4729,6 +5107,55 @@ pub unsafe extern "C" fn rust_pc_diescript(sd: *mut MapSessionData) -> c_int {
     0
 }
 
+/// Exp and equipment durability loss for a death on the player's current
+/// map (`servers::map::death`).
+#[cfg(not(test))]
+unsafe fn pc_apply_death_penalty(sd: *mut MapSessionData) {
+    use crate::servers::char::charstatus::MAX_EQUIP;
+    let policy = crate::servers::map::death::policy_for(
+        (*sd).bl.m, crate::ffi::config::config().death_policy());
+
+    let exp = policy.exp_after((*sd).status.exp);
+    if exp != (*sd).status.exp {
+        tracing::debug!("[map] [death_penalty] char_id={} exp {} -> {}",
+            (*sd).status.id, (*sd).status.exp, exp);
+        (*sd).status.exp = exp;
+        clif_sendstatus(sd, SFLAG_XPMONEY);
+    }
+
+    if policy.dura_loss_pct > 0 {
+        for i in 0..MAX_EQUIP {
+            let eq = &mut (*sd).status.equip[i];
+            if eq.id == 0 { continue; }
+            eq.dura = policy.dura_after(eq.dura, itemdb_dura(eq.id));
+        }
+    }
+}
+
+/// Resurrect a dead player at the point the death policy for their map picks
+/// (bind point, town or in place). Backs `pc:deathRespawn()`.
+#[cfg(not(test))]
+pub unsafe fn pc_death_respawn(sd: *mut MapSessionData) -> c_int {
+    use crate::config::Point;
+    use crate::servers::map::death::{policy_for, respawn_point};
+    if sd.is_null() || (*sd).status.state != PC_DIE as i8 { return -1; }
+    let cfg = crate::ffi::config::config();
+    let policy = policy_for((*sd).bl.m, cfg.death_policy());
+    let bind = if (*sd).bindmap == 0 && (*sd).bindx == 0 && (*sd).bindy == 0 {
+        None
+    } else {
+        Some(Point::new((*sd).bindmap, (*sd).bindx as u16, (*sd).bindy as u16))
+    };
+    let here = Point::new((*sd).bl.m, (*sd).bl.x, (*sd).bl.y);
+    let to = respawn_point(policy.respawn, bind, cfg.death_town.unwrap_or(cfg.start_point), here);
+
+    (*sd).status.state = PC_ALIVE as i8;
+    (*sd).status.hp    = 100;
+    clif_sendstatus(sd, SFLAG_HPMP);
+    rust_pc_warp(sd, to.m as c_int, to.x as c_int, to.y as c_int);
+    0
+}
+
 /// `int pc_res(USER* sd)` — resurrects the player in-place.
 ///
 /// Sets state to alive, restores 100 HP, sends an HP/MP status update, and
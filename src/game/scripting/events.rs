@@ -0,0 +1,196 @@
+//! Named engine events for content scripts.
+//!
+//! `on(event, fn)` subscribes, `off(id)` unsubscribes and `emit(event, ...)`
+//! fires. The engine emits the core events below at fixed points, so a
+//! script can react to a level-up or a kill without the engine knowing which
+//! script cares. Handlers run in registration order; an error in one is
+//! logged and the rest still run. Script-defined event names are allowed and
+//! go through the same bus.
+//!
+//! Handlers live in the Lua registry and are dropped on `sl_reload`, since
+//! reloading re-runs every `on(...)` call.
+
+use std::ffi::{c_int, c_void};
+
+use mlua::{Function, IntoLuaMulti, Lua, MultiValue, Table, Value};
+
+/// `(pc)` — character fully loaded into the world (not fired on resume).
+pub const PLAYER_LOGIN: &str = "playerLogin";
+/// `(pc, oldLevel, newLevel)` — after `onLevel` raised the level.
+pub const LEVEL_UP: &str = "levelUp";
+/// `(mob, killer, info)` — killer is the last attacker's object, or nil.
+/// `info` is `{mobId, spawnId, map, killerId, credits}` where `credits` is
+/// `{ {id, amount, killingBlow}, ... }`: the killing blow first, then assists
+/// by threat (see `servers::map::kill_credit`).
+pub const MOB_KILL: &str = "mobKill";
+/// `(pc, itemId, amount)` — floor item picked up; itemId 0 is gold.
+pub const ITEM_PICKUP: &str = "itemPickup";
+
+/// Events the engine itself emits.
+pub const CORE_EVENTS: &[&str] = &[PLAYER_LOGIN, LEVEL_UP, MOB_KILL, ITEM_PICKUP];
+
+const REGISTRY_KEY: &str = "yuri.events";
+const NEXT_ID_KEY: &str = "yuri.events.next_id";
+
+/// `event name → { {id=, fn=}, ... }`, created on first use.
+fn handlers(lua: &Lua) -> mlua::Result<Table> {
+    if let Ok(t) = lua.named_registry_value::<Table>(REGISTRY_KEY) {
+        return Ok(t);
+    }
+    let t = lua.create_table()?;
+    lua.set_named_registry_value(REGISTRY_KEY, &t)?;
+    Ok(t)
+}
+
+/// Subscribe `f` to `event`; returns the handler id for `off`.
+pub fn on(lua: &Lua, event: &str, f: Function) -> mlua::Result<i64> {
+    let id = lua.named_registry_value::<Option<i64>>(NEXT_ID_KEY)?.unwrap_or(1);
+    lua.set_named_registry_value(NEXT_ID_KEY, id + 1)?;
+    let all = handlers(lua)?;
+    let list = match all.get::<Option<Table>>(event)? {
+        Some(l) => l,
+        None => {
+            let l = lua.create_table()?;
+            all.set(event, &l)?;
+            l
+        }
+    };
+    let entry = lua.create_table()?;
+    entry.set("id", id)?;
+    entry.set("fn", f)?;
+    list.push(entry)?;
+    Ok(id)
+}
+
+/// Remove handler `id` from whichever event holds it.
+pub fn off(lua: &Lua, id: i64) -> mlua::Result<bool> {
+    for pair in handlers(lua)?.pairs::<Value, Table>() {
+        let (_, list) = pair?;
+        for i in 1..=list.raw_len() {
+            let entry: Table = list.raw_get(i)?;
+            if entry.get::<i64>("id")? == id {
+                // table.remove semantics: keep the list dense and ordered.
+                lua.globals().get::<Table>("table")?.get::<Function>("remove")?.call::<()>((list, i))?;
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Drop every handler (before a script reload).
+pub fn clear(lua: &Lua) -> mlua::Result<()> {
+    lua.set_named_registry_value(REGISTRY_KEY, lua.create_table()?)
+}
+
+/// Call `event`'s handlers with `args`; returns how many ran without error.
+/// The list is snapshotted first, so handlers may `on`/`off` freely.
+pub fn emit_with(lua: &Lua, event: &str, args: MultiValue) -> usize {
+    let fns: Vec<Function> = match handlers(lua).and_then(|all| all.get::<Option<Table>>(event)) {
+        Ok(Some(list)) => list.sequence_values::<Table>()
+            .filter_map(|e| e.ok().and_then(|e| e.get::<Function>("fn").ok()))
+            .collect(),
+        Ok(None) => return 0,
+        Err(e) => {
+            tracing::warn!("[scripting] [event] {event}: {e}");
+            return 0;
+        }
+    };
+    let mut ok = 0;
+    for f in fns {
+        match f.call::<()>(args.clone()) {
+            Ok(()) => ok += 1,
+            Err(e) => tracing::warn!("[scripting] [event] {event} handler failed: {e}"),
+        }
+    }
+    ok
+}
+
+/// Emit on the global state. No-op before `sl_init`.
+///
+/// # Safety
+/// Game thread only (see `sl_state`).
+pub unsafe fn emit(event: &str, args: impl IntoLuaMulti) -> usize {
+    let Some(lua) = super::SL_STATE.as_ref() else { return 0 };
+    match args.into_lua_multi(lua) {
+        Ok(mv) => emit_with(lua, event, mv),
+        Err(e) => {
+            tracing::warn!("[scripting] [event] {event}: bad args: {e}");
+            0
+        }
+    }
+}
+
+/// Emit with block-list pointers converted to their script objects (null → nil)
+/// followed by integer `extra` arguments.
+///
+/// # Safety
+/// Every non-null pointer in `bls` must be a live block list. Game thread only.
+pub unsafe fn emit_bl(event: &str, bls: &[*mut c_void], extra: &[c_int]) -> usize {
+    let Some(lua) = super::SL_STATE.as_ref() else { return 0 };
+    let mut mv = MultiValue::new();
+    for &bl in bls {
+        mv.push_back(if bl.is_null() { Value::Nil } else { super::bl_to_lua(lua, bl).unwrap_or(Value::Nil) });
+    }
+    for &n in extra {
+        mv.push_back(Value::Integer(n as i64));
+    }
+    emit_with(lua, event, mv)
+}
+
+/// Emit `mobKill` for `mob` with its kill credits.
+///
+/// # Safety
+/// `mob` must be a live mob; `killer` null or a live block list. Game thread only.
+pub unsafe fn emit_mob_kill(mob: *mut crate::game::mob::MobSpawnData, killer: *mut c_void) -> usize {
+    let Some(lua) = super::SL_STATE.as_ref() else { return 0 };
+    let killer_id = if killer.is_null() { 0 } else { (*(killer as *const crate::database::map_db::BlockList)).id };
+    let credits = crate::servers::map::kill_credit::attribute(
+        killer_id,
+        (*mob).threat.iter().map(|t| (t.user, t.amount)),
+    );
+    let info = (|| -> mlua::Result<Table> {
+        let info = lua.create_table()?;
+        info.set("mobId", (*mob).mobid)?;
+        info.set("spawnId", (*mob).id)?;
+        info.set("map", (*mob).bl.m)?;
+        info.set("killerId", killer_id)?;
+        let list = lua.create_table()?;
+        for c in &credits {
+            let e = lua.create_table()?;
+            e.set("id", c.id)?;
+            e.set("amount", c.amount)?;
+            e.set("killingBlow", c.killing_blow)?;
+            list.push(e)?;
+        }
+        info.set("credits", list)?;
+        Ok(info)
+    })();
+    let info = match info {
+        Ok(t) => Value::Table(t),
+        Err(e) => {
+            tracing::warn!("[scripting] [event] {MOB_KILL}: {e}");
+            Value::Nil
+        }
+    };
+    let mut mv = MultiValue::new();
+    for bl in [&mut (*mob).bl as *mut crate::database::map_db::BlockList as *mut c_void, killer] {
+        mv.push_back(if bl.is_null() { Value::Nil } else { super::bl_to_lua(lua, bl).unwrap_or(Value::Nil) });
+    }
+    mv.push_back(info);
+    emit_with(lua, MOB_KILL, mv)
+}
+
+/// `on`, `off` and `emit` globals.
+pub fn register(lua: &Lua) -> mlua::Result<()> {
+    let g = lua.globals();
+    // on(event, fn) → handler id; see CORE_EVENTS for what the engine emits
+    g.set("on", lua.create_function(|lua, (event, f): (String, Function)| on(lua, &event, f))?)?;
+    // off(id) → true if the handler was registered
+    g.set("off", lua.create_function(|lua, id: i64| off(lua, id))?)?;
+    // emit(event, ...) → number of handlers that ran cleanly
+    g.set("emit", lua.create_function(|lua, (event, args): (String, MultiValue)| {
+        Ok(emit_with(lua, &event, args))
+    })?)?;
+    Ok(())
+}
@@ -48,10 +48,6 @@ extern "C" {
     pub fn map_readglobalreg(m: c_int, attrname: *const c_char) -> c_int;
     pub fn map_setglobalreg(m: c_int, attrname: *const c_char, val: c_int) -> c_int;
 
-    // Game-global registries (no self pointer)
-    pub fn map_readglobalgamereg(attrname: *const c_char) -> c_int;
-    pub fn map_setglobalgamereg(attrname: *const c_char, val: c_int) -> c_int;
-
     // --- Phase 3: globals ---
 
     // C game globals (extern int in map_server.h)
@@ -132,6 +132,217 @@ pub fn register(lua: &Lua) -> mlua::Result<()> {
         Ok(unsafe { (*mp).user as i64 })
     })?)?;
 
+    // mapCensus() → { [map_id] = {players=, mobs=, npcs=} }, truncated
+    // mapCensus(m) → {players=, mobs=, npcs=} or nil if m is not loaded
+    g.set("mapCensus", lua.create_function(|lua, m: Option<i32>| {
+        let entry = |c: &crate::database::map_db::MapCensus| -> mlua::Result<mlua::Table> {
+            let t = lua.create_table()?;
+            t.set("players", c.players)?;
+            t.set("mobs", c.mobs)?;
+            t.set("npcs", c.npcs)?;
+            Ok(t)
+        };
+        if let Some(m) = m {
+            if !(0..=u16::MAX as i32).contains(&m) { return Ok((Value::Nil, false)); }
+            return match crate::ffi::map_db::map_census(m as u16) {
+                Some(c) => Ok((Value::Table(entry(&c)?), false)),
+                None => Ok((Value::Nil, false)),
+            };
+        }
+        let report = crate::ffi::map_db::world_census();
+        let t = lua.create_table()?;
+        for c in &report.maps {
+            t.set(c.map_id, entry(c)?)?;
+        }
+        Ok((Value::Table(t), report.truncated))
+    })?)?;
+
+    // expRate() / dropRate() → current event multipliers (1.0 = normal)
+    g.set("expRate", lua.create_function(|_, ()| Ok(crate::servers::map::rates::exp_rate()))?)?;
+    g.set("dropRate", lua.create_function(|_, ()| Ok(crate::servers::map::rates::drop_rate()))?)?;
+
+    // spawnMob(mob, m, x, y [, opts]) → MobObject for one new one-time mob, or nil.
+    // mob is a mob_db id or name; opts = { start=, ["end"]=, replace=, owner= }.
+    // The handle reads as deleted once the mob is freed, so scripts may keep it.
+    g.set("spawnMob", lua.create_function(|_, (mob, m, x, y, opts): (Value, c_int, c_int, c_int, Option<mlua::Table>)| {
+        let mut spawned = spawn_onetime(mob, m, x, y, 1, opts)?;
+        Ok(spawned.pop())
+    })?)?;
+    // spawnMobs(mob, m, x, y, count [, opts]) → array of MobObject
+    g.set("spawnMobs", lua.create_function(|_, (mob, m, x, y, count, opts): (Value, c_int, c_int, c_int, c_int, Option<mlua::Table>)| {
+        spawn_onetime(mob, m, x, y, count, opts)
+    })?)?;
+
+    // createInstance(baseMap) → private copy's map id, or nil; warp a party into it
+    g.set("createInstance", lua.create_function(|_, base: i32| {
+        let id = unsafe { crate::game::instance::map_create_instance(base) };
+        Ok((id >= 0).then_some(id))
+    })?)?;
+    // destroyInstance(id) → true once occupants are sent back to the base map and it is freed
+    g.set("destroyInstance", lua.create_function(|_, id: i32| {
+        Ok(unsafe { crate::game::instance::map_destroy_instance(id) } == 0)
+    })?)?;
+    // instanceBase(id) → base map id of a live instance, or nil
+    g.set("instanceBase", lua.create_function(|_, id: i32| {
+        Ok(u16::try_from(id).ok().and_then(crate::servers::map::instance::base_of))
+    })?)?;
+
+    // setDeathPenalty(m, {exp=pct, dura=pct, respawn="bind"|"town"|"here"}) —
+    // per-map death policy; omitted keys keep the server-wide values
+    g.set("setDeathPenalty", lua.create_function(|_, (m, opts): (u16, mlua::Table)| {
+        use crate::servers::map::death::{self, DeathRespawn};
+        let mut p = crate::ffi::config::config().death_policy();
+        if let Some(v) = opts.get::<Option<u8>>("exp")? { p.exp_loss_pct = v.min(100); }
+        if let Some(v) = opts.get::<Option<u8>>("dura")? { p.dura_loss_pct = v.min(100); }
+        if let Some(v) = opts.get::<Option<String>>("respawn")? {
+            p.respawn = DeathRespawn::parse(&v)
+                .ok_or_else(|| mlua::Error::external("setDeathPenalty: respawn must be bind, town or here"))?;
+        }
+        death::set_override(m, p);
+        Ok(())
+    })?)?;
+    // clearDeathPenalty(m) → true if the map had its own policy
+    g.set("clearDeathPenalty", lua.create_function(|_, m: u16| {
+        Ok(crate::servers::map::death::clear_override(m))
+    })?)?;
+    // getDeathPenalty(m) → {exp, dura, respawn} in effect on m
+    g.set("getDeathPenalty", lua.create_function(|lua, m: u16| {
+        let p = crate::servers::map::death::policy_for(m, crate::ffi::config::config().death_policy());
+        let t = lua.create_table()?;
+        t.set("exp", p.exp_loss_pct)?;
+        t.set("dura", p.dura_loss_pct)?;
+        t.set("respawn", p.respawn.as_str())?;
+        Ok(t)
+    })?)?;
+
+    // kv.set(key, value, ttlSecs?) / kv.get(key) / kv.del(key) / kv.ttl(key) —
+    // world-scoped integer or string values with optional expiry; see servers::map::kv
+    let kv = lua.create_table()?;
+    kv.set("set", lua.create_function(|_, (key, value, ttl): (String, Value, Option<u64>)| {
+        use crate::servers::map::kv::{self, KvValue};
+        let value = match value {
+            Value::Integer(n) => KvValue::Int(n),
+            Value::Number(n) if n.fract() == 0.0 => KvValue::Int(n as i64),
+            Value::String(s) => KvValue::Str(s.to_str()?.to_string()),
+            Value::Nil => return Ok(kv::del(&key)),
+            other => return Err(mlua::Error::external(format!(
+                "kv.set: value must be an integer or string, got {}", other.type_name()
+            ))),
+        };
+        kv::set(&key, value, ttl).map_err(|e| mlua::Error::external(format!("kv.set: {e}")))?;
+        Ok(true)
+    })?)?;
+    kv.set("get", lua.create_function(|lua, key: String| {
+        use crate::servers::map::kv::{self, KvValue};
+        Ok(match kv::get(&key) {
+            Some(KvValue::Int(n)) => Value::Integer(n),
+            Some(KvValue::Str(s)) => Value::String(lua.create_string(&s)?),
+            None => Value::Nil,
+        })
+    })?)?;
+    kv.set("del", lua.create_function(|_, key: String| Ok(crate::servers::map::kv::del(&key)))?)?;
+    kv.set("ttl", lua.create_function(|_, key: String| Ok(crate::servers::map::kv::ttl(&key)))?)?;
+    g.set("kv", kv)?;
+
+    // countPlayers({minLevel, maxLevel, faction, mapId, class}) → online players
+    // matching every given field; faction is the player's country
+    g.set("countPlayers", lua.create_function(|_, filter: Option<mlua::Table>| {
+        let mut f = crate::servers::map::player_filter::PlayerFilter::default();
+        if let Some(t) = filter {
+            f.min_level = t.get("minLevel")?;
+            f.max_level = t.get("maxLevel")?;
+            f.faction = t.get("faction")?;
+            f.map = t.get("mapId")?;
+            f.class = t.get("class")?;
+        }
+        // Same bound as getUsers.
+        const MAX: usize = 4096;
+        let mut ptrs: Vec<*mut std::ffi::c_void> = vec![std::ptr::null_mut(); MAX];
+        let n = unsafe { sffi::sl_g_getusers(ptrs.as_mut_ptr(), MAX as c_int) } as usize;
+        Ok(ptrs[..n].iter().filter(|&&bl| unsafe {
+            let sd = &*(bl as *const crate::game::pc::MapSessionData);
+            f.matches(sd.status.level, sd.status.class, sd.status.country as u8, sd.bl.m)
+        }).count())
+    })?)?;
+
+    // canTarget(attackerId, targetId) → whether their factions allow an attack on the target's map
+    g.set("canTarget", lua.create_function(|_, (a, b): (u32, u32)| unsafe {
+        Ok(crate::game::mob::can_target(
+            sffi::map_id2bl(a) as *const crate::database::map_db::BlockList,
+            sffi::map_id2bl(b) as *const crate::database::map_db::BlockList,
+        ))
+    })?)?;
+
+    // getGmLevel(player) → level
+    g.set("getGmLevel", lua.create_function(|_, player: mlua::AnyUserData| {
+        let sd = player.borrow::<types::pc::PcObject>()?.ptr as *const crate::game::pc::MapSessionData;
+        Ok(unsafe { (*sd).status.gm_level } as i64)
+    })?)?;
+
+    // setGmLevel(player, level, reason, by?) → true, or false + error. Saved to
+    // ChaGMLevel and audited in GmAudit; see servers::map::gm_grant for who may
+    // change what.
+    g.set("setGmLevel", lua.create_function(|_, (player, level, why, by): (mlua::AnyUserData, i64, String, Option<mlua::AnyUserData>)| {
+        use crate::game::pc::MapSessionData;
+        use crate::servers::map::gm_grant;
+        let sd = player.borrow::<types::pc::PcObject>()?.ptr as *mut MapSessionData;
+        let actor = match &by {
+            Some(ud) => ud.borrow::<types::pc::PcObject>()?.ptr as *const MapSessionData,
+            None => std::ptr::null(),
+        };
+        unsafe {
+            let old = (*sd).status.gm_level;
+            let new = i8::try_from(level).unwrap_or(i8::MAX);
+            let checked = gm_grant::check(
+                (!actor.is_null()).then(|| (*actor).status.gm_level),
+                std::ptr::eq(actor, sd),
+                old,
+                new,
+            ).and_then(|()| gm_grant::reason(&why));
+            let reason = match checked {
+                Ok(r) => r,
+                Err(e) => return Ok((false, Some(e.to_string()))),
+            };
+            let name = |p: *const MapSessionData| CStr::from_ptr((*p).status.name.as_ptr()).to_string_lossy();
+            let target_name = name(sd);
+            let actor_name = if actor.is_null() { "".into() } else { name(actor) };
+            let grant = gm_grant::Grant {
+                actor_id: if actor.is_null() { 0 } else { (*actor).status.id },
+                actor_name: &actor_name,
+                target_id: (*sd).status.id,
+                target_name: &target_name,
+                old,
+                new,
+                reason,
+            };
+            let pool = crate::database::get_pool();
+            if let Err(e) = crate::database::blocking_run(gm_grant::persist(pool, &grant)) {
+                tracing::error!("[map] [gm_grant] {} {}->{} not saved: {e:#}", target_name, old, new);
+                return Ok((false, Some("could not save".to_string())));
+            }
+            (*sd).status.gm_level = new;
+            tracing::info!("[map] [gm_grant] by={:?} target={} level {}->{} reason={:?}",
+                actor_name, target_name, old, new, reason);
+        }
+        Ok((true, None))
+    })?)?;
+
+    // listTimers() → { {id, interval, nextFire, source}, ... } soonest first
+    g.set("listTimers", lua.create_function(|lua, ()| {
+        let t = lua.create_table()?;
+        for (i, info) in crate::ffi::timer::list().into_iter().enumerate() {
+            let e = lua.create_table()?;
+            e.set("id", info.id)?;
+            e.set("interval", info.interval)?;
+            e.set("nextFire", info.next_fire)?;
+            e.set("source", info.source)?;
+            t.set(i + 1, e)?;
+        }
+        Ok(t)
+    })?)?;
+    // cancelTimer(id) → true if a live timer was cancelled; callable from timer callbacks
+    g.set("cancelTimer", lua.create_function(|_, id: i32| Ok(crate::ffi::timer::cancel(id)))?)?;
+
     g.set("getMapXMax", lua.create_function(|_, m: i32| {
         if m < 0 { return Ok(0i64); }
         let mp = unsafe { get_map_ptr(m as u16) };
@@ -703,3 +914,47 @@ fn vs(args: &[Value], idx: usize) -> String {
         _                => String::new(),
     }).unwrap_or_default()
 }
+
+/// Spawn `count` one-time mobs via `mobspawn_onetime` and wrap each in a
+/// `MobObject` sharing its liveness flag.
+fn spawn_onetime(
+    mob: Value,
+    m: c_int,
+    x: c_int,
+    y: c_int,
+    count: c_int,
+    opts: Option<mlua::Table>,
+) -> mlua::Result<Vec<types::mob::MobObject>> {
+    let mob_id: c_uint = match mob {
+        Value::String(s) => {
+            let cs = CString::new(&*s.as_bytes()).map_err(mlua::Error::external)?;
+            unsafe { sffi::rust_mobdb_id(cs.as_ptr()) as c_uint }
+        }
+        Value::Integer(n) if n > 0 => n as c_uint,
+        Value::Number(f) if f > 0.0 => f as c_uint,
+        _ => 0,
+    };
+    if mob_id == 0 || count <= 0 {
+        return Ok(Vec::new());
+    }
+    let opt = |k: &str| -> mlua::Result<c_int> {
+        Ok(match &opts { Some(t) => t.get::<Option<c_int>>(k)?.unwrap_or(0), None => 0 })
+    };
+    let (start, end, replace, owner) = (opt("start")?, opt("end")?, opt("replace")?, opt("owner")?);
+    let ids = unsafe {
+        sffi::rust_mobspawn_onetime(mob_id, m, x, y, count, start, end, replace as c_uint, owner as c_uint)
+    };
+    if ids.is_null() {
+        return Ok(Vec::new());
+    }
+    // Collect before freeing the id array; a zero slot is a spawn that failed.
+    let spawned = (0..count as usize)
+        .map(|i| unsafe { *ids.add(i) })
+        .filter(|&id| id != 0)
+        .map(|id| unsafe { sffi::map_id2bl(id) })
+        .filter(|bl| !bl.is_null())
+        .map(types::mob::MobObject::new)
+        .collect();
+    unsafe { libc::free(ids as *mut std::ffi::c_void) };
+    Ok(spawned)
+}
@@ -2,14 +2,15 @@
 #![allow(non_snake_case, dead_code, unused_variables)]
 
 pub mod async_coro;
+pub mod events;
 pub mod ffi;
 pub mod globals;
+pub mod packets;
 pub mod types;
 
 use mlua::Lua;
 use std::ffi::{CStr, CString, c_char, c_int, c_uint};
 use std::os::raw::c_void;
-use std::sync::{Arc, atomic::{AtomicBool}};
 
 use crate::database::map_db::BlockList;
 use types::floor::FloorListObject;
@@ -49,6 +50,8 @@ pub fn sl_init() {
 
         register_types(&lua).expect("failed to register scripting types");
         globals::register(&lua).expect("failed to register scripting globals");
+        events::register(&lua).expect("failed to register scripting events");
+        packets::register(&lua).expect("failed to register packet handlers");
 
         SL_STATE = Some(lua);
 
@@ -62,11 +65,27 @@ pub fn sl_init() {
             sl_gstate = L as *mut c_void;
         }).expect("exec_raw failed: sl_gstate could not be initialised");
 
-        // Reload scripts (lua_dir comes from config).
+        // Load scripts (lua_dir comes from config). If config isn't usable yet
+        // the state is still fully set up, so a later sl_reload can load them.
+        if let Err(e) = script_dir() {
+            tracing::warn!("[scripting] scripting init deferred: {e:#}; scripts load on the next reload");
+            return;
+        }
         sl_reload();
+
+        if crate::ffi::config::config().lua_validate_refs {
+            sl_validate_item_refs();
+        }
     }
 }
 
+/// The configured script directory, or why there isn't one yet.
+fn script_dir() -> anyhow::Result<&'static std::path::Path> {
+    crate::ffi::config::try_config()
+        .ok_or_else(|| anyhow::anyhow!("config not loaded"))?
+        .script_dir()
+}
+
 /// Convert a Lua value (integer id or light userdata pointer) to a C pointer.
 /// Integer values that are negative or exceed `usize::MAX` map to null.
 fn lua_val_to_ptr(v: mlua::Value) -> *mut c_void {
@@ -88,10 +107,7 @@ macro_rules! ctor {
 fn register_types(lua: &Lua) -> mlua::Result<()> {
     let g = lua.globals();
     g.set("PC",       ctor!(lua, PcObject))?;
-    g.set("MOB", lua.create_function(|_, v: mlua::Value| Ok(MobObject {
-        ptr: lua_val_to_ptr(v),
-        deleted: Arc::new(AtomicBool::new(false)),
-    }))?)?;
+    g.set("MOB", lua.create_function(|_, v: mlua::Value| Ok(MobObject::new(lua_val_to_ptr(v))))?)?;
     // NPC(id) — mirrors the C npcl_ctor: looks up the NPC via map_id2bl.
     // The old C constructor called map_id2npc(id) which resolves the integer ID
     // to a real pointer; storing the raw integer as a pointer would cause a
@@ -161,7 +177,7 @@ fn register_types(lua: &Lua) -> mlua::Result<()> {
             _ => std::ptr::null_mut(),
         };
         if ptr.is_null() { return Ok(mlua::Value::Nil); }
-        Ok(mlua::Value::UserData(lua.create_userdata(MobObject { ptr, deleted: Arc::new(AtomicBool::new(false)) })?))
+        Ok(mlua::Value::UserData(lua.create_userdata(MobObject::new(ptr))?))
     })?)?;
     mob_tbl.set_metatable(Some(mob_mt));
     g.set("Mob", mob_tbl)?;
@@ -231,13 +247,49 @@ fn register_types(lua: &Lua) -> mlua::Result<()> {
 // ---------------------------------------------------------------------------
 pub unsafe fn sl_reload() -> c_int {
     let lua = sl_state();
-    let cfg = crate::ffi::config::config();
-    match load_lua_dir(lua, &cfg.lua_dir) {
+    let dir = match script_dir() {
+        Ok(d) => d.to_string_lossy(),
+        Err(e) => {
+            tracing::error!("[scripting] sl_reload skipped: {e:#}");
+            return -1;
+        }
+    };
+    // Scripts re-subscribe as they load.
+    if let Err(e) = events::clear(lua) {
+        tracing::warn!("[scripting] clearing event handlers failed: {e}");
+    }
+    if let Err(e) = packets::clear(lua) {
+        tracing::warn!("[scripting] clearing packet handlers failed: {e}");
+    }
+    match load_lua_dir(lua, &dir) {
         Ok(_)  => 0,
         Err(e) => { tracing::error!("[scripting] sl_reload failed: {e:#}"); -1 }
     }
 }
 
+/// Log every literal `ITEM(n)`/`RECIPE(n)` in `lua_dir` whose id is not in
+/// item_db/recipe_db. Returns the number of unresolved references.
+pub fn sl_validate_item_refs() -> c_int {
+    use crate::servers::map::script_refs::{validate_dir, RefKind};
+    let dir = match script_dir() {
+        Ok(d) => d,
+        Err(e) => {
+            tracing::warn!("[scripting] [validate_refs] skipped: {e:#}");
+            return 0;
+        }
+    };
+    let bad = validate_dir(dir, &|kind, id| match kind {
+        RefKind::Item => !crate::database::item_db::searchexist(id).is_null(),
+        RefKind::Recipe => !crate::database::recipe_db::searchexist(id).is_null(),
+    });
+    for (path, r) in &bad {
+        tracing::warn!("[scripting] [bad_ref] {}:{} {}({}) does not exist",
+            path.display(), r.line, r.kind.ctor(), r.id);
+    }
+    tracing::info!("[scripting] [validate_refs] unresolved={}", bad.len());
+    bad.len() as c_int
+}
+
 fn load_lua_file(lua: &Lua, path: &std::path::Path) -> mlua::Result<()> {
     let src = std::fs::read(path)
         .map_err(|e| mlua::Error::external(e))?;
@@ -300,14 +352,14 @@ pub unsafe fn sl_luasize() -> c_int {
 pub(crate) unsafe fn bl_to_lua(lua: &Lua, bl: *mut c_void) -> mlua::Result<mlua::Value> {
     debug_assert!(!bl.is_null(), "bl_to_lua: caller must not pass a null pointer");
     if bl.is_null() { return Ok(mlua::Value::Nil); }
-    let bl_type = (*(bl as *const BlockList)).bl_type as c_int;
-    match bl_type {
-        ffi::BL_PC   => lua.pack(PcObject       { ptr: bl }),
-        ffi::BL_MOB  => lua.pack(MobObject      { ptr: bl, deleted: Arc::new(AtomicBool::new(false)) }),
-        ffi::BL_NPC  => lua.pack(NpcObject      { ptr: bl }),
-        ffi::BL_ITEM => lua.pack(FloorListObject::new(bl)),
-        other => {
-            tracing::warn!("[scripting] bl_to_lua: unhandled bl_type={other:#04x}, returning nil");
+    use crate::game::entity::Entity;
+    match Entity::from_bl(bl as *mut BlockList) {
+        Ok(Entity::Pc(_))   => lua.pack(PcObject       { ptr: bl }),
+        Ok(Entity::Mob(_))  => lua.pack(MobObject::new(bl)),
+        Ok(Entity::Npc(_))  => lua.pack(NpcObject      { ptr: bl }),
+        Ok(Entity::Item(_)) => lua.pack(FloorListObject::new(bl)),
+        Err(e) => {
+            tracing::warn!("[scripting] bl_to_lua: {e}, returning nil");
             Ok(mlua::Value::Nil)
         }
     }
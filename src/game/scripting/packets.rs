@@ -0,0 +1,80 @@
+//! Lua handlers for client opcodes the core does not parse.
+//!
+//! `registerPacketHandler(opcode, fn)` installs `fn(pc, payload, opcode)` for
+//! an opcode outside `packet_hooks::CORE_OPCODES`; passing nil removes it.
+//! `payload` is a Lua string holding exactly the frame's declared payload.
+//! A handler that returns false (or errors) leaves the packet to the default
+//! "unknown" treatment. Handlers are dropped on `sl_reload`, like events.
+
+use std::ffi::c_void;
+
+use mlua::{Function, Lua, Table, Value};
+
+use crate::servers::map::packet_hooks;
+
+const REGISTRY_KEY: &str = "yuri.packet_handlers";
+
+/// `opcode → fn`, created on first use.
+fn handlers(lua: &Lua) -> mlua::Result<Table> {
+    if let Ok(t) = lua.named_registry_value::<Table>(REGISTRY_KEY) {
+        return Ok(t);
+    }
+    let t = lua.create_table()?;
+    lua.set_named_registry_value(REGISTRY_KEY, &t)?;
+    Ok(t)
+}
+
+/// Drop every handler (before a script reload).
+pub fn clear(lua: &Lua) -> mlua::Result<()> {
+    lua.set_named_registry_value(REGISTRY_KEY, lua.create_table()?)
+}
+
+/// Offer a decrypted frame from `sd` to the native, then the Lua handler for
+/// its opcode. True if one consumed it.
+///
+/// # Safety
+/// `sd` must be a live player. Game thread only.
+pub unsafe fn dispatch(sd: *mut c_void, frame: &[u8]) -> bool {
+    let Some(&opcode) = frame.get(3) else { return false };
+    let Some(payload) = packet_hooks::payload(frame) else { return false };
+    if let Some(h) = packet_hooks::native(opcode) {
+        if h(sd, payload) {
+            return true;
+        }
+    }
+    let Some(lua) = super::SL_STATE.as_ref() else { return false };
+    let f = match handlers(lua).and_then(|t| t.get::<Option<Function>>(opcode)) {
+        Ok(Some(f)) => f,
+        Ok(None) => return false,
+        Err(e) => {
+            tracing::warn!("[scripting] [packet] 0x{opcode:02X}: {e}");
+            return false;
+        }
+    };
+    let args = (|| Ok::<_, mlua::Error>((
+        super::bl_to_lua(lua, sd)?,
+        lua.create_string(payload)?,
+        opcode,
+    )))();
+    let result = args.and_then(|args| f.call::<Value>(args));
+    match result {
+        Ok(Value::Boolean(false)) => false,
+        Ok(_) => true,
+        Err(e) => {
+            tracing::warn!("[scripting] [packet] 0x{opcode:02X} handler failed: {e}");
+            false
+        }
+    }
+}
+
+/// `registerPacketHandler` global.
+pub fn register(lua: &Lua) -> mlua::Result<()> {
+    // registerPacketHandler(opcode, fn | nil); fn(pc, payload, opcode) → false to decline
+    lua.globals().set("registerPacketHandler", lua.create_function(|lua, (opcode, f): (u8, Option<Function>)| {
+        if packet_hooks::is_core(opcode) {
+            return Err(mlua::Error::external(packet_hooks::HookError::Core(opcode)));
+        }
+        handlers(lua)?.set(opcode, f)
+    })?)?;
+    Ok(())
+}
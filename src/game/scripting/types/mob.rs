@@ -31,6 +31,21 @@ pub struct MobObject {
 // MobObject values are created or used.
 unsafe impl Send for MobObject {}
 
+impl MobObject {
+    /// Wrap `ptr` (a `MobSpawnData`, or null). One-time mobs share a
+    /// liveness flag that `free_onetime` sets, so the handle stays safe to
+    /// hold after the mob dies; permanent spawns are never freed.
+    pub fn new(ptr: *mut c_void) -> Self {
+        let onetime = !ptr.is_null() && unsafe { (*(ptr as *const MobSpawnData)).onetime != 0 };
+        let deleted = if onetime {
+            crate::game::mob::onetime_token(unsafe { (*(ptr as *const MobSpawnData)).bl.id })
+        } else {
+            Arc::new(AtomicBool::new(false))
+        };
+        Self { ptr, deleted }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // C functions not yet in game/mob.rs extern block
 // ---------------------------------------------------------------------------
@@ -574,10 +589,13 @@ impl UserData for MobObject {
                     let deleted_flag = Arc::clone(&this.deleted);
                     return Ok(mlua::Value::Function(lua.create_function(
                         move |_, _: mlua::MultiValue| {
-                            if ptr.is_null() {
+                            if ptr.is_null() || deleted_flag.load(Ordering::Acquire) {
                                 return Ok(());
                             }
-                            unsafe { sffi::sl_g_delete_bl(ptr); }
+                            unsafe {
+                                crate::game::mob::retire_onetime_token((*(ptr as *const MobSpawnData)).bl.id);
+                                sffi::sl_g_delete_bl(ptr);
+                            }
                             deleted_flag.store(true, Ordering::Release);
                             Ok(())
                         }
@@ -730,6 +748,8 @@ impl UserData for MobObject {
                 "race" => data_int!(race),
                 "seeInvis" => data_int!(seeinvis),
                 "isBoss" => data_int!(isboss),
+                "fleePercent" => data_int!(flee_pct),
+                "faction" => data_int!(faction),
                 "getBlock" =>
                     return shared::make_getblock_fn(lua),
                 "getObjectsInCell" | "getAliveObjectsInCell" | "getObjectsInCellWithTraps" =>
@@ -819,7 +839,7 @@ impl UserData for MobObject {
                                     let id = unsafe { *spawned.add(i) };
                                     let bl = unsafe { sffi::map_id2bl(id) };
                                     if !bl.is_null() {
-                                        tbl.set(i + 1, lua.create_userdata(crate::game::scripting::types::mob::MobObject { ptr: bl, deleted: Arc::new(AtomicBool::new(false)) })?)?;
+                                        tbl.set(i + 1, lua.create_userdata(MobObject::new(bl))?)?;
                                     }
                                 }
                                 Ok(())
@@ -859,7 +879,7 @@ impl UserData for MobObject {
                             }
                         }
                     }
-                    tracing::debug!("[scripting] MobObject: unimplemented __index key={key:?}");
+                    crate::log_every!(debug, 10, "[scripting] MobObject: unimplemented __index key={key:?}");
                     Ok(mlua::Value::Nil)
                 }
             }
@@ -984,7 +1004,7 @@ impl UserData for MobObject {
                         unsafe { shared::gfx_write(&mut mob.gfx, key, val_to_int(&val), bytes_owned.as_deref()); }
                     }
                     _ => {
-                        tracing::debug!("[scripting] MobObject: unimplemented __newindex key={key:?}");
+                        crate::log_every!(debug, 10, "[scripting] MobObject: unimplemented __newindex key={key:?}");
                     }
                 }
                 Ok(())
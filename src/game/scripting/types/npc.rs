@@ -1,6 +1,5 @@
 use std::ffi::{c_int, c_uint, CString};
 use std::os::raw::c_void;
-use std::sync::{Arc, atomic::AtomicBool};
 use mlua::{MetaMethod, UserData, UserDataMethods};
 
 use crate::database::map_db::{BlockList, MapData};
@@ -294,7 +293,7 @@ impl UserData for NpcObject {
                             for (i, id) in ids.into_iter().enumerate() {
                                 let bl = unsafe { sffi::map_id2bl(id) };
                                 if !bl.is_null() {
-                                    tbl.set(i + 1, lua.create_userdata(MobObject { ptr: bl, deleted: Arc::new(AtomicBool::new(false)) })?)?;
+                                    tbl.set(i + 1, lua.create_userdata(MobObject::new(bl))?)?;
                                 }
                             }
                             Ok(mlua::Value::Table(tbl))
@@ -420,7 +419,7 @@ impl UserData for NpcObject {
                             }
                         }
                     }
-                    tracing::debug!("[scripting] NpcObject: unimplemented __index key={key:?}");
+                    crate::log_every!(debug, 10, "[scripting] NpcObject: unimplemented __index key={key:?}");
                     Ok(mlua::Value::Nil)
                 }
             }
@@ -520,7 +519,7 @@ impl UserData for NpcObject {
                 }
                 "gfxClone"    => nd.clone = val_to_int(&val) as i8,
                 _ => {
-                    tracing::debug!("[scripting] NpcObject: unimplemented __newindex key={key:?}");
+                    crate::log_every!(debug, 10, "[scripting] NpcObject: unimplemented __newindex key={key:?}");
                 }
             }
             Ok(())
@@ -21,6 +21,20 @@ pub struct PcObject {
 }
 unsafe impl Send for PcObject {}
 
+fn shop_result(rc: c_int) -> (bool, Option<&'static str>) {
+    match crate::servers::map::shop::ShopError::from_code(rc) {
+        Some(e) => (false, Some(e.as_str())),
+        None => (true, None),
+    }
+}
+
+fn bank_result(rc: c_int) -> (bool, Option<&'static str>) {
+    match crate::servers::map::bank::BankError::from_code(rc) {
+        Some(e) => (false, Some(e.as_str())),
+        None => (true, None),
+    }
+}
+
 fn val_to_int(v: &mlua::Value) -> c_int {
     match v {
         mlua::Value::Integer(i) => *i as c_int,
@@ -438,6 +452,10 @@ extern "C" {
     fn sl_pc_deductdura(sd: *mut c_void, eq: c_int, v: c_int);
     fn sl_pc_deductduraequip(sd: *mut c_void);
     fn sl_pc_deductdurainv(sd: *mut c_void, slot: c_int, v: c_int);
+    fn sl_pc_getdura(sd: *mut c_void, slot: c_int, max: *mut c_int) -> c_int;
+    fn sl_pc_getequipdura(sd: *mut c_void, eq: c_int, max: *mut c_int) -> c_int;
+    fn sl_pc_setdura(sd: *mut c_void, slot: c_int, value: c_int) -> c_int;
+    fn sl_pc_setequipdura(sd: *mut c_void, eq: c_int, value: c_int) -> c_int;
     fn sl_pc_hasequipped(sd: *mut c_void, item_id: c_uint) -> c_int;
     fn sl_pc_removeitemslot(sd: *mut c_void, slot: c_int, amount: c_int, typ: c_int);
     fn sl_pc_hasitem(sd: *mut c_void, item_id: c_uint, amount: c_int) -> c_int;
@@ -747,6 +765,9 @@ impl UserData for PcObject {
                 "backstab" => bool_!(sl_pc_backstab),
                 "flank" => bool_!(sl_pc_flank),
                 "spotTraps" => bool_!(sl_pc_spottraps),
+                "newbieProtected" => Ok(mlua::Value::Boolean(unsafe {
+                    crate::game::mob::newbie_protected(sd as *const crate::database::map_db::BlockList)
+                })),
                 "mute" => bool_!(sl_pc_status_mute),
                 "selfBar" => bool_!(sl_pc_selfbar),
                 "groupBars" => bool_!(sl_pc_groupbars),
@@ -953,8 +974,7 @@ impl UserData for PcObject {
                             }
                         }
                     }
-                    eprintln!("[DBG] PcObject unimplemented key={key:?}");
-                    tracing::debug!("[scripting] PcObject: unimplemented __index key={key:?}");
+                    crate::log_every!(debug, 10, "[scripting] PcObject: unimplemented __index key={key:?}");
                     Ok(mlua::Value::Nil)
                 }
             }
@@ -1142,8 +1162,8 @@ impl UserData for PcObject {
                         }
                     }
                     _ => {
-                        tracing::debug!(
-                            "[scripting] PcObject: unimplemented __newindex key={key:?}"
+                        crate::log_every!(
+                            debug, 10, "[scripting] PcObject: unimplemented __newindex key={key:?}"
                         );
                     }
                 }
@@ -1159,6 +1179,15 @@ impl UserData for PcObject {
         methods.add_method(
             "removeHealth",
             |_, this, (damage, caster): (c_int, c_int)| {
+                // PvP damage never lands on a newbie-protected player.
+                let target = this.ptr as *const crate::database::map_db::BlockList;
+                let attacker = if caster > 0 { unsafe { sffi::map_id2sd(caster as u32) } } else { std::ptr::null_mut() };
+                if damage > 0 && !attacker.is_null() && attacker != this.ptr
+                    && unsafe { crate::game::mob::newbie_protected(target) }
+                {
+                    unsafe { sl_pc_sendminitext(attacker, c"That player is under newbie protection.".as_ptr()) };
+                    return Ok(());
+                }
                 unsafe { sl_pc_removehealth(this.ptr, damage, caster) };
                 Ok(())
             },
@@ -1171,6 +1200,10 @@ impl UserData for PcObject {
             unsafe { sl_pc_resurrect(this.ptr) };
             Ok(())
         });
+        // Resurrect where the map's death policy says (bind point, town or here).
+        methods.add_method("deathRespawn", |_, this, ()| {
+            Ok(unsafe { crate::game::pc::pc_death_respawn(this.ptr as *mut crate::game::pc::MapSessionData) } == 0)
+        });
         methods.add_method("showHealth", |_, this, (damage, typ): (c_int, c_int)| {
             unsafe { sl_pc_showhealth(this.ptr, damage, typ) };
             Ok(())
@@ -1197,6 +1230,29 @@ impl UserData for PcObject {
             unsafe { sl_pc_warp(this.ptr, m, x, y) };
             Ok(())
         });
+        // Warp every group member online on this server in one pass, so no
+        // script yield can interleave with members moving. Members on another
+        // map server or offline are skipped; returns how many were warped.
+        methods.add_method("warpGroup", |_, this, (m, x, y): (c_int, c_int, c_int)| {
+            const MAX_MEMBERS: usize = 256;
+            let mut ids = [0u32; MAX_MEMBERS];
+            let n = unsafe {
+                sffi::sl_pc_getgroup(this.ptr, ids.as_mut_ptr(), MAX_MEMBERS as c_int)
+            };
+            let mut members: Vec<u32> = ids[..n.clamp(0, MAX_MEMBERS as c_int) as usize].to_vec();
+            members.sort_unstable();
+            members.dedup();
+            let mut warped = 0;
+            for id in members.into_iter().filter(|&id| id != 0) {
+                let sd = unsafe { sffi::map_id2sd(id) };
+                if sd.is_null() {
+                    continue;
+                }
+                unsafe { sl_pc_warp(sd, m, x, y) };
+                warped += 1;
+            }
+            Ok(warped)
+        });
         methods.add_method("refresh", |_, this, ()| {
             unsafe { sl_pc_refresh(this.ptr) };
             Ok(())
@@ -1288,6 +1344,26 @@ impl UserData for PcObject {
             unsafe { sl_pc_deductdurainv(this.ptr, slot, v) };
             Ok(())
         });
+        // Current and max durability, or nil for an empty slot.
+        methods.add_method("getDura", |_, this, slot: c_int| {
+            let mut max = 0;
+            let dura = unsafe { sl_pc_getdura(this.ptr, slot, &mut max) };
+            Ok(if dura >= 0 { (Some(dura), Some(max)) } else { (None, None) })
+        });
+        methods.add_method("getEquipDura", |_, this, eq: c_int| {
+            let mut max = 0;
+            let dura = unsafe { sl_pc_getequipdura(this.ptr, eq, &mut max) };
+            Ok(if dura >= 0 { (Some(dura), Some(max)) } else { (None, None) })
+        });
+        // Clamped to 0..max; returns the stored value, or nil for an empty slot.
+        methods.add_method("setDura", |_, this, (slot, v): (c_int, c_int)| {
+            let dura = unsafe { sl_pc_setdura(this.ptr, slot, v) };
+            Ok((dura >= 0).then_some(dura))
+        });
+        methods.add_method("setEquipDura", |_, this, (eq, v): (c_int, c_int)| {
+            let dura = unsafe { sl_pc_setequipdura(this.ptr, eq, v) };
+            Ok((dura >= 0).then_some(dura))
+        });
         methods.add_method("hasEquipped", |_, this, id: c_uint| {
             Ok(unsafe { sl_pc_hasequipped(this.ptr, id) } != 0)
         });
@@ -1368,11 +1444,10 @@ impl UserData for PcObject {
         });
 
         // ── Social / network ─────────────────────────────────────────────────
-        methods.add_method("speak", |_, this, (msg, typ): (String, c_int)| {
-            if let Ok(cs) = CString::new(msg.as_bytes()) {
-                let len = cs.as_bytes().len() as c_int;
-                unsafe { sl_pc_speak(this.ptr, cs.as_ptr(), len, typ) };
-            }
+        methods.add_method("speak", |_, this, (msg, typ): (mlua::String, c_int)| {
+            let cs = super::shared::chat_cstring("speak", &msg.as_bytes());
+            let len = cs.as_bytes().len() as c_int;
+            unsafe { sl_pc_speak(this.ptr, cs.as_ptr(), len, typ) };
             Ok(())
         });
         methods.add_method(
@@ -1689,6 +1764,16 @@ impl UserData for PcObject {
         methods.add_method("hasItemDura", |_, this, (id, amount): (c_int, c_int)| {
             Ok(unsafe { sl_pc_hasitemdura(this.ptr, id as c_uint, amount as c_uint) } != 0)
         });
+        // Shop transactions: money and items change together or not at all.
+        // Returns true, or false plus a reason ("not_enough_money", "no_space", ...).
+        methods.add_method("buyItem", |_, this, (id, amount): (c_uint, c_int)| {
+            let rc = unsafe { crate::game::pc::rust_pc_buy_item(this.ptr as *mut _, id, amount) };
+            Ok(shop_result(rc))
+        });
+        methods.add_method("sellItem", |_, this, (slot, amount): (c_int, c_int)| {
+            let rc = unsafe { crate::game::pc::rust_pc_sell_item(this.ptr as *mut _, slot, amount) };
+            Ok(shop_result(rc))
+        });
 
         // ── Bank ─────────────────────────────────────────────────────────────────
         methods.add_method("checkBankItems", |_, this, slot: c_int| {
@@ -1719,6 +1804,32 @@ impl UserData for PcObject {
             }
             Ok(())
         });
+        // Bank transactions: each move happens in one step or not at all.
+        // Returns true, or false plus a reason ("bank_full", "no_space", ...).
+        methods.add_method("bankDepositItem", |_, this, (slot, amount, bank_slot): (c_int, c_int, Option<c_int>)| {
+            let rc = unsafe { crate::game::pc::rust_pc_bank_deposit_item(this.ptr as *mut _, slot, bank_slot.unwrap_or(-1), amount) };
+            Ok(bank_result(rc))
+        });
+        methods.add_method("bankWithdrawItem", |_, this, (bank_slot, amount): (c_int, c_int)| {
+            let rc = unsafe { crate::game::pc::rust_pc_bank_withdraw_item(this.ptr as *mut _, bank_slot, amount) };
+            Ok(bank_result(rc))
+        });
+        // bankGold(n): n > 0 deposits, n < 0 withdraws
+        methods.add_method("bankGold", |_, this, amount: c_int| {
+            let rc = unsafe { crate::game::pc::rust_pc_bank_gold(this.ptr as *mut _, amount) };
+            Ok(bank_result(rc))
+        });
+        // craft(recipeId) → "success" | "critical" | "failed", or nil plus a
+        // reason ("missing_reagents", "no_space", ...). Reagents are used on
+        // "failed"; nothing changes on nil.
+        methods.add_method("craft", |_, this, recipe_id: c_uint| {
+            use crate::servers::map::craft::{CraftError, CraftOutcome};
+            let rc = unsafe { crate::game::pc::rust_pc_craft(this.ptr as *mut _, recipe_id) };
+            Ok(match CraftOutcome::from_code(rc) {
+                Some(o) => (Some(o.as_str()), None),
+                None => (None, CraftError::from_code(rc).map(CraftError::as_str)),
+            })
+        });
         methods.add_method("bankCheckAmount", |_, this, (item, amount, owner, engrave): (c_int, c_int, c_int, String)| {
             let cs = CString::new(engrave.as_bytes()).ok();
             Ok(cs.map_or(0, |c| unsafe { sl_pc_bankcheckamount(this.ptr, item as c_uint, amount as c_uint, owner as c_uint, c.as_ptr()) }))
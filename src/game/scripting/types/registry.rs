@@ -244,14 +244,12 @@ impl UserData for MapRegObject {
 // ---------------------------------------------------------------------------
 impl UserData for GameRegObject {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        // Same store as the C game registry and Rust world state (server_state).
         methods.add_meta_method(MetaMethod::Index, |_, _this, key: String| {
-            let ckey = CString::new(key).map_err(mlua::Error::external)?;
-            let val = unsafe { sffi::map_readglobalgamereg(ckey.as_ptr()) };
-            Ok(val)
+            Ok(crate::servers::map::server_state::get(&key))
         });
         methods.add_meta_method(MetaMethod::NewIndex, |_, _this, (key, val): (String, mlua::Value)| {
-            let ckey = CString::new(key).map_err(mlua::Error::external)?;
-            unsafe { sffi::map_setglobalgamereg(ckey.as_ptr(), val_to_int(&val)?); }
+            crate::servers::map::server_state::set(&key, val_to_int(&val)? as i64);
             Ok(())
         });
     }
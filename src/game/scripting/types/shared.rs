@@ -15,9 +15,31 @@ use crate::game::scripting::types::item::fixed_str;
 use crate::game::scripting::types::mob::MobObject;
 use crate::game::scripting::types::npc::NpcObject;
 use crate::game::scripting::types::pc::PcObject;
+use crate::servers::map::rates;
 
 // ── Object collection methods ─────────────────────────────────────────────────
 
+/// True if `m` is a loaded map and `(x, y)`, when given, lies on it. Logs
+/// (rate-limited) and returns false otherwise, so a content mistake yields an
+/// empty result instead of a foreach over bad map memory.
+unsafe fn query_origin_ok(what: &str, m: c_int, xy: Option<(c_int, c_int)>) -> bool {
+    let mp = if (0..=u16::MAX as c_int).contains(&m) { get_map_ptr(m as u16) } else { std::ptr::null_mut() };
+    if mp.is_null() || (*mp).registry.is_null() || (*mp).xs == 0 {
+        crate::log_every!(warn, 10, "[scripting] {}: map {} is not loaded", what, m);
+        return false;
+    }
+    if let Some((x, y)) = xy {
+        if !(0..(*mp).xs as c_int).contains(&x) || !(0..(*mp).ys as c_int).contains(&y) {
+            crate::log_every!(
+                warn, 10,
+                "[scripting] {}: ({}, {}) is off map {} ({}x{})", what, x, y, m, (*mp).xs, (*mp).ys
+            );
+            return false;
+        }
+    }
+    true
+}
+
 /// Create a `getObjectsInCell` / `getAliveObjectsInCell` / `getObjectsInCellWithTraps`
 /// Lua function. `variant` is the method name string that selects the FFI call.
 /// Mirrors `bll_getobjects_cell` / `bll_getaliveobjects_cell` from scripting.c.
@@ -26,6 +48,9 @@ pub fn make_cell_query_fn(lua: &mlua::Lua, variant: &str) -> mlua::Result<mlua::
     lua.create_function(
         move |lua, (_self, m, x, y, bl_type): (mlua::Value, c_int, c_int, c_int, c_int)| {
             const MAX: usize = 256;
+            if !unsafe { query_origin_ok(&variant, m, Some((x, y))) } {
+                return lua.create_table();
+            }
             let mut ptrs = vec![std::ptr::null_mut::<c_void>(); MAX];
             let raw_count = unsafe {
                 match variant.as_str() {
@@ -73,6 +98,14 @@ pub fn make_area_query_fn(lua: &mlua::Lua, variant: &str, self_ptr: *mut c_void)
             if bl_ptr.is_null() {
                 return Ok(lua.create_table()?);
             }
+            // The area is the engine's fixed AREA_SIZE around the entity, so only
+            // the origin needs checking (an entity mid-warp or on an unloaded map).
+            let bl = unsafe { &*(bl_ptr as *const BlockList) };
+            let origin = (bl.x as c_int, bl.y as c_int);
+            let xy = if variant.ends_with("SameMap") { None } else { Some(origin) };
+            if !unsafe { query_origin_ok(&variant, bl.m as c_int, xy) } {
+                return lua.create_table();
+            }
             let mut ptrs = vec![std::ptr::null_mut::<c_void>(); MAX];
             let raw_count = unsafe {
                 match variant.as_str() {
@@ -105,6 +138,9 @@ pub fn make_area_query_fn(lua: &mlua::Lua, variant: &str, self_ptr: *mut c_void)
 pub fn make_map_query_fn(lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
     lua.create_function(|lua, (_self, m, bl_type): (mlua::Value, c_int, c_int)| {
         const MAX: usize = 4096;
+        if !unsafe { query_origin_ok("getObjectsInMap", m, None) } {
+            return lua.create_table();
+        }
         let mut ptrs = vec![std::ptr::null_mut::<c_void>(); MAX];
         let raw_count = unsafe {
             sffi::sl_g_getobjectsinmap(m, bl_type, ptrs.as_mut_ptr(), MAX as c_int)
@@ -216,6 +252,22 @@ pub fn make_sendaction_fn(lua: &mlua::Lua, self_ptr: *mut c_void) -> mlua::Resul
     }).map(mlua::Value::Function)
 }
 
+/// `text` cleaned for a chat packet (see `servers::map::chat`), logging
+/// when content had to change.
+pub fn chat_cstring(what: &str, text: &[u8]) -> std::ffi::CString {
+    use crate::servers::map::chat;
+    let max = crate::ffi::config::try_config().map_or(chat::MAX_CHAT_LEN, |c| c.chat_max_len);
+    let (out, cleaned) = chat::sanitize(text, max);
+    if cleaned.changed() {
+        crate::log_every!(
+            warn, 10,
+            "[scripting] {}: cut {} byte(s) over chat_max_len {} and {} control byte(s)",
+            what, cleaned.truncated, max, cleaned.stripped
+        );
+    }
+    std::ffi::CString::new(out).expect("sanitize strips NUL")
+}
+
 pub fn make_msg_fn(lua: &mlua::Lua, self_ptr: *mut c_void) -> mlua::Result<mlua::Value> {
     let Some(entity_id) = extract_entity_id(self_ptr) else {
         return lua.create_function(|_, _: mlua::MultiValue| Ok(())).map(mlua::Value::Function);
@@ -223,17 +275,11 @@ pub fn make_msg_fn(lua: &mlua::Lua, self_ptr: *mut c_void) -> mlua::Result<mlua:
     lua.create_function(move |_, args: mlua::MultiValue| {
         let a: Vec<mlua::Value> = args.into_iter().collect();
         let color  = a.get(1).map(|v| val_to_int(v)).unwrap_or(0);
-        let msg    = match a.get(2) {
-            Some(mlua::Value::String(s)) => String::from_utf8_lossy(&*s.as_bytes()).into_owned(),
-            _ => String::new(),
+        let cs     = match a.get(2) {
+            Some(mlua::Value::String(s)) => chat_cstring("msg", &s.as_bytes()),
+            _ => std::ffi::CString::default(),
         };
         let target = a.get(3).map(|v| val_to_int(v)).unwrap_or(-1);
-        let cs = std::ffi::CString::new(msg.as_bytes()).map_err(|e| {
-            mlua::Error::RuntimeError(format!(
-                "msg: string contains interior NUL at byte {}",
-                e.nul_position()
-            ))
-        })?;
         let bl_ptr = unsafe { sffi::map_id2bl(entity_id) };
         if bl_ptr.is_null() { return Ok(()); }
         unsafe { sffi::sl_g_msg(bl_ptr, color, cs.as_ptr(), target); }
@@ -241,6 +287,16 @@ pub fn make_msg_fn(lua: &mlua::Lua, self_ptr: *mut c_void) -> mlua::Result<mlua:
     }).map(mlua::Value::Function)
 }
 
+/// `(amount, copies)` for a `dropItem` by `entity_id`: event-scaled when it
+/// is a mob dropping from its own drop table, unchanged otherwise.
+fn native_drop(entity_id: c_uint, item: c_int, amount: c_int) -> (c_int, c_int) {
+    if !rates::is_native_drop(entity_id) {
+        return (amount, 1);
+    }
+    let stack = crate::ffi::item_db::rust_itemdb_stackamount(item as c_uint);
+    rates::split_drop(amount, stack, rates::drop_rate(), crate::rng::unit())
+}
+
 pub fn make_dropitem_fn(lua: &mlua::Lua, self_ptr: *mut c_void) -> mlua::Result<mlua::Value> {
     let Some(entity_id) = extract_entity_id(self_ptr) else {
         return lua.create_function(|_, _: mlua::MultiValue| Ok(())).map(mlua::Value::Function);
@@ -252,7 +308,10 @@ pub fn make_dropitem_fn(lua: &mlua::Lua, self_ptr: *mut c_void) -> mlua::Result<
         let owner  = a.get(3).map(|v| val_to_int(v)).unwrap_or(0);
         let bl_ptr = unsafe { sffi::map_id2bl(entity_id) };
         if bl_ptr.is_null() { return Ok(()); }
-        unsafe { sffi::sl_g_dropitem(bl_ptr, item, amount, owner); }
+        let (amount, copies) = native_drop(entity_id, item, amount);
+        for _ in 0..copies {
+            unsafe { sffi::sl_g_dropitem(bl_ptr, item, amount, owner); }
+        }
         Ok(())
     }).map(mlua::Value::Function)
 }
@@ -271,7 +330,10 @@ pub fn make_dropitemxy_fn(lua: &mlua::Lua, self_ptr: *mut c_void) -> mlua::Resul
         let owner  = a.get(6).map(|v| val_to_int(v)).unwrap_or(0);
         let bl_ptr = unsafe { sffi::map_id2bl(entity_id) };
         if bl_ptr.is_null() { return Ok(()); }
-        unsafe { sffi::sl_g_dropitemxy(bl_ptr, item, amount, m, x, y, owner); }
+        let (amount, copies) = native_drop(entity_id, item, amount);
+        for _ in 0..copies {
+            unsafe { sffi::sl_g_dropitemxy(bl_ptr, item, amount, m, x, y, owner); }
+        }
         Ok(())
     }).map(mlua::Value::Function)
 }
@@ -12,8 +12,14 @@
 pub mod config;
 /// Core utilities and server lifecycle (replaces core.c)
 pub mod core;
+/// Rate-limited logging for hot error paths (`log_every!`)
+pub mod log_limit;
+/// Connection lifecycle counters and the `/metrics` endpoint
+pub mod metrics;
 /// Network utilities (encryption, session management)
 pub mod network;
+/// Seedable RNG for game-layer rolls (replaces direct `randomMT()` calls)
+pub mod rng;
 /// Database modules (item_db, class_db, etc.)
 pub mod database;
 /// Server implementations (login, char, map)
@@ -0,0 +1,127 @@
+//! Rate-limited logging for hot error paths.
+//!
+//! ```ignore
+//! log_every!(warn, 5, "[session] fd={} read error: {}", fd, e);
+//! ```
+//!
+//! Each call site gets its own limiter: the first event logs, then at most one
+//! line per interval, carrying the number of events suppressed since the last
+//! line. Events are counted, not queued, so a flood costs two atomic ops each.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Milliseconds since first use, offset by one so 0 can mean "never logged".
+fn now_ms() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64 + 1
+}
+
+/// Per-call-site limiter state; `const`-constructible so it can live in a static.
+pub struct RateLimit {
+    last_ms: AtomicU64,
+    suppressed: AtomicU64,
+}
+
+impl RateLimit {
+    pub const fn new() -> Self {
+        Self { last_ms: AtomicU64::new(0), suppressed: AtomicU64::new(0) }
+    }
+
+    /// `Some(suppressed)` if this event should be logged, `None` to drop it.
+    pub fn check(&self, interval: Duration) -> Option<u64> {
+        self.check_at(now_ms(), interval)
+    }
+
+    fn check_at(&self, now: u64, interval: Duration) -> Option<u64> {
+        let last = self.last_ms.load(Ordering::Relaxed);
+        let due = last == 0 || now.saturating_sub(last) >= interval.as_millis() as u64;
+        if due && self.last_ms.compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            return Some(self.suppressed.swap(0, Ordering::Relaxed));
+        }
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `tracing::$level!` at most once per `$secs` seconds from this call site.
+#[macro_export]
+macro_rules! log_every {
+    ($level:ident, $secs:expr, $($arg:tt)+) => {{
+        static __LIMIT: $crate::log_limit::RateLimit = $crate::log_limit::RateLimit::new();
+        if let Some(n) = __LIMIT.check(::std::time::Duration::from_secs($secs)) {
+            if n == 0 {
+                ::tracing::$level!($($arg)+);
+            } else {
+                ::tracing::$level!("{} ({} similar suppressed)", format_args!($($arg)+), n);
+            }
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_logs_then_suppresses_until_interval() {
+        let rl = RateLimit::new();
+        let iv = Duration::from_secs(5);
+        assert_eq!(rl.check_at(100, iv), Some(0));
+        assert_eq!(rl.check_at(200, iv), None);
+        assert_eq!(rl.check_at(4_000, iv), None);
+        assert_eq!(rl.check_at(5_100, iv), Some(2));
+        assert_eq!(rl.check_at(5_200, iv), None);
+    }
+
+    /// Collects formatted log output for `capture`.
+    #[derive(Clone, Default)]
+    struct Sink(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The lines logged on this thread while `f` runs.
+    fn capture(f: impl FnOnce()) -> Vec<String> {
+        let sink = Sink::default();
+        let writer = sink.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .without_time()
+            .with_target(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        let out = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        out.lines().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn test_macro_expands_per_call_site() {
+        let lines = capture(|| {
+            for i in 0..3 {
+                log_every!(debug, 60, "iteration {}", i);
+                log_every!(warn, 60, "other site {}", i);
+            }
+        });
+        // One line per call site: the first event, the rest held back.
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains("DEBUG") && lines[0].ends_with("iteration 0"), "{}", lines[0]);
+        assert!(lines[1].contains("WARN") && lines[1].ends_with("other site 0"), "{}", lines[1]);
+    }
+}
@@ -0,0 +1,297 @@
+//! Connection lifecycle metrics.
+//!
+//! Counters are bumped at the lifecycle points themselves (accept in
+//! `session::setup_connection`, first packet and auth outcome in the login
+//! handlers, map-server links in the char server) rather than derived from
+//! log lines. Session close reasons are already counted by
+//! `SessionManager::disconnect_counts` and are folded into the snapshot.
+//!
+//! On the map server the snapshot also carries `SessionManager::stats`
+//! (buffered bytes, oldest idle session) as gauges.
+//!
+//! Each server can serve the snapshot as Prometheus text on
+//! `<metrics_ip>:<login|char|map>_metrics_port` (`GET /metrics`). The same
+//! listener is the staff endpoint for the connection throttle:
+//!
+//! ```text
+//! GET    /throttle       one "a.b.c.d count" line per tracked IP
+//! DELETE /throttle/a.b.c.d   clear that IP (404 if it had no entry)
+//! ```
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Why a login was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum AuthFailure {
+    BadName,
+    BadPassword,
+    WrongUser,
+    WrongPassword,
+    Banned,
+    Lockout,
+    DoubleLogin,
+    NewSubnet,
+    Maintenance,
+    Draining,
+    ServerError,
+}
+
+impl AuthFailure {
+    pub const ALL: [Self; 11] = [
+        Self::BadName, Self::BadPassword, Self::WrongUser, Self::WrongPassword, Self::Banned,
+        Self::Lockout, Self::DoubleLogin, Self::NewSubnet, Self::Maintenance, Self::Draining,
+        Self::ServerError,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::BadName => "bad_name",
+            Self::BadPassword => "bad_password",
+            Self::WrongUser => "wrong_user",
+            Self::WrongPassword => "wrong_password",
+            Self::Banned => "banned",
+            Self::Lockout => "lockout",
+            Self::DoubleLogin => "double_login",
+            Self::NewSubnet => "new_subnet",
+            Self::Maintenance => "maintenance",
+            Self::Draining => "draining",
+            Self::ServerError => "server_error",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    accepts: AtomicU64,
+    handshakes: AtomicU64,
+    auth_ok: AtomicU64,
+    auth_fail: [AtomicU64; AuthFailure::ALL.len()],
+    link_ok: AtomicU64,
+    link_rejected: AtomicU64,
+    online: AtomicI64,
+}
+
+pub static METRICS: Metrics = Metrics::new();
+
+impl Metrics {
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            accepts: ZERO,
+            handshakes: ZERO,
+            auth_ok: ZERO,
+            auth_fail: [ZERO; AuthFailure::ALL.len()],
+            link_ok: ZERO,
+            link_rejected: ZERO,
+            online: AtomicI64::new(0),
+        }
+    }
+
+    /// A client connection was accepted.
+    pub fn accepted(&self) {
+        self.accepts.fetch_add(1, Ordering::Relaxed);
+        self.online.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An accepted client connection closed.
+    pub fn closed(&self) {
+        self.online.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// `accepted` now and `closed` when the guard drops, for handlers that
+    /// own their connection from accept to close.
+    pub fn connection(&'static self) -> Connection {
+        self.accepted();
+        Connection(self)
+    }
+
+    /// A client sent a well-formed first packet.
+    pub fn handshake(&self) {
+        self.handshakes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn auth_ok(&self) {
+        self.auth_ok.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn auth_failed(&self, why: AuthFailure) {
+        self.auth_fail[why as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An inter-server link (map → char) authenticated, or was refused.
+    pub fn link(&self, ok: bool) {
+        if ok { &self.link_ok } else { &self.link_rejected }.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let load = |a: &AtomicU64| a.load(Ordering::Relaxed);
+        Snapshot {
+            accepts: load(&self.accepts),
+            handshakes: load(&self.handshakes),
+            auth_ok: load(&self.auth_ok),
+            auth_fail: AuthFailure::ALL.map(|r| (r, load(&self.auth_fail[r as usize]))),
+            link_ok: load(&self.link_ok),
+            link_rejected: load(&self.link_rejected),
+            online: self.online.load(Ordering::Relaxed).max(0) as u64,
+            disconnects: crate::session::SESSION_MANAGER
+                .get()
+                .map(|m| m.disconnect_counts().into_iter().map(|(r, n)| (r.name(), n)).collect())
+                .unwrap_or_default(),
+            sessions: crate::session::SESSION_MANAGER.get().map(|m| m.stats()),
+        }
+    }
+}
+
+/// Counts a connection as online while alive; see `Metrics::connection`.
+pub struct Connection(&'static Metrics);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.closed();
+    }
+}
+
+/// Point-in-time copy of every metric.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub accepts: u64,
+    pub handshakes: u64,
+    pub auth_ok: u64,
+    pub auth_fail: [(AuthFailure, u64); AuthFailure::ALL.len()],
+    pub link_ok: u64,
+    pub link_rejected: u64,
+    /// Gauge: accepted client connections still open.
+    pub online: u64,
+    /// Session-layer closes by reason (map server only).
+    pub disconnects: Vec<(&'static str, u64)>,
+    /// Live session buffer/idle totals (map server only).
+    pub sessions: Option<crate::session::SessionStats>,
+}
+
+impl Snapshot {
+    /// Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, u64)]| {
+            let _ = writeln!(out, "# HELP yuri_{name} {help}\n# TYPE yuri_{name} {kind}");
+            for (labels, v) in samples {
+                let _ = writeln!(out, "yuri_{name}{labels} {v}");
+            }
+        };
+        let one = |v: u64| [(String::new(), v)];
+        metric("accepts_total", "counter", "Client connections accepted.", &one(self.accepts));
+        metric("handshakes_total", "counter", "Clients that sent a valid first packet.", &one(self.handshakes));
+        metric("auth_success_total", "counter", "Logins accepted.", &one(self.auth_ok));
+        let fails: Vec<_> = self.auth_fail.iter().map(|(r, n)| (format!("{{reason=\"{}\"}}", r.name()), *n)).collect();
+        metric("auth_failure_total", "counter", "Logins refused, by reason.", &fails);
+        let links = [("{result=\"ok\"}".to_string(), self.link_ok), ("{result=\"rejected\"}".to_string(), self.link_rejected)];
+        metric("link_auth_total", "counter", "Inter-server link authentications.", &links);
+        let closes: Vec<_> = self.disconnects.iter().map(|(r, n)| (format!("{{reason=\"{r}\"}}"), *n)).collect();
+        metric("disconnects_total", "counter", "Sessions closed, by reason.", &closes);
+        metric("online", "gauge", "Client connections currently open.", &one(self.online));
+        if let Some(st) = self.sessions {
+            metric("sessions", "gauge", "Sessions in the session table.", &one(st.sessions as u64));
+            metric("session_write_buffered_bytes", "gauge", "Queued write bytes across sessions.", &one(st.write_buffered as u64));
+            metric("session_write_buffered_max_bytes", "gauge", "Largest single session write backlog.", &one(st.max_write_buffered as u64));
+            metric("session_read_buffered_bytes", "gauge", "Unparsed read bytes across sessions.", &one(st.read_buffered as u64));
+            metric("session_oldest_idle_seconds", "gauge", "Idle time of the longest-idle session.", &one(st.oldest_idle.as_secs()));
+        }
+        out
+    }
+}
+
+/// Status line and body for the request whose head is `req`.
+fn respond(req: &[u8]) -> (&'static str, String) {
+    let line = req.split(|&b| b == b'\r' || b == b'\n').next().unwrap_or_default();
+    let mut parts = std::str::from_utf8(line).unwrap_or_default().split(' ');
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", METRICS.snapshot().render()),
+        (Some("GET"), Some("/throttle")) => {
+            let mut body = String::new();
+            for (ip, count) in crate::network::throttle::list() {
+                let _ = writeln!(body, "{} {}", std::net::Ipv4Addr::from(ip), count);
+            }
+            ("200 OK", body)
+        }
+        (Some("DELETE"), Some(path)) if path.starts_with("/throttle/") => {
+            match path["/throttle/".len()..].parse::<std::net::Ipv4Addr>() {
+                Ok(ip) if crate::network::throttle::unthrottle(u32::from(ip).to_be()) => {
+                    ("200 OK", format!("unthrottled {ip}\n"))
+                }
+                Ok(ip) => ("404 Not Found", format!("{ip} is not throttled\n")),
+                Err(_) => ("400 Bad Request", "expected /throttle/<IPv4 address>\n".to_string()),
+            }
+        }
+        _ => ("404 Not Found", String::new()),
+    }
+}
+
+/// Serve `GET /metrics` and the throttle routes on `addr` until the process exits.
+pub async fn serve(addr: String) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    tracing::info!("[metrics] serving on http://{}/metrics", addr);
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut req = [0u8; 1024];
+            let n = match tokio::time::timeout(std::time::Duration::from_secs(5), stream.read(&mut req)).await {
+                Ok(Ok(n)) => n,
+                _ => return,
+            };
+            let (status, body) = respond(&req[..n]);
+            let head = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(body.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_render() {
+        let m = Metrics::new();
+        m.accepted();
+        m.accepted();
+        m.closed();
+        m.handshake();
+        m.auth_ok();
+        m.auth_failed(AuthFailure::WrongPassword);
+        m.auth_failed(AuthFailure::WrongPassword);
+        m.link(false);
+        let s = m.snapshot();
+        assert_eq!((s.accepts, s.online, s.handshakes, s.auth_ok, s.link_rejected), (2, 1, 1, 1, 1));
+        assert_eq!(s.auth_fail[AuthFailure::WrongPassword as usize], (AuthFailure::WrongPassword, 2));
+
+        let text = s.render();
+        assert!(text.contains("yuri_accepts_total 2\n"));
+        assert!(text.contains("yuri_auth_failure_total{reason=\"wrong_password\"} 2\n"));
+        assert!(text.contains("# TYPE yuri_online gauge\nyuri_online 1\n"));
+
+        let stats = crate::session::SessionStats { sessions: 2, write_buffered: 40, ..Default::default() };
+        let text = Snapshot { sessions: Some(stats), ..s }.render();
+        assert!(text.contains("yuri_sessions 2\n"));
+        assert!(text.contains("yuri_session_write_buffered_bytes 40\n"));
+    }
+
+    #[test]
+    fn test_routes() {
+        assert_eq!(respond(b"GET /metrics HTTP/1.1\r\n\r\n").0, "200 OK");
+        assert_eq!(respond(b"GET /throttle HTTP/1.1\r\n\r\n").0, "200 OK");
+        // TEST-NET-1 is never recorded, so this only reads the table.
+        assert_eq!(respond(b"DELETE /throttle/192.0.2.1 HTTP/1.1\r\n").0, "404 Not Found");
+        assert_eq!(respond(b"DELETE /throttle/not-an-ip HTTP/1.1\r\n").0, "400 Bad Request");
+        assert_eq!(respond(b"POST /metrics HTTP/1.1\r\n").0, "404 Not Found");
+        assert_eq!(respond(b"").0, "404 Not Found");
+    }
+}
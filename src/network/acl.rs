@@ -2,6 +2,20 @@
 //!
 //! Ports `AccessControl` / `access_ipmask()` from session.c to Rust.
 //! Parses "a.b.c.d", "a.b.c.d/bits", or "a.b.c.d/e.f.g.h" CIDR-style strings.
+//! [`Acl`] holds a list of them as an allow list (`interserver_allow`).
+
+use std::net::IpAddr;
+
+/// Why an ACL rule was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AclParseError {
+    #[error("'{0}' is not an IPv4 address (expected a.b.c.d)")]
+    BadAddress(String),
+    #[error("'{0}' has a bad prefix length (expected /0 to /32)")]
+    BadPrefix(String),
+    #[error("'{0}' has a bad netmask (expected a.b.c.d)")]
+    BadMask(String),
+}
 
 /// An IP + mask pair used for access-control comparisons.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -30,35 +44,74 @@ pub enum AclOrder {
 ///
 /// Returns `Some(AccessControl)` on success, `None` on invalid input.
 pub fn parse_ipmask(s: &str) -> Option<AccessControl> {
+    parse_rule(s).ok()
+}
+
+/// [`parse_ipmask`] with the reason for a rejection.
+pub fn parse_rule(s: &str) -> Result<AccessControl, AclParseError> {
+    let s = s.trim();
     if s == "all" {
-        return Some(AccessControl { ip: 0, mask: 0 });
+        return Ok(AccessControl { ip: 0, mask: 0 });
     }
 
     // Try "a.b.c.d/e.f.g.h"
     if let Some((addr_part, mask_part)) = s.split_once('/') {
-        let ip = parse_ipv4(addr_part)?;
+        let ip = parse_ipv4(addr_part).ok_or_else(|| AclParseError::BadAddress(s.to_string()))?;
         // Dotted-decimal mask?
         if mask_part.contains('.') {
-            let mask = parse_ipv4(mask_part)?;
-            return Some(AccessControl { ip, mask });
+            let mask = parse_ipv4(mask_part).ok_or_else(|| AclParseError::BadMask(s.to_string()))?;
+            return Ok(AccessControl { ip, mask });
         }
         // Bit-count prefix
-        let bits: u32 = mask_part.parse().ok()?;
-        if bits > 32 {
-            return None;
-        }
+        let bits = mask_part.parse::<u32>().ok()
+            .filter(|&b| b <= 32)
+            .ok_or_else(|| AclParseError::BadPrefix(s.to_string()))?;
         let mask = prefix_to_mask(bits);
-        return Some(AccessControl { ip, mask });
+        return Ok(AccessControl { ip, mask });
     }
 
     // Plain "a.b.c.d" — exact host
-    let ip = parse_ipv4(s)?;
-    Some(AccessControl {
+    let ip = parse_ipv4(s).ok_or_else(|| AclParseError::BadAddress(s.to_string()))?;
+    Ok(AccessControl {
         ip,
         mask: 0xFFFF_FFFF,
     })
 }
 
+/// An allow list of [`AccessControl`] rules. Empty allows everyone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Acl {
+    rules: Vec<AccessControl>,
+}
+
+impl Acl {
+    /// Parse every rule, failing on the first bad one.
+    pub fn parse<S: AsRef<str>>(rules: &[S]) -> Result<Self, AclParseError> {
+        let rules = rules.iter().map(|r| parse_rule(r.as_ref())).collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Whether `ip` (first octet in the low byte, as [`AccessControl`])
+    /// matches any rule, or the list is empty.
+    pub fn is_allowed(&self, ip: u32) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|r| matches(r, ip))
+    }
+
+    /// [`is_allowed`](Self::is_allowed) for a socket address. IPv4-mapped
+    /// IPv6 peers (`::ffff:a.b.c.d`, from a dual-stack listener) are checked
+    /// as their IPv4 address; other IPv6 peers only pass an empty list.
+    pub fn allows(&self, addr: IpAddr) -> bool {
+        let v4 = match addr {
+            IpAddr::V4(v4) => Some(v4),
+            IpAddr::V6(v6) => v6.to_ipv4_mapped(),
+        };
+        match v4 {
+            Some(v4) => self.is_allowed(u32::from_le_bytes(v4.octets())),
+            None => self.rules.is_empty(),
+        }
+    }
+}
+
 /// Returns true if `ip` (host byte order) falls within `acl`.
 pub fn matches(acl: &AccessControl, ip: u32) -> bool {
     acl.mask == 0 || (ip & acl.mask) == (acl.ip & acl.mask)
@@ -139,6 +192,42 @@ mod tests {
         assert!(!matches(&acl, ip_out));
     }
 
+    fn ip(a: u32, b: u32, c: u32, d: u32) -> u32 {
+        a | (b << 8) | (c << 16) | (d << 24)
+    }
+
+    #[test]
+    fn acl_prefix_edges() {
+        let any = Acl::parse(&["0.0.0.0/0"]).unwrap();
+        assert!(any.is_allowed(ip(0, 0, 0, 0)) && any.is_allowed(ip(255, 255, 255, 255)));
+
+        let host = Acl::parse(&["192.168.1.5/32"]).unwrap();
+        assert!(host.is_allowed(ip(192, 168, 1, 5)));
+        assert!(!host.is_allowed(ip(192, 168, 1, 4)) && !host.is_allowed(ip(192, 168, 1, 6)));
+
+        let acl = Acl::parse(&["10.0.0.0/8", "172.16.0.0/12", "192.168.1.5"]).unwrap();
+        assert!(acl.is_allowed(ip(10, 0, 0, 0)) && acl.is_allowed(ip(10, 255, 255, 255)));
+        assert!(!acl.is_allowed(ip(9, 255, 255, 255)) && !acl.is_allowed(ip(11, 0, 0, 0)));
+        assert!(acl.is_allowed(ip(172, 16, 0, 0)) && acl.is_allowed(ip(172, 31, 255, 255)));
+        assert!(!acl.is_allowed(ip(172, 15, 255, 255)) && !acl.is_allowed(ip(172, 32, 0, 0)));
+        assert!(acl.allows("192.168.1.5".parse().unwrap()));
+        assert!(!acl.allows("192.168.1.6".parse().unwrap()));
+        assert!(!acl.allows("::1".parse().unwrap()));
+        assert!(acl.allows("::ffff:192.168.1.5".parse().unwrap()));
+        assert!(!acl.allows("::ffff:192.168.1.6".parse().unwrap()));
+
+        let open = Acl::default();
+        assert!(open.is_allowed(ip(1, 2, 3, 4)) && open.allows("::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn acl_parse_errors() {
+        assert_eq!(Acl::parse(&["10.0.0.0/33"]), Err(AclParseError::BadPrefix("10.0.0.0/33".into())));
+        assert_eq!(Acl::parse(&["10.0.0/8"]), Err(AclParseError::BadAddress("10.0.0/8".into())));
+        assert_eq!(Acl::parse(&["10.0.0.0/255.0.0"]), Err(AclParseError::BadMask("10.0.0.0/255.0.0".into())));
+        assert_eq!(Acl::parse(&["10.0.0.1", "host.local"]), Err(AclParseError::BadAddress("host.local".into())));
+    }
+
     #[test]
     fn invalid_inputs() {
         assert!(parse_ipmask("").is_none());
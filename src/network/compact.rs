@@ -0,0 +1,51 @@
+//! Returning memory from lookup tables after a spike.
+//!
+//! A `HashMap` keeps its peak capacity after entries are removed, so a server
+//! that briefly held thousands of sessions (or throttle/lockout entries) keeps
+//! paying for them. A periodic pass calls `shrink_sparse` on each table. It
+//! only acts once a table is mostly empty, and it leaves headroom for growth,
+//! so ordinary connect/disconnect churn never triggers it twice in a row.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Tables at or below this capacity are left alone.
+pub const MIN_CAPACITY: usize = 64;
+
+/// Shrink when fewer than 1 in `SPARSE_RATIO` slots are in use.
+pub const SPARSE_RATIO: usize = 4;
+
+/// Shrink `map` to twice its length (at least `MIN_CAPACITY`) if it is
+/// sparse. Returns `(old, new)` capacity when it shrank.
+pub fn shrink_sparse<K: Eq + Hash, V>(map: &mut HashMap<K, V>) -> Option<(usize, usize)> {
+    let (len, cap) = (map.len(), map.capacity());
+    if cap <= MIN_CAPACITY || len * SPARSE_RATIO >= cap {
+        return None;
+    }
+    map.shrink_to((len * 2).max(MIN_CAPACITY));
+    let new = map.capacity();
+    (new < cap).then_some((cap, new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shrinks_only_sparse_tables() {
+        let mut map: HashMap<u32, u32> = (0..5000).map(|i| (i, i)).collect();
+        assert_eq!(shrink_sparse(&mut map), None);
+
+        map.retain(|&k, _| k < 300);
+        let (old, new) = shrink_sparse(&mut map).unwrap();
+        assert!(old >= 5000 && new >= 600 && new < old);
+        assert_eq!(map.len(), 300);
+        // Headroom left: the same population does not shrink again.
+        assert_eq!(shrink_sparse(&mut map), None);
+
+        map.clear();
+        assert!(shrink_sparse(&mut map).is_some());
+        assert_eq!(shrink_sparse(&mut map), None);
+        assert!(map.capacity() >= MIN_CAPACITY);
+    }
+}
@@ -0,0 +1,250 @@
+//! Optional compression of client traffic, negotiated per session.
+//!
+//! Off unless `client_compression` names a codec. A client that supports it
+//! sends a negotiation frame once, any time after connecting:
+//!
+//! ```text
+//! client → AA 00 02 E0 <mask>     mask: bit 0 zlib, bit 1 zstd
+//! server → AA 00 02 E0 <choice>   0 = declined, 1 = zlib, 2 = zstd
+//! ```
+//!
+//! After an accepted negotiation either side may wrap packets in
+//! `AA <len> E1 <compressed bytes>`, where the compressed bytes inflate to
+//! one or more complete ordinary packets. The server wraps each outbound
+//! packet of at least `client_compression_threshold` bytes when that makes it
+//! smaller. Both frame kinds are handled in the session layer: the C parse
+//! callbacks only ever see the original packets, and clients that never
+//! negotiate get the byte stream unchanged.
+//!
+//! Packets are compressed after the C side has encrypted them, so savings
+//! come from the long runs the cipher leaves intact (map and inventory
+//! blocks); small packets are never touched.
+
+use std::io::Read;
+
+use crate::servers::char::codec::{self, Codec};
+
+pub const CMD_NEGOTIATE: u8 = 0xE0;
+pub const CMD_COMPRESSED: u8 = 0xE1;
+/// `AA` plus the big-endian length of everything after it.
+const HEADER_LEN: usize = 3;
+
+/// Server-side settings, from `client_compression*` in config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    pub codec: Codec,
+    pub threshold: usize,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("compressed frame before negotiation")]
+    NotNegotiated,
+    #[error("compressed frame does not inflate to whole packets within {0} bytes")]
+    BadPayload(usize),
+}
+
+fn codec_bit(codec: Codec) -> u8 {
+    match codec {
+        Codec::Zlib => 1,
+        Codec::Zstd => 2,
+    }
+}
+
+fn frame(cmd: u8, body: &[u8]) -> Vec<u8> {
+    let len = (body.len() + 1) as u16;
+    let mut out = Vec::with_capacity(HEADER_LEN + 1 + body.len());
+    out.push(0xAA);
+    out.extend_from_slice(&len.to_be_bytes());
+    out.push(cmd);
+    out.extend_from_slice(body);
+    out
+}
+
+/// Length of the complete frame at the start of `buf`, if there is one.
+/// Anything not starting with `AA` is passed through as-is (`Some(len)`), so
+/// the C parser still sees and rejects it.
+fn frame_len(buf: &[u8]) -> Option<usize> {
+    match buf {
+        [] => None,
+        [b, ..] if *b != 0xAA => Some(buf.len()),
+        [_, hi, lo, ..] => {
+            let total = HEADER_LEN + u16::from_be_bytes([*hi, *lo]) as usize;
+            (buf.len() >= total).then_some(total)
+        }
+        _ => None,
+    }
+}
+
+/// True if `buf` is a sequence of whole `AA` frames.
+fn whole_frames(mut buf: &[u8]) -> bool {
+    while !buf.is_empty() {
+        match frame_len(buf) {
+            Some(n) if buf[0] == 0xAA => buf = &buf[n..],
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Per-session compression state.
+#[derive(Debug)]
+pub struct Compression {
+    policy: Policy,
+    negotiated: bool,
+    /// Received bytes not yet forming a whole frame.
+    partial: Vec<u8>,
+    /// Leading `wdata` bytes already encoded (or passed over).
+    encoded: usize,
+}
+
+impl Compression {
+    pub fn new(policy: Policy) -> Self {
+        Self { policy, negotiated: false, partial: Vec::new(), encoded: 0 }
+    }
+
+    pub fn negotiated(&self) -> bool {
+        self.negotiated
+    }
+
+    /// Split received `data` into frames: negotiation frames are answered
+    /// (reply appended to `reply`), compressed frames are inflated, and
+    /// everything else is appended to `out` for the parser. At most `limit`
+    /// bytes are inflated from any one frame.
+    pub fn decode(&mut self, data: &[u8], limit: usize, out: &mut Vec<u8>, reply: &mut Vec<u8>) -> Result<(), DecodeError> {
+        self.partial.extend_from_slice(data);
+        let buf = std::mem::take(&mut self.partial);
+        let mut rest = &buf[..];
+        while let Some(n) = frame_len(rest) {
+            let (f, tail) = rest.split_at(n);
+            rest = tail;
+            match f {
+                [0xAA, _, _, CMD_NEGOTIATE, mask, ..] => {
+                    let ours = codec_bit(self.policy.codec);
+                    self.negotiated = mask & ours != 0;
+                    reply.extend(frame(CMD_NEGOTIATE, &[if self.negotiated { ours } else { 0 }]));
+                }
+                [0xAA, _, _, CMD_COMPRESSED, body @ ..] => {
+                    if !self.negotiated {
+                        return Err(DecodeError::NotNegotiated);
+                    }
+                    let inflated = inflate(body, limit).ok_or(DecodeError::BadPayload(limit))?;
+                    if !whole_frames(&inflated) {
+                        return Err(DecodeError::BadPayload(limit));
+                    }
+                    out.extend_from_slice(&inflated);
+                }
+                _ => out.extend_from_slice(f),
+            }
+        }
+        self.partial = rest.to_vec();
+        Ok(())
+    }
+
+    /// Compress large packets in `wdata[..size]` past what was already
+    /// encoded, in place. Returns the new committed size (never larger).
+    pub fn encode(&mut self, wdata: &mut [u8], size: usize) -> usize {
+        if !self.negotiated || self.encoded >= size {
+            return size;
+        }
+        let mut read = self.encoded;
+        let mut write = self.encoded;
+        while let Some(n) = frame_len(&wdata[read..size]) {
+            let packet = &wdata[read..read + n];
+            let packed = (packet[0] == 0xAA && n >= self.policy.threshold)
+                .then(|| frame(CMD_COMPRESSED, &codec::compress(self.policy.codec, packet)))
+                .filter(|c| c.len() < n && c.len() - HEADER_LEN <= u16::MAX as usize);
+            match packed {
+                Some(c) => {
+                    wdata[write..write + c.len()].copy_from_slice(&c);
+                    write += c.len();
+                }
+                None => {
+                    wdata.copy_within(read..read + n, write);
+                    write += n;
+                }
+            }
+            read += n;
+        }
+        // A trailing partial packet (never committed by C) stays unencoded.
+        wdata.copy_within(read..size, write);
+        let new_size = write + (size - read);
+        wdata[new_size..size].fill(0);
+        self.encoded = write;
+        new_size
+    }
+
+    /// `sent` bytes left the front of `wdata`.
+    pub fn consumed(&mut self, sent: usize) {
+        self.encoded = self.encoded.saturating_sub(sent);
+    }
+}
+
+fn inflate(body: &[u8], limit: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    codec::decoder(body)?.take(limit as u64 + 1).read_to_end(&mut out).ok()?;
+    (out.len() <= limit).then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(cmd: u8, body_len: usize) -> Vec<u8> {
+        frame(cmd, &vec![0x42; body_len])
+    }
+
+    #[test]
+    fn test_negotiate_then_round_trip() {
+        let policy = Policy { codec: Codec::Zlib, threshold: 64 };
+        let (mut server, mut client) = (Compression::new(policy), Compression::new(policy));
+        let (mut out, mut reply) = (Vec::new(), Vec::new());
+
+        // Before negotiating nothing changes, and compressed frames are refused.
+        let mut w = packet(0x33, 500);
+        assert_eq!(server.encode(&mut w, 500 + 4), 504);
+        let bogus = frame(CMD_COMPRESSED, &codec::compress(Codec::Zlib, &w));
+        assert_eq!(server.decode(&bogus, 1 << 16, &mut out, &mut reply), Err(DecodeError::NotNegotiated));
+
+        // The negotiation frame may arrive split; the zstd-only client is declined.
+        let mut server = Compression::new(policy);
+        server.decode(&[0xAA, 0x00], 1 << 16, &mut out, &mut reply).unwrap();
+        server.decode(&[0x02, CMD_NEGOTIATE, 0b10], 1 << 16, &mut out, &mut reply).unwrap();
+        assert_eq!((reply.as_slice(), server.negotiated()), (&[0xAA, 0, 2, CMD_NEGOTIATE, 0][..], false));
+        reply.clear();
+        server.decode(&[0xAA, 0x00, 0x02, CMD_NEGOTIATE, 0b11], 1 << 16, &mut out, &mut reply).unwrap();
+        assert_eq!(reply, [0xAA, 0, 2, CMD_NEGOTIATE, 1]);
+        assert!(out.is_empty());
+        client.decode(&reply, 1 << 16, &mut Vec::new(), &mut Vec::new()).unwrap();
+
+        // Outbound: the big packet shrinks, the small one is left alone.
+        let (big, small) = (packet(0x33, 2000), packet(0x0A, 10));
+        let mut wdata = [big.clone(), small.clone(), vec![0; 64]].concat();
+        let size = server.encode(&mut wdata, big.len() + small.len());
+        assert!(size < big.len());
+        assert!(wdata[size..].iter().all(|&b| b == 0));
+        // Already-encoded bytes are not touched again.
+        assert_eq!(server.encode(&mut wdata, size), size);
+
+        // The peer inflates it back to the original packets.
+        let mut got = Vec::new();
+        client.decode(&wdata[..size], 1 << 16, &mut got, &mut Vec::new()).unwrap();
+        assert_eq!(got, [big, small].concat());
+    }
+
+    #[test]
+    fn test_inflate_limit_and_partial_frames() {
+        let mut s = Compression::new(Policy { codec: Codec::Zlib, threshold: 64 });
+        s.decode(&[0xAA, 0, 2, CMD_NEGOTIATE, 1], 64, &mut Vec::new(), &mut Vec::new()).unwrap();
+        let bomb = frame(CMD_COMPRESSED, &codec::compress(Codec::Zlib, &packet(0x33, 1000)));
+        assert_eq!(s.decode(&bomb, 64, &mut Vec::new(), &mut Vec::new()), Err(DecodeError::BadPayload(64)));
+
+        let mut s = Compression::new(Policy { codec: Codec::Zlib, threshold: 64 });
+        let p = packet(0x10, 5);
+        let mut out = Vec::new();
+        s.decode(&p[..4], 64, &mut out, &mut Vec::new()).unwrap();
+        assert!(out.is_empty());
+        s.decode(&p[4..], 64, &mut out, &mut Vec::new()).unwrap();
+        assert_eq!(out, p);
+    }
+}
@@ -1,4 +1,5 @@
 use md5::{Digest, Md5};
+use std::sync::Arc;
 
 /// Opcodes that use key1 (static XOR) on the client side.
 const CL_KEY1_PACKETS: &[u8] = &[2, 3, 4, 11, 21, 38, 58, 66, 67, 75, 80, 87, 98, 113, 115, 123];
@@ -173,6 +174,122 @@ pub fn tk_crypt_static(buff: &mut [u8], xor_key: &[u8]) {
     tk_crypt_dynamic(buff, xor_key);
 }
 
+/// A client packet cipher. Each listener picks one by name (`cipher`,
+/// `login_alt_cipher`, `map_cipher`) so legacy and updated clients can share
+/// a binary.
+///
+/// Both directions cover bytes `5..` of a 0xAA frame, like `tk_crypt_dynamic`;
+/// `increment` is the frame's sequence byte (`buf[4]`).
+pub trait Cipher: Send + Sync {
+    fn encrypt(&self, buf: &mut [u8], increment: u8);
+    fn decrypt(&self, buf: &mut [u8], increment: u8);
+    /// Config name, for logs.
+    fn name(&self) -> &'static str;
+
+    /// `encrypt` with the increment taken from the frame header.
+    fn encrypt_packet(&self, buf: &mut [u8]) {
+        if let Some(&inc) = buf.get(4) {
+            self.encrypt(buf, inc);
+        }
+    }
+
+    /// `decrypt` with the increment taken from the frame header.
+    fn decrypt_packet(&self, buf: &mut [u8]) {
+        if let Some(&inc) = buf.get(4) {
+            self.decrypt(buf, inc);
+        }
+    }
+}
+
+/// Names accepted by [`cipher_by_name`].
+pub const CIPHER_NAMES: [&str; 2] = ["xor", "rolling"];
+
+/// Builds the cipher called `name` (see [`CIPHER_NAMES`]) keyed by `key`.
+pub fn cipher_by_name(name: &str, key: &[u8]) -> Option<Box<dyn Cipher>> {
+    match name {
+        "xor" => Some(Box::new(XorCipher::new(key))),
+        "rolling" => Some(Box::new(RollingXorCipher::new(key))),
+        _ => None,
+    }
+}
+
+/// The cipher called `name` keyed by `xor_key`, shared by every listener
+/// that speaks it. Names were checked by `ServerConfig::validate`; anything
+/// else falls back to XOR.
+pub fn listener_cipher(config: &crate::config::ServerConfig, name: &str) -> Arc<dyn Cipher> {
+    let key = config.xor_key.as_bytes();
+    match cipher_by_name(name, key) {
+        Some(c) => Arc::from(c),
+        None => Arc::new(XorCipher::new(key)),
+    }
+}
+
+/// The original scheme: `tk_crypt_static` with `xor_key`. The frame's own
+/// increment is already mixed in by `tk_crypt_dynamic`. An empty key leaves
+/// packets as they are, as before.
+pub struct XorCipher {
+    key: Vec<u8>,
+}
+
+impl XorCipher {
+    pub fn new(key: &[u8]) -> Self {
+        Self { key: key.to_vec() }
+    }
+}
+
+impl Cipher for XorCipher {
+    fn encrypt(&self, buf: &mut [u8], _increment: u8) {
+        tk_crypt_dynamic(buf, &self.key);
+    }
+
+    fn decrypt(&self, buf: &mut [u8], _increment: u8) {
+        tk_crypt_dynamic(buf, &self.key);
+    }
+
+    fn name(&self) -> &'static str {
+        "xor"
+    }
+}
+
+/// `XorCipher` with the key rotated by the increment, so consecutive frames
+/// use different key offsets. The key is null-padded to 9 bytes first.
+pub struct RollingXorCipher {
+    key: Option<[u8; 9]>,
+}
+
+impl RollingXorCipher {
+    pub fn new(key: &[u8]) -> Self {
+        if key.is_empty() {
+            return Self { key: None };
+        }
+        let mut k9 = [0u8; 9];
+        let n = key.len().min(9);
+        k9[..n].copy_from_slice(&key[..n]);
+        Self { key: Some(k9) }
+    }
+
+    fn key_for(&self, increment: u8) -> Vec<u8> {
+        match &self.key {
+            Some(k) => (0..9).map(|i| k[(i + increment as usize) % 9]).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Cipher for RollingXorCipher {
+    fn encrypt(&self, buf: &mut [u8], increment: u8) {
+        tk_crypt_dynamic(buf, &self.key_for(increment));
+    }
+
+    fn decrypt(&self, buf: &mut [u8], increment: u8) {
+        tk_crypt_dynamic(buf, &self.key_for(increment));
+    }
+
+    fn name(&self) -> &'static str {
+        "rolling"
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -225,4 +342,42 @@ mod tests {
         tk_crypt_dynamic(&mut packet, key); // XOR twice = identity
         assert_eq!(&packet[5..], original);
     }
+
+    fn frame(inc: u8, data: &[u8]) -> Vec<u8> {
+        let total = 5 + data.len();
+        let mut packet = vec![0xAA, (total >> 8) as u8, total as u8, 0x02, inc];
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn test_ciphers_round_trip_and_differ() {
+        let key = b"Urk#nI7ni";
+        let plain = frame(3, b"some login payload");
+
+        let xor = cipher_by_name("xor", key).unwrap();
+        let rolling = cipher_by_name("rolling", key).unwrap();
+        assert!(cipher_by_name("rot13", key).is_none());
+        assert_eq!(rolling.name(), "rolling");
+
+        // The default cipher is exactly the legacy static XOR.
+        let mut a = plain.clone();
+        xor.encrypt_packet(&mut a);
+        let mut legacy = plain.clone();
+        tk_crypt_static(&mut legacy, key);
+        assert_eq!(a, legacy);
+
+        let mut b = plain.clone();
+        rolling.encrypt_packet(&mut b);
+        assert_ne!(a, b);
+        rolling.decrypt_packet(&mut b);
+        assert_eq!(b, plain);
+
+        // Increment 0 (and multiples of 9) leave the key unrotated.
+        let mut c = frame(0, b"abc");
+        let mut d = c.clone();
+        xor.encrypt(&mut c, 0);
+        rolling.encrypt(&mut d, 9);
+        assert_eq!(c, d);
+    }
 }
@@ -2,6 +2,13 @@
 //!
 //! Ports ConnectHistory from session.c to Rust.
 //! Tracks connection attempts per IP and supports manual lockout.
+//!
+//! The rate is a sliding window: each IP keeps a ring of ten 100ms buckets
+//! covering the last second, so a burst is counted the same wherever it
+//! falls relative to the cleanup timer. An IP reaching `DDOS_COUNT`
+//! connections within any one second is locked out for `DDOS_AUTORESET`.
+//! The threshold comes from `ddos_connections_per_sec`; loopback and the
+//! configured server IPs are never counted (see `configure`).
 
 use std::collections::HashMap;
 use std::sync::{Mutex, OnceLock};
@@ -12,11 +19,62 @@ pub const DDOS_INTERVAL: u32 = 3 * 1000;
 /// DDoS-locked entries are cleared after this interval (ms).
 pub const DDOS_AUTORESET: u32 = 10 * 60 * 1000;
 
+/// Default for connections within one second that lock an IP out.
+pub const DDOS_COUNT: u32 = 10;
+
+/// Width of one rate bucket (ms).
+const BUCKET_MS: u32 = 100;
+
+/// Buckets in the window; `BUCKETS * BUCKET_MS` is one second.
+const BUCKETS: usize = 10;
+
+/// Connection counts for the last `BUCKETS` buckets of `BUCKET_MS`.
+#[derive(Default)]
+struct RateWindow {
+    counts: [u32; BUCKETS],
+    /// Bucket number (`tick / BUCKET_MS`) of the newest bucket.
+    head: u32,
+}
+
+impl RateWindow {
+    /// Move the window forward to `tick`, zeroing buckets that fell out of
+    /// it. Ticks older than the head count toward the head bucket.
+    fn advance(&mut self, tick: u32) {
+        let slot = tick / BUCKET_MS;
+        let gap = slot.wrapping_sub(self.head);
+        if gap == 0 || gap > u32::MAX / 2 {
+            return;
+        }
+        if gap as usize >= BUCKETS {
+            self.counts = [0; BUCKETS];
+        } else {
+            for s in 1..=gap {
+                self.counts[(self.head.wrapping_add(s) as usize) % BUCKETS] = 0;
+            }
+        }
+        self.head = slot;
+    }
+
+    /// Count one connection at `tick` and return the total over the window.
+    fn record(&mut self, tick: u32) -> u32 {
+        self.advance(tick);
+        let bucket = &mut self.counts[self.head as usize % BUCKETS];
+        *bucket = bucket.saturating_add(1);
+        self.total()
+    }
+
+    fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+}
+
 struct ConnectEntry {
     /// Tick (ms) when this entry was last updated.
     tick: u32,
     /// Whether this IP is in DDoS lockout.
     ddos: bool,
+    /// Recent connection attempts.
+    window: RateWindow,
 }
 
 struct DdosState {
@@ -26,6 +84,10 @@ struct DdosState {
     ddos_interval: u32,
     /// Lockout entry expiry interval (ms).
     ddos_autoreset: u32,
+    /// Connections per second that trigger a lockout.
+    ddos_count: u32,
+    /// Host-byte-order IPs never counted (the inter-server links).
+    exempt: Vec<u32>,
 }
 
 impl DdosState {
@@ -34,8 +96,55 @@ impl DdosState {
             entries: HashMap::new(),
             ddos_interval: DDOS_INTERVAL,
             ddos_autoreset: DDOS_AUTORESET,
+            ddos_count: DDOS_COUNT,
+            exempt: Vec::new(),
         }
     }
+
+    fn is_exempt(&self, ip: u32) -> bool {
+        std::net::Ipv4Addr::from(ip).is_loopback() || self.exempt.contains(&ip)
+    }
+
+    /// Count a connection from `ip` at `tick`; true if the IP is now locked.
+    /// Exempt IPs are not counted and never lock.
+    fn record(&mut self, ip: u32, tick: u32) -> bool {
+        if self.is_exempt(ip) {
+            return false;
+        }
+        let limit = self.ddos_count;
+        let entry = self.entries.entry(ip).or_insert_with(|| ConnectEntry {
+            tick,
+            ddos: false,
+            window: RateWindow { head: tick / BUCKET_MS, ..RateWindow::default() },
+        });
+        if entry.ddos {
+            return true;
+        }
+        entry.tick = tick;
+        if entry.window.record(tick) >= limit {
+            entry.ddos = true;
+        }
+        entry.ddos
+    }
+
+    fn is_locked(&self, ip: u32) -> bool {
+        self.entries.get(&ip).map(|e| e.ddos).unwrap_or(false)
+    }
+
+    /// Drop entries idle past their expiry at `tick`.
+    fn clear(&mut self, tick: u32) -> usize {
+        let ddos_interval = self.ddos_interval;
+        let ddos_autoreset = self.ddos_autoreset;
+        self.entries.retain(|_, entry| {
+            let age = tick.wrapping_sub(entry.tick);
+            if entry.ddos {
+                age <= ddos_autoreset
+            } else {
+                age <= ddos_interval * 3
+            }
+        });
+        self.entries.len()
+    }
 }
 
 static DDOS: OnceLock<Mutex<DdosState>> = OnceLock::new();
@@ -44,6 +153,41 @@ fn get_ddos() -> &'static Mutex<DdosState> {
     DDOS.get_or_init(|| Mutex::new(DdosState::new()))
 }
 
+/// Set the lockout threshold (connections per second) and the IPs it skips.
+/// Both are in effect from the next connection; `exempt` is in network byte
+/// order, like the per-IP cap's list in `SessionManager::set_per_ip_limit`.
+pub fn configure(count: u32, exempt: &[u32]) {
+    let mut state = get_ddos().lock().unwrap();
+    state.ddos_count = count;
+    state.exempt = exempt.iter().map(|&ip| u32::from_be(ip)).collect();
+}
+
+/// Count a connection attempt from an IP. Returns true if the IP is locked,
+/// either already or because this attempt pushed its rate to the threshold.
+///
+/// `ip_net` is in network byte order.
+pub fn record_connection(ip_net: u32) -> bool {
+    let ip = u32::from_be(ip_net);
+    #[cfg(not(test))]
+    let tick = unsafe { crate::ffi::timer::gettick() };
+    #[cfg(test)]
+    let tick: u32 = 0;
+    let mut state = get_ddos().lock().unwrap();
+    let was_locked = state.is_locked(ip);
+    let locked = state.record(ip, tick);
+    if locked && !was_locked {
+        tracing::warn!(
+            "[ddos] lockout ip={}.{}.{}.{} rate>={}/s",
+            (ip >> 24) & 0xFF,
+            (ip >> 16) & 0xFF,
+            (ip >> 8) & 0xFF,
+            ip & 0xFF,
+            state.ddos_count
+        );
+    }
+    locked
+}
+
 /// Mark an IP as DDoS-locked.
 ///
 /// `ip_net` is in network byte order (sin_addr.s_addr), matching what
@@ -58,6 +202,7 @@ pub fn add_ip_lockout(ip_net: u32) {
     let entry = state.entries.entry(ip).or_insert(ConnectEntry {
         tick: 0,
         ddos: false,
+        window: RateWindow::default(),
     });
     entry.ddos = true;
     entry.tick = tick;
@@ -74,9 +219,7 @@ pub fn add_ip_lockout(ip_net: u32) {
 ///
 /// `ip_net` is in network byte order.
 pub fn is_ip_locked(ip_net: u32) -> bool {
-    let ip = u32::from_be(ip_net);
-    let state = get_ddos().lock().unwrap();
-    state.entries.get(&ip).map(|e| e.ddos).unwrap_or(false)
+    get_ddos().lock().unwrap().is_locked(u32::from_be(ip_net))
 }
 
 /// Prune stale connection history entries.
@@ -88,18 +231,83 @@ pub fn connect_check_clear() -> i32 {
     let tick = unsafe { crate::ffi::timer::gettick() };
     #[cfg(test)]
     let tick: u32 = u32::MAX; // expire everything in tests
-    let mut state = get_ddos().lock().unwrap();
-    let ddos_interval = state.ddos_interval;
-    let ddos_autoreset = state.ddos_autoreset;
+    get_ddos().lock().unwrap().clear(tick) as i32
+}
 
-    state.entries.retain(|_, entry| {
-        let age = tick.wrapping_sub(entry.tick);
-        if entry.ddos {
-            age <= ddos_autoreset
-        } else {
-            age <= ddos_interval * 3
+/// Give back capacity after a burst of distinct IPs (see `network::compact`).
+pub fn compact() -> Option<(usize, usize)> {
+    crate::network::compact::shrink_sparse(&mut get_ddos().lock().unwrap().entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: u32 = 0x0A00_0001;
+
+    #[test]
+    fn test_burst_across_bucket_boundary_is_counted() {
+        // Nine at the end of one second and one just after it: a fixed
+        // one-second counter would see 9 + 1, the window sees 10.
+        let mut state = DdosState::new();
+        for _ in 0..9 {
+            assert!(!state.record(IP, 995));
         }
-    });
+        assert!(state.record(IP, 1005));
+        assert!(state.is_locked(IP));
+        // A locked IP stays locked on further attempts.
+        assert!(state.record(IP, 5000));
+    }
 
-    state.entries.len() as i32
+    #[test]
+    fn test_spread_out_connections_pass() {
+        let mut state = DdosState::new();
+        // Nine at the start of every second never puts ten inside one.
+        for step in 0..20u32 {
+            for _ in 0..9 {
+                assert!(!state.record(IP, step * 1000));
+            }
+        }
+        // Buckets older than a second have rolled off.
+        let mut w = RateWindow::default();
+        for t in [0, 100, 900] {
+            w.record(t);
+        }
+        assert_eq!(w.record(1050), 3);
+        assert_eq!(w.record(5000), 1);
+        // A tick behind the head lands in the head bucket.
+        assert_eq!(w.record(4990), 2);
+    }
+
+    #[test]
+    fn test_clear_evicts_idle_and_expired_lockouts() {
+        let mut state = DdosState::new();
+        state.record(IP, 0);
+        for _ in 0..DDOS_COUNT {
+            state.record(IP + 1, 0);
+        }
+        assert_eq!(state.clear(DDOS_INTERVAL * 3), 2);
+        assert_eq!(state.clear(DDOS_INTERVAL * 3 + 1), 1);
+        assert!(state.is_locked(IP + 1));
+        assert_eq!(state.clear(DDOS_AUTORESET + 1), 0);
+        // A fresh window after eviction starts from zero.
+        assert!(!state.record(IP + 1, DDOS_AUTORESET + 2));
+    }
+
+    #[test]
+    fn test_threshold_and_exempt_ips() {
+        let mut state = DdosState::new();
+        state.ddos_count = 3;
+        state.exempt = vec![IP + 1];
+        assert!(!state.record(IP, 0));
+        assert!(!state.record(IP, 0));
+        assert!(state.record(IP, 0));
+        // Server links and loopback reconnecting in a burst are never locked.
+        for _ in 0..DDOS_COUNT * 2 {
+            assert!(!state.record(IP + 1, 0));
+            assert!(!state.record(0x7F00_0001, 0));
+        }
+        assert!(!state.is_locked(IP + 1));
+        assert_eq!(state.entries.len(), 1);
+    }
 }
@@ -1,24 +1,115 @@
 pub mod acl;
+pub mod compact;
+pub mod compress;
 pub mod crypt;
 pub mod ddos;
+pub mod integrity;
+pub mod packet_writer;
+pub mod protocol;
 pub mod throttle;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 use anyhow::{bail, Result};
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+/// A byte stream a server handler can run over: a `TcpStream` in production,
+/// one end of a `tokio::io::duplex` pair in tests.
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Stream for T {}
+
+/// Why [`read_framed_packet_limited`] gave up on a frame.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum FrameError {
+    #[error("expected 0xAA header, got {0:02X}")]
+    BadHeader(u8),
+    #[error("frame payload {len} exceeds limit {max}")]
+    TooLarge { len: usize, max: usize },
+    #[error("frame body of {0} bytes not received in time")]
+    Stalled(usize),
+}
 
 /// Read one 0xAA-framed packet from `stream`.
 /// Returns the full buffer including the 3-byte header.
-pub async fn read_framed_packet(stream: &mut TcpStream) -> Result<Vec<u8>> {
+pub async fn read_framed_packet<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Vec<u8>> {
+    read_framed_packet_limited(stream, u16::MAX as usize, None).await
+}
+
+/// [`read_framed_packet`] for untrusted peers: a frame advertising more than
+/// `max_payload` bytes is refused before anything is allocated, and once the
+/// header is in, the rest must arrive within `body_timeout`. Waiting for the
+/// header itself is not timed. Errors are [`FrameError`] or I/O errors.
+pub async fn read_framed_packet_limited<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    max_payload: usize,
+    body_timeout: Option<std::time::Duration>,
+) -> Result<Vec<u8>> {
     let mut header = [0u8; 3];
     stream.read_exact(&mut header).await?;
     if header[0] != 0xAA {
-        bail!("expected 0xAA header, got {:02X}", header[0]);
+        bail!(FrameError::BadHeader(header[0]));
     }
     let payload_len = u16::from_be_bytes([header[1], header[2]]) as usize;
+    if payload_len > max_payload {
+        bail!(FrameError::TooLarge { len: payload_len, max: max_payload });
+    }
     let total = payload_len + 3;
     let mut buf = vec![0u8; total];
     buf[..3].copy_from_slice(&header);
-    stream.read_exact(&mut buf[3..]).await?;
+    match body_timeout {
+        Some(limit) => match tokio::time::timeout(limit, stream.read_exact(&mut buf[3..])).await {
+            Ok(read) => read?,
+            Err(_) => bail!(FrameError::Stalled(payload_len)),
+        },
+        None => stream.read_exact(&mut buf[3..]).await?,
+    };
     Ok(buf)
 }
+
+/// True when `a` and `b` share the same /24. Both are numeric IPv4 values
+/// (`u32::from(Ipv4Addr)`, first octet highest), not the C `s_addr` layout
+/// that `acl` works in.
+pub fn same_subnet(a: u32, b: u32) -> bool {
+    (a & 0xFFFF_FF00) == (b & 0xFFFF_FF00)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn test_same_subnet() {
+        let ip = |a: u8, b: u8, c: u8, d: u8| u32::from(std::net::Ipv4Addr::new(a, b, c, d));
+        assert!(same_subnet(ip(10, 0, 5, 1), ip(10, 0, 5, 200)));
+        assert!(!same_subnet(ip(10, 0, 5, 1), ip(10, 0, 6, 1)));
+        assert!(!same_subnet(ip(10, 0, 5, 1), ip(192, 168, 5, 1)));
+    }
+
+    fn frame_error(e: anyhow::Error) -> FrameError {
+        e.downcast::<FrameError>().expect("FrameError")
+    }
+
+    #[tokio::test]
+    async fn test_limited_read_refuses_oversize_and_stalls() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        let limit = Some(Duration::from_millis(50));
+
+        client.write_all(&[0xAA, 0x00, 0x02, 0x10, 0x20]).await.unwrap();
+        let pkt = read_framed_packet_limited(&mut server, 4, limit).await.unwrap();
+        assert_eq!(pkt, [0xAA, 0x00, 0x02, 0x10, 0x20]);
+
+        client.write_all(&[0xAA, 0xFF, 0xFF]).await.unwrap();
+        let err = read_framed_packet_limited(&mut server, 4096, limit).await.unwrap_err();
+        assert_eq!(frame_error(err), FrameError::TooLarge { len: 0xFFFF, max: 4096 });
+
+        client.write_all(&[0xAA, 0x00, 0x08, 0x01]).await.unwrap();
+        let err = read_framed_packet_limited(&mut server, 4096, limit).await.unwrap_err();
+        assert_eq!(frame_error(err), FrameError::Stalled(8));
+
+        client.write_all(&[0x55, 0x00, 0x00]).await.unwrap();
+        let err = read_framed_packet(&mut server).await.unwrap_err();
+        assert_eq!(frame_error(err), FrameError::BadHeader(0x55));
+    }
+}
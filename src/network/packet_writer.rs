@@ -0,0 +1,182 @@
+//! Typed packet builder over a session's write buffer
+//!
+//! Replaces the WFIFOHEAD/WFIFOB/WFIFOW/WFIFOSET pattern with a cursor that
+//! tracks the offset for you:
+//!
+//! ```ignore
+//! PacketWriter::new(&mut session)
+//!     .aa_header()          // 0xAA + BE payload length, filled at finish()
+//!     .u8(0x0A)
+//!     .u32_be(char_id)
+//!     .cstr("Yuria", 16)
+//!     .finish()?;           // fills lengths, then commit_write
+//! ```
+//!
+//! Every append goes through the existing `Session::write_*` methods, so
+//! bounds and buffer growth behave exactly as they do for C callers. The
+//! first error is kept and returned by `finish()`; later appends are skipped.
+
+use crate::session::{Session, SessionError};
+
+enum LenField {
+    /// 0xAA frame: BE u16 at `pos` = bytes after the 3-byte header.
+    AaPayload { pos: usize },
+    /// Inter-server variable packet: LE u32 at `pos` = total packet length.
+    TotalU32Le { pos: usize },
+}
+
+pub struct PacketWriter<'a> {
+    session: &'a mut Session,
+    pos: usize,
+    len_fields: Vec<LenField>,
+    err: Option<SessionError>,
+}
+
+impl<'a> PacketWriter<'a> {
+    /// Start a packet at the session's current uncommitted write position.
+    pub fn new(session: &'a mut Session) -> Self {
+        Self { session, pos: 0, len_fields: Vec::new(), err: None }
+    }
+
+    /// Bytes written so far.
+    pub fn len(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == 0
+    }
+
+    fn put(&mut self, f: impl FnOnce(&mut Session, usize) -> Result<(), SessionError>, n: usize) -> &mut Self {
+        if self.err.is_none() {
+            match f(self.session, self.pos) {
+                Ok(()) => self.pos += n,
+                Err(e) => self.err = Some(e),
+            }
+        }
+        self
+    }
+
+    pub fn u8(&mut self, v: u8) -> &mut Self {
+        self.put(|s, p| s.write_u8(p, v), 1)
+    }
+
+    pub fn u16_le(&mut self, v: u16) -> &mut Self {
+        self.put(|s, p| s.write_u16(p, v), 2)
+    }
+
+    pub fn u16_be(&mut self, v: u16) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn u32_le(&mut self, v: u32) -> &mut Self {
+        self.put(|s, p| s.write_u32(p, v), 4)
+    }
+
+    pub fn u32_be(&mut self, v: u32) -> &mut Self {
+        self.bytes(&v.to_be_bytes())
+    }
+
+    pub fn bytes(&mut self, src: &[u8]) -> &mut Self {
+        self.put(|s, p| s.write_buf(p, src), src.len())
+    }
+
+    pub fn zeros(&mut self, n: usize) -> &mut Self {
+        self.bytes(&vec![0u8; n])
+    }
+
+    /// Fixed-width string field: truncated to `width` bytes, NUL-padded.
+    pub fn cstr(&mut self, s: &str, width: usize) -> &mut Self {
+        let b = s.as_bytes();
+        let n = b.len().min(width);
+        self.bytes(&b[..n]).zeros(width - n)
+    }
+
+    /// 0xAA frame header; the BE payload length is filled in by `finish()`.
+    pub fn aa_header(&mut self) -> &mut Self {
+        let pos = self.pos;
+        self.u8(0xAA).u16_be(0);
+        self.len_fields.push(LenField::AaPayload { pos: pos + 1 });
+        self
+    }
+
+    /// Placeholder for a LE u32 total-length field (inter-server variable
+    /// packets, e.g. 0x3004 at offset 2); filled in by `finish()`.
+    pub fn len_u32_le(&mut self) -> &mut Self {
+        let pos = self.pos;
+        self.u32_le(0);
+        self.len_fields.push(LenField::TotalU32Le { pos });
+        self
+    }
+
+    /// Fill length fields and commit the packet (WFIFOSET). Returns its length.
+    pub fn finish(&mut self) -> Result<usize, SessionError> {
+        if let Some(e) = self.err.take() {
+            return Err(e);
+        }
+        let total = self.pos;
+        for field in std::mem::take(&mut self.len_fields) {
+            match field {
+                LenField::AaPayload { pos } => {
+                    let payload = u16::try_from(total - (pos + 2)).map_err(|_| {
+                        SessionError::WriteBufferTooLarge { fd: self.session.fd, requested_pos: total, max: u16::MAX as usize }
+                    })?;
+                    self.session.write_buf(pos, &payload.to_be_bytes())?;
+                }
+                LenField::TotalU32Le { pos } => {
+                    self.session.write_u32(pos, total as u32)?;
+                }
+            }
+        }
+        self.session.commit_write(total)?;
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aa_frame_matches_hand_built_bytes() {
+        let mut session = Session::new(7);
+        let n = PacketWriter::new(&mut session)
+            .aa_header()
+            .u8(0x0A)
+            .u32_be(0x01020304)
+            .cstr("Yuri", 6)
+            .u16_le(0xBEEF)
+            .finish()
+            .unwrap();
+
+        let expected: &[u8] = &[
+            0xAA, 0x00, 0x0D, // header, payload = 13
+            0x0A,
+            0x01, 0x02, 0x03, 0x04,
+            b'Y', b'u', b'r', b'i', 0, 0,
+            0xEF, 0xBE,
+        ];
+        assert_eq!(n, expected.len());
+        assert_eq!(session.wdata_size, expected.len());
+        assert_eq!(&session.wdata[..n], expected);
+    }
+
+    #[test]
+    fn test_total_len_field_and_append_after_commit() {
+        let mut session = Session::new(7);
+        PacketWriter::new(&mut session).u16_le(0x3004).len_u32_le().bytes(&[9, 9, 9]).finish().unwrap();
+        // A second packet starts after the first committed one.
+        PacketWriter::new(&mut session).u16_le(0x3005).u32_le(42).finish().unwrap();
+        assert_eq!(
+            &session.wdata[..session.wdata_size],
+            &[0x04, 0x30, 9, 0, 0, 0, 9, 9, 9, 0x05, 0x30, 42, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_cstr_truncates_to_width() {
+        let mut session = Session::new(7);
+        PacketWriter::new(&mut session).cstr("abcdef", 3).finish().unwrap();
+        assert_eq!(&session.wdata[..session.wdata_size], b"abc");
+    }
+}
@@ -0,0 +1,144 @@
+//! Typed errors for the inter-server protocol handlers.
+//!
+//! Handlers return `Result<(), ProtocolError>` and the connection task logs
+//! the error once, at the boundary, via [`ProtocolError::log`]. Errors from
+//! reading a frame end the connection; errors from handling one only abandon
+//! that packet.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    /// A packet was shorter than its command requires.
+    #[error("bad frame cmd={cmd:04X}: need {need} bytes, got {got}")]
+    BadFrame { cmd: u16, need: usize, got: usize },
+
+    /// The stream ended or errored mid-packet.
+    #[error("short read: {0}")]
+    ShortRead(#[from] std::io::Error),
+
+    #[error("unknown cmd={0:04X}")]
+    UnknownCommand(u16),
+
+    /// Declared variable length is zero or above the hard cap.
+    #[error("cmd={cmd:04X} declared length {len} out of bounds")]
+    BadLength { cmd: u16, len: usize },
+
+    /// Link credentials were rejected.
+    #[error("auth failed: {0}")]
+    AuthFailed(String),
+
+    /// The frame's integrity tag did not verify (see `network::integrity`).
+    #[error("MAC check failed for cmd={0:04X}")]
+    BadMac(u16),
+
+    /// The frame's CRC32 did not match: truncated or corrupted in transit.
+    #[error("checksum mismatch for cmd={0:04X}")]
+    BadChecksum(u16),
+
+    #[error("database error: {0}")]
+    DbError(String),
+
+    /// The peer link's writer channel is gone.
+    #[error("peer link is down")]
+    LinkDown,
+}
+
+impl ProtocolError {
+    pub const KINDS: [&'static str; 9] = [
+        "bad_frame", "short_read", "unknown_command", "bad_length",
+        "auth_failed", "bad_mac", "db_error", "link_down", "bad_checksum",
+    ];
+
+    /// Stable snake_case name, for logs and metrics.
+    pub fn kind(&self) -> &'static str {
+        Self::KINDS[self.kind_index()]
+    }
+
+    fn kind_index(&self) -> usize {
+        match self {
+            Self::BadFrame { .. } => 0,
+            Self::ShortRead(_) => 1,
+            Self::UnknownCommand(_) => 2,
+            Self::BadLength { .. } => 3,
+            Self::AuthFailed(_) => 4,
+            Self::BadMac(_) => 5,
+            Self::DbError(_) => 6,
+            Self::LinkDown => 7,
+            Self::BadChecksum(_) => 8,
+        }
+    }
+
+    /// True when the stream can no longer be trusted or framed and the
+    /// connection must be dropped.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::ShortRead(_) | Self::BadLength { .. } | Self::AuthFailed(_) | Self::BadMac(_)
+                | Self::BadChecksum(_)
+        )
+    }
+
+    /// Count this error and log it with the link's tag (e.g. "[char] [logif]").
+    pub fn log(&self, tag: &str) {
+        ERROR_COUNTS[self.kind_index()].fetch_add(1, Ordering::Relaxed);
+        tracing::warn!("{} {} ({})", tag, self, self.kind());
+    }
+}
+
+impl From<anyhow::Error> for ProtocolError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::DbError(e.to_string())
+    }
+}
+
+impl From<sqlx::Error> for ProtocolError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::DbError(e.to_string())
+    }
+}
+
+static ERROR_COUNTS: [AtomicU64; ProtocolError::KINDS.len()] = [const { AtomicU64::new(0) }; ProtocolError::KINDS.len()];
+
+/// Logged protocol errors by kind since startup.
+pub fn error_counts() -> Vec<(&'static str, u64)> {
+    ProtocolError::KINDS
+        .iter()
+        .zip(ERROR_COUNTS.iter())
+        .map(|(&k, c)| (k, c.load(Ordering::Relaxed)))
+        .collect()
+}
+
+/// `Err(BadFrame)` unless `pkt` holds at least `need` bytes.
+pub fn ensure_len(cmd: u16, pkt: &[u8], need: usize) -> Result<(), ProtocolError> {
+    if pkt.len() < need {
+        return Err(ProtocolError::BadFrame { cmd, need, got: pkt.len() });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensure_len() {
+        assert!(ensure_len(0x1003, &[0u8; 40], 40).is_ok());
+        assert!(matches!(
+            ensure_len(0x1003, &[0u8; 12], 40),
+            Err(ProtocolError::BadFrame { cmd: 0x1003, need: 40, got: 12 })
+        ));
+    }
+
+    #[test]
+    fn test_kind_and_fatality() {
+        let e = ProtocolError::UnknownCommand(0x10FF);
+        assert_eq!(e.kind(), "unknown_command");
+        assert!(!e.is_fatal());
+        assert!(ProtocolError::BadMac(0x1003).is_fatal());
+        assert_eq!(ProtocolError::BadChecksum(0x3004).kind(), "bad_checksum");
+        assert!(ProtocolError::BadChecksum(0x3004).is_fatal());
+        let eof = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+        assert!(ProtocolError::from(eof).is_fatal());
+    }
+}
@@ -2,22 +2,54 @@
 //!
 //! Ports the stThrottle linked list from session.c to Rust.
 //! Tracks per-IP connection counts and blocks repeat offenders.
-//! Resets every 10 minutes via a timer callback (matching C's Remove_Throttle).
+//! Resets periodically via a timer callback (matching C's Remove_Throttle);
+//! the interval and block threshold come from `throttle_reset_secs` /
+//! `throttle_threshold` in server.yaml.
 
 use std::collections::HashMap;
 use std::sync::{Mutex, OnceLock};
 
+/// Default block threshold: any recorded attempt throttles the IP (C behaviour).
+pub const DEFAULT_THRESHOLD: u32 = 1;
+
+/// Default reset interval (ms), matching login_server.c's 10-minute timer.
+pub const DEFAULT_RESET_INTERVAL: u32 = 10 * 60 * 1000;
+
 struct ThrottleState {
     /// Map from host-byte-order IPv4 to connection count.
     counts: HashMap<u32, u32>,
+    /// Count at which an IP is refused.
+    threshold: u32,
 }
 
 impl ThrottleState {
     fn new() -> Self {
         Self {
             counts: HashMap::new(),
+            threshold: DEFAULT_THRESHOLD,
         }
     }
+
+    /// Count one attempt from `ip` (host order); returns the new count.
+    fn record(&mut self, ip: u32) -> u32 {
+        let count = self.counts.entry(ip).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    fn is_throttled(&self, ip: u32) -> bool {
+        self.counts.get(&ip).copied().unwrap_or(0) >= self.threshold
+    }
+
+    fn unthrottle(&mut self, ip: u32) -> bool {
+        self.counts.remove(&ip).is_some()
+    }
+
+    fn list(&self) -> Vec<(u32, u32)> {
+        let mut entries: Vec<(u32, u32)> = self.counts.iter().map(|(&ip, &n)| (ip, n)).collect();
+        entries.sort_unstable();
+        entries
+    }
 }
 
 static THROTTLE: OnceLock<Mutex<ThrottleState>> = OnceLock::new();
@@ -31,32 +63,106 @@ fn get_throttle() -> &'static Mutex<ThrottleState> {
 /// `ip_net` is in network byte order (sin_addr.s_addr).
 pub fn add_throttle(ip_net: u32) {
     let ip = u32::from_be(ip_net);
-    let mut state = get_throttle().lock().unwrap();
-    *state.counts.entry(ip).or_insert(0) += 1;
+    let count = get_throttle().lock().unwrap().record(ip);
     tracing::debug!(
         "[throttle] add ip={}.{}.{}.{} count={}",
         (ip >> 24) & 0xFF,
         (ip >> 16) & 0xFF,
         (ip >> 8) & 0xFF,
         ip & 0xFF,
-        state.counts[&ip],
+        count,
     );
 }
 
-/// Returns true if this IP has been throttled (count > 0).
+/// Set the count at which an IP is refused (clamped to at least 1).
+pub fn set_threshold(threshold: u32) {
+    get_throttle().lock().unwrap().threshold = threshold.max(1);
+}
+
+/// Returns true if this IP has reached the throttle threshold.
 ///
 /// `ip_net` is in network byte order.
 pub fn is_throttled(ip_net: u32) -> bool {
+    get_throttle().lock().unwrap().is_throttled(u32::from_be(ip_net))
+}
+
+/// Clear one IP's throttle entry. Returns true if it had one.
+///
+/// `ip_net` is in network byte order. Takes the same lock as the periodic
+/// reset, so it is safe to call from any thread at any time.
+pub fn unthrottle(ip_net: u32) -> bool {
     let ip = u32::from_be(ip_net);
-    let state = get_throttle().lock().unwrap();
-    state.counts.get(&ip).copied().unwrap_or(0) > 0
+    let removed = get_throttle().lock().unwrap().unthrottle(ip);
+    if removed {
+        tracing::info!(
+            "[throttle] unthrottle ip={}.{}.{}.{}",
+            (ip >> 24) & 0xFF,
+            (ip >> 16) & 0xFF,
+            (ip >> 8) & 0xFF,
+            ip & 0xFF,
+        );
+    }
+    removed
+}
+
+/// Snapshot of all throttle entries as `(ip, count)`, sorted by IP.
+///
+/// IPs are in host byte order (so `Ipv4Addr::from(ip)` prints them).
+pub fn list() -> Vec<(u32, u32)> {
+    get_throttle().lock().unwrap().list()
 }
 
 /// Reset all throttle counts (matches C's Remove_Throttle).
 ///
-/// Called as a timer callback every 10 minutes.
+/// Called as a timer callback every `throttle_reset_secs`.
 pub fn remove_throttle() {
     let mut state = get_throttle().lock().unwrap();
     state.counts.clear();
     tracing::debug!("[throttle] cleared all entries");
 }
+
+/// Give back capacity after a burst of distinct IPs (see `network::compact`).
+pub fn compact() -> Option<(usize, usize)> {
+    crate::network::compact::shrink_sparse(&mut get_throttle().lock().unwrap().counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_list_and_unthrottle() {
+        let ip = u32::from(std::net::Ipv4Addr::new(10, 9, 8, 7));
+        let mut state = ThrottleState::new();
+        state.threshold = 2;
+        state.record(ip);
+        assert!(!state.is_throttled(ip));
+        state.record(ip);
+        state.record(ip - 1);
+        assert!(state.is_throttled(ip));
+        assert_eq!(state.list(), vec![(ip - 1, 1), (ip, 2)]);
+
+        assert!(state.unthrottle(ip));
+        assert!(!state.is_throttled(ip));
+        assert!(!state.unthrottle(ip));
+        assert_eq!(state.list(), vec![(ip - 1, 1)]);
+    }
+
+    #[test]
+    fn test_engages_at_configured_threshold() {
+        let config = crate::config::ServerConfig {
+            throttle_threshold: 5,
+            ..crate::servers::testing::test_config()
+        };
+        let mut state = ThrottleState::new();
+        state.threshold = config.throttle_threshold;
+        let ip = 0x0A00_0002;
+        for n in 1..config.throttle_threshold {
+            state.record(ip);
+            assert!(!state.is_throttled(ip), "throttled after {n}");
+        }
+        state.record(ip);
+        assert!(state.is_throttled(ip));
+        assert!(!state.is_throttled(ip + 1));
+    }
+}
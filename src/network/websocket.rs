@@ -0,0 +1,491 @@
+//! Minimal server-side WebSocket (RFC 6455) transport.
+//!
+//! Browser clients cannot open raw TCP sockets, so a dedicated listener can
+//! accept WebSocket connections instead. After the HTTP Upgrade handshake,
+//! [`WsStream`] presents the connection as a plain byte stream: the game's
+//! 0xAA packets travel as the payload of binary frames, and frame boundaries
+//! carry no meaning, just as TCP segment boundaries carry none. Session and
+//! login code read and write it exactly like a `TcpStream`.
+//!
+//! Only what a game client needs is implemented: binary and continuation
+//! frames, ping/pong and close. Text frames and extensions are refused.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::network::Stream;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest HTTP upgrade request accepted.
+const MAX_HANDSHAKE: usize = 4096;
+
+/// Largest client frame payload; matches the session read buffer cap.
+pub const MAX_FRAME_PAYLOAD: usize = 64 * 1024;
+
+/// Largest payload sent in one server frame; longer writes are split.
+const MAX_OUT_PAYLOAD: usize = 16 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WsError {
+    #[error("bad upgrade request: {0}")]
+    BadRequest(&'static str),
+    #[error("protocol violation: {0}")]
+    Protocol(&'static str),
+    #[error("frame payload {0} bytes exceeds limit")]
+    TooLarge(u64),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl From<WsError> for io::Error {
+    fn from(e: WsError) -> Self {
+        match e {
+            WsError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    let mut sha = Sha1::new();
+    sha.update(key.as_bytes());
+    sha.update(GUID.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(sha.finalize())
+}
+
+/// Validate an HTTP upgrade request (headers only) and return its key.
+pub fn parse_upgrade(req: &[u8]) -> Result<String, WsError> {
+    let req = std::str::from_utf8(req).map_err(|_| WsError::BadRequest("not utf-8"))?;
+    let mut lines = req.split("\r\n");
+    let request_line = lines.next().unwrap_or("");
+    if !request_line.starts_with("GET ") {
+        return Err(WsError::BadRequest("not a GET request"));
+    }
+    let (mut upgrade, mut version, mut key) = (false, false, None);
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+            "sec-websocket-version" => version = value == "13",
+            "sec-websocket-key" => key = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    if !upgrade {
+        return Err(WsError::BadRequest("missing Upgrade: websocket"));
+    }
+    if !version {
+        return Err(WsError::BadRequest("unsupported Sec-WebSocket-Version"));
+    }
+    key.filter(|k| !k.is_empty()).ok_or(WsError::BadRequest("missing Sec-WebSocket-Key"))
+}
+
+/// `101 Switching Protocols` response for `key`.
+pub fn upgrade_response(key: &str) -> String {
+    format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )
+}
+
+/// Run the server side of the opening handshake on `stream`.
+pub async fn accept<S: Stream>(mut stream: S) -> Result<WsStream<S>, WsError> {
+    let mut buf = Vec::with_capacity(512);
+    let mut chunk = [0u8; 512];
+    let end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() >= MAX_HANDSHAKE {
+            return Err(WsError::BadRequest("headers too long"));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let key = match parse_upgrade(&buf[..end]) {
+        Ok(k) => k,
+        Err(e) => {
+            let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n").await;
+            return Err(e);
+        }
+    };
+    stream.write_all(upgrade_response(&key).as_bytes()).await?;
+    let mut ws = WsStream::new(stream);
+    // A client may pipeline its first frame behind the request.
+    ws.raw.extend_from_slice(&buf[end..]);
+    Ok(ws)
+}
+
+/// One decoded frame.
+#[derive(Debug, PartialEq, Eq)]
+struct Frame {
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Decode the frame at the start of `buf`, returning it and the bytes used,
+/// or `None` if more input is needed. Client frames must be masked.
+fn decode_frame(buf: &[u8]) -> Result<Option<(Frame, usize)>, WsError> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    if buf[0] & 0x70 != 0 {
+        return Err(WsError::Protocol("reserved bits set"));
+    }
+    let opcode = buf[0] & 0x0F;
+    if buf[1] & 0x80 == 0 {
+        return Err(WsError::Protocol("unmasked client frame"));
+    }
+    let (len, mut off) = match buf[1] & 0x7F {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() >= 10 => (u64::from_be_bytes(buf[2..10].try_into().unwrap()), 10),
+        126 | 127 => return Ok(None),
+        n => (n as u64, 2),
+    };
+    if len > MAX_FRAME_PAYLOAD as u64 {
+        return Err(WsError::TooLarge(len));
+    }
+    if opcode >= OP_CLOSE && len > 125 {
+        return Err(WsError::Protocol("oversized control frame"));
+    }
+    let len = len as usize;
+    if buf.len() < off + 4 + len {
+        return Ok(None);
+    }
+    let mask = [buf[off], buf[off + 1], buf[off + 2], buf[off + 3]];
+    off += 4;
+    let payload = buf[off..off + len].iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect();
+    Ok(Some((Frame { opcode, payload }, off + len)))
+}
+
+/// Encode one unmasked, final server frame.
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 10);
+    out.push(0x80 | opcode);
+    match payload.len() {
+        n if n < 126 => out.push(n as u8),
+        n if n <= u16::MAX as usize => {
+            out.push(126);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            out.push(127);
+            out.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Byte-stream view of an accepted WebSocket connection.
+///
+/// A write that returns `Pending` has already framed its data; like a
+/// `write_all`, the caller must retry with the same bytes.
+pub struct WsStream<S> {
+    inner: S,
+    /// Undecoded bytes from the socket.
+    raw: Vec<u8>,
+    /// Decoded payload not yet handed to the reader.
+    data: Vec<u8>,
+    data_pos: usize,
+    /// Pong/close frames queued by the read side.
+    ctrl: Vec<u8>,
+    /// The data frame being written, and how many caller bytes it carries.
+    out: Vec<u8>,
+    out_pos: usize,
+    out_len: usize,
+    /// A close frame was received; reads return EOF.
+    closed: bool,
+}
+
+impl<S> WsStream<S> {
+    fn new(inner: S) -> Self {
+        Self {
+            inner,
+            raw: Vec::new(),
+            data: Vec::new(),
+            data_pos: 0,
+            ctrl: Vec::new(),
+            out: Vec::new(),
+            out_pos: 0,
+            out_len: 0,
+            closed: false,
+        }
+    }
+
+    /// Decode every complete frame in `raw`.
+    fn decode_pending(&mut self) -> Result<(), WsError> {
+        while !self.closed {
+            let Some((frame, used)) = decode_frame(&self.raw)? else { break };
+            self.raw.drain(..used);
+            match frame.opcode {
+                OP_BINARY | OP_CONTINUATION => self.data.extend_from_slice(&frame.payload),
+                OP_TEXT => return Err(WsError::Protocol("text frames not supported")),
+                OP_PING => self.ctrl.extend_from_slice(&encode_frame(OP_PONG, &frame.payload)),
+                OP_PONG => {}
+                OP_CLOSE => {
+                    let code = frame.payload.get(..2).unwrap_or(&[]);
+                    self.ctrl.extend_from_slice(&encode_frame(OP_CLOSE, code));
+                    self.closed = true;
+                }
+                _ => return Err(WsError::Protocol("unknown opcode")),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: AsyncWrite + Unpin> WsStream<S> {
+    /// Write out queued control frames. Does nothing while a data frame is
+    /// part way out, since a control frame must not land inside its payload;
+    /// they go out once that frame is finished.
+    fn poll_ctrl(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.out_pos > 0 {
+            return Poll::Ready(Ok(()));
+        }
+        while !self.ctrl.is_empty() {
+            let n = std::task::ready!(Pin::new(&mut self.inner).poll_write(cx, &self.ctrl))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.ctrl.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Finish writing the current data frame, if any.
+    fn poll_out(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.out_pos < self.out.len() {
+            let n = std::task::ready!(Pin::new(&mut self.inner).poll_write(cx, &self.out[self.out_pos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.out_pos += n;
+        }
+        self.out.clear();
+        self.out_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for WsStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            // Best effort: pongs go out whenever the socket has room.
+            if let Poll::Ready(Err(e)) = this.poll_ctrl(cx) {
+                return Poll::Ready(Err(e));
+            }
+            if this.data_pos < this.data.len() {
+                let n = buf.remaining().min(this.data.len() - this.data_pos);
+                buf.put_slice(&this.data[this.data_pos..this.data_pos + n]);
+                this.data_pos += n;
+                if this.data_pos == this.data.len() {
+                    this.data.clear();
+                    this.data_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if this.closed {
+                return Poll::Ready(Ok(()));
+            }
+            this.decode_pending()?;
+            if this.data_pos < this.data.len() || this.closed {
+                continue;
+            }
+            let mut chunk = [0u8; 4096];
+            let mut rb = ReadBuf::new(&mut chunk);
+            std::task::ready!(Pin::new(&mut this.inner).poll_read(cx, &mut rb))?;
+            if rb.filled().is_empty() {
+                this.closed = true;
+                continue;
+            }
+            this.raw.extend_from_slice(rb.filled());
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for WsStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        std::task::ready!(this.poll_ctrl(cx))?;
+        if this.out.is_empty() {
+            if buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            this.out_len = buf.len().min(MAX_OUT_PAYLOAD);
+            this.out = encode_frame(OP_BINARY, &buf[..this.out_len]);
+            this.out_pos = 0;
+        }
+        std::task::ready!(this.poll_out(cx))?;
+        Poll::Ready(Ok(this.out_len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        std::task::ready!(this.poll_ctrl(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.closed {
+            this.ctrl.extend_from_slice(&encode_frame(OP_CLOSE, &1000u16.to_be_bytes()));
+            this.closed = true;
+        }
+        std::task::ready!(this.poll_out(cx))?;
+        std::task::ready!(this.poll_ctrl(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn masked(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x11, 0x22, 0x33, 0x44];
+        let mut f = encode_frame(opcode, payload);
+        let hdr = f.len() - payload.len();
+        f[1] |= 0x80;
+        f.truncate(hdr);
+        f.extend_from_slice(&mask);
+        f.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        f
+    }
+
+    #[test]
+    fn test_accept_key_rfc_example() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_parse_upgrade() {
+        let req = b"GET / HTTP/1.1\r\nHost: x\r\nUpgrade: WebSocket\r\nConnection: Upgrade\r\n\
+                    Sec-WebSocket-Key: abc==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+        assert_eq!(parse_upgrade(req).unwrap(), "abc==");
+        assert!(parse_upgrade(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n").is_err());
+        assert!(parse_upgrade(b"POST / HTTP/1.1\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_decode_frame_lengths_and_mask() {
+        let big = vec![7u8; 300];
+        let raw = masked(OP_BINARY, &big);
+        assert_eq!(decode_frame(&raw[..raw.len() - 1]).unwrap(), None);
+        let (f, used) = decode_frame(&raw).unwrap().unwrap();
+        assert_eq!((f.opcode, f.payload, used), (OP_BINARY, big, raw.len()));
+        assert!(matches!(decode_frame(&encode_frame(OP_BINARY, b"x")), Err(WsError::Protocol(_))));
+    }
+
+    /// Takes at most `step` bytes per write and returns `Pending` on every
+    /// other call, so frames go out in pieces.
+    struct Trickle {
+        written: Vec<u8>,
+        step: usize,
+        stall: bool,
+    }
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            self.stall = !self.stall;
+            if self.stall {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = buf.len().min(self.step);
+            self.written.extend_from_slice(&buf[..n]);
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_control_frame_waits_for_data_frame_boundary() {
+        let mut ws = WsStream::new(Trickle { written: Vec::new(), step: 3, stall: false });
+        let payload = [0xAAu8, 0x00, 0x05, 0x10, 1, 2, 3, 4];
+        let mut write = ws.write_all(&payload);
+        // Start the data frame, then queue a pong as a ping read would.
+        assert!(poll_once(&mut write).is_pending());
+        assert!(poll_once(&mut write).is_pending());
+        drop(write);
+        assert!(ws.out_pos > 0);
+        ws.ctrl.extend_from_slice(&encode_frame(OP_PONG, b"hi"));
+        // The retry finishes the frame; the pong goes out before the next one.
+        ws.write_all(&payload).await.unwrap();
+        ws.write_all(&payload).await.unwrap();
+
+        let data = encode_frame(OP_BINARY, &payload);
+        let mut expected = data.clone();
+        expected.extend_from_slice(&encode_frame(OP_PONG, b"hi"));
+        expected.extend_from_slice(&data);
+        assert_eq!(ws.inner.written, expected);
+    }
+
+    fn poll_once<F: std::future::Future + Unpin>(f: &mut F) -> Poll<F::Output> {
+        std::future::Future::poll(Pin::new(f), &mut Context::from_waker(std::task::Waker::noop()))
+    }
+
+    #[tokio::test]
+    async fn test_stream_roundtrip_over_duplex() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let srv = tokio::spawn(async move {
+            let mut ws = accept(server).await.unwrap();
+            let mut got = vec![0u8; 6];
+            ws.read_exact(&mut got).await.unwrap();
+            ws.write_all(&got).await.unwrap();
+            let mut rest = Vec::new();
+            ws.read_to_end(&mut rest).await.unwrap();
+            (got, rest)
+        });
+
+        let mut client = client;
+        client.write_all(b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n").await.unwrap();
+        // 0xAA packet split across two frames plus a ping, then close.
+        client.write_all(&masked(OP_BINARY, &[0xAA, 0x00, 0x03])).await.unwrap();
+        client.write_all(&masked(OP_PING, b"hi")).await.unwrap();
+        client.write_all(&masked(OP_CONTINUATION, &[0x10, 0x01, 0x02])).await.unwrap();
+
+        let mut resp = vec![0u8; 129];
+        client.read_exact(&mut resp).await.unwrap();
+        assert!(resp.starts_with(b"HTTP/1.1 101"));
+        let mut frames = vec![0u8; 4 + 8];
+        client.read_exact(&mut frames).await.unwrap();
+        assert_eq!(&frames[..4], &encode_frame(OP_PONG, b"hi")[..]);
+        assert_eq!(&frames[4..], &encode_frame(OP_BINARY, &[0xAA, 0x00, 0x03, 0x10, 0x01, 0x02])[..]);
+
+        client.write_all(&masked(OP_CLOSE, &1000u16.to_be_bytes())).await.unwrap();
+        let (got, rest) = srv.await.unwrap();
+        assert_eq!(got, [0xAA, 0x00, 0x03, 0x10, 0x01, 0x02]);
+        assert!(rest.is_empty());
+    }
+}
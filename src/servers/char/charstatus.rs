@@ -237,23 +237,89 @@ pub struct MmoCharStatus {
     pub banks: [BankData; MAX_BANK_SLOTS],
 }
 
-/// Cast a zero-initialized boxed MmoCharStatus to its raw bytes.
-pub fn char_status_to_bytes(s: &MmoCharStatus) -> &[u8] {
+// ── Wire framing ──────────────────────────────────────────────────────────────
+//
+// Every mmo_charstatus blob exchanged between char and map servers is
+// prefixed with an 8-byte header: "YCS", a layout version, and the struct
+// size (u32 LE). Bump CHARSTATUS_VERSION whenever the struct layout changes,
+// so a char/map pair built from different layouts refuses each other's blobs
+// instead of writing misaligned fields to the database.
+
+pub const CHARSTATUS_MAGIC: [u8; 3] = *b"YCS";
+pub const CHARSTATUS_VERSION: u8 = 1;
+pub const CHARSTATUS_HEADER_LEN: usize = 8;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum CharStatusError {
+    #[error("charstatus blob truncated: got {got} bytes, need {need}")]
+    Truncated { got: usize, need: usize },
+    #[error("charstatus blob has no YCS header")]
+    BadMagic,
+    #[error("charstatus layout version {got}, expected {want}")]
+    Version { got: u8, want: u8 },
+    #[error("charstatus size field {got} bytes, expected {want}")]
+    Size { got: usize, want: usize },
+    #[error("charstatus blob malformed: {0}")]
+    Malformed(&'static str),
+}
+
+/// The header that precedes a raw MmoCharStatus on the wire.
+pub fn char_status_header() -> [u8; CHARSTATUS_HEADER_LEN] {
+    let mut h = [0u8; CHARSTATUS_HEADER_LEN];
+    h[..3].copy_from_slice(&CHARSTATUS_MAGIC);
+    h[3] = CHARSTATUS_VERSION;
+    h[4..8].copy_from_slice(&(std::mem::size_of::<MmoCharStatus>() as u32).to_le_bytes());
+    h
+}
+
+/// Prefix raw MmoCharStatus bytes (e.g. a C `sd->status`) with the header.
+pub fn frame_char_status(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(CHARSTATUS_HEADER_LEN + raw.len());
+    out.extend_from_slice(&char_status_header());
+    out.extend_from_slice(raw);
+    out
+}
+
+/// Check the header and length and return the raw struct bytes that follow.
+pub fn char_status_payload(bytes: &[u8]) -> Result<&[u8], CharStatusError> {
+    let want = std::mem::size_of::<MmoCharStatus>();
+    if bytes.len() < CHARSTATUS_HEADER_LEN {
+        return Err(CharStatusError::Truncated { got: bytes.len(), need: CHARSTATUS_HEADER_LEN + want });
+    }
+    if bytes[..3] != CHARSTATUS_MAGIC {
+        return Err(CharStatusError::BadMagic);
+    }
+    if bytes[3] != CHARSTATUS_VERSION {
+        return Err(CharStatusError::Version { got: bytes[3], want: CHARSTATUS_VERSION });
+    }
+    let declared = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    if declared != want {
+        return Err(CharStatusError::Size { got: declared, want });
+    }
+    let body = &bytes[CHARSTATUS_HEADER_LEN..];
+    if body.len() != want {
+        return Err(CharStatusError::Truncated { got: bytes.len(), need: CHARSTATUS_HEADER_LEN + want });
+    }
+    Ok(body)
+}
+
+/// Raw bytes of a MmoCharStatus, framed with the wire header.
+pub fn char_status_to_bytes(s: &MmoCharStatus) -> Vec<u8> {
     // Safety: MmoCharStatus is #[repr(C)] with no padding beyond explicit _pad fields.
-    unsafe {
+    let raw = unsafe {
         std::slice::from_raw_parts(
             s as *const MmoCharStatus as *const u8,
             std::mem::size_of::<MmoCharStatus>(),
         )
-    }
+    };
+    frame_char_status(raw)
 }
 
-/// Copy a byte slice into an aligned, heap-allocated MmoCharStatus.
-/// Returns None if the slice is too short.
-pub fn char_status_from_bytes(bytes: &[u8]) -> Option<Box<MmoCharStatus>> {
-    if bytes.len() < std::mem::size_of::<MmoCharStatus>() {
-        return None;
-    }
+/// Validate a framed blob and copy it into an aligned, heap-allocated
+/// MmoCharStatus. Counts that index fixed arrays are range-checked, so a blob from a mismatched or corrupt peer is refused
+/// rather than persisted.
+pub fn char_status_from_bytes(bytes: &[u8]) -> Result<Box<MmoCharStatus>, CharStatusError> {
+    let body = char_status_payload(bytes)?;
     // Allocate aligned memory and copy bytes in — avoids UB from casting a
     // potentially 1-byte-aligned &[u8] pointer directly to *const MmoCharStatus.
     let mut s: Box<MmoCharStatus> = unsafe {
@@ -266,12 +332,20 @@ pub fn char_status_from_bytes(bytes: &[u8]) -> Option<Box<MmoCharStatus>> {
     };
     unsafe {
         std::ptr::copy_nonoverlapping(
-            bytes.as_ptr(),
+            body.as_ptr(),
             &mut *s as *mut MmoCharStatus as *mut u8,
             std::mem::size_of::<MmoCharStatus>(),
         );
     }
-    Some(s)
+    if s.maxinv as usize > MAX_INVENTORY {
+        return Err(CharStatusError::Malformed("maxinv exceeds MAX_INVENTORY"));
+    }
+    if !(0..=MAX_GLOBALREG as i32).contains(&s.global_reg_num)
+        || !(0..=MAX_GLOBALREG as i32).contains(&s.global_regstring_num)
+    {
+        return Err(CharStatusError::Malformed("registry count out of range"));
+    }
+    Ok(s)
 }
 
 // ── Size verification tests ───────────────────────────────────────────────────
@@ -296,4 +370,40 @@ mod tests {
     fn test_charstatus_size() {
         assert_eq!(std::mem::size_of::<MmoCharStatus>(), 3_171_352);
     }
+
+    fn blank() -> Vec<u8> {
+        frame_char_status(&vec![0u8; std::mem::size_of::<MmoCharStatus>()])
+    }
+
+    #[test]
+    fn test_charstatus_roundtrip() {
+        let mut bytes = blank();
+        bytes[CHARSTATUS_HEADER_LEN..CHARSTATUS_HEADER_LEN + 4].copy_from_slice(&42u32.to_le_bytes());
+        let s = char_status_from_bytes(&bytes).unwrap();
+        assert_eq!(s.id, 42);
+        assert_eq!(char_status_to_bytes(&s), bytes);
+    }
+
+    #[test]
+    fn test_charstatus_rejects_bad_frames() {
+        let good = blank();
+        let size = std::mem::size_of::<MmoCharStatus>();
+
+        // Right length, no header: the old unframed format.
+        let unframed = vec![0u8; size + CHARSTATUS_HEADER_LEN];
+        assert_eq!(char_status_payload(&unframed).unwrap_err(), CharStatusError::BadMagic);
+
+        let mut v = good.clone();
+        v[3] = CHARSTATUS_VERSION + 1;
+        assert!(matches!(char_status_payload(&v), Err(CharStatusError::Version { .. })));
+
+        let mut v = good.clone();
+        v[4..8].copy_from_slice(&((size - 8) as u32).to_le_bytes());
+        assert!(matches!(char_status_payload(&v), Err(CharStatusError::Size { .. })));
+
+        assert!(matches!(char_status_payload(&good[..good.len() - 1]), Err(CharStatusError::Truncated { .. })));
+        let mut long = good.clone();
+        long.push(0);
+        assert!(matches!(char_status_payload(&long), Err(CharStatusError::Truncated { .. })));
+    }
 }
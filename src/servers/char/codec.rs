@@ -0,0 +1,152 @@
+//! Compression for mmo_charstatus blobs on the char↔map link.
+//!
+//! The sender compresses with the configured `charstatus_codec`; the receiver
+//! identifies the codec from the stream's own magic bytes, so both ends agree
+//! without a handshake. A char server on zlib and a map server on zstd (or a
+//! mid-upgrade pair) interoperate, and the C `intif_save` path, which always
+//! uses zlib `compress2`, keeps working whatever is configured.
+//!
+//! zstd needs the `zstd` cargo feature. Without it the codec still parses, so
+//! `ServerConfig::validate` can name the missing feature, and zstd blobs from
+//! a peer fail to decompress instead of being misread.
+
+use std::io::{Read, Write};
+
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+/// zstd level for charstatus: the low end, where zstd already beats zlib's
+/// default on both ratio and speed for these mostly-zero blobs.
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// RFC 1950; what every release before codec selection sent.
+    #[default]
+    Zlib,
+    /// RFC 8878; needs the `zstd` feature.
+    Zstd,
+}
+
+impl Codec {
+    /// The codec that produced `data`, judged from its header.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            // CMF: deflate with a window ≤ 32K; CMF/FLG form a multiple of 31.
+            [cmf, flg, ..] if cmf & 0x0f == 8 && cmf >> 4 <= 7
+                && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31) => Some(Self::Zlib),
+            _ => None,
+        }
+    }
+
+    /// Whether this build can compress and decompress with the codec.
+    pub fn is_available(self) -> bool {
+        self != Self::Zstd || cfg!(feature = "zstd")
+    }
+}
+
+pub fn compress(codec: Codec, raw: &[u8]) -> Vec<u8> {
+    compress_parts(codec, &[raw])
+}
+
+/// Compress the concatenation of `parts` without joining them first (the
+/// charstatus header and ~3MB body are written separately). A codec this
+/// build lacks falls back to zlib, which every receiver reads.
+pub fn compress_parts(codec: Codec, parts: &[&[u8]]) -> Vec<u8> {
+    if codec == Codec::Zstd {
+        if let Some(out) = zstd_parts(parts) {
+            return out;
+        }
+    }
+    write_all(flate2::write::ZlibEncoder::new(Vec::new(), Compression::default()), parts)
+        .finish()
+        .unwrap_or_default()
+}
+
+fn write_all<W: Write>(mut w: W, parts: &[&[u8]]) -> W {
+    for p in parts {
+        // Writing into a Vec cannot fail.
+        let _ = w.write_all(p);
+    }
+    w
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_parts(parts: &[&[u8]]) -> Option<Vec<u8>> {
+    let enc = zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL).ok()?;
+    write_all(enc, parts).finish().ok()
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_parts(_parts: &[&[u8]]) -> Option<Vec<u8>> {
+    None
+}
+
+/// A reader inflating `data`, or None if its codec is unknown or not built in.
+pub fn decoder(data: &[u8]) -> Option<Box<dyn Read + '_>> {
+    match Codec::detect(data)? {
+        Codec::Zlib => Some(Box::new(flate2::read::ZlibDecoder::new(data))),
+        #[cfg(feature = "zstd")]
+        Codec::Zstd => zstd::stream::read::Decoder::with_buffer(data).ok().map(|d| Box::new(d) as Box<dyn Read + '_>),
+        #[cfg(not(feature = "zstd"))]
+        Codec::Zstd => None,
+    }
+}
+
+/// Decompress a blob from any codec this build supports.
+pub fn decompress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match decoder(data) {
+        Some(mut r) => r.read_to_end(&mut out)?,
+        None => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "unknown charstatus codec")),
+    };
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(codec: Codec) {
+        let body: Vec<u8> = (0..100_000u32).map(|i| (i % 7) as u8).collect();
+        let packed = compress_parts(codec, &[b"YCS\x01", &body]);
+        assert_eq!(Codec::detect(&packed), Some(codec));
+        assert!(packed.len() < body.len() / 10);
+        let out = decompress(&packed).unwrap();
+        assert_eq!((&out[..4], &out[4..]), (&b"YCS\x01"[..], &body[..]));
+    }
+
+    #[test]
+    fn test_round_trip_zlib() {
+        round_trip(Codec::Zlib);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_round_trip_zstd() {
+        round_trip(Codec::Zstd);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_zstd_without_the_feature() {
+        assert!(!Codec::Zstd.is_available());
+        // Falls back to zlib on send; a peer's zstd frame is refused, not misread.
+        assert_eq!(Codec::detect(&compress(Codec::Zstd, b"abc")), Some(Codec::Zlib));
+        let frame = [0x28, 0xb5, 0x2f, 0xfd, 0x20, 0x03, 0x19, 0x00, 0x00, b'a', b'b', b'c'];
+        assert_eq!(Codec::detect(&frame), Some(Codec::Zstd));
+        assert!(decompress(&frame).is_err());
+    }
+
+    #[test]
+    fn test_detect_rejects_garbage() {
+        assert_eq!(Codec::detect(b"not zlib"), None);
+        assert_eq!(Codec::detect(&[0x78]), None);
+        assert!(decompress(b"").is_err());
+        // A valid zlib header over a corrupt stream is still an error.
+        assert!(decompress(&[0x78, 0x9c, 0xff, 0xff]).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use sqlx::{MySqlPool, Row, Transaction, MySql};
+use sqlx::{MySqlPool, QueryBuilder, Row, Transaction, MySql};
 use anyhow::Result;
 use md5::{Md5, Digest};
 use crate::servers::char::charstatus::*;
@@ -30,15 +30,15 @@ pub async fn hash_password(pass: &str) -> Result<String> {
     .map_err(|e| anyhow::anyhow!("spawn_blocking failed: {}", e))?
 }
 
-/// Verify password against stored hash.
-/// If stored is a bcrypt hash ($2b$/$2a$ prefix), uses bcrypt::verify on a blocking thread.
-/// Otherwise falls back to MD5("lowercase_name password") or MD5(password).
-pub async fn ispass(name: &str, pass: &str, stored_hash: &str) -> bool {
-    if !is_legacy_hash(stored_hash) {
+/// Check `pass` against `stored`. A bcrypt hash (any `$2?$` prefix) is
+/// verified on a blocking thread; anything else is a legacy MD5 hash, matched
+/// against the MD5 of each of `legacy`.
+async fn verify_password(pass: &str, stored: &str, legacy: &[String]) -> bool {
+    if !is_legacy_hash(stored) {
         let pass = pass.to_owned();
-        let stored_hash = stored_hash.to_owned();
+        let stored = stored.to_owned();
         return tokio::task::spawn_blocking(move || {
-            bcrypt::verify(&pass, &stored_hash).unwrap_or_else(|e| {
+            bcrypt::verify(&pass, &stored).unwrap_or_else(|e| {
                 tracing::error!("[auth] bcrypt::verify error: {}", e);
                 false
             })
@@ -46,29 +46,76 @@ pub async fn ispass(name: &str, pass: &str, stored_hash: &str) -> bool {
         .await
         .unwrap_or(false);
     }
-    let form1 = md5_hex(&format!("{} {}", name.to_lowercase(), pass));
-    let form2 = md5_hex(pass);
-    stored_hash == form1 || stored_hash == form2
+    legacy.iter().any(|form| md5_hex(form) == stored)
+}
+
+/// Verify a character password against its stored hash: bcrypt, or legacy
+/// MD5("lowercase_name password") / MD5(password).
+pub async fn ispass(name: &str, pass: &str, stored_hash: &str) -> bool {
+    let legacy = [format!("{} {}", name.to_lowercase(), pass), pass.to_owned()];
+    verify_password(pass, stored_hash, &legacy).await
 }
 
 /// Returns true if master password matches and hasn't expired.
-/// Supports both bcrypt and legacy MD5 stored hashes.
+/// Supports both bcrypt and legacy MD5(password) stored hashes.
 pub async fn ismastpass(pass: &str, stored: &str, expire: u32) -> bool {
     let now = chrono::Utc::now().timestamp();
     if now > expire as i64 { return false; }
-    if !is_legacy_hash(stored) {
-        let pass = pass.to_owned();
-        let stored = stored.to_owned();
-        return tokio::task::spawn_blocking(move || {
-            bcrypt::verify(&pass, &stored).unwrap_or_else(|e| {
-                tracing::error!("[auth] bcrypt::verify error: {}", e);
-                false
-            })
-        })
-        .await
-        .unwrap_or(false);
+    verify_password(pass, stored, &[pass.to_owned()]).await
+}
+
+/// Replace `name`'s legacy MD5 hash with bcrypt of `pass`, which has just
+/// verified against it. Accounts migrate this way as their owners log in.
+pub async fn upgrade_char_password(pool: &MySqlPool, name: &str, pass: &str) -> Result<()> {
+    let hashed = hash_password(pass).await?;
+    sqlx::query("UPDATE `Character` SET `ChaPassword` = ? WHERE `ChaName` = ?")
+        .bind(hashed).bind(name)
+        .execute(pool).await?;
+    Ok(())
+}
+
+/// [`upgrade_char_password`] for the master password.
+pub async fn upgrade_master_password(pool: &MySqlPool, pass: &str) -> Result<()> {
+    let hashed = hash_password(pass).await?;
+    sqlx::query("UPDATE `AdminPassword` SET `AdmPassword` = ? WHERE `AdmId` = 1")
+        .bind(hashed)
+        .execute(pool).await?;
+    Ok(())
+}
+
+/// Longest password the login protocol carries (16-byte field).
+pub const MAX_PASSWORD_LEN: usize = 16;
+
+/// Passwords refused outright, compared case-insensitively.
+const COMMON_PASSWORDS: &[&str] = &[
+    "1234", "12345", "123456", "1234567", "12345678", "0000", "000000", "1111", "111111",
+    "abcd", "abc123", "pass", "password", "qwerty", "letmein", "admin", "iloveyou",
+];
+
+/// Why `validate_password` refused a new password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PassPolicyError {
+    #[error("password shorter than {0} characters")]
+    TooShort(usize),
+    #[error("password is the character name")]
+    SameAsName,
+    #[error("password is too common")]
+    TooCommon,
+}
+
+/// Check a new character's password against the server's policy: at least
+/// `min_len` characters (`min_password_len`), not the name, not a common one.
+pub fn validate_password(name: &str, pass: &str, min_len: usize) -> Result<(), PassPolicyError> {
+    if pass.chars().count() < min_len {
+        return Err(PassPolicyError::TooShort(min_len));
     }
-    md5_hex(pass) == stored
+    if pass.eq_ignore_ascii_case(name) {
+        return Err(PassPolicyError::SameAsName);
+    }
+    if COMMON_PASSWORDS.iter().any(|p| p.eq_ignore_ascii_case(pass)) {
+        return Err(PassPolicyError::TooCommon);
+    }
+    Ok(())
 }
 
 /// Returns true if character name is already taken.
@@ -82,26 +129,50 @@ pub async fn is_name_used(pool: &MySqlPool, name: &str) -> Result<bool> {
     Ok(row.map(|(n,)| n > 0).unwrap_or(false))
 }
 
-/// Create a new character. Returns 0 on success, 1 if name taken, 2 on DB error.
+/// Why `create_char` did not create the character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CharCreateError {
+    #[error("name already taken")]
+    NameTaken,
+    #[error("database error")]
+    Db,
+    #[error(transparent)]
+    WeakPassword(#[from] PassPolicyError),
+}
+
+/// Legacy 0x2002 result codes: 1 name taken, 2 database error, 3 weak password.
+impl From<CharCreateError> for i32 {
+    fn from(e: CharCreateError) -> i32 {
+        match e {
+            CharCreateError::NameTaken => 1,
+            CharCreateError::Db => 2,
+            CharCreateError::WeakPassword(_) => 3,
+        }
+    }
+}
+
+/// Create a new character. The password must pass `validate_password`.
 pub async fn create_char(
     pool: &MySqlPool,
     name: &str, pass: &str, totem: u8, sex: u8,
     country: u8, face: u16, hair: u16, face_color: u16, hair_color: u16,
     start_m: u32, start_x: u32, start_y: u32,
-) -> i32 {
+    min_pass_len: usize,
+) -> Result<(), CharCreateError> {
+    validate_password(name, pass, min_pass_len)?;
     match is_name_used(pool, name).await {
-        Err(_)       => return 2,
-        Ok(true)     => return 1,
-        Ok(false)    => {}
-    }
-    let hashed = match hash_password(pass).await {
-        Ok(h) => h,
         Err(e) => {
-            tracing::error!("[char] hash_password failed: {}", e);
-            return 2;
+            tracing::error!("[char] [newchar] name lookup failed: {}", e);
+            return Err(CharCreateError::Db);
         }
-    };
-    let res = sqlx::query(
+        Ok(true) => return Err(CharCreateError::NameTaken),
+        Ok(false) => {}
+    }
+    let hashed = hash_password(pass).await.map_err(|e| {
+        tracing::error!("[char] hash_password failed: {}", e);
+        CharCreateError::Db
+    })?;
+    sqlx::query(
         "INSERT INTO `Character` (`ChaName`, `ChaPassword`, `ChaTotem`, `ChaSex`,
          `ChaNation`, `ChaFace`, `ChaMapId`, `ChaX`, `ChaY`,
          `ChaHair`, `ChaHairColor`, `ChaFaceColor`)
@@ -111,11 +182,15 @@ pub async fn create_char(
     .bind(country).bind(face).bind(start_m).bind(start_x).bind(start_y)
     .bind(hair).bind(hair_color).bind(face_color)
     .execute(pool)
-    .await;
-    if res.is_err() { 2 } else { 0 }
+    .await
+    .map_err(|e| {
+        tracing::error!("[char] [newchar] insert failed for {}: {}", name, e);
+        CharCreateError::Db
+    })?;
+    Ok(())
 }
 
-/// Fetch stored MD5 password hash for a character name.
+/// Fetch the stored password hash (bcrypt or legacy MD5) for a character name.
 pub async fn get_char_password(pool: &MySqlPool, name: &str) -> Result<Option<String>> {
     let row: Option<(String,)> = sqlx::query_as(
         "SELECT `ChaPassword` FROM `Character` WHERE `ChaName` = ?"
@@ -174,6 +249,26 @@ pub async fn is_account_banned(pool: &MySqlPool, char_id: u32) -> bool {
     row.map(|(n,)| n > 0).unwrap_or(false)
 }
 
+/// Every character id on the account that owns `char_id`, including it
+/// (empty when the character has no account row).
+pub async fn account_char_ids(pool: &MySqlPool, char_id: u32) -> Vec<u32> {
+    type Slots = (Option<u32>, Option<u32>, Option<u32>, Option<u32>, Option<u32>, Option<u32>);
+    let row: Option<Slots> = sqlx::query_as(
+        "SELECT `AccountCharId1`, `AccountCharId2`, `AccountCharId3`,
+                `AccountCharId4`, `AccountCharId5`, `AccountCharId6`
+         FROM `Accounts`
+         WHERE ? IN (`AccountCharId1`, `AccountCharId2`, `AccountCharId3`,
+                     `AccountCharId4`, `AccountCharId5`, `AccountCharId6`)
+         LIMIT 1"
+    )
+    .bind(char_id)
+    .fetch_optional(pool)
+    .await
+    .unwrap_or(None);
+    row.map(|(a, b, c, d, e, f)| [a, b, c, d, e, f].into_iter().flatten().filter(|&id| id != 0).collect())
+        .unwrap_or_default()
+}
+
 /// Clear all stale ChaOnline flags on startup (handles crashes/ungraceful shutdowns).
 pub async fn reset_all_online(pool: &MySqlPool) {
     if let Err(e) = sqlx::query("UPDATE `Character` SET `ChaOnline` = 0 WHERE `ChaOnline` = 1")
@@ -193,27 +288,154 @@ pub async fn set_online(pool: &MySqlPool, char_id: u32, online: bool) {
     }
 }
 
-/// Change password after verifying old password. Returns 0=ok, -2=no user, -3=wrong pass, -1=db error.
-pub async fn set_char_password(pool: &MySqlPool, name: &str, pass: &str, newpass: &str) -> i32 {
+/// Flag `char_id` online and record the `server_id` of the stack it logged in
+/// through, so a draining login server counts only its own players.
+pub async fn mark_online(pool: &MySqlPool, char_id: u32, server_id: i32) {
+    if let Err(e) = sqlx::query("UPDATE `Character` SET `ChaOnline` = 1, `ChaServer` = ? WHERE `ChaId` = ?")
+        .bind(server_id.max(0)).bind(char_id)
+        .execute(pool).await
+    {
+        tracing::error!("Failed to mark ChaId {} online on server {}: {}", char_id, server_id, e);
+    }
+}
+
+/// Last recorded successful login for a character.
+/// `ip` is the IPv4 address as a big-endian u32; both fields are 0 when never recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LastLogin {
+    pub ip: u32,
+    pub time: u32,
+}
+
+impl LastLogin {
+    pub fn is_recorded(&self) -> bool {
+        self.ip != 0
+    }
+
+    pub fn ip_addr(&self) -> std::net::Ipv4Addr {
+        std::net::Ipv4Addr::from(self.ip)
+    }
+}
+
+/// Fetch the stored last-login IP/time for char_id.
+pub async fn get_last_login(pool: &MySqlPool, char_id: u32) -> Result<Option<LastLogin>> {
+    let row: Option<(u32, u32)> = sqlx::query_as(
+        "SELECT `ChaLastLoginIp`, `ChaLastLoginTime` FROM `Character` WHERE `ChaId` = ?"
+    )
+    .bind(char_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|(ip, time)| LastLogin { ip, time }))
+}
+
+/// Record a successful login from `ip` at the current time.
+pub async fn record_last_login(pool: &MySqlPool, char_id: u32, ip: u32) {
+    let now = chrono::Utc::now().timestamp() as u32;
+    if let Err(e) = sqlx::query(
+        "UPDATE `Character` SET `ChaLastLoginIp` = ?, `ChaLastLoginTime` = ? WHERE `ChaId` = ?"
+    )
+    .bind(ip).bind(now).bind(char_id)
+    .execute(pool).await
+    {
+        tracing::error!("Failed to record last login for ChaId {}: {}", char_id, e);
+    }
+}
+
+/// Why `set_char_password` did not change the password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PassChangeError {
+    #[error("no such character")]
+    NoUser,
+    #[error("wrong password")]
+    WrongPass,
+    #[error("database error")]
+    Db,
+}
+
+/// Legacy codes: -2 no user, -3 wrong password, -1 database error.
+impl From<PassChangeError> for i32 {
+    fn from(e: PassChangeError) -> i32 {
+        match e {
+            PassChangeError::NoUser => -2,
+            PassChangeError::WrongPass => -3,
+            PassChangeError::Db => -1,
+        }
+    }
+}
+
+/// Change password after verifying the old one.
+pub async fn set_char_password(pool: &MySqlPool, name: &str, pass: &str, newpass: &str) -> Result<(), PassChangeError> {
     let stored = match get_char_password(pool, name).await {
         Ok(Some(h)) => h,
-        Ok(None) => return -2,
-        Err(_) => return -1,
-    };
-    if !ispass(name, pass, &stored).await { return -3; }
-    let hashed = match hash_password(newpass).await {
-        Ok(h) => h,
+        Ok(None) => return Err(PassChangeError::NoUser),
         Err(e) => {
-            tracing::error!("[char] hash_password failed: {}", e);
-            return -1;
+            tracing::error!("[char] [setpass] lookup failed for {}: {}", name, e);
+            return Err(PassChangeError::Db);
         }
     };
-    let res = sqlx::query(
+    if !ispass(name, pass, &stored).await {
+        return Err(PassChangeError::WrongPass);
+    }
+    let hashed = hash_password(newpass).await.map_err(|e| {
+        tracing::error!("[char] hash_password failed: {}", e);
+        PassChangeError::Db
+    })?;
+    sqlx::query(
         "UPDATE `Character` SET `ChaPassword` = ? WHERE `ChaName` = ?"
     )
     .bind(hashed).bind(name)
-    .execute(pool).await;
-    if res.is_err() { -1 } else { 0 }
+    .execute(pool).await
+    .map_err(|e| {
+        tracing::error!("[char] [setpass] update failed for {}: {}", name, e);
+        PassChangeError::Db
+    })?;
+    Ok(())
+}
+
+/// The 67-column `Character` row read by `load_char_bytes`; column order is
+/// the order the blob is filled in. sqlx caches prepared statements per
+/// connection keyed by SQL text, so one fixed string is prepared once per
+/// pooled connection rather than on every login.
+const LOAD_CHAR_SQL: &str =
+    "SELECT `ChaName`, `ChaClnId`, `ChaClanTitle`, `ChaTitle`, \
+     `ChaF1Name`, `ChaLevel`, `ChaPthId`, `ChaMark`, \
+     `ChaTotem`, `ChaKarma`, `ChaCurrentVita`, `ChaBaseVita`, \
+     `ChaCurrentMana`, `ChaBaseMana`, `ChaExperience`, `ChaGold`, `ChaSex`, \
+     `ChaNation`, `ChaFace`, `ChaHairColor`, `ChaArmorColor`, \
+     `ChaMapId`, `ChaX`, `ChaY`, `ChaSide`, `ChaState`, `ChaHair`, `ChaFaceColor`, \
+     `ChaSkinColor`, `ChaPartner`, `ChaClanChat`, `ChaPathChat`, `ChaNoviceChat`, \
+     `ChaSettings`, `ChaGMLevel`, `ChaDisguise`, `ChaDisguiseColor`, \
+     `ChaMaximumBankSlots`, `ChaBankGold`, `ChaMaximumInventory`, `ChaPK`, \
+     `ChaKilledBy`, `ChaKillsPK`, `ChaPKDuration`, `ChaMuted`, `ChaHeroes`, `ChaTier`, \
+     `ChaExperienceSoldMagic`, `ChaExperienceSoldHealth`, `ChaExperienceSoldStats`, \
+     `ChaBaseMight`, `ChaBaseWill`, `ChaBaseGrace`, `ChaBaseArmor`, `ChaMiniMapToggle`, \
+     `ChaLastIP`, `ChaAFKMessage`, `ChaTutor`, `ChaAlignment`, \
+     `ChaProfileVitaStats`, `ChaProfileEquipList`, `ChaProfileLegends`, \
+     `ChaProfileSpells`, `ChaProfileInventory`, `ChaProfileBankItems`, \
+     `ChaPthRank`, `ChaClnRank` \
+     FROM `Character` WHERE `ChaId` = ? LIMIT 1";
+
+/// Rolling `load_char_bytes` latencies, reported as p50/p99 every
+/// `LOAD_TIMING_WINDOW` loads when `char_load_timing` is on.
+pub const LOAD_TIMING_WINDOW: usize = 100;
+
+#[derive(Debug, Default)]
+pub struct LoadTimings {
+    samples: std::sync::Mutex<Vec<std::time::Duration>>,
+}
+
+impl LoadTimings {
+    /// Record one load. Returns (p50, p99) and starts a new window once
+    /// `LOAD_TIMING_WINDOW` samples are in.
+    pub fn record(&self, d: std::time::Duration) -> Option<(std::time::Duration, std::time::Duration)> {
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples.push(d);
+        if samples.len() < LOAD_TIMING_WINDOW { return None; }
+        let mut sorted = std::mem::take(&mut *samples);
+        sorted.sort_unstable();
+        let pct = |p: usize| sorted[(sorted.len() * p / 100).min(sorted.len() - 1)];
+        Some((pct(50), pct(99)))
+    }
 }
 
 /// Load a character from DB and return it as a raw byte blob for zlib transfer.
@@ -226,25 +448,7 @@ pub async fn load_char_bytes(pool: &MySqlPool, char_id: u32, login_name: &str) -
 
     // ── Main character row ────────────────────────────────────────────────────
     // Use manual row access because 67 columns exceeds sqlx's tuple FromRow limit (16).
-    let row = sqlx::query(
-        "SELECT `ChaName`, `ChaClnId`, `ChaClanTitle`, `ChaTitle`, \
-         `ChaF1Name`, `ChaLevel`, `ChaPthId`, `ChaMark`, \
-         `ChaTotem`, `ChaKarma`, `ChaCurrentVita`, `ChaBaseVita`, \
-         `ChaCurrentMana`, `ChaBaseMana`, `ChaExperience`, `ChaGold`, `ChaSex`, \
-         `ChaNation`, `ChaFace`, `ChaHairColor`, `ChaArmorColor`, \
-         `ChaMapId`, `ChaX`, `ChaY`, `ChaSide`, `ChaState`, `ChaHair`, `ChaFaceColor`, \
-         `ChaSkinColor`, `ChaPartner`, `ChaClanChat`, `ChaPathChat`, `ChaNoviceChat`, \
-         `ChaSettings`, `ChaGMLevel`, `ChaDisguise`, `ChaDisguiseColor`, \
-         `ChaMaximumBankSlots`, `ChaBankGold`, `ChaMaximumInventory`, `ChaPK`, \
-         `ChaKilledBy`, `ChaKillsPK`, `ChaPKDuration`, `ChaMuted`, `ChaHeroes`, `ChaTier`, \
-         `ChaExperienceSoldMagic`, `ChaExperienceSoldHealth`, `ChaExperienceSoldStats`, \
-         `ChaBaseMight`, `ChaBaseWill`, `ChaBaseGrace`, `ChaBaseArmor`, `ChaMiniMapToggle`, \
-         `ChaLastIP`, `ChaAFKMessage`, `ChaTutor`, `ChaAlignment`, \
-         `ChaProfileVitaStats`, `ChaProfileEquipList`, `ChaProfileLegends`, \
-         `ChaProfileSpells`, `ChaProfileInventory`, `ChaProfileBankItems`, \
-         `ChaPthRank`, `ChaClnRank` \
-         FROM `Character` WHERE `ChaId` = ? LIMIT 1"
-    ).bind(char_id).fetch_optional(pool).await?;
+    let row = sqlx::query(LOAD_CHAR_SQL).bind(char_id).fetch_optional(pool).await?;
 
     let row = match row { Some(r) => r, None => anyhow::bail!("character not found") };
 
@@ -544,17 +748,15 @@ pub async fn load_char_bytes(pool: &MySqlPool, char_id: u32, login_name: &str) -
     }
 
     tracing::info!("[char] [load_char] name={} map={} x={} y={}", i8_slice_to_str(&s.name), s.last_pos.m, s.last_pos.x, s.last_pos.y);
-    Ok(char_status_to_bytes(&s).to_vec())
+    Ok(char_status_to_bytes(&s))
 }
 
-/// Save a character from a raw byte blob back to the DB.
+/// Save a character back to the DB.
 /// Mirrors mmo_char_todb + sub-table save functions in char_db.c.
-pub async fn save_char_bytes(pool: &MySqlPool, raw: &[u8]) -> Result<()> {
-
-    let s = match char_status_from_bytes(raw) {
-        Some(s) => s,
-        None => anyhow::bail!("invalid char status bytes: got {} bytes, need {}", raw.len(), std::mem::size_of::<crate::servers::char::charstatus::MmoCharStatus>()),
-    };
+/// Callers validate the wire blob with `char_status_from_bytes` first.
+/// The `Character` row and every sub-table are written in one transaction:
+/// a save that fails part-way leaves the previous save intact.
+pub async fn save_char_status(pool: &MySqlPool, s: &MmoCharStatus) -> Result<()> {
     if s.id == 0 { return Ok(()); }
 
     let name      = i8_slice_to_str(&s.name);
@@ -653,49 +855,56 @@ fn i8_slice_to_str(src: &[i8]) -> String {
 }
 
 
+/// Replace a character's inventory with one multi-row INSERT.
+/// Empty slots are simply not inserted, matching the C save.
 async fn save_items_inventory(tx: &mut Transaction<'_, MySql>, char_id: u32, items: &[crate::servers::char::charstatus::Item]) -> Result<()> {
     sqlx::query("DELETE FROM `Inventory` WHERE `InvChaId`=?")
         .bind(char_id).execute(&mut **tx).await?;
-    for (i, item) in items.iter().enumerate().take(MAX_INVENTORY) {
-        if item.id == 0 { continue; }
-        let name = i8_slice_to_str(&item.real_name);
-        let note = i8_slice_to_str(&item.note);
-        sqlx::query(
-            "INSERT INTO `Inventory` \
-             (`InvChaId`,`InvItmId`,`InvAmount`,`InvDurability`,`InvChaIdOwner`,\
-              `InvCustom`,`InvTimer`,`InvEngrave`,`InvCustomLook`,`InvCustomLookColor`,\
-              `InvCustomIcon`,`InvCustomIconColor`,`InvProtected`,`InvNote`,`InvPosition`) \
-             VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)"
-        ).bind(char_id).bind(item.id).bind(item.amount).bind(item.dura).bind(item.owner)
-         .bind(item.custom).bind(item.time).bind(&name)
-         .bind(item.custom_look).bind(item.custom_look_color)
-         .bind(item.custom_icon).bind(item.custom_icon_color)
-         .bind(item.protected).bind(&note).bind(i as u32)
-         .execute(&mut **tx).await?;
-    }
+    let rows: Vec<_> = items.iter().enumerate().take(MAX_INVENTORY)
+        .filter(|(_, item)| item.id != 0)
+        .collect();
+    if rows.is_empty() { return Ok(()); }
+    let mut qb = QueryBuilder::<MySql>::new(
+        "INSERT INTO `Inventory` \
+         (`InvChaId`,`InvItmId`,`InvAmount`,`InvDurability`,`InvChaIdOwner`,\
+          `InvCustom`,`InvTimer`,`InvEngrave`,`InvCustomLook`,`InvCustomLookColor`,\
+          `InvCustomIcon`,`InvCustomIconColor`,`InvProtected`,`InvNote`,`InvPosition`) "
+    );
+    qb.push_values(rows, |mut b, (i, item)| {
+        b.push_bind(char_id).push_bind(item.id).push_bind(item.amount).push_bind(item.dura)
+         .push_bind(item.owner).push_bind(item.custom).push_bind(item.time)
+         .push_bind(i8_slice_to_str(&item.real_name))
+         .push_bind(item.custom_look).push_bind(item.custom_look_color)
+         .push_bind(item.custom_icon).push_bind(item.custom_icon_color)
+         .push_bind(item.protected).push_bind(i8_slice_to_str(&item.note)).push_bind(i as u32);
+    });
+    qb.build().execute(&mut **tx).await?;
     Ok(())
 }
 
+/// Replace a character's equipment with one multi-row INSERT.
 async fn save_items_equipment(tx: &mut Transaction<'_, MySql>, char_id: u32, items: &[crate::servers::char::charstatus::Item]) -> Result<()> {
     sqlx::query("DELETE FROM `Equipment` WHERE `EqpChaId`=?")
         .bind(char_id).execute(&mut **tx).await?;
-    for (i, item) in items.iter().enumerate().take(MAX_EQUIP) {
-        if item.id == 0 { continue; }
-        let name = i8_slice_to_str(&item.real_name);
-        let note = i8_slice_to_str(&item.note);
-        sqlx::query(
-            "INSERT INTO `Equipment` \
-             (`EqpChaId`,`EqpItmId`,`EqpDurability`,`EqpChaIdOwner`,`EqpCustom`,`EqpTimer`,\
-              `EqpEngrave`,`EqpCustomLook`,`EqpCustomLookColor`,`EqpCustomIcon`,\
-              `EqpCustomIconColor`,`EqpProtected`,`EqpNote`,`EqpSlot`) \
-             VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?)"
-        ).bind(char_id).bind(item.id).bind(item.dura).bind(item.owner)
-         .bind(item.custom).bind(item.time)
-         .bind(&name).bind(item.custom_look).bind(item.custom_look_color)
-         .bind(item.custom_icon).bind(item.custom_icon_color)
-         .bind(item.protected).bind(&note).bind(i as u32)
-         .execute(&mut **tx).await?;
-    }
+    let rows: Vec<_> = items.iter().enumerate().take(MAX_EQUIP)
+        .filter(|(_, item)| item.id != 0)
+        .collect();
+    if rows.is_empty() { return Ok(()); }
+    let mut qb = QueryBuilder::<MySql>::new(
+        "INSERT INTO `Equipment` \
+         (`EqpChaId`,`EqpItmId`,`EqpDurability`,`EqpChaIdOwner`,`EqpCustom`,`EqpTimer`,\
+          `EqpEngrave`,`EqpCustomLook`,`EqpCustomLookColor`,`EqpCustomIcon`,\
+          `EqpCustomIconColor`,`EqpProtected`,`EqpNote`,`EqpSlot`) "
+    );
+    qb.push_values(rows, |mut b, (i, item)| {
+        b.push_bind(char_id).push_bind(item.id).push_bind(item.dura).push_bind(item.owner)
+         .push_bind(item.custom).push_bind(item.time)
+         .push_bind(i8_slice_to_str(&item.real_name))
+         .push_bind(item.custom_look).push_bind(item.custom_look_color)
+         .push_bind(item.custom_icon).push_bind(item.custom_icon_color)
+         .push_bind(item.protected).push_bind(i8_slice_to_str(&item.note)).push_bind(i as u32);
+    });
+    qb.build().execute(&mut **tx).await?;
     Ok(())
 }
 
@@ -824,6 +1033,38 @@ async fn save_banks(tx: &mut Transaction<'_, MySql>, char_id: u32, banks: &[crat
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_load_timings_percentiles() {
+        let t = LoadTimings::default();
+        for ms in 1..LOAD_TIMING_WINDOW as u64 {
+            assert!(t.record(std::time::Duration::from_millis(ms)).is_none());
+        }
+        let (p50, p99) = t.record(std::time::Duration::from_millis(LOAD_TIMING_WINDOW as u64)).unwrap();
+        assert_eq!(p50, std::time::Duration::from_millis(51));
+        assert_eq!(p99, std::time::Duration::from_millis(100));
+        assert!(t.record(std::time::Duration::from_millis(1)).is_none());
+    }
+
+    #[test]
+    fn test_validate_password() {
+        assert_eq!(validate_password("Alice", "r4inb0w", 4), Ok(()));
+        assert_eq!(validate_password("Alice", "x", 4), Err(PassPolicyError::TooShort(4)));
+        assert_eq!(validate_password("Alice", "abc", 4), Err(PassPolicyError::TooShort(4)));
+        assert_eq!(validate_password("Alice", "r4inb0w", 8), Err(PassPolicyError::TooShort(8)));
+        assert_eq!(validate_password("Alice", "alice", 4), Err(PassPolicyError::SameAsName));
+        assert_eq!(validate_password("Alice", "Password", 4), Err(PassPolicyError::TooCommon));
+    }
+
+    #[test]
+    fn test_error_legacy_codes() {
+        assert_eq!(i32::from(CharCreateError::NameTaken), 1);
+        assert_eq!(i32::from(CharCreateError::Db), 2);
+        assert_eq!(i32::from(CharCreateError::from(PassPolicyError::TooCommon)), 3);
+        assert_eq!(i32::from(PassChangeError::NoUser), -2);
+        assert_eq!(i32::from(PassChangeError::WrongPass), -3);
+        assert_eq!(i32::from(PassChangeError::Db), -1);
+    }
+
     #[test]
     fn test_is_legacy_hash_md5() {
         assert!(is_legacy_hash("5f4dcc3b5aa765d61d8327deb882cf99")); // MD5("password")
@@ -892,4 +1133,37 @@ mod tests {
         let expire = (chrono::Utc::now().timestamp() + 3600) as u32;
         assert!(ismastpass("adminpass", &hash, expire).await);
     }
+
+    /// Needs a MariaDB with the yuri schema, e.g.
+    /// `YURI_TEST_DATABASE_URL=mysql://... cargo test -- --ignored`.
+    #[tokio::test]
+    #[ignore = "needs a MariaDB; set YURI_TEST_DATABASE_URL"]
+    async fn test_inventory_round_trip() {
+        let url = std::env::var("YURI_TEST_DATABASE_URL").expect("YURI_TEST_DATABASE_URL");
+        let pool = MySqlPool::connect(&url).await.unwrap();
+        let name = format!("inv{}", chrono::Utc::now().timestamp_micros() % 1_000_000_000);
+        assert_eq!(create_char(&pool, &name, "secret", 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 4).await, Ok(()));
+        let id = char_login_lookup(&pool, &name).await.unwrap().unwrap().char_id;
+
+        let blob = load_char_bytes(&pool, id, &name).await.unwrap();
+        let mut s = char_status_from_bytes(&blob).unwrap();
+        for i in 0..20 {
+            s.inventory[i].id = 1000 + i as u32;
+            s.inventory[i].amount = i as i32 + 1;
+            copy_str_to_i8(&mut s.inventory[i].note, &format!("note {i}"));
+        }
+        save_char_status(&pool, &s).await.unwrap();
+
+        let blob = load_char_bytes(&pool, id, &name).await.unwrap();
+        let loaded = char_status_from_bytes(&blob).unwrap();
+        for i in 0..20 {
+            assert_eq!(loaded.inventory[i].id, 1000 + i as u32);
+            assert_eq!(loaded.inventory[i].amount, i as i32 + 1);
+            assert_eq!(i8_slice_to_str(&loaded.inventory[i].note), format!("note {i}"));
+        }
+        assert_eq!(loaded.inventory[20].id, 0);
+
+        sqlx::query("DELETE FROM `Inventory` WHERE `InvChaId` = ?").bind(id).execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM `Character` WHERE `ChaId` = ?").bind(id).execute(&pool).await.unwrap();
+    }
 }
@@ -1,15 +1,34 @@
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::sync::mpsc;
 use tokio::time::{Duration, interval};
 use super::{CharState, LoginEntry};
 use super::db;
 use crate::network::crypt::tk_crypt_static;
+use crate::network::integrity::{MacKey, Verifier};
+use crate::network::{same_subnet, Stream};
+use crate::network::protocol::{ensure_len, ProtocolError};
+use crate::session::ReconnectPolicy;
 
 // Packet length table for 0x1000–0x1006 (0 = end/unused)
 const PKT_LENS: &[usize] = &[3, 20, 43, 40, 52, 0, 0];
 
+/// Variable-length reason text that follows a rejecting 0x1000.
+const CMD_REJECT_TEXT: u16 = 0x1005;
+const REJECT_TEXT_MAX: usize = 255;
+
+/// Meaning of a non-zero 0x1000 status (login `INTIF_REJECT_*`).
+fn reject_reason(code: u8) -> &'static str {
+    match code {
+        0x01 => "login_id/login_pw do not match the login server's",
+        0x02 => "another char server is already linked",
+        0x03 => "auth packet was malformed",
+        _ => "unknown reason",
+    }
+}
+
+/// Keep the login server link up, redialling on the `reconnect_*` schedule;
+/// gives up once `reconnect_max_attempts` dials in a row have failed.
 pub async fn connect_to_login(state: Arc<CharState>) {
     let mut ticker = interval(Duration::from_secs(10));
     loop {
@@ -23,21 +42,21 @@ pub async fn connect_to_login(state: Arc<CharState>) {
             }
         }
 
-        let addr = format!("{}:{}", state.config.login_ip, state.config.login_port);
+        let config = state.config.get();
+        let addr = format!("{}:{}", config.login_ip, config.login_port);
         tracing::info!("[char] [logif] Connecting to login server at {}", addr);
 
-        match TcpStream::connect(&addr).await {
+        match ReconnectPolicy::from_config(&config).connect(&addr, "[char] [logif]").await {
             Ok(stream) => {
-                run_login_connection(Arc::clone(&state), stream).await;
-            }
-            Err(e) => {
-                tracing::warn!("[char] [logif] Connect failed: {}", e);
+                run_login_connection(Arc::clone(&state), stream, &addr).await;
             }
+            Err(_) => return,
         }
     }
 }
 
-async fn run_login_connection(state: Arc<CharState>, mut stream: TcpStream) {
+/// Drive the login-server link over `stream` until it drops.
+pub async fn run_login_connection(state: Arc<CharState>, mut stream: impl Stream, peer: &str) {
     // Send auth handshake: 0xAA + BE_len(66) + 0xFF + RAND_INC(0) + login_id(32) + login_pw(32)
     // The Rust login server reads exactly 69 bytes (3-byte 0xAA header + 66 payload).
     // The C client sends 72 (69 + 3 set_packet_indexes trailer), but the Rust login server
@@ -47,28 +66,33 @@ async fn run_login_connection(state: Arc<CharState>, mut stream: TcpStream) {
     pkt[1] = 0x00; pkt[2] = 0x42; // 66 in big-endian
     pkt[3] = 0xFF;
     pkt[4] = 0x00; // RAND_INC placeholder
-    let lid = state.config.login_id.as_bytes();
-    let lpw = state.config.login_pw.as_bytes();
+    let config = state.config.get();
+    let lid = config.login_id.as_bytes();
+    let lpw = config.login_pw.as_bytes();
     let lid_len = lid.len().min(32);
     let lpw_len = lpw.len().min(32);
     pkt[5..5 + lid_len].copy_from_slice(&lid[..lid_len]);
     pkt[37..37 + lpw_len].copy_from_slice(&lpw[..lpw_len]);
-    let xk = state.config.xor_key.as_bytes();
+    let xk = config.xor_key.as_bytes();
     tk_crypt_static(&mut pkt, xk);
+    let mac = MacKey::from_config(&config);
+    let mut sealer = mac.sealer();
+    let mut verifier = mac.verifier();
+    sealer.seal(&mut pkt);
     if stream.write_all(&pkt).await.is_err() {
         return;
     }
-
     let (tx, mut rx) = mpsc::channel::<Vec<u8>>(64);
     {
         let mut lt = state.login_tx.lock().await;
         *lt = Some(tx);
     }
 
-    let (mut rh, mut wh) = stream.into_split();
+    let (mut rh, mut wh) = tokio::io::split(stream);
 
     let writer = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
+        while let Some(mut msg) = rx.recv().await {
+            sealer.seal(&mut msg);
             if wh.write_all(&msg).await.is_err() {
                 break;
             }
@@ -76,45 +100,18 @@ async fn run_login_connection(state: Arc<CharState>, mut stream: TcpStream) {
     });
 
     loop {
-        let mut cmd_bytes = [0u8; 2];
-        if rh.read_exact(&mut cmd_bytes).await.is_err() {
-            break;
-        }
-
-        // 0xAA-framed packets (banner, keep-alive responses): skip them.
-        // We already read [0xAA, hi_byte]. Read 1 more byte for lo_byte of the BE length,
-        // then skip `payload_len` bytes.
-        if cmd_bytes[0] == 0xAA {
-            let mut lo = [0u8; 1];
-            if rh.read_exact(&mut lo).await.is_err() {
-                break;
-            }
-            let skip = u16::from_be_bytes([cmd_bytes[1], lo[0]]) as usize;
-            let mut discard = vec![0u8; skip];
-            if rh.read_exact(&mut discard).await.is_err() {
+        let pkt = match read_login_frame(&mut rh, &mut verifier).await {
+            Ok(Some(pkt)) => pkt,
+            Ok(None) => continue,
+            Err(e) => {
+                e.log(&format!("[char] [logif] peer={}", peer));
                 break;
             }
-            continue;
-        }
-
-        let cmd = u16::from_le_bytes(cmd_bytes);
-        let idx = (cmd as usize).wrapping_sub(0x1000);
-        if idx >= PKT_LENS.len() || PKT_LENS[idx] == 0 {
-            tracing::warn!("[char] [logif] unknown cmd={:04X}, dropping connection", cmd);
-            break;
-        }
-
-        let rest_len = PKT_LENS[idx].saturating_sub(2);
-        let mut rest = vec![0u8; rest_len];
-        if rh.read_exact(&mut rest).await.is_err() {
-            break;
+        };
+        let cmd = u16::from_le_bytes([pkt[0], pkt[1]]);
+        if let Err(e) = dispatch_login_packet(&state, cmd, &pkt).await {
+            e.log("[char] [logif]");
         }
-
-        let mut pkt = Vec::with_capacity(PKT_LENS[idx]);
-        pkt.extend_from_slice(&cmd_bytes);
-        pkt.extend_from_slice(&rest);
-
-        dispatch_login_packet(&state, cmd, &pkt).await;
     }
 
     {
@@ -125,43 +122,101 @@ async fn run_login_connection(state: Arc<CharState>, mut stream: TcpStream) {
     tracing::warn!("[char] [logif] Login server connection lost");
 }
 
-async fn dispatch_login_packet(state: &Arc<CharState>, cmd: u16, pkt: &[u8]) {
+/// Read one inter-server frame from the login server and verify its MAC.
+///
+/// Returns `Ok(None)` for 0xAA client-protocol frames (banner, keep-alive
+/// responses), which are skipped and never carry a MAC.
+async fn read_login_frame<R: AsyncRead + Unpin>(
+    rh: &mut R,
+    verifier: &mut Verifier,
+) -> Result<Option<Vec<u8>>, ProtocolError> {
+    let mut cmd_bytes = [0u8; 2];
+    rh.read_exact(&mut cmd_bytes).await?;
+
+    // We already read [0xAA, hi_byte]. Read 1 more byte for lo_byte of the BE length,
+    // then skip `payload_len` bytes.
+    if cmd_bytes[0] == 0xAA {
+        let mut lo = [0u8; 1];
+        rh.read_exact(&mut lo).await?;
+        let skip = u16::from_be_bytes([cmd_bytes[1], lo[0]]) as usize;
+        let mut discard = vec![0u8; skip];
+        rh.read_exact(&mut discard).await?;
+        return Ok(None);
+    }
+
+    let cmd = u16::from_le_bytes(cmd_bytes);
+    let pkt = if cmd == CMD_REJECT_TEXT {
+        // Variable length: [cmd:2][len:2][text]
+        let mut len_bytes = [0u8; 2];
+        rh.read_exact(&mut len_bytes).await?;
+        let len = u16::from_le_bytes(len_bytes) as usize;
+        if len > REJECT_TEXT_MAX {
+            return Err(ProtocolError::BadLength { cmd, len });
+        }
+        let mut pkt = vec![0u8; 4 + len];
+        pkt[..2].copy_from_slice(&cmd_bytes);
+        pkt[2..4].copy_from_slice(&len_bytes);
+        rh.read_exact(&mut pkt[4..]).await?;
+        pkt
+    } else {
+        let idx = (cmd as usize).wrapping_sub(0x1000);
+        if idx >= PKT_LENS.len() || PKT_LENS[idx] == 0 {
+            return Err(ProtocolError::UnknownCommand(cmd));
+        }
+        let mut pkt = vec![0u8; PKT_LENS[idx]];
+        pkt[..2].copy_from_slice(&cmd_bytes);
+        rh.read_exact(&mut pkt[2..]).await?;
+        pkt
+    };
+
+    if !verifier.check(rh, &pkt).await {
+        return Err(verifier.failure(cmd));
+    }
+    Ok(Some(pkt))
+}
+
+async fn dispatch_login_packet(state: &Arc<CharState>, cmd: u16, pkt: &[u8]) -> Result<(), ProtocolError> {
     match cmd {
         0x1000 => {
             if pkt.len() >= 3 && pkt[2] != 0 {
-                tracing::warn!("[char] [logif] Login server rejected connection result={}", pkt[2]);
+                tracing::warn!("[char] [logif] Login server rejected connection: {} (result={})",
+                    reject_reason(pkt[2]), pkt[2]);
             } else {
                 tracing::info!("[char] [logif] Connected to Login Server");
             }
         }
-        0x1001 => handle_usedname(state, pkt).await,
-        0x1002 => handle_newchar(state, pkt).await,
-        0x1003 => handle_login(state, pkt).await,
-        0x1004 => handle_setpass(state, pkt).await,
-        _ => tracing::warn!("[char] [logif] unhandled cmd={:04X}", cmd),
+        0x1001 => return handle_usedname(state, pkt).await,
+        0x1002 => return handle_newchar(state, pkt).await,
+        0x1003 => return handle_login(state, pkt).await,
+        0x1004 => return handle_setpass(state, pkt).await,
+        CMD_REJECT_TEXT => {
+            let text = String::from_utf8_lossy(&pkt[4..]);
+            tracing::warn!("[char] [logif] Login server says: {}", text);
+        }
+        _ => return Err(ProtocolError::UnknownCommand(cmd)),
     }
+    Ok(())
 }
 
-async fn handle_usedname(state: &Arc<CharState>, pkt: &[u8]) {
-    if pkt.len() < 20 {
-        return;
-    }
+async fn handle_usedname(state: &Arc<CharState>, pkt: &[u8]) -> Result<(), ProtocolError> {
+    ensure_len(0x1001, pkt, 20)?;
     let name = std::str::from_utf8(&pkt[4..20]).unwrap_or("").trim_end_matches('\0');
-    let used = db::is_name_used(&state.db, name).await.unwrap_or(true);
+    let used = db::is_name_used(&state.db, name).await;
     let mut resp = [0u8; 5];
     resp[0] = 0x01; resp[1] = 0x20; // cmd 0x2001 LE
     resp[2] = pkt[2]; resp[3] = pkt[3]; // session_id passthrough
-    resp[4] = if used { 1 } else { 0 };
+    // A lookup failure reports the name as taken.
+    resp[4] = if matches!(used, Ok(false)) { 0 } else { 1 };
     send_to_login(state, resp.to_vec()).await;
+    used?;
+    Ok(())
 }
 
-async fn handle_newchar(state: &Arc<CharState>, pkt: &[u8]) {
-    if pkt.len() < 43 {
-        return;
-    }
+async fn handle_newchar(state: &Arc<CharState>, pkt: &[u8]) -> Result<(), ProtocolError> {
+    ensure_len(0x1002, pkt, 43)?;
     let name = std::str::from_utf8(&pkt[4..20]).unwrap_or("").trim_end_matches('\0');
     let pass = std::str::from_utf8(&pkt[20..36]).unwrap_or("").trim_end_matches('\0');
-    let cfg = &state.config;
+    let cfg = state.config.get();
     let res = db::create_char(
         &state.db, name, pass,
         pkt[39],          // totem
@@ -174,19 +229,21 @@ async fn handle_newchar(state: &Arc<CharState>, pkt: &[u8]) {
         cfg.start_point.m as u32,
         cfg.start_point.x as u32,
         cfg.start_point.y as u32,
+        cfg.min_password_len,
     ).await;
+    if let Err(e) = &res {
+        tracing::info!("[char] [newchar] refused name={}: {}", name, e);
+    }
     let mut resp = [0u8; 5];
     resp[0] = 0x02; resp[1] = 0x20; // cmd 0x2002 LE
     resp[2] = pkt[2]; resp[3] = pkt[3];
-    resp[4] = res as u8;
+    resp[4] = res.map_or_else(i32::from, |()| 0) as u8;
     send_to_login(state, resp.to_vec()).await;
+    Ok(())
 }
 
-async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
-    if pkt.len() < 40 {
-        tracing::warn!("[char] [login] pkt too short: {}", pkt.len());
-        return;
-    }
+async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) -> Result<(), ProtocolError> {
+    ensure_len(0x1003, pkt, 40)?;
     let name = std::str::from_utf8(&pkt[4..20]).unwrap_or("").trim_end_matches('\0');
     let pass = std::str::from_utf8(&pkt[20..36]).unwrap_or("").trim_end_matches('\0');
     tracing::debug!("[char] [login] attempt name={}", name);
@@ -199,8 +256,8 @@ async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
     tracing::info!("[char] [login] checking password");
     let stored_hash = match db::get_char_password(&state.db, name).await {
         Ok(Some(h)) => h,
-        Ok(None) => { tracing::warn!("[char] [login] no user"); resp[4] = 0x02; send_to_login(state, resp).await; return; }
-        Err(e)    => { tracing::warn!("[char] [login] db err: {}", e); resp[4] = 0x01; send_to_login(state, resp).await; return; }
+        Ok(None) => { tracing::warn!("[char] [login] no user"); resp[4] = 0x02; send_to_login(state, resp).await; return Ok(()); }
+        Err(e)    => { resp[4] = 0x01; send_to_login(state, resp).await; return Err(e.into()); }
     };
 
     let (mast_ok, mast_hash) = match db::get_master_password(&state.db).await {
@@ -212,7 +269,7 @@ async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
         tracing::warn!("[char] [login] wrong password");
         resp[4] = 0x03;
         send_to_login(state, resp).await;
-        return;
+        return Ok(());
     }
     tracing::info!("[char] [login] password ok");
     // Silently upgrade legacy MD5 password to bcrypt — runs in background, does not block login.
@@ -223,53 +280,29 @@ async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
         let pass = pass.to_owned();
         let name = name.to_owned();
         tokio::spawn(async move {
-            match db::hash_password(&pass).await {
-                Ok(new_hash) => {
-                    match sqlx::query(
-                        "UPDATE `Character` SET `ChaPassword` = ? WHERE `ChaName` = ?"
-                    )
-                    .bind(&new_hash).bind(&name)
-                    .execute(&pool).await
-                    {
-                        Ok(_) => tracing::info!("[char] [login] rehashed password for {} to bcrypt", name),
-                        Err(e) => tracing::error!("[char] [login] failed to persist rehashed password for {}: {}", name, e),
-                    }
-                }
+            match db::upgrade_char_password(&pool, &name, &pass).await {
+                Ok(()) => tracing::info!("[char] [login] rehashed password for {} to bcrypt", name),
                 Err(e) => tracing::error!("[char] [login] failed to rehash password for {}: {}", name, e),
             }
         });
     }
 
     // Silently upgrade legacy MD5 admin password to bcrypt — runs in background, does not block login
-    if mast_ok {
-        if let Some(mhash) = mast_hash {
-            if db::is_legacy_hash(&mhash) {
-                let pool = state.db.clone();
-                let pass = pass.to_owned();
-                tokio::spawn(async move {
-                    match db::hash_password(&pass).await {
-                        Ok(new_hash) => {
-                            match sqlx::query(
-                                "UPDATE `AdminPassword` SET `AdmPassword` = ? WHERE `AdmId` = 1"
-                            )
-                            .bind(&new_hash)
-                            .execute(&pool).await
-                            {
-                                Ok(_) => tracing::info!("[char] [login] rehashed admin password to bcrypt"),
-                                Err(e) => tracing::error!("[char] [login] failed to persist rehashed admin password: {}", e),
-                            }
-                        }
-                        Err(e) => tracing::error!("[char] [login] failed to rehash admin password: {}", e),
-                    }
-                });
+    if mast_ok && mast_hash.as_deref().is_some_and(db::is_legacy_hash) {
+        let pool = state.db.clone();
+        let pass = pass.to_owned();
+        tokio::spawn(async move {
+            match db::upgrade_master_password(&pool, &pass).await {
+                Ok(()) => tracing::info!("[char] [login] rehashed admin password to bcrypt"),
+                Err(e) => tracing::error!("[char] [login] failed to rehash admin password: {}", e),
             }
-        }
+        });
     }
 
     let char_info = match db::char_login_lookup(&state.db, name).await {
         Ok(Some(c)) => c,
-        Ok(None) => { tracing::warn!("[char] [login] char not found"); resp[4] = 0x02; send_to_login(state, resp).await; return; }
-        Err(e)    => { tracing::warn!("[char] [login] lookup err: {}", e); resp[4] = 0x01; send_to_login(state, resp).await; return; }
+        Ok(None) => { tracing::warn!("[char] [login] char not found"); resp[4] = 0x02; send_to_login(state, resp).await; return Ok(()); }
+        Err(e)    => { resp[4] = 0x01; send_to_login(state, resp).await; return Err(e.into()); }
     };
     tracing::info!("[char] [login] char_id={} map_id={}", char_info.char_id, char_info.map_id);
 
@@ -277,7 +310,25 @@ async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
     if char_info.banned || db::is_account_banned(&state.db, char_info.char_id).await {
         resp[4] = 0x04;
         send_to_login(state, resp).await;
-        return;
+        return Ok(());
+    }
+
+    // Compare the client's subnet against the last recorded login.
+    let client_ip = u32::from_be_bytes([pkt[36], pkt[37], pkt[38], pkt[39]]);
+    if client_ip != 0 {
+        if let Ok(Some(last)) = db::get_last_login(&state.db, char_info.char_id).await {
+            if last.is_recorded() && !same_subnet(last.ip, client_ip) {
+                tracing::warn!(
+                    "[char] [login] subnet change name={} last_ip={} last_time={} new_ip={}",
+                    name, last.ip_addr(), last.time, std::net::Ipv4Addr::from(client_ip)
+                );
+                if state.config.get().login_subnet_lock {
+                    resp[4] = 0x07;
+                    send_to_login(state, resp).await;
+                    return Ok(());
+                }
+            }
+        }
     }
 
     // Find map server that hosts this character's map
@@ -290,27 +341,62 @@ async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
 
     let map_idx = match map_idx {
         Some(i) => i,
-        None => { resp[4] = 0x05; send_to_login(state, resp).await; return; }
+        None => { resp[4] = 0x05; send_to_login(state, resp).await; return Ok(()); }
     };
 
-    // Check if already online — lock online, record result, then drop before locking map_servers.
-    let already_online = {
-        let online = state.online.lock().await;
-        online.contains_key(&char_info.char_id)
+    release_account_holds(state, char_info.char_id).await;
+
+    // Check if already online. map_servers is read first and dropped before
+    // online is locked. A player parked for reconnect-resume on a map server
+    // is let back in from the same subnet and routed to that map server,
+    // which re-attaches the live character.
+    let connected: Vec<bool> = state.map_servers.lock().await.iter().map(Option::is_some).collect();
+    let policy = state.config.get().double_login;
+    let (existing, holder) = {
+        let mut online = state.online.lock().await;
+        match online.get_mut(&char_info.char_id) {
+            None => (None, map_idx),
+            Some(e) => {
+                let map_up = connected.get(e.map_server_idx).copied().unwrap_or(false);
+                let action = existing_session(e, client_ip, map_up, policy, std::time::Instant::now());
+                let holder = e.map_server_idx;
+                match action {
+                    Existing::Resume => {
+                        if let Some(h) = e.resume.take() {
+                            tracing::info!("[char] [login] resume name={} token={:08X}", name, h.token);
+                        }
+                    }
+                    Existing::Kick => { e.resume = None; }
+                    Existing::Reject => {}
+                    Existing::Reclaim => { online.remove(&char_info.char_id); }
+                }
+                (Some(action), holder)
+            }
+        }
     };
-    if already_online {
-        resp[4] = 0x06;
-        send_to_login(state, resp).await;
-        // Force-kick on map server (0x3804)
-        let servers = state.map_servers.lock().await;
-        if let Some(Some(s)) = servers.get(map_idx) {
-            let mut kick = vec![0u8; 6];
-            kick[0] = 0x04; kick[1] = 0x38; // 0x3804 LE
-            kick[2..6].copy_from_slice(&char_info.char_id.to_le_bytes());
-            let _ = s.tx.send(kick).await;
+    // A map server still linked but stuck holds a copy of the reclaimed
+    // entry; it is kicked once the new entry is in place.
+    let mut reclaimed_on = None;
+    let map_idx = match existing {
+        None => map_idx,
+        Some(Existing::Resume) => holder,
+        Some(Existing::Reclaim) => {
+            tracing::warn!("[char] [login] reclaimed stale online entry name={} map=#{}", name, holder);
+            if connected.get(holder).copied().unwrap_or(false) {
+                reclaimed_on = Some(holder);
+            }
+            map_idx
         }
-        return;
-    }
+        Some(action) => {
+            tracing::info!("[char] [login] already online name={} map=#{} policy={:?}", name, holder, policy);
+            resp[4] = 0x06;
+            send_to_login(state, resp).await;
+            if action == Existing::Kick {
+                kick_on_map(state, holder, char_info.char_id).await;
+            }
+            return Ok(());
+        }
+    };
 
     // Route player: send 0x3802 to map server
     let char_id_le = char_info.char_id.to_le_bytes();
@@ -325,7 +411,7 @@ async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
         map_msg[34..38].copy_from_slice(&pkt[36..40]);
     }
 
-    {
+    let routed = {
         let servers = state.map_servers.lock().await;
         if let Some(Some(s)) = servers.get(map_idx) {
             let _ = s.tx.send(map_msg).await;
@@ -335,11 +421,18 @@ async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
             resp[5..5 + nlen].copy_from_slice(&name.as_bytes()[..nlen]);
             resp[21..25].copy_from_slice(&s.ip.to_le_bytes());
             resp[25..27].copy_from_slice(&s.port.to_le_bytes());
+            true
         } else {
-            resp[4] = 0x05;
-            send_to_login(state, resp).await;
-            return;
+            false
+        }
+    };
+    if !routed {
+        resp[4] = 0x05;
+        send_to_login(state, resp).await;
+        if let Some(holder) = reclaimed_on {
+            kick_on_map(state, holder, char_info.char_id).await;
         }
+        return Ok(());
     }
 
     send_to_login(state, resp).await;
@@ -350,15 +443,75 @@ async fn handle_login(state: &Arc<CharState>, pkt: &[u8]) {
         online.insert(char_info.char_id, LoginEntry {
             map_server_idx: map_idx,
             char_name: name.to_string(),
+            resume: None,
+            map: None,
+            map_seq: 0,
+            reclaimed_on,
         });
     }
-    db::set_online(&state.db, char_info.char_id, true).await;
+    db::mark_online(&state.db, char_info.char_id, state.config.get().server_id).await;
+    // Kicked only now, so the old copy's logout finds the new entry and its
+    // `reclaimed_on` marker instead of releasing this session.
+    if let Some(holder) = reclaimed_on {
+        kick_on_map(state, holder, char_info.char_id).await;
+    }
+
+    if client_ip != 0 {
+        let pool = state.db.clone();
+        let char_id = char_info.char_id;
+        tokio::spawn(async move {
+            db::record_last_login(&pool, char_id, client_ip).await;
+        });
+    }
+    Ok(())
 }
 
-async fn handle_setpass(state: &Arc<CharState>, pkt: &[u8]) {
-    if pkt.len() < 52 {
-        return;
+/// What `handle_login` does with a character's existing online entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Existing {
+    /// Parked after a drop and this login is from the same subnet: route it back.
+    Resume,
+    /// The entry outlived its map server or its resume hold: drop it, log in.
+    Reclaim,
+    /// Refuse the login and leave the session alone.
+    Reject,
+    /// Refuse the login and kick the session.
+    Kick,
+}
+
+/// How long past a resume hold's end the map server has to log the parked
+/// character out; an entry still here after that is stale.
+const STALE_HOLD_AFTER: Duration = Duration::from_secs(60);
+
+/// Decide what a login from `client_ip` does with `entry`, the character's
+/// existing session; `map_connected` is whether its map server is linked.
+fn existing_session(
+    entry: &LoginEntry,
+    client_ip: u32,
+    map_connected: bool,
+    policy: super::DoubleLoginPolicy,
+    now: std::time::Instant,
+) -> Existing {
+    if !map_connected {
+        return Existing::Reclaim;
     }
+    if let Some(h) = &entry.resume {
+        if h.expires > now {
+            if client_ip != 0 && same_subnet(h.ip, client_ip) {
+                return Existing::Resume;
+            }
+        } else if now.duration_since(h.expires) >= STALE_HOLD_AFTER {
+            return Existing::Reclaim;
+        }
+    }
+    match policy {
+        super::DoubleLoginPolicy::Reject => Existing::Reject,
+        super::DoubleLoginPolicy::Kick => Existing::Kick,
+    }
+}
+
+async fn handle_setpass(state: &Arc<CharState>, pkt: &[u8]) -> Result<(), ProtocolError> {
+    ensure_len(0x1004, pkt, 52)?;
     let name    = std::str::from_utf8(&pkt[4..20]).unwrap_or("").trim_end_matches('\0');
     let pass    = std::str::from_utf8(&pkt[20..36]).unwrap_or("").trim_end_matches('\0');
     let newpass = std::str::from_utf8(&pkt[36..52]).unwrap_or("").trim_end_matches('\0');
@@ -366,8 +519,9 @@ async fn handle_setpass(state: &Arc<CharState>, pkt: &[u8]) {
     let mut resp = [0u8; 5];
     resp[0] = 0x04; resp[1] = 0x20; // cmd 0x2004 LE
     resp[2] = pkt[2]; resp[3] = pkt[3];
-    resp[4] = res.unsigned_abs() as u8;
+    resp[4] = res.map_or_else(i32::from, |()| 0).unsigned_abs() as u8;
     send_to_login(state, resp.to_vec()).await;
+    Ok(())
 }
 
 async fn send_to_login(state: &Arc<CharState>, msg: Vec<u8>) {
@@ -377,8 +531,67 @@ async fn send_to_login(state: &Arc<CharState>, msg: Vec<u8>) {
     }
 }
 
+/// Force `char_id` off map server `map_idx` (0x3804). A parked character is
+/// expired there and logged out on its next resume sweep.
+async fn kick_on_map(state: &Arc<CharState>, map_idx: usize, char_id: u32) {
+    let servers = state.map_servers.lock().await;
+    if let Some(Some(s)) = servers.get(map_idx) {
+        let mut kick = vec![0u8; 6];
+        kick[0] = 0x04; kick[1] = 0x38; // 0x3804 LE
+        kick[2..6].copy_from_slice(&char_id.to_le_bytes());
+        let _ = s.tx.send(kick).await;
+    }
+}
+
+/// A fresh login drops the resume hold on every other character of the same
+/// account: the player has moved on, so a parked sibling is logged out now
+/// instead of lingering in the world until its window ends.
+async fn release_account_holds(state: &Arc<CharState>, char_id: u32) {
+    let siblings = db::account_char_ids(&state.db, char_id).await;
+    let released: Vec<(u32, usize)> = {
+        let mut online = state.online.lock().await;
+        siblings.into_iter()
+            .filter(|&id| id != char_id)
+            .filter_map(|id| {
+                let e = online.get_mut(&id)?;
+                e.resume.take()?;
+                Some((id, e.map_server_idx))
+            })
+            .collect()
+    };
+    for (id, map_idx) in released {
+        tracing::info!("[char] [login] account of char_id={} logged in again, releasing hold on char_id={}", char_id, id);
+        kick_on_map(state, map_idx, id).await;
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::servers::char::{DoubleLoginPolicy, ResumeHold};
+    use std::time::Instant;
+
+    #[test]
+    fn test_existing_session_policy() {
+        let now = Instant::now();
+        let ip = u32::from(std::net::Ipv4Addr::new(10, 0, 5, 1));
+        let mut entry = LoginEntry {
+            map_server_idx: 0, char_name: "a".into(), resume: None, map: None, map_seq: 0, reclaimed_on: None,
+        };
+        assert_eq!(existing_session(&entry, ip, true, DoubleLoginPolicy::Kick, now), Existing::Kick);
+        assert_eq!(existing_session(&entry, ip, true, DoubleLoginPolicy::Reject, now), Existing::Reject);
+        assert_eq!(existing_session(&entry, ip, false, DoubleLoginPolicy::Reject, now), Existing::Reclaim);
+
+        entry.resume = Some(ResumeHold { token: 1, ip: ip + 1, expires: now + Duration::from_secs(30) });
+        assert_eq!(existing_session(&entry, ip, true, DoubleLoginPolicy::Reject, now), Existing::Resume);
+        assert_eq!(existing_session(&entry, 0x0B00_0001, true, DoubleLoginPolicy::Reject, now), Existing::Reject);
+
+        // A hold that ended without a logout is only reclaimed after the grace.
+        let ended = now + Duration::from_secs(30);
+        assert_eq!(existing_session(&entry, ip, true, DoubleLoginPolicy::Reject, ended), Existing::Reject);
+        assert_eq!(existing_session(&entry, ip, true, DoubleLoginPolicy::Reject, ended + STALE_HOLD_AFTER), Existing::Reclaim);
+    }
+
     #[test]
     fn test_logif_packet_lens() {
         assert_eq!(super::PKT_LENS[0x1000 - 0x1000], 3);
@@ -1,13 +1,16 @@
-use std::io::{Read, Write};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use flate2::Compression;
-use flate2::read::ZlibDecoder;
-use flate2::write::ZlibEncoder;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
 use tokio::sync::mpsc;
 use super::{CharState, MapFifo};
+use super::codec;
 use super::db;
+use super::charstatus::char_status_from_bytes;
+use super::packet::SaveNowResult;
+use crate::network::integrity::MacKey;
+use crate::network::Stream;
+use crate::servers::map::presence;
+use crate::session::DisconnectReason;
 
 const MAX_PKT_LEN: usize = 16 * 1024 * 1024; // 16 MiB hard cap for variable-length packets
 
@@ -32,14 +35,15 @@ const PKT_LENS: &[i32] = &[
     20,   // 0x300E findnewmp
     4124, // 0x300F nmail write copy
     30,   // 0x3010
-    255,  // 0x3011
-    255,  // 0x3012
-    255,  // 0x3013
-    255,  // 0x3014
+    -1,   // 0x3011 save now (variable)
+    7,    // 0x3012 disconnect reason
+    16,   // 0x3013 resume hold
+    12,   // 0x3014 map change
     255,  // 0x3015
+    2,    // 0x3016 shutdown notice
 ];
 
-pub async fn handle_map_server(state: Arc<CharState>, mut stream: TcpStream, first_cmd_bytes: [u8; 2]) {
+pub async fn handle_map_server(state: Arc<CharState>, mut stream: impl Stream, peer: SocketAddr, first_cmd_bytes: [u8; 2]) {
     // Read rest of 0x3000 auth packet (72 total, 2 already read)
     let mut rest = vec![0u8; 70];
     if stream.read_exact(&mut rest).await.is_err() {
@@ -50,14 +54,28 @@ pub async fn handle_map_server(state: Arc<CharState>, mut stream: TcpStream, fir
     pkt.extend_from_slice(&first_cmd_bytes);
     pkt.extend_from_slice(&rest);
 
+    let mac = MacKey::from_config(&state.config.get());
+    let mut sealer = mac.sealer();
+    let mut verifier = mac.verifier();
+    if !verifier.check(&mut stream, &pkt).await {
+        tracing::error!("[char] [mapif] auth packet from {} failed {} check", peer, verifier.what());
+        crate::metrics::METRICS.link(false);
+        return;
+    }
+
     // Auth: char_id at offset 2 (32 bytes), char_pw at offset 34 (32 bytes)
     let got_id = std::str::from_utf8(&pkt[2..34]).unwrap_or("").trim_end_matches('\0');
     let got_pw = std::str::from_utf8(&pkt[34..66]).unwrap_or("").trim_end_matches('\0');
 
-    if got_id != state.config.char_id || got_pw != state.config.char_pw {
-        let _ = stream.write_all(&[0x00, 0x38, 0x01, 0x00]).await;
+    let config = state.config.get();
+    if got_id != config.char_id || got_pw != config.char_pw {
+        let mut reject = vec![0x00, 0x38, 0x01, 0x00];
+        sealer.seal(&mut reject);
+        let _ = stream.write_all(&reject).await;
+        crate::metrics::METRICS.link(false);
         return;
     }
+    crate::metrics::METRICS.link(true);
 
     let ip = u32::from_le_bytes([pkt[66], pkt[67], pkt[68], pkt[69]]);
     let port = u16::from_le_bytes([pkt[70], pkt[71]]);
@@ -74,13 +92,16 @@ pub async fn handle_map_server(state: Arc<CharState>, mut stream: TcpStream, fir
     };
 
     // Auth success: send 0x3800 result=0x00, server_idx
-    let _ = stream.write_all(&[0x00, 0x38, 0x00, idx as u8]).await;
+    let mut accept = vec![0x00, 0x38, 0x00, idx as u8];
+    sealer.seal(&mut accept);
+    let _ = stream.write_all(&accept).await;
     tracing::info!("[char] [mapif] Map Server connected id={} port={}", idx, port);
 
-    let (mut rh, mut wh) = stream.into_split();
+    let (mut rh, mut wh) = tokio::io::split(stream);
 
     let writer = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
+        while let Some(mut msg) = rx.recv().await {
+            sealer.seal(&mut msg);
             if wh.write_all(&msg).await.is_err() {
                 break;
             }
@@ -97,6 +118,8 @@ pub async fn handle_map_server(state: Arc<CharState>, mut stream: TcpStream, fir
         let table_idx = (cmd as usize).wrapping_sub(0x3000);
         if table_idx >= PKT_LENS.len() || PKT_LENS[table_idx] == 0 {
             tracing::warn!("[char] [mapif] unknown cmd={:04X}", cmd);
+            // With MACs on, the tag position is unknowable; resync is impossible.
+            if mac.is_enabled() { break; }
             continue;
         }
 
@@ -131,6 +154,11 @@ pub async fn handle_map_server(state: Arc<CharState>, mut stream: TcpStream, fir
         }
         pkt.extend_from_slice(&rest);
 
+        if !verifier.check(&mut rh, &pkt).await {
+            tracing::error!("[char] [mapif] cmd={:04X} from map server #{} ({}) failed {} check, dropping connection", cmd, idx, peer, verifier.what());
+            break;
+        }
+
         tracing::info!("[char] [mapif] recv cmd={:04X} len={}", cmd, pkt_len);
         dispatch_map_packet(&state, idx, cmd, &pkt).await;
     }
@@ -162,9 +190,9 @@ async fn dispatch_map_packet(state: &Arc<CharState>, map_idx: usize, cmd: u16, p
         0x3001 => handle_mapset(state, map_idx, pkt).await,
         0x3002 => handle_map_login(state, pkt).await,
         0x3003 => handle_request_char(state, map_idx, pkt).await,
-        0x3004 => { let _ = handle_save_char(state, pkt).await; }
-        0x3005 => handle_logout(state, pkt).await,
-        0x3007 => handle_save_char_logout(state, pkt).await,
+        0x3004 => { let _ = handle_save_char(state, map_idx, pkt).await; }
+        0x3005 => handle_logout(state, map_idx, pkt).await,
+        0x3007 => handle_save_char_logout(state, map_idx, pkt).await,
         0x3008 => handle_delete_post(state, map_idx, pkt).await,
         0x3009 => handle_show_posts(state, map_idx, pkt).await,
         0x300A => handle_read_post(state, map_idx, pkt).await,
@@ -173,6 +201,11 @@ async fn dispatch_map_packet(state: &Arc<CharState>, map_idx: usize, cmd: u16, p
         0x300D => handle_nmail_write(state, map_idx, pkt).await,
         0x300E => { /* findnewmp — no-op in C */ }
         0x300F => handle_nmail_write_copy(state, pkt).await,
+        0x3011 => handle_save_now(state, map_idx, pkt).await,
+        0x3012 => handle_disconnect_reason(state, map_idx, pkt).await,
+        0x3013 => handle_resume_hold(state, map_idx, pkt).await,
+        0x3014 => handle_map_change(state, map_idx, pkt).await,
+        0x3016 => handle_shutdown_notice(state, map_idx).await,
         _ => tracing::warn!("[char] [mapif] unhandled cmd={:04X}", cmd),
     }
 }
@@ -228,7 +261,17 @@ async fn handle_request_char(state: &Arc<CharState>, map_idx: usize, pkt: &[u8])
 
     tracing::info!("[char] [mapif] handle_request_char char_id={} session_id={} login_name={}", char_id, session_id, login_name);
 
-    let char_bytes = match db::load_char_bytes(&state.db, char_id, login_name).await {
+    let started = std::time::Instant::now();
+    let loaded = db::load_char_bytes(&state.db, char_id, login_name).await;
+    if state.config.get().char_load_timing {
+        if let Some((p50, p99)) = state.load_timings.record(started.elapsed()) {
+            tracing::info!(
+                "[char] [load_char] last {} loads p50={}us p99={}us",
+                db::LOAD_TIMING_WINDOW, p50.as_micros(), p99.as_micros()
+            );
+        }
+    }
+    let char_bytes = match loaded {
         Ok(b) => b,
         Err(e) => {
             tracing::error!("[char] [mapif] load_char_bytes FAILED for char_id={}: {}", char_id, e);
@@ -236,9 +279,7 @@ async fn handle_request_char(state: &Arc<CharState>, map_idx: usize, pkt: &[u8])
         }
     };
 
-    let mut enc = ZlibEncoder::new(Vec::new(), Compression::default());
-    let _ = enc.write_all(&char_bytes);
-    let compressed = enc.finish().unwrap_or_default();
+    let compressed = codec::compress(state.config.get().charstatus_codec, &char_bytes);
     let clen = compressed.len() as u32;
 
     // Build response 0x3803
@@ -253,48 +294,261 @@ async fn handle_request_char(state: &Arc<CharState>, map_idx: usize, pkt: &[u8])
     send_to_map(state, map_idx, resp).await;
 }
 
-async fn handle_save_char(state: &Arc<CharState>, pkt: &[u8]) -> Option<u32> {
-    if pkt.len() < 6 {
+fn decompress_char(compressed: &[u8]) -> Option<Vec<u8>> {
+    let raw = codec::decompress(compressed).ok()?;
+    if raw.len() < 4 {
         return None;
     }
-    let total_len = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]) as usize;
-    let data_len = total_len.saturating_sub(6);
-    if pkt.len() < 6 + data_len {
-        return None;
+    Some(raw)
+}
+
+/// Whether map server `map_idx` may overwrite `char_id`'s saved state. A
+/// character online through another map server, or whose entry this map
+/// server's copy was reclaimed from, belongs to a newer session that a stale
+/// snapshot must not roll back. With no entry at all (e.g. after a char
+/// server restart) the save is kept.
+async fn owns_save(state: &Arc<CharState>, map_idx: usize, char_id: u32) -> bool {
+    match state.online.lock().await.get(&char_id) {
+        None => true,
+        Some(e) => e.map_server_idx == map_idx && e.reclaimed_on != Some(map_idx),
     }
-    let compressed = &pkt[6..6 + data_len];
+}
 
-    let mut dec = ZlibDecoder::new(compressed);
-    let mut raw = Vec::new();
-    if dec.read_to_end(&mut raw).is_err() {
+/// 0x3004/0x3007 body: decode and persist the charstatus unless `owns_save`
+/// refuses it. Returns the character id whenever the blob decoded.
+async fn handle_save_char(state: &Arc<CharState>, map_idx: usize, pkt: &[u8]) -> Option<u32> {
+    if pkt.len() < 6 {
         return None;
     }
-    if raw.len() < 4 {
+    let total_len = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]) as usize;
+    let data_len = total_len.saturating_sub(6);
+    if pkt.len() < 6 + data_len {
         return None;
     }
-    let char_id = u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]);
+    let raw = decompress_char(&pkt[6..6 + data_len])?;
+    let status = match char_status_from_bytes(&raw) {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("[char] [save_char] rejected blob: {}", e);
+            return None;
+        }
+    };
+    let char_id = status.id;
     tracing::debug!("[char] [save_char] char_id={} decompressed_bytes={}", char_id, raw.len());
-    if let Err(e) = db::save_char_bytes(&state.db, &raw).await {
+    if !owns_save(state, map_idx, char_id).await {
+        tracing::warn!(
+            "[char] [save_char] char_id={} from map #{} dropped; a newer session owns it",
+            char_id, map_idx
+        );
+        return Some(char_id);
+    }
+    if let Err(e) = db::save_char_status(&state.db, &status).await {
         tracing::error!("[char] [save_char] char_id={} failed: {}", char_id, e);
     }
     Some(char_id)
 }
 
-async fn handle_logout(state: &Arc<CharState>, pkt: &[u8]) {
+// ── 0x3011 — Save now ────────────────────────────────────────────────────────
+
+/// Persist one character immediately and ack with 0x3812, so the forcing side
+/// knows whether the write committed.
+///
+/// Layout: [2..6]=total_len, [6..8]=requester fd, [8..]=compressed mmo_charstatus.
+/// Only the map server the character is logged in through may save it; a
+/// request for a character online elsewhere (or not online) is refused. A
+/// blob that cannot be decoded is acked as `Failed` with char_id 0.
+async fn handle_save_now(state: &Arc<CharState>, map_idx: usize, pkt: &[u8]) {
+    if pkt.len() < 8 { return; }
+    let requester = u16::from_le_bytes([pkt[6], pkt[7]]);
+    let status = match decompress_char(&pkt[8..]) {
+        None => Err("an unreadable charstatus".to_string()),
+        Some(raw) => char_status_from_bytes(&raw).map_err(|e| format!("a bad charstatus: {}", e)),
+    };
+    let status = match status {
+        Ok(s) => s,
+        Err(why) => {
+            // No char_id to report; the requester still hears that it failed.
+            tracing::warn!("[char] [save_now] map #{} sent {}", map_idx, why);
+            send_to_map(state, map_idx, build_save_now_ack(0, requester, SaveNowResult::Failed)).await;
+            return;
+        }
+    };
+    let char_id = status.id;
+
+    let owner = state.online.lock().await.get(&char_id)
+        .map(|e| (e.map_server_idx, e.reclaimed_on == Some(map_idx)));
+    let result = match owner {
+        Some((idx, false)) if idx == map_idx => match db::save_char_status(&state.db, &status).await {
+            Ok(()) => SaveNowResult::Saved,
+            Err(e) => {
+                tracing::error!("[char] [save_now] char_id={} failed: {}", char_id, e);
+                SaveNowResult::Failed
+            }
+        },
+        Some((idx, reclaimed)) => {
+            tracing::warn!(
+                "[char] [save_now] char_id={} requested by map #{} but online via map #{}{}",
+                char_id, map_idx, idx, if reclaimed { " (reclaimed copy)" } else { "" }
+            );
+            SaveNowResult::WrongServer
+        }
+        None => {
+            tracing::warn!("[char] [save_now] char_id={} is not online", char_id);
+            SaveNowResult::NotOnline
+        }
+    };
+    tracing::info!("[char] [save_now] char_id={} result={:?}", char_id, result);
+
+    send_to_map(state, map_idx, build_save_now_ack(char_id, requester, result)).await;
+}
+
+/// 0x3812 (9 bytes): [2..6]=char_id, [6..8]=requester fd, [8]=SaveNowResult.
+fn build_save_now_ack(char_id: u32, requester: u16, result: SaveNowResult) -> Vec<u8> {
+    let mut resp = Vec::with_capacity(9);
+    write_u16_le(&mut resp, 0x3812);
+    write_u32_le(&mut resp, char_id);
+    write_u16_le(&mut resp, requester);
+    resp.push(result as u8);
+    resp
+}
+
+async fn handle_logout(state: &Arc<CharState>, map_idx: usize, pkt: &[u8]) {
     if pkt.len() < 6 {
         return;
     }
     let char_id = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]);
+    release_online(state, map_idx, char_id).await;
+}
+
+/// Log `char_id` out on behalf of map server `map_idx`. If the character has
+/// since logged in again (its old entry was reclaimed), the logout is the old
+/// copy's and the newer session is left alone, whichever map server it is on.
+async fn release_online(state: &Arc<CharState>, map_idx: usize, char_id: u32) {
+    {
+        let mut online = state.online.lock().await;
+        if let Some(e) = online.get_mut(&char_id) {
+            if e.take_reclaimed_logout(map_idx) {
+                tracing::info!(
+                    "[char] [mapif] logout of reclaimed copy char_id={} from map #{} ignored",
+                    char_id, map_idx
+                );
+                return;
+            }
+            if e.map_server_idx != map_idx {
+                tracing::warn!(
+                    "[char] [mapif] logout of char_id={} from map #{} ignored; online via map #{}",
+                    char_id, map_idx, e.map_server_idx
+                );
+                return;
+            }
+        }
+    }
     db::set_online(&state.db, char_id, false).await;
     let mut online = state.online.lock().await;
-    online.remove(&char_id);
+    if online.get(&char_id).is_some_and(|e| e.map_server_idx == map_idx) {
+        online.remove(&char_id);
+    }
 }
 
-async fn handle_save_char_logout(state: &Arc<CharState>, pkt: &[u8]) {
-    if let Some(char_id) = handle_save_char(state, pkt).await {
-        db::set_online(&state.db, char_id, false).await;
+/// 0x3012 — map server reports why a player's client session ended.
+/// Sent just before the 0x3007 save+logout, so the online entry is still here.
+async fn handle_disconnect_reason(state: &Arc<CharState>, map_idx: usize, pkt: &[u8]) {
+    if pkt.len() < 7 {
+        return;
+    }
+    let char_id = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]);
+    let reason = DisconnectReason::from_u8(pkt[6]);
+    let name = state.online.lock().await
+        .get(&char_id)
+        .map(|e| e.char_name.clone())
+        .unwrap_or_default();
+    tracing::info!(
+        "[char] [mapif] disconnect map={} char_id={} name={} reason={}",
+        map_idx, char_id, name, reason.name()
+    );
+}
+
+/// 0x3013 — map server parked a player after a transient drop.
+/// Layout: [2..6]=char_id, [6..10]=token, [10..14]=client ip, [14..16]=grace secs.
+/// The online entry stays; a login from the same subnet before the hold
+/// expires is routed back to this map server instead of being kicked.
+async fn handle_resume_hold(state: &Arc<CharState>, map_idx: usize, pkt: &[u8]) {
+    if pkt.len() < 16 {
+        return;
+    }
+    let char_id = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]);
+    let token = u32::from_le_bytes([pkt[6], pkt[7], pkt[8], pkt[9]]);
+    let ip = u32::from_le_bytes([pkt[10], pkt[11], pkt[12], pkt[13]]);
+    let grace = u16::from_le_bytes([pkt[14], pkt[15]]);
+    let mut online = state.online.lock().await;
+    match online.get_mut(&char_id) {
+        Some(e) if e.map_server_idx == map_idx => {
+            e.resume = Some(super::ResumeHold {
+                token,
+                ip,
+                expires: std::time::Instant::now() + std::time::Duration::from_secs(grace as u64),
+            });
+            tracing::info!("[char] [mapif] resume hold char_id={} token={:08X} grace={}s", char_id, token, grace);
+        }
+        _ => tracing::warn!("[char] [mapif] resume hold for char_id={} not online on map #{}", char_id, map_idx),
+    }
+}
+
+/// 0x3014 — a character moved to another map on its map server.
+/// Layout: [2..6]=char_id, [6..8]=map id, [8..12]=sequence. Reports from a
+/// map server the character is no longer on, or older than the last one
+/// applied, are dropped.
+async fn handle_map_change(state: &Arc<CharState>, map_idx: usize, pkt: &[u8]) {
+    if pkt.len() < 12 {
+        return;
+    }
+    let char_id = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]);
+    let m = u16::from_le_bytes([pkt[6], pkt[7]]);
+    let seq = u32::from_le_bytes([pkt[8], pkt[9], pkt[10], pkt[11]]);
+    let mut online = state.online.lock().await;
+    if let Some(e) = online.get_mut(&char_id) {
+        if e.map_server_idx == map_idx && presence::is_newer(seq, e.map_seq) {
+            e.map = Some(m);
+            e.map_seq = seq;
+        }
+    }
+}
+
+/// 0x3016 — the map server is stopping and has sent its final saves.
+///
+/// Packets on a link are handled in order and each save is written before
+/// the next is read, so by now every earlier save is committed. Log out the
+/// server's characters and ack with 0x3813 so it can exit.
+async fn handle_shutdown_notice(state: &Arc<CharState>, map_idx: usize) {
+    let released: Vec<u32> = {
         let mut online = state.online.lock().await;
-        online.remove(&char_id);
+        let ids: Vec<u32> = online.iter()
+            .filter(|(_, e)| e.map_server_idx == map_idx)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in &ids {
+            online.remove(id);
+        }
+        ids
+    };
+    for &char_id in &released {
+        db::set_online(&state.db, char_id, false).await;
+    }
+    tracing::info!("[char] [mapif] Map Server #{} shutting down, released {} characters", map_idx, released.len());
+    send_to_map(state, map_idx, build_shutdown_ack(released.len() as u32)).await;
+}
+
+/// 0x3813 (6 bytes): [2..6]=characters logged out.
+fn build_shutdown_ack(released: u32) -> Vec<u8> {
+    let mut resp = Vec::with_capacity(6);
+    write_u16_le(&mut resp, 0x3813);
+    write_u32_le(&mut resp, released);
+    resp
+}
+
+async fn handle_save_char_logout(state: &Arc<CharState>, map_idx: usize, pkt: &[u8]) {
+    if let Some(char_id) = handle_save_char(state, map_idx, pkt).await {
+        release_online(state, map_idx, char_id).await;
     }
 }
 
@@ -685,8 +939,8 @@ mod tests {
 
     #[test]
     fn test_pkt_lens_table_size() {
-        // Table covers 0x3000..=0x3015 (22 entries)
-        assert_eq!(PKT_LENS.len(), 22);
+        // Table covers 0x3000..=0x3016 (23 entries)
+        assert_eq!(PKT_LENS.len(), 23);
     }
 
     #[test]
@@ -696,4 +950,24 @@ mod tests {
         assert_eq!(PKT_LENS[10], 34);  // boards_read_post_0 + 2
         assert_eq!(PKT_LENS[12], 4086); // boards_post_0 + 2
     }
+
+    #[test]
+    fn test_save_now_ack_layout() {
+        let ack = build_save_now_ack(1234, 7, SaveNowResult::WrongServer);
+        assert_eq!(ack.len(), 9);
+        assert_eq!(u16::from_le_bytes([ack[0], ack[1]]), 0x3812);
+        assert_eq!(u32::from_le_bytes([ack[2], ack[3], ack[4], ack[5]]), 1234);
+        assert_eq!(u16::from_le_bytes([ack[6], ack[7]]), 7);
+        assert_eq!(ack[8], SaveNowResult::WrongServer as u8);
+    }
+
+    #[test]
+    fn test_decompress_char_rejects_garbage() {
+        assert!(decompress_char(b"not zlib").is_none());
+
+        for c in [codec::Codec::Zlib, codec::Codec::Zstd] {
+            let raw = decompress_char(&codec::compress(c, &[1, 0, 0, 0, 9])).unwrap();
+            assert_eq!(raw, vec![1, 0, 0, 0, 9]);
+        }
+    }
 }
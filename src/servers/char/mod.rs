@@ -1,4 +1,5 @@
 pub mod charstatus;
+pub mod codec;
 pub mod db;
 pub mod login;
 pub mod map;
@@ -8,11 +9,14 @@ use anyhow::Result;
 use std::sync::Arc;
 use std::collections::HashMap;
 use tokio::sync::Mutex;
-use tokio::net::{TcpListener, TcpStream};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
 use tokio::time::{Duration, sleep};
 use tokio::io::AsyncReadExt;
 use sqlx::MySqlPool;
-use crate::config::ServerConfig;
+use serde::{Deserialize, Serialize};
+use crate::config::{LiveConfig, ServerConfig};
+use crate::network::Stream;
 
 /// One connected map server's state.
 #[derive(Debug)]
@@ -28,39 +32,99 @@ pub struct MapFifo {
 pub struct LoginEntry {
     pub map_server_idx: usize,
     pub char_name: String,
+    /// Set while the map server holds the player parked after a network drop.
+    pub resume: Option<ResumeHold>,
+    /// Map the character is on, once the map server has reported it (0x3014).
+    pub map: Option<u16>,
+    /// Sequence of the last applied map report; older ones are ignored.
+    pub map_seq: u32,
+    /// Map server still holding an older copy of this character whose entry
+    /// was reclaimed by this login. Its logout is the next one from there.
+    pub reclaimed_on: Option<usize>,
+}
+
+impl LoginEntry {
+    /// True if a logout from map server `map_idx` belongs to the reclaimed
+    /// older copy rather than this session; the marker is used up.
+    pub fn take_reclaimed_logout(&mut self, map_idx: usize) -> bool {
+        if self.reclaimed_on == Some(map_idx) {
+            self.reclaimed_on = None;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// What a login for a character that is already online does (`double_login`).
+/// Either way the new client is told `LGN_DBLLOGIN`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DoubleLoginPolicy {
+    /// Leave the existing session alone.
+    Reject,
+    /// Kick the existing session off its map server, so a retry gets in.
+    #[default]
+    Kick,
+}
+
+/// A map server's promise to re-attach a parked player (see 0x3013).
+#[derive(Debug, Clone, Copy)]
+pub struct ResumeHold {
+    pub token: u32,
+    /// Client IPv4 at disconnect, host byte order.
+    pub ip: u32,
+    pub expires: std::time::Instant,
 }
 
 pub struct CharState {
     pub db: MySqlPool,
-    pub config: ServerConfig,
+    /// Live config, swapped on SIGHUP (see `servers::reload_on_hangup`)
+    pub config: Arc<LiveConfig>,
     /// char_id → LoginEntry
     pub online: Mutex<HashMap<u32, LoginEntry>>,
     /// index → MapFifo
     pub map_servers: Mutex<Vec<Option<MapFifo>>>,
     /// sender to login server connection task
     pub login_tx: Mutex<Option<tokio::sync::mpsc::Sender<Vec<u8>>>>,
+    /// Who may connect as a map server (`interserver_allow`)
+    pub interserver_acl: crate::network::acl::Acl,
+    /// `load_char_bytes` latencies, kept when `char_load_timing` is on
+    pub load_timings: db::LoadTimings,
 }
 
 impl CharState {
     pub fn new(db: MySqlPool, config: ServerConfig) -> Self {
+        let interserver_acl = crate::network::acl::Acl::parse(&config.interserver_allow)
+            .expect("interserver_allow is checked by ServerConfig::validate");
         Self {
             db,
-            config,
+            config: Arc::new(LiveConfig::new(config)),
             online: Mutex::new(HashMap::new()),
             map_servers: Mutex::new(Vec::new()),
             login_tx: Mutex::new(None),
+            interserver_acl,
+            load_timings: db::LoadTimings::default(),
         }
     }
 
+    /// Ids of online characters last reported on map `m`.
+    pub async fn chars_on_map(&self, m: u16) -> Vec<u32> {
+        self.online.lock().await.iter()
+            .filter(|(_, e)| e.map == Some(m))
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
     pub async fn run(state: Arc<Self>, bind_addr: &str) -> Result<()> {
         let listener = TcpListener::bind(bind_addr).await?;
         tracing::info!("[char] [ready] addr={}", bind_addr);
         loop {
             match listener.accept().await {
-                Ok((stream, _peer)) => {
+                Ok((stream, peer)) => {
                     let s = Arc::clone(&state);
                     tokio::spawn(async move {
-                        handle_new_connection(s, stream).await;
+                        handle_new_connection(s, stream, peer).await;
                     });
                 }
                 Err(e) => {
@@ -70,9 +134,25 @@ impl CharState {
             }
         }
     }
+
+    /// Wait up to `timeout` for every map server link to close, so their
+    /// final saves land before this server stops. Returns the links still
+    /// open when it gave up.
+    pub async fn drain_map_links(&self, timeout: Duration) -> usize {
+        let open = || async { self.map_servers.lock().await.iter().filter(|s| s.is_some()).count() };
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let n = open().await;
+            if n == 0 || tokio::time::Instant::now() >= deadline {
+                return n;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    }
 }
 
-async fn handle_new_connection(state: Arc<CharState>, mut stream: TcpStream) {
+/// Serve one inbound connection over any byte stream (see `servers::testing`).
+pub async fn handle_new_connection(state: Arc<CharState>, mut stream: impl Stream, peer: SocketAddr) {
     let mut cmd_bytes = [0u8; 2];
     if stream.read_exact(&mut cmd_bytes).await.is_err() {
         return;
@@ -80,7 +160,11 @@ async fn handle_new_connection(state: Arc<CharState>, mut stream: TcpStream) {
     let cmd = u16::from_le_bytes(cmd_bytes);
 
     if cmd == 0x3000 {
-        map::handle_map_server(state, stream, cmd_bytes).await;
+        if !state.interserver_acl.allows(peer.ip()) {
+            tracing::warn!("[char] [mapif] refused map server link from {} (not in interserver_allow)", peer);
+            return;
+        }
+        map::handle_map_server(state, stream, peer, cmd_bytes).await;
     } else {
         tracing::warn!("[char] [unknown_cmd] cmd={:04X}", cmd);
     }
@@ -95,4 +179,15 @@ mod tests {
         let _ = std::mem::size_of::<MapFifo>();
         let _ = std::mem::size_of::<LoginEntry>();
     }
+
+    #[test]
+    fn test_reclaimed_logout_is_used_once() {
+        let mut e = LoginEntry {
+            map_server_idx: 1, char_name: "a".into(), resume: None, map: None, map_seq: 0,
+            reclaimed_on: Some(1),
+        };
+        assert!(!e.take_reclaimed_logout(0));
+        assert!(e.take_reclaimed_logout(1));
+        assert!(!e.take_reclaimed_logout(1));
+    }
 }
@@ -1,19 +1,56 @@
 use anyhow::Result;
 use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
+use crate::network::Stream;
 
 pub use crate::network::read_framed_packet;
 
 /// Read one raw packet from a plain (non-0xAA-framed) interserver stream.
 /// Reads exactly `len` bytes starting with a 2-byte LE command already known.
-pub async fn read_exact_bytes(stream: &mut TcpStream, len: usize) -> Result<Vec<u8>> {
+pub async fn read_exact_bytes(stream: &mut impl Stream, len: usize) -> Result<Vec<u8>> {
     let mut buf = vec![0u8; len];
     stream.read_exact(&mut buf).await?;
     Ok(buf)
 }
 
+/// Result code carried by the 0x3812 save-now ack (char→map).
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveNowResult {
+    /// `save_char_bytes` committed.
+    Saved = 0,
+    /// The blob was unreadable or the database write failed.
+    Failed = 1,
+    /// The character is online through a different map server; that
+    /// server holds the live copy, so the request was refused.
+    WrongServer = 2,
+    /// The character is not registered as online anywhere.
+    NotOnline = 3,
+}
+
+impl SaveNowResult {
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::Saved),
+            1 => Some(Self::Failed),
+            2 => Some(Self::WrongServer),
+            3 => Some(Self::NotOnline),
+            _ => None,
+        }
+    }
+
+    /// Short human-readable description (shown to the requesting GM).
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Saved => "saved",
+            Self::Failed => "save failed",
+            Self::WrongServer => "online on another map server",
+            Self::NotOnline => "not online",
+        }
+    }
+}
+
 /// Read a 2-byte LE command word from a stream.
-pub async fn read_cmd(stream: &mut TcpStream) -> Result<u16> {
+pub async fn read_cmd(stream: &mut impl Stream) -> Result<u16> {
     let mut b = [0u8; 2];
     stream.read_exact(&mut b).await?;
     Ok(u16::from_le_bytes(b))
@@ -21,10 +58,25 @@ pub async fn read_cmd(stream: &mut TcpStream) -> Result<u16> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_cmd_le_parse() {
         let bytes = [0x00u8, 0x30]; // 0x3000 in LE
         let cmd = u16::from_le_bytes(bytes);
         assert_eq!(cmd, 0x3000);
     }
+
+    #[test]
+    fn test_save_now_result_roundtrip() {
+        for r in [
+            SaveNowResult::Saved,
+            SaveNowResult::Failed,
+            SaveNowResult::WrongServer,
+            SaveNowResult::NotOnline,
+        ] {
+            assert_eq!(SaveNowResult::from_u8(r as u8), Some(r));
+        }
+        assert_eq!(SaveNowResult::from_u8(4), None);
+    }
 }
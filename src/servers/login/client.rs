@@ -1,12 +1,13 @@
 use std::sync::Arc;
 use std::net::SocketAddr;
-use tokio::net::TcpStream;
+use crate::network::Stream;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc;
 
-use super::{LoginState, CharResponse, LGN_ERRDB, LGN_ERRPASS, LGN_ERRUSER};
-use super::packet::{read_client_packet, build_message, build_version_ok, build_version_patch};
-use crate::network::crypt::tk_crypt_static;
+use super::{LoginState, CharResponse, LGN_DRAINING, LGN_ERRDB, LGN_ERRPASS, LGN_ERRUSER};
+use super::packet::{build_message, build_version_ok, build_version_patch};
+use crate::metrics::{AuthFailure, METRICS};
+use crate::network::crypt::Cipher;
 
 struct SessionData {
     name: String,
@@ -40,10 +41,11 @@ pub fn is_valid_password(s: &str) -> bool {
 
 pub async fn handle_client(
     state: Arc<LoginState>,
-    mut stream: TcpStream,
+    mut stream: impl Stream,
     peer: SocketAddr,
     session_id: u16,
     first_packet: Vec<u8>,
+    cipher: Arc<dyn Cipher>,
 ) {
     let mut sd = SessionData::default();
     let mut queue: Vec<Vec<u8>> = vec![first_packet];
@@ -52,7 +54,7 @@ pub async fn handle_client(
         let mut pkt = if let Some(p) = queue.pop() {
             p
         } else {
-            match read_client_packet(&mut stream).await {
+            match state.read_idle(&mut stream).await {
                 Ok(p) => p,
                 Err(e) => {
                     tracing::info!("[login] [client_disconnect] session={} peer={} reason={}", session_id, peer, e);
@@ -62,8 +64,7 @@ pub async fn handle_client(
         };
 
         // Decrypt packet in place
-        let xk = state.config.xor_key.as_bytes().to_vec();
-        tk_crypt_static(&mut pkt, &xk);
+        cipher.decrypt_packet(&mut pkt);
 
         if pkt.len() < 4 {
             tracing::warn!("[login] [short_packet] session={} len={} raw={:02X?}", session_id, pkt.len(), &pkt[..]);
@@ -74,35 +75,57 @@ pub async fn handle_client(
         tracing::debug!("[login] [packet_in] session={} cmd={:02X}", session_id, cmd);
 
         match cmd {
-            0x00 => dispatch_version_check(&mut stream, &pkt, &state).await,
-            0x02 => dispatch_register(&mut stream, &pkt, &state, &mut sd, session_id).await,
-            0x03 => dispatch_login(&mut stream, &pkt, &state, &mut sd, session_id, &peer).await,
-            0x04 => dispatch_create_char(&mut stream, &pkt, &state, &mut sd, session_id).await,
+            0x00 => dispatch_version_check(&mut stream, &pkt, &state, &*cipher).await,
+            0x02 => dispatch_register(&mut stream, &pkt, &state, &mut sd, session_id, &*cipher).await,
+            0x03 => {
+                // A locked-out IP gets no more guesses on a connection it
+                // opened earlier, and is dropped once it reaches the threshold.
+                if locked_out(&state, &peer, session_id).await {
+                    METRICS.auth_failed(AuthFailure::Lockout);
+                    return;
+                }
+                dispatch_login(&mut stream, &pkt, &state, &mut sd, session_id, &peer, &*cipher).await;
+                if locked_out(&state, &peer, session_id).await {
+                    return;
+                }
+            }
+            0x04 => dispatch_create_char(&mut stream, &pkt, &state, &mut sd, session_id, &*cipher).await,
             0x10 => dispatch_heartbeat(&mut stream).await,
-            0x26 => dispatch_change_pass(&mut stream, &pkt, &state, &mut sd, session_id).await,
+            0x26 => dispatch_change_pass(&mut stream, &pkt, &state, &mut sd, session_id, &*cipher).await,
             0x57 | 0x71 | 0x62 => {
                 tracing::debug!("[login] [client_ping] session={} cmd={:02X} raw={:02X?}",
                     session_id, cmd, &pkt[..pkt.len().min(16)]);
             }
-            0x7B => super::meta::dispatch_meta(&mut stream, &pkt, &state).await,
+            0x7B => super::meta::dispatch_meta(&mut stream, &pkt, &state, &*cipher).await,
             _ => tracing::warn!("[login] [packet_unknown] cmd={:02X} session={}", cmd, session_id),
         }
     }
 }
 
-async fn dispatch_version_check(stream: &mut TcpStream, pkt: &[u8], state: &LoginState) {
+/// Whether `peer` is locked out (see `lockout`); logs the close if so.
+async fn locked_out(state: &LoginState, peer: &SocketAddr, session_id: u16) -> bool {
+    let std::net::IpAddr::V4(v4) = peer.ip() else { return false };
+    if !state.is_locked_out(u32::from(v4)).await {
+        return false;
+    }
+    tracing::info!("[login] [lockout] ip={} session={} closed", peer.ip(), session_id);
+    true
+}
+
+async fn dispatch_version_check(stream: &mut impl Stream, pkt: &[u8], state: &LoginState, cipher: &dyn Cipher) {
     if pkt.len() < 9 { return; }
     // The version check packet is sent unencrypted by the client.
-    // handle_client applied tk_crypt_static globally; re-apply here to reverse it
-    // (XOR is its own inverse), restoring the original bytes before reading.
+    // handle_client decrypted every packet; encrypt again here to reverse it,
+    // restoring the original bytes before reading.
     let mut pkt = pkt.to_vec();
-    tk_crypt_static(&mut pkt, state.config.xor_key.as_bytes());
+    cipher.encrypt_packet(&mut pkt);
     let ver  = u16::from_be_bytes([pkt[4], pkt[5]]);
     let deep = u16::from_be_bytes([pkt[7], pkt[8]]);
     tracing::info!("[login] [version_check] client_version={} patch={}", ver, deep);
 
-    let xk = &state.config.xor_key;
-    let nex = state.config.version as u16;
+    let config = state.config.get();
+    let xk = &config.xor_key;
+    let nex = config.version as u16;
     let response = if ver == nex {
         build_version_ok(xk)
     } else {
@@ -111,19 +134,19 @@ async fn dispatch_version_check(stream: &mut TcpStream, pkt: &[u8], state: &Logi
     let _ = stream.write_all(&response).await;
 }
 
-async fn dispatch_heartbeat(stream: &mut TcpStream) {
+async fn dispatch_heartbeat(stream: &mut impl Stream) {
     let pkt: &[u8] = &[0xAA, 0x00, 0x07, 0x60, 0x00, 0x55, 0xE0, 0xD8, 0xA2, 0xA0];
     let _ = stream.write_all(pkt).await;
 }
 
 async fn dispatch_register(
-    stream: &mut TcpStream,
+    stream: &mut impl Stream,
     pkt: &[u8],
     state: &LoginState,
     sd: &mut SessionData,
     session_id: u16,
+    cipher: &dyn Cipher,
 ) {
-    let xk = state.config.xor_key.as_bytes();
     if pkt.len() < 6 { return; }
     let name_len = pkt[5] as usize;
     if pkt.len() < 6 + name_len + 1 { return; }
@@ -131,7 +154,7 @@ async fn dispatch_register(
         .unwrap_or("").trim_end_matches('\0').to_string();
 
     if !is_valid_name(&name) {
-        let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRUSER], xk)).await;
+        let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRUSER], cipher)).await;
         return;
     }
 
@@ -141,7 +164,7 @@ async fn dispatch_register(
         .unwrap_or("").trim_end_matches('\0').to_string();
 
     if !is_valid_password(&pass) {
-        let _ = stream.write_all(&build_message(0x05, &state.messages.0[LGN_ERRPASS], xk)).await;
+        let _ = stream.write_all(&build_message(0x05, &state.messages.0[LGN_ERRPASS], cipher)).await;
         return;
     }
 
@@ -155,18 +178,18 @@ async fn dispatch_register(
     let nb = name.as_bytes();
     msg[4..4 + nb.len().min(16)].copy_from_slice(&nb[..nb.len().min(16)]);
 
-    forward_to_char(state, stream, msg, session_id, xk, &state.messages.0[LGN_ERRDB]).await;
+    forward_to_char(state, stream, msg, session_id, cipher, &state.messages.0[LGN_ERRDB]).await;
 }
 
 async fn dispatch_login(
-    stream: &mut TcpStream,
+    stream: &mut impl Stream,
     pkt: &[u8],
     state: &LoginState,
     sd: &mut SessionData,
     session_id: u16,
     peer: &SocketAddr,
+    cipher: &dyn Cipher,
 ) {
-    let xk = state.config.xor_key.as_bytes();
     if pkt.len() < 6 { return; }
     let name_len = pkt[5] as usize;
     if pkt.len() < 6 + name_len + 1 { return; }
@@ -174,7 +197,8 @@ async fn dispatch_login(
         .unwrap_or("").trim_end_matches('\0').to_string();
 
     if !is_valid_name(&name) {
-        let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRUSER], xk)).await;
+        METRICS.auth_failed(AuthFailure::BadName);
+        let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRUSER], cipher)).await;
         return;
     }
 
@@ -184,7 +208,19 @@ async fn dispatch_login(
         .unwrap_or("").trim_end_matches('\0').to_string();
 
     if !is_valid_password(&pass) {
-        let _ = stream.write_all(&build_message(0x05, &state.messages.0[LGN_ERRPASS], xk)).await;
+        METRICS.auth_failed(AuthFailure::BadPassword);
+        let _ = stream.write_all(&build_message(0x05, &state.messages.0[LGN_ERRPASS], cipher)).await;
+        return;
+    }
+
+    if state.drain.is_draining() {
+        tracing::info!("[login] [drain] refused name={} ip={}", name, peer.ip());
+        METRICS.auth_failed(AuthFailure::Draining);
+        let text = match state.messages.0[LGN_DRAINING].as_str() {
+            "" => "This server is not accepting new logins. Please use another server.",
+            t => t,
+        };
+        let _ = stream.write_all(&build_message(0x03, text, cipher)).await;
         return;
     }
 
@@ -193,17 +229,18 @@ async fn dispatch_login(
         if super::db::get_maintenance_mode(pool).await {
             let gm = super::db::get_char_gm_level(pool, &name).await;
             if gm == 0 {
+                METRICS.auth_failed(AuthFailure::Maintenance);
                 let _ = stream.write_all(&build_message(0x03,
                     "Server is undergoing maintenance. Please visit www.website.com or the facebook group for more details.",
-                    xk)).await;
+                    cipher)).await;
                 return;
             }
         }
-        if state.config.require_reg != 0 {
+        if state.config.get().require_reg != 0 {
             if super::db::get_account_for_char(pool, &name).await == 0 {
                 let _ = stream.write_all(&build_message(0x03,
                     "You must attach your character to an account to play.\n\nPlease visit www.website.com to attach your character to an account.",
-                    xk)).await;
+                    cipher)).await;
                 return;
             }
         }
@@ -220,19 +257,39 @@ async fn dispatch_login(
     msg[4..4 + nb.len().min(16)].copy_from_slice(&nb[..nb.len().min(16)]);
     let pb = pass.as_bytes();
     msg[20..20 + pb.len().min(16)].copy_from_slice(&pb[..pb.len().min(16)]);
-    if let std::net::IpAddr::V4(v4) = peer.ip() {
-        msg[36..40].copy_from_slice(&v4.octets());
-    }
+    let ip = match peer.ip() {
+        std::net::IpAddr::V4(v4) => {
+            msg[36..40].copy_from_slice(&v4.octets());
+            Some(u32::from(v4))
+        }
+        _ => None,
+    };
 
-    forward_to_char(state, stream, msg, session_id, xk, &state.messages.0[LGN_ERRDB]).await;
+    let result = forward_to_char(state, stream, msg, session_id, cipher, &state.messages.0[LGN_ERRDB]).await;
+    if let Some(ip) = ip {
+        let mut lockout = state.lockout.lock().await;
+        match result {
+            Some(0x00) => { lockout.remove(&ip); }
+            Some(0x03) => {
+                let config = state.config.get();
+                let window = std::time::Duration::from_secs(config.login_lockout_window_secs);
+                let fails = super::lockout::record_failure(&mut lockout, ip, window, std::time::Instant::now());
+                if fails == config.login_lockout_threshold {
+                    tracing::info!("[login] [lockout] ip={} locked after {} wrong passwords", peer.ip(), fails);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 async fn dispatch_create_char(
-    stream: &mut TcpStream,
+    stream: &mut impl Stream,
     pkt: &[u8],
     state: &LoginState,
     sd: &mut SessionData,
     session_id: u16,
+    cipher: &dyn Cipher,
 ) {
     if sd.name.is_empty() || sd.pass.is_empty() { return; }
     if pkt.len() < 13 { return; }
@@ -248,7 +305,6 @@ async fn dispatch_create_char(
         .unwrap_or_default()
         .subsec_nanos() % 2) as u8;
 
-    let xk = state.config.xor_key.as_bytes();
     let mut msg = vec![0u8; 43];
     msg[0] = 0x02; msg[1] = 0x10;
     msg[2] = (session_id & 0xFF) as u8;
@@ -260,17 +316,17 @@ async fn dispatch_create_char(
     msg[36] = sd.face; msg[37] = sd.sex; msg[38] = sd.country;
     msg[39] = sd.totem; msg[40] = sd.hair; msg[41] = sd.hair_color; msg[42] = sd.face_color;
 
-    forward_to_char(state, stream, msg, session_id, xk, &state.messages.0[LGN_ERRDB]).await;
+    forward_to_char(state, stream, msg, session_id, cipher, &state.messages.0[LGN_ERRDB]).await;
 }
 
 async fn dispatch_change_pass(
-    stream: &mut TcpStream,
+    stream: &mut impl Stream,
     pkt: &[u8],
     state: &LoginState,
     sd: &mut SessionData,
     session_id: u16,
+    cipher: &dyn Cipher,
 ) {
-    let xk = state.config.xor_key.as_bytes();
     if pkt.len() < 6 { return; }
     let name_len = pkt[5] as usize;
     if name_len > 16 { return; }
@@ -285,7 +341,7 @@ async fn dispatch_change_pass(
 
     let name = std::str::from_utf8(&pkt[6..6 + name_len]).unwrap_or("").trim_end_matches('\0');
     if !is_valid_name(name) {
-        let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRUSER], xk)).await;
+        let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRUSER], cipher)).await;
         return;
     }
 
@@ -299,17 +355,19 @@ async fn dispatch_change_pass(
     msg[20..20 + old_pass_len.min(16)].copy_from_slice(&pkt[old_off + 1..old_off + 1 + old_pass_len.min(16)]);
     msg[36..36 + new_pass_len.min(16)].copy_from_slice(&pkt[new_off + 1..new_off + 1 + new_pass_len.min(16)]);
 
-    forward_to_char(state, stream, msg, session_id, xk, &state.messages.0[LGN_ERRDB]).await;
+    forward_to_char(state, stream, msg, session_id, cipher, &state.messages.0[LGN_ERRDB]).await;
 }
 
+/// Send `msg` to the char server and relay its answer to the client.
+/// Returns the answer's result byte, or None if none arrived.
 async fn forward_to_char(
     state: &LoginState,
-    stream: &mut TcpStream,
+    stream: &mut impl Stream,
     msg: Vec<u8>,
     session_id: u16,
-    xk: &[u8],
+    cipher: &dyn Cipher,
     err_db_msg: &str,
-) {
+) -> Option<u8> {
     // The char server relays a single response per request. For login (0x2003),
     // the response arrives after the map server acks via mapif_parse_login and
     // contains the map server IP:port for the client redirect.
@@ -339,9 +397,9 @@ async fn forward_to_char(
 
     if !sent {
         tracing::warn!("[login] [forward_to_char] session={} FAILED to send to char server", session_id);
-        let _ = stream.write_all(&build_message(0x03, err_db_msg, xk)).await;
+        let _ = stream.write_all(&build_message(0x03, err_db_msg, cipher)).await;
         remove_pending().await;
-        return;
+        return None;
     }
     tracing::debug!("[login] [forward_to_char] session={} sent OK, waiting for response...", session_id);
 
@@ -356,21 +414,24 @@ async fn forward_to_char(
         }
         Ok(None) => {
             tracing::warn!("[login] [forward_to_char] session={} channel closed (no response)", session_id);
-            let _ = stream.write_all(&build_message(0x03, err_db_msg, xk)).await;
+            let _ = stream.write_all(&build_message(0x03, err_db_msg, cipher)).await;
             remove_pending().await;
-            return;
+            return None;
         }
         Err(_) => {
             tracing::warn!("[login] [forward_to_char] session={} TIMEOUT waiting for char response", session_id);
-            let _ = stream.write_all(&build_message(0x03, err_db_msg, xk)).await;
+            let _ = stream.write_all(&build_message(0x03, err_db_msg, cipher)).await;
             remove_pending().await;
-            return;
+            return None;
         }
     };
 
-    super::interserver::dispatch_char_response(stream, state, &resp).await;
+    if let Err(e) = super::interserver::dispatch_char_response(stream, state, &resp, cipher).await {
+        e.log("[login] [dispatch_char_response]");
+    }
 
     remove_pending().await;
+    resp.data.get(4).copied()
 }
 
 #[cfg(test)]
@@ -1,15 +1,76 @@
 use sqlx::MySqlPool;
 
-/// Returns true if `ip` (dotted-decimal string) is in `BannedIP`.
-pub async fn is_ip_banned(pool: &MySqlPool, ip: &str) -> bool {
-    let row: Option<(i64,)> = sqlx::query_as(
-        "SELECT COUNT(*) FROM `BannedIP` WHERE `BndIP` = ?"
+/// A `BannedIP` row: why, and until when (Unix seconds; None = permanent).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BanInfo {
+    pub reason: String,
+    pub expires: Option<i64>,
+}
+
+impl BanInfo {
+    /// Whether the ban is still in force at `now` (Unix seconds).
+    pub fn is_active(&self, now: i64) -> bool {
+        !matches!(self.expires, Some(t) if t <= now)
+    }
+
+    /// What the client is told: `banned` (`LGN_BANNED`), then the reason and,
+    /// for a temporary ban, when it ends.
+    pub fn message(&self, banned: &str) -> String {
+        let mut text = banned.to_string();
+        if !self.reason.is_empty() {
+            text.push(' ');
+            text.push_str(&self.reason);
+        }
+        if let Some(until) = self.expires.and_then(|t| chrono::DateTime::from_timestamp(t, 0)) {
+            text.push_str(&until.format(" (until %Y-%m-%d %H:%M UTC)").to_string());
+        }
+        text
+    }
+}
+
+/// The ban in force at `now`: a permanent one if any, else the latest to expire.
+fn active_ban(bans: impl IntoIterator<Item = BanInfo>, now: i64) -> Option<BanInfo> {
+    bans.into_iter()
+        .filter(|b| b.is_active(now))
+        .max_by_key(|b| b.expires.unwrap_or(i64::MAX))
+}
+
+/// The ban on `ip` (dotted-decimal string) in `BannedIP`, if one is in force.
+/// Expired temporary bans are ignored.
+///
+/// If the reason/expiry query fails (e.g. migration 29 has not run), the
+/// plain row count from before it decides, as a permanent ban with no
+/// reason. If that fails too the IP is let through, as it always was, but
+/// both errors are logged.
+pub async fn is_ip_banned(pool: &MySqlPool, ip: &str) -> Option<BanInfo> {
+    let rows: sqlx::Result<Vec<(String, Option<i64>)>> = sqlx::query_as(
+        "SELECT `BndReason`, `BndExpires` FROM `BannedIP` WHERE `BndIP` = ?"
     )
     .bind(ip)
-    .fetch_optional(pool)
-    .await
-    .unwrap_or(None);
-    row.map(|(n,)| n > 0).unwrap_or(false)
+    .fetch_all(pool)
+    .await;
+    match rows {
+        Ok(rows) => {
+            let bans = rows.into_iter().map(|(reason, expires)| BanInfo { reason, expires });
+            active_ban(bans, chrono::Utc::now().timestamp())
+        }
+        Err(e) => {
+            tracing::warn!("[login] [ip_ban] ban lookup for {} failed, falling back to row count: {}", ip, e);
+            let count: sqlx::Result<(i64,)> = sqlx::query_as(
+                "SELECT COUNT(*) FROM `BannedIP` WHERE `BndIP` = ?"
+            )
+            .bind(ip)
+            .fetch_one(pool)
+            .await;
+            match count {
+                Ok((n,)) => (n > 0).then(|| BanInfo { reason: String::new(), expires: None }),
+                Err(e) => {
+                    tracing::error!("[login] [ip_ban] ban check for {} failed, letting it through: {}", ip, e);
+                    None
+                }
+            }
+        }
+    }
 }
 
 /// Returns true if the `Maintenance` table flag is non-zero.
@@ -23,6 +84,17 @@ pub async fn get_maintenance_mode(pool: &MySqlPool) -> bool {
     row.map(|(n,)| n != 0).unwrap_or(false)
 }
 
+/// Number of characters online that logged in through `server_id`'s char
+/// server (`ChaServer`, set by `char::db::mark_online`). Other stacks sharing
+/// the database are not counted.
+pub async fn count_online(pool: &MySqlPool, server_id: i32) -> sqlx::Result<u64> {
+    let (n,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM `Character` WHERE `ChaOnline` = 1 AND `ChaServer` = ?")
+        .bind(server_id.max(0))
+        .fetch_one(pool)
+        .await?;
+    Ok(n.max(0) as u64)
+}
+
 /// Returns the GM level for `char_name`, or 0 if not found.
 pub async fn get_char_gm_level(pool: &MySqlPool, char_name: &str) -> u32 {
     let row: Option<(u32,)> = sqlx::query_as(
@@ -79,4 +151,20 @@ pub async fn update_char_last_ip(pool: &MySqlPool, char_name: &str, ip: &str) {
 mod tests {
     // DB integration tests require a live DATABASE_URL; skipped in CI.
     // Pattern matches src/database/mob_db.rs convention.
+    use super::*;
+
+    #[test]
+    fn test_expired_bans_are_ignored() {
+        let ban = |reason: &str, expires| BanInfo { reason: reason.into(), expires };
+        let now = 1_800_000_000;
+        assert_eq!(active_ban([ban("old", Some(now - 1)), ban("ends now", Some(now))], now), None);
+        assert_eq!(active_ban([ban("a", Some(now + 60)), ban("b", Some(now + 3600))], now), Some(ban("b", Some(now + 3600))));
+        assert_eq!(active_ban([ban("temp", Some(now + 60)), ban("perm", None)], now), Some(ban("perm", None)));
+
+        assert_eq!(ban("", None).message("Banned."), "Banned.");
+        assert_eq!(
+            ban("Botting.", Some(now)).message("Banned."),
+            "Banned. Botting. (until 2027-01-15 08:00 UTC)"
+        );
+    }
 }
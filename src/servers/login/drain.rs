@@ -0,0 +1,68 @@
+//! Drain mode: refuse new logins, leave live players alone.
+//!
+//! For rolling updates. While draining, the login server answers each new
+//! login with `LGN_DRAINING` ("please use another server") instead of
+//! forwarding it to the char server; players already in the world keep
+//! playing. Unlike the `Maintenance` table flag, GMs are refused too and
+//! nothing is written to the database, so it affects only this process.
+//!
+//! Toggle with `SIGUSR1` (start) / `SIGUSR2` (stop), or start draining with
+//! `--drain`. While draining the online count is polled and a line logged
+//! once it reaches zero, meaning the server can be stopped. Only characters
+//! that logged in through this stack (`ChaServer` = `server_id`) count, so
+//! players on other servers sharing the database don't hold the drain open.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Default)]
+pub struct Drain {
+    on: AtomicBool,
+}
+
+impl Drain {
+    pub fn is_draining(&self) -> bool {
+        self.on.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the state changed.
+    pub fn set(&self, on: bool) -> bool {
+        self.on.swap(on, Ordering::Relaxed) != on
+    }
+}
+
+/// Tracks the online count during a drain so "empty" is reported once per
+/// drain (again only if players return and leave).
+#[derive(Debug, Default)]
+pub struct EmptyWatch {
+    reported: bool,
+}
+
+impl EmptyWatch {
+    /// Feed the latest count; true exactly when it should be reported empty.
+    pub fn observe(&mut self, draining: bool, online: u64) -> bool {
+        if !draining || online > 0 {
+            self.reported = false;
+            return false;
+        }
+        !std::mem::replace(&mut self.reported, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_reported_once_per_drain() {
+        let d = Drain::default();
+        assert!(d.set(true) && !d.set(true) && d.is_draining());
+
+        let mut w = EmptyWatch::default();
+        assert!(!w.observe(true, 3));
+        assert!(w.observe(true, 0));
+        assert!(!w.observe(true, 0));
+        assert!(!w.observe(true, 1));
+        assert!(w.observe(true, 0));
+        assert!(!w.observe(false, 0) && w.observe(true, 0));
+    }
+}
@@ -1,62 +1,144 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use crate::network::Stream;
 use tokio::sync::mpsc;
 
 use super::{
     LoginState, CharResponse,
-    LGN_WRONGPASS, LGN_WRONGUSER, LGN_USEREXIST, LGN_ERRDB,
-    LGN_NEWCHAR, LGN_CHGPASS, LGN_DBLLOGIN, LGN_BANNED, LGN_ERRSERVER,
+    LGN_WRONGPASS, LGN_WRONGUSER, LGN_USEREXIST, LGN_ERRDB, LGN_ERRPASS,
+    LGN_NEWCHAR, LGN_CHGPASS, LGN_DBLLOGIN, LGN_BANNED, LGN_ERRSERVER, LGN_NEWSUBNET,
+    LGN_CHARREJECT,
 };
-use super::packet::{build_message, build_intif_auth_response};
-use crate::network::crypt::{set_packet_indexes, tk_crypt_static};
+use super::packet::{
+    build_message, build_intif_auth_response, build_intif_reject, build_intif_reject_text,
+    INTIF_REJECT_AUTH, INTIF_REJECT_BUSY, INTIF_REJECT_MALFORMED,
+};
+use crate::network::crypt::{set_packet_indexes, tk_crypt_static, Cipher};
+use crate::network::integrity::{MacKey, Sealer, Verifier};
+use crate::network::protocol::{ensure_len, ProtocolError};
 
 const PKT_LENS: [usize; 6] = [69, 5, 5, 27, 5, 0];
 
-pub async fn promote_to_charserver(state: Arc<LoginState>, mut stream: TcpStream, first: Vec<u8>) {
-    // Reject if char server already connected
-    {
-        let tx = state.char_tx.lock().await;
-        if tx.is_some() {
-            let _ = stream.write_all(&build_intif_auth_response(false)).await;
-            return;
-        }
-    }
+/// Keep-alive the char server sends every 10 s ([0xFF, 0x01]).
+const CMD_KEEPALIVE: u16 = 0x01FF;
 
-    if first.len() < 69 {
-        let _ = stream.write_all(&build_intif_auth_response(false)).await;
-        return;
+/// Decrypt the 69-byte char-server auth packet and return (login_id, login_pw).
+///
+/// The char server encrypts the packet with `tk_crypt_static` before sending.
+fn parse_char_auth(first: &[u8], xor_key: &[u8]) -> Result<(String, String), ProtocolError> {
+    ensure_len(0x00FF, first, PKT_LENS[0])?;
+    let mut first = first[..PKT_LENS[0]].to_vec();
+    tk_crypt_static(&mut first, xor_key);
+    let field = |b: &[u8]| std::str::from_utf8(b).unwrap_or("").trim_end_matches('\0').to_owned();
+    Ok((field(&first[5..37]), field(&first[37..69])))
+}
+
+async fn authenticate_char(
+    state: &LoginState,
+    stream: &mut impl Stream,
+    verifier: &mut Verifier,
+    first: &[u8],
+) -> Result<(), ProtocolError> {
+    let config = state.config.get();
+    let (login_id, login_pw) = parse_char_auth(first, config.xor_key.as_bytes())?;
+    // The tag covers the auth packet as sent, i.e. before XOR decryption.
+    if !verifier.check(stream, first).await {
+        return Err(verifier.failure(0x00FF));
     }
+    if login_id != config.login_id || login_pw != config.login_pw {
+        return Err(ProtocolError::AuthFailed(format!("id={}", login_id)));
+    }
+    Ok(())
+}
 
-    // Decrypt: char_server encrypts the packet with tk_crypt_static before sending
-    let mut first = first;
-    tk_crypt_static(&mut first, state.config.xor_key.as_bytes());
+/// Why a char server could not be linked. Every variant except a bad MAC
+/// has already been answered with a rejecting 0x1000 and a 0x1005 reason.
+#[derive(Debug, thiserror::Error)]
+pub enum PromoteError {
+    #[error("another char server is already linked")]
+    AlreadyLinked,
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+}
 
-    let login_id = std::str::from_utf8(&first[5..37]).unwrap_or("").trim_end_matches('\0');
-    let login_pw = std::str::from_utf8(&first[37..69]).unwrap_or("").trim_end_matches('\0');
+impl PromoteError {
+    /// Status byte for the rejecting 0x1000, or None to drop silently.
+    fn reject_code(&self) -> Option<u8> {
+        match self {
+            Self::AlreadyLinked => Some(INTIF_REJECT_BUSY),
+            // A bad tag gets no reply: the peer may not be a char server at all.
+            Self::Protocol(ProtocolError::BadMac(_) | ProtocolError::BadChecksum(_)) => None,
+            Self::Protocol(ProtocolError::AuthFailed(_)) => Some(INTIF_REJECT_AUTH),
+            Self::Protocol(_) => Some(INTIF_REJECT_MALFORMED),
+        }
+    }
+}
 
-    if login_id != state.config.login_id || login_pw != state.config.login_pw {
-        let _ = stream.write_all(&build_intif_auth_response(false)).await;
-        tracing::warn!("[login] [char_auth_failed] id={}", login_id);
-        return;
+/// Send the 0x1000 reject and its 0x1005 reason text for `err`.
+async fn send_reject(state: &LoginState, stream: &mut impl Stream, sealer: &mut Sealer, err: &PromoteError) {
+    let Some(code) = err.reject_code() else { return };
+    let prefix = &state.messages.0[LGN_CHARREJECT];
+    let text = if prefix.is_empty() { err.to_string() } else { format!("{}: {}", prefix, err) };
+    for mut frame in [build_intif_reject(code), build_intif_reject_text(&text)] {
+        sealer.seal(&mut frame);
+        if stream.write_all(&frame).await.is_err() {
+            return;
+        }
     }
+    let _ = stream.flush().await;
+}
 
-    let _ = stream.write_all(&build_intif_auth_response(true)).await;
-    tracing::info!("[login] [char_server_connect] Char Server accepted.");
+/// Authenticate a char server on `stream` and serve the link until it drops.
+///
+/// Returns `Err` without registering anything if the link is refused; the
+/// peer has been sent the reason. `Ok` means the link ran and later closed.
+pub async fn promote_to_charserver(
+    state: Arc<LoginState>,
+    mut stream: impl Stream,
+    peer: SocketAddr,
+    first: Vec<u8>,
+) -> Result<(), PromoteError> {
+    let mac = MacKey::from_config(&state.config.get());
+    let mut sealer = mac.sealer();
+    let mut verifier = mac.verifier();
 
+    let auth = authenticate_char(&state, &mut stream, &mut verifier, &first).await;
+    // Check and claim the link under one lock so two char servers racing
+    // through auth cannot both register.
     let (tx, mut rx) = mpsc::channel::<Vec<u8>>(64);
-    {
-        let mut ct = state.char_tx.lock().await;
-        *ct = Some(tx);
+    let claimed = match auth {
+        Err(e) => Err(PromoteError::from(e)),
+        Ok(()) => {
+            let mut ct = state.char_tx.lock().await;
+            if ct.is_some() {
+                Err(PromoteError::AlreadyLinked)
+            } else {
+                *ct = Some(tx);
+                Ok(())
+            }
+        }
+    };
+    if let Err(e) = claimed {
+        send_reject(&state, &mut stream, &mut sealer, &e).await;
+        return Err(e);
     }
 
-    let (mut read_half, mut write_half) = stream.into_split();
+    let mut resp = build_intif_auth_response(true);
+    sealer.seal(&mut resp);
+    let _ = stream.write_all(&resp).await;
+    tracing::info!("[login] [char_server_connect] Char Server accepted peer={}", peer);
+
+    let (read_half, mut write_half) = tokio::io::split(stream);
+    // Frames are read a few bytes at a time; buffer so each is not a syscall.
+    let mut read_half = BufReader::with_capacity(state.config.get().interserver_read_buffer, read_half);
 
     // Spawn writer: forwards messages from client tasks to char server
     let writer = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
+        while let Some(mut msg) = rx.recv().await {
             tracing::debug!("[login] [intif_writer] writing {} bytes to char server: {:02X?}",
                 msg.len(), &msg[..msg.len().min(20)]);
+            sealer.seal(&mut msg);
             if write_half.write_all(&msg).await.is_err() {
                 tracing::error!("[login] [intif_writer] write failed, breaking");
                 break;
@@ -66,27 +148,20 @@ pub async fn promote_to_charserver(state: Arc<LoginState>, mut stream: TcpStream
 
     // Reader loop: receives char server responses and routes to pending client tasks
     loop {
-        let mut cmd_bytes = [0u8; 2];
-        if read_half.read_exact(&mut cmd_bytes).await.is_err() {
-            break;
-        }
-        let cmd = u16::from_le_bytes(cmd_bytes);
-
-        let idx = (cmd as usize).wrapping_sub(0x2000);
-        if idx >= PKT_LENS.len() || PKT_LENS[idx] == 0 {
-            tracing::warn!("[login] [intif_unknown_cmd] cmd={:04X}", cmd);
-            continue;
-        }
-
-        let pkt_len = PKT_LENS[idx];
-        let mut rest = vec![0u8; pkt_len - 2];
-        if read_half.read_exact(&mut rest).await.is_err() {
-            break;
-        }
-
-        let mut pkt = Vec::with_capacity(pkt_len);
-        pkt.extend_from_slice(&cmd_bytes);
-        pkt.extend_from_slice(&rest);
+        let pkt = match read_char_frame(&mut read_half, &mut verifier).await {
+            Ok(Some(pkt)) => pkt,
+            Ok(None) => continue,
+            // With MACs on, the tag position is unknowable; resync is impossible.
+            Err(e @ ProtocolError::UnknownCommand(_)) if !mac.is_enabled() => {
+                e.log("[login] [intif]");
+                continue;
+            }
+            Err(e) => {
+                e.log(&format!("[login] [intif] peer={}", peer));
+                break;
+            }
+        };
+        let cmd = u16::from_le_bytes([pkt[0], pkt[1]]);
 
         let session_id = u16::from_le_bytes([pkt[2], pkt[3]]);
         tracing::debug!("[login] [intif_recv] cmd={:04X} session={} pkt_len={} raw={:02X?}",
@@ -113,70 +188,117 @@ pub async fn promote_to_charserver(state: Arc<LoginState>, mut stream: TcpStream
     }
     writer.abort();
     tracing::info!("[login] [char_server_disconnect] Char Server connection lost.");
+    Ok(())
+}
+
+/// Read one frame from the char server and verify its MAC.
+/// Returns `Ok(None)` for keep-alives.
+async fn read_char_frame<R: AsyncRead + Unpin>(
+    r: &mut R,
+    verifier: &mut Verifier,
+) -> Result<Option<Vec<u8>>, ProtocolError> {
+    let mut cmd_bytes = [0u8; 2];
+    r.read_exact(&mut cmd_bytes).await?;
+    let cmd = u16::from_le_bytes(cmd_bytes);
+
+    if cmd == CMD_KEEPALIVE {
+        if !verifier.check(r, &cmd_bytes).await {
+            return Err(verifier.failure(cmd));
+        }
+        return Ok(None);
+    }
+
+    let idx = (cmd as usize).wrapping_sub(0x2000);
+    if idx >= PKT_LENS.len() || PKT_LENS[idx] == 0 {
+        return Err(ProtocolError::UnknownCommand(cmd));
+    }
+
+    let mut pkt = vec![0u8; PKT_LENS[idx]];
+    pkt[..2].copy_from_slice(&cmd_bytes);
+    r.read_exact(&mut pkt[2..]).await?;
+
+    if !verifier.check(r, &pkt).await {
+        return Err(verifier.failure(cmd));
+    }
+    Ok(Some(pkt))
 }
 
 pub async fn dispatch_char_response(
-    stream: &mut TcpStream,
+    stream: &mut impl Stream,
     state: &LoginState,
     resp: &CharResponse,
-) {
+    cipher: &dyn Cipher,
+) -> Result<(), ProtocolError> {
     let pkt = &resp.data;
-    let xk = state.config.xor_key.as_bytes();
 
-    if pkt.len() < 2 { return; }
+    ensure_len(0, pkt, 2)?;
     let cmd = u16::from_le_bytes([pkt[0], pkt[1]]);
 
     match cmd {
         0x2001 => {
-            if pkt.len() < 5 { return; }
+            ensure_len(cmd, pkt, 5)?;
             match pkt[4] {
-                0x01 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_USEREXIST], xk)).await; }
-                0x00 => { let _ = stream.write_all(&build_message(0x00, "\x00", xk)).await; }
-                _    => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRDB], xk)).await; }
+                0x01 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_USEREXIST], cipher)).await; }
+                0x00 => { let _ = stream.write_all(&build_message(0x00, "\x00", cipher)).await; }
+                _    => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRDB], cipher)).await; }
             }
         }
         0x2002 => {
-            if pkt.len() < 5 { return; }
+            ensure_len(cmd, pkt, 5)?;
             match pkt[4] {
-                0x01 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_USEREXIST], xk)).await; }
-                0x00 => { let _ = stream.write_all(&build_message(0x00, &state.messages.0[LGN_NEWCHAR], xk)).await; }
-                _    => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRDB], xk)).await; }
+                0x01 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_USEREXIST], cipher)).await; }
+                0x00 => { let _ = stream.write_all(&build_message(0x00, &state.messages.0[LGN_NEWCHAR], cipher)).await; }
+                0x03 => { let _ = stream.write_all(&build_message(0x05, &state.messages.0[LGN_ERRPASS], cipher)).await; }
+                _    => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRDB], cipher)).await; }
             }
         }
         0x2003 => {
-            if pkt.len() < 27 { return; }
+            ensure_len(cmd, pkt, 27)?;
             let name_2003 = std::str::from_utf8(&pkt[5..21]).unwrap_or("?").trim_end_matches('\0');
             let ip_bytes = &pkt[21..25];
             let port_bytes = &pkt[25..27];
             tracing::debug!("[login] [intif_connectconfirm] result={:#04X} name={} ip_bytes={:02X?} port_bytes={:02X?}",
                 pkt[4], name_2003, ip_bytes, port_bytes);
+            use crate::metrics::{AuthFailure, METRICS};
             match pkt[4] {
-                0x00 => send_auth_success(stream, state, pkt).await,
-                0x01 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRDB], xk)).await; }
-                0x02 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_WRONGUSER], xk)).await; }
-                0x03 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_WRONGPASS], xk)).await; }
-                0x04 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_BANNED], xk)).await; }
-                0x05 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRSERVER], xk)).await; }
-                0x06 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_DBLLOGIN], xk)).await; }
+                0x00 => METRICS.auth_ok(),
+                0x01 => METRICS.auth_failed(AuthFailure::ServerError),
+                0x02 => METRICS.auth_failed(AuthFailure::WrongUser),
+                0x03 => METRICS.auth_failed(AuthFailure::WrongPassword),
+                0x04 => METRICS.auth_failed(AuthFailure::Banned),
+                0x05 => METRICS.auth_failed(AuthFailure::ServerError),
+                0x06 => METRICS.auth_failed(AuthFailure::DoubleLogin),
+                0x07 => METRICS.auth_failed(AuthFailure::NewSubnet),
+                _ => {}
+            }
+            match pkt[4] {
+                0x00 => send_auth_success(stream, state, pkt, cipher).await,
+                0x01 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRDB], cipher)).await; }
+                0x02 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_WRONGUSER], cipher)).await; }
+                0x03 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_WRONGPASS], cipher)).await; }
+                0x04 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_BANNED], cipher)).await; }
+                0x05 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRSERVER], cipher)).await; }
+                0x06 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_DBLLOGIN], cipher)).await; }
+                0x07 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_NEWSUBNET], cipher)).await; }
                 _    => tracing::warn!("[login] [intif_connectconfirm] unknown result={}", pkt[4]),
             }
         }
         0x2004 => {
-            if pkt.len() < 5 { return; }
+            ensure_len(cmd, pkt, 5)?;
             match pkt[4] {
-                0x00 => { let _ = stream.write_all(&build_message(0x00, &state.messages.0[LGN_CHGPASS], xk)).await; }
-                0x01 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRDB], xk)).await; }
-                0x02 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_WRONGUSER], xk)).await; }
-                0x03 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_WRONGPASS], xk)).await; }
+                0x00 => { let _ = stream.write_all(&build_message(0x00, &state.messages.0[LGN_CHGPASS], cipher)).await; }
+                0x01 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_ERRDB], cipher)).await; }
+                0x02 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_WRONGUSER], cipher)).await; }
+                0x03 => { let _ = stream.write_all(&build_message(0x03, &state.messages.0[LGN_WRONGPASS], cipher)).await; }
                 _    => {}
             }
         }
-        _ => tracing::warn!("[login] [dispatch_char_response] unknown cmd={:04X}", cmd),
+        _ => return Err(ProtocolError::UnknownCommand(cmd)),
     }
+    Ok(())
 }
 
-async fn send_auth_success(stream: &mut TcpStream, state: &LoginState, pkt: &[u8]) {
-    let xk = state.config.xor_key.as_bytes();
+async fn send_auth_success(stream: &mut impl Stream, state: &LoginState, pkt: &[u8], cipher: &dyn Cipher) {
     tracing::debug!("[login] [send_auth_success] sending redirect to client");
 
     // Packet 1: session-ok (11 bytes: 8 payload + 3 index bytes from set_packet_indexes)
@@ -187,7 +309,7 @@ async fn send_auth_success(stream: &mut TcpStream, state: &LoginState, pkt: &[u8
     buf1[4] = 0x17;
     buf1[5] = 0x00; buf1[6] = 0x00; buf1[7] = 0x00;
     set_packet_indexes(&mut buf1);
-    tk_crypt_static(&mut buf1, xk);
+    cipher.encrypt_packet(&mut buf1);
     if let Err(e) = stream.write_all(&buf1[..11]).await {
         tracing::error!("[login] [send_auth_success] buf1 write error: {}", e);
         return;
@@ -213,7 +335,8 @@ async fn send_auth_success(stream: &mut TcpStream, state: &LoginState, pkt: &[u8
     // session_id is written after char_name as SWAP32(session_id) = BE u32.
     let char_name_len = char_name.len();
     let name_len_field = char_name_len + 16; // matches C: strlen(thing) + 16
-    let xk_bytes = state.config.xor_key.as_bytes();
+    let config = state.config.get();
+    let xk_bytes = config.xor_key.as_bytes();
     // C uses strcpy which copies all chars; [13..22) = full xor_key (9 bytes).
     // Position [22] is then overwritten by char_name_len, matching C behavior.
     let xk_copy_len = xk_bytes.len().min(9);
@@ -265,6 +388,34 @@ mod tests {
         assert_eq!(session_id, 5);
     }
 
+    #[test]
+    fn test_truncated_auth_packet_is_bad_frame() {
+        let err = parse_char_auth(&[0xAA, 0x00, 0x42, 0xFF], b"secret").unwrap_err();
+        assert!(matches!(err, ProtocolError::BadFrame { need: 69, got: 4, .. }));
+    }
+
+    #[test]
+    fn test_parse_char_auth_roundtrip() {
+        let mut pkt = vec![0u8; 69];
+        pkt[0] = 0xAA; pkt[1] = 0x00; pkt[2] = 0x42; pkt[3] = 0xFF;
+        pkt[5..10].copy_from_slice(b"login");
+        pkt[37..41].copy_from_slice(b"pass");
+        tk_crypt_static(&mut pkt, b"secret");
+        let (id, pw) = parse_char_auth(&pkt, b"secret").unwrap();
+        assert_eq!((id.as_str(), pw.as_str()), ("login", "pass"));
+    }
+
+    #[tokio::test]
+    async fn test_read_char_frame_errors() {
+        let mut v = crate::network::integrity::MacKey::default().verifier();
+        let mut unknown: &[u8] = &[0x09, 0x20, 0, 0];
+        assert!(matches!(read_char_frame(&mut unknown, &mut v).await, Err(ProtocolError::UnknownCommand(0x2009))));
+        let mut short: &[u8] = &[0x03, 0x20, 0, 0, 0];
+        assert!(matches!(read_char_frame(&mut short, &mut v).await, Err(ProtocolError::ShortRead(_))));
+        let mut keepalive: &[u8] = &[0xFF, 0x01];
+        assert!(matches!(read_char_frame(&mut keepalive, &mut v).await, Ok(None)));
+    }
+
     #[test]
     fn test_packet_len_table() {
         assert_eq!(PKT_LENS[0x2003 - 0x2000], 27);
@@ -0,0 +1,82 @@
+//! Failed-login lockout per client IP.
+//!
+//! Each wrong password from an IP is counted; once the count reaches
+//! `login_lockout_threshold`, new connections from that IP are refused. The
+//! count resets once `login_lockout_window_secs` pass after the IP's last
+//! failure (so a shared NAT recovers on its own), and a successful login
+//! clears it at once. Stale entries are swept periodically so the table only
+//! holds recent offenders.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// IP → (failures, time of the last one)
+pub type Table = HashMap<u32, (u32, Instant)>;
+
+/// Whether `ip` has `threshold` recent failures. An entry older than
+/// `window` is dropped instead.
+pub fn is_locked(table: &mut Table, ip: u32, threshold: u32, window: Duration, now: Instant) -> bool {
+    match table.get(&ip) {
+        Some(&(_, last)) if now.duration_since(last) >= window => {
+            table.remove(&ip);
+            false
+        }
+        Some(&(count, _)) => count >= threshold,
+        None => false,
+    }
+}
+
+/// Count a wrong password from `ip`, starting over if its last failure is
+/// older than `window`. Returns the new count.
+pub fn record_failure(table: &mut Table, ip: u32, window: Duration, now: Instant) -> u32 {
+    let entry = table.entry(ip).or_insert((0, now));
+    if now.duration_since(entry.1) >= window {
+        entry.0 = 0;
+    }
+    entry.0 += 1;
+    entry.1 = now;
+    entry.0
+}
+
+/// Drop entries whose last failure is at least `window` old; returns how many.
+pub fn sweep(table: &mut Table, window: Duration, now: Instant) -> usize {
+    let before = table.len();
+    table.retain(|_, &mut (_, last)| now.duration_since(last) < window);
+    before - table.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_decays_after_window() {
+        let window = Duration::from_secs(900);
+        let t0 = Instant::now();
+        let mut table = Table::new();
+        for n in 1..=3 {
+            assert_eq!(record_failure(&mut table, 7, window, t0), n);
+        }
+        assert!(is_locked(&mut table, 7, 3, window, t0 + Duration::from_secs(60)));
+        assert!(!is_locked(&mut table, 8, 3, window, t0));
+
+        // A failure after the window starts a fresh count.
+        assert_eq!(record_failure(&mut table, 7, window, t0 + window), 1);
+        assert!(!is_locked(&mut table, 7, 3, window, t0 + window));
+
+        record_failure(&mut table, 9, window, t0);
+        assert!(!is_locked(&mut table, 9, 1, window, t0 + window));
+        assert!(!table.contains_key(&9), "a stale entry is dropped on check");
+    }
+
+    #[test]
+    fn test_sweep_evicts_stale_entries() {
+        let window = Duration::from_secs(60);
+        let t0 = Instant::now();
+        let mut table = Table::new();
+        record_failure(&mut table, 1, window, t0);
+        record_failure(&mut table, 2, window, t0 + Duration::from_secs(30));
+        assert_eq!(sweep(&mut table, window, t0 + Duration::from_secs(60)), 1);
+        assert!(table.contains_key(&2) && !table.contains_key(&1));
+    }
+}
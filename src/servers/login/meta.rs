@@ -1,14 +1,14 @@
 use std::fs;
 use std::path::Path;
 use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
+use crate::network::Stream;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use flate2::Crc;
 use std::io::Write;
 
 use super::LoginState;
-use crate::network::crypt::{set_packet_indexes, tk_crypt_static};
+use crate::network::crypt::{set_packet_indexes, Cipher};
 
 fn compute_crc32(data: &[u8]) -> u32 {
     let mut crc = Crc::new();
@@ -22,16 +22,16 @@ fn zlib_compress(data: &[u8]) -> Vec<u8> {
     encoder.finish().unwrap_or_default()
 }
 
-pub async fn dispatch_meta(stream: &mut TcpStream, pkt: &[u8], state: &LoginState) {
+pub async fn dispatch_meta(stream: &mut impl Stream, pkt: &[u8], state: &LoginState, cipher: &dyn Cipher) {
     if pkt.len() < 6 { return; }
     match pkt[5] {
-        0 => send_meta_file(stream, pkt, state).await,
-        1 => send_meta_list(stream, state).await,
+        0 => send_meta_file(stream, pkt, state, cipher).await,
+        1 => send_meta_list(stream, state, cipher).await,
         _ => {}
     }
 }
 
-async fn send_meta_file(stream: &mut TcpStream, pkt: &[u8], state: &LoginState) {
+async fn send_meta_file(stream: &mut impl Stream, pkt: &[u8], state: &LoginState, cipher: &dyn Cipher) {
     if pkt.len() < 7 { return; }
     let fname_len = pkt[6] as usize;
     if pkt.len() < 7 + fname_len { return; }
@@ -48,7 +48,7 @@ async fn send_meta_file(stream: &mut TcpStream, pkt: &[u8], state: &LoginState)
         return;
     }
 
-    let meta_dir = match fs::canonicalize(&state.config.meta_dir) {
+    let meta_dir = match fs::canonicalize(&state.config.get().meta_dir) {
         Ok(d) => d,
         Err(_) => return,
     };
@@ -97,13 +97,13 @@ async fn send_meta_file(stream: &mut TcpStream, pkt: &[u8], state: &LoginState)
     buf[2] = (payload & 0xFF) as u8;
 
     set_packet_indexes(&mut buf);
-    tk_crypt_static(&mut buf, state.config.xor_key.as_bytes());
+    cipher.encrypt_packet(&mut buf);
     let _ = stream.write_all(&buf[..total + 3]).await;
 }
 
-async fn send_meta_list(stream: &mut TcpStream, state: &LoginState) {
-    let xk = state.config.xor_key.as_bytes();
-    let files = &state.config.meta;
+async fn send_meta_list(stream: &mut impl Stream, state: &LoginState, cipher: &dyn Cipher) {
+    let config = state.config.get();
+    let files = &config.meta;
 
     let entry_size: usize = files.iter().map(|f| 1 + f.len() + 4).sum();
     let payload = 3 + 2 + entry_size; // [0x6F][??][mode=1][count_2B] + entries
@@ -122,7 +122,7 @@ async fn send_meta_list(stream: &mut TcpStream, state: &LoginState) {
 
     let mut off = 8;
     for fname in files {
-        let path = format!("{}{}", state.config.meta_dir, fname);
+        let path = format!("{}{}", config.meta_dir, fname);
         let data = fs::read(&path).unwrap_or_default();
         let crc = compute_crc32(&data);
 
@@ -135,7 +135,7 @@ async fn send_meta_list(stream: &mut TcpStream, state: &LoginState) {
     }
 
     set_packet_indexes(&mut buf);
-    tk_crypt_static(&mut buf, xk);
+    cipher.encrypt_packet(&mut buf);
     let _ = stream.write_all(&buf[..total + 3]).await;
 }
 
@@ -1,6 +1,8 @@
 pub mod client;
 pub mod db;
+pub mod drain;
 pub mod interserver;
+pub mod lockout;
 pub mod meta;
 pub mod packet;
 
@@ -13,12 +15,14 @@ use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use tokio::net::{TcpListener, TcpStream};
 use sqlx::MySqlPool;
-use crate::config::ServerConfig;
+use crate::config::{LiveConfig, ServerConfig};
+use crate::network::Stream;
+use crate::network::crypt::{listener_cipher, Cipher};
 use crate::servers::login::packet::read_client_packet;
 
-/// The 11 localised error messages, indexed by LGN_* constants.
+/// The localised login messages, indexed by LGN_* constants.
 #[derive(Debug, Clone, Default)]
-pub struct LoginMessages(pub [String; 11]);
+pub struct LoginMessages(pub [String; 14]);
 
 // Message key indices — mirror C enum in login_server.h
 pub const LGN_ERRSERVER: usize = 0;
@@ -32,6 +36,12 @@ pub const LGN_NEWCHAR:   usize = 7;
 pub const LGN_CHGPASS:   usize = 8;
 pub const LGN_DBLLOGIN:  usize = 9;
 pub const LGN_BANNED:    usize = 10;
+// Rust-only: no C counterpart.
+pub const LGN_NEWSUBNET: usize = 11;
+/// Prefix of the reject text sent to a char server that fails to link.
+pub const LGN_CHARREJECT: usize = 12;
+/// Sent instead of logging in while the server drains (see `drain`).
+pub const LGN_DRAINING: usize = 13;
 
 /// Parses a `key: value` lang file (same format as C `lang_read`).
 /// Lines starting with `//` are comments. Unknown keys are silently ignored.
@@ -56,6 +66,9 @@ pub fn parse_lang_file(content: &str) -> Result<LoginMessages> {
                 "LGN_CHGPASS"   => msgs.0[LGN_CHGPASS]   = val,
                 "LGN_DBLLOGIN"  => msgs.0[LGN_DBLLOGIN]  = val,
                 "LGN_BANNED"    => msgs.0[LGN_BANNED]     = val,
+                "LGN_NEWSUBNET" => msgs.0[LGN_NEWSUBNET] = val,
+                "LGN_CHARREJECT" => msgs.0[LGN_CHARREJECT] = val,
+                "LGN_DRAINING"  => msgs.0[LGN_DRAINING]  = val,
                 _ => {}
             }
         }
@@ -63,6 +76,9 @@ pub fn parse_lang_file(content: &str) -> Result<LoginMessages> {
     Ok(msgs)
 }
 
+/// "CONNECTED SERVER", sent to every new connection before its first packet.
+const CONNECT_BANNER: &[u8] = b"\xAA\x00\x13\x7E\x1B\x43\x4F\x4E\x4E\x45\x43\x54\x45\x44\x20\x53\x45\x52\x56\x45\x52\x0A";
+
 /// Char server response routed back to a waiting client task.
 pub struct CharResponse {
     pub session_id: u16,
@@ -71,59 +87,85 @@ pub struct CharResponse {
 
 pub struct LoginState {
     pub db: Option<MySqlPool>,
-    pub config: ServerConfig,
+    /// Live config, swapped on SIGHUP (see `servers::reload_on_hangup`)
+    pub config: Arc<LiveConfig>,
     pub messages: LoginMessages,
-    pub lockout: Mutex<HashMap<u32, u32>>,  // ip → fail count
+    /// Recent wrong passwords per IP (see `lockout`)
+    pub lockout: Mutex<lockout::Table>,
     pub pending: Mutex<HashMap<u16, tokio::sync::mpsc::Sender<CharResponse>>>,
     pub char_tx: Mutex<Option<tokio::sync::mpsc::Sender<Vec<u8>>>>,
+    pub drain: drain::Drain,
+    /// Who may connect as a char server (`interserver_allow`)
+    pub interserver_acl: crate::network::acl::Acl,
+    /// Client cipher on `login_port` and the WebSocket listener (`cipher`)
+    pub cipher: Arc<dyn Cipher>,
 }
 
 impl LoginState {
     pub fn new(db: MySqlPool, config: ServerConfig, messages: LoginMessages) -> Self {
+        let interserver_acl = crate::network::acl::Acl::parse(&config.interserver_allow)
+            .expect("interserver_allow is checked by ServerConfig::validate");
+        let cipher = listener_cipher(&config, &config.cipher);
         Self {
             db: Some(db),
-            config,
+            config: Arc::new(LiveConfig::new(config)),
             messages,
             lockout: Mutex::new(HashMap::new()),
             pending: Mutex::new(HashMap::new()),
             char_tx: Mutex::new(None),
+            drain: drain::Drain::default(),
+            interserver_acl,
+            cipher,
         }
     }
 
+    /// No database, the harness config; see `servers::testing`.
+    #[cfg(any(test, feature = "testing"))]
     pub fn test_only() -> Self {
-        let config: ServerConfig = serde_yaml::from_str(r#"
-sql_ip: "127.0.0.1"
-sql_id: "test"
-sql_pw: "test"
-sql_db: "testdb"
-login_id: "loginid"
-login_pw: "loginpw"
-login_ip: "127.0.0.1"
-char_id: "charid"
-char_pw: "charpw"
-char_ip: "127.0.0.1"
-map_ip: "127.0.0.1"
-xor_key: "test"
-start_point:
-  m: 0
-  x: 1
-  y: 1
-"#).expect("test config parse failed");
+        let config = crate::servers::testing::test_config();
+        let cipher = listener_cipher(&config, &config.cipher);
         Self {
             db: None,
-            config,
+            config: Arc::new(LiveConfig::new(config)),
             messages: LoginMessages::default(),
             lockout: Mutex::new(HashMap::new()),
             pending: Mutex::new(HashMap::new()),
             char_tx: Mutex::new(None),
+            drain: drain::Drain::default(),
+            interserver_acl: crate::network::acl::Acl::default(),
+            cipher,
+        }
+    }
+
+    /// Read the next packet from `stream`, giving up once it has been silent
+    /// for `idle_timeout_secs` (0 waits forever).
+    pub(crate) async fn read_idle(&self, stream: &mut impl Stream) -> Result<Vec<u8>> {
+        match self.config.get().idle_timeout_secs {
+            0 => read_client_packet(stream).await,
+            secs => tokio::time::timeout(std::time::Duration::from_secs(secs), read_client_packet(stream))
+                .await
+                .map_err(|_| anyhow::anyhow!("idle for {}s", secs))?,
         }
     }
 
-    pub async fn handle_new_connection(
+    pub async fn handle_new_connection(state: Arc<Self>, stream: TcpStream, peer: SocketAddr) {
+        // Use the OS socket fd as session_id, matching the C login server where
+        // session_id == the client's file descriptor (typically 4, 5, 6, ...).
+        let session_id = stream.as_raw_fd() as u16;
+        let cipher = Arc::clone(&state.cipher);
+        Self::handle_stream(state, stream, peer, session_id, cipher).await;
+    }
+
+    /// Serve one connection over any byte stream (see `servers::testing`),
+    /// speaking `cipher` to a game client.
+    pub async fn handle_stream(
         state: Arc<Self>,
-        mut stream: TcpStream,
+        mut stream: impl Stream,
         peer: SocketAddr,
+        session_id: u16,
+        cipher: Arc<dyn Cipher>,
     ) {
+        let _open = crate::metrics::METRICS.connection();
         let ip_u32 = match peer.ip() {
             std::net::IpAddr::V4(v4) => u32::from(v4),
             _ => return,
@@ -132,31 +174,37 @@ start_point:
         // Check IP ban
         if let Some(pool) = &state.db {
             let ip_str = format!("{}", peer.ip());
-            if db::is_ip_banned(pool, &ip_str).await {
-                tracing::info!("[login] [banned] ip={}", ip_str);
+            if let Some(ban) = db::is_ip_banned(pool, &ip_str).await {
+                tracing::info!("[login] [banned] ip={} reason={:?} expires={:?}", ip_str, ban.reason, ban.expires);
+                crate::metrics::METRICS.auth_failed(crate::metrics::AuthFailure::Banned);
+                // Tell the client why before closing.
+                if stream.write_all(CONNECT_BANNER).await.is_ok() {
+                    let text = ban.message(&state.messages.0[LGN_BANNED]);
+                    let _ = stream.write_all(&packet::build_message(0x03, &text, &*cipher)).await;
+                }
                 return;
             }
         }
 
         // Check lockout
-        {
-            let lock = state.lockout.lock().await;
-            if lock.get(&ip_u32).copied().unwrap_or(0) >= 10 {
-                tracing::info!("[login] [lockout] ip={}", peer.ip());
-                return;
-            }
+        if state.is_locked_out(ip_u32).await {
+            tracing::info!("[login] [lockout] ip={}", peer.ip());
+            crate::metrics::METRICS.auth_failed(crate::metrics::AuthFailure::Lockout);
+            return;
         }
 
         // Send connect banner (mirrors C clif_accept ok branch)
-        let banner: &[u8] = b"\xAA\x00\x13\x7E\x1B\x43\x4F\x4E\x4E\x45\x43\x54\x45\x44\x20\x53\x45\x52\x56\x45\x52\x0A";
-        if stream.write_all(banner).await.is_err() {
+        if stream.write_all(CONNECT_BANNER).await.is_err() {
             return;
         }
 
         // Read first packet to determine role
-        let first = match read_client_packet(&mut stream).await {
+        let first = match state.read_idle(&mut stream).await {
             Ok(p) => p,
-            Err(_) => return,
+            Err(e) => {
+                tracing::info!("[login] [client_disconnect] session={} peer={} reason={}", session_id, peer, e);
+                return;
+            }
         };
 
         if first.len() < 4 {
@@ -165,18 +213,107 @@ start_point:
 
         let cmd = first[3];
         if cmd == 0xFF {
-            interserver::promote_to_charserver(state, stream, first).await;
+            if !state.interserver_acl.allows(peer.ip()) {
+                tracing::warn!("[login] [char_auth_failed] peer={} not in interserver_allow", peer);
+                return;
+            }
+            match interserver::promote_to_charserver(state, stream, peer, first).await {
+                Ok(()) => {}
+                Err(interserver::PromoteError::Protocol(e)) => {
+                    e.log(&format!("[login] [char_auth_failed] peer={}", peer));
+                }
+                Err(e) => tracing::warn!("[login] [char_auth_failed] peer={} {}", peer, e),
+            }
         } else {
-            // Use the OS socket fd as session_id, matching the C login server where
-            // session_id == the client's file descriptor (typically 4, 5, 6, ...).
-            let session_id = stream.as_raw_fd() as u16;
-            client::handle_client(state, stream, peer, session_id, first).await;
+            crate::metrics::METRICS.handshake();
+            client::handle_client(state, stream, peer, session_id, first, cipher).await;
+        }
+    }
+
+    /// Toggle drain mode on SIGUSR1/SIGUSR2 and, while draining, poll the
+    /// online count every `poll` and log once it reaches zero.
+    pub async fn run_drain_control(state: Arc<Self>, poll: std::time::Duration) -> anyhow::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut start = signal(SignalKind::user_defined1())?;
+        let mut stop = signal(SignalKind::user_defined2())?;
+        let mut ticker = tokio::time::interval(poll);
+        let mut watch = drain::EmptyWatch::default();
+        loop {
+            tokio::select! {
+                _ = start.recv() => {
+                    if state.drain.set(true) {
+                        tracing::warn!("[login] [drain] draining: new logins are refused");
+                    }
+                }
+                _ = stop.recv() => {
+                    if state.drain.set(false) {
+                        tracing::warn!("[login] [drain] drain cancelled: accepting logins");
+                    }
+                }
+                _ = ticker.tick() => {}
+            }
+            let draining = state.drain.is_draining();
+            let Some(pool) = state.db.as_ref().filter(|_| draining) else {
+                watch.observe(false, 0);
+                continue;
+            };
+            match db::count_online(pool, state.config.get().server_id).await {
+                Ok(n) if watch.observe(true, n) => {
+                    tracing::warn!("[login] [drain] no players online; safe to stop");
+                }
+                Ok(n) => tracing::debug!("[login] [drain] online={}", n),
+                Err(e) => tracing::warn!("[login] [drain] online count failed: {}", e),
+            }
+        }
+    }
+
+    /// Whether `ip` has reached `login_lockout_threshold` wrong passwords
+    /// within the window (see `lockout`).
+    pub(crate) async fn is_locked_out(&self, ip: u32) -> bool {
+        let config = self.config.get();
+        let window = std::time::Duration::from_secs(config.login_lockout_window_secs);
+        let mut lock = self.lockout.lock().await;
+        lockout::is_locked(&mut lock, ip, config.login_lockout_threshold, window, std::time::Instant::now())
+    }
+
+    /// Every `every`, evict lockout entries past their window and shrink the
+    /// table if a spike left it mostly empty (see `network::compact`).
+    async fn run_compaction(state: Arc<Self>, every: std::time::Duration) {
+        let mut ticker = tokio::time::interval(every);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let window = std::time::Duration::from_secs(state.config.get().login_lockout_window_secs);
+            let mut table = state.lockout.lock().await;
+            let evicted = lockout::sweep(&mut table, window, std::time::Instant::now());
+            if evicted > 0 {
+                tracing::debug!("[login] [lockout] swept {} expired entries", evicted);
+            }
+            if let Some((old, new)) = crate::network::compact::shrink_sparse(&mut *table) {
+                tracing::info!("[login] [compact] lockout capacity {old} -> {new}");
+            }
         }
     }
 
     pub async fn run(state: Arc<Self>, bind_addr: &str) -> anyhow::Result<()> {
         let listener = TcpListener::bind(bind_addr).await?;
-        tracing::info!("[login] [ready] addr={}", bind_addr);
+        tracing::info!("[login] [ready] addr={} cipher={}", bind_addr, state.cipher.name());
+        let config = state.config.get();
+        if config.login_alt_port != 0 {
+            let alt_addr = format!("{}:{}", config.login_ip, config.login_alt_port);
+            let alt_listener = TcpListener::bind(&alt_addr).await?;
+            let cipher = listener_cipher(&config, &config.login_alt_cipher);
+            tracing::info!("[login] [ready] alt addr={} cipher={}", alt_addr, cipher.name());
+            tokio::spawn(Self::run_alt(Arc::clone(&state), alt_listener, cipher));
+        }
+        #[cfg(feature = "websocket")]
+        if config.login_ws_port != 0 {
+            let ws_addr = format!("{}:{}", config.login_ip, config.login_ws_port);
+            let ws_listener = TcpListener::bind(&ws_addr).await?;
+            tracing::info!("[login] [ready] websocket addr={}", ws_addr);
+            tokio::spawn(Self::run_websocket(Arc::clone(&state), ws_listener));
+        }
+        tokio::spawn(Self::run_compaction(Arc::clone(&state), std::time::Duration::from_secs(60)));
         loop {
             let (stream, peer) = listener.accept().await?;
             let s = Arc::clone(&state);
@@ -185,6 +322,54 @@ start_point:
             });
         }
     }
+
+    /// Accept clients on `login_alt_port`, which speak `cipher`.
+    async fn run_alt(state: Arc<Self>, listener: TcpListener, cipher: Arc<dyn Cipher>) {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(c) => c,
+                Err(e) => {
+                    crate::log_every!(error, 5, "[login] [alt_accept] error: {}", e);
+                    continue;
+                }
+            };
+            let s = Arc::clone(&state);
+            let cipher = Arc::clone(&cipher);
+            tokio::spawn(async move {
+                let session_id = stream.as_raw_fd() as u16;
+                Self::handle_stream(s, stream, peer, session_id, cipher).await;
+            });
+        }
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl LoginState {
+    /// Accept browser clients: upgrade each connection, then serve it like TCP.
+    async fn run_websocket(state: Arc<Self>, listener: TcpListener) {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(c) => c,
+                Err(e) => {
+                    crate::log_every!(error, 5, "[login] [ws_accept] error: {}", e);
+                    continue;
+                }
+            };
+            let s = Arc::clone(&state);
+            tokio::spawn(async move {
+                let session_id = stream.as_raw_fd() as u16;
+                let handshake = crate::network::websocket::accept(stream);
+                match tokio::time::timeout(std::time::Duration::from_secs(5), handshake).await {
+                    Ok(Ok(ws)) => {
+                        let cipher = Arc::clone(&s.cipher);
+                        Self::handle_stream(s, ws, peer, session_id, cipher).await
+                    }
+                    Ok(Err(e)) => tracing::info!("[login] [ws_handshake] peer={} failed: {}", peer, e),
+                    Err(_) => tracing::info!("[login] [ws_handshake] peer={} timed out", peer),
+                }
+            });
+        }
+    }
 }
 
 #[cfg(test)]
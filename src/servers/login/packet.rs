@@ -1,17 +1,25 @@
 use anyhow::Result;
-use tokio::net::TcpStream;
+use crate::network::Stream;
 
-use crate::network::crypt::{set_packet_indexes, tk_crypt_static};
-use crate::network::read_framed_packet;
+use crate::network::crypt::{set_packet_indexes, Cipher};
+use crate::network::read_framed_packet_limited;
 
-/// Reads one complete 0xAA-framed packet from the stream.
-pub async fn read_client_packet(stream: &mut TcpStream) -> Result<Vec<u8>> {
-    read_framed_packet(stream).await
+/// Largest payload accepted from a login-port peer. Handshake, login and
+/// char-server auth packets are all well under this.
+pub const CLIENT_MAX_PAYLOAD: usize = 4096;
+
+/// How long a peer has to deliver a packet once its header has arrived.
+pub const CLIENT_BODY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Reads one complete 0xAA-framed packet from the stream, refusing frames
+/// over `CLIENT_MAX_PAYLOAD` and bodies that stall.
+pub async fn read_client_packet(stream: &mut impl Stream) -> Result<Vec<u8>> {
+    read_framed_packet_limited(stream, CLIENT_MAX_PAYLOAD, Some(CLIENT_BODY_TIMEOUT)).await
 }
 
 /// Builds a `clif_message` packet: 0xAA-framed, cmd=0x02, encrypted.
 /// `code`: sub-command (0x00=ok, 0x03=error, 0x05=pass-error)
-pub fn build_message(code: u8, text: &str, xor_key: &[u8]) -> Vec<u8> {
+pub fn build_message(code: u8, text: &str, cipher: &dyn Cipher) -> Vec<u8> {
     let text_bytes = text.as_bytes();
     let text_len = std::cmp::min(text_bytes.len(), 255);
     let payload_len = text_len + 6;
@@ -26,7 +34,7 @@ pub fn build_message(code: u8, text: &str, xor_key: &[u8]) -> Vec<u8> {
     buf[6] = text_len as u8;
     buf[7..7 + text_len].copy_from_slice(&text_bytes[..text_len]);
     set_packet_indexes(&mut buf);
-    tk_crypt_static(&mut buf, xor_key);
+    cipher.encrypt_packet(&mut buf);
     buf
 }
 
@@ -70,9 +78,37 @@ pub fn build_version_patch(nex_version: u16, patch_url: &str) -> Vec<u8> {
     buf
 }
 
+/// Reason codes in the status byte of a rejecting 0x1000. Any non-zero
+/// status is a reject, so older char servers still see a refusal.
+pub const INTIF_REJECT_AUTH: u8 = 0x01;
+pub const INTIF_REJECT_BUSY: u8 = 0x02;
+pub const INTIF_REJECT_MALFORMED: u8 = 0x03;
+
+/// Longest reject text carried by 0x1005.
+pub const INTIF_REJECT_TEXT_MAX: usize = 255;
+
 /// Builds the interserver accept/reject packet (3 bytes, LE cmd=0x1000).
 pub fn build_intif_auth_response(accepted: bool) -> Vec<u8> {
-    vec![0x00, 0x10, if accepted { 0x00 } else { 0x01 }]
+    build_intif_reject(if accepted { 0x00 } else { INTIF_REJECT_AUTH })
+}
+
+/// 0x1000 with an explicit status byte (0 = accepted, else `INTIF_REJECT_*`).
+pub fn build_intif_reject(reason: u8) -> Vec<u8> {
+    vec![0x00, 0x10, reason]
+}
+
+/// 0x1005 reject text, sent after a rejecting 0x1000:
+/// `[cmd:2 LE][len:2 LE][text]`, text truncated to `INTIF_REJECT_TEXT_MAX`.
+pub fn build_intif_reject_text(text: &str) -> Vec<u8> {
+    let mut end = text.len().min(INTIF_REJECT_TEXT_MAX);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut pkt = Vec::with_capacity(4 + end);
+    pkt.extend_from_slice(&0x1005u16.to_le_bytes());
+    pkt.extend_from_slice(&(end as u16).to_le_bytes());
+    pkt.extend_from_slice(&text.as_bytes()[..end]);
+    pkt
 }
 
 #[cfg(test)]
@@ -97,11 +133,21 @@ mod tests {
     fn test_build_intif_auth_response() {
         assert_eq!(build_intif_auth_response(true),  vec![0x00, 0x10, 0x00]);
         assert_eq!(build_intif_auth_response(false), vec![0x00, 0x10, 0x01]);
+        assert_eq!(build_intif_reject(INTIF_REJECT_BUSY), vec![0x00, 0x10, 0x02]);
+    }
+
+    #[test]
+    fn test_build_intif_reject_text_caps_length() {
+        assert_eq!(build_intif_reject_text("no"), vec![0x05, 0x10, 0x02, 0x00, b'n', b'o']);
+        let long = build_intif_reject_text(&"é".repeat(200));
+        let len = u16::from_le_bytes([long[2], long[3]]) as usize;
+        assert!(len <= INTIF_REJECT_TEXT_MAX && len % 2 == 0);
+        assert_eq!(long.len(), 4 + len);
     }
 
     #[test]
     fn test_build_message_starts_with_aa() {
-        let pkt = build_message(0x03, "oops", b"key\x00\x00\x00\x00\x00\x00");
+        let pkt = build_message(0x03, "oops", &crate::network::crypt::XorCipher::new(b"key"));
         assert_eq!(pkt[0], 0xAA);
         assert_eq!(pkt[3], 0x02); // cmd
     }
@@ -0,0 +1,173 @@
+//! Personal bank transactions.
+//!
+//! Bank NPC scripts used to move items and gold by adjusting inventory,
+//! `banks[]`, `money` and `bankMoney` in separate Lua calls, so an error or
+//! disconnect between steps could duplicate or lose goods. The
+//! `rust_pc_bank_*` functions in `game::pc` do each move in one call on the
+//! map thread; this module holds the checks they run first, so nothing can
+//! fail once the first field changes. Like `shop`, a refusal changes nothing.
+
+/// Why a bank transaction was refused. Nothing has changed when one is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BankError {
+    BadAmount,
+    /// Empty or out-of-range slot, or fewer units held than asked.
+    BadSlot,
+    /// No free bank slot within `maxslots`, or the chosen slot holds
+    /// something else.
+    BankFull,
+    /// The inventory cannot take the withdrawn units.
+    NoSpace,
+    NotEnoughMoney,
+    /// The credit would overflow the purse or the bank balance.
+    MoneyCap,
+}
+
+impl BankError {
+    const ALL: [BankError; 6] = [
+        Self::BadAmount, Self::BadSlot, Self::BankFull, Self::NoSpace, Self::NotEnoughMoney,
+        Self::MoneyCap,
+    ];
+
+    /// Negative code returned over FFI (0 is success).
+    pub fn code(self) -> i32 {
+        -(self as i32) - 1
+    }
+
+    /// Inverse of [`code`](Self::code); `None` for 0 (success) or unknown codes.
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.code() == code)
+    }
+
+    /// Short reason string handed back to Lua.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::BadAmount => "bad_amount",
+            Self::BadSlot => "bad_slot",
+            Self::BankFull => "bank_full",
+            Self::NoSpace => "no_space",
+            Self::NotEnoughMoney => "not_enough_money",
+            Self::MoneyCap => "money_cap",
+        }
+    }
+}
+
+/// What makes two stacks the same item: id, owner, engraving (ignoring ASCII
+/// case, as the C bank did) and custom look/icon.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ItemKey<'a> {
+    pub id: u32,
+    pub owner: u32,
+    pub name: &'a [u8],
+    pub look: (u32, u32),
+    pub icon: (u32, u32),
+}
+
+impl ItemKey<'_> {
+    pub fn same(&self, other: &ItemKey) -> bool {
+        self.id == other.id
+            && self.owner == other.owner
+            && self.look == other.look
+            && self.icon == other.icon
+            && self.name.eq_ignore_ascii_case(other.name)
+    }
+}
+
+/// One bank slot as `deposit_slot` sees it; `key.id == 0` is empty.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BankView<'a> {
+    pub key: ItemKey<'a>,
+    pub amount: u32,
+}
+
+/// Where `add` units of `key` go among the first `limit` bank slots: `want`
+/// if given (empty, or already the same item), else the slot already holding
+/// the item, else the first empty one.
+pub fn deposit_slot(bank: &[BankView], limit: usize, key: &ItemKey, add: u32, want: Option<usize>) -> Result<usize, BankError> {
+    if add == 0 {
+        return Err(BankError::BadAmount);
+    }
+    let bank = &bank[..limit.min(bank.len())];
+    let fits = |i: usize| {
+        let s = &bank[i];
+        if s.key.id == 0 {
+            Ok(i)
+        } else if !s.key.same(key) {
+            Err(BankError::BankFull)
+        } else if s.amount.checked_add(add).is_none() {
+            Err(BankError::BadAmount)
+        } else {
+            Ok(i)
+        }
+    };
+    match want {
+        Some(i) if i >= bank.len() => Err(BankError::BadSlot),
+        Some(i) => fits(i),
+        None => match bank.iter().position(|s| s.key.id != 0 && s.key.same(key)) {
+            Some(i) => fits(i),
+            None => bank.iter().position(|s| s.key.id == 0).ok_or(BankError::BankFull),
+        },
+    }
+}
+
+/// New `(money, bankmoney)` after moving `amount` gold into the bank
+/// (negative: out of it).
+pub fn gold_transfer(amount: i64, money: u32, bank: u32) -> Result<(u32, u32), BankError> {
+    let (from, to) = if amount >= 0 { (money, bank) } else { (bank, money) };
+    let n = amount.unsigned_abs();
+    if n == 0 || n > u32::MAX as u64 {
+        return Err(BankError::BadAmount);
+    }
+    let n = n as u32;
+    if n > from {
+        return Err(BankError::NotEnoughMoney);
+    }
+    let to = to.checked_add(n).ok_or(BankError::MoneyCap)?;
+    let from = from - n;
+    Ok(if amount >= 0 { (from, to) } else { (to, from) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_slot_merges_and_respects_limit() {
+        let sword = ItemKey { id: 7, name: b"Hero", ..Default::default() };
+        let bank = [
+            BankView { key: ItemKey { id: 9, ..Default::default() }, amount: 1 },
+            BankView::default(),
+            BankView { key: ItemKey { id: 7, name: b"hero", ..Default::default() }, amount: 2 },
+            BankView::default(),
+        ];
+        assert_eq!(deposit_slot(&bank, 4, &sword, 1, None), Ok(2));
+        assert_eq!(deposit_slot(&bank, 2, &sword, 1, None), Ok(1));
+        assert_eq!(deposit_slot(&bank[..1], 4, &sword, 1, None), Err(BankError::BankFull));
+        assert_eq!(deposit_slot(&bank, 4, &sword, 1, Some(0)), Err(BankError::BankFull));
+        assert_eq!(deposit_slot(&bank, 4, &sword, 1, Some(3)), Ok(3));
+        assert_eq!(deposit_slot(&bank, 3, &sword, 1, Some(3)), Err(BankError::BadSlot));
+        assert_eq!(deposit_slot(&bank, 4, &sword, u32::MAX, None), Err(BankError::BadAmount));
+        assert_eq!(deposit_slot(&bank, 4, &sword, 0, None), Err(BankError::BadAmount));
+
+        let owned = ItemKey { owner: 5, ..sword };
+        assert_eq!(deposit_slot(&bank, 4, &owned, 1, None), Ok(1));
+    }
+
+    #[test]
+    fn test_gold_transfer_bounds() {
+        assert_eq!(gold_transfer(40, 100, 0), Ok((60, 40)));
+        assert_eq!(gold_transfer(-40, 0, 100), Ok((40, 60)));
+        assert_eq!(gold_transfer(101, 100, 0), Err(BankError::NotEnoughMoney));
+        assert_eq!(gold_transfer(-1, u32::MAX, 1), Err(BankError::MoneyCap));
+        assert_eq!(gold_transfer(1, 1, u32::MAX), Err(BankError::MoneyCap));
+        assert_eq!(gold_transfer(0, 1, 1), Err(BankError::BadAmount));
+        assert_eq!(gold_transfer(i64::MIN, 1, 1), Err(BankError::BadAmount));
+    }
+
+    #[test]
+    fn test_error_codes_roundtrip() {
+        assert_eq!(BankError::BadAmount.code(), -1);
+        assert_eq!(BankError::from_code(-6), Some(BankError::MoneyCap));
+        assert_eq!(BankError::from_code(0), None);
+    }
+}
@@ -1,12 +1,41 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
 use super::MapState;
-use super::packet::{PKT_LENS, dispatch};
+use super::packet::{PKT_LENS, SHUTDOWN_NOTICE, dispatch};
+use crate::network::integrity::MacKey;
+use crate::session::ReconnectPolicy;
 
 const MAX_PKT_LEN: usize = 16 * 1024 * 1024;
 
+/// Sends from C (`ffi::map_char`) still on their way to the link channel.
+pub static SENDS_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Last step before the process exits: once every queued send has reached
+/// the link, send 0x3016 and wait for char_server's 0x3813. Char handles a
+/// link's packets in order, so the ack means the final saves are written.
+/// False if the link is down or no ack came within `timeout`.
+pub async fn shutdown_flush(state: &Arc<MapState>, timeout: Duration) -> bool {
+    let flush = async {
+        while SENDS_IN_FLIGHT.load(Ordering::Acquire) > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        {
+            let ct = state.char_tx.lock().await;
+            let Some(tx) = ct.as_ref() else { return false };
+            if tx.send(SHUTDOWN_NOTICE.to_vec()).await.is_err() { return false; }
+        }
+        state.shutdown_ack.notified().await;
+        true
+    };
+    tokio::time::timeout(timeout, flush).await.unwrap_or(false)
+}
+
+/// Keep the char server link up, redialling on the `reconnect_*` schedule.
+/// Stops for good once `reconnect_max_attempts` dials in a row have failed.
 pub async fn connect_to_char(state: Arc<MapState>) {
     use tokio::time::{Duration, interval};
     let mut ticker = interval(Duration::from_secs(1));
@@ -16,11 +45,12 @@ pub async fn connect_to_char(state: Arc<MapState>) {
             let tx = state.char_tx.lock().await;
             if tx.is_some() { continue; }
         }
-        let addr = format!("{}:{}", state.config.char_ip, state.config.char_port);
+        let config = state.config.get();
+        let addr = format!("{}:{}", config.char_ip, config.char_port);
         tracing::info!("[map] [charif] Connecting to char server at {}", addr);
-        match TcpStream::connect(&addr).await {
+        match ReconnectPolicy::from_config(&config).connect(&addr, "[map] [charif]").await {
             Ok(stream) => run_char_connection(Arc::clone(&state), stream).await,
-            Err(e) => tracing::warn!("[map] [charif] Connect failed: {}", e),
+            Err(_) => return,
         }
     }
 }
@@ -31,19 +61,25 @@ async fn run_char_connection(state: Arc<MapState>, mut stream: TcpStream) {
     // [66..70]=map_ip (u32 LE), [70..72]=map_port (u16 LE)
     let mut pkt = vec![0u8; 72];
     pkt[0] = 0x00; pkt[1] = 0x30; // cmd 0x3000 LE
-    let cid = state.config.char_id.as_bytes();
-    let cpw = state.config.char_pw.as_bytes();
+    let config = state.config.get();
+    let cid = config.char_id.as_bytes();
+    let cpw = config.char_pw.as_bytes();
     pkt[2..2 + cid.len().min(32)].copy_from_slice(&cid[..cid.len().min(32)]);
     pkt[34..34 + cpw.len().min(32)].copy_from_slice(&cpw[..cpw.len().min(32)]);
     // map_ip as raw bytes in network byte order (big-endian) — matches C convention
     // where IPs are stored as u32 in network order and written directly to packets.
-    let map_ip_u32: u32 = state.config.map_ip.parse::<std::net::Ipv4Addr>()
+    let map_ip_u32: u32 = config.map_ip.parse::<std::net::Ipv4Addr>()
         .map(u32::from)
         .unwrap_or(0);
     pkt[66..70].copy_from_slice(&map_ip_u32.to_be_bytes());
-    pkt[70..72].copy_from_slice(&state.config.map_port.to_le_bytes());
+    pkt[70..72].copy_from_slice(&config.map_port.to_le_bytes());
 
+    let mac = MacKey::from_config(&config);
+    let mut sealer = mac.sealer();
+    let mut verifier = mac.verifier();
+    sealer.seal(&mut pkt);
     if stream.write_all(&pkt).await.is_err() { return; }
+    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
 
     let (tx, mut rx) = mpsc::channel::<Vec<u8>>(64);
     {
@@ -54,7 +90,8 @@ async fn run_char_connection(state: Arc<MapState>, mut stream: TcpStream) {
     let (mut rh, mut wh) = stream.into_split();
 
     let writer = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
+        while let Some(mut msg) = rx.recv().await {
+            sealer.seal(&mut msg);
             if wh.write_all(&msg).await.is_err() { break; }
         }
     });
@@ -93,6 +130,11 @@ async fn run_char_connection(state: Arc<MapState>, mut stream: TcpStream) {
         if let Some(lb) = len_bytes { full_pkt.extend_from_slice(&lb); }
         full_pkt.extend_from_slice(&rest);
 
+        if !verifier.check(&mut rh, &full_pkt).await {
+            tracing::error!("[map] [charif] cmd={:04X} failed {} check from {}, dropping connection", cmd, verifier.what(), peer);
+            break;
+        }
+
         tracing::info!("[map] [charif] recv cmd={:04X} len={}", cmd, pkt_len);
         dispatch(&state, cmd, &full_pkt).await;
     }
@@ -0,0 +1,79 @@
+//! Cleaning script-built chat text before it reaches a client packet.
+//!
+//! The speak and message packets carry the text after a length field the
+//! client trusts, and the say path escapes it into a fixed 255-byte buffer
+//! for logging, so text from `speak`/`msg` is capped at `chat_max_len` bytes.
+//! Control bytes (including NUL, which ends the C string early) are dropped.
+//!
+//! Truncation never splits a character: valid UTF-8 is cut at a char
+//! boundary, and other text is treated as the client's double-byte encoding,
+//! where a lead byte (≥ 0x80) and its trail byte are kept or dropped together.
+
+/// Hard ceiling for `chat_max_len`: `Sql_EscapeString` can double every byte
+/// into the 255-byte escape buffer in `clif_sendscriptsay`.
+pub const MAX_CHAT_LEN: usize = 127;
+
+/// What `sanitize` had to change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cleaned {
+    /// Bytes cut from the end to fit the limit.
+    pub truncated: usize,
+    /// Control bytes removed.
+    pub stripped: usize,
+}
+
+impl Cleaned {
+    pub fn changed(&self) -> bool {
+        self.truncated > 0 || self.stripped > 0
+    }
+}
+
+/// `text` without control bytes, cut to at most `max` bytes.
+pub fn sanitize(text: &[u8], max: usize) -> (Vec<u8>, Cleaned) {
+    let mut out: Vec<u8> = text.iter().copied().filter(|&b| b >= 0x20 && b != 0x7f).collect();
+    let stripped = text.len() - out.len();
+    let total = out.len();
+    if total > max {
+        let cut = match std::str::from_utf8(&out) {
+            Ok(s) => (0..=max).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0),
+            Err(_) => {
+                let mut i = 0;
+                while i < total {
+                    let w = if out[i] >= 0x80 { 2 } else { 1 };
+                    if i + w > max {
+                        break;
+                    }
+                    i += w;
+                }
+                i
+            }
+        };
+        out.truncate(cut);
+    }
+    let truncated = total - out.len();
+    (out, Cleaned { truncated, stripped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_control_bytes_and_cuts_at_char_boundary() {
+        let (out, c) = sanitize(b"hi\0\nthere\x7f", 100);
+        assert_eq!((out.as_slice(), c), (&b"hithere"[..], Cleaned { truncated: 0, stripped: 3 }));
+
+        // "aé€": 1 + 2 + 3 bytes; a 5-byte cap can't hold the euro sign.
+        let (out, c) = sanitize("aé€".as_bytes(), 5);
+        assert_eq!((out.as_slice(), c.truncated), ("aé".as_bytes(), 3));
+    }
+
+    #[test]
+    fn test_double_byte_text_keeps_pairs_together() {
+        // Not UTF-8: two EUC-KR characters around an ASCII byte.
+        let text = [0xb0, 0xa1, b'x', 0xb3, 0xaa];
+        let (out, c) = sanitize(&text, 4);
+        assert_eq!((out.as_slice(), c.truncated), (&text[..3], 2));
+        assert!(!sanitize(b"ok", 4).1.changed());
+    }
+}
@@ -0,0 +1,182 @@
+//! Recipe crafting as one transaction.
+//!
+//! `rust_pc_craft` in `game::pc` checks a recipe against the player's
+//! inventory, removes every reagent, rolls the outcome and grants the product
+//! in a single call on the map thread, so a script error or disconnect can't
+//! land between "reagents taken" and "item given". All checks here run before
+//! the first change; once reagents are taken the call always finishes.
+//!
+//! A recipe's product is the item whose identifier matches `RecIdentifier`
+//! (`RecCritIdentifier` on a critical). `RecSuccessRate` and `RecCritRate` are
+//! percentages; a failed roll still uses up the reagents. Skill, token and
+//! superior-material requirements stay with the calling script.
+
+use crate::servers::map::shop::SlotView;
+
+/// Why a craft was refused. Nothing has changed when one is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CraftError {
+    UnknownRecipe,
+    MissingReagents,
+    /// The product would not fit once the reagents are gone.
+    NoSpace,
+    /// The recipe names no item that exists.
+    NoProduct,
+}
+
+impl CraftError {
+    const ALL: [CraftError; 4] = [Self::UnknownRecipe, Self::MissingReagents, Self::NoSpace, Self::NoProduct];
+
+    /// Negative code returned over FFI.
+    pub fn code(self) -> i32 {
+        -(self as i32) - 1
+    }
+
+    /// Inverse of [`code`](Self::code); `None` for outcomes and unknown codes.
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.code() == code)
+    }
+
+    /// Short reason string handed back to Lua.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::UnknownRecipe => "unknown_recipe",
+            Self::MissingReagents => "missing_reagents",
+            Self::NoSpace => "no_space",
+            Self::NoProduct => "no_product",
+        }
+    }
+}
+
+/// How a craft that went ahead turned out; positive codes over FFI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CraftOutcome {
+    Success = 1,
+    Critical = 2,
+    /// Roll failed: reagents used, nothing made.
+    Failed = 3,
+}
+
+impl CraftOutcome {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        [Self::Success, Self::Critical, Self::Failed].into_iter().find(|o| o.code() == code)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Critical => "critical",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// `(item, amount)` reagents from a recipe's alternating material/amount
+/// pairs, skipping empty pairs and merging repeats.
+pub fn reagents(materials: &[i32]) -> Vec<(u32, i32)> {
+    let mut out: Vec<(u32, i32)> = Vec::new();
+    for pair in materials.chunks_exact(2) {
+        let (id, amount) = (pair[0], pair[1]);
+        if id <= 0 || amount <= 0 {
+            continue;
+        }
+        match out.iter_mut().find(|(i, _)| *i == id as u32) {
+            Some((_, n)) => *n = n.saturating_add(amount),
+            None => out.push((id as u32, amount)),
+        }
+    }
+    out
+}
+
+/// Which inventory slots to take how many units from, lowest slot first.
+/// Only `plain` slots count: an engraved, owned or protected copy is never
+/// used up as a reagent.
+pub fn take_plan(slots: &[SlotView], reagents: &[(u32, i32)]) -> Result<Vec<(usize, i32)>, CraftError> {
+    let mut plan = Vec::new();
+    for &(id, mut need) in reagents {
+        for (i, s) in slots.iter().enumerate() {
+            if need == 0 {
+                break;
+            }
+            if s.id == id && s.plain && s.amount > 0 {
+                let take = need.min(s.amount);
+                plan.push((i, take));
+                need -= take;
+            }
+        }
+        if need > 0 {
+            return Err(CraftError::MissingReagents);
+        }
+    }
+    Ok(plan)
+}
+
+/// `slots` as they will be after `plan` is taken.
+pub fn after_plan(slots: &[SlotView], plan: &[(usize, i32)]) -> Vec<SlotView> {
+    let mut out = slots.to_vec();
+    for &(i, n) in plan {
+        out[i].amount -= n;
+        if out[i].amount <= 0 {
+            out[i] = SlotView::default();
+        }
+    }
+    out
+}
+
+/// Outcome for rolls `success_roll`/`crit_roll` in `0..100`.
+pub fn roll(success_rate: u32, crit_rate: u32, success_roll: u32, crit_roll: u32) -> CraftOutcome {
+    if success_roll >= success_rate {
+        CraftOutcome::Failed
+    } else if crit_roll < crit_rate {
+        CraftOutcome::Critical
+    } else {
+        CraftOutcome::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(id: u32, amount: i32) -> SlotView {
+        SlotView { id, amount, plain: true }
+    }
+
+    #[test]
+    fn test_reagents_and_plan() {
+        let mats = reagents(&[5, 2, 0, 0, 6, 1, 5, 1, 7, 0]);
+        assert_eq!(mats, vec![(5, 3), (6, 1)]);
+
+        let slots = [slot(5, 2), slot(6, 1), slot(9, 4), slot(5, 5)];
+        let plan = take_plan(&slots, &mats).unwrap();
+        assert_eq!(plan, vec![(0, 2), (3, 1), (1, 1)]);
+        let after = after_plan(&slots, &plan);
+        assert_eq!(after.iter().map(|s| (s.id, s.amount)).collect::<Vec<_>>(), vec![(0, 0), (0, 0), (9, 4), (5, 4)]);
+
+        assert_eq!(take_plan(&slots, &[(5, 8)]), Err(CraftError::MissingReagents));
+        assert_eq!(take_plan(&slots, &[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_plan_skips_special_copies() {
+        let engraved = SlotView { id: 5, amount: 3, plain: false };
+        assert_eq!(take_plan(&[engraved], &[(5, 1)]), Err(CraftError::MissingReagents));
+        assert_eq!(take_plan(&[engraved, slot(5, 1)], &[(5, 1)]), Ok(vec![(1, 1)]));
+    }
+
+    #[test]
+    fn test_roll_and_codes() {
+        assert_eq!(roll(60, 10, 59, 9), CraftOutcome::Critical);
+        assert_eq!(roll(60, 10, 59, 10), CraftOutcome::Success);
+        assert_eq!(roll(60, 100, 60, 0), CraftOutcome::Failed);
+        assert_eq!(roll(0, 0, 0, 0), CraftOutcome::Failed);
+
+        assert_eq!(CraftError::from_code(CraftError::NoSpace.code()), Some(CraftError::NoSpace));
+        assert_eq!(CraftOutcome::from_code(2), Some(CraftOutcome::Critical));
+        assert_eq!(CraftError::from_code(1), None);
+    }
+}
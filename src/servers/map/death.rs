@@ -0,0 +1,145 @@
+//! Player death penalty policy.
+//!
+//! The server-wide policy comes from config (`death_exp_loss_pct`,
+//! `death_dura_loss_pct`, `death_respawn`, `death_town`); scripts can replace
+//! it for a single map (an arena with no penalty, a hardcore dungeon that
+//! sends you to town). `pc_diescript` applies the exp and durability loss,
+//! and `pc:deathRespawn()` resurrects at the point the policy picks.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Point;
+
+/// Where a dead player comes back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeathRespawn {
+    /// The character's bind point (`bindMap`/`bindX`/`bindY`), else town.
+    Bind,
+    /// `death_town`, else the new-character start point.
+    Town,
+    /// Where they fell.
+    #[default]
+    Here,
+}
+
+impl DeathRespawn {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "bind" => Some(Self::Bind),
+            "town" => Some(Self::Town),
+            "here" => Some(Self::Here),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Bind => "bind",
+            Self::Town => "town",
+            Self::Here => "here",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DeathPolicy {
+    /// Percent of current exp lost on death (0-100).
+    pub exp_loss_pct: u8,
+    /// Percent of each equipped item's max durability lost on death (0-100).
+    pub dura_loss_pct: u8,
+    pub respawn: DeathRespawn,
+}
+
+impl DeathPolicy {
+    /// Exp left after the loss, rounded in the player's favour.
+    pub fn exp_after(&self, exp: u32) -> u32 {
+        exp - (exp as u64 * self.exp_loss_pct.min(100) as u64 / 100) as u32
+    }
+
+    /// Durability left after the loss. Never drops an item below 1 so a death
+    /// can wear gear down but not break it outright; items without durability
+    /// (`max <= 0`) are untouched.
+    pub fn dura_after(&self, dura: i32, max: i32) -> i32 {
+        if max <= 0 || dura <= 1 || self.dura_loss_pct == 0 {
+            return dura;
+        }
+        let loss = ((max as i64 * self.dura_loss_pct.min(100) as i64) / 100).max(1) as i32;
+        (dura - loss).max(1)
+    }
+}
+
+/// The respawn cell for `respawn`. `bind` is `None` when the character has
+/// never bound; `town` is `death_town` or the start point.
+pub fn respawn_point(respawn: DeathRespawn, bind: Option<Point>, town: Point, here: Point) -> Point {
+    match respawn {
+        DeathRespawn::Here => here,
+        DeathRespawn::Town => town,
+        DeathRespawn::Bind => bind.unwrap_or(town),
+    }
+}
+
+/// Per-map policies set by scripts; reset on restart.
+static MAP_OVERRIDES: Mutex<Option<HashMap<u16, DeathPolicy>>> = Mutex::new(None);
+
+pub fn set_override(m: u16, policy: DeathPolicy) {
+    MAP_OVERRIDES.lock().unwrap().get_or_insert_with(HashMap::new).insert(m, policy);
+}
+
+/// Drop `m`'s override; true if it had one.
+pub fn clear_override(m: u16) -> bool {
+    MAP_OVERRIDES.lock().unwrap().as_mut().is_some_and(|t| t.remove(&m).is_some())
+}
+
+/// `m`'s override, else `default`.
+pub fn policy_for(m: u16, default: DeathPolicy) -> DeathPolicy {
+    MAP_OVERRIDES.lock().unwrap().as_ref().and_then(|t| t.get(&m).copied()).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_losses_round_for_player_and_clamp() {
+        let p = DeathPolicy { exp_loss_pct: 10, dura_loss_pct: 25, respawn: DeathRespawn::Here };
+        assert_eq!(p.exp_after(1005), 905);
+        assert_eq!(p.exp_after(u32::MAX), u32::MAX - u32::MAX / 10);
+        assert_eq!(p.dura_after(1000, 1000), 750);
+        assert_eq!(p.dura_after(100, 1000), 1);
+        assert_eq!(p.dura_after(3, 2), 2);
+        assert_eq!(p.dura_after(0, 1000), 0);
+        assert_eq!(p.dura_after(50, 0), 50);
+        let none = DeathPolicy::default();
+        assert_eq!(none.exp_after(500), 500);
+        assert_eq!(none.dura_after(500, 1000), 500);
+    }
+
+    #[test]
+    fn test_respawn_point_falls_back_to_town() {
+        let town = Point::new(1, 10, 10);
+        let here = Point::new(7, 3, 4);
+        let bind = Point::new(2, 5, 5);
+        assert_eq!(respawn_point(DeathRespawn::Bind, Some(bind), town, here), bind);
+        assert_eq!(respawn_point(DeathRespawn::Bind, None, town, here), town);
+        assert_eq!(respawn_point(DeathRespawn::Town, Some(bind), town, here), town);
+        assert_eq!(respawn_point(DeathRespawn::Here, Some(bind), town, here), here);
+        assert_eq!(DeathRespawn::parse("BIND"), Some(DeathRespawn::Bind));
+        assert_eq!(DeathRespawn::parse("home"), None);
+    }
+
+    #[test]
+    fn test_map_override_wins_until_cleared() {
+        let global = DeathPolicy { exp_loss_pct: 5, ..Default::default() };
+        let arena = DeathPolicy::default();
+        set_override(60123, arena);
+        assert_eq!(policy_for(60123, global), arena);
+        assert_eq!(policy_for(60124, global), global);
+        assert!(clear_override(60123));
+        assert!(!clear_override(60123));
+        assert_eq!(policy_for(60123, global), global);
+    }
+}
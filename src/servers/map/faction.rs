@@ -0,0 +1,128 @@
+//! Faction relations and the single "may A attack B here" rule.
+//!
+//! A player's faction is their `country`; a mob's is `MobFaction` from the
+//! mob db (default `WILD`). Note that `side` on players, mobs and NPCs is the
+//! facing direction, not a faction.
+//!
+//! Relations come from `faction_relations` in server.yaml and are installed
+//! at boot. Pairs not listed there fall back to: same faction → ally,
+//! either side `WILD` → hostile, otherwise neutral. `can_target` then allows
+//! hostile pairs everywhere and everyone else (allies included, i.e.
+//! friendly fire) only on PvP maps, which matches the behaviour before
+//! factions existed when no relations are configured.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+/// Default mob faction: hostile to every other faction unless configured.
+pub const WILD: u8 = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    Ally,
+    Neutral,
+    Hostile,
+}
+
+/// One configured pair; relations are symmetric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FactionRelation {
+    pub a: u8,
+    pub b: u8,
+    pub relation: Relation,
+}
+
+#[derive(Debug, Default)]
+pub struct FactionTable {
+    pairs: HashMap<(u8, u8), Relation>,
+}
+
+impl FactionTable {
+    pub fn new(relations: &[FactionRelation]) -> Self {
+        let mut pairs = HashMap::new();
+        for r in relations {
+            pairs.insert(key(r.a, r.b), r.relation);
+        }
+        Self { pairs }
+    }
+
+    pub fn relation(&self, a: u8, b: u8) -> Relation {
+        if let Some(&r) = self.pairs.get(&key(a, b)) {
+            return r;
+        }
+        if a == b {
+            Relation::Ally
+        } else if a == WILD || b == WILD {
+            Relation::Hostile
+        } else {
+            Relation::Neutral
+        }
+    }
+
+    /// Whether `attacker` may target `target` on a map whose pvp flag is `map_pvp`.
+    pub fn can_target(&self, attacker: u8, target: u8, map_pvp: u8) -> bool {
+        match self.relation(attacker, target) {
+            Relation::Hostile => true,
+            Relation::Neutral | Relation::Ally => map_pvp != 0,
+        }
+    }
+}
+
+fn key(a: u8, b: u8) -> (u8, u8) {
+    (a.min(b), a.max(b))
+}
+
+static TABLE: RwLock<Option<FactionTable>> = RwLock::new(None);
+
+/// Replace the live relation table (boot, config reload).
+pub fn install(relations: &[FactionRelation]) {
+    *TABLE.write().unwrap() = Some(FactionTable::new(relations));
+}
+
+/// `FactionTable::can_target` on the live table (defaults only if none installed).
+pub fn can_target(attacker: u8, target: u8, map_pvp: u8) -> bool {
+    match TABLE.read().unwrap().as_ref() {
+        Some(t) => t.can_target(attacker, target, map_pvp),
+        None => FactionTable::default().can_target(attacker, target, map_pvp),
+    }
+}
+
+pub fn relation(a: u8, b: u8) -> Relation {
+    match TABLE.read().unwrap().as_ref() {
+        Some(t) => t.relation(a, b),
+        None => FactionTable::default().relation(a, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_match_pre_faction_behaviour() {
+        let t = FactionTable::default();
+        // Mobs attack players anywhere; players fight each other only on PvP maps.
+        assert!(t.can_target(WILD, 0, 0));
+        assert!(t.can_target(1, WILD, 0));
+        assert!(!t.can_target(0, 0, 0) && t.can_target(0, 0, 1));
+        assert!(!t.can_target(1, 2, 0) && t.can_target(1, 2, 1));
+        assert_eq!(t.relation(WILD, WILD), Relation::Ally);
+    }
+
+    #[test]
+    fn test_configured_pairs_are_symmetric_and_override() {
+        let t = FactionTable::new(&[
+            FactionRelation { a: 2, b: 1, relation: Relation::Hostile },
+            FactionRelation { a: 1, b: 10, relation: Relation::Ally },
+            FactionRelation { a: WILD, b: 3, relation: Relation::Neutral },
+        ]);
+        assert!(t.can_target(1, 2, 0) && t.can_target(2, 1, 0));
+        // Guards of faction 10 leave nation 1 alone outside PvP maps.
+        assert!(!t.can_target(10, 1, 0));
+        assert!(!t.can_target(WILD, 3, 0) && t.can_target(WILD, 3, 1));
+        assert!(t.can_target(WILD, 4, 0));
+    }
+}
@@ -0,0 +1,62 @@
+//! Native "flee at low health" decision for mob AI.
+//!
+//! A mob whose mob_db `MobFleePercent` is non-zero switches to `MOB_ESCAPE`
+//! once its vita drops below that share of maxvita, and steps away from its
+//! target each move tick until it recovers or loses the target. A zero
+//! threshold leaves escape entirely to the Lua AI scripts, as before.
+
+/// Facing/step direction as used by `MobSpawnData::side`.
+pub const SIDE_UP: i32 = 0;
+pub const SIDE_RIGHT: i32 = 1;
+pub const SIDE_DOWN: i32 = 2;
+pub const SIDE_LEFT: i32 = 3;
+
+/// True when `current` vita is below `pct` percent of `max`. Always false
+/// for `pct == 0`.
+pub fn should_flee(current: u32, max: u32, pct: u8) -> bool {
+    pct > 0 && max > 0 && (current as u64) * 100 < (max as u64) * pct.min(100) as u64
+}
+
+/// Step directions for a mob at `(mx, my)` fleeing a threat at `(tx, ty)`,
+/// best first: directly away on the dominant axis, then away on the other
+/// axis, then the remaining perpendicular. Never the step towards the threat.
+pub fn flee_sides(mx: i32, my: i32, tx: i32, ty: i32) -> [i32; 3] {
+    let dx = mx - tx;
+    let dy = my - ty;
+    let away_x = if dx >= 0 { SIDE_RIGHT } else { SIDE_LEFT };
+    let away_y = if dy >= 0 { SIDE_DOWN } else { SIDE_UP };
+    if dx.abs() >= dy.abs() {
+        [away_x, away_y, opposite(away_y)]
+    } else {
+        [away_y, away_x, opposite(away_x)]
+    }
+}
+
+fn opposite(side: i32) -> i32 {
+    (side + 2) % 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_flee_threshold() {
+        assert!(!should_flee(10, 100, 0));
+        assert!(should_flee(24, 100, 25));
+        assert!(!should_flee(25, 100, 25));
+        assert!(!should_flee(0, 0, 50));
+        assert!(should_flee(u32::MAX - 1, u32::MAX, 100));
+    }
+
+    #[test]
+    fn test_flee_sides_point_away() {
+        // Threat to the left: run right, then down/up.
+        assert_eq!(flee_sides(10, 10, 7, 9), [SIDE_RIGHT, SIDE_DOWN, SIDE_UP]);
+        // Threat below: run up first.
+        assert_eq!(flee_sides(10, 10, 10, 14), [SIDE_UP, SIDE_RIGHT, SIDE_LEFT]);
+        // Same cell: any direction except none; still three distinct choices.
+        let s = flee_sides(5, 5, 5, 5);
+        assert!(s[0] != s[1] && s[1] != s[2] && s[0] != s[2]);
+    }
+}
@@ -0,0 +1,117 @@
+//! Granting and revoking GM levels from scripts, persisted and audited.
+//!
+//! `setGmLevel(player, level, reason, by)` in Lua goes through `check`, then
+//! `persist` writes `ChaGMLevel` and a `GmAudit` row in one transaction
+//! before the in-memory level changes, so the new level survives relog even
+//! if the map server dies before the next save.
+//!
+//! The acting player (`by`) must outrank both the target's current and new
+//! level, so nobody can raise a peer or create their equal. A player may
+//! always lower their own level. Without an acting player a script can only
+//! revoke (lower); raising needs someone accountable in the audit log.
+
+use anyhow::Result;
+use sqlx::MySqlPool;
+
+pub const MAX_GM_LEVEL: i8 = 99;
+pub const MAX_REASON_LEN: usize = 255;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum GrantError {
+    #[error("level must be 0-{MAX_GM_LEVEL}")]
+    BadLevel,
+    #[error("a reason is required")]
+    NoReason,
+    #[error("raising a GM level needs an acting player")]
+    NoActor,
+    #[error("level {actor} cannot change a level {old} player to {new}")]
+    Outranked { actor: i8, old: i8, new: i8 },
+}
+
+/// Whether an actor at `actor` (`None` = script alone) may move a player
+/// from `old` to `new`. `own` = the actor is the target.
+pub fn check(actor: Option<i8>, own: bool, old: i8, new: i8) -> Result<(), GrantError> {
+    if !(0..=MAX_GM_LEVEL).contains(&new) {
+        return Err(GrantError::BadLevel);
+    }
+    match actor {
+        None if new > old => Err(GrantError::NoActor),
+        None => Ok(()),
+        Some(_) if own && new <= old => Ok(()),
+        Some(a) if a > old.max(new) => Ok(()),
+        Some(a) => Err(GrantError::Outranked { actor: a, old, new }),
+    }
+}
+
+/// `reason` trimmed and cut to `MAX_REASON_LEN` bytes at a character boundary.
+pub fn reason(raw: &str) -> Result<&str, GrantError> {
+    let r = raw.trim();
+    if r.is_empty() {
+        return Err(GrantError::NoReason);
+    }
+    let mut end = r.len().min(MAX_REASON_LEN);
+    while !r.is_char_boundary(end) {
+        end -= 1;
+    }
+    Ok(&r[..end])
+}
+
+/// One level change, as recorded in `GmAudit`.
+#[derive(Debug, Clone)]
+pub struct Grant<'a> {
+    /// 0 = no acting player.
+    pub actor_id: u32,
+    pub actor_name: &'a str,
+    pub target_id: u32,
+    pub target_name: &'a str,
+    pub old: i8,
+    pub new: i8,
+    pub reason: &'a str,
+}
+
+/// Write the new level and its audit row together.
+pub async fn persist(pool: &MySqlPool, g: &Grant<'_>) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE `Character` SET `ChaGMLevel` = ? WHERE `ChaId` = ?")
+        .bind(g.new as u32)
+        .bind(g.target_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO `GmAudit` (`GmaActorId`, `GmaActorName`, `GmaTargetId`, `GmaTargetName`, \
+         `GmaOldLevel`, `GmaNewLevel`, `GmaReason`) VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(g.actor_id)
+    .bind(g.actor_name)
+    .bind(g.target_id)
+    .bind(g.target_name)
+    .bind(g.old as u32)
+    .bind(g.new as u32)
+    .bind(g.reason)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_rules() {
+        assert_eq!(check(Some(99), false, 0, 50), Ok(()));
+        assert_eq!(check(Some(50), false, 0, 50), Err(GrantError::Outranked { actor: 50, old: 0, new: 50 }));
+        assert_eq!(check(Some(60), false, 70, 0), Err(GrantError::Outranked { actor: 60, old: 70, new: 0 }));
+        assert_eq!(check(Some(50), true, 50, 10), Ok(()));
+        assert_eq!(check(Some(50), true, 50, 60), Err(GrantError::Outranked { actor: 50, old: 50, new: 60 }));
+        assert_eq!(check(None, false, 20, 0), Ok(()));
+        assert_eq!(check(None, false, 0, 1), Err(GrantError::NoActor));
+        assert_eq!(check(Some(99), false, 0, 100), Err(GrantError::BadLevel));
+        assert_eq!(check(Some(99), false, 0, -1), Err(GrantError::BadLevel));
+
+        assert_eq!(reason("  "), Err(GrantError::NoReason));
+        assert_eq!(reason(" event host "), Ok("event host"));
+        assert_eq!(reason(&"é".repeat(200)).unwrap().len(), 254);
+    }
+}
@@ -0,0 +1,119 @@
+//! Map instance id namespace.
+//!
+//! Instances are private copies of a loaded map (one per party, for
+//! dungeons). They live in the top of the map slot array, from
+//! `INSTANCE_MAP_START` up to `MAP_SLOTS`, so an instance id is an ordinary
+//! map id to the block grid, warps and `pc_warp`, but can never collide with a
+//! static map from the `Maps` table: the loader refuses ids in this range.
+//!
+//! Instance maps are not announced to the char server, so a character must
+//! never be saved inside one: saves and logouts record the base map at the
+//! same cell (`save_map`), the map server applies the same rewrite on load,
+//! and `map_destroy_instance` warps everyone back to the base map before the
+//! slot is freed.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::database::map_db::MAP_SLOTS;
+
+/// First map id reserved for instances.
+pub const INSTANCE_MAP_START: u16 = 60000;
+
+/// True for ids in the instance range.
+pub fn is_instance(m: u16) -> bool {
+    m >= INSTANCE_MAP_START && (m as usize) < MAP_SLOTS
+}
+
+/// Live instances: instance id → base map id.
+#[derive(Debug, Default)]
+pub struct InstanceTable {
+    live: BTreeMap<u16, u16>,
+}
+
+impl InstanceTable {
+    /// Reserve the lowest free instance id for a copy of `base`. `None` when
+    /// `base` is itself an instance or the range is exhausted.
+    pub fn alloc(&mut self, base: u16) -> Option<u16> {
+        if is_instance(base) {
+            return None;
+        }
+        let id = (INSTANCE_MAP_START..MAP_SLOTS as u16).find(|id| !self.live.contains_key(id))?;
+        self.live.insert(id, base);
+        Some(id)
+    }
+
+    /// Forget `id`, returning its base map.
+    pub fn release(&mut self, id: u16) -> Option<u16> {
+        self.live.remove(&id)
+    }
+
+    pub fn base_of(&self, id: u16) -> Option<u16> {
+        self.live.get(&id).copied()
+    }
+
+    /// Map id to store for a character standing on `m`: the base map of a
+    /// live instance, `m` itself for anything else. Never an instance id: a
+    /// released one falls back to map 0, where the loader sends characters
+    /// whose saved map is not loaded.
+    pub fn save_map(&self, m: u16) -> u16 {
+        if is_instance(m) { self.base_of(m).unwrap_or(0) } else { m }
+    }
+
+    /// `(instance, base)` pairs in id order.
+    pub fn list(&self) -> Vec<(u16, u16)> {
+        self.live.iter().map(|(&i, &b)| (i, b)).collect()
+    }
+}
+
+/// Instances on this map server. Only touched from the game thread, but
+/// behind a Mutex so the GM/Lua accessors need no unsafe.
+pub static INSTANCES: Mutex<InstanceTable> = Mutex::new(InstanceTable { live: BTreeMap::new() });
+
+/// Base map of a live instance.
+pub fn base_of(id: u16) -> Option<u16> {
+    INSTANCES.lock().unwrap().base_of(id)
+}
+
+/// See `InstanceTable::save_map`.
+pub fn save_map(m: u16) -> u16 {
+    INSTANCES.lock().unwrap().save_map(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_reuses_lowest_free_id() {
+        let mut t = InstanceTable::default();
+        assert_eq!(t.alloc(10), Some(INSTANCE_MAP_START));
+        assert_eq!(t.alloc(10), Some(INSTANCE_MAP_START + 1));
+        assert_eq!(t.release(INSTANCE_MAP_START), Some(10));
+        assert_eq!(t.alloc(12), Some(INSTANCE_MAP_START));
+        assert_eq!(t.base_of(INSTANCE_MAP_START), Some(12));
+        assert_eq!(t.release(INSTANCE_MAP_START + 7), None);
+    }
+
+    #[test]
+    fn test_instances_do_not_nest_or_overflow() {
+        let mut t = InstanceTable::default();
+        assert_eq!(t.alloc(INSTANCE_MAP_START), None);
+        let n = MAP_SLOTS - INSTANCE_MAP_START as usize;
+        for _ in 0..n {
+            assert!(t.alloc(1).is_some());
+        }
+        assert_eq!(t.alloc(1), None);
+        assert!(!is_instance(INSTANCE_MAP_START - 1) && is_instance(MAP_SLOTS as u16 - 1));
+    }
+
+    #[test]
+    fn test_save_map_records_the_base() {
+        let mut t = InstanceTable::default();
+        let id = t.alloc(42).unwrap();
+        assert_eq!(t.save_map(id), 42);
+        assert_eq!(t.save_map(42), 42);
+        t.release(id);
+        assert_eq!(t.save_map(id), 0);
+    }
+}
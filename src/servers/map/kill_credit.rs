@@ -0,0 +1,64 @@
+//! Who gets credit for a mob kill.
+//!
+//! The mob's threat table already accumulates every player's damage (plus
+//! threat scripts add), so one rule here replaces per-mob "who gets credit"
+//! Lua: the killing blow is credited first, then every other threat holder
+//! as an assist, most threat first. The list travels with the `mobKill`
+//! event.
+
+/// One credited participant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Credit {
+    pub id: u32,
+    /// Threat accumulated against the mob.
+    pub amount: u32,
+    pub killing_blow: bool,
+}
+
+/// Credits for a kill by `killer` (0 if unknown) given the threat table's
+/// `(user, amount)` slots. Empty slots are skipped and repeated users summed.
+pub fn attribute(killer: u32, threat: impl IntoIterator<Item = (u32, u32)>) -> Vec<Credit> {
+    let mut credits: Vec<Credit> = Vec::new();
+    for (id, amount) in threat {
+        if id == 0 {
+            continue;
+        }
+        match credits.iter_mut().find(|c| c.id == id) {
+            Some(c) => c.amount = c.amount.saturating_add(amount),
+            None => credits.push(Credit { id, amount, killing_blow: id == killer }),
+        }
+    }
+    if killer != 0 && !credits.iter().any(|c| c.killing_blow) {
+        credits.push(Credit { id: killer, amount: 0, killing_blow: true });
+    }
+    credits.retain(|c| c.killing_blow || c.amount > 0);
+    credits.sort_by(|a, b| {
+        b.killing_blow.cmp(&a.killing_blow)
+            .then(b.amount.cmp(&a.amount))
+            .then(a.id.cmp(&b.id))
+    });
+    credits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_killing_blow_first_then_assists_by_threat() {
+        let c = attribute(7, [(3, 50), (0, 99), (7, 10), (5, 80), (3, 40), (9, 0)]);
+        assert_eq!(c, vec![
+            Credit { id: 7, amount: 10, killing_blow: true },
+            Credit { id: 3, amount: 90, killing_blow: false },
+            Credit { id: 5, amount: 80, killing_blow: false },
+        ]);
+    }
+
+    #[test]
+    fn test_killer_missing_from_threat_table() {
+        let c = attribute(4, [(2, 5)]);
+        assert_eq!(c[0], Credit { id: 4, amount: 0, killing_blow: true });
+        assert_eq!(c.len(), 2);
+        assert_eq!(attribute(0, [(2, 5)]), vec![Credit { id: 2, amount: 5, killing_blow: false }]);
+    }
+}
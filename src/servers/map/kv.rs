@@ -0,0 +1,311 @@
+//! World-scoped key-value store for scripts, with per-key expiry.
+//!
+//! `kv.set(key, value, ttlSecs)` / `kv.get(key)` / `kv.del(key)` in Lua. Values
+//! are integers or strings; a key without a TTL lives until deleted. Expired
+//! keys read as nil straight away and are dropped by the next sweep. Expiry
+//! is absolute unix time, so a key set for "24 hours" still means 24 hours
+//! across a restart: the store is saved to `ScriptKv` alongside the
+//! server-state autosave and reloaded at boot, behind the same load gate.
+//!
+//! The store is bounded (`MAX_ENTRIES`, `MAX_KEY_LEN`, `MAX_VALUE_LEN`) so a
+//! runaway script can't grow it without limit; a set that would exceed the
+//! bound fails instead of evicting someone else's key.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+use super::server_state::LoadGate;
+
+pub const MAX_ENTRIES: usize = 10_000;
+pub const MAX_KEY_LEN: usize = 64;
+pub const MAX_VALUE_LEN: usize = 1024;
+/// Current on-disk format version.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KvValue {
+    Int(i64),
+    Str(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    value: KvValue,
+    /// Unix seconds; `None` never expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+}
+
+impl Entry {
+    fn live(&self, now: u64) -> bool {
+        self.expires.is_none_or(|t| now < t)
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum KvError {
+    #[error("key must be 1-{MAX_KEY_LEN} bytes")]
+    BadKey,
+    #[error("string values are limited to {MAX_VALUE_LEN} bytes")]
+    ValueTooLong,
+    #[error("store is full ({MAX_ENTRIES} keys)")]
+    Full,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Document {
+    version: u32,
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Default)]
+pub struct KvStore {
+    entries: HashMap<String, Entry>,
+    generation: u64,
+    saved_generation: u64,
+}
+
+impl KvStore {
+    /// Store `value` under `key`, expiring `ttl` seconds after `now` (`None`
+    /// or 0: never).
+    pub fn set(&mut self, key: &str, value: KvValue, ttl: Option<u64>, now: u64) -> Result<(), KvError> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN {
+            return Err(KvError::BadKey);
+        }
+        if matches!(&value, KvValue::Str(s) if s.len() > MAX_VALUE_LEN) {
+            return Err(KvError::ValueTooLong);
+        }
+        if !self.entries.contains_key(key) && self.entries.len() >= MAX_ENTRIES && self.sweep(now) == 0 {
+            return Err(KvError::Full);
+        }
+        let expires = ttl.filter(|&t| t > 0).map(|t| now.saturating_add(t));
+        self.entries.insert(key.to_string(), Entry { value, expires });
+        self.generation += 1;
+        Ok(())
+    }
+
+    /// The live value for `key`; an expired key is removed.
+    pub fn get(&mut self, key: &str, now: u64) -> Option<KvValue> {
+        match self.entries.get(key) {
+            Some(e) if e.live(now) => Some(e.value.clone()),
+            Some(_) => {
+                self.del(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Seconds until `key` expires; `None` if missing, expired or permanent.
+    pub fn ttl(&self, key: &str, now: u64) -> Option<u64> {
+        self.entries.get(key).filter(|e| e.live(now)).and_then(|e| e.expires).map(|t| t - now)
+    }
+
+    pub fn del(&mut self, key: &str) -> bool {
+        let removed = self.entries.remove(key).is_some();
+        if removed {
+            self.generation += 1;
+        }
+        removed
+    }
+
+    /// Drop every expired key; returns how many went.
+    pub fn sweep(&mut self, now: u64) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, e| e.live(now));
+        let n = before - self.entries.len();
+        if n > 0 {
+            self.generation += 1;
+        }
+        n
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.generation != self.saved_generation
+    }
+
+    pub fn encode(&self) -> (String, u64) {
+        let doc = Document { version: FORMAT_VERSION, entries: self.entries.clone() };
+        (serde_json::to_string(&doc).expect("kv entries always serialize"), self.generation)
+    }
+
+    pub fn mark_saved(&mut self, generation: u64) {
+        self.saved_generation = self.saved_generation.max(generation);
+    }
+
+    /// Replace the contents with a stored document, dropping anything that
+    /// expired while the server was down.
+    pub fn decode(&mut self, data: &str, now: u64) -> Result<()> {
+        let doc: Document = serde_json::from_str(data).context("malformed kv store")?;
+        anyhow::ensure!(
+            doc.version <= FORMAT_VERSION,
+            "kv store version {} is newer than this build ({})", doc.version, FORMAT_VERSION
+        );
+        self.entries = doc.entries;
+        self.entries.retain(|_, e| e.live(now));
+        self.generation = 0;
+        self.saved_generation = 0;
+        Ok(())
+    }
+}
+
+static STORE: Mutex<Option<KvStore>> = Mutex::new(None);
+static GATE: LoadGate = LoadGate::new("ScriptKv");
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn with<R>(f: impl FnOnce(&mut KvStore, u64) -> R) -> R {
+    f(STORE.lock().unwrap().get_or_insert_with(KvStore::default), now())
+}
+
+pub fn set(key: &str, value: KvValue, ttl: Option<u64>) -> Result<(), KvError> {
+    with(|s, now| s.set(key, value, ttl, now))
+}
+
+pub fn get(key: &str) -> Option<KvValue> {
+    with(|s, now| s.get(key, now))
+}
+
+pub fn ttl(key: &str) -> Option<u64> {
+    with(|s, now| s.ttl(key, now))
+}
+
+pub fn del(key: &str) -> bool {
+    with(|s, _| s.del(key))
+}
+
+pub fn sweep() -> usize {
+    with(|s, now| s.sweep(now))
+}
+
+/// Load this server's store. Returns the number of live keys.
+pub async fn load(pool: &MySqlPool, server_id: i32) -> Result<usize> {
+    let row: Option<(String,)> = sqlx::query_as(
+        "SELECT `SkvData` FROM `ScriptKv` WHERE `SkvServerId` = ?",
+    )
+    .bind(server_id)
+    .fetch_optional(pool)
+    .await
+    .context("reading ScriptKv")?;
+
+    let mut fresh = KvStore::default();
+    if let Some((data,)) = row {
+        fresh.decode(&data, now())?;
+    }
+    let n = fresh.len();
+    *STORE.lock().unwrap() = Some(fresh);
+    GATE.open();
+    Ok(n)
+}
+
+/// Sweep, then write the store if anything changed since the last flush.
+/// Returns whether a write happened. Keys set after a failed boot load are
+/// kept in memory only.
+pub async fn flush(pool: &MySqlPool, server_id: i32) -> Result<bool> {
+    GATE.check()?;
+    let (data, generation) = {
+        let mut guard = STORE.lock().unwrap();
+        match guard.as_mut() {
+            Some(s) => {
+                s.sweep(now());
+                if !s.is_dirty() {
+                    return Ok(false);
+                }
+                s.encode()
+            }
+            None => return Ok(false),
+        }
+    };
+    sqlx::query(
+        "INSERT INTO `ScriptKv` (`SkvServerId`, `SkvVersion`, `SkvData`) VALUES (?, ?, ?) \
+         ON DUPLICATE KEY UPDATE `SkvVersion` = VALUES(`SkvVersion`), `SkvData` = VALUES(`SkvData`)",
+    )
+    .bind(server_id)
+    .bind(FORMAT_VERSION)
+    .bind(&data)
+    .execute(pool)
+    .await
+    .context("writing ScriptKv")?;
+    with(|s, _| s.mark_saved(generation));
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_flush_refuses_before_load() {
+        // No test loads the store, so the gate is still shut.
+        let pool = crate::servers::testing::unreachable_pool();
+        let err = flush(&pool, 0).await.unwrap_err();
+        assert!(err.to_string().contains("ScriptKv was not loaded"), "{err}");
+    }
+
+    #[test]
+    fn test_ttl_expires_lazily_and_on_sweep() {
+        let mut s = KvStore::default();
+        s.set("boss.spawned", KvValue::Int(1), Some(60), 1000).unwrap();
+        s.set("motd", KvValue::Str("hi".into()), None, 1000).unwrap();
+        s.set("short", KvValue::Int(2), Some(5), 1000).unwrap();
+        assert_eq!(s.ttl("boss.spawned", 1010), Some(50));
+        assert_eq!(s.ttl("motd", 1010), None);
+        assert_eq!(s.get("short", 1004), Some(KvValue::Int(2)));
+        assert_eq!(s.get("short", 1005), None);
+        assert_eq!(s.len(), 2, "expired key removed on access");
+        assert_eq!(s.sweep(1060), 1);
+        assert_eq!(s.get("motd", u64::MAX), Some(KvValue::Str("hi".into())));
+        assert!(s.del("motd") && !s.del("motd"));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut s = KvStore::default();
+        assert_eq!(s.set("", KvValue::Int(1), None, 0), Err(KvError::BadKey));
+        assert_eq!(s.set(&"k".repeat(MAX_KEY_LEN + 1), KvValue::Int(1), None, 0), Err(KvError::BadKey));
+        let long = KvValue::Str("x".repeat(MAX_VALUE_LEN + 1));
+        assert_eq!(s.set("k", long, None, 0), Err(KvError::ValueTooLong));
+        for i in 0..MAX_ENTRIES {
+            s.set(&i.to_string(), KvValue::Int(1), if i == 0 { Some(10) } else { None }, 0).unwrap();
+        }
+        // Overwriting is fine; a new key needs a slot, which a sweep can free.
+        s.set("1", KvValue::Int(2), None, 0).unwrap();
+        assert_eq!(s.set("new", KvValue::Int(1), None, 5), Err(KvError::Full));
+        s.set("new", KvValue::Int(1), None, 10).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip_drops_expired() {
+        let mut s = KvStore::default();
+        s.set("a", KvValue::Int(-3), None, 100).unwrap();
+        s.set("b", KvValue::Str("x".into()), Some(10), 100).unwrap();
+        s.set("c", KvValue::Int(1), Some(1000), 100).unwrap();
+        let (data, gen) = s.encode();
+        s.mark_saved(gen);
+        assert!(!s.is_dirty());
+
+        let mut r = KvStore::default();
+        r.decode(&data, 200).unwrap();
+        assert_eq!((r.len(), r.get("a", 200), r.get("b", 200)), (2, Some(KvValue::Int(-3)), None));
+        assert_eq!(r.ttl("c", 200), Some(900));
+        assert!(r.decode(r#"{"version":2,"entries":{}}"#, 0).is_err());
+    }
+}
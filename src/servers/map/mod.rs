@@ -1,18 +1,49 @@
+pub mod bank;
 pub mod char;
+pub mod chat;
+pub mod craft;
+pub mod death;
+pub mod faction;
+pub mod flee;
+pub mod gm_grant;
+pub mod instance;
+pub mod kill_credit;
+pub mod kv;
+pub mod movement;
+pub mod newbie;
+pub mod onetime_ids;
 pub mod packet;
+pub mod packet_hooks;
+pub mod player_filter;
+pub mod presence;
+pub mod rates;
+pub mod resume;
+pub mod script_refs;
+pub mod server_state;
+pub mod shop;
+pub mod spawn_shards;
+pub mod tick_budget;
 
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use sqlx::MySqlPool;
-use crate::config::ServerConfig;
+use crate::config::{LiveConfig, ServerConfig};
 
 pub struct MapState {
     pub db: MySqlPool,
-    pub config: ServerConfig,
+    /// Live config, swapped on SIGHUP alongside the FFI copy
+    pub config: Arc<LiveConfig>,
     /// Raw TCP write channel to char_server. None = not connected.
     pub char_tx: Mutex<Option<tokio::sync::mpsc::Sender<Vec<u8>>>>,
     /// Pending auth tokens: char_name → session fd on map server
     pub auth_db: Mutex<std::collections::HashMap<String, AuthEntry>>,
+    /// Players parked after a transient drop, awaiting reconnect.
+    /// std::sync::Mutex: touched from C callbacks on the game thread.
+    pub resume: std::sync::Mutex<resume::ResumeTable>,
+    /// Last map reported to char_server per character (0x3014).
+    pub presence: std::sync::Mutex<presence::MapPresence>,
+    /// Signalled by char_server's 0x3813 shutdown ack.
+    pub shutdown_ack: tokio::sync::Notify,
 }
 
 #[derive(Debug, Clone)]
@@ -27,9 +58,12 @@ impl MapState {
     pub fn new(db: MySqlPool, config: ServerConfig) -> Self {
         Self {
             db,
-            config,
+            config: Arc::new(LiveConfig::new(config)),
             char_tx: Mutex::new(None),
             auth_db: Mutex::new(std::collections::HashMap::new()),
+            resume: std::sync::Mutex::new(resume::ResumeTable::default()),
+            presence: std::sync::Mutex::new(presence::MapPresence::default()),
+            shutdown_ack: tokio::sync::Notify::new(),
         }
     }
 }
@@ -0,0 +1,164 @@
+//! Server-side walk rate check (anti-speedhack).
+//!
+//! Each walk packet moves a player one tile, so a speedhack shows up as walk
+//! packets arriving faster than the character's `speed` allows. A step takes
+//! `BASE_STEP_MS * speed / 100` ms (the timing the old commented-out C check
+//! used), shortened by `move_tolerance_pct` for latency jitter. Steps are
+//! paced with a GCRA-style schedule that lets `move_burst` early steps
+//! through (packets bunched by the network) but holds the long-run rate.
+//!
+//! A refused step is bounced back like a blocked move. Refusals within
+//! `STRIKE_WINDOW_MS` of each other count as strikes; the player is reported
+//! to GMs at `REPORT_STRIKES` and disconnected at `move_kick_after` (0 = never).
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::Instant;
+
+/// Milliseconds per tile at speed 100.
+pub const BASE_STEP_MS: u64 = 330;
+/// Strikes at which a player is reported to GMs.
+pub const REPORT_STRIKES: u32 = 5;
+/// Quiet time after which strikes reset.
+pub const STRIKE_WINDOW_MS: u64 = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovePolicy {
+    pub enabled: bool,
+    /// Percent shaved off each step's duration (0-90).
+    pub tolerance_pct: u8,
+    /// Early steps allowed back to back.
+    pub burst: u8,
+    /// Strikes before disconnect; 0 = never.
+    pub kick_after: u32,
+}
+
+impl MovePolicy {
+    pub const OFF: Self = Self { enabled: false, tolerance_pct: 0, burst: 0, kick_after: 0 };
+
+    /// Minimum time per tile for `speed`, after tolerance.
+    pub fn step_ms(&self, speed: i32) -> u64 {
+        let base = BASE_STEP_MS * speed.clamp(1, 1000) as u64 / 100;
+        base * (100 - self.tolerance_pct.min(90) as u64) / 100
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Reject { strikes: u32 },
+    /// Refused, and the strike count just reached `REPORT_STRIKES`.
+    Report { strikes: u32 },
+    /// Refused, and the strike count reached `kick_after`.
+    Kick { strikes: u32 },
+}
+
+/// Per-player pacing state.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MoveClock {
+    /// When the next step is due at the sustained rate.
+    due: u64,
+    strikes: u32,
+    last_strike: u64,
+}
+
+impl MoveClock {
+    pub fn step(&mut self, policy: &MovePolicy, speed: i32, now: u64) -> Verdict {
+        let step = policy.step_ms(speed);
+        if now + step * policy.burst as u64 >= self.due {
+            self.due = self.due.max(now) + step;
+            return Verdict::Allow;
+        }
+        if now.saturating_sub(self.last_strike) > STRIKE_WINDOW_MS {
+            self.strikes = 0;
+        }
+        self.strikes += 1;
+        self.last_strike = now;
+        match self.strikes {
+            s if policy.kick_after > 0 && s >= policy.kick_after => Verdict::Kick { strikes: s },
+            REPORT_STRIKES => Verdict::Report { strikes: REPORT_STRIKES },
+            s => Verdict::Reject { strikes: s },
+        }
+    }
+}
+
+static POLICY: RwLock<MovePolicy> = RwLock::new(MovePolicy::OFF);
+static CLOCKS: Mutex<Option<HashMap<u32, MoveClock>>> = Mutex::new(None);
+
+/// Replace the live policy (boot, config reload).
+pub fn install(policy: MovePolicy) {
+    *POLICY.write().unwrap() = policy;
+}
+
+/// Milliseconds since first use (monotonic, never 0).
+fn now_ms() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64 + 1
+}
+
+/// Check one walk step by `char_id` against the live policy.
+pub fn check(char_id: u32, speed: i32) -> Verdict {
+    let now = now_ms();
+    let policy = *POLICY.read().unwrap();
+    if !policy.enabled {
+        return Verdict::Allow;
+    }
+    let mut clocks = CLOCKS.lock().unwrap();
+    clocks.get_or_insert_with(HashMap::new).entry(char_id).or_default().step(&policy, speed, now)
+}
+
+/// `char_id` left this server.
+pub fn forget(char_id: u32) {
+    if let Some(clocks) = CLOCKS.lock().unwrap().as_mut() {
+        clocks.remove(&char_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: MovePolicy = MovePolicy { enabled: true, tolerance_pct: 0, burst: 2, kick_after: 8 };
+
+    #[test]
+    fn test_honest_walking_passes_with_jitter() {
+        let mut c = MoveClock::default();
+        let step = POLICY.step_ms(100);
+        assert_eq!(step, 330);
+        // Steady pace with packets arriving in bunches of three.
+        let mut t = 1_000;
+        for _ in 0..20 {
+            for k in 0..3 {
+                assert_eq!(c.step(&POLICY, 100, t + k * 5), Verdict::Allow);
+            }
+            t += 3 * step;
+        }
+    }
+
+    #[test]
+    fn test_fast_walker_is_refused_reported_and_kicked() {
+        let mut c = MoveClock::default();
+        let mut verdicts = Vec::new();
+        for i in 0..20u64 {
+            verdicts.push(c.step(&POLICY, 100, 1_000 + i * 100));
+        }
+        let allowed = verdicts.iter().filter(|v| **v == Verdict::Allow).count();
+        assert!(allowed < 10, "{allowed} steps allowed at 3x speed");
+        assert!(verdicts.contains(&Verdict::Report { strikes: REPORT_STRIKES }));
+        assert!(verdicts.contains(&Verdict::Kick { strikes: 8 }));
+
+        // Strikes reset after a quiet minute.
+        let later = 1_000 + 20 * 100 + STRIKE_WINDOW_MS + 1;
+        c.step(&POLICY, 100, later);
+        c.step(&POLICY, 100, later);
+        c.step(&POLICY, 100, later);
+        assert_eq!(c.step(&POLICY, 100, later), Verdict::Reject { strikes: 1 });
+    }
+
+    #[test]
+    fn test_tolerance_shortens_step() {
+        let p = MovePolicy { tolerance_pct: 25, ..POLICY };
+        assert_eq!(p.step_ms(80), 198);
+        assert_eq!(MovePolicy { tolerance_pct: 200, ..POLICY }.step_ms(100), 33);
+    }
+}
@@ -0,0 +1,64 @@
+//! Newbie protection around the welcome area.
+//!
+//! A player below `newbie_protect_until_level` standing within
+//! `newbie_safe_radius` tiles of `start_point` (same map) can't be targeted:
+//! mobs won't acquire them and PvP damage against them is refused. Radius 0
+//! extends the protection to every map, so it lasts purely by level. The flag
+//! is derived from level and position each time, never stored.
+
+use std::sync::RwLock;
+
+use crate::config::Point;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NewbiePolicy {
+    /// Protected while below this level; 0 = off.
+    pub until_level: u8,
+    /// Chebyshev distance from `start`; 0 = anywhere.
+    pub safe_radius: u16,
+    pub start: Point,
+}
+
+impl NewbiePolicy {
+    pub const OFF: Self = Self { until_level: 0, safe_radius: 0, start: Point { m: 0, x: 0, y: 0 } };
+
+    pub fn protects(&self, level: u8, at: Point) -> bool {
+        if level >= self.until_level {
+            return false;
+        }
+        self.safe_radius == 0
+            || (at.m == self.start.m
+                && at.x.abs_diff(self.start.x) <= self.safe_radius
+                && at.y.abs_diff(self.start.y) <= self.safe_radius)
+    }
+}
+
+static POLICY: RwLock<NewbiePolicy> = RwLock::new(NewbiePolicy::OFF);
+
+/// Replace the live policy (boot, config reload).
+pub fn install(policy: NewbiePolicy) {
+    *POLICY.write().unwrap() = policy;
+}
+
+/// `NewbiePolicy::protects` on the live policy.
+pub fn protects(level: u8, at: Point) -> bool {
+    POLICY.read().unwrap().protects(level, at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protection_by_level_and_area() {
+        let p = NewbiePolicy { until_level: 10, safe_radius: 5, start: Point::new(1, 20, 20) };
+        assert!(p.protects(9, Point::new(1, 25, 15)));
+        assert!(!p.protects(10, Point::new(1, 20, 20)));
+        assert!(!p.protects(1, Point::new(1, 26, 20)));
+        assert!(!p.protects(1, Point::new(2, 20, 20)));
+
+        let anywhere = NewbiePolicy { safe_radius: 0, ..p };
+        assert!(anywhere.protects(9, Point::new(7, 0, 0)));
+        assert!(!NewbiePolicy::OFF.protects(0, Point::new(0, 0, 0)));
+    }
+}
@@ -0,0 +1,105 @@
+//! Block-id allocation for one-time (scripted) mobs.
+//!
+//! Ids are handed out from `[start, limit)`. Freed ids below the high-water
+//! mark go on a free list and are reused lowest first; freeing the topmost id
+//! lowers the mark past every free id beneath it, so holes left by mobs dying
+//! out of spawn order never strand the ids above them. The mark is what the
+//! mob timer and GM listings iterate up to (`MOB_ONETIME_MAX`).
+
+use std::collections::BTreeSet;
+
+#[derive(Debug)]
+pub struct OnetimeIds {
+    start: u32,
+    limit: u32,
+    /// One past the highest id in use.
+    top: u32,
+    free: BTreeSet<u32>,
+}
+
+impl OnetimeIds {
+    pub const fn new(start: u32, limit: u32) -> Self {
+        Self { start, limit, top: start, free: BTreeSet::new() }
+    }
+
+    /// One past the highest id in use.
+    pub fn top(&self) -> u32 {
+        self.top
+    }
+
+    /// Ids below `top` that are free.
+    pub fn holes(&self) -> usize {
+        self.free.len()
+    }
+
+    /// The lowest free id, or `None` once the range is exhausted.
+    pub fn alloc(&mut self) -> Option<u32> {
+        if let Some(id) = self.free.pop_first() {
+            return Some(id);
+        }
+        (self.top < self.limit).then(|| {
+            self.top += 1;
+            self.top - 1
+        })
+    }
+
+    /// Return `id` to the pool. False if it was not allocated.
+    pub fn release(&mut self, id: u32) -> bool {
+        if id < self.start || id >= self.top || !self.free.insert(id) {
+            return false;
+        }
+        while self.top > self.start && self.free.remove(&(self.top - 1)) {
+            self.top -= 1;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_out_of_order_frees_do_not_leak() {
+        let mut ids = OnetimeIds::new(100, 200);
+        let a: Vec<u32> = (0..10).map(|_| ids.alloc().unwrap()).collect();
+        assert_eq!((a[0], ids.top()), (100, 110));
+
+        // Free from the middle first: the mark stays, the holes are reused.
+        for &id in &[a[3], a[5], a[4]] {
+            assert!(ids.release(id));
+        }
+        assert!(!ids.release(a[4]));
+        assert_eq!((ids.top(), ids.holes()), (110, 3));
+        assert_eq!(ids.alloc(), Some(103));
+        assert!(ids.release(103));
+
+        // Freeing the top trims through the holes beneath it.
+        for &id in &[a[9], a[7], a[8], a[6]] {
+            assert!(ids.release(id));
+        }
+        assert_eq!((ids.top(), ids.holes()), (103, 0));
+        for &id in &[a[1], a[0], a[2]] {
+            assert!(ids.release(id));
+        }
+        assert_eq!((ids.top(), ids.holes()), (100, 0));
+
+        // Many rounds of interleaved spawn/kill never grow the range.
+        for round in 0..50u32 {
+            let batch: Vec<u32> = (0..8).map(|_| ids.alloc().unwrap()).collect();
+            for i in [3, 0, 7, 5, 1, 6, 2, 4] {
+                ids.release(batch[(i + round as usize) % 8]);
+            }
+        }
+        assert_eq!((ids.top(), ids.holes()), (100, 0));
+    }
+
+    #[test]
+    fn test_exhaustion() {
+        let mut ids = OnetimeIds::new(5, 7);
+        assert_eq!((ids.alloc(), ids.alloc(), ids.alloc()), (Some(5), Some(6), None));
+        assert!(ids.release(5));
+        assert_eq!(ids.alloc(), Some(5));
+        assert!(!ids.release(7));
+    }
+}
@@ -1,13 +1,14 @@
 use std::sync::Arc;
 use super::MapState;
+use crate::servers::char::packet::SaveNowResult;
 
-/// Packet length table for incoming 0x3800–0x3811 packets from char_server.
+/// Packet length table for incoming 0x3800–0x3813 packets from char_server.
 /// Index = cmd - 0x3800. -1 = variable (read 4-byte len at offset 2). 0 = unknown.
 pub const PKT_LENS: &[i32] = &[
     4,   // 0x3800 accept
     -1,  // 0x3801 mapset (variable)
     38,  // 0x3802 authadd
-    -1,  // 0x3803 charload (variable, zlib)
+    -1,  // 0x3803 charload (variable, compressed)
     6,   // 0x3804 checkonline
     -1,  // 0x3805 unused
     255, // 0x3806 unused
@@ -22,6 +23,8 @@ pub const PKT_LENS: &[i32] = &[
     -1,  // 0x380F readpost (variable)
     255, // 0x3810 unused
     30,  // 0x3811
+    9,   // 0x3812 savenowack
+    6,   // 0x3813 shutdownack
 ];
 
 pub async fn dispatch(state: &Arc<MapState>, cmd: u16, pkt: &[u8]) {
@@ -32,26 +35,17 @@ pub async fn dispatch(state: &Arc<MapState>, cmd: u16, pkt: &[u8]) {
         0x3803 => handle_charload(state, pkt).await,
         0x3804 => handle_checkonline(state, pkt).await,
         0x3808..=0x380F => forward_to_c(state, cmd, pkt).await,
+        0x3812 => handle_save_now_ack(state, pkt).await,
+        0x3813 => handle_shutdown_ack(state, pkt),
         _ => tracing::warn!("[map] [charif] unhandled cmd={:04X}", cmd),
     }
 }
 
-/// 0x3800 — char_server accepted our registration.
-/// C: intif_parse_accept — sends back 0x3001 with map list.
-async fn handle_accept(state: &Arc<MapState>, pkt: &[u8]) {
-    if pkt.len() < 4 { return; }
-    if pkt[2] != 0 {
-        tracing::warn!("[map] [charif] char_server rejected connection result={}", pkt[2]);
-        return;
-    }
-    let server_id = pkt[3];
-    tracing::info!("[map] [charif] Connected to Char Server server_id={}", server_id);
-
-    tracing::info!("[map] [charif] handle_accept");
-    // Collect loaded map IDs from the Rust-owned map array.
+/// Ids of every map loaded on this server.
+pub fn loaded_map_ids() -> Vec<u16> {
     // map[i].tile != NULL means the map was loaded (same check as C gm_command.c:1504).
     #[cfg(not(test))]
-    let map_ids: Vec<u16> = unsafe {
+    let map_ids = unsafe {
         let map_ptr = crate::ffi::map_db::map;
         let map_n   = crate::ffi::map_db::map_n as usize;
         if map_ptr.is_null() {
@@ -65,21 +59,42 @@ async fn handle_accept(state: &Arc<MapState>, pkt: &[u8]) {
         }
     };
     #[cfg(test)]
-    let map_ids: Vec<u16> = vec![];
+    let map_ids = vec![];
+    map_ids
+}
 
-    // 0x3001 packet: [0..2]=cmd, [2..6]=total_len (u32 LE), [6..8]=map_count (u16 LE),
-    //                [8..] = map_ids (u16 LE each)
+/// 0x3001 — the full map list; char_server replaces what it had for us.
+///
+/// Layout: [0..2]=cmd, [2..6]=total_len (u32 LE), [6..8]=map_count (u16 LE),
+///         [8..] = map_ids (u16 LE each)
+pub fn mapset_packet(map_ids: &[u16]) -> Vec<u8> {
     let map_count = map_ids.len() as u16;
     let total_len = 8u32 + map_count as u32 * 2;
     let mut resp = Vec::with_capacity(total_len as usize);
     resp.extend_from_slice(&0x3001u16.to_le_bytes());
     resp.extend_from_slice(&total_len.to_le_bytes());
     resp.extend_from_slice(&map_count.to_le_bytes());
-    for id in &map_ids {
+    for id in map_ids {
         resp.extend_from_slice(&id.to_le_bytes());
     }
-    tracing::info!("[map] [charif] sending map list count={}", map_count);
-    send_to_char(state, resp).await;
+    resp
+}
+
+/// 0x3800 — char_server accepted our registration.
+/// C: intif_parse_accept — sends back 0x3001 with map list.
+async fn handle_accept(state: &Arc<MapState>, pkt: &[u8]) {
+    if pkt.len() < 4 { return; }
+    if pkt[2] != 0 {
+        tracing::warn!("[map] [charif] char_server rejected connection result={}", pkt[2]);
+        return;
+    }
+    let server_id = pkt[3];
+    tracing::info!("[map] [charif] Connected to Char Server server_id={}", server_id);
+
+    tracing::info!("[map] [charif] handle_accept");
+    let map_ids = loaded_map_ids();
+    tracing::info!("[map] [charif] sending map list count={}", map_ids.len());
+    send_to_char(state, mapset_packet(&map_ids)).await;
 }
 
 /// 0x3802 — char_server is routing a player to this map server.
@@ -114,23 +129,26 @@ async fn handle_authadd(state: &Arc<MapState>, pkt: &[u8]) {
     send_to_char(state, resp).await;
 }
 
-/// 0x3803 — char_server sent a zlib-compressed mmo_charstatus for a player session.
+/// 0x3803 — char_server sent a compressed mmo_charstatus for a player session.
 /// C: intif_parse_charload — decompresses and calls intif_mmo_tosd(fd, status).
 async fn handle_charload(_state: &Arc<MapState>, pkt: &[u8]) {
     tracing::info!("[map] [charif] handle_charload len={}", pkt.len());
     if pkt.len() < 8 { return; }
     let session_fd = u16::from_le_bytes([pkt[6], pkt[7]]);
-    let compressed = &pkt[8..];
-
-    use std::io::Read;
-    use flate2::read::ZlibDecoder;
-    let mut dec = ZlibDecoder::new(compressed);
-    let mut raw = Vec::new();
-    if dec.read_to_end(&mut raw).is_err() {
-        tracing::warn!("[map] [charif] charload: zlib decompression failed");
+    let mut raw = match crate::servers::char::codec::decompress(&pkt[8..]) {
+        Ok(raw) => raw,
+        Err(e) => {
+            tracing::warn!("[map] [charif] charload: decompression failed: {}", e);
+            return;
+        }
+    };
+    tracing::info!("[map] [charif] charload session_fd={} bytes={}", session_fd, raw.len());
+    if let Err(e) = crate::servers::char::charstatus::char_status_payload(&raw) {
+        tracing::error!("[map] [charif] charload session_fd={} rejected: {}", session_fd, e);
         return;
     }
-    tracing::info!("[map] [charif] charload session_fd={} bytes={}", session_fd, raw.len());
+    // C copies a bare mmo_charstatus; drop the wire header.
+    raw.drain(..crate::servers::char::charstatus::CHARSTATUS_HEADER_LEN);
 
     // Hand off to C game logic: intif_mmo_tosd allocates USER, queries position,
     // calls pc_setpos + all clif_send* to put the player in the world.
@@ -172,10 +190,15 @@ async fn handle_charload(_state: &Arc<MapState>, pkt: &[u8]) {
 }
 
 /// 0x3804 — char_server is checking / forcing a player offline.
-async fn handle_checkonline(_state: &Arc<MapState>, pkt: &[u8]) {
+async fn handle_checkonline(state: &Arc<MapState>, pkt: &[u8]) {
     tracing::info!("[map] [charif] handle_checkonline len={}", pkt.len());
     if pkt.len() < 6 { return; }
     let char_id = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]);
+    // A parked player has no socket to kick; let the next resume sweep log it out.
+    if state.resume.lock().unwrap().expire_now(char_id) {
+        tracing::info!("[map] [charif] checkonline char_id={} was parked, expiring", char_id);
+        return;
+    }
     // TODO: kick the player from the map once map_parse.c FFI is wired
     tracing::info!("[map] [charif] checkonline char_id={} (kick TODO)", char_id);
 }
@@ -186,6 +209,61 @@ async fn forward_to_c(_state: &Arc<MapState>, cmd: u16, _pkt: &[u8]) {
     tracing::debug!("[map] [charif] forward_to_c cmd={:04X} (TODO: call C handler)", cmd);
 }
 
+/// 0x3812 — char_server finished (or refused) a 0x3011 save-now request.
+/// Layout: [2..6]=char_id, [6..8]=requester fd, [8]=SaveNowResult.
+async fn handle_save_now_ack(_state: &Arc<MapState>, pkt: &[u8]) {
+    if pkt.len() < 9 { return; }
+    let char_id = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]);
+    let requester = u16::from_le_bytes([pkt[6], pkt[7]]);
+    match SaveNowResult::from_u8(pkt[8]) {
+        Some(SaveNowResult::Saved) => {
+            tracing::info!("[map] [charif] savenow char_id={} committed", char_id);
+        }
+        Some(r) => {
+            tracing::warn!("[map] [charif] savenow char_id={} refused: {}", char_id, r.describe());
+        }
+        None => {
+            tracing::warn!("[map] [charif] savenow char_id={} unknown result={}", char_id, pkt[8]);
+            return;
+        }
+    }
+    #[cfg(not(test))]
+    crate::ffi::map_char::call_savenow_ack(requester as i32, char_id, pkt[8]);
+    #[cfg(test)]
+    let _ = requester;
+}
+
+/// 0x3016 — this server is stopping; sent after its final saves (2 bytes).
+pub const SHUTDOWN_NOTICE: [u8; 2] = 0x3016u16.to_le_bytes();
+
+/// 0x3813 — char_server handled everything up to our 0x3016.
+/// Layout: [2..6]=characters it logged out.
+fn handle_shutdown_ack(state: &Arc<MapState>, pkt: &[u8]) {
+    if pkt.len() < 6 { return; }
+    let released = u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]);
+    tracing::info!("[map] [charif] shutdown acked, {} characters released", released);
+    state.shutdown_ack.notify_one();
+}
+
+/// Build a 0x3011 save-now request (map→char) from a raw mmo_charstatus.
+///
+/// Layout: [0..2]=cmd, [2..6]=total_len (u32 LE), [6..8]=requester fd (u16 LE),
+///         [8..]=`codec`-compressed framed mmo_charstatus (see `charstatus::frame_char_status`).
+/// `requester_fd` is echoed back in the 0x3812 ack so the result can be
+/// reported to whoever asked.
+pub fn build_save_now(codec: crate::servers::char::codec::Codec, requester_fd: u16, raw_status: &[u8]) -> Vec<u8> {
+    let header = crate::servers::char::charstatus::char_status_header();
+    let compressed = crate::servers::char::codec::compress_parts(codec, &[&header, raw_status]);
+
+    let total_len = 8 + compressed.len() as u32;
+    let mut pkt = Vec::with_capacity(total_len as usize);
+    pkt.extend_from_slice(&0x3011u16.to_le_bytes());
+    pkt.extend_from_slice(&total_len.to_le_bytes());
+    pkt.extend_from_slice(&requester_fd.to_le_bytes());
+    pkt.extend_from_slice(&compressed);
+    pkt
+}
+
 fn read_str(src: &[u8], offset: usize, len: usize) -> String {
     let end = (offset + len).min(src.len());
     let s = &src[offset..end];
@@ -243,4 +321,26 @@ mod tests {
         let src = b"abcdefghijklmnop";
         assert_eq!(read_str(src, 0, 16), "abcdefghijklmnop");
     }
+    #[test]
+    fn test_pkt_lens_savenow_ack() {
+        assert_eq!(PKT_LENS[0x12], 9);
+    }
+    #[test]
+    fn test_build_save_now_layout() {
+        use crate::servers::char::codec::{self, Codec};
+        let raw = [0x2Au8, 0, 0, 0, 1, 2, 3];
+        let pkt = build_save_now(Codec::Zlib, 17, &raw);
+        assert_eq!(u16::from_le_bytes([pkt[0], pkt[1]]), 0x3011);
+        assert_eq!(u32::from_le_bytes([pkt[2], pkt[3], pkt[4], pkt[5]]) as usize, pkt.len());
+        assert_eq!(u16::from_le_bytes([pkt[6], pkt[7]]), 17);
+        assert_eq!(Codec::detect(&pkt[8..]), Some(Codec::Zlib));
+        let out = codec::decompress(&pkt[8..]).unwrap();
+        assert_eq!(out, crate::servers::char::charstatus::frame_char_status(&raw));
+    }
+    #[test]
+    fn test_mapset_packet_layout() {
+        let pkt = mapset_packet(&[3, 0x0102]);
+        assert_eq!(pkt, [0x01, 0x30, 12, 0, 0, 0, 2, 0, 3, 0, 0x02, 0x01]);
+        assert_eq!(mapset_packet(&[]).len(), 8);
+    }
 }
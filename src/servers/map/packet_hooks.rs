@@ -0,0 +1,99 @@
+//! Handlers for client opcodes the core does not parse.
+//!
+//! `clif_parse` falls through to its "unknown packet" branch for any opcode
+//! outside `CORE_OPCODES`; before logging it, the frame is offered to a
+//! handler registered here (a Rust plugin via `register_native`) or from Lua
+//! (`registerPacketHandler`, see `game::scripting::packets`). Handlers only
+//! ever see `payload(frame)`: the bytes after the 5-byte header up to the
+//! frame's declared length, never the rest of the read buffer. An opcode with
+//! no handler keeps the old treatment.
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::RwLock;
+
+/// Opcodes `clif_parse` handles itself (0x10 before login, the rest after);
+/// handlers for these would never run, so registering one is refused.
+pub const CORE_OPCODES: &[u8] = &[
+    0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13,
+    0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F, 0x20, 0x23, 0x24, 0x27, 0x29, 0x2A,
+    0x2D, 0x2E, 0x30, 0x32, 0x34, 0x38, 0x39, 0x3A, 0x3B, 0x3F, 0x41, 0x42, 0x43, 0x4A, 0x4C,
+    0x4F, 0x60, 0x66, 0x69, 0x6B, 0x73, 0x75, 0x77, 0x7B, 0x7C, 0x7D, 0x82, 0x83, 0x84, 0x85,
+];
+
+/// Frame header: 0xAA, big-endian length, opcode, sequence byte.
+pub const HEADER_LEN: usize = 5;
+
+pub fn is_core(opcode: u8) -> bool {
+    CORE_OPCODES.contains(&opcode)
+}
+
+/// The payload of a decrypted client frame, bounded by its declared length.
+/// `None` if the buffer is shorter than the length field claims.
+pub fn payload(frame: &[u8]) -> Option<&[u8]> {
+    if frame.len() < 3 {
+        return None;
+    }
+    let declared = u16::from_be_bytes([frame[1], frame[2]]) as usize + 3;
+    let frame = frame.get(..declared)?;
+    Some(frame.get(HEADER_LEN..).unwrap_or(&[]))
+}
+
+/// Rust-side handler: `(sd, payload)`, returns true if it consumed the packet.
+pub type NativeHandler = fn(sd: *mut c_void, payload: &[u8]) -> bool;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum HookError {
+    #[error("opcode 0x{0:02X} is handled by the core")]
+    Core(u8),
+    #[error("opcode 0x{0:02X} already has a native handler")]
+    Taken(u8),
+}
+
+static NATIVE: RwLock<Option<HashMap<u8, NativeHandler>>> = RwLock::new(None);
+
+/// Install a Rust handler for `opcode`. Native handlers are consulted before
+/// Lua ones and stay for the life of the process.
+pub fn register_native(opcode: u8, handler: NativeHandler) -> Result<(), HookError> {
+    if is_core(opcode) {
+        return Err(HookError::Core(opcode));
+    }
+    let mut native = NATIVE.write().unwrap();
+    let map = native.get_or_insert_with(HashMap::new);
+    if map.contains_key(&opcode) {
+        return Err(HookError::Taken(opcode));
+    }
+    map.insert(opcode, handler);
+    Ok(())
+}
+
+pub fn native(opcode: u8) -> Option<NativeHandler> {
+    NATIVE.read().unwrap().as_ref()?.get(&opcode).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_is_bounded_by_declared_length() {
+        // Declared length 4 → 7-byte frame; the trailing 0xEE belongs to the next packet.
+        let buf = [0xAA, 0x00, 0x04, 0x90, 0x01, 0x11, 0x22, 0xEE];
+        assert_eq!(payload(&buf), Some(&[0x11, 0x22][..]));
+        assert_eq!(payload(&buf[..6]), None);
+        assert_eq!(payload(&[0xAA, 0x00, 0x01, 0x90]), Some(&[][..]));
+        assert_eq!(payload(&[0xAA, 0x00]), None);
+    }
+
+    #[test]
+    fn test_register_native() {
+        fn consume(_: *mut c_void, p: &[u8]) -> bool {
+            !p.is_empty()
+        }
+        assert_eq!(register_native(0x0A, consume), Err(HookError::Core(0x0A)));
+        assert_eq!(register_native(0xF1, consume), Ok(()));
+        assert_eq!(register_native(0xF1, consume), Err(HookError::Taken(0xF1)));
+        assert!(native(0xF1).unwrap()(std::ptr::null_mut(), &[1]));
+        assert!(native(0xF2).is_none());
+    }
+}
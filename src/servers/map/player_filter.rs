@@ -0,0 +1,40 @@
+//! Server-side player filter for `countPlayers`.
+//!
+//! Every field is optional; an empty filter matches everyone. Faction is the
+//! player's `country`, as in `servers::map::faction`.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayerFilter {
+    pub min_level: Option<u8>,
+    pub max_level: Option<u8>,
+    pub faction: Option<u8>,
+    pub map: Option<u16>,
+    pub class: Option<u8>,
+}
+
+impl PlayerFilter {
+    pub fn matches(&self, level: u8, class: u8, faction: u8, map: u16) -> bool {
+        self.min_level.is_none_or(|v| level >= v)
+            && self.max_level.is_none_or(|v| level <= v)
+            && self.faction.is_none_or(|v| faction == v)
+            && self.map.is_none_or(|v| map == v)
+            && self.class.is_none_or(|v| class == v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_are_optional_and_combine() {
+        assert!(PlayerFilter::default().matches(1, 0, 0, 0));
+        let f = PlayerFilter { min_level: Some(50), map: Some(3), ..Default::default() };
+        assert!(f.matches(50, 2, 1, 3));
+        assert!(!f.matches(49, 2, 1, 3));
+        assert!(!f.matches(99, 2, 1, 4));
+        let f = PlayerFilter { max_level: Some(10), faction: Some(2), class: Some(1), ..Default::default() };
+        assert!(f.matches(10, 1, 2, 9));
+        assert!(!f.matches(11, 1, 2, 9) && !f.matches(5, 0, 2, 9) && !f.matches(5, 1, 1, 9));
+    }
+}
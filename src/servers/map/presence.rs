@@ -0,0 +1,85 @@
+//! Which map each online character is on, reported to char_server.
+//!
+//! The map server sends 0x3014 whenever a character's map actually changes
+//! (including the first placement after login), so char_server's online
+//! entry carries the current map id. Packets to char_server are sent from
+//! independent tasks and can arrive out of order during rapid warping, so
+//! each update carries a per-character sequence number and char_server
+//! keeps only the newest.
+
+use std::collections::HashMap;
+
+/// Map-server side: last map reported per character.
+#[derive(Debug, Default)]
+pub struct MapPresence {
+    chars: HashMap<u32, (u16, u32)>,
+}
+
+impl MapPresence {
+    /// `char_id` is now on map `m`. Returns the sequence number to report,
+    /// or `None` if char_server already has this map.
+    pub fn note(&mut self, char_id: u32, m: u16) -> Option<u32> {
+        match self.chars.get_mut(&char_id) {
+            Some((cur, _)) if *cur == m => None,
+            Some((cur, seq)) => {
+                *cur = m;
+                *seq = seq.wrapping_add(1).max(1);
+                Some(*seq)
+            }
+            None => {
+                self.chars.insert(char_id, (m, 1));
+                Some(1)
+            }
+        }
+    }
+
+    /// `char_id` left this server.
+    pub fn forget(&mut self, char_id: u32) {
+        self.chars.remove(&char_id);
+    }
+}
+
+/// Char-server side: whether an update with `seq` supersedes `current`
+/// (0 = nothing received yet). Wrapping, so a long session never stalls.
+pub fn is_newer(seq: u32, current: u32) -> bool {
+    current == 0 || (seq.wrapping_sub(current) as i32) > 0
+}
+
+/// 0x3014 — map change (map→char, 12 bytes).
+/// Layout: [2..6]=char_id, [6..8]=map id, [8..12]=sequence.
+pub fn build_map_change(char_id: u32, m: u16, seq: u32) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(12);
+    pkt.extend_from_slice(&0x3014u16.to_le_bytes());
+    pkt.extend_from_slice(&char_id.to_le_bytes());
+    pkt.extend_from_slice(&m.to_le_bytes());
+    pkt.extend_from_slice(&seq.to_le_bytes());
+    pkt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_real_changes_are_reported() {
+        let mut p = MapPresence::default();
+        assert_eq!(p.note(7, 3), Some(1));
+        assert_eq!(p.note(7, 3), None);
+        assert_eq!(p.note(7, 4), Some(2));
+        assert_eq!(p.note(8, 4), Some(1));
+        p.forget(7);
+        assert_eq!(p.note(7, 4), Some(1));
+
+        let pkt = build_map_change(7, 0x0102, 5);
+        assert_eq!(pkt, [0x14, 0x30, 7, 0, 0, 0, 0x02, 0x01, 5, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_out_of_order_updates_keep_newest() {
+        assert!(is_newer(1, 0));
+        assert!(is_newer(3, 2));
+        assert!(!is_newer(2, 3));
+        assert!(!is_newer(3, 3));
+        assert!(is_newer(1, u32::MAX));
+    }
+}
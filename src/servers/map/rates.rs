@@ -0,0 +1,112 @@
+//! Event experience/drop multipliers.
+//!
+//! `exp_rate` and `drop_rate` from server.yaml are copied here at startup and
+//! can be changed while the server runs (GM `eventrate`), so the award paths
+//! read them from atomics rather than from the immutable config.
+//!
+//! `drop_rate` only touches a mob's native drops: the `dropItem` calls the
+//! mob makes on itself while its `mobDrops` hook runs. Items players handed
+//! to the mob, or scripts put in its inventory, drop exactly as they went in.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Upper bound accepted for either multiplier.
+pub const MAX_RATE: f32 = 100.0;
+
+// f32 bit patterns; 0x3F80_0000 is 1.0.
+static EXP_RATE: AtomicU32 = AtomicU32::new(0x3F80_0000);
+static DROP_RATE: AtomicU32 = AtomicU32::new(0x3F80_0000);
+/// Block id of the mob whose `mobDrops` hook is running; 0 when none.
+static NATIVE_DROPS_OF: AtomicU32 = AtomicU32::new(0);
+
+/// True for a finite multiplier in `0.0..=MAX_RATE`.
+pub fn in_bounds(rate: f32) -> bool {
+    rate.is_finite() && (0.0..=MAX_RATE).contains(&rate)
+}
+
+pub fn exp_rate() -> f32 {
+    f32::from_bits(EXP_RATE.load(Ordering::Relaxed))
+}
+
+pub fn drop_rate() -> f32 {
+    f32::from_bits(DROP_RATE.load(Ordering::Relaxed))
+}
+
+/// Replace both multipliers. Out-of-bounds values are rejected and nothing changes.
+pub fn set(exp: f32, drop: f32) -> bool {
+    if !in_bounds(exp) || !in_bounds(drop) {
+        return false;
+    }
+    EXP_RATE.store(exp.to_bits(), Ordering::Relaxed);
+    DROP_RATE.store(drop.to_bits(), Ordering::Relaxed);
+    true
+}
+
+/// Mark `mob_id`'s drop-table hook as running, or clear the mark with 0.
+pub fn set_native_drops(mob_id: u32) {
+    NATIVE_DROPS_OF.store(mob_id, Ordering::Relaxed);
+}
+
+/// True while `bl_id` is the mob whose drop-table hook is running.
+pub fn is_native_drop(bl_id: u32) -> bool {
+    bl_id != 0 && NATIVE_DROPS_OF.load(Ordering::Relaxed) == bl_id
+}
+
+/// `(amount per drop, number of drops)` for a native drop of `amount` units
+/// of an item stacking to `stack`. A stackable item drops once with the
+/// scaled amount; a non-stackable one stays at amount 1 and the scaled
+/// count becomes separate copies.
+pub fn split_drop(amount: i32, stack: i32, rate: f32, roll: f32) -> (i32, i32) {
+    let n = scale_drop(amount, rate, roll);
+    if stack > 1 { (n, (n > 0) as i32) } else { (1, n) }
+}
+
+/// `exp` scaled by `rate`, rounded to nearest and saturated at `u32::MAX`.
+pub fn scale_exp(exp: i64, rate: f32) -> i64 {
+    ((exp as f64) * rate as f64).round().clamp(0.0, u32::MAX as f64) as i64
+}
+
+/// `amount` scaled by `rate`. The fractional part becomes one extra unit
+/// with that probability, where `roll` is uniform in `[0, 1)`; so at 0.5x a
+/// single item drops half the time and at 1.5x it drops as two half the time.
+pub fn scale_drop(amount: i32, rate: f32, roll: f32) -> i32 {
+    let scaled = amount.max(0) as f64 * rate as f64;
+    let whole = scaled.floor();
+    let extra = if ((scaled - whole) as f32) > roll { 1.0 } else { 0.0 };
+    (whole + extra).min(i32::MAX as f64) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounds() {
+        assert!(in_bounds(0.0) && in_bounds(1.0) && in_bounds(MAX_RATE));
+        assert!(!in_bounds(-0.5) && !in_bounds(MAX_RATE + 1.0) && !in_bounds(f32::NAN));
+    }
+
+    #[test]
+    fn test_scale_exp_rounds_and_saturates() {
+        assert_eq!(scale_exp(1000, 1.0), 1000);
+        assert_eq!(scale_exp(1001, 1.5), 1502);
+        assert_eq!(scale_exp(u32::MAX as i64, 2.0), u32::MAX as i64);
+    }
+
+    #[test]
+    fn test_scale_drop_fraction_is_probabilistic() {
+        assert_eq!(scale_drop(3, 2.0, 0.9), 6);
+        assert_eq!(scale_drop(1, 0.5, 0.2), 1);
+        assert_eq!(scale_drop(1, 0.5, 0.7), 0);
+        assert_eq!(scale_drop(1, 1.5, 0.2), 2);
+        assert_eq!(scale_drop(4, 1.0, 0.99), 4);
+    }
+
+    #[test]
+    fn test_split_drop_copies_non_stackables() {
+        assert_eq!(split_drop(2, 10, 2.0, 0.5), (4, 1));
+        assert_eq!(split_drop(1, 10, 0.5, 0.7), (0, 0));
+        assert_eq!(split_drop(1, 1, 3.0, 0.5), (1, 3));
+        assert_eq!(split_drop(1, 0, 0.5, 0.7), (1, 0));
+    }
+}
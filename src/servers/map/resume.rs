@@ -0,0 +1,177 @@
+//! Reconnect-resume for brief client drops.
+//!
+//! When a player's socket dies with a network error or the client goes away
+//! without asking to log out (see [`is_transient`]) and `resume_grace_secs`
+//! is non-zero, the map server parks the in-memory `USER` instead of running
+//! the logout/save path. If the same character
+//! logs in again from the same /24 within the grace window, the parked `USER`
+//! is re-attached to the new session and the character load round trip is
+//! skipped. Otherwise the park expires and the normal disconnect runs.
+//!
+//! The legacy client cannot carry a token of its own, so the resume token is
+//! held server-side: it is bound to the character id and the client subnet,
+//! and is also handed to char_server (0x3013) so the login path lets the
+//! player back in instead of treating it as a double login. Logging in to
+//! another character on the same account releases the hold.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::network::same_subnet;
+use crate::session::DisconnectReason;
+
+/// One parked player.
+#[derive(Debug, Clone, Copy)]
+pub struct Parked {
+    pub token: u32,
+    /// Client IPv4 at disconnect, host byte order.
+    pub ip: u32,
+    /// Opaque `USER*` owned by C while parked.
+    pub sd: usize,
+    /// Session `eof` code of the drop, reported as the disconnect reason if
+    /// the park expires; the fd it came from is gone by then.
+    pub eof: i32,
+    pub expires: Instant,
+}
+
+/// Parked players keyed by character id.
+#[derive(Debug, Default)]
+pub struct ResumeTable {
+    parked: HashMap<u32, Parked>,
+}
+
+/// Disconnects that may be a dropped link rather than a deliberate quit. A
+/// clean close from the client counts, since mobile links and NAT timeouts
+/// often end that way. An in-game logout also ends with the client closing
+/// the socket, so C checks `Session::quitting` (set on 0x0B) before parking.
+pub fn is_transient(reason: DisconnectReason) -> bool {
+    matches!(
+        reason,
+        DisconnectReason::ReadError
+            | DisconnectReason::WriteError
+            | DisconnectReason::SlowConsumer
+            | DisconnectReason::PeerClosed
+    )
+}
+
+impl ResumeTable {
+    /// Park `sd`, dropped with `eof`, for `grace` and return its token (never 0).
+    pub fn park(&mut self, char_id: u32, ip: u32, sd: usize, eof: i32, grace: Duration) -> u32 {
+        let token = rand::random::<u32>().max(1);
+        self.parked.insert(char_id, Parked { token, ip, sd, eof, expires: Instant::now() + grace });
+        token
+    }
+
+    /// Claim the parked `USER` for `char_id` if `ip` is on the same subnet and
+    /// the grace window has not passed. A subnet mismatch leaves it parked.
+    pub fn take(&mut self, char_id: u32, ip: u32) -> Option<Parked> {
+        let p = self.parked.get(&char_id)?;
+        if p.expires <= Instant::now() || !same_subnet(p.ip, ip) {
+            return None;
+        }
+        self.parked.remove(&char_id)
+    }
+
+    /// Force a park to expire on the next sweep (e.g. char_server kick).
+    pub fn expire_now(&mut self, char_id: u32) -> bool {
+        match self.parked.get_mut(&char_id) {
+            Some(p) => {
+                p.expires = Instant::now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove and return every park whose window has passed.
+    pub fn drain_expired(&mut self, now: Instant) -> Vec<(u32, Parked)> {
+        let ids: Vec<u32> = self.parked.iter()
+            .filter(|(_, p)| p.expires <= now)
+            .map(|(&id, _)| id)
+            .collect();
+        ids.into_iter()
+            .filter_map(|id| self.parked.remove(&id).map(|p| (id, p)))
+            .collect()
+    }
+
+    /// Remove and return every park (server shutdown).
+    pub fn drain_all(&mut self) -> Vec<(u32, Parked)> {
+        self.parked.drain().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.parked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parked.is_empty()
+    }
+}
+
+/// 0x3013 — map→char resume hold (16 bytes).
+///
+/// Layout: [0..2]=cmd, [2..6]=char_id, [6..10]=token, [10..14]=client ip
+///         (host order), [14..16]=grace seconds. All LE.
+pub fn build_resume_hold(char_id: u32, token: u32, ip: u32, grace_secs: u16) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(16);
+    pkt.extend_from_slice(&0x3013u16.to_le_bytes());
+    pkt.extend_from_slice(&char_id.to_le_bytes());
+    pkt.extend_from_slice(&token.to_le_bytes());
+    pkt.extend_from_slice(&ip.to_le_bytes());
+    pkt.extend_from_slice(&grace_secs.to_le_bytes());
+    pkt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: u32 = 0x0A00_0510; // 10.0.5.16
+
+    #[test]
+    fn test_take_requires_same_subnet_and_window() {
+        let mut t = ResumeTable::default();
+        let token = t.park(7, IP, 0x1000, 3, Duration::from_secs(30));
+        assert_ne!(token, 0);
+
+        assert!(t.take(7, 0x0A00_0610).is_none(), "other /24 must not resume");
+        assert_eq!(t.len(), 1, "mismatch leaves the park in place");
+
+        let p = t.take(7, IP + 3).unwrap();
+        assert_eq!((p.token, p.sd, p.eof), (token, 0x1000, 3));
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_expired_park_is_drained_not_taken() {
+        let mut t = ResumeTable::default();
+        t.park(7, IP, 0x1000, 3, Duration::from_secs(30));
+        t.park(8, IP, 0x2000, 3, Duration::from_secs(30));
+        assert!(t.expire_now(7));
+        assert!(t.take(7, IP).is_none());
+
+        let drained = t.drain_expired(Instant::now());
+        assert_eq!(drained.len(), 1);
+        assert_eq!(drained[0].0, 7);
+        assert_eq!(t.len(), 1);
+    }
+
+    #[test]
+    fn test_transient_reasons() {
+        assert!(is_transient(DisconnectReason::ReadError));
+        assert!(is_transient(DisconnectReason::WriteError));
+        assert!(is_transient(DisconnectReason::PeerClosed));
+        assert!(!is_transient(DisconnectReason::ServerKick));
+    }
+
+    #[test]
+    fn test_build_resume_hold_layout() {
+        let pkt = build_resume_hold(42, 9, IP, 30);
+        assert_eq!(pkt.len(), 16);
+        assert_eq!(&pkt[0..2], &[0x13, 0x30]);
+        assert_eq!(u32::from_le_bytes(pkt[2..6].try_into().unwrap()), 42);
+        assert_eq!(u32::from_le_bytes(pkt[6..10].try_into().unwrap()), 9);
+        assert_eq!(u32::from_le_bytes(pkt[10..14].try_into().unwrap()), IP);
+        assert_eq!(u16::from_le_bytes(pkt[14..16].try_into().unwrap()), 30);
+    }
+}
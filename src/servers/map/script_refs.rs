@@ -0,0 +1,129 @@
+//! Static check of literal `ITEM(n)` / `RECIPE(n)` references in Lua scripts.
+//!
+//! A typo'd id in a shop or quest script only shows up when a player walks
+//! that path. `scan` finds calls whose argument is an integer literal and
+//! `validate_dir` reports those that do not resolve, so they can be logged
+//! at boot (`lua_validate_refs`) or on demand (GM `validaterefs`). Calls with
+//! computed arguments and names are left alone.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefKind {
+    Item,
+    Recipe,
+}
+
+impl RefKind {
+    pub fn ctor(self) -> &'static str {
+        match self {
+            Self::Item => "ITEM",
+            Self::Recipe => "RECIPE",
+        }
+    }
+}
+
+/// One literal reference; `line` is 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptRef {
+    pub kind: RefKind,
+    pub id: u32,
+    pub line: usize,
+}
+
+/// Literal-id `ITEM(...)`/`RECIPE(...)` calls in `src`, skipping `--` line
+/// comments. Identifiers that merely end in ITEM (`BITEM`, `BANKITEM`) do
+/// not count.
+pub fn scan(src: &str) -> Vec<ScriptRef> {
+    let mut out = Vec::new();
+    for (n, line) in src.lines().enumerate() {
+        let code = line.find("--").map_or(line, |c| &line[..c]);
+        for kind in [RefKind::Item, RefKind::Recipe] {
+            let name = kind.ctor();
+            let mut from = 0;
+            while let Some(pos) = code[from..].find(name) {
+                let start = from + pos;
+                from = start + name.len();
+                let prev = code[..start].chars().next_back();
+                if prev.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    continue;
+                }
+                if let Some(id) = literal_arg(&code[from..]) {
+                    out.push(ScriptRef { kind, id, line: n + 1 });
+                }
+            }
+        }
+    }
+    out
+}
+
+/// The integer in `(  123  )` at the start of `rest`, if that is all there is.
+fn literal_arg(rest: &str) -> Option<u32> {
+    let inner = rest.trim_start().strip_prefix('(')?;
+    let close = inner.find(')')?;
+    inner[..close].trim().parse().ok()
+}
+
+/// Every `.lua` file under `dir` with its unresolved references, as judged
+/// by `exists`. Unreadable files and directories are skipped.
+pub fn validate_dir(dir: &Path, exists: &dyn Fn(RefKind, u32) -> bool) -> Vec<(PathBuf, ScriptRef)> {
+    let mut bad = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        let Ok(rd) = std::fs::read_dir(&d) else { continue };
+        for entry in rd.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if path.extension().and_then(|e| e.to_str()) == Some("lua") {
+                let Ok(src) = std::fs::read(&path) else { continue };
+                for r in scan(&String::from_utf8_lossy(&src)) {
+                    if !exists(r.kind, r.id) {
+                        bad.push((path.clone(), r));
+                    }
+                }
+            }
+        }
+    }
+    bad.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.line.cmp(&b.1.line)));
+    bad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_finds_literal_calls_only() {
+        let src = "local a = ITEM(12)\n\
+                   local b = ITEM( 7 ) + RECIPE(3)\n\
+                   local c = ITEM(id) BITEM(4) BANKITEM(5) my_ITEM(6)\n\
+                   -- ITEM(99) in a comment\n\
+                   x = ITEM(\"Sword\") y = RECIPE (8)";
+        let refs = scan(src);
+        let got: Vec<(RefKind, u32, usize)> = refs.iter().map(|r| (r.kind, r.id, r.line)).collect();
+        assert_eq!(got, vec![
+            (RefKind::Item, 12, 1),
+            (RefKind::Item, 7, 2),
+            (RefKind::Recipe, 3, 2),
+            (RefKind::Recipe, 8, 5),
+        ]);
+    }
+
+    #[test]
+    fn test_validate_dir_reports_unknown_ids() {
+        let dir = std::env::temp_dir().join(format!("yuri_refs_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shops")).unwrap();
+        std::fs::write(dir.join("shops/a.lua"), "sell(ITEM(1))\nsell(ITEM(2))\n").unwrap();
+        std::fs::write(dir.join("b.lua"), "craft(RECIPE(1))\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ITEM(2)\n").unwrap();
+
+        let bad = validate_dir(&dir, &|kind, id| kind == RefKind::Item && id == 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let got: Vec<(String, u32)> = bad.iter()
+            .map(|(p, r)| (p.file_name().unwrap().to_string_lossy().into_owned(), r.id))
+            .collect();
+        assert_eq!(got, vec![("b.lua".to_string(), 1), ("a.lua".to_string(), 2)]);
+    }
+}
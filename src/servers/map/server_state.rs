@@ -0,0 +1,278 @@
+//! World-shared state that survives restarts.
+//!
+//! One named-integer store per map server: `GAMEREG` values, world-event
+//! timers, global kill counters. Rust systems use `get`/`set`/`add`; Lua's
+//! `GAMEREG` and the C `map_readglobalgamereg`/`map_setglobalgamereg` go
+//! through the same functions. Keys are case-insensitive (as GAMEREG always
+//! was) and a value of 0 is the same as unset.
+//!
+//! Writes only touch memory. The whole store is written as one versioned
+//! JSON document to `ServerState` on the autosave tick and at shutdown, and
+//! read back at boot. The first boot after upgrading imports the legacy
+//! per-key `GameRegistry<server_id>` rows. A failed boot load leaves the
+//! store memory-only; see [`LoadGate`].
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+/// Current on-disk format version.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Document {
+    version: u32,
+    values: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct ServerState {
+    values: BTreeMap<String, i64>,
+    /// Bumped on every change; a flush only marks clean the generation it wrote.
+    generation: u64,
+    saved_generation: u64,
+}
+
+impl ServerState {
+    pub fn get(&self, key: &str) -> i64 {
+        self.values.get(&key.to_ascii_lowercase()).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, key: &str, val: i64) {
+        let key = key.to_ascii_lowercase();
+        let changed = if val == 0 {
+            self.values.remove(&key).is_some()
+        } else {
+            self.values.insert(key, val) != Some(val)
+        };
+        if changed {
+            self.generation += 1;
+        }
+    }
+
+    /// Add `delta` and return the new value (saturating).
+    pub fn add(&mut self, key: &str, delta: i64) -> i64 {
+        let v = self.get(key).saturating_add(delta);
+        self.set(key, v);
+        v
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.generation != self.saved_generation
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The serialized store and the generation it reflects.
+    pub fn encode(&self) -> (String, u64) {
+        let doc = Document { version: FORMAT_VERSION, values: self.values.clone() };
+        (serde_json::to_string(&doc).expect("map of i64 always serializes"), self.generation)
+    }
+
+    pub fn mark_saved(&mut self, generation: u64) {
+        self.saved_generation = self.saved_generation.max(generation);
+    }
+
+    /// Replace the contents with a stored document. Unknown future versions
+    /// are refused rather than half-read.
+    pub fn decode(&mut self, data: &str) -> Result<()> {
+        let doc: Document = serde_json::from_str(data).context("malformed server state")?;
+        anyhow::ensure!(
+            doc.version <= FORMAT_VERSION,
+            "server state version {} is newer than this build ({})", doc.version, FORMAT_VERSION
+        );
+        self.values = doc.values.into_iter()
+            .filter(|&(_, v)| v != 0)
+            .map(|(k, v)| (k.to_ascii_lowercase(), v))
+            .collect();
+        self.generation = 0;
+        self.saved_generation = 0;
+        Ok(())
+    }
+}
+
+/// Whether a persisted table was read at boot. Until it was, nothing may be
+/// written to it: the stored copy was never seen, so a flush would replace it
+/// with whatever little the process has in memory. Shared with `kv`.
+pub(crate) struct LoadGate {
+    table: &'static str,
+    loaded: AtomicBool,
+}
+
+impl LoadGate {
+    pub(crate) const fn new(table: &'static str) -> Self {
+        Self { table, loaded: AtomicBool::new(false) }
+    }
+
+    /// The boot load of `table` succeeded.
+    pub(crate) fn open(&self) {
+        self.loaded.store(true, Ordering::Release);
+    }
+
+    /// Err unless `open` was called.
+    pub(crate) fn check(&self) -> Result<()> {
+        anyhow::ensure!(
+            self.loaded.load(Ordering::Acquire),
+            "{} was not loaded at boot; not overwriting the stored copy", self.table
+        );
+        Ok(())
+    }
+}
+
+static STATE: Mutex<Option<ServerState>> = Mutex::new(None);
+static GATE: LoadGate = LoadGate::new("ServerState");
+
+fn with<R>(f: impl FnOnce(&mut ServerState) -> R) -> R {
+    f(STATE.lock().unwrap().get_or_insert_with(ServerState::default))
+}
+
+pub fn get(key: &str) -> i64 {
+    with(|s| s.get(key))
+}
+
+pub fn set(key: &str, val: i64) {
+    with(|s| s.set(key, val))
+}
+
+pub fn add(key: &str, delta: i64) -> i64 {
+    with(|s| s.add(key, delta))
+}
+
+/// Load this server's state, importing the legacy GameRegistry table when
+/// there is no stored document yet. Returns the number of keys loaded.
+pub async fn load(pool: &MySqlPool, server_id: i32) -> Result<usize> {
+    let row: Option<(String,)> = sqlx::query_as(
+        "SELECT `SstData` FROM `ServerState` WHERE `SstServerId` = ?",
+    )
+    .bind(server_id)
+    .fetch_optional(pool)
+    .await
+    .context("reading ServerState")?;
+
+    let mut fresh = ServerState::default();
+    match row {
+        Some((data,)) => fresh.decode(&data)?,
+        None => {
+            // GrgValue is unsigned in the schema but was always read as a C int.
+            let legacy: Vec<(String, u32)> = sqlx::query_as(&format!(
+                "SELECT `GrgIdentifier`, `GrgValue` FROM `GameRegistry{server_id}`"
+            ))
+            .fetch_all(pool)
+            .await
+            .or_else(|e| if is_missing_table(&e) { Ok(Vec::new()) } else { Err(e) })
+            .with_context(|| format!("importing GameRegistry{server_id}"))?;
+            for (k, v) in legacy {
+                fresh.set(&k, v as i32 as i64);
+            }
+            if !fresh.is_empty() {
+                tracing::info!("[map] [server_state] imported {} GameRegistry{} keys", fresh.len(), server_id);
+            }
+        }
+    }
+    let n = fresh.len();
+    *STATE.lock().unwrap() = Some(fresh);
+    GATE.open();
+    Ok(n)
+}
+
+/// A server with no legacy table has nothing to import.
+fn is_missing_table(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.code().as_deref() == Some("42S02"))
+}
+
+/// Write the store if anything changed since the last flush. Returns whether
+/// a write happened; an error without writing if `load` never succeeded.
+pub async fn flush(pool: &MySqlPool, server_id: i32) -> Result<bool> {
+    GATE.check()?;
+    let (data, generation) = {
+        let guard = STATE.lock().unwrap();
+        match guard.as_ref() {
+            Some(s) if s.is_dirty() => s.encode(),
+            _ => return Ok(false),
+        }
+    };
+    sqlx::query(
+        "INSERT INTO `ServerState` (`SstServerId`, `SstVersion`, `SstData`) VALUES (?, ?, ?) \
+         ON DUPLICATE KEY UPDATE `SstVersion` = VALUES(`SstVersion`), `SstData` = VALUES(`SstData`)",
+    )
+    .bind(server_id)
+    .bind(FORMAT_VERSION)
+    .bind(&data)
+    .execute(pool)
+    .await
+    .context("writing ServerState")?;
+    with(|s| s.mark_saved(generation));
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_case_insensitive_and_zero_unsets() {
+        let mut s = ServerState::default();
+        s.set("PoemAccept", 1);
+        assert_eq!(s.get("poemaccept"), 1);
+        assert!(s.is_dirty());
+        assert_eq!(s.add("kills.dragon", 2), 2);
+        assert_eq!(s.add("KILLS.DRAGON", i64::MAX), i64::MAX);
+        s.set("poemAccept", 0);
+        assert_eq!(s.get("PoemAccept"), 0);
+        assert_eq!(s.len(), 1);
+    }
+
+    #[test]
+    fn test_round_trip_and_dirty_tracking() {
+        let mut s = ServerState::default();
+        s.set("event.timer", 1_700_000_000);
+        s.set("gamereg", -5);
+        let (data, gen) = s.encode();
+        s.set("late", 1);
+        s.mark_saved(gen);
+        assert!(s.is_dirty(), "a change made during the flush stays dirty");
+
+        let mut r = ServerState::default();
+        r.decode(&data).unwrap();
+        assert_eq!((r.get("event.timer"), r.get("GAMEREG"), r.get("late")), (1_700_000_000, -5, 0));
+        assert!(!r.is_dirty());
+        s.set("late", 1);
+        assert_eq!(s.encode().1, gen + 1, "setting an unchanged value is not a change");
+    }
+
+    #[test]
+    fn test_load_gate_opens_once_loaded() {
+        let gate = LoadGate::new("Example");
+        let err = gate.check().unwrap_err();
+        assert!(err.to_string().starts_with("Example was not loaded"), "{err}");
+        gate.open();
+        assert!(gate.check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_flush_refuses_before_load() {
+        // No test loads the store, so the gate is still shut.
+        let pool = crate::servers::testing::unreachable_pool();
+        let err = flush(&pool, 0).await.unwrap_err();
+        assert!(err.to_string().contains("ServerState was not loaded"), "{err}");
+    }
+
+    #[test]
+    fn test_decode_refuses_newer_versions() {
+        let mut s = ServerState::default();
+        assert!(s.decode(r#"{"version":2,"values":{}}"#).is_err());
+        assert!(s.decode("not json").is_err());
+        s.decode(r#"{"version":1,"values":{"A":3,"b":0}}"#).unwrap();
+        assert_eq!((s.get("a"), s.len()), (3, 1));
+    }
+}
@@ -0,0 +1,160 @@
+//! NPC shop transactions.
+//!
+//! Shop scripts used to deduct money and grant items as separate Lua calls,
+//! so a disconnect or script error between the two steps could take gold
+//! without giving the item (or the reverse). `rust_pc_buy_item` and
+//! `rust_pc_sell_item` in `game::pc` now do the whole exchange in one call on
+//! the map thread; this module holds the checks they run first, so that once
+//! the first mutation happens nothing can fail.
+
+/// Why a shop transaction was refused. Nothing has changed when one is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShopError {
+    BadAmount,
+    /// The item is not in item_db or has no price.
+    NotForSale,
+    NotEnoughMoney,
+    NoSpace,
+    /// Empty or out-of-range inventory slot, or fewer units held than asked.
+    BadSlot,
+    NotSellable,
+    /// The credit would overflow the player's purse.
+    MoneyCap,
+}
+
+impl ShopError {
+    const ALL: [ShopError; 7] = [
+        Self::BadAmount, Self::NotForSale, Self::NotEnoughMoney, Self::NoSpace,
+        Self::BadSlot, Self::NotSellable, Self::MoneyCap,
+    ];
+
+    /// Negative code returned over FFI (0 is success).
+    pub fn code(self) -> i32 {
+        -(self as i32) - 1
+    }
+
+    /// Inverse of [`code`](Self::code); `None` for 0 (success) or unknown codes.
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.code() == code)
+    }
+
+    /// Short reason string handed back to Lua.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::BadAmount => "bad_amount",
+            Self::NotForSale => "not_for_sale",
+            Self::NotEnoughMoney => "not_enough_money",
+            Self::NoSpace => "no_space",
+            Self::BadSlot => "bad_slot",
+            Self::NotSellable => "not_sellable",
+            Self::MoneyCap => "money_cap",
+        }
+    }
+}
+
+/// Total price of `amount` units at `unit_price`, if `money` covers it.
+pub fn buy_cost(unit_price: i32, amount: i32, money: u32) -> Result<u32, ShopError> {
+    if amount <= 0 {
+        return Err(ShopError::BadAmount);
+    }
+    if unit_price <= 0 {
+        return Err(ShopError::NotForSale);
+    }
+    let cost = (unit_price as u64) * (amount as u64);
+    if cost > money as u64 {
+        return Err(ShopError::NotEnoughMoney);
+    }
+    Ok(cost as u32)
+}
+
+/// Gold paid for `amount` of the `held` units in a slot at `unit_sell`.
+pub fn sell_credit(unit_sell: i32, amount: i32, held: i32, money: u32) -> Result<u32, ShopError> {
+    if amount <= 0 {
+        return Err(ShopError::BadAmount);
+    }
+    if amount > held {
+        return Err(ShopError::BadSlot);
+    }
+    if unit_sell <= 0 {
+        return Err(ShopError::NotSellable);
+    }
+    let credit = (unit_sell as u64) * (amount as u64);
+    if money as u64 + credit > u32::MAX as u64 {
+        return Err(ShopError::MoneyCap);
+    }
+    Ok(credit as u32)
+}
+
+/// What `room_for` needs to know about one inventory slot.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SlotView {
+    pub id: u32,
+    pub amount: i32,
+    /// No owner, engraving or custom look — i.e. stacks with shop stock.
+    pub plain: bool,
+}
+
+/// How many units of a plain `item_id` fit in `slots`.
+///
+/// `stack` is the per-slot stack size; `max_owned` is item_db's per-player
+/// cap (0 = none) and `owned` how many the player already has, equipped
+/// included.
+pub fn room_for(slots: &[SlotView], item_id: u32, stack: i32, max_owned: i32, owned: i32) -> i32 {
+    let stack = stack.max(1);
+    let room: i64 = slots.iter()
+        .map(|s| match s {
+            s if s.id == 0 => stack as i64,
+            s if s.id == item_id && s.plain => (stack - s.amount).max(0) as i64,
+            _ => 0,
+        })
+        .sum();
+    let room = room.min(i32::MAX as i64) as i32;
+    if max_owned > 0 {
+        room.min((max_owned - owned).max(0))
+    } else {
+        room
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buy_cost_checks_money_and_overflow() {
+        assert_eq!(buy_cost(50, 3, 150), Ok(150));
+        assert_eq!(buy_cost(50, 3, 149), Err(ShopError::NotEnoughMoney));
+        assert_eq!(buy_cost(0, 1, 1000), Err(ShopError::NotForSale));
+        assert_eq!(buy_cost(50, 0, 1000), Err(ShopError::BadAmount));
+        assert_eq!(buy_cost(i32::MAX, i32::MAX, u32::MAX), Err(ShopError::NotEnoughMoney));
+    }
+
+    #[test]
+    fn test_sell_credit_caps_purse() {
+        assert_eq!(sell_credit(10, 2, 5, 0), Ok(20));
+        assert_eq!(sell_credit(10, 6, 5, 0), Err(ShopError::BadSlot));
+        assert_eq!(sell_credit(0, 1, 5, 0), Err(ShopError::NotSellable));
+        assert_eq!(sell_credit(10, 1, 5, u32::MAX - 5), Err(ShopError::MoneyCap));
+    }
+
+    #[test]
+    fn test_room_for_stacks_and_cap() {
+        let slots = [
+            SlotView { id: 7, amount: 8, plain: true },
+            SlotView { id: 7, amount: 1, plain: false },
+            SlotView { id: 9, amount: 1, plain: true },
+            SlotView::default(),
+        ];
+        assert_eq!(room_for(&slots, 7, 10, 0, 0), 2 + 10);
+        assert_eq!(room_for(&slots, 7, 10, 12, 9), 3);
+        assert_eq!(room_for(&slots[..3], 5, 10, 0, 0), 0);
+    }
+
+    #[test]
+    fn test_error_codes_roundtrip() {
+        assert_eq!(ShopError::BadAmount.code(), -1);
+        assert_eq!(ShopError::MoneyCap.code(), -7);
+        assert_eq!(ShopError::from_code(-4), Some(ShopError::NoSpace));
+        assert_eq!(ShopError::from_code(0), None);
+    }
+}
@@ -0,0 +1,163 @@
+//! Which `Spawns*` tables a map server loads, and how they merge.
+//!
+//! By default a server reads `Spawns<server_id>`. For merged worlds,
+//! `spawn_shards` lists several shard ids to read (`Spawns<id>` each), or
+//! `spawn_table` names one unified table. Rows are de-duplicated by `SpnId`:
+//! the source listed first keeps the id and later duplicates are skipped
+//! and logged, so renumber one side (or reorder the list) to load both.
+//!
+//! Each loaded spawn remembers its source, so `SpnLastDeath` updates go back
+//! to the table the row came from; spawns added in game go to the first
+//! source.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Highest shard id with a `Spawns<id>` table.
+pub const MAX_SHARD_ID: u32 = 99;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpawnSource {
+    Shard(u32),
+    Table(String),
+}
+
+impl SpawnSource {
+    pub fn table(&self) -> String {
+        match self {
+            Self::Shard(id) => format!("Spawns{id}"),
+            Self::Table(name) => name.clone(),
+        }
+    }
+
+    /// The table name, if it is safe to splice into SQL: a shard id up to
+    /// `MAX_SHARD_ID` or a `valid_table_name`.
+    pub fn checked_table(&self) -> Option<String> {
+        match self {
+            Self::Shard(id) if *id <= MAX_SHARD_ID => Some(self.table()),
+            Self::Table(name) if valid_table_name(name) => Some(name.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The own-shard source for a C `serverid`, if it is in `0..=MAX_SHARD_ID`.
+pub fn shard_for_server(server_id: i32) -> Option<SpawnSource> {
+    u32::try_from(server_id).ok().filter(|&id| id <= MAX_SHARD_ID).map(SpawnSource::Shard)
+}
+
+/// Safe to splice into SQL as a backquoted identifier.
+pub fn valid_table_name(name: &str) -> bool {
+    (1..=64).contains(&name.len()) && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// The sources for this server: `table`, else `shards`, else its own shard.
+pub fn sources(server_id: i32, shards: &[u32], table: Option<&str>) -> Vec<SpawnSource> {
+    if let Some(t) = table {
+        return vec![SpawnSource::Table(t.to_string())];
+    }
+    if shards.is_empty() {
+        return vec![SpawnSource::Shard(server_id.max(0) as u32)];
+    }
+    let mut out: Vec<SpawnSource> = Vec::with_capacity(shards.len());
+    for &s in shards {
+        if !out.contains(&SpawnSource::Shard(s)) {
+            out.push(SpawnSource::Shard(s));
+        }
+    }
+    out
+}
+
+/// A dropped duplicate: `id` was already loaded from source `kept`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    pub id: u32,
+    pub kept: usize,
+    pub dropped: usize,
+}
+
+/// Merge per-source rows (in source order) by `id`, first source winning.
+/// Returns `(source index, row)` sorted by id, and the rows that lost.
+pub fn merge<T>(per_source: Vec<Vec<T>>, id: impl Fn(&T) -> u32) -> (Vec<(usize, T)>, Vec<Conflict>) {
+    let mut seen: HashMap<u32, usize> = HashMap::new();
+    let mut rows = Vec::new();
+    let mut conflicts = Vec::new();
+    for (src, list) in per_source.into_iter().enumerate() {
+        for row in list {
+            let k = id(&row);
+            match seen.get(&k) {
+                Some(&kept) => conflicts.push(Conflict { id: k, kept, dropped: src }),
+                None => {
+                    seen.insert(k, src);
+                    rows.push((src, row));
+                }
+            }
+        }
+    }
+    rows.sort_by_key(|(_, r)| id(r));
+    (rows, conflicts)
+}
+
+struct Origins {
+    tables: Vec<String>,
+    by_id: HashMap<u32, usize>,
+}
+
+static ORIGINS: RwLock<Option<Origins>> = RwLock::new(None);
+
+/// Record where each loaded spawn came from (replaces the previous load).
+pub fn record(sources: &[SpawnSource], rows: impl IntoIterator<Item = (u32, usize)>) {
+    *ORIGINS.write().unwrap() = Some(Origins {
+        tables: sources.iter().map(SpawnSource::table).collect(),
+        by_id: rows.into_iter().collect(),
+    });
+}
+
+/// Table spawn `spn_id` was loaded from; the primary table if unknown.
+pub fn table_for(spn_id: u32) -> Option<String> {
+    let guard = ORIGINS.read().unwrap();
+    let o = guard.as_ref()?;
+    let src = o.by_id.get(&spn_id).copied().unwrap_or(0);
+    o.tables.get(src).cloned()
+}
+
+/// Where new spawns are written: the first source.
+pub fn primary_table() -> Option<String> {
+    ORIGINS.read().unwrap().as_ref().and_then(|o| o.tables.first().cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources_default_to_own_shard() {
+        assert_eq!(sources(3, &[], None), vec![SpawnSource::Shard(3)]);
+        assert_eq!(sources(3, &[1, 2, 1], None), vec![SpawnSource::Shard(1), SpawnSource::Shard(2)]);
+        assert_eq!(sources(3, &[1], Some("SpawnsWorld"))[0].table(), "SpawnsWorld");
+        assert!(valid_table_name("Spawns_all2"));
+        assert!(!valid_table_name("Spawns`; DROP") && !valid_table_name(""));
+    }
+
+    #[test]
+    fn test_checked_table_rejects_out_of_range() {
+        assert_eq!(SpawnSource::Shard(99).checked_table().as_deref(), Some("Spawns99"));
+        assert_eq!(SpawnSource::Shard(100).checked_table(), None);
+        assert_eq!(SpawnSource::Table("Spawns`x".into()).checked_table(), None);
+        assert_eq!(shard_for_server(7), Some(SpawnSource::Shard(7)));
+        assert_eq!(shard_for_server(-1), None);
+        assert_eq!(shard_for_server(100), None);
+    }
+
+    #[test]
+    fn test_merge_first_source_wins() {
+        let (rows, conflicts) = merge(vec![vec![(5, 'a'), (1, 'a')], vec![(5, 'b'), (2, 'b')]], |r| r.0);
+        assert_eq!(rows, vec![(0, (1, 'a')), (1, (2, 'b')), (0, (5, 'a'))]);
+        assert_eq!(conflicts, vec![Conflict { id: 5, kept: 0, dropped: 1 }]);
+
+        record(&[SpawnSource::Shard(0), SpawnSource::Shard(7)], rows.iter().map(|(s, r)| (r.0, *s)));
+        assert_eq!(table_for(2).as_deref(), Some("Spawns7"));
+        assert_eq!(table_for(99).as_deref(), Some("Spawns0"));
+        assert_eq!(primary_table().as_deref(), Some("Spawns0"));
+    }
+}
@@ -0,0 +1,62 @@
+//! Time budgets for the game tick.
+//!
+//! `run_async_server` times each `timer_do` call and logs (rate-limited) when
+//! one runs past `tick_budget_ms`, so a stall shows up as a number instead of
+//! unexplained lag. Separately, the mob sweep in `mob_timer_spawns` can be
+//! capped at `mob_tick_budget_ms`: once spent it stops, and the next call
+//! resumes at the first mob it did not reach. The sweep's phase counter only
+//! advances when a pass completes, so under load every mob still gets its
+//! periodic work, just less often.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Mob sweep cap in microseconds; 0 = unlimited.
+static MOB_BUDGET_US: AtomicU64 = AtomicU64::new(0);
+
+/// Set the mob sweep cap (boot, config reload). Zero = unlimited.
+pub fn install(mob_budget: Duration) {
+    MOB_BUDGET_US.store(mob_budget.as_micros() as u64, Ordering::Relaxed);
+}
+
+pub fn mob_budget() -> Duration {
+    Duration::from_micros(MOB_BUDGET_US.load(Ordering::Relaxed))
+}
+
+/// A point in time after which work should stop; a zero budget never passes.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    pub fn after(budget: Duration) -> Self {
+        Self((!budget.is_zero()).then(|| Instant::now() + budget))
+    }
+
+    pub fn passed(&self) -> bool {
+        self.0.is_some_and(|end| Instant::now() >= end)
+    }
+}
+
+/// Whether a tick that took `elapsed` should be reported (budget 0 = never).
+pub fn overran(elapsed: Duration, budget: Duration) -> bool {
+    !budget.is_zero() && elapsed > budget
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_and_overrun() {
+        assert!(!Deadline::after(Duration::ZERO).passed());
+        assert!(!Deadline::after(Duration::from_secs(60)).passed());
+        let d = Deadline::after(Duration::from_micros(1));
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(d.passed());
+
+        let budget = Duration::from_millis(10);
+        assert!(overran(Duration::from_millis(11), budget));
+        assert!(!overran(Duration::from_millis(10), budget));
+        assert!(!overran(Duration::from_secs(5), Duration::ZERO));
+    }
+}
@@ -9,3 +9,46 @@
 pub mod login;
 pub mod char;
 pub mod map;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+/// Resolves on the first SIGTERM or SIGINT.
+pub async fn terminated() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut term = signal(SignalKind::terminate())?;
+    let mut int = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        _ = term.recv() => {}
+        _ = int.recv() => {}
+    }
+    Ok(())
+}
+
+/// Log the outcome of a successful config reload; `tag` is the server's log prefix.
+pub fn log_reload(tag: &str, ignored: &[&'static str]) {
+    tracing::info!("[{}] [config] reloaded", tag);
+    if !ignored.is_empty() {
+        tracing::warn!("[{}] [config] ignored on reload (restart to apply): {}", tag, ignored.join(", "));
+    }
+}
+
+/// Re-read `path` into `live` on every SIGHUP. A file that fails to load
+/// or validate is logged and the running config is kept.
+pub async fn reload_on_hangup(
+    live: std::sync::Arc<crate::config::LiveConfig>,
+    path: std::path::PathBuf,
+    tag: &'static str,
+) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hup = signal(SignalKind::hangup())?;
+    while hup.recv().await.is_some() {
+        match live.reload(&path) {
+            Ok(ignored) => log_reload(tag, &ignored),
+            Err(e) => tracing::error!(
+                "[{}] [config] reload of {} failed, keeping the running config: {:#}",
+                tag, path.display(), e
+            ),
+        }
+    }
+    Ok(())
+}
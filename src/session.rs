@@ -2,33 +2,54 @@
 //!
 //! This module replaces session.c with memory-safe async Rust implementation.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::sync::{Arc, OnceLock};
-use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Mutex as StdMutex, RwLock};
 use std::time::{Duration, Instant};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::net::TcpStream;
 use tokio::runtime::Runtime;
 use tokio::sync::Mutex;
 
+use crate::network::compress;
+use crate::network::crypt::Cipher;
+
 /// Buffer size constants
 pub const RFIFO_SIZE: usize = 16 * 1024;
 pub const WFIFO_SIZE: usize = 16 * 1024;
 
+/// Default initial buffers for inter-server links, which burst the map list
+/// on connect and carry compressed charstatus blobs.
+pub const INTERSERVER_RFIFO_SIZE: usize = 64 * 1024;
+pub const INTERSERVER_WFIFO_SIZE: usize = 256 * 1024;
+
 /// Maximum read buffer size.
 ///
 /// Inter-server connections (e.g. map→char) burst large payloads on connect
 /// (map list, etc.) that can exceed RFIFO_SIZE.  Dropping bytes in a stream
 /// protocol corrupts all subsequent packet framing, so we grow up to this
 /// limit instead.  Connections that exceed it are closed, not silently truncated.
-const MAX_RDATA_SIZE: usize = 64 * 1024;
+pub const MAX_RDATA_SIZE: usize = 64 * 1024;
+
+/// Time a WebSocket client gets to complete its upgrade request.
+#[cfg(feature = "websocket")]
+const WS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default idle limit for accepted sessions (the old C `stall_time`);
+/// overridden at startup by `idle_timeout_secs` in config.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
 
-/// Maximum number of sessions
+/// Default session cap; overridden at startup by `max_sessions` in config.
 pub const MAX_SESSIONS: usize = 1024;
 
+/// Utilization (percent of the session cap) at which a warning is logged.
+const SESSION_WARN_PERCENT: usize = 90;
+
 /// Maximum write buffer size (4MB).
 ///
 /// Must accommodate inter-server packets that compress struct mmo_charstatus
@@ -36,7 +57,225 @@ pub const MAX_SESSIONS: usize = 1024;
 /// worst-case compressed size before compress2 runs, which is ~3.17MB.
 /// The old C session.c used dynamic realloc with no hard cap; 4MB matches
 /// the original behaviour while providing a reasonable upper bound.
-const MAX_WDATA_SIZE: usize = 4 * 1024 * 1024;
+pub const MAX_WDATA_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default write cap for game client sessions (`Session::max_wdata`);
+/// overridden at startup by `client_write_cap` in config. Inter-server links
+/// keep `MAX_WDATA_SIZE`.
+pub const CLIENT_WDATA_CAP: usize = 64 * 1024;
+
+/// Recommended `write_pressure()` ceiling for producers of large packets.
+///
+/// Above this the peer is not draining fast enough: yield (re-arm a timer,
+/// retry next tick) instead of enqueuing more. It leaves room under
+/// `MAX_WDATA_SIZE` for one worst-case compressed charstatus reservation
+/// (~3.17MB), so a producer that checks before each big write never hits
+/// `WriteBufferTooLarge`.
+pub const WRITE_HIGH_WATER: usize = 512 * 1024;
+
+/// What a session is for; picks its initial buffer capacities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionRole {
+    /// Accepted game client.
+    Client,
+    /// Link to another server (outgoing connections from `rust_make_connection`).
+    InterServer,
+}
+
+/// Who is on the other end of a session, fixed when the session is created
+/// (accepted → `Client`, dialled → `CharLink`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u8)]
+pub enum SessionKind {
+    /// Game client.
+    #[default]
+    Client = 0,
+    LoginLink = 1,
+    CharLink = 2,
+    MapLink = 3,
+    /// Operator tooling.
+    Admin = 4,
+}
+
+impl SessionKind {
+    pub const ALL: [SessionKind; 5] = [
+        SessionKind::Client,
+        SessionKind::LoginLink,
+        SessionKind::CharLink,
+        SessionKind::MapLink,
+        SessionKind::Admin,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SessionKind::Client => "client",
+            SessionKind::LoginLink => "login_link",
+            SessionKind::CharLink => "char_link",
+            SessionKind::MapLink => "map_link",
+            SessionKind::Admin => "admin",
+        }
+    }
+
+    /// Another server process rather than a person.
+    pub fn is_server_link(self) -> bool {
+        matches!(self, SessionKind::LoginLink | SessionKind::CharLink | SessionKind::MapLink)
+    }
+
+    /// The buffer-sizing role for this kind.
+    pub fn role(self) -> SessionRole {
+        if self.is_server_link() { SessionRole::InterServer } else { SessionRole::Client }
+    }
+}
+
+/// Initial `rdata`/`wdata` capacities. Buffers still grow on demand up to
+/// `MAX_RDATA_SIZE`/`MAX_WDATA_SIZE`; these only size the first allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSizes {
+    pub read: usize,
+    pub write: usize,
+}
+
+impl BufferSizes {
+    pub const CLIENT: Self = Self { read: RFIFO_SIZE, write: WFIFO_SIZE };
+    pub const INTERSERVER: Self = Self { read: INTERSERVER_RFIFO_SIZE, write: INTERSERVER_WFIFO_SIZE };
+
+    /// Clamped to the hard caps.
+    pub fn capped(self) -> Self {
+        Self { read: self.read.min(MAX_RDATA_SIZE), write: self.write.min(MAX_WDATA_SIZE) }
+    }
+}
+
+/// Retry schedule for outbound server links: the map server's char link,
+/// the char server's login link, and deferred `rust_make_connection`s.
+///
+/// Attempt `n` (1-based) that fails waits `initial * 2^(n-1)`, capped at
+/// `max`, before the next; after `max_attempts` failures (0 = never give up)
+/// the session is shut down as before. An attempt still pending after
+/// `connect_timeout` counts as failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub initial: Duration,
+    pub max: Duration,
+    pub max_attempts: u32,
+    pub connect_timeout: Duration,
+}
+
+impl ReconnectPolicy {
+    pub const DEFAULT: Self = Self {
+        initial: Duration::from_secs(1),
+        max: Duration::from_secs(30),
+        max_attempts: 0,
+        connect_timeout: Duration::from_secs(10),
+    };
+
+    /// Delay after the `attempt`th failure, or `None` to give up.
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if self.max_attempts != 0 && attempt >= self.max_attempts {
+            return None;
+        }
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        Some(self.initial.saturating_mul(factor).min(self.max))
+    }
+
+    /// The `reconnect_*` and `connect_timeout_ms` settings.
+    pub fn from_config(c: &crate::config::ServerConfig) -> Self {
+        Self {
+            initial: Duration::from_millis(c.reconnect_initial_ms),
+            max: Duration::from_millis(c.reconnect_max_ms),
+            max_attempts: c.reconnect_max_attempts,
+            connect_timeout: Duration::from_millis(c.connect_timeout_ms),
+        }
+    }
+
+    /// Dial `addr` until it answers, sleeping [`delay`](Self::delay) after
+    /// each failure. Returns the last error once the attempts run out.
+    /// `tag` prefixes the log lines (e.g. `"[map] [charif]"`).
+    pub async fn connect(&self, addr: &str, tag: &str) -> std::io::Result<TcpStream> {
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            let err = match connect_within(addr, self.connect_timeout, TcpStream::connect(addr)).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => e,
+            };
+            match self.delay(attempt) {
+                Some(wait) => {
+                    tracing::warn!(
+                        "{} connect to {} failed (attempt {}): {}; retrying in {}ms",
+                        tag, addr, attempt, err, wait.as_millis()
+                    );
+                    tokio::time::sleep(wait).await;
+                }
+                None => {
+                    tracing::error!("{} connect to {} failed after {} attempt(s): {}", tag, addr, attempt, err);
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+/// TCP keepalive probing for accepted sockets: after `idle` with nothing
+/// received the kernel sends up to `count` probes `interval` apart, and
+/// resets the connection if none is answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keepalive {
+    pub idle: Duration,
+    pub interval: Duration,
+    pub count: u32,
+}
+
+impl Keepalive {
+    pub const DEFAULT: Self = Self {
+        idle: Duration::from_secs(120),
+        interval: Duration::from_secs(30),
+        count: 3,
+    };
+}
+
+/// Configurable options `apply_socket_opts` sets on accepted sockets, on top
+/// of the fixed ones carried over from C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketOpts {
+    /// None = keepalive off
+    pub keepalive: Option<Keepalive>,
+    /// Disable Nagle's algorithm so small packets go out immediately
+    pub nodelay: bool,
+}
+
+impl SocketOpts {
+    pub const DEFAULT: Self = Self { keepalive: Some(Keepalive::DEFAULT), nodelay: false };
+}
+
+/// Default `SessionManager` cap on concurrent sessions from one client IP.
+pub const DEFAULT_MAX_PER_IP: usize = 8;
+
+/// Open accepted sessions per client IP, for the per-IP cap.
+#[derive(Debug, Default)]
+struct IpConnections {
+    /// Open count by IP (network order, as `client_addr_raw`)
+    open: HashMap<u32, usize>,
+    /// IP of each counted fd, so removal needs only the fd
+    fds: HashMap<i32, u32>,
+}
+
+impl IpConnections {
+    fn add(&mut self, fd: i32, ip: u32) {
+        if self.fds.insert(fd, ip).is_none() {
+            *self.open.entry(ip).or_default() += 1;
+        }
+    }
+
+    fn remove(&mut self, fd: i32) {
+        let Some(ip) = self.fds.remove(&fd) else { return };
+        if let std::collections::hash_map::Entry::Occupied(mut e) = self.open.entry(ip) {
+            *e.get_mut() -= 1;
+            if *e.get() == 0 {
+                e.remove();
+            }
+        }
+    }
+}
 
 /// Error types for session operations
 #[derive(Debug, thiserror::Error)]
@@ -71,8 +310,8 @@ pub enum SessionError {
     #[error("Session not found: fd={0}")]
     SessionNotFound(i32),
 
-    #[error("Maximum sessions exceeded (limit: {MAX_SESSIONS})")]
-    MaxSessionsExceeded,
+    #[error("Maximum sessions exceeded (limit: {0})")]
+    MaxSessionsExceeded(usize),
 
     #[error("File descriptor overflow")]
     FdOverflow,
@@ -81,6 +320,65 @@ pub enum SessionError {
     Io(#[from] std::io::Error),
 }
 
+/// Why a session ended, derived from its final `eof` code.
+///
+/// The numeric values match the eof codes set by the session layer and by C
+/// (`rust_session_set_eof`), are what the `closed` callback receives, and are
+/// what travels on the wire in the map→char 0x3012 disconnect notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DisconnectReason {
+    /// eof code the session layer does not know about
+    Unknown = 0,
+    /// eof=1: server-initiated close (kick, timeout, C set_eof)
+    ServerKick = 1,
+    /// eof=2: socket write or outbound connect failed
+    WriteError = 2,
+    /// eof=3: socket read failed or read buffer overflowed
+    ReadError = 3,
+    /// eof=4: peer closed the connection cleanly
+    PeerClosed = 4,
+    /// eof=5: client fell too far behind; its send queue overflowed
+    SlowConsumer = 5,
+}
+
+impl DisconnectReason {
+    pub const ALL: [DisconnectReason; 6] = [
+        DisconnectReason::Unknown,
+        DisconnectReason::ServerKick,
+        DisconnectReason::WriteError,
+        DisconnectReason::ReadError,
+        DisconnectReason::PeerClosed,
+        DisconnectReason::SlowConsumer,
+    ];
+
+    pub fn from_eof(eof: i32) -> Self {
+        match eof {
+            1 => DisconnectReason::ServerKick,
+            2 => DisconnectReason::WriteError,
+            3 => DisconnectReason::ReadError,
+            4 => DisconnectReason::PeerClosed,
+            5 => DisconnectReason::SlowConsumer,
+            _ => DisconnectReason::Unknown,
+        }
+    }
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::from_eof(v as i32)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DisconnectReason::Unknown => "unknown",
+            DisconnectReason::ServerKick => "server_kick",
+            DisconnectReason::WriteError => "write_error",
+            DisconnectReason::ReadError => "read_error",
+            DisconnectReason::PeerClosed => "peer_closed",
+            DisconnectReason::SlowConsumer => "slow_consumer",
+        }
+    }
+}
+
 /// Callback function pointers for C interop
 #[derive(Clone, Copy, Default)]
 pub struct SessionCallbacks {
@@ -88,10 +386,64 @@ pub struct SessionCallbacks {
     pub accept: Option<unsafe extern "C" fn(i32) -> i32>,
     /// Called when packet data is received and ready to parse
     pub parse: Option<unsafe extern "C" fn(i32) -> i32>,
-    /// Called when session has been idle for too long
+    /// Called once when nothing has been read for the session's idle timeout;
+    /// the session is then closed (eof=1) unless the callback set eof itself
     pub timeout: Option<unsafe extern "C" fn(i32) -> i32>,
     /// Called when session is being shut down
     pub shutdown: Option<unsafe extern "C" fn(i32) -> i32>,
+    /// Called right after `shutdown` with the fd and why the session ended
+    /// (`DisconnectReason as i32`)
+    pub closed: Option<unsafe extern "C" fn(i32, i32)>,
+}
+
+/// Point-in-time copy of a session's state for admin tooling; see
+/// `SessionManager::for_each_session`.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionSnapshot {
+    pub fd: i32,
+    pub addr: Option<SocketAddr>,
+    pub kind: SessionKind,
+    pub eof: i32,
+    /// Received bytes not yet consumed by the parser
+    pub rdata_pending: usize,
+    /// Queued bytes not yet flushed to the socket
+    pub wdata_pending: usize,
+    pub rdata_capacity: usize,
+    pub wdata_capacity: usize,
+    /// Time since the last read or write
+    pub idle: Duration,
+}
+
+impl SessionSnapshot {
+    fn of(s: &Session, now: Instant) -> Self {
+        Self {
+            fd: s.fd,
+            addr: s.client_addr,
+            kind: s.kind,
+            eof: s.eof,
+            rdata_pending: s.rdata_size.saturating_sub(s.rdata_pos),
+            wdata_pending: s.buffered_write_bytes(),
+            rdata_capacity: s.rdata.capacity(),
+            wdata_capacity: s.wdata.capacity(),
+            idle: now.saturating_duration_since(s.last_activity),
+        }
+    }
+}
+
+/// Totals across all sessions for monitoring; see `SessionManager::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    pub sessions: usize,
+    /// Sum of queued, unflushed write bytes
+    pub write_buffered: usize,
+    /// Sum of received, unparsed read bytes
+    pub read_buffered: usize,
+    /// Largest single write backlog (a session's `max_wdata` closes it)
+    pub max_write_buffered: usize,
+    /// Idle time of the longest-idle session
+    pub oldest_idle: Duration,
+    /// Sessions locked mid-I/O and left out of the sums
+    pub busy: usize,
 }
 
 /// Global session manager (thread-safe, sync-accessible from C callbacks)
@@ -106,6 +458,40 @@ pub struct SessionManager {
     pub listeners: StdMutex<HashMap<i32, std::net::TcpListener>>,
     /// Ordered list of listener fds
     pub listen_fds: StdMutex<Vec<i32>>,
+    /// Closed-session counts, indexed by `DisconnectReason as usize`
+    disconnects: [AtomicU64; DisconnectReason::ALL.len()],
+    /// Session cap for `allocate_fd`/`insert_session`
+    max_sessions: AtomicUsize,
+    /// Set while utilization is at or above SESSION_WARN_PERCENT, so the
+    /// warning is logged once per crossing rather than on every accept
+    near_capacity: AtomicBool,
+    /// Initial buffer capacities, indexed by `SessionRole as usize`
+    buffer_sizes: RwLock<[BufferSizes; 2]>,
+    /// Write buffer caps, indexed by `SessionRole as usize`
+    write_caps: RwLock<[usize; 2]>,
+    /// `Session::send_queue_depth` for new client sessions
+    client_send_queue: AtomicUsize,
+    /// Retry schedule for deferred outbound connects
+    reconnect: RwLock<ReconnectPolicy>,
+    /// Compression offered to client sessions; None = disabled
+    client_compression: RwLock<Option<compress::Policy>>,
+    /// Idle limit for accepted sessions; None = never time out
+    idle_timeout: RwLock<Option<Duration>>,
+    /// Per-listener overrides of `idle_timeout`, by listener fd
+    listener_idle: RwLock<HashMap<i32, Option<Duration>>>,
+    /// Packet cipher for sessions accepted on a listener, by listener fd;
+    /// listeners without one leave the static branch to C's `xor_key`
+    listener_cipher: RwLock<HashMap<i32, Arc<dyn Cipher>>>,
+    /// Set by `begin_drain`: new connections are turned away
+    draining: AtomicBool,
+    /// Options applied to accepted sockets
+    socket_opts: RwLock<SocketOpts>,
+    /// Accepted sessions per client IP
+    ip_conns: StdMutex<IpConnections>,
+    /// Cap on `ip_conns` per IP; 0 = unlimited
+    max_per_ip: AtomicUsize,
+    /// IPs (network order) the per-IP cap skips, besides loopback
+    ip_exempt: RwLock<Vec<u32>>,
 }
 
 impl SessionManager {
@@ -116,28 +502,189 @@ impl SessionManager {
             default_callbacks: StdMutex::new(SessionCallbacks::default()),
             listeners: StdMutex::new(HashMap::new()),
             listen_fds: StdMutex::new(Vec::new()),
+            disconnects: Default::default(),
+            max_sessions: AtomicUsize::new(MAX_SESSIONS),
+            near_capacity: AtomicBool::new(false),
+            buffer_sizes: RwLock::new([BufferSizes::CLIENT, BufferSizes::INTERSERVER]),
+            write_caps: RwLock::new([CLIENT_WDATA_CAP, MAX_WDATA_SIZE]),
+            client_send_queue: AtomicUsize::new(0),
+            reconnect: RwLock::new(ReconnectPolicy::DEFAULT),
+            client_compression: RwLock::new(None),
+            idle_timeout: RwLock::new(Some(DEFAULT_IDLE_TIMEOUT)),
+            listener_idle: RwLock::new(HashMap::new()),
+            listener_cipher: RwLock::new(HashMap::new()),
+            draining: AtomicBool::new(false),
+            socket_opts: RwLock::new(SocketOpts::DEFAULT),
+            ip_conns: StdMutex::new(IpConnections::default()),
+            max_per_ip: AtomicUsize::new(DEFAULT_MAX_PER_IP),
+            ip_exempt: RwLock::new(Vec::new()),
         }
     }
 
+    /// Initial buffer capacities for a new session of `role` (sync)
+    pub fn buffer_sizes(&self, role: SessionRole) -> BufferSizes {
+        self.buffer_sizes.read().unwrap()[role as usize]
+    }
+
+    /// Change the initial buffer capacities for `role`; affects new sessions only (sync)
+    pub fn set_buffer_sizes(&self, role: SessionRole, sizes: BufferSizes) {
+        self.buffer_sizes.write().unwrap()[role as usize] = sizes.capped();
+    }
+
+    /// Write buffer cap for a new session of `role` (sync)
+    pub fn write_cap(&self, role: SessionRole) -> usize {
+        self.write_caps.read().unwrap()[role as usize]
+    }
+
+    /// Change the write buffer cap for `role`, clamped to `MAX_WDATA_SIZE`;
+    /// affects new (and retagged) sessions only (sync)
+    pub fn set_write_cap(&self, role: SessionRole, cap: usize) {
+        self.write_caps.write().unwrap()[role as usize] = cap.clamp(1, MAX_WDATA_SIZE);
+    }
+
+    /// Send queue depth for new client sessions; 0 = disabled (sync)
+    pub fn client_send_queue_depth(&self) -> usize {
+        self.client_send_queue.load(Ordering::Relaxed)
+    }
+
+    /// Change the client send queue depth; affects new sessions only (sync)
+    pub fn set_client_send_queue_depth(&self, depth: usize) {
+        self.client_send_queue.store(depth, Ordering::Relaxed);
+    }
+
+    /// Options applied to accepted sockets (sync)
+    pub fn socket_opts(&self) -> SocketOpts {
+        *self.socket_opts.read().unwrap()
+    }
+
+    /// Change the accepted-socket options; affects new connections only (sync)
+    pub fn set_socket_opts(&self, opts: SocketOpts) {
+        *self.socket_opts.write().unwrap() = opts;
+    }
+
+    /// Retry schedule for outbound connects (sync)
+    pub fn reconnect_policy(&self) -> ReconnectPolicy {
+        *self.reconnect.read().unwrap()
+    }
+
+    /// Change the outbound retry schedule; affects connects not yet started (sync)
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        *self.reconnect.write().unwrap() = policy;
+    }
+
+    /// Compression offered to new client sessions (sync)
+    pub fn client_compression(&self) -> Option<compress::Policy> {
+        *self.client_compression.read().unwrap()
+    }
+
+    /// Change the compression offered to client sessions; affects new sessions only (sync)
+    pub fn set_client_compression(&self, policy: Option<compress::Policy>) {
+        *self.client_compression.write().unwrap() = policy;
+    }
+
+    /// Idle limit for sessions accepted on `listen_fd` (sync)
+    pub fn idle_timeout(&self, listen_fd: i32) -> Option<Duration> {
+        match self.listener_idle.read().unwrap().get(&listen_fd) {
+            Some(&t) => t,
+            None => *self.idle_timeout.read().unwrap(),
+        }
+    }
+
+    /// Change the idle limit for listeners without an override; None or zero
+    /// disables it. Affects new sessions only (sync)
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        *self.idle_timeout.write().unwrap() = timeout.filter(|t| !t.is_zero());
+    }
+
+    /// Override the idle limit for sessions accepted on `listen_fd`; None or
+    /// zero disables it there. Affects new sessions only (sync)
+    pub fn set_listener_idle_timeout(&self, listen_fd: i32, timeout: Option<Duration>) {
+        self.listener_idle.write().unwrap().insert(listen_fd, timeout.filter(|t| !t.is_zero()));
+    }
+
+    /// Cipher for sessions accepted on `listen_fd`, if one was set (sync)
+    pub fn listener_cipher(&self, listen_fd: i32) -> Option<Arc<dyn Cipher>> {
+        self.listener_cipher.read().unwrap().get(&listen_fd).cloned()
+    }
+
+    /// Use `cipher` for the static branch of sessions accepted on
+    /// `listen_fd` (-1 = the WebSocket listener). Affects new sessions only (sync)
+    pub fn set_listener_cipher(&self, listen_fd: i32, cipher: Arc<dyn Cipher>) {
+        self.listener_cipher.write().unwrap().insert(listen_fd, cipher);
+    }
+
+    /// Stop admitting connections: from now on `accept_loop` answers each
+    /// new socket with a "server restarting" message and closes it. Existing
+    /// sessions are untouched. Returns false if already draining (sync)
+    pub fn begin_drain(&self) -> bool {
+        !self.draining.swap(true, Ordering::Relaxed)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Open game client sessions (inter-server links excluded). A session
+    /// locked mid-I/O is counted, so a drain never ends early (sync)
+    pub fn active_client_count(&self) -> usize {
+        self.sessions.read().unwrap()
+            .values()
+            .filter(|s| match s.try_lock() {
+                Ok(s) => s.kind.role() == SessionRole::Client && s.eof == 0,
+                Err(_) => true,
+            })
+            .count()
+    }
+
+    /// Current session cap (sync)
+    pub fn max_sessions(&self) -> usize {
+        self.max_sessions.load(Ordering::Relaxed)
+    }
+
+    /// Change the session cap (sync). Lowering it below the live count does
+    /// not drop sessions; new ones are refused until the count falls below.
+    pub fn set_max_sessions(&self, limit: usize) {
+        self.max_sessions.store(limit.max(1), Ordering::Relaxed);
+    }
+
+    /// Live sessions and the cap, as `(current, limit)` (sync)
+    pub fn utilization(&self) -> (usize, usize) {
+        (self.session_count(), self.max_sessions())
+    }
+
     /// Allocate a new file descriptor (sync)
     pub fn allocate_fd(&self) -> Result<i32, SessionError> {
+        let limit = self.max_sessions();
         let fd = self.next_fd.fetch_add(1, Ordering::Relaxed);
-        if fd > MAX_SESSIONS as i32 {
-            return Err(SessionError::MaxSessionsExceeded);
+        if fd < 0 || fd as usize > limit {
+            return Err(SessionError::MaxSessionsExceeded(limit));
         }
         Ok(fd)
     }
 
     /// Insert a session (sync)
     pub fn insert_session(&self, fd: i32, session: Arc<Mutex<Session>>) -> Result<(), SessionError> {
+        let limit = self.max_sessions();
         let mut sessions = self.sessions.write().unwrap();
-        if sessions.len() >= MAX_SESSIONS {
-            return Err(SessionError::MaxSessionsExceeded);
+        if sessions.len() >= limit {
+            return Err(SessionError::MaxSessionsExceeded(limit));
         }
         sessions.insert(fd, session);
+        let count = sessions.len();
+        drop(sessions);
+        self.check_capacity(count, limit);
         Ok(())
     }
 
+    fn check_capacity(&self, count: usize, limit: usize) {
+        let near = count * 100 >= limit * SESSION_WARN_PERCENT;
+        if near && !self.near_capacity.swap(true, Ordering::Relaxed) {
+            tracing::warn!("[session] utilization {}/{} sessions ({}%)", count, limit, count * 100 / limit);
+        } else if !near {
+            self.near_capacity.store(false, Ordering::Relaxed);
+        }
+    }
+
     /// Get a session by fd (sync)
     pub fn get_session(&self, fd: i32) -> Option<Arc<Mutex<Session>>> {
         self.sessions.read().unwrap().get(&fd).cloned()
@@ -146,6 +693,34 @@ impl SessionManager {
     /// Remove a session (sync)
     pub fn remove_session(&self, fd: i32) {
         self.sessions.write().unwrap().remove(&fd);
+        self.ip_conns.lock().unwrap().remove(fd);
+    }
+
+    /// Count `fd` against `ip` (network order) for the per-IP cap until it
+    /// is removed (sync)
+    pub fn track_ip(&self, fd: i32, ip: u32) {
+        self.ip_conns.lock().unwrap().add(fd, ip);
+    }
+
+    /// Open accepted sessions from `ip` (sync)
+    pub fn connections_from(&self, ip: u32) -> usize {
+        self.ip_conns.lock().unwrap().open.get(&ip).copied().unwrap_or(0)
+    }
+
+    /// Whether `ip` already has as many sessions as the per-IP cap allows.
+    /// Loopback and exempt IPs never are (sync)
+    pub fn ip_at_limit(&self, ip: u32) -> bool {
+        let max = self.max_per_ip.load(Ordering::Relaxed);
+        max != 0
+            && !std::net::Ipv4Addr::from(u32::from_be(ip)).is_loopback()
+            && !self.ip_exempt.read().unwrap().contains(&ip)
+            && self.connections_from(ip) >= max
+    }
+
+    /// Change the per-IP cap (0 = unlimited) and the IPs it skips (sync)
+    pub fn set_per_ip_limit(&self, max: usize, exempt: Vec<u32>) {
+        self.max_per_ip.store(max, Ordering::Relaxed);
+        *self.ip_exempt.write().unwrap() = exempt;
     }
 
     /// Get default callbacks (sync)
@@ -158,11 +733,107 @@ impl SessionManager {
         *self.default_callbacks.lock().unwrap() = callbacks;
     }
 
+    /// Shrink the session table if a spike left it mostly empty (sync).
+    /// Returns `(old, new)` capacity when it shrank.
+    pub fn compact(&self) -> Option<(usize, usize)> {
+        crate::network::compact::shrink_sparse(&mut self.sessions.write().unwrap())
+    }
+
     /// Get session count (sync)
     pub fn session_count(&self) -> usize {
         self.sessions.read().unwrap().len()
     }
 
+    /// Live sessions by kind, in `SessionKind::ALL` order (sync). A session
+    /// locked by another thread at that instant is left out.
+    pub fn kind_counts(&self) -> Vec<(SessionKind, usize)> {
+        let mut counts = [0usize; SessionKind::ALL.len()];
+        self.for_each_session(|_, snap| counts[snap.kind as usize] += 1);
+        SessionKind::ALL.iter().map(|&k| (k, counts[k as usize])).collect()
+    }
+
+    /// Buffer and idle totals over every session (sync); see `for_each_session`.
+    pub fn stats(&self) -> SessionStats {
+        let mut st = SessionStats::default();
+        st.busy = self.for_each_session(|_, snap| {
+            st.sessions += 1;
+            st.write_buffered += snap.wdata_pending;
+            st.read_buffered += snap.rdata_pending;
+            st.max_write_buffered = st.max_write_buffered.max(snap.wdata_pending);
+            st.oldest_idle = st.oldest_idle.max(snap.idle);
+        });
+        st.sessions += st.busy;
+        st
+    }
+
+    /// Call `f` with a snapshot of every active session, in fd order (sync).
+    ///
+    /// The session table lock is released before any session is touched, and
+    /// each session is locked only long enough to copy its snapshot, so `f`
+    /// may call back into the manager. Sessions locked elsewhere at that
+    /// instant (mid-I/O) are skipped; returns how many were skipped.
+    pub fn for_each_session(&self, mut f: impl FnMut(i32, &SessionSnapshot)) -> usize {
+        let mut sessions: Vec<(i32, Arc<Mutex<Session>>)> = self.sessions.read().unwrap()
+            .iter()
+            .map(|(&fd, s)| (fd, Arc::clone(s)))
+            .collect();
+        sessions.sort_unstable_by_key(|&(fd, _)| fd);
+        let now = Instant::now();
+        let mut skipped = 0;
+        for (fd, session) in sessions {
+            let snap = match session.try_lock() {
+                Ok(s) => SessionSnapshot::of(&s, now),
+                Err(_) => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            f(fd, &snap);
+        }
+        skipped
+    }
+
+    /// Queue `payload` on each of `fds` and wake their I/O tasks, one notify
+    /// per session (sync). The session `Arc`s are taken under a single read
+    /// lock of the table. Returns the fds that did not get it: gone, closing,
+    /// locked by another task, or with no room left in the write buffer.
+    pub fn broadcast(&self, fds: &[i32], payload: &[u8]) -> Vec<i32> {
+        let targets: Vec<(i32, Option<Arc<Mutex<Session>>>)> = {
+            let sessions = self.sessions.read().unwrap();
+            fds.iter().map(|&fd| (fd, sessions.get(&fd).cloned())).collect()
+        };
+        let mut failed = Vec::new();
+        for (fd, session) in targets {
+            let queued = session.as_ref()
+                .and_then(|s| s.try_lock().ok())
+                .filter(|s| s.eof == 0)
+                .is_some_and(|mut s| s.write_buf(0, payload).and_then(|()| s.commit_write(payload.len())).is_ok());
+            if !queued {
+                failed.push(fd);
+            }
+        }
+        failed
+    }
+
+    /// `broadcast` to every live session (sync).
+    pub fn broadcast_all(&self, payload: &[u8]) -> Vec<i32> {
+        self.broadcast(&self.get_all_fds(), payload)
+    }
+
+    /// Count a closed session under the reason derived from its eof code (sync)
+    pub fn record_disconnect(&self, eof: i32) {
+        let reason = DisconnectReason::from_eof(eof);
+        self.disconnects[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Closed-session counts by reason since startup (sync)
+    pub fn disconnect_counts(&self) -> Vec<(DisconnectReason, u64)> {
+        DisconnectReason::ALL
+            .iter()
+            .map(|&r| (r, self.disconnects[r as usize].load(Ordering::Relaxed)))
+            .collect()
+    }
+
     /// Get snapshot of all active session fds (sync)
     pub fn get_all_fds(&self) -> Vec<i32> {
         self.sessions.read().unwrap().keys().copied().collect()
@@ -217,13 +888,18 @@ fn drain_pending_connections() -> Vec<i32> {
 
 /// Set up a new session from an established TCP connection (sync).
 pub fn setup_connection(
-    stream: TcpStream,
+    stream: SessionStream,
     addr: SocketAddr,
     manager: &SessionManager,
 ) -> Result<i32, SessionError> {
     let fd = manager.allocate_fd()?;
 
-    let mut session = Session::new(fd);
+    let kind = SessionKind::Client;
+    let mut session = Session::with_kind(fd, kind, manager.buffer_sizes(kind.role()));
+    session.set_write_cap(manager.write_cap(kind.role()));
+    if kind.role() == SessionRole::Client {
+        session.send_queue_depth = manager.client_send_queue_depth();
+    }
     session.client_addr = Some(addr);
     session.client_addr_raw = match addr.ip() {
         std::net::IpAddr::V4(ipv4) => u32::from(ipv4).to_be(),
@@ -231,9 +907,13 @@ pub fn setup_connection(
     };
     session.socket = Some(Arc::new(Mutex::new(stream)));
     session.callbacks = manager.get_default_callbacks();
+    session.compression = manager.client_compression().map(|p| Box::new(compress::Compression::new(p)));
 
+    let ip = session.client_addr_raw;
     let session_arc = Arc::new(Mutex::new(session));
     manager.insert_session(fd, session_arc)?;
+    manager.track_ip(fd, ip);
+    crate::metrics::METRICS.accepted();
 
     tracing::info!("[session] New connection: fd={}, addr={}", fd, addr);
     #[cfg(not(test))]
@@ -254,13 +934,66 @@ pub fn init_runtime() -> &'static Runtime {
     })
 }
 
+/// Transport under a session: raw TCP, or (with the `websocket` feature)
+/// WebSocket-framed TCP for browser clients. Both carry the same byte stream.
+pub enum SessionStream {
+    Tcp(TcpStream),
+    #[cfg(feature = "websocket")]
+    Ws(crate::network::websocket::WsStream<TcpStream>),
+}
+
+impl From<TcpStream> for SessionStream {
+    fn from(s: TcpStream) -> Self {
+        Self::Tcp(s)
+    }
+}
+
+impl AsyncRead for SessionStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(feature = "websocket")]
+            Self::Ws(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for SessionStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(feature = "websocket")]
+            Self::Ws(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(feature = "websocket")]
+            Self::Ws(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(feature = "websocket")]
+            Self::Ws(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
 /// Session state for a single client connection
 pub struct Session {
     /// File descriptor (for C compatibility)
     pub fd: i32,
 
-    /// TCP socket (Tokio async)
-    pub socket: Option<Arc<Mutex<TcpStream>>>,
+    /// Socket (Tokio async); TCP or WebSocket-framed TCP
+    pub socket: Option<Arc<Mutex<SessionStream>>>,
+
+    /// What the peer is; see `SessionKind`
+    pub kind: SessionKind,
 
     /// Client address
     pub client_addr: Option<SocketAddr>,
@@ -281,16 +1014,36 @@ pub struct Session {
     /// Write buffer (FIFO)
     pub wdata: Vec<u8>,
     pub wdata_size: usize,
+    /// Most bytes the write buffer may hold; writes past it fail with
+    /// `WriteBufferTooLarge`. See `set_write_cap`.
+    pub max_wdata: usize,
+    /// Encoded bytes the socket would not take yet, oldest first. Only used
+    /// when `send_queue_depth` is non-zero.
+    pub send_queue: VecDeque<Vec<u8>>,
+    /// Most flushes `send_queue` may hold before the session is closed as a
+    /// slow consumer (eof=5); 0 = no queue, flushes wait for the socket
+    pub send_queue_depth: usize,
 
     /// Connection state (0=ok, 1=eof, 2=write error, 3=read error, etc.)
     pub eof: i32,
 
+    /// The client asked to log out (0x0B). Its socket close that follows is
+    /// a deliberate quit, never a drop to hold for reconnect-resume.
+    pub quitting: bool,
+
     /// Packet increment counter
     pub increment: u8,
 
     /// Last activity timestamp
     pub last_activity: Instant,
 
+    /// Close after this long without a read; None = never (outbound links)
+    pub idle_timeout: Option<Duration>,
+
+    /// Cipher for packets outside the EncHash-keyed opcodes, from the
+    /// listener that accepted the session; None = C's `tk_crypt_static`
+    pub cipher: Option<Arc<dyn Cipher>>,
+
     /// Session-specific data (opaque pointer for C)
     ///
     /// This is a raw pointer to C-managed memory. The C code is responsible for:
@@ -319,30 +1072,51 @@ pub struct Session {
     /// The caller is responsible for calling write_notify.notify_one() once
     /// after all writes are complete.
     pub suppress_notify: bool,
+
+    /// Client compression state, when the server offers it (see `network::compress`)
+    pub compression: Option<Box<compress::Compression>>,
 }
 
 impl Session {
     /// Create a new session with the given file descriptor
     pub fn new(fd: i32) -> Self {
+        Self::with_buffers(fd, BufferSizes::CLIENT)
+    }
+
+    /// Create a new client session whose buffers start at `sizes`
+    pub fn with_buffers(fd: i32, sizes: BufferSizes) -> Self {
+        Self::with_kind(fd, SessionKind::Client, sizes)
+    }
+
+    /// Create a new session of `kind` whose buffers start at `sizes`
+    pub fn with_kind(fd: i32, kind: SessionKind, sizes: BufferSizes) -> Self {
         Self {
             fd,
+            kind,
             socket: None,
             client_addr: None,
             client_addr_raw: 0,
             connect_addr: None,
             write_notify: Arc::new(tokio::sync::Notify::new()),
-            rdata: Vec::with_capacity(RFIFO_SIZE),
+            rdata: Vec::with_capacity(sizes.read),
             rdata_pos: 0,
             rdata_size: 0,
-            wdata: Vec::with_capacity(WFIFO_SIZE),
+            wdata: Vec::with_capacity(sizes.write),
             wdata_size: 0,
+            max_wdata: MAX_WDATA_SIZE,
+            send_queue: VecDeque::new(),
+            send_queue_depth: 0,
             eof: 0,
+            quitting: false,
             increment: 0,
             last_activity: Instant::now(),
+            idle_timeout: None,
+            cipher: None,
             session_data: None,
             callbacks: SessionCallbacks::default(),
             shutdown_called: false,
             suppress_notify: false,
+            compression: None,
         }
     }
 
@@ -423,6 +1197,22 @@ impl Session {
         self.rdata_size - self.rdata_pos
     }
 
+    /// Limit the write buffer to `cap` bytes (at most `MAX_WDATA_SIZE`).
+    /// Bytes already queued stay; only later writes are refused.
+    pub fn set_write_cap(&mut self, cap: usize) {
+        self.max_wdata = cap.clamp(1, MAX_WDATA_SIZE);
+    }
+
+    /// Committed bytes not yet flushed to the socket
+    pub fn buffered_write_bytes(&self) -> usize {
+        self.wdata_size + self.send_queue.iter().map(Vec::len).sum::<usize>()
+    }
+
+    /// Time since the last read
+    pub fn idle_duration(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
     /// Write u8 with automatic buffer growth
     pub fn write_u8(&mut self, pos: usize, val: u8) -> Result<(), SessionError> {
         let actual_pos = self
@@ -435,17 +1225,17 @@ impl Session {
             })?;
 
         let end = actual_pos + 1;
-        if end > MAX_WDATA_SIZE {
+        if end > self.max_wdata {
             return Err(SessionError::WriteBufferTooLarge {
                 fd: self.fd,
                 requested_pos: end,
-                max: MAX_WDATA_SIZE,
+                max: self.max_wdata,
             });
         }
 
-        // Auto-grow in 1KB chunks, clamped to MAX_WDATA_SIZE
+        // Auto-grow in 1KB chunks, clamped to max_wdata
         if end > self.wdata.len() {
-            self.wdata.resize(end.saturating_add(1024).min(MAX_WDATA_SIZE), 0);
+            self.wdata.resize(end.saturating_add(1024).min(self.max_wdata), 0);
         }
 
         self.wdata[actual_pos] = val;
@@ -464,16 +1254,16 @@ impl Session {
             })?;
 
         let end = actual_pos + 2;
-        if end > MAX_WDATA_SIZE {
+        if end > self.max_wdata {
             return Err(SessionError::WriteBufferTooLarge {
                 fd: self.fd,
                 requested_pos: end,
-                max: MAX_WDATA_SIZE,
+                max: self.max_wdata,
             });
         }
 
         if end > self.wdata.len() {
-            self.wdata.resize(end.saturating_add(1024).min(MAX_WDATA_SIZE), 0);
+            self.wdata.resize(end.saturating_add(1024).min(self.max_wdata), 0);
         }
 
         let bytes = val.to_le_bytes();
@@ -494,16 +1284,16 @@ impl Session {
             })?;
 
         let end = actual_pos + 4;
-        if end > MAX_WDATA_SIZE {
+        if end > self.max_wdata {
             return Err(SessionError::WriteBufferTooLarge {
                 fd: self.fd,
                 requested_pos: end,
-                max: MAX_WDATA_SIZE,
+                max: self.max_wdata,
             });
         }
 
         if end > self.wdata.len() {
-            self.wdata.resize(end.saturating_add(1024).min(MAX_WDATA_SIZE), 0);
+            self.wdata.resize(end.saturating_add(1024).min(self.max_wdata), 0);
         }
 
         let bytes = val.to_le_bytes();
@@ -512,21 +1302,32 @@ impl Session {
         Ok(())
     }
 
+    /// Committed bytes not yet flushed to the socket, including flushes
+    /// parked in `send_queue`.
+    pub fn write_pressure(&self) -> usize {
+        self.buffered_write_bytes()
+    }
+
+    /// True once `write_pressure()` passes `WRITE_HIGH_WATER`.
+    pub fn is_write_congested(&self) -> bool {
+        self.buffered_write_bytes() > WRITE_HIGH_WATER
+    }
+
     /// Commit write buffer (like WFIFOSET)
     pub fn commit_write(&mut self, len: usize) -> Result<(), SessionError> {
         let new_size = self.wdata_size.checked_add(len).ok_or(
             SessionError::WriteBufferTooLarge {
                 fd: self.fd,
                 requested_pos: usize::MAX,
-                max: MAX_WDATA_SIZE,
+                max: self.max_wdata,
             },
         )?;
 
-        if new_size > MAX_WDATA_SIZE {
+        if new_size > self.max_wdata {
             return Err(SessionError::WriteBufferTooLarge {
                 fd: self.fd,
                 requested_pos: new_size,
-                max: MAX_WDATA_SIZE,
+                max: self.max_wdata,
             });
         }
 
@@ -615,17 +1416,17 @@ impl Session {
             })?;
 
         let end = actual_pos + 1;
-        if end > MAX_WDATA_SIZE {
+        if end > self.max_wdata {
             return Err(SessionError::WriteBufferTooLarge {
                 fd: self.fd,
                 requested_pos: end,
-                max: MAX_WDATA_SIZE,
+                max: self.max_wdata,
             });
         }
 
-        // Ensure buffer is large enough, clamped to MAX_WDATA_SIZE
+        // Ensure buffer is large enough, clamped to max_wdata
         if end > self.wdata.len() {
-            self.wdata.resize(end.saturating_add(1024).min(MAX_WDATA_SIZE), 0);
+            self.wdata.resize(end.saturating_add(1024).min(self.max_wdata), 0);
         }
 
         Ok(self.wdata.as_mut_ptr().wrapping_add(actual_pos))
@@ -642,16 +1443,16 @@ impl Session {
                 pos: size,
             })?;
 
-        if needed > MAX_WDATA_SIZE {
+        if needed > self.max_wdata {
             return Err(SessionError::WriteBufferTooLarge {
                 fd: self.fd,
                 requested_pos: needed,
-                max: MAX_WDATA_SIZE,
+                max: self.max_wdata,
             });
         }
 
         if needed > self.wdata.len() {
-            self.wdata.resize(needed.saturating_add(1024).min(MAX_WDATA_SIZE), 0);
+            self.wdata.resize(needed.saturating_add(1024).min(self.max_wdata), 0);
         }
 
         Ok(())
@@ -659,12 +1460,19 @@ impl Session {
 
     /// Copy data from read buffer into a destination buffer (safe RFIFOP + memcpy)
     pub fn read_buf(&self, pos: usize, dst: &mut [u8]) -> Result<(), SessionError> {
+        dst.copy_from_slice(self.peek_buf(pos, dst.len())?);
+        Ok(())
+    }
+
+    /// Borrow `len` unread bytes at `pos` without copying or consuming them
+    /// (safe RFIFOP); same bounds checks as `read_buf`.
+    pub fn peek_buf(&self, pos: usize, len: usize) -> Result<&[u8], SessionError> {
         let actual_pos = self.rdata_pos.checked_add(pos).ok_or(SessionError::ReadOutOfBounds {
             fd: self.fd,
             pos: usize::MAX,
             size: self.rdata_size,
         })?;
-        let end = actual_pos.checked_add(dst.len()).ok_or(SessionError::ReadOutOfBounds {
+        let end = actual_pos.checked_add(len).ok_or(SessionError::ReadOutOfBounds {
             fd: self.fd,
             pos: actual_pos,
             size: self.rdata_size,
@@ -678,8 +1486,7 @@ impl Session {
             });
         }
 
-        dst.copy_from_slice(&self.rdata[actual_pos..end]);
-        Ok(())
+        Ok(&self.rdata[actual_pos..end])
     }
 
     /// Copy data into the write buffer (safe WFIFOP + memcpy)
@@ -695,22 +1502,63 @@ impl Session {
 
         let end = actual_pos + src.len();
 
-        if end > MAX_WDATA_SIZE {
+        if end > self.max_wdata {
             return Err(SessionError::WriteBufferTooLarge {
                 fd: self.fd,
                 requested_pos: end,
-                max: MAX_WDATA_SIZE,
+                max: self.max_wdata,
             });
         }
 
         if end > self.wdata.len() {
-            self.wdata.resize(end.saturating_add(1024).min(MAX_WDATA_SIZE), 0);
+            self.wdata.resize(end.saturating_add(1024).min(self.max_wdata), 0);
         }
 
         self.wdata[actual_pos..end].copy_from_slice(src);
         Ok(())
     }
 
+    /// Drop the first `sent` committed bytes after they reached the socket.
+    ///
+    /// Unsent committed bytes (and anything committed while the flush was in
+    /// flight) move to the front. Only the vacated tail is zeroed, so stale
+    /// payload cannot leak into a later packet that C only partially
+    /// overwrites. The allocation is kept so raw WFIFOP pointers stay valid.
+    pub fn consume_wdata(&mut self, sent: usize) {
+        let sent = sent.min(self.wdata_size);
+        if sent == 0 {
+            return;
+        }
+        let old_size = self.wdata_size;
+        self.wdata.copy_within(sent..old_size, 0);
+        self.wdata_size = old_size - sent;
+        self.wdata[self.wdata_size..old_size].fill(0);
+        if let Some(c) = self.compression.as_mut() {
+            c.consumed(sent);
+        }
+    }
+
+    /// Run received bytes through client compression, if enabled, queueing
+    /// any negotiation reply. Returns the bytes the parser should see.
+    pub fn decode_inbound<'a>(&mut self, data: &'a [u8]) -> Result<std::borrow::Cow<'a, [u8]>, compress::DecodeError> {
+        let Some(c) = self.compression.as_mut() else {
+            return Ok(std::borrow::Cow::Borrowed(data));
+        };
+        let (mut out, mut reply) = (Vec::new(), Vec::new());
+        c.decode(data, MAX_RDATA_SIZE, &mut out, &mut reply)?;
+        if !reply.is_empty() && self.write_buf(0, &reply).is_ok() {
+            let _ = self.commit_write(reply.len());
+        }
+        Ok(std::borrow::Cow::Owned(out))
+    }
+
+    /// Compress committed packets for a client that negotiated it.
+    pub fn encode_outbound(&mut self) {
+        if let Some(c) = self.compression.as_mut() {
+            self.wdata_size = c.encode(&mut self.wdata, self.wdata_size);
+        }
+    }
+
     /// Compacts the read buffer by moving unread data to the beginning.
     pub fn flush_read_buffer(&mut self) {
         if self.rdata_pos == self.rdata_size {
@@ -733,6 +1581,67 @@ impl Session {
 unsafe impl Send for Session {}
 unsafe impl Sync for Session {}
 
+/// Push the session-layer settings in `c` to `manager`. Runs at startup and
+/// again after each config reload.
+#[cfg(not(test))]
+fn apply_session_config(manager: &SessionManager, c: &crate::config::ServerConfig) {
+    manager.set_max_sessions(c.max_sessions);
+    manager.set_buffer_sizes(SessionRole::Client, BufferSizes {
+        read: c.client_read_buffer, write: c.client_write_buffer,
+    });
+    manager.set_buffer_sizes(SessionRole::InterServer, BufferSizes {
+        read: c.interserver_read_buffer, write: c.interserver_write_buffer,
+    });
+    manager.set_write_cap(SessionRole::Client, c.client_write_cap);
+    manager.set_client_send_queue_depth(c.client_send_queue_depth);
+    let exempt = [&c.login_ip, &c.char_ip, &c.map_ip]
+        .iter()
+        .filter_map(|s| s.parse::<std::net::Ipv4Addr>().ok())
+        .map(|ip| u32::from(ip).to_be())
+        .collect::<Vec<_>>();
+    crate::network::ddos::configure(c.ddos_connections_per_sec, &exempt);
+    manager.set_per_ip_limit(c.max_connections_per_ip, exempt);
+    manager.set_client_compression(c.client_compression.map(|codec| compress::Policy {
+        codec,
+        threshold: c.client_compression_threshold,
+    }));
+    manager.set_reconnect_policy(ReconnectPolicy::from_config(c));
+    manager.set_idle_timeout(Some(Duration::from_secs(c.idle_timeout_secs)));
+    manager.set_socket_opts(SocketOpts {
+        keepalive: (c.tcp_keepalive_idle_secs > 0).then(|| Keepalive {
+            idle: Duration::from_secs(c.tcp_keepalive_idle_secs),
+            interval: Duration::from_secs(c.tcp_keepalive_interval_secs),
+            count: c.tcp_keepalive_probes,
+        }),
+        nodelay: c.nodelay,
+    });
+}
+
+/// Reload the map server's config on every SIGHUP: the FFI copy (and C
+/// globals), `MapState`'s live copy and the session-layer settings.
+#[cfg(not(test))]
+async fn reload_config_on_hangup() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("[rust_server] [config] cannot listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    while hup.recv().await.is_some() {
+        match crate::ffi::config::reload() {
+            Ok((c, ignored)) => {
+                crate::ffi::map_char::set_config(std::sync::Arc::new(c.clone()));
+                apply_session_config(get_session_manager(), c);
+                crate::network::throttle::set_threshold(c.throttle_threshold);
+                crate::servers::log_reload("rust_server", &ignored);
+            }
+            Err(e) => tracing::error!("[rust_server] [config] reload failed, keeping the running config: {:#}", e),
+        }
+    }
+}
+
 /// Run the async game server.
 ///
 /// Replaces the C main loop in core.c:
@@ -758,18 +1667,65 @@ pub async fn run_async_server(_port: u16) -> Result<(), Box<dyn std::error::Erro
         );
     }
 
-    // Register throttle reset timer (10 min interval, matching login_server.c).
+    // Ping the shared DB pool so a database restart heals on its own.
+    #[cfg(not(test))]
+    if let (Some(c), Some(pool)) = (crate::ffi::config::try_config(), crate::database::try_pool()) {
+        if c.db_health_check_secs > 0 {
+            let every = Duration::from_secs(c.db_health_check_secs);
+            tokio::task::spawn_local(crate::database::run_health_checks(pool.clone(), every));
+        }
+    }
+
+    // Give back table memory after connection spikes (every minute).
+    #[cfg(not(test))]
+    unsafe {
+        crate::ffi::timer::timer_insert(
+            60_000,
+            60_000,
+            Some(crate::ffi::session::rust_compact_tables),
+            0,
+            0,
+        );
+    }
+
+    // Register throttle reset timer (default 10 min, matching login_server.c).
     #[cfg(not(test))]
     unsafe {
+        use crate::network::throttle;
+        let (threshold, reset_ms) = crate::ffi::config::try_config()
+            .map(|c| (c.throttle_threshold, c.throttle_reset_secs.saturating_mul(1000)))
+            .unwrap_or((throttle::DEFAULT_THRESHOLD, throttle::DEFAULT_RESET_INTERVAL));
+        throttle::set_threshold(threshold);
         crate::ffi::timer::timer_insert(
-            10 * 60 * 1000,
-            10 * 60 * 1000,
+            reset_ms,
+            reset_ms,
             Some(crate::ffi::session::rust_remove_throttle),
             0,
             0,
         );
     }
 
+    #[cfg(not(test))]
+    if let Some(c) = crate::ffi::config::try_config() {
+        apply_session_config(manager, c);
+    }
+
+    // Re-read the config file on SIGHUP. Runs on this LocalSet, between
+    // timer ticks, so C never sees the globals change mid-callback.
+    #[cfg(not(test))]
+    tokio::task::spawn_local(reload_config_on_hangup());
+
+    tracing::info!("[rust_server] session cap {}", manager.max_sessions());
+
+    #[cfg(not(test))]
+    let tick_budget = crate::ffi::config::try_config()
+        .map_or(Duration::from_millis(10), |c| Duration::from_millis(c.tick_budget_ms));
+    #[cfg(not(test))]
+    let drain_timeout = crate::ffi::config::try_config()
+        .map_or(Duration::ZERO, |c| Duration::from_secs(c.drain_timeout_secs));
+    #[cfg(not(test))]
+    let mut drain_deadline: Option<Instant> = None;
+
     // Take all registered std::net listeners, convert to tokio, spawn accept tasks
     let listen_fds = manager.listen_fds.lock().unwrap().clone();
 
@@ -778,10 +1734,18 @@ pub async fn run_async_server(_port: u16) -> Result<(), Box<dyn std::error::Erro
             std_listener.set_nonblocking(true)?;
             let listener = tokio::net::TcpListener::from_std(std_listener)?;
             tracing::info!("[rust_server] Spawning accept loop for listener fd={}", fd);
-            tokio::task::spawn_local(accept_loop(listener, fd));
+            tokio::task::spawn_local(accept_loop(listener, fd, false));
         }
     }
 
+    #[cfg(all(feature = "websocket", not(test)))]
+    if let Some(config) = crate::ffi::config::try_config().filter(|c| c.map_ws_port != 0) {
+        let addr = format!("{}:{}", config.map_ip, config.map_ws_port);
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        tracing::info!("[rust_server] Spawning WebSocket accept loop on {}", addr);
+        tokio::task::spawn_local(accept_loop(listener, -1, true));
+    }
+
     // Timer tick interval (10ms, matching C's SERVER_TICK_RATE_NS)
     let mut timer_interval = tokio::time::interval(Duration::from_millis(10));
 
@@ -791,8 +1755,14 @@ pub async fn run_async_server(_port: u16) -> Result<(), Box<dyn std::error::Erro
                 // Drive C timer system (synchronous call - no block_on needed)
                 #[cfg(not(test))]
                 unsafe {
+                    let started = Instant::now();
                     let tick = crate::ffi::timer::gettick_nocache();
                     crate::ffi::timer::timer_do(tick);
+                    let elapsed = started.elapsed();
+                    if crate::servers::map::tick_budget::overran(elapsed, tick_budget) {
+                        crate::log_every!(warn, 10, "[rust_server] timer tick took {:?} (budget {:?})",
+                            elapsed, tick_budget);
+                    }
                 }
 
                 // Spawn I/O tasks for connections made during timer callbacks.
@@ -802,11 +1772,27 @@ pub async fn run_async_server(_port: u16) -> Result<(), Box<dyn std::error::Erro
                     tokio::task::spawn_local(session_io_task(fd));
                 }
 
-                // Check shutdown signal
+                // Check shutdown signal. With a drain timeout, stop accepting
+                // and keep ticking until the last client leaves or time runs out.
                 #[cfg(not(test))]
                 if crate::ffi::core::rust_should_shutdown() != 0 {
-                    tracing::info!("[rust_server] Shutdown requested");
-                    break;
+                    let deadline = *drain_deadline.get_or_insert_with(|| {
+                        tracing::info!("[rust_server] Shutdown requested");
+                        if !drain_timeout.is_zero() {
+                            manager.begin_drain();
+                            tracing::info!("[rust_server] Draining: waiting up to {:?} for {} clients",
+                                drain_timeout, manager.active_client_count());
+                        }
+                        Instant::now() + drain_timeout
+                    });
+                    let clients = manager.active_client_count();
+                    if clients == 0 || Instant::now() >= deadline {
+                        if clients > 0 {
+                            tracing::warn!("[rust_server] Drain timed out with {} clients still connected", clients);
+                        }
+                        break;
+                    }
+                    crate::log_every!(info, 10, "[rust_server] Draining: {} clients remaining", clients);
                 }
             }
         }
@@ -820,8 +1806,28 @@ pub async fn run_async_server(_port: u16) -> Result<(), Box<dyn std::error::Erro
     Ok(())
 }
 
-/// Accept loop for a single listener socket
-async fn accept_loop(listener: tokio::net::TcpListener, _listen_fd: i32) {
+/// Await one connect attempt to `addr` for at most `limit`; running out of
+/// time is a `TimedOut` error naming the target and the time spent.
+async fn connect_within<S>(
+    addr: impl std::fmt::Display,
+    limit: Duration,
+    connect: impl std::future::Future<Output = std::io::Result<S>>,
+) -> std::io::Result<S> {
+    let started = Instant::now();
+    match tokio::time::timeout(limit, connect).await {
+        Ok(result) => result,
+        Err(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("connect to {} timed out after {}ms", addr, started.elapsed().as_millis()),
+        )),
+    }
+}
+
+/// Accept loop for a single listener socket. With `websocket`, each
+/// connection must complete a WebSocket upgrade before it becomes a session.
+async fn accept_loop(listener: tokio::net::TcpListener, _listen_fd: i32, websocket: bool) {
+    #[cfg(not(feature = "websocket"))]
+    let _ = websocket;
     let local_addr = listener.local_addr().map(|a| a.to_string()).unwrap_or_else(|_| "unknown".to_string());
     tracing::info!("[accept] Listening on fd={} addr={}", _listen_fd, local_addr);
 
@@ -833,25 +1839,63 @@ async fn accept_loop(listener: tokio::net::TcpListener, _listen_fd: i32) {
                     std::net::IpAddr::V4(ipv4) => u32::from(ipv4).to_be(),
                     _ => 0,
                 };
-                if crate::network::ddos::is_ip_locked(ip_net) {
-                    tracing::warn!("[accept] DDoS-locked IP {}, refusing connection", addr);
+                if ip_net != 0 && crate::network::ddos::record_connection(ip_net) {
+                    crate::log_every!(warn, 5, "[accept] DDoS-locked IP {}, refusing connection", addr);
                     continue;
                 }
                 if crate::network::throttle::is_throttled(ip_net) {
                     tracing::warn!("[accept] Throttled IP {}, refusing connection", addr);
                     continue;
                 }
-                apply_socket_opts(&stream);
+                if get_session_manager().ip_at_limit(ip_net) {
+                    crate::log_every!(warn, 5, "[accept] {} already has {} connections, refusing",
+                        addr, get_session_manager().connections_from(ip_net));
+                    continue;
+                }
+                if get_session_manager().is_draining() {
+                    tracing::info!("[accept] Draining, turning away {}", addr);
+                    tokio::task::spawn_local(refuse_draining(stream, websocket));
+                    continue;
+                }
+                apply_socket_opts(&stream, &get_session_manager().socket_opts());
                 tracing::info!("[accept] New connection from {} on listener fd={}", addr, _listen_fd);
-                tokio::task::spawn_local(session_io_task_from_accept(stream, addr));
+                #[cfg(feature = "websocket")]
+                if websocket {
+                    tokio::task::spawn_local(async move {
+                        match tokio::time::timeout(WS_HANDSHAKE_TIMEOUT, crate::network::websocket::accept(stream)).await {
+                            Ok(Ok(ws)) => session_io_task_from_accept(SessionStream::Ws(ws), addr, _listen_fd).await,
+                            Ok(Err(e)) => tracing::warn!("[accept] websocket handshake from {} failed: {}", addr, e),
+                            Err(_) => tracing::warn!("[accept] websocket handshake from {} timed out", addr),
+                        }
+                    });
+                    continue;
+                }
+                tokio::task::spawn_local(session_io_task_from_accept(stream.into(), addr, _listen_fd));
             }
             Err(e) => {
-                tracing::error!("[accept] fd={} accept error: {}", _listen_fd, e);
+                crate::log_every!(error, 5, "[accept] fd={} accept error: {}", _listen_fd, e);
             }
         }
     }
 }
 
+/// Text sent to connections turned away while draining.
+const DRAIN_NOTICE: &str = "Server restarting. Please reconnect in a moment.";
+
+/// Send the drain notice to a just-accepted socket and close it. WebSocket
+/// peers haven't upgraded yet, so they are just closed.
+async fn refuse_draining(mut stream: TcpStream, websocket: bool) {
+    if !websocket {
+        #[cfg(not(test))]
+        let key = crate::ffi::config::try_config().map(|c| c.xor_key.clone()).unwrap_or_default();
+        #[cfg(test)]
+        let key = String::new();
+        let frame = crate::servers::login::packet::build_message(0x03, DRAIN_NOTICE, &crate::network::crypt::XorCipher::new(key.as_bytes()));
+        let _ = tokio::time::timeout(Duration::from_secs(2), stream.write_all(&frame)).await;
+    }
+    let _ = stream.shutdown().await;
+}
+
 /// Apply the same socket options as the old C `setsocketopts()`.
 ///
 /// - `SO_REUSEADDR` / `SO_REUSEPORT` (unix): allows the port to be reused
@@ -859,7 +1903,14 @@ async fn accept_loop(listener: tokio::net::TcpListener, _listen_fd: i32) {
 /// - `IPPROTO_TCP / 0`: matches what the C code did (TCP_NODELAY was
 ///   intentionally commented out; the `0` call was kept as-is).
 /// - `SO_LINGER` with `l_onoff=0`: graceful close, no hard timeout.
-fn apply_socket_opts(stream: &TcpStream) {
+///
+/// Then the configurable ones from `opts`:
+///
+/// - `SO_KEEPALIVE` plus `TCP_KEEPIDLE`/`TCP_KEEPINTVL`/`TCP_KEEPCNT` (Linux),
+///   so a peer that vanished without a FIN is reset by the kernel.
+/// - `TCP_NODELAY`: movement and combat packets are tiny, and Nagle
+///   coalescing them shows up as stutter.
+fn apply_socket_opts(stream: &TcpStream, opts: &SocketOpts) {
     let fd = stream.as_raw_fd();
     let yes: libc::c_int = 1;
     unsafe {
@@ -902,12 +1953,47 @@ fn apply_socket_opts(stream: &TcpStream) {
             tracing::warn!("[accept] Unable to set SO_LINGER for fd={}", fd);
         }
     }
+
+    if let Some(ka) = opts.keepalive {
+        let secs = |d: Duration| d.as_secs().clamp(1, libc::c_int::MAX as u64) as libc::c_int;
+        let mut set = vec![(libc::SOL_SOCKET, libc::SO_KEEPALIVE, "SO_KEEPALIVE", 1)];
+        #[cfg(target_os = "linux")]
+        set.extend([
+            (libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, "TCP_KEEPIDLE", secs(ka.idle)),
+            (libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, "TCP_KEEPINTVL", secs(ka.interval)),
+            (libc::IPPROTO_TCP, libc::TCP_KEEPCNT, "TCP_KEEPCNT", ka.count.clamp(1, i32::MAX as u32) as libc::c_int),
+        ]);
+        #[cfg(not(target_os = "linux"))]
+        let _ = (ka, secs);
+        for (level, name, label, value) in set {
+            if !setsockopt_int(fd, level, name, value) {
+                tracing::warn!("[accept] Unable to set {} for fd={}: {}", label, fd, std::io::Error::last_os_error());
+            }
+        }
+    }
+
+    if opts.nodelay && !setsockopt_int(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY, 1) {
+        tracing::warn!("[accept] Unable to set TCP_NODELAY for fd={}: {}", fd, std::io::Error::last_os_error());
+    }
+}
+
+/// `setsockopt` with an int value; false on failure.
+fn setsockopt_int(fd: RawFd, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> bool {
+    unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const _ as *const libc::c_void,
+            std::mem::size_of_val(&value) as libc::socklen_t,
+        ) == 0
+    }
 }
 
 /// Set up session from an accepted connection and run its I/O task.
 /// Calls the accept callback (e.g. clif_accept) before entering the I/O loop
 /// so the server can send its initial handshake packet.
-async fn session_io_task_from_accept(stream: TcpStream, addr: SocketAddr) {
+async fn session_io_task_from_accept(stream: SessionStream, addr: SocketAddr, listen_fd: i32) {
     let manager = get_session_manager();
     let fd = match setup_connection(stream, addr, manager) {
         Ok(fd) => fd,
@@ -921,7 +2007,11 @@ async fn session_io_task_from_accept(stream: TcpStream, addr: SocketAddr) {
     // The callback may write to the session's write buffer; we flush it below.
     let accept_cb = {
         match manager.get_session(fd) {
-            Some(arc) => arc.try_lock().ok().and_then(|s| s.callbacks.accept),
+            Some(arc) => arc.try_lock().ok().and_then(|mut s| {
+                s.idle_timeout = manager.idle_timeout(listen_fd);
+                s.cipher = manager.listener_cipher(listen_fd);
+                s.callbacks.accept
+            }),
             None => None,
         }
     };
@@ -936,43 +2026,145 @@ async fn session_io_task_from_accept(stream: TcpStream, addr: SocketAddr) {
 }
 
 /// Flush session write buffer to socket immediately (used after accept callback).
+///
+/// The committed bytes are copied out and written with the session unlocked,
+/// tracking how many actually reached the socket. Only that sent prefix is then
+/// dropped from `wdata`: anything not yet written (partial write, error) stays
+/// queued ahead of whatever C committed in the meantime, so nothing committed
+/// is silently lost. On a hard write error the session is marked eof=2.
 async fn flush_wdata_to_socket(fd: i32, manager: &SessionManager) {
     let session_arc = match manager.get_session(fd) {
         Some(a) => a,
         None => return,
     };
 
-    let (socket_arc, wdata) = {
+    let (socket_arc, pending) = {
         let mut session = session_arc.lock().await;
         let socket_arc = match session.socket.as_ref() {
             Some(s) => s.clone(),
             None => return,
         };
-        let wdata = if session.wdata_size > 0 {
-            let prev_size = session.wdata_size;
-            let data = session.wdata[..prev_size].to_vec();
-            // Zero the flushed region before resetting the logical length.
-            // This prevents stale payload bytes from appearing in the next
-            // packet if C only partially overwrites the committed range.
-            // Don't call wdata.clear() — keep the allocation intact so that
-            // raw pointers returned by WFIFOP (rust_session_wdata_ptr) remain
-            // valid even if a flush races with C code writing to the buffer.
-            session.wdata[..prev_size].fill(0);
-            session.wdata_size = 0;
-            data
-        } else {
+        if session.send_queue_depth > 0 {
+            flush_queued(fd, &mut session, &socket_arc).await;
             return;
-        };
-        (socket_arc, wdata)
+        }
+        if session.wdata_size == 0 {
+            return;
+        }
+        session.encode_outbound();
+        (socket_arc, session.wdata[..session.wdata_size].to_vec())
     };
 
+    let (sent, result) = {
+        let mut socket = socket_arc.lock().await;
+        write_tracked(&mut *socket, &pending).await
+    };
+
+    {
+        let mut session = session_arc.lock().await;
+        session.consume_wdata(sent);
+        if let Err(e) = result {
+            crate::log_every!(
+                error, 5,
+                "[session] fd={} flush write error after {}/{} bytes: {} ({} bytes kept queued)",
+                fd, sent, pending.len(), e, session.wdata_size
+            );
+            session.eof = 2;
+        }
+    }
+}
+
+/// Backpressure flush for sessions with a send queue: move `wdata` onto the
+/// queue, then write as much of the queue as the socket takes without waiting.
+/// Whatever is left stays queued for the next flush; a queue longer than
+/// `send_queue_depth` closes the session as a slow consumer.
+async fn flush_queued(fd: i32, session: &mut Session, socket_arc: &Arc<Mutex<SessionStream>>) {
+    if session.wdata_size > 0 {
+        session.encode_outbound();
+        let size = session.wdata_size;
+        session.send_queue.push_back(session.wdata[..size].to_vec());
+        session.consume_wdata(size);
+    }
+
     let mut socket = socket_arc.lock().await;
-    if let Err(e) = socket.write_all(&wdata).await {
-        tracing::error!("[session] fd={} flush write error: {}", fd, e);
-        if let Some(arc) = manager.get_session(fd) {
-            arc.lock().await.eof = 2;
+    while let Some(chunk) = session.send_queue.front_mut() {
+        match write_now(&mut *socket, chunk).await {
+            Ok(0) => {
+                crate::log_every!(error, 5, "[session] fd={} flush write returned 0", fd);
+                session.eof = 2;
+                return;
+            }
+            Ok(n) if n == chunk.len() => {
+                session.send_queue.pop_front();
+            }
+            Ok(n) => {
+                chunk.drain(..n);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                crate::log_every!(error, 5, "[session] fd={} flush write error: {}", fd, e);
+                session.eof = 2;
+                return;
+            }
         }
     }
+
+    if session.send_queue.len() > session.send_queue_depth && session.eof == 0 {
+        crate::log_every!(
+            warn, 5,
+            "[session] fd={} slow consumer: {} flushes ({} bytes) unsent, closing",
+            fd, session.send_queue.len(), session.buffered_write_bytes()
+        );
+        session.eof = 5;
+    }
+}
+
+/// One write attempt that never waits; `WouldBlock` when the socket is full.
+async fn write_now<W: AsyncWrite + Unpin>(w: &mut W, data: &[u8]) -> std::io::Result<usize> {
+    std::future::poll_fn(|cx| match Pin::new(&mut *w).poll_write(cx, data) {
+        Poll::Pending => Poll::Ready(Err(std::io::ErrorKind::WouldBlock.into())),
+        ready => ready,
+    })
+    .await
+}
+
+/// How often a session with queued sends retries the socket.
+const SEND_QUEUE_RETRY: Duration = Duration::from_millis(10);
+
+/// Resolves after `SEND_QUEUE_RETRY` when sends are queued; never otherwise.
+async fn send_queue_retry(queued: bool) {
+    if queued {
+        tokio::time::sleep(SEND_QUEUE_RETRY).await
+    } else {
+        std::future::pending().await
+    }
+}
+
+/// Write `data` fully, returning how many bytes went out and the first hard
+/// error. `Interrupted` / `WouldBlock` are transient and retried; a zero-length
+/// write is reported as `WriteZero`.
+async fn write_tracked<W: AsyncWriteExt + Unpin>(w: &mut W, data: &[u8]) -> (usize, std::io::Result<()>) {
+    let mut sent = 0;
+    while sent < data.len() {
+        match w.write(&data[sent..]).await {
+            Ok(0) => return (sent, Err(std::io::ErrorKind::WriteZero.into())),
+            Ok(n) => sent += n,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock) => {
+                tokio::task::yield_now().await;
+            }
+            Err(e) => return (sent, Err(e)),
+        }
+    }
+    (sent, Ok(()))
+}
+
+/// Resolves at `deadline`; never when there is none.
+async fn idle_sleep(deadline: Option<Instant>) {
+    match deadline {
+        Some(d) => tokio::time::sleep_until(d.into()).await,
+        None => std::future::pending().await,
+    }
 }
 
 /// Per-session I/O task.
@@ -997,28 +2189,41 @@ async fn session_io_task(fd: i32) {
     };
 
     if let Some(addr) = connect_addr {
-        match TcpStream::connect(addr).await {
-            Ok(stream) => {
-                session_arc.lock().await.socket = Some(Arc::new(Mutex::new(stream)));
+        let policy = manager.reconnect_policy();
+        let mut attempt = 0u32;
+        let connected = loop {
+            attempt += 1;
+            let err = match connect_within(addr, policy.connect_timeout, TcpStream::connect(addr)).await {
+                Ok(stream) => break Some(stream),
+                Err(e) => e,
+            };
+            // Stop retrying if the session was closed while we waited.
+            let closed = session_arc.lock().await.eof != 0;
+            match policy.delay(attempt).filter(|_| !closed) {
+                Some(wait) => {
+                    tracing::warn!(
+                        "[session] fd={} connect to {} failed (attempt {}): {}; retrying in {}ms",
+                        fd, addr, attempt, err, wait.as_millis()
+                    );
+                    tokio::time::sleep(wait).await;
+                }
+                None => {
+                    tracing::error!("[session] fd={} connect to {} failed after {} attempt(s): {}", fd, addr, attempt, err);
+                    break None;
+                }
+            }
+        };
+        match connected {
+            Some(stream) => {
+                session_arc.lock().await.socket = Some(Arc::new(Mutex::new(stream.into())));
                 tracing::info!("[session] fd={} connected to {}", fd, addr);
                 // Flush any write data queued before the connection was established
                 // (e.g. auth packet written by check_connect_login before connect completes)
                 flush_wdata_to_socket(fd, manager).await;
             }
-            Err(e) => {
-                tracing::error!("[session] fd={} connect to {} failed: {}", fd, addr, e);
-                let shutdown_cb = {
-                    let mut session = session_arc.lock().await;
-                    if session.shutdown_called {
-                        None
-                    } else {
-                        session.shutdown_called = true;
-                        session.callbacks.shutdown
-                    }
-                };
-                if let Some(cb) = shutdown_cb {
-                    unsafe { cb(fd); }
-                }
+            None => {
+                session_arc.lock().await.eof = 2;
+                run_close_callbacks(fd, &session_arc).await;
                 manager.remove_session(fd);
                 return;
             }
@@ -1051,11 +2256,17 @@ async fn session_io_task(fd: i32) {
             break;
         }
 
-        // Get socket reference
-        let socket_arc = {
+        // Get socket reference and the idle deadline. The deadline is
+        // recomputed every pass from `last_activity`, which each read moves,
+        // so data arriving just before it re-arms the timer.
+        let (socket_arc, idle_deadline, queued) = {
             let session = session_arc.lock().await;
             match session.socket.as_ref() {
-                Some(s) => s.clone(),
+                Some(s) => (
+                    s.clone(),
+                    session.idle_timeout.map(|t| session.last_activity + t),
+                    !session.send_queue.is_empty(),
+                ),
                 None => break,
             }
         };
@@ -1067,6 +2278,7 @@ async fn session_io_task(fd: i32) {
         enum Event {
             Read(std::io::Result<usize>),
             WriteReady,
+            Idle,
         }
 
         let event = {
@@ -1074,6 +2286,8 @@ async fn session_io_task(fd: i32) {
             tokio::select! {
                 result = socket.read(&mut read_buf) => Event::Read(result),
                 _ = write_notify.notified() => Event::WriteReady,
+                _ = send_queue_retry(queued) => Event::WriteReady,
+                _ = idle_sleep(idle_deadline) => Event::Idle,
             }
         };
 
@@ -1081,6 +2295,23 @@ async fn session_io_task(fd: i32) {
             Event::WriteReady => {
                 flush_wdata_to_socket(fd, manager).await;
             }
+            Event::Idle => {
+                // Fire the timeout callback once, then close; the eof branch
+                // at the top of the loop gives C its cleanup parse call.
+                let timeout_cb = {
+                    let mut session = session_arc.lock().await;
+                    session.idle_timeout = None;
+                    session.callbacks.timeout
+                };
+                tracing::info!("[session] fd={} idle timeout", fd);
+                if let Some(cb) = timeout_cb {
+                    unsafe { cb(fd); }
+                }
+                let mut session = session_arc.lock().await;
+                if session.eof == 0 {
+                    session.eof = 1;
+                }
+            }
             Event::Read(Ok(0)) => {
                 // Peer closed connection — set eof and give C one last parse call
                 {
@@ -1105,16 +2336,26 @@ async fn session_io_task(fd: i32) {
                 // connection rather than corrupt it.
                 let overflow = {
                     let mut session = session_arc.lock().await;
+                    let data = match session.decode_inbound(&read_buf[..n]) {
+                        Ok(d) => d.into_owned(),
+                        Err(e) => {
+                            crate::log_every!(warn, 5, "[session] fd={} bad compressed frame: {}, closing", fd, e);
+                            session.eof = 3;
+                            break;
+                        }
+                    };
+                    let n = data.len();
                     let new_size = session.rdata_size + n;
                     if new_size > MAX_RDATA_SIZE {
-                        tracing::warn!(
+                        crate::log_every!(
+                            warn, 5,
                             "[session] fd={} rdata overflow ({} bytes), closing connection",
                             fd, new_size
                         );
                         session.eof = 3;
                         true
                     } else {
-                        session.rdata.extend_from_slice(&read_buf[..n]);
+                        session.rdata.extend_from_slice(&data);
                         session.rdata_size += n;
                         session.last_activity = Instant::now();
                         false
@@ -1162,7 +2403,7 @@ async fn session_io_task(fd: i32) {
                 }
             }
             Event::Read(Err(e)) => {
-                tracing::error!("[session] fd={} read error: {}", fd, e);
+                crate::log_every!(error, 5, "[session] fd={} read error: {}", fd, e);
                 let mut session = session_arc.lock().await;
                 session.eof = 3;
                 break;
@@ -1170,22 +2411,18 @@ async fn session_io_task(fd: i32) {
         }
     }
 
-    // Invoke C shutdown callback then remove session.
-    // The flag prevents a double-call if shutdown_all_sessions races here.
-    let shutdown_cb = {
-        let mut session = session_arc.lock().await;
-        if session.shutdown_called {
-            None
-        } else {
-            session.shutdown_called = true;
-            session.callbacks.shutdown
-        }
+    // Invoke C shutdown callbacks then remove session.
+    run_close_callbacks(fd, &session_arc).await;
+    let (eof, accepted) = {
+        let session = session_arc.lock().await;
+        (session.eof, session.connect_addr.is_none())
     };
-    if let Some(cb) = shutdown_cb {
-        unsafe { cb(fd); }
+    manager.record_disconnect(eof);
+    if accepted {
+        crate::metrics::METRICS.closed();
     }
     manager.remove_session(fd);
-    tracing::info!("[session] fd={} closed", fd);
+    tracing::info!("[session] fd={} closed reason={}", fd, DisconnectReason::from_eof(eof).name());
 }
 
 /// Shutdown all active sessions (called on server exit)
@@ -1197,24 +2434,40 @@ async fn shutdown_all_sessions() {
 
     for fd in fds {
         if let Some(session_arc) = manager.get_session(fd) {
-            let shutdown_cb = {
+            {
                 let mut session = session_arc.lock().await;
-                if session.shutdown_called {
-                    None
-                } else {
-                    session.shutdown_called = true;
-                    session.callbacks.shutdown
+                if session.eof == 0 {
+                    session.eof = 1;
                 }
-            };
-            if let Some(cb) = shutdown_cb {
-                tracing::debug!("[rust_server] Calling shutdown callback for fd={}", fd);
-                unsafe { cb(fd); }
             }
+            tracing::debug!("[rust_server] Calling shutdown callbacks for fd={}", fd);
+            run_close_callbacks(fd, &session_arc).await;
             manager.remove_session(fd);
         }
     }
 }
 
+/// Call the session's `shutdown` then `closed` callbacks. Runs at most once
+/// per session, so `shutdown_all_sessions` racing the I/O task can't
+/// double-call them.
+async fn run_close_callbacks(fd: i32, session_arc: &Arc<Mutex<Session>>) {
+    let (shutdown_cb, closed_cb) = {
+        let mut session = session_arc.lock().await;
+        if session.shutdown_called {
+            return;
+        }
+        session.shutdown_called = true;
+        (session.callbacks.shutdown, session.callbacks.closed)
+    };
+    if let Some(cb) = shutdown_cb {
+        unsafe { cb(fd); }
+    }
+    if let Some(cb) = closed_cb {
+        let reason = DisconnectReason::from_eof(session_arc.lock().await.eof);
+        unsafe { cb(fd, reason as i32); }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1229,6 +2482,20 @@ mod tests {
         assert_eq!(session.wdata_size, 0);
     }
 
+    #[test]
+    fn test_buffer_sizes_per_role() {
+        let manager = SessionManager::new();
+        assert_eq!(manager.buffer_sizes(SessionRole::Client), BufferSizes::CLIENT);
+        manager.set_buffer_sizes(SessionRole::InterServer, BufferSizes { read: usize::MAX, write: 1 << 20 });
+        let sizes = manager.buffer_sizes(SessionRole::InterServer);
+        assert_eq!(sizes, BufferSizes { read: MAX_RDATA_SIZE, write: 1 << 20 });
+        assert_eq!(manager.buffer_sizes(SessionRole::Client), BufferSizes::CLIENT);
+
+        let session = Session::with_buffers(1, BufferSizes { read: 512, write: 2048 });
+        assert!(session.rdata.capacity() >= 512 && session.rdata.capacity() < RFIFO_SIZE);
+        assert!(session.wdata.capacity() >= 2048);
+    }
+
     #[test]
     fn test_read_u8_bounds_check() {
         let mut session = Session::new(1);
@@ -1270,6 +2537,23 @@ mod tests {
         assert!(session.read_u32(1).is_err());
     }
 
+    #[test]
+    fn test_peek_buf_borrows_without_consuming() {
+        let mut session = Session::new(1);
+        session.rdata = vec![0xAA, 0x00, 0x03, 0x10, 0x20, 0x30];
+        session.rdata_size = 6;
+        session.rdata_pos = 1;
+
+        assert_eq!(session.peek_buf(0, 2).unwrap(), &[0x00, 0x03]);
+        assert_eq!(session.peek_buf(2, 3).unwrap(), &[0x10, 0x20, 0x30]);
+        assert_eq!(session.peek_buf(5, 0).unwrap(), &[] as &[u8]);
+        assert_eq!(session.rdata_pos, 1);
+
+        assert!(session.peek_buf(3, 3).is_err());
+        assert!(session.peek_buf(usize::MAX, 1).is_err());
+        assert!(session.peek_buf(1, usize::MAX).is_err());
+    }
+
     #[test]
     fn test_write_u8_auto_grow() {
         let mut session = Session::new(1);
@@ -1324,6 +2608,34 @@ mod tests {
         assert!(session.commit_write(1024).is_err());
     }
 
+    #[test]
+    fn test_write_pressure_tracks_unflushed_bytes() {
+        let mut session = Session::new(1);
+        assert_eq!(session.write_pressure(), 0);
+        session.ensure_wdata_capacity(WRITE_HIGH_WATER + 1).unwrap();
+        session.commit_write(WRITE_HIGH_WATER).unwrap();
+        assert_eq!(session.write_pressure(), WRITE_HIGH_WATER);
+        assert!(!session.is_write_congested());
+        session.commit_write(1).unwrap();
+        assert!(session.is_write_congested());
+
+        // Below the mark there is room for a worst-case charstatus reservation.
+        assert!(WRITE_HIGH_WATER + 3_170_000 < MAX_WDATA_SIZE);
+        assert!(session.ensure_wdata_capacity(3_170_000).is_ok());
+    }
+
+    #[test]
+    fn test_write_pressure_counts_send_queue() {
+        let mut session = Session::new(1);
+        session.send_queue.push_back(vec![0; WRITE_HIGH_WATER]);
+        assert_eq!(session.write_pressure(), WRITE_HIGH_WATER);
+        assert!(!session.is_write_congested());
+        session.ensure_wdata_capacity(1).unwrap();
+        session.commit_write(1).unwrap();
+        assert_eq!(session.write_pressure(), WRITE_HIGH_WATER + 1);
+        assert!(session.is_write_congested());
+    }
+
     #[test]
     fn test_write_buffer_size_limit() {
         let mut session = Session::new(1);
@@ -1448,6 +2760,55 @@ mod tests {
         assert_eq!(sess.fd, 5);
     }
 
+    #[test]
+    fn test_session_kind_counts_and_roles() {
+        let manager = SessionManager::new();
+        manager.insert_session(1, Arc::new(Mutex::new(Session::new(1)))).unwrap();
+        manager.insert_session(2, Arc::new(Mutex::new(Session::new(2)))).unwrap();
+        let link = Session::with_kind(3, SessionKind::CharLink, BufferSizes::INTERSERVER);
+        manager.insert_session(3, Arc::new(Mutex::new(link))).unwrap();
+
+        let counts: HashMap<_, _> = manager.kind_counts().into_iter().collect();
+        assert_eq!(counts[&SessionKind::Client], 2);
+        assert_eq!(counts[&SessionKind::CharLink], 1);
+        assert_eq!(counts[&SessionKind::Admin], 0);
+
+        assert_eq!(SessionKind::MapLink.role(), SessionRole::InterServer);
+        assert_eq!(SessionKind::Admin.role(), SessionRole::Client);
+        assert_eq!(SessionKind::from_u8(4), Some(SessionKind::Admin));
+        assert_eq!(SessionKind::from_u8(5), None);
+    }
+
+    #[test]
+    fn test_for_each_session_snapshots() {
+        let manager = SessionManager::new();
+        for fd in [9, 4, 6] {
+            let mut s = Session::new(fd);
+            s.client_addr = Some(SocketAddr::from(([127, 0, 0, 1], 2000 + fd as u16)));
+            s.rdata_size = 10;
+            s.rdata_pos = 4;
+            s.wdata_size = fd as usize;
+            manager.insert_session(fd, Arc::new(Mutex::new(s))).unwrap();
+        }
+        let busy = manager.get_session(6).unwrap();
+        let _guard = busy.try_lock().unwrap();
+
+        let mut seen = Vec::new();
+        let skipped = manager.for_each_session(|fd, snap| {
+            // The table lock is not held while the callback runs.
+            assert!(manager.get_session(fd).is_some());
+            seen.push((fd, snap.addr.unwrap().port(), snap.rdata_pending, snap.wdata_pending, snap.eof));
+        });
+        assert_eq!(skipped, 1);
+        assert_eq!(seen, vec![(4, 2004, 6, 4, 0), (9, 2009, 6, 9, 0)]);
+
+        let st = manager.stats();
+        assert_eq!(
+            (st.sessions, st.busy, st.write_buffered, st.read_buffered, st.max_write_buffered),
+            (3, 1, 13, 12, 9)
+        );
+    }
+
     #[test]
     fn test_session_manager_remove() {
         let manager = SessionManager::new();
@@ -1466,8 +2827,10 @@ mod tests {
     fn test_session_manager_max_sessions() {
         let manager = SessionManager::new();
 
+        manager.set_max_sessions(8);
+
         // Fill to limit
-        for i in 0..MAX_SESSIONS {
+        for i in 0..8 {
             let session = Session::new(i as i32);
             manager.insert_session(i as i32, Arc::new(Mutex::new(session)))
                 .unwrap();
@@ -1477,6 +2840,383 @@ mod tests {
         let session = Session::new(9999);
         let result = manager.insert_session(9999, Arc::new(Mutex::new(session)));
         assert!(result.is_err());
-        assert!(matches!(result, Err(SessionError::MaxSessionsExceeded)));
+        assert!(matches!(result, Err(SessionError::MaxSessionsExceeded(8))));
+        assert_eq!(manager.utilization(), (8, 8));
+    }
+
+    #[test]
+    fn test_per_ip_limit_counts_open_sessions() {
+        let manager = SessionManager::new();
+        let ip = |a, b, c, d| u32::from(std::net::Ipv4Addr::new(a, b, c, d)).to_be();
+        let (player, server) = (ip(203, 0, 113, 7), ip(10, 0, 0, 2));
+        manager.set_per_ip_limit(2, vec![server]);
+
+        manager.track_ip(1, player);
+        assert!(!manager.ip_at_limit(player));
+        manager.track_ip(2, player);
+        manager.track_ip(2, player);
+        assert_eq!(manager.connections_from(player), 2);
+        assert!(manager.ip_at_limit(player));
+
+        for fd in 3..6 {
+            manager.track_ip(fd, server);
+            manager.track_ip(fd + 10, ip(127, 0, 0, 1));
+        }
+        assert!(!manager.ip_at_limit(server));
+        assert!(!manager.ip_at_limit(ip(127, 0, 0, 1)));
+
+        // Removal frees a slot; removing an untracked fd is harmless.
+        manager.remove_session(2);
+        manager.remove_session(99);
+        assert_eq!(manager.connections_from(player), 1);
+        assert!(!manager.ip_at_limit(player));
+        manager.remove_session(1);
+        assert_eq!(manager.connections_from(player), 0);
+
+        manager.set_per_ip_limit(0, Vec::new());
+        assert!(!manager.ip_at_limit(server));
+    }
+
+    #[test]
+    fn test_reconnect_policy_doubles_to_cap_then_gives_up() {
+        let ms = Duration::from_millis;
+        let p = ReconnectPolicy { initial: ms(500), max: ms(3000), max_attempts: 5, ..ReconnectPolicy::DEFAULT };
+        let delays: Vec<_> = (1..=5).map(|n| p.delay(n)).collect();
+        assert_eq!(delays, vec![Some(ms(500)), Some(ms(1000)), Some(ms(2000)), Some(ms(3000)), None]);
+        let forever = ReconnectPolicy { max_attempts: 0, ..p };
+        assert_eq!(forever.delay(1000), Some(ms(3000)));
+    }
+
+    #[test]
+    fn test_listener_cipher_is_per_listener() {
+        use crate::network::crypt::{RollingXorCipher, XorCipher};
+        let manager = SessionManager::new();
+        manager.set_listener_cipher(3, Arc::new(RollingXorCipher::new(b"key")));
+        manager.set_listener_cipher(-1, Arc::new(XorCipher::new(b"key")));
+        assert_eq!(manager.listener_cipher(3).map(|c| c.name()), Some("rolling"));
+        assert_eq!(manager.listener_cipher(-1).map(|c| c.name()), Some("xor"));
+        assert!(manager.listener_cipher(4).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connect_within_times_out_stalled_connect() {
+        let addr: SocketAddr = "10.0.0.9:2005".parse().unwrap();
+        let stalled = std::future::pending::<std::io::Result<()>>();
+        let err = connect_within(addr, Duration::from_millis(20), stalled).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(err.to_string().contains("10.0.0.9:2005"), "{err}");
+
+        let refused = async { Err::<(), _>(std::io::Error::from(std::io::ErrorKind::ConnectionRefused)) };
+        let err = connect_within(addr, Duration::from_secs(5), refused).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ConnectionRefused);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_policy_connect_gives_up_after_max_attempts() {
+        let ms = Duration::from_millis;
+        let p = ReconnectPolicy { initial: ms(1), max: ms(1), max_attempts: 3, connect_timeout: ms(500) };
+        let err = p.connect("127.0.0.1:1", "[test]").await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ConnectionRefused);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        assert!(p.connect(&addr, "[test]").await.is_ok());
+    }
+
+    #[test]
+    fn test_allocate_fd_honors_configured_cap() {
+        let manager = SessionManager::new();
+        manager.set_max_sessions(2);
+        assert_eq!(manager.allocate_fd().unwrap(), 1);
+        assert_eq!(manager.allocate_fd().unwrap(), 2);
+        assert!(matches!(manager.allocate_fd(), Err(SessionError::MaxSessionsExceeded(2))));
+    }
+
+    #[test]
+    fn test_disconnect_counts_by_reason() {
+        let manager = SessionManager::new();
+        manager.record_disconnect(3);
+        manager.record_disconnect(3);
+        manager.record_disconnect(4);
+        manager.record_disconnect(99);
+        let counts: HashMap<_, _> = manager.disconnect_counts().into_iter().collect();
+        assert_eq!(counts[&DisconnectReason::ReadError], 2);
+        assert_eq!(counts[&DisconnectReason::PeerClosed], 1);
+        assert_eq!(counts[&DisconnectReason::Unknown], 1);
+        assert_eq!(counts[&DisconnectReason::ServerKick], 0);
+        assert_eq!(DisconnectReason::from_u8(DisconnectReason::WriteError as u8), DisconnectReason::WriteError);
+    }
+
+    #[test]
+    fn test_consume_wdata_keeps_unsent_bytes() {
+        let mut session = Session::new(1);
+        session.write_buf(0, &[1, 2, 3, 4, 5]).unwrap();
+        session.commit_write(5).unwrap();
+
+        session.consume_wdata(2);
+        assert_eq!(session.wdata_size, 3);
+        assert_eq!(&session.wdata[..5], &[3, 4, 5, 0, 0]);
+
+        session.consume_wdata(99);
+        assert_eq!(session.wdata_size, 0);
+        assert!(session.wdata[..5].iter().all(|&b| b == 0));
+    }
+
+    #[tokio::test]
+    async fn test_write_tracked_reports_partial_progress() {
+        struct Flaky { accepted: Vec<u8>, calls: usize }
+        impl tokio::io::AsyncWrite for Flaky {
+            fn poll_write(
+                mut self: std::pin::Pin<&mut Self>,
+                _cx: &mut std::task::Context<'_>,
+                buf: &[u8],
+            ) -> std::task::Poll<std::io::Result<usize>> {
+                self.calls += 1;
+                let r = match self.calls {
+                    1 => Err(std::io::ErrorKind::Interrupted.into()),
+                    2 => { self.accepted.extend_from_slice(&buf[..2]); Ok(2) }
+                    _ => Err(std::io::ErrorKind::BrokenPipe.into()),
+                };
+                std::task::Poll::Ready(r)
+            }
+            fn poll_flush(self: std::pin::Pin<&mut Self>, _: &mut std::task::Context<'_>) -> std::task::Poll<std::io::Result<()>> {
+                std::task::Poll::Ready(Ok(()))
+            }
+            fn poll_shutdown(self: std::pin::Pin<&mut Self>, _: &mut std::task::Context<'_>) -> std::task::Poll<std::io::Result<()>> {
+                std::task::Poll::Ready(Ok(()))
+            }
+        }
+
+        let mut w = Flaky { accepted: Vec::new(), calls: 0 };
+        let (sent, res) = write_tracked(&mut w, &[7, 8, 9, 10]).await;
+        assert_eq!(sent, 2);
+        assert_eq!(w.accepted, [7, 8]);
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::BrokenPipe);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_rearms_on_read_and_fires_once() {
+        static TIMEOUTS: AtomicUsize = AtomicUsize::new(0);
+        unsafe extern "C" fn on_timeout(_fd: i32) -> i32 {
+            TIMEOUTS.fetch_add(1, Ordering::SeqCst);
+            0
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, addr) = listener.accept().await.unwrap();
+        let manager = get_session_manager();
+        let fd = setup_connection(server.into(), addr, manager).unwrap();
+        {
+            let arc = manager.get_session(fd).unwrap();
+            let mut s = arc.lock().await;
+            s.idle_timeout = Some(Duration::from_millis(200));
+            s.callbacks.timeout = Some(on_timeout);
+        }
+
+        let start = Instant::now();
+        let peer = async {
+            // Just inside the deadline: must re-arm, not drop.
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            client.write_all(&[0]).await.unwrap();
+            let mut buf = [0u8; 1];
+            client.read(&mut buf).await
+        };
+        let (_, closed) = tokio::join!(session_io_task(fd), peer);
+
+        assert_eq!(closed.unwrap(), 0);
+        assert!(start.elapsed() >= Duration::from_millis(340), "closed after {:?}", start.elapsed());
+        assert_eq!(TIMEOUTS.load(Ordering::SeqCst), 1);
+        assert!(manager.get_session(fd).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_closed_callback_gets_reason_once() {
+        static CLOSED: StdMutex<Vec<(i32, i32)>> = StdMutex::new(Vec::new());
+        static SHUTDOWNS: AtomicUsize = AtomicUsize::new(0);
+        unsafe extern "C" fn on_shutdown(_fd: i32) -> i32 {
+            SHUTDOWNS.fetch_add(1, Ordering::SeqCst);
+            0
+        }
+        unsafe extern "C" fn on_closed(fd: i32, reason: i32) {
+            CLOSED.lock().unwrap().push((fd, reason));
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, addr) = listener.accept().await.unwrap();
+        let manager = get_session_manager();
+        let fd = setup_connection(server.into(), addr, manager).unwrap();
+        let arc = manager.get_session(fd).unwrap();
+        {
+            let mut s = arc.lock().await;
+            s.callbacks.shutdown = Some(on_shutdown);
+            s.callbacks.closed = Some(on_closed);
+        }
+
+        drop(client);
+        session_io_task(fd).await;
+        run_close_callbacks(fd, &arc).await;
+
+        assert_eq!(SHUTDOWNS.load(Ordering::SeqCst), 1);
+        assert_eq!(*CLOSED.lock().unwrap(), vec![(fd, DisconnectReason::PeerClosed as i32)]);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_queues_once_per_session_and_reports_failures() {
+        let manager = SessionManager::new();
+        for fd in 1..=3 {
+            manager.insert_session(fd, Arc::new(Mutex::new(Session::new(fd)))).unwrap();
+        }
+        manager.get_session(2).unwrap().lock().await.eof = 1;
+        let full = manager.get_session(3).unwrap();
+        full.lock().await.wdata_size = MAX_WDATA_SIZE - 2;
+
+        let failed = manager.broadcast(&[1, 2, 3, 99], b"hello");
+        assert_eq!(failed, vec![2, 3, 99]);
+
+        let one = manager.get_session(1).unwrap();
+        let s = one.lock().await;
+        assert_eq!(&s.wdata[..s.wdata_size], b"hello");
+        drop(s);
+
+        let failed = manager.broadcast_all(b"!");
+        assert_eq!(failed, vec![2]);
+        let s = one.lock().await;
+        assert_eq!(&s.wdata[..s.wdata_size], b"hello!");
+    }
+
+    #[test]
+    fn test_write_cap_follows_session_role() {
+        let manager = SessionManager::new();
+        assert_eq!(manager.write_cap(SessionRole::Client), CLIENT_WDATA_CAP);
+        assert_eq!(manager.write_cap(SessionRole::InterServer), MAX_WDATA_SIZE);
+        manager.set_write_cap(SessionRole::Client, 4096);
+
+        let chunk = vec![0u8; 3000];
+        let mut client = Session::new(1);
+        client.set_write_cap(manager.write_cap(SessionRole::Client));
+        assert!(client.write_buf(0, &chunk).is_ok());
+        assert!(client.commit_write(chunk.len()).is_ok());
+        assert!(matches!(
+            client.write_buf(0, &chunk),
+            Err(SessionError::WriteBufferTooLarge { max: 4096, .. })
+        ));
+
+        let mut link = Session::with_kind(2, SessionKind::CharLink, BufferSizes::INTERSERVER);
+        link.set_write_cap(manager.write_cap(SessionKind::CharLink.role()));
+        for _ in 0..3 {
+            link.write_buf(0, &chunk).unwrap();
+            link.commit_write(chunk.len()).unwrap();
+        }
+        assert_eq!(link.buffered_write_bytes(), 9000);
+    }
+
+    #[tokio::test]
+    async fn test_drain_counts_clients_and_refuses_with_notice() {
+        let manager = SessionManager::new();
+        manager.insert_session(1, Arc::new(Mutex::new(Session::new(1)))).unwrap();
+        manager.insert_session(2, Arc::new(Mutex::new(Session::with_kind(2, SessionKind::CharLink, BufferSizes::INTERSERVER)))).unwrap();
+        manager.insert_session(3, Arc::new(Mutex::new(Session::new(3)))).unwrap();
+        assert_eq!(manager.active_client_count(), 2);
+        manager.get_session(3).unwrap().lock().await.eof = 1;
+        assert_eq!(manager.active_client_count(), 1);
+
+        assert!(!manager.is_draining());
+        assert!(manager.begin_drain());
+        assert!(!manager.begin_drain());
+        assert!(manager.is_draining());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        refuse_draining(accepted, false).await;
+        let mut got = Vec::new();
+        client.read_to_end(&mut got).await.unwrap();
+        assert_eq!(got[0], 0xAA);
+        assert_eq!(got[3], 0x02);
+        assert_eq!(&got[7..7 + DRAIN_NOTICE.len()], DRAIN_NOTICE.as_bytes());
+    }
+
+    fn getsockopt_int(stream: &TcpStream, level: libc::c_int, name: libc::c_int) -> libc::c_int {
+        let mut value: libc::c_int = 0;
+        let mut len = std::mem::size_of_val(&value) as libc::socklen_t;
+        let rc = unsafe {
+            libc::getsockopt(stream.as_raw_fd(), level, name, &mut value as *mut _ as *mut libc::c_void, &mut len)
+        };
+        assert_eq!(rc, 0);
+        value
+    }
+
+    #[tokio::test]
+    async fn test_socket_opts_keepalive() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        apply_socket_opts(&stream, &SocketOpts { keepalive: None, nodelay: false });
+        assert_eq!(getsockopt_int(&stream, libc::SOL_SOCKET, libc::SO_KEEPALIVE), 0);
+
+        let ka = Keepalive { idle: Duration::from_secs(45), interval: Duration::from_secs(5), count: 4 };
+        apply_socket_opts(&stream, &SocketOpts { keepalive: Some(ka), nodelay: false });
+        assert_ne!(getsockopt_int(&stream, libc::SOL_SOCKET, libc::SO_KEEPALIVE), 0);
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(getsockopt_int(&stream, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE), 45);
+            assert_eq!(getsockopt_int(&stream, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL), 5);
+            assert_eq!(getsockopt_int(&stream, libc::IPPROTO_TCP, libc::TCP_KEEPCNT), 4);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socket_opts_nodelay() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        apply_socket_opts(&stream, &SocketOpts::DEFAULT);
+        assert_eq!(getsockopt_int(&stream, libc::IPPROTO_TCP, libc::TCP_NODELAY), 0);
+        apply_socket_opts(&stream, &SocketOpts { nodelay: true, ..SocketOpts::DEFAULT });
+        assert_ne!(getsockopt_int(&stream, libc::IPPROTO_TCP, libc::TCP_NODELAY), 0);
+        assert!(stream.nodelay().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_send_queue_backpressure_closes_slow_consumer() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let socket: Arc<Mutex<SessionStream>> = Arc::new(Mutex::new(stream.into()));
+
+        let mut s = Session::new(1);
+        s.send_queue_depth = 2;
+        s.write_buf(0, b"hi").unwrap();
+        s.commit_write(2).unwrap();
+        // A fresh socket may not report writable yet; retry like the I/O task.
+        for _ in 0..50 {
+            flush_queued(1, &mut s, &socket).await;
+            if s.send_queue.is_empty() {
+                break;
+            }
+            tokio::time::sleep(SEND_QUEUE_RETRY).await;
+        }
+        assert!(s.send_queue.is_empty() && s.wdata_size == 0);
+        let mut got = [0u8; 2];
+        client.read_exact(&mut got).await.unwrap();
+        assert_eq!(&got, b"hi");
+
+        // The client stops reading: flushes pile up until the queue overflows.
+        let chunk = vec![7u8; 1024 * 1024];
+        let mut flushes = 0;
+        while s.eof == 0 && flushes < 64 {
+            s.write_buf(0, &chunk).unwrap();
+            s.commit_write(chunk.len()).unwrap();
+            flush_queued(1, &mut s, &socket).await;
+            flushes += 1;
+        }
+        assert_eq!(s.eof, 5);
+        assert_eq!(s.send_queue.len(), 3);
+        assert!(s.buffered_write_bytes() > 2 * chunk.len());
+        assert_eq!(DisconnectReason::from_eof(s.eof), DisconnectReason::SlowConsumer);
     }
 }
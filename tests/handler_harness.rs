@@ -0,0 +1,181 @@
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use yuri::servers::testing::{read_frame, CharHarness, LoginHarness};
+
+/// 0x3000 map-server auth: char_id(32) + char_pw(32) + ip(4) + port(2).
+fn map_auth(id: &str, pw: &str) -> Vec<u8> {
+    let mut pkt = vec![0u8; 72];
+    pkt[0..2].copy_from_slice(&0x3000u16.to_le_bytes());
+    pkt[2..2 + id.len()].copy_from_slice(id.as_bytes());
+    pkt[34..34 + pw.len()].copy_from_slice(pw.as_bytes());
+    pkt[70..72].copy_from_slice(&2001u16.to_le_bytes());
+    pkt
+}
+
+/// 0x03 client login: length-prefixed name and password, encrypted.
+fn login_request(h: &LoginHarness, name: &str, pass: &str) -> Vec<u8> {
+    let mut pkt = vec![0xAA, 0x00, 0x00, 0x03, 0x01, name.len() as u8];
+    pkt.extend_from_slice(name.as_bytes());
+    pkt.push(pass.len() as u8);
+    pkt.extend_from_slice(pass.as_bytes());
+    let len = (pkt.len() - 3) as u16;
+    pkt[1..3].copy_from_slice(&len.to_be_bytes());
+    h.state.cipher.encrypt_packet(&mut pkt);
+    pkt
+}
+
+#[tokio::test]
+async fn test_login_version_check_over_duplex() {
+    let h = LoginHarness::new();
+    let mut client = h.connect();
+
+    let banner = read_frame(&mut client).await.unwrap();
+    assert_eq!(banner.len(), 22);
+
+    // Version 0 against the configured 750: a patch notice naming 750.
+    client.write_all(&[0xAA, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]).await.unwrap();
+    let resp = read_frame(&mut client).await.unwrap();
+    assert_eq!((resp[3], resp[4]), (0x00, 0x02), "version patch response");
+    assert_eq!(u16::from_be_bytes([resp[5], resp[6]]), 750);
+
+    // The right version is accepted and handed the xor key.
+    let mut client = h.connect();
+    read_frame(&mut client).await.unwrap();
+    let [hi, lo] = 750u16.to_be_bytes();
+    client.write_all(&[0xAA, 0x00, 0x06, 0x00, hi, lo, 0x00, 0x00, 0x00]).await.unwrap();
+    let resp = read_frame(&mut client).await.unwrap();
+    assert_eq!((resp[3], resp[4], resp[5]), (0x00, 0x00, 0x27), "version ok response");
+    assert_eq!(&resp[11..15], b"test");
+}
+
+#[tokio::test]
+async fn test_login_closes_a_stalled_client() {
+    let h = LoginHarness::new();
+    let config = h.state.config.get();
+    h.state.config.set(std::sync::Arc::new(yuri::config::ServerConfig { idle_timeout_secs: 1, ..(*config).clone() }));
+
+    // Silent after the banner: dropped before the first packet.
+    let mut client = h.connect();
+    read_frame(&mut client).await.unwrap();
+    let mut buf = [0u8; 1];
+    let n = tokio::time::timeout(Duration::from_secs(3), client.read(&mut buf)).await.unwrap().unwrap();
+    assert_eq!(n, 0, "stalled connection should be closed");
+
+    // Traffic inside the window rearms it; silence after it still closes.
+    let mut client = h.connect();
+    read_frame(&mut client).await.unwrap();
+    let [hi, lo] = 750u16.to_be_bytes();
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        client.write_all(&[0xAA, 0x00, 0x06, 0x00, hi, lo, 0x00, 0x00, 0x00]).await.unwrap();
+        read_frame(&mut client).await.unwrap();
+    }
+    let n = tokio::time::timeout(Duration::from_secs(3), client.read(&mut buf)).await.unwrap().unwrap();
+    assert_eq!(n, 0, "connection should be closed once silent");
+}
+
+#[tokio::test]
+async fn test_login_lockout_closes_an_open_connection() {
+    let h = LoginHarness::new();
+    let mut client = h.connect();
+    read_frame(&mut client).await.unwrap();
+
+    // Not locked yet: with no char server the login is answered, not dropped.
+    client.write_all(&login_request(&h, "Yuria", "secret")).await.unwrap();
+    read_frame(&mut client).await.unwrap();
+
+    // The IP reaches the threshold while this connection is still open.
+    let threshold = h.state.config.get().login_lockout_threshold;
+    h.state.lockout.lock().await.insert(0x7F00_0001, (threshold, std::time::Instant::now()));
+    client.write_all(&login_request(&h, "Yuria", "secret")).await.unwrap();
+    let mut buf = [0u8; 1];
+    let n = tokio::time::timeout(Duration::from_secs(2), client.read(&mut buf)).await.unwrap().unwrap();
+    assert_eq!(n, 0, "connection should be closed without a reply");
+}
+
+#[tokio::test]
+async fn test_char_map_server_auth_and_mapset() {
+    let h = CharHarness::new();
+
+    let mut bad = h.connect();
+    bad.write_all(&map_auth("charid", "nope")).await.unwrap();
+    let mut resp = [0u8; 4];
+    bad.read_exact(&mut resp).await.unwrap();
+    assert_eq!(resp, [0x00, 0x38, 0x01, 0x00]);
+
+    let mut map = h.connect();
+    map.write_all(&map_auth("charid", "charpw")).await.unwrap();
+    map.read_exact(&mut resp).await.unwrap();
+    assert_eq!(resp, [0x00, 0x38, 0x00, 0x00]);
+
+    // 0x3001 mapset: cmd(2) + total_len(4) + count(2) + ids
+    let mut mapset = vec![0x01, 0x30];
+    mapset.extend_from_slice(&12u32.to_le_bytes());
+    mapset.extend_from_slice(&2u16.to_le_bytes());
+    mapset.extend_from_slice(&7u16.to_le_bytes());
+    mapset.extend_from_slice(&9u16.to_le_bytes());
+    map.write_all(&mapset).await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(Some(s)) = h.state.map_servers.lock().await.first() {
+                if s.maps == [7, 9] {
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("mapset never registered");
+}
+
+#[tokio::test]
+async fn test_char_login_link_handshake() {
+    let login = LoginHarness::new();
+    let chr = CharHarness::new();
+
+    let mut to_login = login.connect();
+    let mut to_char = chr.connect_login();
+    tokio::spawn(async move {
+        let _ = tokio::io::copy_bidirectional(&mut to_login, &mut to_char).await;
+    });
+
+    tokio::time::timeout(Duration::from_secs(2), async {
+        while login.state.char_tx.lock().await.is_none() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("char server never authenticated with login");
+}
+
+#[tokio::test]
+async fn test_char_map_shutdown_notice_acks_and_drains() {
+    let h = CharHarness::new();
+    let mut map = h.connect();
+    map.write_all(&map_auth("charid", "charpw")).await.unwrap();
+    let mut resp = [0u8; 4];
+    map.read_exact(&mut resp).await.unwrap();
+    assert_eq!(resp, [0x00, 0x38, 0x00, 0x00]);
+
+    h.state.online.lock().await.insert(42, yuri::servers::char::LoginEntry {
+        map_server_idx: 0,
+        char_name: "Yuria".into(),
+        resume: None,
+        map: None,
+        map_seq: 0,
+        reclaimed_on: None,
+    });
+    assert_eq!(h.state.drain_map_links(Duration::from_millis(50)).await, 1);
+
+    // 0x3016 → 0x3813 with the number of characters logged out.
+    map.write_all(&0x3016u16.to_le_bytes()).await.unwrap();
+    let mut ack = [0u8; 6];
+    tokio::time::timeout(Duration::from_secs(5), map.read_exact(&mut ack)).await.unwrap().unwrap();
+    assert_eq!(ack, [0x13, 0x38, 1, 0, 0, 0]);
+    assert!(h.state.online.lock().await.is_empty());
+
+    drop(map);
+    assert_eq!(h.state.drain_map_links(Duration::from_secs(2)).await, 0);
+}
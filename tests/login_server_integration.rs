@@ -73,4 +73,13 @@ async fn test_char_server_bad_auth_rejected() {
     let mut resp = vec![0u8; 3];
     char_client.read_exact(&mut resp).await.unwrap();
     assert_eq!(resp, build_intif_auth_response(false));
+
+    // Followed by the 0x1005 reason text, then close.
+    let mut hdr = [0u8; 4];
+    char_client.read_exact(&mut hdr).await.unwrap();
+    assert_eq!(&hdr[..2], &[0x05, 0x10]);
+    let mut text = vec![0u8; u16::from_le_bytes([hdr[2], hdr[3]]) as usize];
+    char_client.read_exact(&mut text).await.unwrap();
+    assert!(String::from_utf8(text).unwrap().contains("auth failed"));
+    assert_eq!(char_client.read(&mut [0u8; 1]).await.unwrap(), 0);
 }